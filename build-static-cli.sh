@@ -1,3 +1,7 @@
 docker build . --tag ppcli
 docker run -v ${HOME}/ppcli_static:/host ppcli:latest
-echo "A ppcli static binary was generated in ${HOME}/ppcli_static"
+VERSION=$(grep '^version' ppcli/Cargo.toml | head -n 1 | cut -d '"' -f 2)
+TARBALL=ppcli-${VERSION}-linux_x86_64.tgz
+# the auto-upgrade refuses release tarballs without a matching .sha256
+(cd ${HOME}/ppcli_static && tar czf ${TARBALL} ppcli && sha256sum ${TARBALL} > ${TARBALL}.sha256)
+echo "A ppcli static binary was generated in ${HOME}/ppcli_static, along with the ${TARBALL} release tarball and its checksum"
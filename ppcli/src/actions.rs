@@ -1,44 +1,56 @@
 use crate::database::ActionType;
 use crate::database::{ItemOfInterest, ItemType, LinkedItem};
+use crate::edit_remote::scp_location;
 use projectpadsql::models::{InterestType, RunOn, ServerAccessType};
 use std::borrow::Cow;
 
-enum SshCommandType {
+enum SshCommandType<'a> {
     Ssh,
-    Scp,
+    /// copy the file at that path on the server
+    Scp(&'a str),
+}
+
+const DEFAULT_SSH_PORT: i32 = 22;
+
+fn ssh_addr_port(item: &ItemOfInterest) -> Option<(&str, i32)> {
+    let srv = item.server_info.as_ref().unwrap();
+    if srv.server_host.is_empty() {
+        None
+    } else {
+        Some((
+            &srv.server_host,
+            srv.server_port.unwrap_or(DEFAULT_SSH_PORT),
+        ))
+    }
+}
+
+fn ssh_target(item: &ItemOfInterest, addr: &str) -> String {
+    let username = &item.server_info.as_ref().unwrap().server_username;
+    if username.is_empty() {
+        addr.to_string()
+    } else {
+        format!("{}@{}", username, addr)
+    }
 }
 
 fn try_prepare_ssh_command(
     item: &ItemOfInterest,
     ssh_command_type: SshCommandType,
 ) -> Option<String> {
-    // TODO must be a cleaner way to express this...
-    if let Some([addr, port]) = match item
-        .server_info
-        .as_ref()
-        .unwrap()
-        .server_ip
-        .split(':')
-        .collect::<Vec<&str>>()[..]
-    {
-        [addr, port] => Some([addr, port]),
-        [addr] => Some([addr, "22"]),
-        _ => None,
-    } {
-        let username = &item.server_info.as_ref().unwrap().server_username;
-        let user_param = if username.is_empty() {
-            Cow::Borrowed("")
-        } else {
-            Cow::Owned(format!("{}@", username))
-        };
+    if let Some((addr, port)) = ssh_addr_port(item) {
+        let target = ssh_target(item, addr);
         Some(match (ssh_command_type, port) {
             // don't pass in the -p/-P parameter if we're using the default port
             // I sometimes use alt-enter to edit a ssh command into a scp command
             // and the -p/-P difference gets in the way...
-            (SshCommandType::Ssh, "22") => format!("ssh {}{}", user_param, addr),
-            (SshCommandType::Scp, "22") => format!("scp {}{}", user_param, addr),
-            (SshCommandType::Ssh, _) => format!("ssh -p {} {}{}", port, user_param, addr),
-            (SshCommandType::Scp, _) => format!("scp -P {} {}{}", port, user_param, addr),
+            (SshCommandType::Ssh, DEFAULT_SSH_PORT) => format!("ssh {}", target),
+            (SshCommandType::Scp(path), DEFAULT_SSH_PORT) => {
+                format!("scp {}", scp_location(&target, path))
+            }
+            (SshCommandType::Ssh, _) => format!("ssh -p {} {}", port, target),
+            (SshCommandType::Scp(path), _) => {
+                format!("scp -P {} {}", port, scp_location(&target, path))
+            }
         })
     } else {
         None
@@ -102,6 +114,46 @@ fn get_value_edit_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     )
 }
 
+// actions implemented by ppcli itself, see edit_remote.rs and grep_log.rs
+fn get_value_ppcli_remote<'a>(
+    item: &'a ItemOfInterest,
+    subcommand: &str,
+) -> std::borrow::Cow<'a, str> {
+    if let Some((addr, port)) = ssh_addr_port(item) {
+        Cow::Owned(format!(
+            "ppcli {} {}{} {}",
+            subcommand,
+            if port == DEFAULT_SSH_PORT {
+                Cow::Borrowed("")
+            } else {
+                Cow::Owned(format!("-p {} ", port))
+            },
+            ssh_target(item, addr),
+            shell_words::quote(item.poi_info.as_ref().unwrap().path.to_str().unwrap())
+        ))
+    } else {
+        Cow::Borrowed(&item.item_text)
+    }
+}
+
+// download the file, edit it with the local editor, upload it back
+fn get_value_edit_file_locally(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    get_value_ppcli_remote(item, "edit-remote")
+}
+
+// same, overwriting the file with sudo and keeping a .bak copy of the original
+fn get_value_edit_file_locally_sudo(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    get_value_ppcli_remote(item, "edit-remote --sudo --backup")
+}
+
+fn is_root_login(item: &ItemOfInterest) -> bool {
+    item.server_info.as_ref().unwrap().server_username == "root"
+}
+
+fn get_value_grep_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    get_value_ppcli_remote(item, "grep-log")
+}
+
 fn get_value_tail_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     get_value_action_file(item, ForcePseudoTTY::No, Cow::Borrowed("tail -f"))
 }
@@ -111,12 +163,11 @@ fn get_value_less_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
 }
 
 fn get_value_fetch_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
-    if let Some(scp_command) = try_prepare_ssh_command(item, SshCommandType::Scp) {
-        let filename = item.poi_info.as_ref().unwrap().path.to_str().unwrap();
+    let filename = item.poi_info.as_ref().unwrap().path.to_str().unwrap();
+    if let Some(scp_command) = try_prepare_ssh_command(item, SshCommandType::Scp(filename)) {
         let base_command = format!(
-            "{}:{} {}",
+            "{} {}",
             scp_command,
-            filename,
             dirs::download_dir().unwrap().to_str().unwrap()
         );
         Cow::Owned(if filename.contains('`') {
@@ -208,6 +259,7 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
             vec![
                 Action::new(ActionType::TailLog, get_value_tail_file, item.clone()),
                 Action::new(ActionType::LessLog, get_value_less_file, item.clone()),
+                Action::new(ActionType::GrepLog, get_value_grep_file, item.clone()),
                 Action::new(ActionType::FetchLog, get_value_fetch_file, item),
             ]
         }
@@ -232,11 +284,37 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
             }]
         }
         i if matches!(i.linked_item, LinkedItem::ServerId(_)) && is_ssh_access(i) => {
-            vec![Action::new(
+            let mut actions = vec![Action::new(
                 ActionType::SshShell,
                 get_value_server_ssh,
-                item,
-            )]
+                item.clone(),
+            )];
+            if i.server_info
+                .as_ref()
+                .map_or(false, |s| s.server_has_otp_secret)
+            {
+                actions.push(Action {
+                    desc: ActionType::CopyOtp,
+                    // the code is computed only once the user picked the action,
+                    // see database::get_server_otp_code
+                    get_string: |_| Cow::Borrowed(""),
+                    allowed_actions: vec![AllowedAction::CopyToClipboard],
+                    item: item.clone(),
+                });
+            }
+            if i.server_info
+                .as_ref()
+                .map_or(false, |s| s.server_has_attachments)
+            {
+                actions.push(Action {
+                    desc: ActionType::SaveAttachments,
+                    // see database::save_server_attachments
+                    get_string: |_| Cow::Borrowed(""),
+                    allowed_actions: vec![AllowedAction::Run],
+                    item,
+                });
+            }
+            actions
         }
         i if [
             ItemType::InterestItemType(InterestType::PoiCommandToRun),
@@ -263,11 +341,33 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
         i if i.item_type == ItemType::InterestItemType(InterestType::PoiConfigFile)
             && is_ssh_access(i) =>
         {
-            vec![
+            let mut actions = vec![
                 Action::new(ActionType::EditCfg, get_value_edit_file, item.clone()),
-                Action::new(ActionType::LessCfg, get_value_less_file, item.clone()),
-                Action::new(ActionType::FetchCfg, get_value_fetch_file, item),
-            ]
+                Action::new(
+                    ActionType::EditCfgLocally,
+                    get_value_edit_file_locally,
+                    item.clone(),
+                ),
+            ];
+            // no need for sudo if we log in as root
+            if !is_root_login(i) {
+                actions.push(Action::new(
+                    ActionType::EditCfgLocallySudo,
+                    get_value_edit_file_locally_sudo,
+                    item.clone(),
+                ));
+            }
+            actions.push(Action::new(
+                ActionType::LessCfg,
+                get_value_less_file,
+                item.clone(),
+            ));
+            actions.push(Action::new(
+                ActionType::FetchCfg,
+                get_value_fetch_file,
+                item,
+            ));
+            actions
         }
         _ => Vec::new(),
     }
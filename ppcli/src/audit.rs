@@ -0,0 +1,60 @@
+// `ppcli audit ...`: report what doesn't follow the policies of the projects,
+// with a non-zero exit code, so that it can run from cron or CI.
+use chrono::prelude::*;
+use diesel::prelude::*;
+use projectpadsql::models::EntityType;
+use projectpadsql::password_audit::{self, OverduePassword};
+
+type SResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+fn entity_type_desc(entity_type: EntityType) -> &'static str {
+    match entity_type {
+        EntityType::Server => "server",
+        EntityType::ServerDatabase => "database",
+        EntityType::ServerWebsite => "website",
+        EntityType::ServerExtraUserAccount => "extra user",
+        _ => "item",
+    }
+}
+
+fn describe(now: NaiveDateTime, overdue: &OverduePassword) -> String {
+    let age = match overdue.password_changed_at {
+        Some(changed_at) => format!(
+            "changed on {}, {} days ago",
+            changed_at.format("%Y-%m-%d"),
+            (now - changed_at).num_days()
+        ),
+        None => "never recorded as changed".to_string(),
+    };
+    let item = if overdue.entity_type == EntityType::Server {
+        "".to_string()
+    } else {
+        format!(" > {}", overdue.title)
+    };
+    format!(
+        "{} > {} [{}]{} ({}): {} (max {} days)",
+        overdue.project_name,
+        overdue.server_desc,
+        overdue.environment,
+        item,
+        entity_type_desc(overdue.entity_type),
+        age,
+        overdue.max_age_days
+    )
+}
+
+/// print the passwords which must be changed as per the rotation
+/// policies of their projects. Returns their count.
+pub fn audit_passwords(conn: &SqliteConnection) -> SResult<usize> {
+    let now = Utc::now().naive_utc();
+    let overdue = password_audit::overdue_passwords(conn, now)?;
+    for o in &overdue {
+        println!("{}", describe(now, o));
+    }
+    if overdue.is_empty() {
+        println!("No password is overdue for rotation.");
+    } else {
+        println!("{} password(s) overdue for rotation.", overdue.len());
+    }
+    Ok(overdue.len())
+}
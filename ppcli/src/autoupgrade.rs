@@ -1,82 +1,297 @@
+// the upgrade is done in-process: we fetch the release tarball from a
+// `ReleaseSource` (the github releases, or a local file for air-gapped
+// machines), verify its sha256 checksum, extract the ppcli binary next to
+// the current one, and swap it in with a rename, which is atomic.
+// The binary we replaced is kept as `ppcli.previous`, for `--rollback`.
+use sha2::{Digest, Sha256};
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 
 type UResult<T> = Result<T, Box<dyn std::error::Error>>;
 
-fn has_requirements() -> bool {
-    is_in_path("curl")
-        && is_in_path("jq")
-        && is_in_path("grep")
-        && is_in_path("head")
-        && is_in_path("tar")
+const RELEASES_URL: &str = "https://api.github.com/repos/emmanueltouzery/projectpad2/releases";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub version: String,
+    pub download_url: String,
 }
 
-pub fn apply_upgrade(download_url: &str) -> UResult<()> {
+pub struct ReleaseArchive {
+    pub tarball: Vec<u8>,
+    /// hex-encoded sha256 of the tarball, as published with the release
+    pub sha256: String,
+}
+
+pub trait ReleaseSource {
+    /// the newest release for this OS and architecture, if any
+    fn latest_release(&self) -> UResult<Option<Release>>;
+    fn fetch(&self, release: &Release) -> UResult<ReleaseArchive>;
+}
+
+/// the releases published on github. Each tarball must be published
+/// together with a `<tarball>.sha256` file.
+pub struct GithubReleases;
+
+impl GithubReleases {
+    fn get(url: &str) -> UResult<ureq::Response> {
+        let resp = ureq::get(url)
+            .set("User-Agent", concat!("ppcli/", env!("CARGO_PKG_VERSION")))
+            .timeout(HTTP_TIMEOUT)
+            .call();
+        if let Some(err) = resp.synthetic_error() {
+            return Err(format!("error fetching {}: {}", url, err).into());
+        }
+        if !resp.ok() {
+            return Err(format!("error fetching {}: HTTP {}", url, resp.status()).into());
+        }
+        Ok(resp)
+    }
+}
+
+impl ReleaseSource for GithubReleases {
+    fn latest_release(&self) -> UResult<Option<Release>> {
+        let releases: serde_json::Value =
+            serde_json::from_str(&Self::get(RELEASES_URL)?.into_string()?)?;
+        let platform = format!("{}_{}", env::consts::OS, env::consts::ARCH);
+        // the releases are sorted by date, newest first
+        let download_url = releases
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|r| r["assets"].as_array())
+            .flatten()
+            .filter_map(|a| a["browser_download_url"].as_str())
+            .find(|url| url.contains("cli") && url.contains(&platform) && url.ends_with(".tgz"));
+        download_url
+            .map(|url| {
+                Ok(Release {
+                    version: download_url_extract_version(url)?.to_string(),
+                    download_url: url.to_string(),
+                })
+            })
+            .transpose()
+    }
+
+    fn fetch(&self, release: &Release) -> UResult<ReleaseArchive> {
+        let mut tarball = vec![];
+        Self::get(&release.download_url)?
+            .into_reader()
+            .read_to_end(&mut tarball)?;
+        let sha256 = parse_checksum_file(
+            &Self::get(&(release.download_url.clone() + ".sha256"))?.into_string()?,
+        )?;
+        Ok(ReleaseArchive { tarball, sha256 })
+    }
+}
+
+/// a release tarball that was copied on the machine by hand. The checksum
+/// is either given explicitly, or read from a `<tarball>.sha256` file next
+/// to the tarball.
+pub struct LocalFile {
+    pub path: PathBuf,
+    pub sha256: Option<String>,
+}
+
+impl ReleaseSource for LocalFile {
+    fn latest_release(&self) -> UResult<Option<Release>> {
+        let path_str = self
+            .path
+            .to_str()
+            .ok_or("the tarball path is an invalid string")?;
+        Ok(Some(Release {
+            version: download_url_extract_version(path_str)
+                .unwrap_or("unknown")
+                .to_string(),
+            download_url: path_str.to_string(),
+        }))
+    }
+
+    fn fetch(&self, release: &Release) -> UResult<ReleaseArchive> {
+        let tarball = fs::read(&release.download_url)?;
+        let sha256 = match &self.sha256 {
+            Some(s) => s.trim().to_lowercase(),
+            None => {
+                let checksum_path = release.download_url.clone() + ".sha256";
+                parse_checksum_file(&fs::read_to_string(&checksum_path).map_err(|e| {
+                    format!(
+                        "can't read the checksum file {} ({}), pass the checksum with --sha256",
+                        checksum_path, e
+                    )
+                })?)?
+            }
+        };
+        Ok(ReleaseArchive { tarball, sha256 })
+    }
+}
+
+/// accepts the output of sha256sum: `<hex>  <filename>`, or just the hex
+fn parse_checksum_file(contents: &str) -> UResult<String> {
+    let checksum = contents
+        .split_whitespace()
+        .next()
+        .ok_or("the checksum file is empty")?
+        .to_lowercase();
+    if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid sha256 checksum: {}", checksum).into());
+    }
+    Ok(checksum)
+}
+
+fn confirm_upgrade(release: &Release) -> UResult<bool> {
     println!(
-        "ppcli has detected a ppcli version at:\n   {}\n   current version: {}\nUpgrade? y/n",
-        download_url,
+        "ppcli has detected a ppcli version at:\n   {}\n   new version: {}\n   current version: {}\nUpgrade? y/n",
+        release.download_url,
+        release.version,
         env!("CARGO_PKG_VERSION")
     );
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     let input_trimmed = input.trim();
-    if input_trimmed != "y" && input_trimmed != "Y" {
+    Ok(input_trimmed == "y" || input_trimmed == "Y")
+}
+
+pub fn apply_upgrade(download_url: &str) -> UResult<()> {
+    let release = Release {
+        version: download_url_extract_version(download_url)?.to_string(),
+        download_url: download_url.to_string(),
+    };
+    if !confirm_upgrade(&release)? {
         return Ok(());
     }
-    let parent_str = ppcli_path()?;
+    install_release(&GithubReleases, &release, &ppcli_path()?)
+}
 
-    Command::new("sh")
-        .args(&[
-            "-c",
-            &("cd ".to_string() + &parent_str + " && curl -L " + download_url + " | tar -xz"),
-        ])
-        .spawn()?
-        .wait()?;
+pub fn try_upgrade() -> UResult<()> {
+    let release = GithubReleases
+        .latest_release()?
+        .ok_or("can't find a URL of a newer version of ppcli")?;
+    apply_upgrade(&release.download_url)
+}
 
-    Ok(())
+pub fn upgrade_from_file(path: &Path, sha256: Option<String>) -> UResult<()> {
+    let source = LocalFile {
+        path: fs::canonicalize(path)?,
+        sha256,
+    };
+    let release = source
+        .latest_release()?
+        .ok_or("can't read the release tarball")?;
+    if !confirm_upgrade(&release)? {
+        return Ok(());
+    }
+    install_release(&source, &release, &ppcli_path()?)
+}
+
+pub fn is_upgrade_available() -> UResult<Option<String>> {
+    let release = GithubReleases.latest_release()?;
+    Ok(release
+        .filter(|r| r.version != env!("CARGO_PKG_VERSION"))
+        .map(|r| r.download_url))
 }
 
-fn ppcli_path() -> UResult<String> {
-    let path = env::args().next().ok_or("can't get the current app path")?;
-    let full_path = std::fs::canonicalize(&path)?;
-    let parent = full_path
-        .parent()
-        .ok_or("can't get the parent folder of the ppcli install")?;
-    let parent_str = parent
-        .to_str()
-        .ok_or("the ppcli parent folder is an invalid string")?;
-    Ok(parent_str.to_owned())
+fn ppcli_path() -> UResult<PathBuf> {
+    Ok(fs::canonicalize(env::current_exe()?)?)
 }
 
-fn get_latest_download_url() -> UResult<String> {
-    if !has_requirements() {
-        return Err("Auto-upgrade requires the following programs in the path: curl, jq, grep, head and tar.".into());
+fn sibling_path(binary: &Path, suffix: &str) -> UResult<PathBuf> {
+    let fname = binary
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or("can't get the file name of the ppcli install")?;
+    Ok(binary.with_file_name(format!("{}{}", fname, suffix)))
+}
+
+fn previous_binary_path(binary: &Path) -> UResult<PathBuf> {
+    sibling_path(binary, ".previous")
+}
+
+pub fn install_release(
+    source: &dyn ReleaseSource,
+    release: &Release,
+    binary: &Path,
+) -> UResult<()> {
+    let archive = source.fetch(release)?;
+    let actual_sha256 = format!("{:x}", Sha256::digest(&archive.tarball));
+    if actual_sha256 != archive.sha256 {
+        return Err(format!(
+            "checksum mismatch for {}: expected {}, got {}. Not upgrading.",
+            release.download_url, archive.sha256, actual_sha256
+        )
+        .into());
+    }
+
+    // extract in the same folder as the binary, so that the final
+    // rename doesn't cross filesystems and is atomic.
+    let new_binary = sibling_path(binary, ".new")?;
+    let res = extract_binary(&archive.tarball, &new_binary)
+        .and_then(|_| check_runs(&new_binary))
+        .and_then(|_| swap_binaries(&new_binary, binary));
+    if res.is_err() {
+        let _ = fs::remove_file(&new_binary);
     }
+    res
+}
 
-    let download_bytes = Command::new("sh")
-        .args(&[
-            "-c",
-            &[
-                "curl -sSf https://api.github.com/repos/emmanueltouzery/projectpad2/releases \
-                               | jq '.[] | .assets | select(length > 0)' \
-                               | jq -r '.[] | .browser_download_url' \
-                               | grep cli \
-                               | grep ",
-                std::env::consts::OS,
-                "_",
-                std::env::consts::ARCH,
-                " | head -n 1",
-            ]
-            .join(""),
-        ])
-        .output()?
-        .stdout;
-    let download_url = std::str::from_utf8(&download_bytes)?.trim();
-    if !download_url.starts_with("https://") {
-        return Err("can't find a URL of a newer version of ppcli".into());
+fn extract_binary(tarball: &[u8], dest: &Path) -> UResult<()> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name().and_then(|f| f.to_str()) == Some("ppcli") {
+            let mut contents = vec![];
+            entry.read_to_end(&mut contents)?;
+            fs::write(dest, contents)?;
+            fs::set_permissions(dest, fs::Permissions::from_mode(0o755))?;
+            return Ok(());
+        }
     }
-    Ok(download_url.to_owned())
+    Err("the release tarball doesn't contain a ppcli binary".into())
+}
+
+/// don't swap in a binary that can't run on this machine
+fn check_runs(binary: &Path) -> UResult<()> {
+    let status = Command::new(binary).arg("--version").output()?.status;
+    if !status.success() {
+        return Err(format!("the new ppcli binary failed to run: {}", status).into());
+    }
+    Ok(())
+}
+
+fn swap_binaries(new_binary: &Path, binary: &Path) -> UResult<()> {
+    let previous = previous_binary_path(binary)?;
+    if previous.exists() {
+        fs::remove_file(&previous)?;
+    }
+    // hard link rather than rename, so that there is always a ppcli
+    // binary in place, even if we get interrupted
+    if fs::hard_link(binary, &previous).is_err() {
+        fs::copy(binary, &previous)?;
+    }
+    fs::rename(new_binary, binary)?;
+    Ok(())
+}
+
+pub fn rollback() -> UResult<()> {
+    rollback_binary(&ppcli_path()?)
+}
+
+fn rollback_binary(binary: &Path) -> UResult<()> {
+    let previous = previous_binary_path(binary)?;
+    if !previous.exists() {
+        return Err(format!(
+            "no previous ppcli binary to roll back to ({} doesn't exist)",
+            previous.display()
+        )
+        .into());
+    }
+    fs::rename(&previous, binary)?;
+    Ok(())
 }
 
 fn download_url_extract_version(download_url: &str) -> UResult<&str> {
@@ -90,45 +305,90 @@ fn download_url_extract_version(download_url: &str) -> UResult<&str> {
         .ok_or_else(|| format!("failed parsing download URL: {}", download_url).into())
 }
 
-// i don't want to add too many dependencies to ppcli itself,
-// just because of the auto-upgrade...
-// i would need to depend on a http library (ok, i already have
-// openssl), and a tar.gz extracting library. Not that bad, but...
-pub fn try_upgrade() -> UResult<()> {
-    let download_url = get_latest_download_url()?;
-    apply_upgrade(&download_url)
+#[test]
+fn parses_version_from_url() {
+    assert_eq!("2.1.0", download_url_extract_version(
+        "https://github.com/emmanueltouzery/projectpad2/releases/download/v2.1.0/ppcli-2.1.0-linux_x86_64.tgz").unwrap());
 }
 
-pub fn is_upgrade_available() -> UResult<Option<String>> {
-    let download_url = get_latest_download_url()?;
-    let version = download_url_extract_version(&download_url)?;
-    let is_new_version = version != env!("CARGO_PKG_VERSION");
-    Ok(Some(download_url).filter(|_| is_new_version))
-}
-
-// https://stackoverflow.com/a/37499032/516188
-fn is_in_path<P>(exe_name: P) -> bool
-where
-    P: AsRef<Path>,
-{
-    env::var_os("PATH")
-        .and_then(|paths| {
-            env::split_paths(&paths)
-                .filter_map(|dir| {
-                    let full_path = dir.join(&exe_name);
-                    if full_path.is_file() {
-                        Some(full_path)
-                    } else {
-                        None
-                    }
-                })
-                .next()
-        })
-        .is_some()
+#[test]
+fn parses_checksum_file() {
+    assert_eq!(
+        "a".repeat(64),
+        parse_checksum_file(&format!(
+            "{}  ppcli-2.1.0-linux_x86_64.tgz\n",
+            "A".repeat(64)
+        ))
+        .unwrap()
+    );
+    assert!(parse_checksum_file("abc").is_err());
+}
+
+#[cfg(test)]
+fn test_tarball(binary_contents: &[u8]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        vec![],
+        flate2::Compression::default(),
+    ));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(binary_contents.len() as u64);
+    header.set_mode(0o755);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "ppcli", binary_contents)
+        .unwrap();
+    builder.into_inner().unwrap().finish().unwrap()
 }
 
 #[test]
-fn parses_version_from_url() {
-    assert_eq!("2.1.0", download_url_extract_version(
-        "https://github.com/emmanueltouzery/projectpad2/releases/download/v2.1.0/ppcli-2.1.0-linux_x86_64.tgz").unwrap());
+fn install_from_local_file_verifies_swaps_and_rolls_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let binary = dir.path().join("ppcli");
+    fs::write(&binary, "#!/bin/sh\necho old\n").unwrap();
+    fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let tarball = test_tarball(b"#!/bin/sh\necho new\n");
+    let tarball_path = dir.path().join("ppcli-9.9.9-linux_x86_64.tgz");
+    fs::write(&tarball_path, &tarball).unwrap();
+
+    let bad_source = LocalFile {
+        path: tarball_path.clone(),
+        sha256: Some("0".repeat(64)),
+    };
+    let release = bad_source.latest_release().unwrap().unwrap();
+    assert_eq!("9.9.9", release.version);
+    assert!(install_release(&bad_source, &release, &binary).is_err());
+    assert_eq!(
+        "#!/bin/sh\necho old\n",
+        fs::read_to_string(&binary).unwrap()
+    );
+
+    fs::write(
+        dir.path().join("ppcli-9.9.9-linux_x86_64.tgz.sha256"),
+        format!(
+            "{:x}  ppcli-9.9.9-linux_x86_64.tgz",
+            Sha256::digest(&tarball)
+        ),
+    )
+    .unwrap();
+    let source = LocalFile {
+        path: tarball_path,
+        sha256: None,
+    };
+    install_release(&source, &release, &binary).unwrap();
+    assert_eq!(
+        "#!/bin/sh\necho new\n",
+        fs::read_to_string(&binary).unwrap()
+    );
+    assert_eq!(
+        "#!/bin/sh\necho old\n",
+        fs::read_to_string(dir.path().join("ppcli.previous")).unwrap()
+    );
+
+    rollback_binary(&binary).unwrap();
+    assert_eq!(
+        "#!/bin/sh\necho old\n",
+        fs::read_to_string(&binary).unwrap()
+    );
+    assert!(rollback_binary(&binary).is_err());
 }
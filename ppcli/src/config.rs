@@ -53,16 +53,13 @@ pub fn upgrade_days_since_last_check() -> Result<u64, Box<dyn std::error::Error>
 /// "blank" queries to the query history (if the user manually selected an entry
 /// without filtering). However because the actions sorting is
 /// based on number of occurences, we don't want to de-duplicate those.
+// per profile: the actions refer to the items of the database
 fn history_file_path() -> PathBuf {
-    let mut path = projectpadsql::config_path();
-    path.push("cli-history");
-    path
+    projectpadsql::profile::file_path(&projectpadsql::profile::current(), "cli-history")
 }
 
 fn actions_file_path() -> PathBuf {
-    let mut path = projectpadsql::config_path();
-    path.push("action-history");
-    path
+    projectpadsql::profile::file_path(&projectpadsql::profile::current(), "action-history")
 }
 
 pub fn read_action_history() -> Result<Vec<ExecutedAction>, std::io::Error> {
@@ -2,9 +2,17 @@ use super::actions;
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 use projectpadsql::models::*;
+use projectpadsql::repo::{
+    AttachmentRepo, EnvironmentRepo, ProjectRepo, ServerPoiRepo, ServerRepo, TagRepo,
+};
+use projectpadsql::secrets;
+use projectpadsql::totp::Totp;
 use skim::prelude::*;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
 use std::string::ToString;
 use strum_macros::{Display, EnumString};
 
@@ -20,8 +28,13 @@ pub enum ItemType {
 pub struct ServerInfo {
     pub server_desc: String,
     pub server_username: String,
-    pub server_ip: String,
+    pub server_host: String,
+    pub server_port: Option<i32>,
     pub server_access_type: ServerAccessType,
+    pub server_has_otp_secret: bool,
+    pub server_has_attachments: bool,
+    // last so that it doesn't influence the sorting
+    pub server_id: i32,
 }
 
 #[derive(Debug, Clone)]
@@ -39,8 +52,9 @@ pub enum LinkedItem {
 #[derive(Debug, Clone)]
 pub struct ItemOfInterest {
     pub linked_item: LinkedItem,
+    pub project_id: i32,
     pub project_name: String,
-    pub env: Option<EnvironmentType>,
+    pub env: Option<Environment>,
     pub item_type: ItemType,
     pub poi_desc: Option<String>,
     pub item_text: String,
@@ -49,159 +63,237 @@ pub struct ItemOfInterest {
     pub run_on: Option<RunOn>,
 }
 
-fn filter_servers(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
-    use projectpadsql::schema::project::dsl as prj;
-    use projectpadsql::schema::server::dsl as srv;
-    srv::server
-        .inner_join(prj::project)
-        .select((
-            srv::id,
-            prj::name,
-            srv::desc,
-            srv::environment,
-            srv::ip,
-            srv::server_type,
-            srv::username,
-            srv::access_type,
-        ))
-        .filter(srv::access_type.ne_all(vec![
-            ServerAccessType::SrvAccessRdp,
-            ServerAccessType::SrvAccessWww,
-        ]))
-        .load::<(_, _, String, _, String, _, _, _)>(db_conn)
-        .unwrap()
+/// the environments of all the projects, by project id and environment name
+type EnvironmentsByName = HashMap<(i32, String), Environment>;
+
+fn load_environments(
+    db_conn: &SqliteConnection,
+) -> projectpadsql::error::Result<EnvironmentsByName> {
+    Ok(EnvironmentRepo::new(db_conn)
+        .list()?
+        .into_iter()
+        .map(|env| ((env.project_id, env.name.clone()), env))
+        .collect())
+}
+
+/// the names of the projects not in the trash, by project id
+fn load_project_names(
+    db_conn: &SqliteConnection,
+) -> projectpadsql::error::Result<HashMap<i32, String>> {
+    Ok(ProjectRepo::new(db_conn)
+        .list()?
         .into_iter()
-        .map(
-            |(
-                id,
-                project_name,
-                server_desc,
-                srv_env,
-                server_ip,
-                server_type,
-                server_username,
-                server_access_type,
-            )| {
-                ItemOfInterest {
-                    linked_item: LinkedItem::ServerId(id),
-                    project_name,
-                    env: Some(srv_env),
-                    item_type: ItemType::ServerItemType(server_type),
-                    poi_desc: Some(server_desc.clone()),
-                    item_text: server_ip.clone(),
-                    server_info: Some(ServerInfo {
-                        server_desc,
-                        server_username,
-                        server_ip,
-                        server_access_type,
-                    }),
-                    poi_info: None,
-                    run_on: None,
+        .map(|prj| (prj.id, prj.name))
+        .collect())
+}
+
+fn find_environment(envs: &EnvironmentsByName, project_id: i32, name: String) -> Environment {
+    envs.get(&(project_id, name.clone()))
+        .cloned()
+        // the project doesn't have that environment anymore
+        .unwrap_or_else(|| Environment {
+            id: 0,
+            short_label: name.to_uppercase(),
+            name,
+            color: "".to_string(),
+            ordering: 0,
+            dangerous: false,
+            project_id,
+        })
+}
+
+/// ppcli can't connect to RDP servers or websites
+fn is_supported_access_type(access_type: ServerAccessType) -> bool {
+    !matches!(
+        access_type,
+        ServerAccessType::SrvAccessRdp | ServerAccessType::SrvAccessWww
+    )
+}
+
+fn server_info(server: &Server, with_attachments: &HashSet<i32>) -> ServerInfo {
+    ServerInfo {
+        server_desc: server.desc.clone(),
+        server_username: server.username.clone(),
+        server_host: server.host.clone(),
+        server_port: server.port,
+        server_access_type: server.access_type,
+        server_has_otp_secret: !server.otp_secret.is_empty(),
+        server_has_attachments: with_attachments.contains(&server.id),
+        server_id: server.id,
+    }
+}
+
+fn filter_servers(
+    servers: &[Server],
+    project_names: &HashMap<i32, String>,
+    envs: &EnvironmentsByName,
+    with_attachments: &HashSet<i32>,
+) -> Vec<ItemOfInterest> {
+    servers
+        .iter()
+        .filter(|srv| is_supported_access_type(srv.access_type))
+        .filter_map(|srv| {
+            let project_name = project_names.get(&srv.project_id)?;
+            Some(ItemOfInterest {
+                linked_item: LinkedItem::ServerId(srv.id),
+                project_id: srv.project_id,
+                project_name: project_name.clone(),
+                env: Some(find_environment(
+                    envs,
+                    srv.project_id,
+                    srv.environment.clone(),
+                )),
+                item_type: ItemType::ServerItemType(srv.server_type),
+                poi_desc: Some(srv.desc.clone()),
+                item_text: ServerAddress {
+                    host: srv.host.clone(),
+                    port: srv.port,
+                    ..ServerAddress::default()
                 }
-            },
-        )
+                .to_string(),
+                server_info: Some(server_info(srv, with_attachments)),
+                poi_info: None,
+                run_on: None,
+            })
+        })
         .collect()
 }
 
-fn filter_project_pois(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
-    use projectpadsql::schema::project::dsl as prj;
+fn filter_project_pois(
+    db_conn: &SqliteConnection,
+    project_names: &HashMap<i32, String>,
+) -> projectpadsql::error::Result<Vec<ItemOfInterest>> {
     use projectpadsql::schema::project_point_of_interest::dsl as prj_poi;
-    prj_poi::project_point_of_interest
-        .inner_join(prj::project)
-        .select((
-            prj_poi::id,
-            prj::name,
-            prj_poi::desc,
-            prj_poi::text,
-            prj_poi::interest_type,
-            prj_poi::path,
-        ))
-        .load::<(_, _, _, _, _, String)>(db_conn)
-        .unwrap()
+    Ok(prj_poi::project_point_of_interest
+        .filter(prj_poi::deleted_at.is_null())
+        .load::<ProjectPointOfInterest>(db_conn)?
         .into_iter()
-        .map(
-            |(id, project_name, prj_poi_desc, item_text, prj_poi_interest_type, prj_path)| {
-                ItemOfInterest {
-                    linked_item: LinkedItem::ProjectPoiId(id),
-                    project_name,
-                    env: None,
-                    item_type: ItemType::InterestItemType(prj_poi_interest_type),
-                    poi_desc: Some(prj_poi_desc),
-                    item_text,
-                    server_info: None,
-                    poi_info: Some(PoiInfo {
-                        path: prj_path.into(),
-                    }),
-                    run_on: None,
-                }
-            },
-        )
-        .collect()
+        .filter_map(|poi| {
+            let project_name = project_names.get(&poi.project_id)?;
+            Some(ItemOfInterest {
+                linked_item: LinkedItem::ProjectPoiId(poi.id),
+                project_id: poi.project_id,
+                project_name: project_name.clone(),
+                env: None,
+                item_type: ItemType::InterestItemType(poi.interest_type),
+                poi_desc: Some(poi.desc),
+                item_text: poi.text,
+                server_info: None,
+                poi_info: Some(PoiInfo {
+                    path: poi.path.into(),
+                }),
+                run_on: None,
+            })
+        })
+        .collect())
+}
+
+fn filter_server_pois(
+    db_conn: &SqliteConnection,
+    servers: &[Server],
+    project_names: &HashMap<i32, String>,
+    envs: &EnvironmentsByName,
+    with_attachments: &HashSet<i32>,
+) -> projectpadsql::error::Result<Vec<ItemOfInterest>> {
+    let servers_by_id: HashMap<_, _> = servers
+        .iter()
+        .filter(|srv| is_supported_access_type(srv.access_type))
+        .map(|srv| (srv.id, srv))
+        .collect();
+    Ok(ServerPoiRepo::new(db_conn)
+        .list()?
+        .into_iter()
+        .filter_map(|poi| {
+            let srv = servers_by_id.get(&poi.server_id)?;
+            let project_name = project_names.get(&srv.project_id)?;
+            Some(ItemOfInterest {
+                linked_item: LinkedItem::ServerPoiId(poi.id),
+                project_id: srv.project_id,
+                project_name: project_name.clone(),
+                env: Some(find_environment(
+                    envs,
+                    srv.project_id,
+                    srv.environment.clone(),
+                )),
+                item_type: ItemType::InterestItemType(poi.interest_type),
+                poi_desc: Some(poi.desc),
+                item_text: poi.text,
+                server_info: Some(server_info(srv, with_attachments)),
+                poi_info: Some(PoiInfo {
+                    path: poi.path.into(),
+                }),
+                run_on: Some(poi.run_on),
+            })
+        })
+        .collect())
 }
 
-fn filter_server_pois(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
-    use projectpadsql::schema::project::dsl as prj;
-    use projectpadsql::schema::server::dsl as srv;
-    use projectpadsql::schema::server_point_of_interest::dsl as srv_poi;
-    srv_poi::server_point_of_interest
-        .inner_join(srv::server.inner_join(prj::project))
-        .select((
-            srv_poi::id,
-            prj::name,
-            srv::desc,
-            srv_poi::desc,
-            srv::environment,
-            srv_poi::text,
-            srv_poi::interest_type,
-            srv::username,
-            srv_poi::path,
-            srv::access_type,
-            srv::ip,
-            srv_poi::run_on,
-        ))
-        .filter(srv::access_type.ne_all(vec![
-            ServerAccessType::SrvAccessRdp,
-            ServerAccessType::SrvAccessWww,
-        ]))
-        .load::<(_, _, _, _, _, _, _, _, String, _, _, _)>(db_conn)
-        .unwrap()
+/// keep the items which have all the tags. Server points of interest
+/// also have the tags of their server.
+fn filter_by_tags(
+    db_conn: &SqliteConnection,
+    items: Vec<ItemOfInterest>,
+    tags: &[String],
+) -> projectpadsql::error::Result<Vec<ItemOfInterest>> {
+    if tags.is_empty() {
+        return Ok(items);
+    }
+    let wanted: Vec<_> = tags.iter().map(|t| Tag::normalize_name(t)).collect();
+    let repo = TagRepo::new(db_conn);
+    let load_tags = |entity_type, ids: Vec<i32>| repo.list_for_items(entity_type, &ids);
+    let server_tags = load_tags(
+        EntityType::Server,
+        items
+            .iter()
+            .filter_map(|i| i.server_info.as_ref().map(|s| s.server_id))
+            .collect(),
+    )?;
+    let server_poi_tags = load_tags(
+        EntityType::ServerPoi,
+        items
+            .iter()
+            .filter_map(|i| match i.linked_item {
+                LinkedItem::ServerPoiId(id) => Some(id),
+                _ => None,
+            })
+            .collect(),
+    )?;
+    let project_poi_tags = load_tags(
+        EntityType::ProjectPoi,
+        items
+            .iter()
+            .filter_map(|i| match i.linked_item {
+                LinkedItem::ProjectPoiId(id) => Some(id),
+                _ => None,
+            })
+            .collect(),
+    )?;
+    Ok(items
         .into_iter()
-        .map(
-            |(
-                id,
-                project_name,
-                server_desc,
-                server_poi_desc,
-                srv_env,
-                item_text,
-                srv_poi_interest_type,
-                server_username,
-                srv_poi_path,
-                server_access_type,
-                server_ip,
-                run_on_val,
-            )| {
-                ItemOfInterest {
-                    linked_item: LinkedItem::ServerPoiId(id),
-                    project_name,
-                    env: Some(srv_env),
-                    item_type: ItemType::InterestItemType(srv_poi_interest_type),
-                    poi_desc: Some(server_poi_desc),
-                    item_text,
-                    server_info: Some(ServerInfo {
-                        server_desc,
-                        server_username,
-                        server_ip,
-                        server_access_type,
-                    }),
-                    poi_info: Some(PoiInfo {
-                        path: srv_poi_path.into(),
-                    }),
-                    run_on: Some(run_on_val),
+        .filter(|item| {
+            let mut item_tags: Vec<&Tag> = vec![];
+            if let Some(server_info) = &item.server_info {
+                item_tags.extend(
+                    server_tags
+                        .get(&server_info.server_id)
+                        .into_iter()
+                        .flatten(),
+                );
+            }
+            match item.linked_item {
+                LinkedItem::ServerPoiId(id) => {
+                    item_tags.extend(server_poi_tags.get(&id).into_iter().flatten())
                 }
-            },
-        )
-        .collect()
+                LinkedItem::ProjectPoiId(id) => {
+                    item_tags.extend(project_poi_tags.get(&id).into_iter().flatten())
+                }
+                LinkedItem::ServerId(_) => {}
+            }
+            wanted
+                .iter()
+                .all(|name| item_tags.iter().any(|t| &t.name == name))
+        })
+        .collect())
 }
 
 #[derive(Hash, PartialEq, Eq, EnumString, Display, Clone, Copy, PartialOrd, Ord, Debug)]
@@ -212,6 +304,8 @@ pub enum ActionType {
     LessLog,
     #[strum(serialize = "fetch log")]
     FetchLog,
+    #[strum(serialize = "grep log")]
+    GrepLog,
     #[strum(serialize = "ssh folder")]
     SshFolder,
     #[strum(serialize = "ssh shell")]
@@ -222,10 +316,18 @@ pub enum ActionType {
     RunCmd,
     #[strum(serialize = "edit cfg")]
     EditCfg,
+    #[strum(serialize = "edit local")]
+    EditCfgLocally,
+    #[strum(serialize = "edit sudo")]
+    EditCfgLocallySudo,
     #[strum(serialize = "less cfg")]
     LessCfg,
     #[strum(serialize = "fetch cfg")]
     FetchCfg,
+    #[strum(serialize = "copy otp")]
+    CopyOtp,
+    #[strum(serialize = "save attachments")]
+    SaveAttachments,
 }
 
 #[derive(Hash, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Debug)]
@@ -243,16 +345,33 @@ impl ExecutedAction {
 pub fn load_items<T>(
     conn: &SqliteConnection,
     display_mode: DisplayMode,
+    tags: &[String],
     item_sender: &Sender<Arc<dyn SkimItem>>,
     ranked_items: &HashMap<ExecutedAction, T>,
-) where
+) -> projectpadsql::error::Result<()>
+where
     T: Ord,
 {
-    let mut items = filter_server_pois(&conn);
-    items.extend(filter_project_pois(&conn));
-    items.extend(filter_servers(&conn));
+    let envs = load_environments(conn)?;
+    let project_names = load_project_names(conn)?;
+    let servers = ServerRepo::new(conn).list()?;
+    let with_attachments =
+        AttachmentRepo::new(conn).item_ids_with_attachments(EntityType::Server)?;
+    let mut items = filter_server_pois(conn, &servers, &project_names, &envs, &with_attachments)?;
+    items.extend(filter_project_pois(conn, &project_names)?);
+    items.extend(filter_servers(
+        &servers,
+        &project_names,
+        &envs,
+        &with_attachments,
+    ));
+    let items = filter_by_tags(conn, items, tags)?;
     if items.is_empty() {
-        println!("No items to display. Keep in mind that ppcli will only display non RDP/non WWW servers, and point of interests");
+        if tags.is_empty() {
+            println!("No items to display. Keep in mind that ppcli will only display non RDP/non WWW servers, and point of interests");
+        } else {
+            println!("No items with the tags {}", tags.join(", "));
+        }
         std::process::exit(0);
     }
     // items.reverse();
@@ -280,6 +399,84 @@ pub fn load_items<T>(
             inner: action,
         }));
     }
+    Ok(())
+}
+
+/// if the value is protected by a master passphrase, prompts for it
+fn reveal(conn: &SqliteConnection, value: String) -> projectpadsql::error::Result<String> {
+    if !secrets::is_encrypted(&value) {
+        return Ok(value);
+    }
+    let passphrase = rpassword::read_password_from_tty(Some("Master passphrase: "))
+        .map_err(projectpadsql::error::Error::Io)?;
+    secrets::unlock(conn, &passphrase)?.decrypt(&value)
+}
+
+/// the password of the server, None if it's protected by the master
+/// passphrase: we don't prompt for it, the caller didn't ask for the password
+pub fn get_unprotected_server_password(
+    conn: &SqliteConnection,
+    server_id: i32,
+) -> projectpadsql::error::Result<Option<String>> {
+    let password = ServerRepo::new(conn).get(server_id)?.password;
+    Ok(Some(password).filter(|p| !secrets::is_encrypted(p)))
+}
+
+/// the current one-time code of the server, see `projectpadsql::totp`
+pub fn get_server_otp_code(
+    conn: &SqliteConnection,
+    server_id: i32,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let otp_secret = reveal(conn, ServerRepo::new(conn).get(server_id)?.otp_secret)?;
+    let totp: Totp = otp_secret.parse()?;
+    Ok(totp.current_code().0)
+}
+
+/// write the attachments of the server to the current folder, readable only by
+/// the user. Existing files are not overwritten. Returns the names of the files
+/// saved, which are kept when a later one fails, and the error.
+pub fn save_server_attachments(
+    conn: &SqliteConnection,
+    server_id: i32,
+) -> (Vec<String>, Result<(), Box<dyn std::error::Error>>) {
+    let mut saved = vec![];
+    let result = write_server_attachments(conn, server_id, &mut saved);
+    (saved, result)
+}
+
+fn write_server_attachments(
+    conn: &SqliteConnection,
+    server_id: i32,
+    saved: &mut Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = AttachmentRepo::new(conn);
+    for attachment in repo.list_for_item(EntityType::Server, server_id)? {
+        // the name could come from an import: don't let it point elsewhere
+        let file_name = Path::new(&attachment.name)
+            .file_name()
+            .ok_or_else(|| format!("invalid attachment name: {}", attachment.name))?;
+        let contents = repo.contents(attachment.id)?;
+        // create_new checks for an existing file and creates it in one step
+        let path = Path::new(file_name);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => {
+                    format!("{} already exists, not overwriting it", path.display())
+                }
+                _ => format!("{}: {}", path.display(), e),
+            })?;
+        if let Err(e) = file.write_all(&contents) {
+            // don't leave a truncated key behind
+            let _ = fs::remove_file(path);
+            return Err(format!("{}: {}", path.display(), e).into());
+        }
+        saved.push(path.display().to_string());
+    }
+    Ok(())
 }
 
 fn render_row(cols_spec: &[usize], action: &actions::Action, display_mode: DisplayMode) -> String {
@@ -290,12 +487,14 @@ fn render_row(cols_spec: &[usize], action: &actions::Action, display_mode: Displ
         .env
         .as_ref()
         .map(|env| display_env(env, display_mode))
-        .unwrap_or(if display_mode == DisplayMode::Color {
-            "-   "
-        } else {
-            "-  "
-        })
-        .to_string();
+        .unwrap_or_else(|| {
+            if display_mode == DisplayMode::Color {
+                "-   "
+            } else {
+                "-  "
+            }
+            .to_string()
+        });
     // col2.truncate(cols_spec[1]);
     let mut col3 = render_type(&item.item_type).to_string();
     col3.truncate(cols_spec[2]);
@@ -336,16 +535,15 @@ pub enum DisplayMode {
     Color,
 }
 
-fn display_env(env: &EnvironmentType, display_mode: DisplayMode) -> &'static str {
-    match (env, display_mode) {
-        (EnvironmentType::EnvDevelopment, DisplayMode::Color) => "\x1b[32m\x1b[1m❚DEV\x1b[0m",
-        (EnvironmentType::EnvDevelopment, DisplayMode::Plain) => "DEV",
-        (EnvironmentType::EnvStage, DisplayMode::Color) => "\x1b[34m\x1b[1m❚STG\x1b[0m",
-        (EnvironmentType::EnvStage, DisplayMode::Plain) => "STG",
-        (EnvironmentType::EnvUat, DisplayMode::Color) => "\x1b[33m\x1b[1m❚UAT\x1b[0m",
-        (EnvironmentType::EnvUat, DisplayMode::Plain) => "UAT",
-        (EnvironmentType::EnvProd, DisplayMode::Color) => "\x1b[31m\x1b[1m❚PRD\x1b[0m",
-        (EnvironmentType::EnvProd, DisplayMode::Plain) => "PRD",
+fn display_env(env: &Environment, display_mode: DisplayMode) -> String {
+    // the column is three characters wide
+    let label = format!("{:<3.3}", env.short_label);
+    match (env.rgb(), display_mode) {
+        (Some((r, g, b)), DisplayMode::Color) => {
+            format!("\x1b[38;2;{};{};{}m\x1b[1m❚{}\x1b[0m", r, g, b, label)
+        }
+        (None, DisplayMode::Color) => format!("\x1b[1m❚{}\x1b[0m", label),
+        (_, DisplayMode::Plain) => label,
     }
 }
 
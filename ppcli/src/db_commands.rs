@@ -0,0 +1,259 @@
+// `ppcli db ...`: database maintenance, so that ppcli can be used
+// on a machine where the projectpad GUI is not installed.
+use crate::secretservice;
+use diesel::prelude::*;
+use projectpadsql::check::{self, Fix, Issue};
+use projectpadsql::sync::{self, BackendKind};
+use projectpadsql::{backup, cipher, migrations, secrets};
+use std::path::Path;
+
+type SResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+fn print_applied_migrations(applied: &[i32]) {
+    if applied.is_empty() {
+        println!(
+            "The database is up to date (version {}).",
+            migrations::SCHEMA_VERSION
+        );
+    } else {
+        for version in applied {
+            println!("applied migration {}", version);
+        }
+        println!(
+            "The database was migrated to version {}.",
+            migrations::SCHEMA_VERSION
+        );
+    }
+}
+
+/// create a new database, and store its password in the OS keyring
+pub fn init_db() -> SResult<()> {
+    let db_path = projectpadsql::database_path();
+    if db_path.exists() {
+        return Err(format!("a database already exists at {}", db_path.display()).into());
+    }
+    let pass = rpassword::read_password_from_tty(Some("Password for the new database: "))?;
+    if pass.is_empty() {
+        return Err("the password can't be empty".into());
+    }
+    let confirm = rpassword::read_password_from_tty(Some("Confirm the password: "))?;
+    if pass != confirm {
+        return Err("the passwords don't match".into());
+    }
+    std::fs::create_dir_all(projectpadsql::config_path())?;
+    let conn = SqliteConnection::establish(&db_path.to_string_lossy())?;
+    projectpadsql::try_unlock_db_file(&conn, &pass, &db_path)?;
+    print_applied_migrations(&migrations::migrate_db_if_needed(&conn, None)?);
+    secretservice::set_keyring_pass(&pass).map_err(|e| {
+        format!(
+            "The database was created, but storing the password in the OS keyring failed: {}",
+            e
+        )
+    })?;
+    println!("Created {}.", db_path.display());
+    Ok(())
+}
+
+/// apply the pending migrations, after taking a backup of the database
+pub fn migrate_db(conn: &SqliteConnection) -> SResult<()> {
+    let db_path = projectpadsql::database_path();
+    let from_version = projectpadsql::get_db_version(conn)?;
+    let applied = migrations::migrate_db_if_needed(conn, Some(&db_path))?;
+    if !applied.is_empty() {
+        println!(
+            "The database was backed up to {}.",
+            migrations::pre_migration_backup_path(&db_path, from_version).display()
+        );
+    }
+    print_applied_migrations(&applied);
+    Ok(())
+}
+
+/// protect the passwords with a master passphrase, on top of the database password
+pub fn protect_passwords(conn: &SqliteConnection) -> SResult<()> {
+    if secrets::is_enabled(conn)? {
+        return Err("the passwords are already protected by a master passphrase".into());
+    }
+    let pass = rpassword::read_password_from_tty(Some("New master passphrase: "))?;
+    if pass.is_empty() {
+        return Err("the passphrase can't be empty".into());
+    }
+    let confirm = rpassword::read_password_from_tty(Some("Confirm the passphrase: "))?;
+    if pass != confirm {
+        return Err("the passphrases don't match".into());
+    }
+    secrets::enable(conn, &pass)?;
+    println!("The passwords are now encrypted with the master passphrase.");
+    Ok(())
+}
+
+/// remove the master passphrase, the passwords are then stored in plain text again
+pub fn unprotect_passwords(conn: &SqliteConnection) -> SResult<()> {
+    if !secrets::is_enabled(conn)? {
+        return Err("the passwords are not protected by a master passphrase".into());
+    }
+    let pass = rpassword::read_password_from_tty(Some("Master passphrase: "))?;
+    let key = secrets::unlock(conn, &pass)?;
+    secrets::disable(conn, &key)?;
+    println!("The master passphrase was removed.");
+    Ok(())
+}
+
+/// copy the database to `dest`, encrypted with the database password
+pub fn backup_db(conn: &SqliteConnection, dest: &Path) -> SResult<()> {
+    projectpadsql::backup(conn, dest)?;
+    println!("The database was backed up to {}.", dest.display());
+    Ok(())
+}
+
+/// replace the database with a backup, after checking the backup
+pub fn restore_db(pass: &str, backup_path: &Path) -> SResult<()> {
+    let db_path = projectpadsql::database_path();
+    // restore keeps the previous database only if there was one,
+    // afterwards db_path always exists
+    let had_db = db_path.exists();
+    let backup_version = backup::restore(backup_path, pass, &db_path)?;
+    println!("The database was restored from {}.", backup_path.display());
+    if had_db {
+        println!(
+            "The previous database was kept in {}.",
+            backup::before_restore_path(&db_path).display()
+        );
+    }
+    if backup_version < migrations::SCHEMA_VERSION {
+        println!("Run `ppcli db migrate` to upgrade the database to the latest schema version.");
+    }
+    Ok(())
+}
+
+/// convert the database to the sqlcipher 4 format, see `cipher::upgrade`
+pub fn upgrade_cipher(pass: &str) -> SResult<()> {
+    let db_path = projectpadsql::database_path();
+    if cipher::upgrade(&db_path, pass)? {
+        println!("The database now uses the sqlcipher 4 format.");
+        println!(
+            "The previous database was kept in {}, for `ppcli db rollback-cipher`.",
+            cipher::rollback_path(&db_path).display()
+        );
+    } else {
+        println!("The database already uses the sqlcipher 4 format.");
+    }
+    Ok(())
+}
+
+/// go back to the database from before `upgrade_cipher`
+pub fn rollback_cipher() -> SResult<()> {
+    if cipher::rollback(&projectpadsql::database_path())? {
+        println!("The database was restored from before the sqlcipher 4 conversion.");
+    } else {
+        println!("The database already uses the sqlcipher 3 format.");
+    }
+    Ok(())
+}
+
+/// enable the sync with the other devices, or change its settings
+pub fn sync_setup(
+    conn: &SqliteConnection,
+    device_name: &str,
+    git: bool,
+    location: &Path,
+) -> SResult<()> {
+    if !location.is_dir() {
+        return Err(format!("{} is not a folder", location.display()).into());
+    }
+    let pass = rpassword::read_password_from_tty(Some("Passphrase of the team: "))?;
+    if pass.is_empty() {
+        return Err("the passphrase can't be empty".into());
+    }
+    let backend = if git {
+        BackendKind::Git
+    } else {
+        BackendKind::Folder
+    };
+    sync::enable(
+        conn,
+        device_name,
+        backend,
+        &location.to_string_lossy(),
+        &pass,
+    )?;
+    println!("The sync is set up, run `ppcli db sync` to sync now.");
+    Ok(())
+}
+
+/// share the local changes with the other devices, and apply theirs
+pub fn sync_db(conn: &SqliteConnection) -> SResult<()> {
+    let settings =
+        sync::settings(conn)?.ok_or("the sync is not set up, see `ppcli db sync-setup`")?;
+    let report = sync::sync(conn, settings.backend().as_ref())?;
+    println!(
+        "Sent {} changes, received {}, applied {}.",
+        report.pushed, report.received, report.applied
+    );
+    if report.waiting > 0 {
+        println!(
+            "{} changes wait for entities which the other devices didn't share yet.",
+            report.waiting
+        );
+    }
+    let conflicts = sync::conflicts(conn)?;
+    if !conflicts.is_empty() {
+        println!(
+            "{} conflicting edits to resolve in projectpad (Sync in the menu):",
+            conflicts.len()
+        );
+        for conflict in conflicts {
+            println!(
+                "  {} '{}', edited on {}",
+                conflict.entity_type, conflict.title, conflict.device_name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn describe_fix(fix: &Fix) -> String {
+    match fix {
+        Fix::Delete => "delete it".to_string(),
+        Fix::MoveToTrash => "move it to the trash".to_string(),
+        Fix::ClearTunnel => "remove the ssh tunnel".to_string(),
+        Fix::ClearDatabase => "remove the database".to_string(),
+        Fix::Rename(desc) => format!("rename it to '{}'", desc),
+        Fix::ClearGroupName => "remove it from the group".to_string(),
+        Fix::RemoveAuthKey => "remove the key".to_string(),
+    }
+}
+
+fn describe_issue(issue: &Issue) -> String {
+    format!(
+        "{} > {} ({}): {}, fix: {}",
+        issue.project_name,
+        issue.title,
+        issue.entity_type,
+        issue.detail,
+        describe_fix(&issue.fix)
+    )
+}
+
+/// report the dangling references and the other issues of the database,
+/// and fix them if requested. Returns the count of issues left.
+pub fn check_db(conn: &SqliteConnection, fix: bool) -> SResult<usize> {
+    let issues = projectpadsql::check(conn)?;
+    for issue in &issues {
+        println!("{}", describe_issue(issue));
+    }
+    if issues.is_empty() {
+        println!("No issue found.");
+        Ok(0)
+    } else if fix {
+        check::fix_all(conn, &issues)?;
+        println!("Fixed {} issue(s).", issues.len());
+        Ok(0)
+    } else {
+        println!(
+            "{} issue(s) found, run `ppcli db check --fix` to fix them.",
+            issues.len()
+        );
+        Ok(issues.len())
+    }
+}
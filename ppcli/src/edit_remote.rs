@@ -0,0 +1,363 @@
+// "edit local": edit a remote config file with the local editor instead
+// of whatever editor is installed on the server.
+// We download the file to a private temporary folder, run the local
+// editor, display a diff, and after confirmation upload the file back.
+// We refuse to upload if the remote file was modified in the meantime.
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+type SResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+pub struct RemoteFile<'a> {
+    pub ssh_target: &'a str,
+    pub port: Option<&'a str>,
+    pub path: &'a str,
+}
+
+impl<'a> RemoteFile<'a> {
+    pub fn ssh(&self, force_tty: bool) -> Command {
+        let mut cmd = Command::new("ssh");
+        if let Some(port) = self.port {
+            cmd.args(&["-p", port]);
+        }
+        if force_tty {
+            // for instance so that sudo can prompt for the password
+            cmd.arg("-t");
+        }
+        cmd.arg(self.ssh_target);
+        cmd
+    }
+
+    fn scp(&self) -> Command {
+        let mut cmd = Command::new("scp");
+        cmd.args(scp_args(self.port));
+        cmd
+    }
+
+    fn scp_target(&self, remote_path: &str) -> String {
+        scp_location(self.ssh_target, remote_path)
+    }
+
+    /// a remote temporary file, readable only by the ssh user
+    fn make_remote_tmp(&self) -> SResult<String> {
+        let tmp_output = self.ssh(false).arg("mktemp").output()?;
+        if !tmp_output.status.success() {
+            return Err(format!(
+                "failed creating a remote temporary file: {}",
+                tmp_output.status
+            )
+            .into());
+        }
+        Ok(String::from_utf8(tmp_output.stdout)?.trim().to_string())
+    }
+
+    fn read_contents(&self, sudo: bool) -> SResult<Vec<u8>> {
+        if sudo {
+            return self.sudo_read_contents();
+        }
+        let output = self
+            .ssh(false)
+            .arg(format!("cat -- {}", shell_words::quote(self.path)))
+            .stderr(Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            return Err(format!("failed reading {}: {}", self.path, output.status).into());
+        }
+        Ok(output.stdout)
+    }
+
+    // sudo may need the terminal to prompt for the password, so it can't
+    // write the contents to our stdout: it writes them to a remote
+    // temporary file which we then download.
+    fn sudo_read_contents(&self) -> SResult<Vec<u8>> {
+        let remote_tmp = self.make_remote_tmp()?;
+        let contents = self.sudo_download(&remote_tmp);
+        // the temporary file is a copy of a file which may be readable only with sudo
+        let rm_status = self
+            .ssh(false)
+            .arg(format!("rm -f -- {}", shell_words::quote(&remote_tmp)))
+            .status()?;
+        let contents = contents?;
+        if !rm_status.success() {
+            return Err(format!(
+                "failed removing the remote temporary file {}: {}",
+                remote_tmp, rm_status
+            )
+            .into());
+        }
+        Ok(contents)
+    }
+
+    fn sudo_download(&self, remote_tmp: &str) -> SResult<Vec<u8>> {
+        let status = self
+            .ssh(true)
+            .arg(sudo_read_command(self.path, remote_tmp))
+            .status()?;
+        if !status.success() {
+            return Err(format!("failed reading {}: {}", self.path, status).into());
+        }
+        let local = tempfile::NamedTempFile::new()?;
+        let scp_status = self
+            .scp()
+            .arg(self.scp_target(remote_tmp))
+            .arg(local.path())
+            .status()?;
+        if !scp_status.success() {
+            return Err(format!("failed downloading {}: {}", self.path, scp_status).into());
+        }
+        Ok(fs::read(local.path())?)
+    }
+
+    fn upload(&self, local: &Path, sudo: bool, backup: bool) -> SResult<()> {
+        let remote_tmp = self.make_remote_tmp()?;
+        let scp_status = self
+            .scp()
+            .arg(local)
+            .arg(self.scp_target(&remote_tmp))
+            .status()?;
+        if !scp_status.success() {
+            return Err(format!("failed uploading the file: {}", scp_status).into());
+        }
+        let status = self
+            .ssh(sudo)
+            .arg(install_command(self.path, &remote_tmp, sudo, backup))
+            .status()?;
+        if !status.success() {
+            return Err(format!("failed writing {}: {}", self.path, status).into());
+        }
+        Ok(())
+    }
+}
+
+/// `user@host:path` for scp. IPv6 addresses must be between brackets there,
+/// unlike for ssh, which gets the host as a separate argument.
+pub fn scp_location(ssh_target: &str, path: &str) -> String {
+    let (user, host) = match ssh_target.rfind('@') {
+        Some(idx) => ssh_target.split_at(idx + 1),
+        None => ("", ssh_target),
+    };
+    if host.contains(':') && !host.starts_with('[') {
+        format!("{}[{}]:{}", user, host, path)
+    } else {
+        format!("{}{}:{}", user, host, path)
+    }
+}
+
+fn scp_args(port: Option<&str>) -> Vec<&str> {
+    let mut args = vec!["-q"];
+    if let Some(port) = port {
+        args.extend(&["-P", port]);
+    }
+    args
+}
+
+/// the shell command copying the file to the temporary file, with sudo.
+/// The redirection is done by the shell of the ssh user, which owns the
+/// temporary file.
+fn sudo_read_command(path: &str, remote_tmp: &str) -> String {
+    format!(
+        "sudo cat -- {} > {}",
+        shell_words::quote(path),
+        shell_words::quote(remote_tmp)
+    )
+}
+
+/// the shell command moving the uploaded temporary file over the remote file
+fn install_command(path: &str, remote_tmp: &str, sudo: bool, backup: bool) -> String {
+    // cp over an existing file preserves its owner and permissions
+    let sudo_prefix = if sudo { "sudo " } else { "" };
+    let path = shell_words::quote(path);
+    let tmp = shell_words::quote(remote_tmp);
+    let backup_cmd = if backup {
+        format!("{}cp -p -- {} {}.bak && ", sudo_prefix, path, path)
+    } else {
+        "".to_string()
+    };
+    format!(
+        "{}{}cp -- {} {}; res=$?; rm -f -- {}; exit $res",
+        backup_cmd, sudo_prefix, tmp, path, tmp
+    )
+}
+
+fn sha256(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+fn run_editor(path: &Path) -> SResult<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vim".to_string());
+    // $EDITOR may contain parameters, for instance "code --wait"
+    let editor_elts = shell_words::split(&editor)?;
+    let (editor_bin, editor_args) = editor_elts
+        .split_first()
+        .ok_or("the editor command is empty")?;
+    let status = Command::new(editor_bin)
+        .args(editor_args)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(format!("the editor exited with {}", status).into());
+    }
+    Ok(())
+}
+
+fn display_diff(remote: &RemoteFile, original: &Path, edited: &Path) -> SResult<()> {
+    let remote_desc = scp_location(remote.ssh_target, remote.path);
+    Command::new("diff")
+        .args(&["-u", "--label", &remote_desc, "--label", "edited"])
+        .arg(original)
+        .arg(edited)
+        .status()?;
+    Ok(())
+}
+
+pub fn confirm(question: &str) -> SResult<bool> {
+    print!("{} y/n ", question);
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let input_trimmed = input.trim();
+    Ok(input_trimmed == "y" || input_trimmed == "Y")
+}
+
+/// write the contents to a new file in the folder, readable only by the user.
+/// Existing files are not overwritten: file.conf, then file-1.conf, file-2.conf...
+fn save_rescue_copy(dir: &Path, fname: &Path, contents: &[u8]) -> SResult<PathBuf> {
+    let stem = fname.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = fname
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    for counter in 0.. {
+        let path = if counter == 0 {
+            dir.join(fname)
+        } else {
+            dir.join(format!("{}-{}{}", stem, counter, extension))
+        };
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(contents)?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+pub fn edit_remote_file(remote: &RemoteFile, sudo: bool, backup: bool) -> SResult<()> {
+    let original = remote.read_contents(sudo)?;
+    let original_hash = sha256(&original);
+
+    // tempdir creates the folder readable only by the current user
+    let dir = tempfile::Builder::new().prefix("ppcli-edit").tempdir()?;
+    let fname = Path::new(remote.path)
+        .file_name()
+        .ok_or_else(|| format!("invalid remote file name: {}", remote.path))?;
+    let original_path = dir.path().join("original");
+    let edited_path = dir.path().join(fname);
+    fs::write(&original_path, &original)?;
+    fs::write(&edited_path, &original)?;
+
+    run_editor(&edited_path)?;
+    let edited = fs::read(&edited_path)?;
+    if edited == original {
+        println!("No changes, not uploading.");
+        return Ok(());
+    }
+    display_diff(remote, &original_path, &edited_path)?;
+    // keep the edits somewhere when not uploading them,
+    // the temporary folder is deleted on return
+    let save_edits = || {
+        save_rescue_copy(
+            &dirs::download_dir().unwrap_or_else(std::env::temp_dir),
+            Path::new(fname),
+            &edited,
+        )
+    };
+    if !confirm(&format!("Upload the changes to {}?", remote.path))? {
+        println!(
+            "Not uploading. Your version was saved to {}",
+            save_edits()?.display()
+        );
+        return Ok(());
+    }
+
+    // a change on the server between this check and the end of the upload
+    // would still be overwritten: scp can't upload only if the file is
+    // unchanged. The check catches the changes made while editing and
+    // reviewing the diff, the window after it is short.
+    if sha256(&remote.read_contents(sudo)?) != original_hash {
+        return Err(format!(
+            "{} was modified on the server while you were editing it, not uploading. Your version was saved to {}",
+            remote.path,
+            save_edits()?.display()
+        )
+        .into());
+    }
+    remote.upload(&edited_path, sudo, backup)?;
+    println!("Uploaded {}.", remote.path);
+    Ok(())
+}
+
+#[test]
+fn remote_commands() {
+    assert_eq!(vec!["-q"], scp_args(None));
+    assert_eq!(vec!["-q", "-P", "2222"], scp_args(Some("2222")));
+    assert_eq!(
+        "sudo cat -- '/etc/my app.conf' > /tmp/tmp.x1",
+        sudo_read_command("/etc/my app.conf", "/tmp/tmp.x1")
+    );
+    assert_eq!(
+        "cp -- /tmp/tmp.x1 /home/me/app.conf; res=$?; rm -f -- /tmp/tmp.x1; exit $res",
+        install_command("/home/me/app.conf", "/tmp/tmp.x1", false, false)
+    );
+    assert_eq!(
+        "sudo cp -p -- '/etc/my app.conf' '/etc/my app.conf'.bak && sudo cp -- /tmp/tmp.x1 '/etc/my app.conf'; res=$?; rm -f -- /tmp/tmp.x1; exit $res",
+        install_command("/etc/my app.conf", "/tmp/tmp.x1", true, true)
+    );
+}
+
+#[test]
+fn scp_locations() {
+    assert_eq!(
+        "root@10.0.0.1:/etc/app.conf",
+        scp_location("root@10.0.0.1", "/etc/app.conf")
+    );
+    assert_eq!("db:/etc/app.conf", scp_location("db", "/etc/app.conf"));
+    assert_eq!(
+        "root@[fe80::1]:/etc/app.conf",
+        scp_location("root@fe80::1", "/etc/app.conf")
+    );
+    assert_eq!("[::1]:/tmp/x", scp_location("::1", "/tmp/x"));
+    assert_eq!("[::1]:/tmp/x", scp_location("[::1]", "/tmp/x"));
+}
+
+#[test]
+fn rescue_copy_does_not_overwrite() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let fname = Path::new("app.conf");
+    fs::write(dir.path().join(fname), "mine").unwrap();
+    let first = save_rescue_copy(dir.path(), fname, b"edited").unwrap();
+    let second = save_rescue_copy(dir.path(), fname, b"edited again").unwrap();
+    assert_eq!(dir.path().join("app-1.conf"), first);
+    assert_eq!(dir.path().join("app-2.conf"), second);
+    assert_eq!("mine", fs::read_to_string(dir.path().join(fname)).unwrap());
+    assert_eq!("edited again", fs::read_to_string(&second).unwrap());
+    assert_eq!(
+        0o600,
+        fs::metadata(&first).unwrap().permissions().mode() & 0o777
+    );
+}
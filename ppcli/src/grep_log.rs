@@ -0,0 +1,161 @@
+// "grep log": search a remote log file and its rotated siblings
+// (file.1, file.2.gz...), or follow it with tail -F. The matches are
+// highlighted by the remote grep.
+use crate::edit_remote::{confirm, RemoteFile};
+use std::io::Write;
+use std::path::Path;
+
+type SResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+fn prompt(question: &str) -> SResult<String> {
+    print!("{} ", question);
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// parses time windows like 30m, 12h or 7d, into minutes.
+/// None if the window is invalid or too large
+fn parse_time_window(window: &str) -> Option<u32> {
+    let unit = window.chars().last()?;
+    let count = window[..window.len() - unit.len_utf8()]
+        .parse::<u32>()
+        .ok()?;
+    match unit {
+        'm' => Some(count),
+        'h' => count.checked_mul(60),
+        'd' => count.checked_mul(60 * 24),
+        _ => None,
+    }
+}
+
+fn follow_command(path: &str, pattern: &str) -> String {
+    format!(
+        "tail -F -- {} | grep --line-buffered --color=always -e {}",
+        shell_words::quote(path),
+        shell_words::quote(pattern)
+    )
+}
+
+/// the log file and its rotated siblings, oldest first, restricted to the
+/// files modified in the last `minutes` if given. The time window applies
+/// to whole files, not to the lines: the format of the timestamps differs
+/// from a log to the next, so an old line of a recently modified file
+/// still matches. The files are sorted by modification time in one go:
+/// `find -exec ls -tr {} +` would sort each batch of files separately.
+/// zgrep handles both compressed and plain files.
+fn search_command(path: &str, pattern: &str, minutes: Option<u32>) -> SResult<String> {
+    let log_path = Path::new(path);
+    let dir = log_path
+        .parent()
+        .and_then(|p| p.to_str())
+        .filter(|p| !p.is_empty())
+        .unwrap_or(".");
+    let fname = log_path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or_else(|| format!("invalid log file name: {}", path))?;
+    Ok(format!(
+        "cd {} && find . -maxdepth 1 -type f \\( -name {} -o -name {} \\){} -printf '%T@ %p\\n' \
+         | sort -n | cut -d' ' -f2- | xargs -r -d '\\n' zgrep -H --color=always -e {}",
+        shell_words::quote(dir),
+        shell_words::quote(fname),
+        shell_words::quote(&format!("{}.*", fname)),
+        minutes
+            .map(|m| format!(" -mmin -{}", m))
+            .unwrap_or_else(|| "".to_string()),
+        shell_words::quote(pattern)
+    ))
+}
+
+pub fn grep_log(remote: &RemoteFile) -> SResult<()> {
+    let pattern = prompt("Pattern:")?;
+    if pattern.is_empty() {
+        return Err("no pattern given".into());
+    }
+    let follow = confirm("Follow the log?")?;
+    let remote_cmd = if follow {
+        follow_command(remote.path, &pattern)
+    } else {
+        let window =
+            prompt("Only files modified in the last (for instance 30m, 12h, 7d, empty for all):")?;
+        let minutes = if window.is_empty() {
+            None
+        } else {
+            Some(
+                parse_time_window(&window)
+                    .ok_or_else(|| format!("invalid time window: {}", window))?,
+            )
+        };
+        search_command(remote.path, &pattern, minutes)?
+    };
+    // force a tty so that ctrl-c stops the remote tail
+    let status = remote.ssh(true).arg(remote_cmd).status()?;
+    // grep exits with 1 if there was no match, and then xargs with 123.
+    // When following, the user stops the search with ctrl-c.
+    match status.code() {
+        _ if follow => {}
+        Some(1) | Some(123) => println!("No match."),
+        Some(0) => {}
+        _ => return Err(format!("the search failed: {}", status).into()),
+    }
+    Ok(())
+}
+
+#[test]
+fn parses_time_windows() {
+    assert_eq!(Some(30), parse_time_window("30m"));
+    assert_eq!(Some(12 * 60), parse_time_window("12h"));
+    assert_eq!(Some(7 * 24 * 60), parse_time_window("7d"));
+    assert_eq!(None, parse_time_window("7"));
+    assert_eq!(None, parse_time_window("d"));
+    assert_eq!(None, parse_time_window(""));
+    assert_eq!(None, parse_time_window("7é"));
+    assert_eq!(None, parse_time_window("9999999d"));
+    assert_eq!(None, parse_time_window("99999999h"));
+}
+
+#[test]
+fn builds_search_command() {
+    assert_eq!(
+        "cd /var/log/app && find . -maxdepth 1 -type f \\( -name app.log -o -name 'app.log.*' \\) -mmin -60 \
+         -printf '%T@ %p\\n' | sort -n | cut -d' ' -f2- | xargs -r -d '\\n' zgrep -H --color=always -e 'connection refused'",
+        search_command("/var/log/app/app.log", "connection refused", Some(60)).unwrap()
+    );
+}
+
+#[test]
+fn searches_the_oldest_files_first() {
+    let dir = tempfile::tempdir().unwrap();
+    // the order of the names is not the order of the modification times
+    for (name, age_hours) in &[
+        ("app.log", 0),
+        ("app.log.1", 1),
+        ("app.log.10", 10),
+        ("app.log.2", 2),
+    ] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, format!("m{} line\n", age_hours)).unwrap();
+        let status = std::process::Command::new("touch")
+            .arg("-d")
+            .arg(format!("@{}", 1_600_000_000 - age_hours * 3600))
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    let log_path = dir.path().join("app.log");
+    let output = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(search_command(log_path.to_str().unwrap(), "line", None).unwrap())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let positions: Vec<_> = ["m10 ", "m2 ", "m1 ", "m0 "]
+        .iter()
+        .map(|m| stdout.find(m))
+        .collect();
+    assert!(positions.iter().all(|p| p.is_some()), "{}", stdout);
+    assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", stdout);
+}
@@ -1,3 +1,6 @@
+#[macro_use]
+extern crate diesel;
+
 use crate::database::ExecutedAction;
 #[cfg(test)]
 use crate::database::{ActionType, LinkedItem};
@@ -16,17 +19,23 @@ use std::sync::mpsc;
 use structopt::clap::arg_enum;
 use structopt::StructOpt;
 mod actions;
+mod audit;
 mod autoupgrade;
 pub mod config;
 mod database;
+mod db_commands;
+mod edit_remote;
+mod grep_log;
 #[cfg_attr(target_os = "linux", path = "secretservice_linux.rs")]
 #[cfg_attr(not(target_os = "linux"), path = "secretservice_generic.rs")]
 mod secretservice;
+mod sessions;
+mod shell_integration;
 
 const ZSH_FUNCTION: &str = include_str!("../shell/integration.zsh");
 
+// the newest supported version is projectpadsql::migrations::SCHEMA_VERSION
 const MIN_SUPPORTED_DB_SCHEMA_VERSION: i32 = 21;
-const MAX_SUPPORTED_DB_SCHEMA_VERSION: i32 = 22;
 
 #[derive(StructOpt)]
 #[structopt(version = env!("CARGO_PKG_VERSION"))]
@@ -34,17 +43,144 @@ struct Options {
     /// Upgrade ppcli
     #[structopt(long)]
     upgrade: bool,
+    /// Upgrade from a release tarball on disk instead of downloading it
+    #[structopt(long, requires = "upgrade", parse(from_os_str))]
+    from_file: Option<PathBuf>,
+    /// The sha256 checksum of the tarball given with --from-file, if there is no <tarball>.sha256 file next to it
+    #[structopt(long, requires = "from-file")]
+    sha256: Option<String>,
+    /// Restore the ppcli binary that was replaced by the last upgrade
+    #[structopt(long, conflicts_with = "upgrade")]
+    rollback: bool,
     /// Disable color display
     #[structopt(long="no-color", parse(from_flag = display_from_no_color))]
     display_mode: DisplayMode,
     /// Disable the new version check
     #[structopt(long = "no-upgrade-check", parse(from_flag = std::ops::Not::not))]
     upgrade_check: bool,
-    #[structopt(long = "shell-integration", hidden = true)]
-    shell_integration_mode: bool,
+    /// Only display the items with this tag, or on a server with this tag. Can be repeated
+    #[structopt(long = "tag", number_of_values = 1)]
+    tags: Vec<String>,
+    /// --shell-integration alone means the legacy protocol
+    #[structopt(long = "shell-integration", hidden = true,
+                possible_values = &shell_integration::Protocol::variants(), case_insensitive = true)]
+    shell_integration_mode: Option<Option<shell_integration::Protocol>>,
     /// Print to stdout the function for a given shell
     #[structopt(long, default_value = "none")]
     print_shell_function: Shell,
+    /// Record the commands run by ppcli, to review them later with `ppcli sessions`
+    #[structopt(long)]
+    record: bool,
+    /// When the sessions storage gets created, encrypt it with the database password
+    #[structopt(long)]
+    encrypt_sessions: bool,
+    /// The database profile, instead of the one from the PROJECTPAD_PROFILE
+    /// environment variable, or the default profile
+    #[structopt(long)]
+    profile: Option<String>,
+    #[structopt(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(StructOpt)]
+enum DbCommand {
+    /// Create a new database, and store its password in the OS keyring
+    Init,
+    /// Upgrade the database to the latest schema version
+    Migrate,
+    /// Encrypt the passwords with a master passphrase, on top of the database password
+    ProtectPasswords,
+    /// Remove the master passphrase, storing the passwords in plain text again
+    UnprotectPasswords,
+    /// Copy the database to a file, encrypted with the database password.
+    /// Safe while projectpad is running
+    Backup {
+        /// The file to create
+        #[structopt(parse(from_os_str))]
+        dest: PathBuf,
+    },
+    /// Convert the database to the sqlcipher 4 format, which is encrypted with stronger
+    /// settings. Requires ppcli and projectpad built with sqlcipher 4. Close projectpad first
+    UpgradeCipher,
+    /// Go back to the database from before `db upgrade-cipher`. The changes made since are lost
+    RollbackCipher,
+    /// Replace the database with a backup, after checking the backup.
+    /// The daily backups of the GUI app are in the backups folder, next to the database
+    Restore {
+        /// The backup file
+        #[structopt(parse(from_os_str))]
+        backup: PathBuf,
+    },
+    /// Sync the database with the other devices of the team, through a shared folder
+    /// or a clone of a git repository. The passphrase of the team is asked for
+    SyncSetup {
+        /// The name of this device, as the other devices show it
+        #[structopt(long)]
+        device_name: String,
+        /// The location is the work tree of a git clone, which is pulled and pushed
+        #[structopt(long)]
+        git: bool,
+        /// The shared folder, or the work tree of the git clone
+        #[structopt(parse(from_os_str))]
+        location: PathBuf,
+    },
+    /// Share the local changes with the other devices, and apply theirs.
+    /// Conflicting edits are resolved in projectpad
+    Sync,
+    /// Look for dangling references between the items, ssh tunnel loops, duplicate
+    /// server descriptions, empty group names and unreadable authentication keys.
+    /// Exits with code 1 if there are issues left
+    Check {
+        /// Apply the fix suggested for each issue
+        #[structopt(long)]
+        fix: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum AuditCommand {
+    /// List the passwords overdue for rotation as per the policies of their projects.
+    /// Exits with code 1 if there are any
+    Passwords,
+}
+
+#[derive(StructOpt)]
+enum SubCommand {
+    /// List the recorded sessions, or replay one of them
+    Sessions {
+        /// The id of the session to replay
+        replay_id: Option<i32>,
+    },
+    /// Edit a remote file with the local editor, through ssh
+    EditRemote {
+        /// The ssh port
+        #[structopt(short, long)]
+        port: Option<String>,
+        /// Use sudo to read and overwrite the remote file
+        #[structopt(long)]
+        sudo: bool,
+        /// Keep a copy of the original remote file, with a .bak extension
+        #[structopt(long)]
+        backup: bool,
+        /// The server to connect to, for instance user@host
+        ssh_target: String,
+        /// The path of the file on the server
+        path: String,
+    },
+    /// Database maintenance
+    Db(DbCommand),
+    /// Check the database against the policies of the projects
+    Audit(AuditCommand),
+    /// Search a remote log file and its rotated versions, through ssh
+    GrepLog {
+        /// The ssh port
+        #[structopt(short, long)]
+        port: Option<String>,
+        /// The server to connect to, for instance user@host
+        ssh_target: String,
+        /// The path of the log file on the server
+        path: String,
+    },
 }
 
 arg_enum! {
@@ -124,8 +260,26 @@ enum UpgradeAvailableData {
 
 pub fn main() {
     let flag_options = Options::from_args();
+    if let Some(ref profile) = flag_options.profile {
+        ok_or_exit!(
+            projectpadsql::profile::check_name(profile),
+            "Error selecting the profile: {}",
+            1
+        );
+        // through the environment, so that the commands that we run
+        // (the shell integration calls us back) use the same profile
+        env::set_var(projectpadsql::profile::PROFILE_ENV_VAR, profile);
+    }
+    if flag_options.rollback {
+        ok_or_exit!(autoupgrade::rollback(), "Error rolling back the upgrade: {}", 1);
+        std::process::exit(0);
+    }
     if flag_options.upgrade {
-        match autoupgrade::try_upgrade() {
+        let res = match flag_options.from_file {
+            Some(ref path) => autoupgrade::upgrade_from_file(path, flag_options.sha256.clone()),
+            None => autoupgrade::try_upgrade(),
+        };
+        match res {
             Ok(()) => {
                 // don't bug the user about this upgrade for
                 // some time now (even if the user rejected the upgrade)
@@ -138,16 +292,102 @@ pub fn main() {
         }
         std::process::exit(0);
     }
+    if let Some(SubCommand::EditRemote {
+        ref port,
+        sudo,
+        backup,
+        ref ssh_target,
+        ref path,
+    }) = flag_options.cmd
+    {
+        let remote = edit_remote::RemoteFile {
+            ssh_target,
+            port: port.as_deref(),
+            path,
+        };
+        ok_or_exit!(
+            edit_remote::edit_remote_file(&remote, sudo, backup),
+            "Error editing the remote file: {}",
+            7
+        );
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::GrepLog {
+        ref port,
+        ref ssh_target,
+        ref path,
+    }) = flag_options.cmd
+    {
+        let remote = edit_remote::RemoteFile {
+            ssh_target,
+            port: port.as_deref(),
+            path,
+        };
+        ok_or_exit!(grep_log::grep_log(&remote), "Error searching the log: {}", 7);
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::Db(DbCommand::Init)) = flag_options.cmd {
+        ok_or_exit!(db_commands::init_db(), "Error creating the database: {}", 3);
+        std::process::exit(0);
+    }
     if flag_options.print_shell_function == Shell::Zsh {
         println!("\n{}", ZSH_FUNCTION);
         std::process::exit(0);
     }
     let db_pass = ok_or_exit!(
         secretservice::get_keyring_pass().and_then(|r| r.ok_or_else(|| "no matching credentials".into())),
-        "Cannot find the database password in the OS keyring, aborting: did you run the projectpad GUI app to create a database first? {}",
+        "Cannot find the database password in the OS keyring, aborting: did you create a database first, with the projectpad GUI app or `ppcli db init`? {}",
         1
     );
 
+    if let Some(SubCommand::Db(DbCommand::Restore { ref backup })) = flag_options.cmd {
+        // before opening the database, which may be damaged
+        ok_or_exit!(
+            db_commands::restore_db(&db_pass, backup),
+            "Error restoring the database: {}",
+            5
+        );
+        std::process::exit(0);
+    }
+
+    if let Some(SubCommand::Db(DbCommand::UpgradeCipher)) = flag_options.cmd {
+        ok_or_exit!(
+            db_commands::upgrade_cipher(&db_pass),
+            "Error converting the database: {}",
+            5
+        );
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::Db(DbCommand::RollbackCipher)) = flag_options.cmd {
+        ok_or_exit!(
+            db_commands::rollback_cipher(),
+            "Error rolling back the database: {}",
+            5
+        );
+        std::process::exit(0);
+    }
+
+    if let Some(SubCommand::Sessions { replay_id }) = flag_options.cmd {
+        if !sessions::sessions_db_path().is_file() {
+            println!("No recorded sessions. Run ppcli with --record to record sessions.");
+            std::process::exit(0);
+        }
+        let sessions_conn = ok_or_exit!(
+            sessions::open_sessions_db(&db_pass, flag_options.encrypt_sessions),
+            "Cannot open the sessions database, aborting. {}",
+            6
+        );
+        ok_or_exit!(
+            match replay_id {
+                Some(id) => sessions::replay_session(&sessions_conn, id),
+                None => sessions::list_sessions(&sessions_conn),
+            },
+            "Error reading the sessions: {}",
+            6
+        );
+        std::process::exit(0);
+    }
+
     let db_path_raw = projectpadsql::database_path();
     let db_path = some_or_exit!(
         db_path_raw.to_str(),
@@ -162,16 +402,85 @@ pub fn main() {
     );
 
     ok_or_exit!(
-        projectpadsql::try_unlock_db(&conn, &db_pass),
+        projectpadsql::try_unlock_db_file(&conn, &db_pass, &db_path_raw),
         "Failed unlocking the database with the password, aborting. {}",
         4
     );
 
+    if let Some(SubCommand::Db(DbCommand::Migrate)) = flag_options.cmd {
+        ok_or_exit!(db_commands::migrate_db(&conn), "Error migrating the database: {}", 5);
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::Db(DbCommand::Backup { ref dest })) = flag_options.cmd {
+        ok_or_exit!(
+            db_commands::backup_db(&conn, dest),
+            "Error backing up the database: {}",
+            5
+        );
+        std::process::exit(0);
+    }
+
     ok_or_exit!(
         check_db_version(&conn),
         "{} https://github.com/emmanueltouzery/projectpad2",
         5
     );
+    // like the GUI, once the schema is up to date: the migrations run without them
+    ok_or_exit!(
+        conn.execute("PRAGMA foreign_keys = ON"),
+        "Error enabling the foreign keys: {}",
+        5
+    );
+
+    if let Some(SubCommand::Db(DbCommand::ProtectPasswords)) = flag_options.cmd {
+        ok_or_exit!(
+            db_commands::protect_passwords(&conn),
+            "Error protecting the passwords: {}",
+            5
+        );
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::Db(DbCommand::UnprotectPasswords)) = flag_options.cmd {
+        ok_or_exit!(
+            db_commands::unprotect_passwords(&conn),
+            "Error removing the master passphrase: {}",
+            5
+        );
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::Db(DbCommand::SyncSetup {
+        ref device_name,
+        git,
+        ref location,
+    })) = flag_options.cmd
+    {
+        ok_or_exit!(
+            db_commands::sync_setup(&conn, device_name, git, location),
+            "Error setting up the sync: {}",
+            5
+        );
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::Db(DbCommand::Sync)) = flag_options.cmd {
+        ok_or_exit!(db_commands::sync_db(&conn), "Error syncing: {}", 5);
+        std::process::exit(0);
+    }
+    if let Some(SubCommand::Db(DbCommand::Check { fix })) = flag_options.cmd {
+        let issue_count = ok_or_exit!(
+            db_commands::check_db(&conn, fix),
+            "Error checking the database: {}",
+            5
+        );
+        std::process::exit(if issue_count > 0 { 1 } else { 0 });
+    }
+    if let Some(SubCommand::Audit(AuditCommand::Passwords)) = flag_options.cmd {
+        let overdue_count = ok_or_exit!(
+            audit::audit_passwords(&conn),
+            "Error auditing the passwords: {}",
+            5
+        );
+        std::process::exit(if overdue_count > 0 { 1 } else { 0 });
+    }
 
     // start a thread to, if we didn't check for 7 days, check whether there is
     // a new version of ppcli available (in a thread not to block the GUI).
@@ -226,7 +535,17 @@ pub fn main() {
 
     let display_mode = flag_options.display_mode;
     let ranked_items = get_ranked_items(&history_executed_actions);
-    std::thread::spawn(move || database::load_items(&conn, display_mode, &tx_item, &ranked_items));
+    let tags = flag_options.tags.clone();
+    // get the connection back once the items are loaded, the shell
+    // integration may need it after the user made the selection
+    let items_loader = std::thread::spawn(move || {
+        ok_or_exit!(
+            database::load_items(&conn, display_mode, &tags, &tx_item, &ranked_items),
+            "Error loading the items: {}",
+            1
+        );
+        conn
+    });
 
     let (selected_items, query, accept_key) = Skim::run_with(&options, Some(rx_item))
         .map(|out| (out.selected_items, out.query, out.final_key))
@@ -250,39 +569,81 @@ pub fn main() {
 
         let action = &myitem.inner;
         let action_str = &(action.get_string)(&action.item);
-        let upgrade_url = if flag_options.shell_integration_mode {
-            // in shell integration mode, we check for upgrades before handling
-            // the command, because we just print out the command, the shell
-            // will execute it.
-            handle_upgrade_info_and_get_download_url(&has_upgrade_rx)
-                .map(Cow::Owned)
-                .unwrap_or(Cow::Borrowed(""))
-        } else {
-            Cow::Borrowed("")
+        let shell_integration = flag_options
+            .shell_integration_mode
+            .map(|p| p.unwrap_or(shell_integration::Protocol::Legacy));
+        // in shell integration mode, we check for upgrades before handling
+        // the command, because we just print out the command, the shell
+        // will execute it.
+        let shell_output = |shell_action, working_dir| {
+            let conn = items_loader.join().unwrap();
+            shell_integration::print_output(
+                shell_integration.unwrap(),
+                &conn,
+                shell_action,
+                action,
+                action_str,
+                working_dir,
+                handle_upgrade_info_and_get_download_url(&has_upgrade_rx),
+            )
         };
         match accept_key {
-            Key::Ctrl('y') if flag_options.shell_integration_mode => println!("C\x00{}\x00\x00{}", action_str, upgrade_url),
+            // whatever the key, we can only copy the code. We copy it ourselves
+            // also in shell integration mode: the shell doesn't need to see it
+            _ if action.desc == ActionType::CopyOtp => {
+                let conn = items_loader.join().unwrap();
+                let server_id = action.item.server_info.as_ref().unwrap().server_id;
+                match database::get_server_otp_code(&conn, server_id) {
+                    Ok(code) => {
+                        copy_command_to_clipboard(&code);
+                        eprintln!("One-time code copied to the clipboard");
+                    }
+                    Err(e) => eprintln!("Error computing the one-time code: {}", e),
+                }
+            }
+            // the files are written in the current folder, the shell has nothing to do
+            _ if action.desc == ActionType::SaveAttachments => {
+                let conn = items_loader.join().unwrap();
+                let server_id = action.item.server_info.as_ref().unwrap().server_id;
+                let (saved, result) = database::save_server_attachments(&conn, server_id);
+                for name in saved {
+                    eprintln!("Saved {}", name);
+                }
+                if let Err(e) = result {
+                    eprintln!("Error saving the attachments: {}", e);
+                }
+            }
+            Key::Ctrl('y') if shell_integration.is_some() => shell_output(shell_integration::ShellAction::Copy, None),
             Key::Ctrl('y') => copy_command_to_clipboard(action_str),
-            Key::AltEnter if flag_options.shell_integration_mode => println!("P\x00{}\x00\x00{}", action_str, upgrade_url),
+            Key::AltEnter if shell_integration.is_some() => shell_output(shell_integration::ShellAction::Paste, None),
             Key::AltEnter =>
             // copy to command-line if run is not allowed for that action
                     // if !val_action.allowed_actions.contains(&AllowedAction::Run) =>
             {
                 write_command_line_to_terminal(action_str)
             }
-            Key::Enter if flag_options.shell_integration_mode => println!(
-                "R\x00{}\x00{}\x00{}", action_str, &run_command_folder(&action)
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "".to_string()), upgrade_url),
-            Key::Enter => run_command(
-                action_str,
-                &run_command_folder(&action)
-                    .unwrap_or_else(|| dirs::home_dir().unwrap()),
-            ),
+            Key::Enter if shell_integration.is_some() => {
+                shell_output(shell_integration::ShellAction::Run, run_command_folder(&action))
+            }
+            Key::Enter => {
+                let sessions_conn = if flag_options.record {
+                    sessions::open_sessions_db(&db_pass, flag_options.encrypt_sessions)
+                        .map_err(|e| eprintln!("Cannot open the sessions database, not recording: {}", e))
+                        .ok()
+                } else {
+                    None
+                };
+                run_command(
+                    action_str,
+                    &run_command_folder(&action)
+                        .unwrap_or_else(|| dirs::home_dir().unwrap()),
+                    sessions_conn.as_ref().map(|c| (c, action)),
+                )
+            }
             _ => {}
         }
     }
-    if !flag_options.shell_integration_mode {
+    if flag_options.shell_integration_mode.is_none() {
         if let Some(download_url) = handle_upgrade_info_and_get_download_url(&has_upgrade_rx) {
             if let Ok(()) = autoupgrade::apply_upgrade(&download_url) {
                 let _ = config::upgrade_check_mark_done();
@@ -332,21 +693,29 @@ fn run_command_folder(action: &actions::Action) -> Option<PathBuf> {
 }
 
 fn check_db_version(conn: &SqliteConnection) -> Result<(), Box<dyn std::error::Error>> {
-    let version = projectpadsql::get_db_version(conn)?;
-    if version < MIN_SUPPORTED_DB_SCHEMA_VERSION {
-        return Err(format!("The database version ({}), is older than the oldest version supported by this application. Please upgrade the main projectpad application.", version).into());
-    }
-    if version > MAX_SUPPORTED_DB_SCHEMA_VERSION {
-        println!("The database version ({}), is newer than the newest version supported by this application. Please upgrade this CLI application.", version);
-        if let Err(e) = autoupgrade::try_upgrade() {
-            eprintln!("Error in auto-upgrade: {}", e);
+    match projectpadsql::migrations::check_schema_version(conn, MIN_SUPPORTED_DB_SCHEMA_VERSION) {
+        Err(e @ projectpadsql::Error::SchemaTooOld { .. }) => Err(format!(
+            "{}. Please run `ppcli db migrate`, or upgrade the main projectpad application.",
+            e
+        )
+        .into()),
+        Err(e @ projectpadsql::Error::SchemaTooNew { .. }) => {
+            println!("{}. Please upgrade this CLI application.", e);
+            if let Err(e) = autoupgrade::try_upgrade() {
+                eprintln!("Error in auto-upgrade: {}", e);
+            }
+            std::process::exit(1);
         }
-        std::process::exit(1);
+        Err(e) => Err(e.into()),
+        Ok(_) => Ok(()),
     }
-    Ok(())
 }
 
-fn run_command(command_line: &str, cur_dir: &Path) {
+fn run_command(
+    command_line: &str,
+    cur_dir: &Path,
+    recording: Option<(&SqliteConnection, &actions::Action)>,
+) {
     let cl_elts = shell_words::split(command_line).unwrap_or_else(|e| {
         println!("Couldn't parse the command: {}: {}", command_line, e);
         Vec::new()
@@ -361,15 +730,34 @@ fn run_command(command_line: &str, cur_dir: &Path) {
         } else {
             Cow::Borrowed(cur_dir)
         };
-        println!("Running {} in folder {:?}...", command_line, actual_dir);
-        Command::new(cl_elts[0].clone())
-            .args(cl_elts.iter().skip(1))
-            .current_dir::<&Path>(actual_dir.borrow())
-            .status()
-            .map(|_| ())
-            .unwrap_or_else(|e| {
-                println!("Error launching process: {}", e);
-            });
+        println!(
+            "Running {} in folder {:?}{}...",
+            command_line,
+            actual_dir,
+            if recording.is_some() {
+                " (recording the session)"
+            } else {
+                ""
+            }
+        );
+        match recording {
+            Some((sessions_conn, action)) => sessions::run_recorded(
+                sessions_conn,
+                action,
+                command_line,
+                &cl_elts,
+                actual_dir.borrow(),
+            ),
+            None => Command::new(cl_elts[0].clone())
+                .args(cl_elts.iter().skip(1))
+                .current_dir::<&Path>(actual_dir.borrow())
+                .status()
+                .map(|_| ())
+                .map_err(|e| e.into()),
+        }
+        .unwrap_or_else(|e| {
+            println!("Error launching process: {}", e);
+        });
     }
 }
 
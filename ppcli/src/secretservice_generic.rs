@@ -1,7 +1,14 @@
 use std::error::Error;
 
 pub fn get_keyring_pass() -> Result<Option<String>, Box<dyn Error>> {
-    let service = "projectpad-cli";
+    let service = projectpadsql::profile::keyring_service(&projectpadsql::profile::current());
     let kr = keyring::Keyring::new(&service, &service);
     Ok(kr.get_password().ok())
 }
+
+pub fn set_keyring_pass(pass: &str) -> Result<(), Box<dyn Error>> {
+    let service = projectpadsql::profile::keyring_service(&projectpadsql::profile::current());
+    let kr = keyring::Keyring::new(&service, &service);
+    kr.set_password(pass)?;
+    Ok(())
+}
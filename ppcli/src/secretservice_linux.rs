@@ -35,6 +35,20 @@ trait Service {
     )>;
 }
 
+#[dbus_proxy(
+    interface = "org.freedesktop.Secret.Collection",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets/aliases/default"
+)]
+trait Collection {
+    fn create_item(
+        &self,
+        properties: std::collections::HashMap<&str, zvariant::Value>,
+        secret: &(zvariant::ObjectPath, Vec<u8>, Vec<u8>, &str),
+        replace: bool,
+    ) -> zbus::Result<(zvariant::OwnedObjectPath, zvariant::OwnedObjectPath)>;
+}
+
 #[derive(Debug, Serialize, Deserialize, Type)]
 pub struct SecretsResponse(
     HashMap<
@@ -49,8 +63,9 @@ pub fn get_keyring_pass() -> Result<Option<String>, Box<dyn Error>> {
     let proxy = ServiceProxy::new(&connection)?;
     let (_val, session_path) = proxy.open_session("plain", &Value::Str(Str::from("")))?;
 
+    let service = projectpadsql::profile::keyring_service(&projectpadsql::profile::current());
     let (unlocked, _locked) =
-        proxy.search_items([("service", "projectpad-cli")].iter().cloned().collect())?;
+        proxy.search_items([("service", service.as_str())].iter().cloned().collect())?;
 
     if unlocked.is_empty() {
         return Ok(None);
@@ -62,3 +77,43 @@ pub fn get_keyring_pass() -> Result<Option<String>, Box<dyn Error>> {
        .and_then(|s| std::str::from_utf8(&s.1.2).ok())
        .map(|s| s.to_string()))
 }
+
+pub fn set_keyring_pass(pass: &str) -> Result<(), Box<dyn Error>> {
+    let connection = zbus::Connection::new_session()?;
+
+    let proxy = ServiceProxy::new(&connection)?;
+    let (_val, session_path) = proxy.open_session("plain", &Value::Str(Str::from("")))?;
+
+    // same attributes as the keyring crate, which the GUI uses
+    let service = projectpadsql::profile::keyring_service(&projectpadsql::profile::current());
+    let attributes: HashMap<&str, &str> = [
+        ("application", "rust-keyring"),
+        ("service", service.as_str()),
+        ("username", service.as_str()),
+    ]
+    .iter()
+    .cloned()
+    .collect();
+    let mut properties = HashMap::new();
+    properties.insert(
+        "org.freedesktop.Secret.Item.Label",
+        Value::from(format!("Password for {} on {}", service, service)),
+    );
+    properties.insert("org.freedesktop.Secret.Item.Attributes", Value::from(attributes));
+
+    let collection = CollectionProxy::new(&connection)?;
+    let (_item, prompt) = collection.create_item(
+        properties,
+        &(
+            (*session_path).clone(),
+            vec![],
+            pass.as_bytes().to_vec(),
+            "text/plain",
+        ),
+        true,
+    )?;
+    if prompt.as_str() != "/" {
+        return Err("the OS keyring is locked, unlock it and try again".into());
+    }
+    Ok(())
+}
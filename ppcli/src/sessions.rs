@@ -0,0 +1,450 @@
+// recording of the commands that ppcli runs, in a pseudo-terminal that we relay,
+// like the `script` command does. The goal is to be able to review later what was
+// done on a server, for instance for post-mortems after an incident in production.
+//
+// The transcripts are stored in a separate sqlite database next to the projectpad
+// database. If the user asks for it when that database gets created, it's encrypted
+// with sqlcipher and the projectpad database password.
+use crate::actions::Action;
+use crate::database::LinkedItem;
+use chrono::prelude::*;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+type SResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// when replaying, don't make the user wait more than that between two outputs
+const REPLAY_MAX_IDLE: Duration = Duration::from_secs(2);
+
+// write the transcript to the database in batches, not on every read
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const SESSIONS_SCHEMA: &str = "
+CREATE TABLE session (id INTEGER PRIMARY KEY,
+    start_time INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    project_id INTEGER NOT NULL,
+    project_name TEXT NOT NULL,
+    server_id INTEGER,
+    server_poi_id INTEGER,
+    project_poi_id INTEGER,
+    action_desc TEXT NOT NULL,
+    command TEXT NOT NULL,
+    exit_code INTEGER);
+
+CREATE TABLE session_chunk (id INTEGER PRIMARY KEY,
+    session_id INTEGER NOT NULL,
+    elapsed_ms INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    FOREIGN KEY(session_id) REFERENCES session(id) ON DELETE CASCADE);";
+
+table! {
+    session {
+        id -> Integer,
+        start_time -> BigInt,
+        duration_ms -> BigInt,
+        project_id -> Integer,
+        project_name -> Varchar,
+        server_id -> Nullable<Integer>,
+        server_poi_id -> Nullable<Integer>,
+        project_poi_id -> Nullable<Integer>,
+        action_desc -> Varchar,
+        command -> Varchar,
+        exit_code -> Nullable<Integer>,
+    }
+}
+
+table! {
+    session_chunk {
+        id -> Integer,
+        session_id -> Integer,
+        elapsed_ms -> BigInt,
+        data -> Binary,
+    }
+}
+
+no_arg_sql_function!(
+    last_insert_rowid,
+    diesel::sql_types::Integer,
+    "Represents the SQL last_insert_row() function"
+);
+
+#[derive(Queryable, Debug)]
+pub struct Session {
+    pub id: i32,
+    pub start_time: i64,
+    pub duration_ms: i64,
+    pub project_id: i32,
+    pub project_name: String,
+    pub server_id: Option<i32>,
+    pub server_poi_id: Option<i32>,
+    pub project_poi_id: Option<i32>,
+    pub action_desc: String,
+    pub command: String,
+    pub exit_code: Option<i32>,
+}
+
+/// per profile: it may be encrypted with the database password
+pub fn sessions_db_path() -> PathBuf {
+    projectpadsql::profile::file_path(&projectpadsql::profile::current(), "sessions.db")
+}
+
+/// open the sessions database, creating it if needed. `encrypt_if_new`
+/// is only relevant when the database doesn't exist yet: an existing
+/// database stays in the format it was created with.
+pub fn open_sessions_db(db_pass: &str, encrypt_if_new: bool) -> SResult<SqliteConnection> {
+    let path = sessions_db_path();
+    let path_str = path.to_string_lossy();
+    if !path.is_file() {
+        let conn = SqliteConnection::establish(&path_str)?;
+        if encrypt_if_new {
+            projectpadsql::try_unlock_db(&conn, db_pass)?;
+        }
+        conn.execute(SESSIONS_SCHEMA)?;
+        return Ok(conn);
+    }
+    let conn = SqliteConnection::establish(&path_str)?;
+    if conn.execute("SELECT count(*) FROM sqlite_master").is_ok() {
+        return Ok(conn);
+    }
+    // can't read it in plaintext => it's an encrypted database.
+    // sqlcipher wants the key to be the first operation on the
+    // connection, so open a new one.
+    let conn = SqliteConnection::establish(&path_str)?;
+    projectpadsql::try_unlock_db(&conn, db_pass).map_err(|e| {
+        format!(
+            "{} (was the database password changed since the sessions database was created?)",
+            e
+        )
+    })?;
+    Ok(conn)
+}
+
+struct RawTerminal {
+    orig_termios: libc::termios,
+}
+
+impl RawTerminal {
+    /// returns None if stdin is not a terminal
+    fn enable() -> Option<RawTerminal> {
+        unsafe {
+            let mut orig_termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut orig_termios) != 0 {
+                return None;
+            }
+            let mut raw = orig_termios;
+            libc::cfmakeraw(&mut raw);
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw);
+            Some(RawTerminal { orig_termios })
+        }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.orig_termios);
+        }
+    }
+}
+
+/// returns (master, slave). The pty gets the size of our terminal.
+fn open_pty() -> io::Result<(File, File)> {
+    let mut master: RawFd = -1;
+    let mut slave: RawFd = -1;
+    unsafe {
+        let mut winsize: libc::winsize = std::mem::zeroed();
+        let winsize_ptr = if libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, &mut winsize) == 0 {
+            &winsize as *const libc::winsize
+        } else {
+            std::ptr::null()
+        };
+        if libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            winsize_ptr,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((File::from_raw_fd(master), File::from_raw_fd(slave)))
+    }
+}
+
+struct Recorder<'a> {
+    conn: &'a SqliteConnection,
+    session_id: i32,
+    pending: Vec<(i64, Vec<u8>)>,
+    last_flush: Instant,
+}
+
+impl<'a> Recorder<'a> {
+    fn start(conn: &'a SqliteConnection, action: &Action, command_line: &str) -> SResult<Self> {
+        let item = &action.item;
+        let changeset = (
+            session::start_time.eq(SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)?
+                .as_secs() as i64),
+            session::duration_ms.eq(0),
+            session::project_id.eq(item.project_id),
+            session::project_name.eq(&item.project_name),
+            session::server_id.eq(item.server_info.as_ref().map(|s| s.server_id)),
+            session::server_poi_id.eq(match item.linked_item {
+                LinkedItem::ServerPoiId(id) => Some(id),
+                _ => None,
+            }),
+            session::project_poi_id.eq(match item.linked_item {
+                LinkedItem::ProjectPoiId(id) => Some(id),
+                _ => None,
+            }),
+            session::action_desc.eq(action.desc.to_string()),
+            session::command.eq(command_line),
+        );
+        diesel::insert_into(session::table)
+            .values(changeset)
+            .execute(conn)?;
+        Ok(Recorder {
+            conn,
+            session_id: diesel::select(last_insert_rowid).get_result(conn)?,
+            pending: vec![],
+            last_flush: Instant::now(),
+        })
+    }
+
+    fn add_chunk(&mut self, elapsed: Duration, data: &[u8]) -> SResult<()> {
+        self.pending
+            .push((elapsed.as_millis() as i64, data.to_vec()));
+        if self.last_flush.elapsed() > FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> SResult<()> {
+        let session_id = self.session_id;
+        let pending = std::mem::take(&mut self.pending);
+        self.conn.transaction::<_, diesel::result::Error, _>(|| {
+            for (elapsed_ms, data) in pending {
+                diesel::insert_into(session_chunk::table)
+                    .values((
+                        session_chunk::session_id.eq(session_id),
+                        session_chunk::elapsed_ms.eq(elapsed_ms),
+                        session_chunk::data.eq(data),
+                    ))
+                    .execute(self.conn)?;
+            }
+            Ok(())
+        })?;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    fn finish(mut self, duration: Duration, exit_code: Option<i32>) -> SResult<()> {
+        self.flush()?;
+        diesel::update(session::table.find(self.session_id))
+            .set((
+                session::duration_ms.eq(duration.as_millis() as i64),
+                session::exit_code.eq(exit_code),
+            ))
+            .execute(self.conn)?;
+        Ok(())
+    }
+}
+
+/// run the command in a pseudo-terminal, relaying the input and output
+/// between the user's terminal and the command, and recording the output.
+pub fn run_recorded(
+    conn: &SqliteConnection,
+    action: &Action,
+    command_line: &str,
+    cl_elts: &[String],
+    cur_dir: &Path,
+) -> SResult<()> {
+    let (mut master, slave) = open_pty()?;
+    // before spawning, so that we don't leave the command behind if we can't record
+    let mut recorder = Recorder::start(conn, action, command_line)?;
+    let spawned = unsafe {
+        Command::new(&cl_elts[0])
+            .args(&cl_elts[1..])
+            .current_dir(cur_dir)
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave.try_clone()?))
+            .pre_exec(|| {
+                // new session, with the pty as the controlling terminal,
+                // so that ssh, vim and so on behave as in a normal terminal
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            })
+            .spawn()
+    };
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            recorder.finish(Duration::from_secs(0), None)?;
+            return Err(e.into());
+        }
+    };
+    // we must close our end of the slave, otherwise we won't notice
+    // when the command exits
+    drop(slave);
+
+    let start = Instant::now();
+    let relayed = {
+        let _raw_terminal = RawTerminal::enable();
+        relay(&mut master, &mut recorder, start)
+    };
+    if relayed.is_err() {
+        // nobody relays the terminal of the command anymore: hang it up,
+        // so that we can wait for the command instead of leaving a zombie
+        drop(master);
+        let _ = child.kill();
+    }
+    let status = child.wait();
+    // finish the session even if something failed, with no exit code if
+    // we don't know it
+    let finished = recorder.finish(start.elapsed(), status.as_ref().ok().and_then(|s| s.code()));
+    relayed?;
+    status?;
+    finished
+}
+
+fn relay(master: &mut File, recorder: &mut Recorder, start: Instant) -> SResult<()> {
+    let mut buf = [0u8; 4096];
+    let mut stdout = io::stdout();
+    let mut fds = [
+        libc::pollfd {
+            fd: master.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+    let mut stdin_open = true;
+    loop {
+        let nfds = if stdin_open { 2 } else { 1 };
+        if unsafe { libc::poll(fds.as_mut_ptr(), nfds, -1) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+        if fds[0].revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0 {
+            match master.read(&mut buf) {
+                // linux gives us EIO when the command has exited
+                Ok(0) | Err(_) => return Ok(()),
+                Ok(n) => {
+                    stdout.write_all(&buf[..n])?;
+                    stdout.flush()?;
+                    recorder.add_chunk(start.elapsed(), &buf[..n])?;
+                }
+            }
+        }
+        if stdin_open && fds[1].revents & (libc::POLLIN | libc::POLLHUP) != 0 {
+            let n = unsafe {
+                libc::read(
+                    libc::STDIN_FILENO,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if n <= 0 {
+                stdin_open = false;
+            } else {
+                master.write_all(&buf[..n as usize])?;
+            }
+        }
+    }
+}
+
+fn format_timestamp(secs: i64) -> String {
+    Local
+        .timestamp(secs, 0)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+fn session_item_desc(s: &Session) -> String {
+    match (s.server_id, s.server_poi_id, s.project_poi_id) {
+        (Some(srv), Some(poi), _) => format!("server {} poi {}", srv, poi),
+        (Some(srv), None, _) => format!("server {}", srv),
+        (None, _, Some(poi)) => format!("project poi {}", poi),
+        _ => "-".to_string(),
+    }
+}
+
+pub fn list_sessions(conn: &SqliteConnection) -> SResult<()> {
+    let sessions = session::table
+        .order(session::start_time.desc())
+        .load::<Session>(conn)?;
+    if sessions.is_empty() {
+        println!("No recorded sessions.");
+        return Ok(());
+    }
+    for s in sessions {
+        println!(
+            "{:>5}  {}  {:>6}s  {:<15} {:<20} {:<10} {}",
+            s.id,
+            format_timestamp(s.start_time),
+            s.duration_ms / 1000,
+            s.project_name,
+            session_item_desc(&s),
+            s.action_desc,
+            s.command
+        );
+    }
+    println!("\nReplay a session with: ppcli sessions <id>");
+    Ok(())
+}
+
+pub fn replay_session(conn: &SqliteConnection, session_id: i32) -> SResult<()> {
+    let s = session::table
+        .find(session_id)
+        .first::<Session>(conn)
+        .optional()?
+        .ok_or_else(|| format!("No session with id {}", session_id))?;
+    println!(
+        "Replaying session {} from {}: {}\n",
+        s.id,
+        format_timestamp(s.start_time),
+        s.command
+    );
+    let chunks = session_chunk::table
+        .filter(session_chunk::session_id.eq(session_id))
+        .order(session_chunk::id.asc())
+        .select((session_chunk::elapsed_ms, session_chunk::data))
+        .load::<(i64, Vec<u8>)>(conn)?;
+    let mut stdout = io::stdout();
+    let mut prev_elapsed_ms = 0;
+    for (elapsed_ms, data) in chunks {
+        let delay = Duration::from_millis((elapsed_ms - prev_elapsed_ms).max(0) as u64);
+        std::thread::sleep(delay.min(REPLAY_MAX_IDLE));
+        prev_elapsed_ms = elapsed_ms;
+        stdout.write_all(&data)?;
+        stdout.flush()?;
+    }
+    println!(
+        "\n\nEnd of the session replay (exit code: {}).",
+        s.exit_code
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "none".to_string())
+    );
+    Ok(())
+}
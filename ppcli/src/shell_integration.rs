@@ -0,0 +1,238 @@
+// the output of ppcli in shell integration mode: ppcli doesn't run the
+// command itself, it hands it over to the shell function which then runs
+// it, so that it ends up in the shell history.
+//
+// two protocols:
+// - legacy: a NUL-separated string, `R\0cmd\0folder\0upgrade_url`,
+//   that integration.zsh splits by index;
+// - json: a versioned JSON object, so that the shell functions can evolve
+//   without depending on the position of the fields.
+use crate::actions::Action;
+use crate::database::{self, ActionType};
+use diesel::sqlite::SqliteConnection;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use structopt::clap::arg_enum;
+
+/// bump this when making incompatible changes to the JSON output
+pub const JSON_PROTOCOL_VERSION: u32 = 1;
+
+arg_enum! {
+    #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+    pub enum Protocol {
+        Legacy,
+        Json,
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellAction {
+    /// run the command
+    Run,
+    /// paste the command to the prompt
+    Paste,
+    /// copy the command to the clipboard
+    Copy,
+}
+
+impl ShellAction {
+    fn legacy_code(self) -> &'static str {
+        match self {
+            ShellAction::Run => "R",
+            ShellAction::Paste => "P",
+            ShellAction::Copy => "C",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct UpgradeInfo {
+    pub download_url: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JsonOutput {
+    pub version: u32,
+    pub action: ShellAction,
+    pub command: String,
+    pub working_dir: Option<String>,
+    /// environment variables the shell should set when running the command
+    pub environment: BTreeMap<&'static str, String>,
+    /// the shell should ask the user for confirmation before running the command
+    pub confirm: bool,
+    /// like environment, but the values are secrets: the shell should
+    /// not display them, or put them in the history
+    pub secrets: BTreeMap<&'static str, String>,
+    pub upgrade: Option<UpgradeInfo>,
+}
+
+pub fn print_output(
+    protocol: Protocol,
+    conn: &SqliteConnection,
+    shell_action: ShellAction,
+    action: &Action,
+    command: &str,
+    working_dir: Option<PathBuf>,
+    upgrade_url: Option<String>,
+) {
+    match protocol {
+        Protocol::Legacy => println!(
+            "{}\x00{}\x00{}\x00{}",
+            shell_action.legacy_code(),
+            command,
+            working_dir
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|| "".to_string()),
+            upgrade_url.unwrap_or_else(|| "".to_string())
+        ),
+        Protocol::Json => {
+            let server_password = action
+                .item
+                .server_info
+                .as_ref()
+                .filter(|_| needs_server_password(shell_action, action))
+                .and_then(|srv| {
+                    database::get_unprotected_server_password(conn, srv.server_id)
+                        .map_err(|e| eprintln!("Error loading the server password: {}", e))
+                        .ok()
+                        .flatten()
+                });
+            let output = json_output(
+                shell_action,
+                action,
+                command,
+                working_dir,
+                upgrade_url,
+                server_password,
+            );
+            println!(
+                "{}",
+                serde_json::to_string(&output).expect("serializing the shell integration output")
+            );
+        }
+    }
+}
+
+/// the password is passed only when opening a shell on the server, where
+/// the user may need it, for instance for sudo. Not to commands which are
+/// copied or pasted, or which only read files.
+fn needs_server_password(shell_action: ShellAction, action: &Action) -> bool {
+    shell_action == ShellAction::Run && action.desc == ActionType::SshShell
+}
+
+fn json_output(
+    shell_action: ShellAction,
+    action: &Action,
+    command: &str,
+    working_dir: Option<PathBuf>,
+    upgrade_url: Option<String>,
+    server_password: Option<String>,
+) -> JsonOutput {
+    let item = &action.item;
+    let mut environment = BTreeMap::new();
+    environment.insert("PPCLI_PROJECT", item.project_name.clone());
+    if let Some(env) = &item.env {
+        environment.insert("PPCLI_ENVIRONMENT", env.name.clone());
+    }
+    let mut secrets = BTreeMap::new();
+    if let Some(srv) = &item.server_info {
+        environment.insert("PPCLI_SERVER", srv.server_desc.clone());
+        environment.insert("PPCLI_SERVER_IP", srv.server_host.clone());
+        if let Some(port) = srv.server_port {
+            environment.insert("PPCLI_SERVER_PORT", port.to_string());
+        }
+        if !srv.server_username.is_empty() {
+            environment.insert("PPCLI_SERVER_USERNAME", srv.server_username.clone());
+        }
+    }
+    if let Some(pass) = server_password.filter(|p| !p.is_empty()) {
+        secrets.insert("PPCLI_SERVER_PASSWORD", pass);
+    }
+    JsonOutput {
+        version: JSON_PROTOCOL_VERSION,
+        action: shell_action,
+        command: command.to_string(),
+        working_dir: working_dir.map(|p| p.to_string_lossy().to_string()),
+        environment,
+        confirm: shell_action == ShellAction::Run
+            && item.env.as_ref().map_or(false, |env| env.dangerous),
+        secrets,
+        upgrade: upgrade_url.map(|download_url| UpgradeInfo { download_url }),
+    }
+}
+
+#[test]
+fn json_output_for_prod_server_run() {
+    use crate::database::{ItemOfInterest, ItemType, LinkedItem, ServerInfo};
+    use projectpadsql::models::{Environment, ServerAccessType, ServerType};
+    let action = Action {
+        item: ItemOfInterest {
+            linked_item: LinkedItem::ServerId(3),
+            project_id: 1,
+            project_name: "proj".to_string(),
+            env: Some(Environment {
+                id: 4,
+                name: "Production".to_string(),
+                short_label: "PRD".to_string(),
+                color: "#df421e".to_string(),
+                ordering: 3,
+                dangerous: true,
+                project_id: 1,
+            }),
+            item_type: ItemType::ServerItemType(ServerType::SrvApplication),
+            poi_desc: None,
+            item_text: "srv".to_string(),
+            server_info: Some(ServerInfo {
+                server_desc: "srv".to_string(),
+                server_username: "root".to_string(),
+                server_host: "10.0.0.1".to_string(),
+                server_port: None,
+                server_access_type: ServerAccessType::SrvAccessSsh,
+                server_has_otp_secret: false,
+                server_has_attachments: false,
+                server_id: 3,
+            }),
+            poi_info: None,
+            run_on: None,
+        },
+        desc: ActionType::SshShell,
+        get_string: |_| "ssh root@10.0.0.1".into(),
+        allowed_actions: vec![],
+    };
+    let output = json_output(
+        ShellAction::Run,
+        &action,
+        "ssh root@10.0.0.1",
+        None,
+        None,
+        Some("secret".to_string()),
+    );
+    assert_eq!(
+        serde_json::json!({
+            "version": 1,
+            "action": "run",
+            "command": "ssh root@10.0.0.1",
+            "working_dir": null,
+            "environment": {
+                "PPCLI_ENVIRONMENT": "Production",
+                "PPCLI_PROJECT": "proj",
+                "PPCLI_SERVER": "srv",
+                "PPCLI_SERVER_IP": "10.0.0.1",
+                "PPCLI_SERVER_USERNAME": "root",
+            },
+            "confirm": true,
+            "secrets": { "PPCLI_SERVER_PASSWORD": "secret" },
+            "upgrade": null,
+        }),
+        serde_json::to_value(&output).unwrap()
+    );
+    assert!(needs_server_password(ShellAction::Run, &action));
+    assert!(!needs_server_password(ShellAction::Copy, &action));
+    let tail = Action {
+        desc: ActionType::TailLog,
+        ..action
+    };
+    assert!(!needs_server_password(ShellAction::Run, &tail));
+}
@@ -11,12 +11,20 @@ pub type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Config {
     pub prefer_dark_theme: bool,
+    /// entities in the trash for longer than that are deleted for good
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+}
+
+fn default_trash_retention_days() -> u32 {
+    30
 }
 
 impl Config {
     pub fn default_config() -> Config {
         Config {
             prefer_dark_theme: false,
+            trash_retention_days: default_trash_retention_days(),
         }
     }
 
@@ -1,12 +1,13 @@
 use super::import_export_dtos::*;
 use diesel::prelude::*;
 use projectpadsql::models::{
-    EnvironmentType, Project, ProjectNote, ProjectPointOfInterest, Server, ServerDatabase,
+    EntityType, Environment, Project, ProjectNote, ProjectPointOfInterest, Server, ServerDatabase,
     ServerExtraUserAccount, ServerLink, ServerNote, ServerPointOfInterest, ServerWebsite,
 };
+use projectpadsql::repo::{AttachmentRepo, CustomFieldRepo, EnvironmentRepo, TagRepo};
 use projectpadsql::sqlite_is;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::path::PathBuf;
 use std::{borrow, env, fs, path, process, time};
@@ -68,67 +69,31 @@ fn export_project(
     extra_files: &mut HashMap<PathBuf, Vec<u8>>,
     project_folder: &Path,
 ) -> ExportResult<ProjectImportExport> {
-    let group_names = projectpadsql::get_project_group_names(sql_conn, project.id);
+    let group_names = projectpadsql::get_project_group_names(sql_conn, project.id)?;
     let mut is_first_env = true;
     let mut project_extra_files = HashMap::new();
 
-    let development_environment = if project.has_dev {
-        let e = Some(export_env(
+    let project_envs = EnvironmentRepo::new(sql_conn).list_for_project(project.id)?;
+    let mut environments = vec![];
+    for env in &project_envs {
+        environments.push(export_env(
             sql_conn,
             project,
-            EnvironmentType::EnvDevelopment,
+            env,
+            &project_envs,
             is_first_env,
             &group_names,
             &mut project_extra_files,
         )?);
         is_first_env = false;
-        e
-    } else {
-        None
-    };
-
-    let staging_environment = if project.has_stage {
-        let e = Some(export_env(
-            sql_conn,
-            project,
-            EnvironmentType::EnvStage,
-            is_first_env,
-            &group_names,
-            &mut project_extra_files,
-        )?);
-        is_first_env = false;
-        e
-    } else {
-        None
-    };
-
-    let uat_environment = if project.has_uat {
-        let e = Some(export_env(
-            sql_conn,
-            project,
-            EnvironmentType::EnvUat,
-            is_first_env,
-            &group_names,
-            &mut project_extra_files,
-        )?);
-        is_first_env = false;
-        e
-    } else {
-        None
-    };
-
-    let prod_environment = if project.has_prod {
-        Some(export_env(
-            sql_conn,
-            project,
-            EnvironmentType::EnvProd,
-            is_first_env,
-            &group_names,
-            &mut project_extra_files,
-        )?)
-    } else {
-        None
-    };
+    }
+    let attachments = export_attachments(
+        sql_conn,
+        EntityType::Project,
+        project.id,
+        "",
+        &mut project_extra_files,
+    )?;
 
     for (path, contents) in project_extra_files {
         let mut path_with_prj = project_folder.to_path_buf();
@@ -144,12 +109,25 @@ fn export_project(
         }
     }
 
+    let custom_fields = CustomFieldRepo::new(sql_conn)
+        .list_definitions_for_project(project.id)?
+        .into_iter()
+        .map(|d| CustomFieldImportExport {
+            items: d.entity_type.to_string(),
+            name: d.name,
+            field_type: d.field_type,
+        })
+        .collect();
+
     Ok(ProjectImportExport {
         project_name: project.name.clone(),
-        development_environment,
-        staging_environment,
-        uat_environment,
-        prod_environment,
+        environments,
+        custom_fields,
+        attachments,
+        development_environment: None,
+        staging_environment: None,
+        uat_environment: None,
+        prod_environment: None,
     })
 }
 
@@ -308,20 +286,41 @@ fn yaml_fix_multiline_strings(raw_output: &str) -> String {
 fn export_env(
     sql_conn: &diesel::SqliteConnection,
     project: &Project,
-    env: EnvironmentType,
+    env: &Environment,
+    project_envs: &[Environment],
     is_first_env: bool,
     group_names: &[String],
     extra_files: &mut HashMap<PathBuf, Vec<u8>>,
 ) -> ExportResult<ProjectEnvImportExport> {
-    let items = export_env_group(sql_conn, project, env, is_first_env, None, extra_files)?;
+    let items = export_env_group(
+        sql_conn,
+        project,
+        env,
+        project_envs,
+        is_first_env,
+        None,
+        extra_files,
+    )?;
 
     let mut items_in_groups = HashMap::new();
     for gn in group_names {
-        let group = export_env_group(sql_conn, project, env, is_first_env, Some(gn), extra_files)?;
+        let group = export_env_group(
+            sql_conn,
+            project,
+            env,
+            project_envs,
+            is_first_env,
+            Some(gn),
+            extra_files,
+        )?;
         items_in_groups.insert(gn.clone(), group);
     }
 
     Ok(ProjectEnvImportExport {
+        name: env.name.clone(),
+        short_label: env.short_label.clone(),
+        color: env.color.clone(),
+        dangerous: env.dangerous,
         items,
         items_in_groups,
     })
@@ -330,12 +329,14 @@ fn export_env(
 fn export_env_group(
     sql_conn: &diesel::SqliteConnection,
     project: &Project,
-    env: EnvironmentType,
+    env: &Environment,
+    project_envs: &[Environment],
     is_first_env: bool,
     group_name: Option<&str>,
     extra_files: &mut HashMap<PathBuf, Vec<u8>>,
 ) -> ExportResult<ProjectEnvGroupImportExport> {
     use projectpadsql::schema::project_note::dsl as prj_note;
+    use projectpadsql::schema::project_note_environment::dsl as prj_note_env;
     use projectpadsql::schema::project_point_of_interest::dsl as prj_poi;
     use projectpadsql::schema::server::dsl as srv;
     use projectpadsql::schema::server_link::dsl as srvl;
@@ -343,50 +344,61 @@ fn export_env_group(
         .filter(
             srv::project_id
                 .eq(project.id)
-                .and(srv::environment.eq(env))
-                .and(sqlite_is(srv::group_name, group_name)),
+                .and(srv::environment.eq(&env.name))
+                .and(sqlite_is(srv::group_name, group_name))
+                .and(srv::deleted_at.is_null()),
         )
         .order((srv::group_name.asc(), srv::desc.asc()))
         .load::<Server>(sql_conn)?;
 
-    let mut notes_query = prj_note::project_note
+    let project_notes = prj_note::project_note
         .filter(
             prj_note::project_id
                 .eq(project.id)
-                .and(sqlite_is(prj_note::group_name, group_name)),
+                .and(sqlite_is(prj_note::group_name, group_name))
+                .and(prj_note::deleted_at.is_null())
+                .and(
+                    prj_note::id.eq_any(
+                        prj_note_env::project_note_environment
+                            .filter(prj_note_env::environment_id.eq(env.id))
+                            .select(prj_note_env::project_note_id),
+                    ),
+                ),
         )
-        .into_boxed();
-    notes_query = match env {
-        EnvironmentType::EnvDevelopment => notes_query.filter(prj_note::has_dev.eq(true)),
-        EnvironmentType::EnvStage => notes_query.filter(prj_note::has_stage.eq(true)),
-        EnvironmentType::EnvUat => notes_query.filter(prj_note::has_uat.eq(true)),
-        EnvironmentType::EnvProd => notes_query.filter(prj_note::has_prod.eq(true)),
-    };
-
-    let project_notes = notes_query
         .order(prj_note::title.asc())
         .load::<ProjectNote>(sql_conn)?;
 
+    let env_repo = EnvironmentRepo::new(sql_conn);
+    let earlier_env_ids: Vec<_> = project_envs
+        .iter()
+        .take_while(|e| e.id != env.id)
+        .map(|e| e.id)
+        .collect();
     let project_notes_import_export = project_notes
         .into_iter()
-        .map(|n| {
+        .map(|n| -> ExportResult<_> {
             // we don't want to repeat the same note, once for each environment.
             // is this the first time we export this note?
             // YES => we export the full note
             // NO => we will display just "shared"
-            let is_first_env_for_this_note = matches!(
-                (
-                    n.has_dev && project.has_dev,
-                    n.has_stage && project.has_stage,
-                    n.has_uat && project.has_uat,
-                    env,
-                ),
-                (_, _, _, EnvironmentType::EnvDevelopment)
-                    | (false, _, _, EnvironmentType::EnvStage)
-                    | (false, false, _, EnvironmentType::EnvUat)
-                    | (false, false, false, EnvironmentType::EnvProd)
-            );
-            ProjectNoteImportExport {
+            let is_first_env_for_this_note = !env_repo
+                .list_for_note(n.id)?
+                .iter()
+                .any(|e| earlier_env_ids.contains(&e.id));
+            let attachments = if is_first_env_for_this_note {
+                export_attachments(
+                    sql_conn,
+                    EntityType::ProjectNote,
+                    n.id,
+                    &n.title,
+                    extra_files,
+                )?
+            } else {
+                vec![]
+            };
+            Ok(ProjectNoteImportExport {
+                tags: export_tags(sql_conn, EntityType::ProjectNote, n.id)?,
+                attachments,
                 title: n.title.clone(),
                 contents: n.contents,
                 shared_with_other_environments: if is_first_env_for_this_note {
@@ -394,16 +406,17 @@ fn export_env_group(
                 } else {
                     Some(n.title)
                 },
-            }
+            })
         })
-        .collect();
+        .collect::<ExportResult<_>>()?;
 
     let server_links = srvl::server_link
         .filter(
             srvl::project_id
                 .eq(project.id)
-                .and(srvl::environment.eq(env))
-                .and(sqlite_is(srvl::group_name, group_name)),
+                .and(srvl::environment.eq(&env.name))
+                .and(sqlite_is(srvl::group_name, group_name))
+                .and(srvl::deleted_at.is_null()),
         )
         .order(srvl::desc.asc())
         .load::<ServerLink>(sql_conn)?;
@@ -417,29 +430,33 @@ fn export_env_group(
         .filter(
             prj_poi::project_id
                 .eq(project.id)
-                .and(sqlite_is(prj_poi::group_name, group_name)),
+                .and(sqlite_is(prj_poi::group_name, group_name))
+                .and(prj_poi::deleted_at.is_null()),
         )
         .order((prj_poi::desc.asc(), prj_poi::path.asc()))
         .load::<ProjectPointOfInterest>(sql_conn)?;
 
     let project_pois_export = project_pois
         .into_iter()
-        .map(|ppoi| ProjectPoiImportExport {
-            desc: ppoi.desc.clone(),
-            path: ppoi.path,
-            text: ppoi.text.clone(),
-            interest_type: ppoi.interest_type,
-            shared_with_other_environments: if is_first_env {
-                None
-            } else {
-                Some(if ppoi.desc.is_empty() {
-                    ppoi.text
+        .map(|ppoi| -> ExportResult<_> {
+            Ok(ProjectPoiImportExport {
+                tags: export_tags(sql_conn, EntityType::ProjectPoi, ppoi.id)?,
+                desc: ppoi.desc.clone(),
+                path: ppoi.path,
+                text: ppoi.text.clone(),
+                interest_type: ppoi.interest_type,
+                shared_with_other_environments: if is_first_env {
+                    None
                 } else {
-                    ppoi.desc
-                })
-            },
+                    Some(if ppoi.desc.is_empty() {
+                        ppoi.text
+                    } else {
+                        ppoi.desc
+                    })
+                },
+            })
         })
-        .collect();
+        .collect::<ExportResult<_>>()?;
 
     Ok(ProjectEnvGroupImportExport {
         servers: srvs
@@ -458,7 +475,7 @@ fn export_server(
     extra_files: &mut HashMap<PathBuf, Vec<u8>>,
 ) -> ExportResult<ServerWithItemsImportExport> {
     let items = export_server_items(sql_conn, extra_files, &server, None)?;
-    let group_names = projectpadsql::get_server_group_names(sql_conn, server.id);
+    let group_names = projectpadsql::get_server_group_names(sql_conn, server.id)?;
     let mut items_in_groups = HashMap::new();
     for gn in &group_names {
         let items = export_server_items(sql_conn, extra_files, &server, Some(&gn))?;
@@ -480,6 +497,15 @@ fn export_server(
         _ => None,
     };
     Ok(ServerWithItemsImportExport {
+        tags: export_tags(sql_conn, EntityType::Server, server.id)?,
+        attachments: export_attachments(
+            sql_conn,
+            EntityType::Server,
+            server.id,
+            &server.desc,
+            extra_files,
+        )?,
+        custom_fields: export_custom_fields(sql_conn, EntityType::Server, server.id)?,
         server: ServerImportExport { server, data_path },
         items,
         items_in_groups,
@@ -520,16 +546,26 @@ fn export_server_items(
         .filter(
             srv_poi::server_id
                 .eq(server.id)
-                .and(sqlite_is(srv_poi::group_name, group_name)),
+                .and(sqlite_is(srv_poi::group_name, group_name))
+                .and(srv_poi::deleted_at.is_null()),
         )
         .order(srv_poi::desc.asc())
-        .load::<ServerPointOfInterest>(sql_conn)?;
+        .load::<ServerPointOfInterest>(sql_conn)?
+        .into_iter()
+        .map(|poi| -> ExportResult<_> {
+            Ok(ServerPoiImportExport {
+                tags: export_tags(sql_conn, EntityType::ServerPoi, poi.id)?,
+                poi,
+            })
+        })
+        .collect::<ExportResult<Vec<_>>>()?;
 
     let server_websites = srv_www::server_website
         .filter(
             srv_www::server_id
                 .eq(server.id)
-                .and(sqlite_is(srv_www::group_name, group_name)),
+                .and(sqlite_is(srv_www::group_name, group_name))
+                .and(srv_www::deleted_at.is_null()),
         )
         .order(srv_www::desc.asc())
         .load::<ServerWebsite>(sql_conn)?
@@ -541,28 +577,52 @@ fn export_server_items(
         .filter(
             srv_db::server_id
                 .eq(server.id)
-                .and(sqlite_is(srv_db::group_name, group_name)),
+                .and(sqlite_is(srv_db::group_name, group_name))
+                .and(srv_db::deleted_at.is_null()),
         )
         .order(srv_db::desc.asc())
         .load::<ServerDatabase>(sql_conn)?
         .into_iter()
-        .map(ServerDatabaseImportExport)
-        .collect();
+        .map(|db| -> ExportResult<_> {
+            Ok(ServerDatabaseImportExport {
+                tags: export_tags(sql_conn, EntityType::ServerDatabase, db.id)?,
+                custom_fields: export_custom_fields(sql_conn, EntityType::ServerDatabase, db.id)?,
+                db,
+            })
+        })
+        .collect::<ExportResult<Vec<_>>>()?;
 
     let server_notes = srv_note::server_note
         .filter(
             srv_note::server_id
                 .eq(server.id)
-                .and(sqlite_is(srv_note::group_name, group_name)),
+                .and(sqlite_is(srv_note::group_name, group_name))
+                .and(srv_note::deleted_at.is_null()),
         )
         .order(srv_note::title.asc())
-        .load::<ServerNote>(sql_conn)?;
+        .load::<ServerNote>(sql_conn)?
+        .into_iter()
+        .map(|note| -> ExportResult<_> {
+            Ok(ServerNoteImportExport {
+                tags: export_tags(sql_conn, EntityType::ServerNote, note.id)?,
+                attachments: export_attachments(
+                    sql_conn,
+                    EntityType::ServerNote,
+                    note.id,
+                    &note.title,
+                    extra_files,
+                )?,
+                note,
+            })
+        })
+        .collect::<ExportResult<Vec<_>>>()?;
 
     let server_extra_users = srv_usr::server_extra_user_account
         .filter(
             srv_usr::server_id
                 .eq(server.id)
-                .and(sqlite_is(srv_usr::group_name, group_name)),
+                .and(sqlite_is(srv_usr::group_name, group_name))
+                .and(srv_usr::deleted_at.is_null()),
         )
         .order(srv_usr::username.asc())
         .load::<ServerExtraUserAccount>(sql_conn)?;
@@ -580,6 +640,66 @@ fn export_server_items(
     })
 }
 
+fn export_tags(
+    sql_conn: &SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+) -> ExportResult<Vec<String>> {
+    Ok(TagRepo::new(sql_conn)
+        .list_for_item(entity_type, entity_id)?
+        .into_iter()
+        .map(|t| t.name)
+        .collect())
+}
+
+fn export_custom_fields(
+    sql_conn: &SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+) -> ExportResult<BTreeMap<String, String>> {
+    Ok(CustomFieldRepo::new(sql_conn)
+        .list_for_item(entity_type, entity_id)?
+        .into_iter()
+        .map(|f| (f.definition.name, f.value))
+        .collect())
+}
+
+/// the attachments are exported as extra files, in a folder named
+/// after the item. Returns the paths of the files.
+fn export_attachments(
+    sql_conn: &diesel::SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+    item_desc: &str,
+    extra_files: &mut HashMap<PathBuf, Vec<u8>>,
+) -> ExportResult<Vec<PathBuf>> {
+    let repo = AttachmentRepo::new(sql_conn);
+    let attachments = repo.list_for_item(entity_type, entity_id)?;
+    if attachments.is_empty() {
+        return Ok(vec![]);
+    }
+    let path_base = if item_desc.is_empty() {
+        "attachments".to_string()
+    } else {
+        format!("{}_attachments", escape_filename(item_desc))
+    };
+    let sub_path = find_unique_data_path(path_base, &extra_files);
+    let mut paths = vec![];
+    for attachment in attachments {
+        let mut path = PathBuf::from(&sub_path);
+        path.push(&attachment.name);
+        // an item can have several attachments with the same name
+        let mut counter = 1;
+        while extra_files.contains_key(&path) {
+            counter += 1;
+            path.set_file_name(format!("{}-{}", counter, attachment.name));
+        }
+        extra_files.insert(path.clone(), repo.contents(attachment.id)?);
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
 fn export_server_extra_user(
     user: ServerExtraUserAccount,
     extra_files: &mut HashMap<PathBuf, Vec<u8>>,
@@ -602,6 +722,7 @@ fn export_server_extra_user(
     ServerExtraUserImportExport {
         username: user.username,
         password: user.password,
+        otp_secret: user.otp_secret,
         desc: user.desc,
         data_path,
         auth_key_filename: user.auth_key_filename,
@@ -620,7 +741,7 @@ fn to_server_link_import_export(
         .first::<(Server, Project)>(sql_conn)?;
     let server = ServerPath {
         project_name: prj.name,
-        environment: srv.environment,
+        environment: srv.environment.clone(),
         server_id: Some(srv.id).filter(|_| srv.desc.is_empty()),
         server_desc: Some(srv.desc).filter(|d| !d.is_empty()),
     };
@@ -645,7 +766,7 @@ fn to_server_website_import_export(
                 .first::<(ServerDatabase, (Server, Project))>(sql_conn)?;
             Some(ServerDatabasePath {
                 project_name: prj.name,
-                environment: srv.environment,
+                environment: srv.environment.clone(),
                 server_id: if srv.desc.is_empty() {
                     Some(srv.id)
                 } else {
@@ -664,11 +785,14 @@ fn to_server_website_import_export(
     };
 
     Ok(ServerWebsiteImportExport {
+        tags: export_tags(sql_conn, EntityType::ServerWebsite, website.id)?,
+        custom_fields: export_custom_fields(sql_conn, EntityType::ServerWebsite, website.id)?,
         desc: website.desc,
         url: website.url,
         text: website.text,
         username: website.username,
         password: website.password,
+        otp_secret: website.otp_secret,
         server_database,
     })
 }
@@ -718,7 +842,7 @@ mod tests {
             // the replace is a workaround for a minor issue (trailing \n, i think)
             // that i'm not particularly interested in at this point
             raw_output
-                .replace("          \nuat_environ", "uat_environ")
+                .replace("            \n  - name: UAT", "  - name: UAT")
                 .trim_end()
         );
     }
@@ -3,9 +3,10 @@ use super::import_export_dtos::*;
 use crate::sql_util::insert_row;
 use diesel::dsl::count;
 use diesel::prelude::*;
-use projectpadsql::models::EnvironmentType;
+use projectpadsql::models::{CustomFieldDefinition, EntityType, Environment};
+use projectpadsql::repo::{AttachmentRepo, CustomFieldRepo, EnvironmentRepo, TagRepo};
 use projectpadsql::sqlite_is;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::{borrow, fs, process, str};
 
@@ -61,10 +62,13 @@ pub fn do_import(
 
 fn import_projects(
     sql_conn: &diesel::SqliteConnection,
-    projects_contents: Vec<(PathBuf, ProjectImportExport)>,
+    mut projects_contents: Vec<(PathBuf, ProjectImportExport)>,
     import_folder: &Path,
 ) -> ImportResult<()> {
     use projectpadsql::schema::project::dsl as prj;
+    for (_, decoded) in &mut projects_contents {
+        decoded.upgrade_legacy_environments();
+    }
     let sorted_projects = sort_by_deps(projects_contents);
     for (project_path, decoded) in sorted_projects {
         let mut project_folder = import_folder.to_path_buf();
@@ -86,11 +90,7 @@ fn import_projects(
             vec![]
         };
         let changeset = (
-            prj::name.eq(decoded.project_name),
-            prj::has_dev.eq(decoded.development_environment.is_some()),
-            prj::has_stage.eq(decoded.staging_environment.is_some()),
-            prj::has_uat.eq(decoded.uat_environment.is_some()),
-            prj::has_prod.eq(decoded.prod_environment.is_some()),
+            prj::name.eq(&decoded.project_name),
             prj::icon.eq(Some(icon)),
         );
         let project_id = insert_row(
@@ -100,45 +100,71 @@ fn import_projects(
         .map_err(to_boxed_stderr)?;
         let mut unprocessed_websites = vec![];
 
-        if let Some(dev_env) = decoded.development_environment {
-            unprocessed_websites.extend(import_project_env_first_pass(
-                sql_conn,
-                &project_folder,
-                project_id,
-                EnvironmentType::EnvDevelopment,
-                &dev_env,
-            )?);
-        }
-        if let Some(stg_env) = decoded.staging_environment {
-            unprocessed_websites.extend(import_project_env_first_pass(
-                sql_conn,
-                &project_folder,
-                project_id,
-                EnvironmentType::EnvStage,
-                &stg_env,
-            )?);
-        }
-        if let Some(uat_env) = decoded.uat_environment {
-            unprocessed_websites.extend(import_project_env_first_pass(
-                sql_conn,
-                &project_folder,
-                project_id,
-                EnvironmentType::EnvUat,
-                &uat_env,
-            )?);
-        }
-        if let Some(prod_env) = decoded.prod_environment {
+        let defaults = Environment::defaults(project_id);
+        let environments = EnvironmentRepo::new(sql_conn).save_for_project(
+            project_id,
+            &decoded
+                .environments
+                .iter()
+                .map(|env| Environment {
+                    id: 0,
+                    name: env.name.clone(),
+                    short_label: env.short_label.clone(),
+                    // the color is optional in the YAML
+                    color: Some(env.color.clone())
+                        .filter(|c| !c.is_empty())
+                        .or_else(|| {
+                            defaults
+                                .iter()
+                                .find(|d| d.name == env.name)
+                                .map(|d| d.color.clone())
+                        })
+                        .unwrap_or_else(|| "#888888".to_string()),
+                    ordering: 0,
+                    dangerous: env.dangerous,
+                    project_id,
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        CustomFieldRepo::new(sql_conn).save_definitions(
+            project_id,
+            &decoded
+                .custom_fields
+                .iter()
+                .map(|f| -> ImportResult<_> {
+                    Ok(CustomFieldDefinition {
+                        id: 0,
+                        project_id,
+                        entity_type: f
+                            .items
+                            .parse()
+                            .map_err(|_| format!("Invalid custom field items: {}", f.items))?,
+                        name: f.name.clone(),
+                        field_type: f.field_type,
+                        ordering: 0,
+                    })
+                })
+                .collect::<ImportResult<Vec<_>>>()?,
+        )?;
+        for (env, project_env) in environments.iter().zip(&decoded.environments) {
             unprocessed_websites.extend(import_project_env_first_pass(
                 sql_conn,
                 &project_folder,
                 project_id,
-                EnvironmentType::EnvProd,
-                &prod_env,
+                env,
+                project_env,
             )?);
         }
         for unprocessed_website in unprocessed_websites {
             import_server_website(sql_conn, &unprocessed_website)?;
         }
+        import_attachments(
+            sql_conn,
+            &project_folder,
+            EntityType::Project,
+            project_id,
+            &decoded.attachments,
+        )?;
     }
     Ok(())
 }
@@ -223,7 +249,7 @@ fn import_project_env_first_pass(
     sql_conn: &diesel::SqliteConnection,
     import_folder: &Path,
     project_id: i32,
-    env: EnvironmentType,
+    env: &Environment,
     project_env: &ProjectEnvImportExport,
 ) -> ImportResult<Vec<UnprocessedWebsite>> {
     let mut unprocessed_websites = import_project_env_group_first_pass(
@@ -254,14 +280,21 @@ fn import_project_env_group_first_pass(
     import_folder: &Path,
     project_id: i32,
     items: &ProjectEnvGroupImportExport,
-    env: EnvironmentType,
+    env: &Environment,
     group_name: Option<&str>,
 ) -> ImportResult<Vec<UnprocessedWebsite>> {
     for project_poi in &items.project_pois {
         import_project_poi(sql_conn, project_id, group_name, project_poi)?;
     }
     for project_note in &items.project_notes {
-        import_project_note(sql_conn, project_id, group_name, env, project_note)?;
+        import_project_note(
+            sql_conn,
+            import_folder,
+            project_id,
+            group_name,
+            env,
+            project_note,
+        )?;
     }
     for server_link in &items.server_links {
         import_server_link(sql_conn, project_id, group_name, env, server_link)?;
@@ -299,22 +332,29 @@ fn import_project_poi(
         prj_poi::interest_type.eq(project_poi.interest_type),
         prj_poi::project_id.eq(project_id),
     );
-    insert_row(
+    let project_poi_id = insert_row(
         sql_conn,
         diesel::insert_into(prj_poi::project_point_of_interest).values(changeset),
     )
-    .map_err(to_boxed_stderr)
-    .map(|_| ())
+    .map_err(to_boxed_stderr)?;
+    import_tags(
+        sql_conn,
+        EntityType::ProjectPoi,
+        project_poi_id,
+        &project_poi.tags,
+    )
 }
 
 fn import_project_note(
     sql_conn: &diesel::SqliteConnection,
+    import_folder: &Path,
     project_id: i32,
     group_name: Option<&str>,
-    env: EnvironmentType,
+    env: &Environment,
     project_note: &ProjectNoteImportExport,
 ) -> ImportResult<()> {
     use projectpadsql::schema::project_note::dsl as prj_note;
+    use projectpadsql::schema::project_note_environment::dsl as prj_note_env;
     if let Some(shared_title) = project_note.shared_with_other_environments.as_ref() {
         // update the row to mark that it's active
         // also for this environment
@@ -327,42 +367,40 @@ fn import_project_note(
                     .and(prj_note::project_id.eq(project_id)),
             )
             .first::<i32>(sql_conn)?;
-        let what = prj_note::project_note.filter(prj_note::id.eq(note_id_to_update));
-
-        match env {
-            // dev is the first, normally we come here at the 2nd
-            // environment the earlier => skip it
-            EnvironmentType::EnvStage => diesel::update(what)
-                .set(prj_note::has_stage.eq(true))
-                .execute(sql_conn),
-            EnvironmentType::EnvUat => diesel::update(what)
-                .set(prj_note::has_uat.eq(true))
-                .execute(sql_conn),
-            EnvironmentType::EnvProd => diesel::update(what)
-                .set(prj_note::has_prod.eq(true))
-                .execute(sql_conn),
-            _ => unreachable!(),
-        }
-        .map(|_| ())?;
+        diesel::insert_into(prj_note_env::project_note_environment)
+            .values((
+                prj_note_env::project_note_id.eq(note_id_to_update),
+                prj_note_env::environment_id.eq(env.id),
+            ))
+            .execute(sql_conn)?;
         Ok(())
     } else {
         // this note was not imported yet, import it the first time
         let changeset = (
             prj_note::title.eq(&project_note.title),
             prj_note::contents.eq(&project_note.contents),
-            prj_note::has_dev.eq(env == EnvironmentType::EnvDevelopment),
-            prj_note::has_stage.eq(env == EnvironmentType::EnvStage),
-            prj_note::has_uat.eq(env == EnvironmentType::EnvUat),
-            prj_note::has_prod.eq(env == EnvironmentType::EnvProd),
             prj_note::group_name.eq(group_name),
             prj_note::project_id.eq(project_id),
         );
-        insert_row(
+        let note_id = insert_row(
             sql_conn,
             diesel::insert_into(prj_note::project_note).values(changeset),
         )
-        .map_err(to_boxed_stderr)
-        .map(|_| ())
+        .map_err(to_boxed_stderr)?;
+        EnvironmentRepo::new(sql_conn).set_for_note(note_id, &[env.id])?;
+        import_tags(
+            sql_conn,
+            EntityType::ProjectNote,
+            note_id,
+            &project_note.tags,
+        )?;
+        import_attachments(
+            sql_conn,
+            import_folder,
+            EntityType::ProjectNote,
+            note_id,
+            &project_note.attachments,
+        )
     }
 }
 
@@ -370,7 +408,7 @@ fn import_server_link(
     sql_conn: &diesel::SqliteConnection,
     project_id: i32,
     group_name: Option<&str>,
-    env: EnvironmentType,
+    env: &Environment,
     server_link: &ServerLinkImportExport,
 ) -> ImportResult<()> {
     use projectpadsql::schema::server_link::dsl as srv_link;
@@ -382,7 +420,7 @@ fn import_server_link(
             srv_link::group_name.eq(group_name),
             srv_link::linked_server_id.eq(linked_server_id),
             srv_link::project_id.eq(project_id),
-            srv_link::environment.eq(env),
+            srv_link::environment.eq(&env.name),
         );
         insert_row(
             sql_conn,
@@ -399,7 +437,7 @@ fn import_server(
     sql_conn: &diesel::SqliteConnection,
     import_folder: &Path,
     project_id: i32,
-    env: EnvironmentType,
+    env: &Environment,
     group_name: Option<&str>,
     server: &ServerWithItemsImportExport,
 ) -> ImportResult<Vec<UnprocessedWebsite>> {
@@ -419,20 +457,39 @@ fn import_server(
     let changeset = (
         srv::desc.eq(&server.server.server.desc), // TODO -ETOOMANYSERVERS
         srv::is_retired.eq(server.server.server.is_retired),
-        srv::ip.eq(&server.server.server.ip),
+        srv::host.eq(&server.server.server.host),
+        srv::port.eq(server.server.server.port),
+        srv::protocol.eq(server.server.server.protocol.as_ref()),
+        srv::path.eq(&server.server.server.path),
         srv::text.eq(&server.server.server.text),
         srv::group_name.eq(group_name),
         srv::username.eq(&server.server.server.username),
         srv::password.eq(&server.server.server.password),
+        srv::otp_secret.eq(&server.server.server.otp_secret),
         srv::auth_key.eq(auth_key_contents),
         srv::auth_key_filename.eq(server.server.server.auth_key_filename.as_ref()),
         srv::server_type.eq(server.server.server.server_type),
         srv::access_type.eq(server.server.server.access_type),
-        srv::environment.eq(env),
+        srv::environment.eq(&env.name),
         srv::project_id.eq(project_id),
     );
     let server_id = insert_row(sql_conn, diesel::insert_into(srv::server).values(changeset))
         .map_err(to_boxed_stderr)?;
+    import_tags(sql_conn, EntityType::Server, server_id, &server.tags)?;
+    import_custom_fields(
+        sql_conn,
+        server_id,
+        EntityType::Server,
+        server_id,
+        &server.custom_fields,
+    )?;
+    import_attachments(
+        sql_conn,
+        import_folder,
+        EntityType::Server,
+        server_id,
+        &server.attachments,
+    )?;
 
     import_server_items(sql_conn, import_folder, server_id, None, &server.items)?;
     for (group_name, items) in &server.items_in_groups {
@@ -469,50 +526,67 @@ fn import_server_items(
     for db in &items.server_databases {
         use projectpadsql::schema::server_database::dsl as srv_db;
         let changeset = (
-            srv_db::desc.eq(&db.0.desc),
-            srv_db::name.eq(&db.0.name),
+            srv_db::desc.eq(&db.db.desc),
+            srv_db::name.eq(&db.db.name),
             srv_db::group_name.eq(group_name),
-            srv_db::text.eq(&db.0.text),
-            srv_db::username.eq(&db.0.username),
-            srv_db::password.eq(&db.0.password),
+            srv_db::text.eq(&db.db.text),
+            srv_db::username.eq(&db.db.username),
+            srv_db::password.eq(&db.db.password),
             srv_db::server_id.eq(server_id),
         );
-        insert_row(
+        let db_id = insert_row(
             sql_conn,
             diesel::insert_into(srv_db::server_database).values(changeset),
         )
         .map_err(to_boxed_stderr)?;
+        import_tags(sql_conn, EntityType::ServerDatabase, db_id, &db.tags)?;
+        import_custom_fields(
+            sql_conn,
+            server_id,
+            EntityType::ServerDatabase,
+            db_id,
+            &db.custom_fields,
+        )?;
     }
     for note in &items.server_notes {
         use projectpadsql::schema::server_note::dsl as srv_note;
         let changeset = (
-            srv_note::title.eq(&note.title),
+            srv_note::title.eq(&note.note.title),
             srv_note::group_name.eq(group_name),
-            srv_note::contents.eq(&note.contents),
+            srv_note::contents.eq(&note.note.contents),
             srv_note::server_id.eq(server_id),
         );
-        insert_row(
+        let note_id = insert_row(
             sql_conn,
             diesel::insert_into(srv_note::server_note).values(changeset),
         )
         .map_err(to_boxed_stderr)?;
+        import_tags(sql_conn, EntityType::ServerNote, note_id, &note.tags)?;
+        import_attachments(
+            sql_conn,
+            import_folder,
+            EntityType::ServerNote,
+            note_id,
+            &note.attachments,
+        )?;
     }
     for poi in &items.server_pois {
         use projectpadsql::schema::server_point_of_interest::dsl as srv_poi;
         let changeset = (
-            srv_poi::desc.eq(&poi.desc),
-            srv_poi::path.eq(&poi.path),
-            srv_poi::text.eq(&poi.text),
+            srv_poi::desc.eq(&poi.poi.desc),
+            srv_poi::path.eq(&poi.poi.path),
+            srv_poi::text.eq(&poi.poi.text),
             srv_poi::group_name.eq(group_name),
-            srv_poi::interest_type.eq(poi.interest_type),
-            srv_poi::run_on.eq(poi.run_on),
+            srv_poi::interest_type.eq(poi.poi.interest_type),
+            srv_poi::run_on.eq(poi.poi.run_on),
             srv_poi::server_id.eq(server_id),
         );
-        insert_row(
+        let poi_id = insert_row(
             sql_conn,
             diesel::insert_into(srv_poi::server_point_of_interest).values(changeset),
         )
         .map_err(to_boxed_stderr)?;
+        import_tags(sql_conn, EntityType::ServerPoi, poi_id, &poi.tags)?;
     }
     for user in &items.server_extra_users {
         use projectpadsql::schema::server_extra_user_account::dsl as srv_usr;
@@ -530,6 +604,7 @@ fn import_server_items(
             srv_usr::group_name.eq(group_name),
             srv_usr::username.eq(&user.username),
             srv_usr::password.eq(&user.password),
+            srv_usr::otp_secret.eq(&user.otp_secret),
             srv_usr::auth_key.eq(auth_key_contents),
             srv_usr::auth_key_filename.eq(&user.auth_key_filename),
             srv_usr::server_id.eq(server_id),
@@ -561,14 +636,86 @@ fn import_server_website(
         srv_www::group_name.eq(website_info.group_name.as_ref()),
         srv_www::username.eq(&website_info.website.username),
         srv_www::password.eq(&website_info.website.password),
+        srv_www::otp_secret.eq(&website_info.website.otp_secret),
         srv_www::server_database_id.eq(new_databaseid),
         srv_www::server_id.eq(website_info.server_id),
     );
-    insert_row(
+    let website_id = insert_row(
         sql_conn,
         diesel::insert_into(srv_www::server_website).values(changeset),
     )
     .map_err(to_boxed_stderr)?;
+    import_tags(
+        sql_conn,
+        EntityType::ServerWebsite,
+        website_id,
+        &website_info.website.tags,
+    )?;
+    import_custom_fields(
+        sql_conn,
+        website_info.server_id,
+        EntityType::ServerWebsite,
+        website_id,
+        &website_info.website.custom_fields,
+    )
+}
+
+fn import_tags(
+    sql_conn: &diesel::SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+    tags: &[String],
+) -> ImportResult<()> {
+    if !tags.is_empty() {
+        TagRepo::new(sql_conn).set_for_item(entity_type, entity_id, tags)?;
+    }
+    Ok(())
+}
+
+/// the custom fields are defined by the project, which was imported first
+fn import_custom_fields(
+    sql_conn: &diesel::SqliteConnection,
+    server_id: i32,
+    entity_type: EntityType,
+    entity_id: i32,
+    values: &BTreeMap<String, String>,
+) -> ImportResult<()> {
+    if values.is_empty() {
+        return Ok(());
+    }
+    let repo = CustomFieldRepo::new(sql_conn);
+    let definitions = repo.list_definitions_for_server(server_id, entity_type)?;
+    let values = values
+        .iter()
+        .map(|(name, value)| {
+            definitions
+                .iter()
+                .find(|d| &d.name == name)
+                .map(|d| (d.id, value.clone()))
+                .ok_or_else(|| format!("Unknown custom field: {}", name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    repo.set_for_item(entity_type, entity_id, &values)?;
+    Ok(())
+}
+
+fn import_attachments(
+    sql_conn: &diesel::SqliteConnection,
+    import_folder: &Path,
+    entity_type: EntityType,
+    entity_id: i32,
+    attachments: &[PathBuf],
+) -> ImportResult<()> {
+    let repo = AttachmentRepo::new(sql_conn);
+    for attachment_path in attachments {
+        let name = attachment_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| format!("Invalid attachment path: {}", attachment_path.display()))?;
+        let mut path = import_folder.to_path_buf();
+        path.push(attachment_path);
+        repo.add(entity_type, entity_id, name, &fs::read(path)?)?;
+    }
     Ok(())
 }
 
@@ -595,7 +742,7 @@ fn get_linked_server_id(
         .filter(
             prj::name
                 .eq(&server_path.project_name)
-                .and(srv::environment.eq(server_path.environment))
+                .and(srv::environment.eq(&server_path.environment))
                 .and(srv::desc.eq(server_path.server_desc.as_ref().unwrap())),
         )
         .first::<i32>(sql_conn)
@@ -652,7 +799,7 @@ fn get_new_databaseid(
                 .filter(
                     prj::name
                         .eq(&db_path.project_name)
-                        .and(srv::environment.eq(db_path.environment))
+                        .and(srv::environment.eq(&db_path.environment))
                         // we know server_desc is present, because server_id is not.
                         .and(srv::desc.eq(db_path.server_desc.as_ref().unwrap())),
                 )
@@ -679,11 +826,84 @@ fn get_new_databaseid(
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::sql_thread;
-    use projectpadsql::models::Project;
+    use projectpadsql::models::{Project, Server};
     use std::collections::HashMap;
 
-    pub const SAMPLE_YAML_PROJECT: &str = r#"
+    pub const SAMPLE_YAML_PROJECT: &str = r##"
+---
+project_name: Demo
+environments:
+  - name: Development
+    short_label: DEV
+    color: "#46a046"
+    dangerous: false
+    items:
+      project_pois:
+        - desc: my first script
+          path: /my/path/on/disk
+          text: sh run.sh myparams
+          interest_type: PoiCommandTerminal
+          tags:
+            - scripts
+      project_notes:
+        - title: My note
+          contents: |2
+            * First
+            * Second
+            * Third
+  - name: UAT
+    short_label: UAT
+    color: "#eed680"
+    dangerous: false
+    items:
+      servers:
+        - server:
+            desc: My server
+            host: 254.245.33.34
+            port: 2222
+            text: Comments about my server
+            username: itisi
+            password: i
+            server_type: SrvApplication
+            access_type: SrvAccessSsh
+          tags:
+            - k8s
+            - on-call
+          custom_fields:
+            rack: B12
+          items:
+            server_websites:
+              - desc: my website
+                url: "https://mywww.com"
+                username: itisi
+                password: pass!
+                server_database:
+                  project_name: Demo
+                  environment: UAT
+                  server_desc: My server
+                  database_desc: mydb
+            server_databases:
+              - desc: mydb
+                tags:
+                  - reports
+                custom_fields:
+                  backups: "https://backups.example.com/mydb"
+            server_extra_users:
+              - username: monitor
+                password: monpass
+                desc: metrics user
+      project_pois:
+        - shared_with_other_environments: my first script
+custom_fields:
+  - items: server
+    name: rack
+    field_type: Text
+  - items: server_database
+    name: backups
+    field_type: Url"##;
+
+    /// the format of the older versions, with fixed environments
+    const LEGACY_YAML_PROJECT: &str = r##"
 ---
 project_name: Demo
 development_environment:
@@ -704,7 +924,7 @@ uat_environment:
     servers:
       - server:
           desc: My server
-          ip: 254.245.33.34
+          ip: "254.245.33.34:2222"
           text: Comments about my server
           username: itisi
           password: i
@@ -728,11 +948,11 @@ uat_environment:
               password: monpass
               desc: metrics user
     project_pois:
-      - shared_with_other_environments: my first script"#;
+      - shared_with_other_environments: my first script"##;
 
     pub fn tests_load_yaml(yaml: &str) -> SqliteConnection {
         let db_conn = SqliteConnection::establish(":memory:").unwrap();
-        sql_thread::migrate_db_if_needed(&db_conn).unwrap();
+        projectpadsql::migrations::migrate_db_if_needed(&db_conn, None).unwrap();
         let input = serde_yaml::from_str(yaml).unwrap();
         import_projects(
             &db_conn,
@@ -750,7 +970,7 @@ uat_environment:
                 desc: "".to_string(),
                 server: ServerPath {
                     project_name: dep_prj.to_string(),
-                    environment: EnvironmentType::EnvDevelopment,
+                    environment: "Development".to_string(),
                     server_id: None,
                     server_desc: None,
                 },
@@ -758,7 +978,13 @@ uat_environment:
             .collect();
         ProjectImportExport {
             project_name: pname.to_string(),
-            development_environment: Some(ProjectEnvImportExport {
+            attachments: vec![],
+            custom_fields: vec![],
+            environments: vec![ProjectEnvImportExport {
+                name: "Development".to_string(),
+                short_label: "DEV".to_string(),
+                color: "#46a046".to_string(),
+                dangerous: false,
                 items: ProjectEnvGroupImportExport {
                     servers: vec![],
                     server_links: depends_server_links,
@@ -766,7 +992,8 @@ uat_environment:
                     project_notes: vec![],
                 },
                 items_in_groups: HashMap::new(),
-            }),
+            }],
+            development_environment: None,
             staging_environment: None,
             uat_environment: None,
             prod_environment: None,
@@ -819,11 +1046,138 @@ uat_environment:
         assert_eq!(1, imported_projects.len());
         let p = imported_projects.get(0).unwrap();
         assert_eq!("Demo", p.name);
-        assert_eq!(true, p.has_dev);
-        assert_eq!(true, p.has_uat);
-        assert_eq!(false, p.has_stage);
-        assert_eq!(false, p.has_prod);
+        let envs = EnvironmentRepo::new(&db_conn)
+            .list_for_project(p.id)
+            .unwrap();
+        assert_eq!(
+            vec!["Development", "UAT"],
+            envs.iter().map(|e| e.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!("#eed680", envs[1].color);
+        let tags = TagRepo::new(&db_conn);
+        use projectpadsql::schema::server::dsl as srv;
+        let server = srv::server.first::<Server>(&db_conn).unwrap();
+        assert_eq!("254.245.33.34:2222", server.address().to_string());
+        let server_id = server.id;
+        assert_eq!(
+            vec!["k8s", "on-call"],
+            tags.list_for_item(EntityType::Server, server_id)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.name)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(4, tags.list().unwrap().len());
+        let custom_fields = CustomFieldRepo::new(&db_conn);
+        assert_eq!(
+            vec![("rack".to_string(), "B12".to_string())],
+            custom_fields
+                .list_for_item(EntityType::Server, server_id)
+                .unwrap()
+                .into_iter()
+                .map(|f| (f.definition.name, f.value))
+                .collect::<Vec<_>>()
+        );
+        // the website's database path was resolved
+        use projectpadsql::schema::server_website::dsl as srvw;
+        assert_eq!(
+            Some(1),
+            srvw::server_website
+                .select(srvw::server_database_id)
+                .first::<Option<i32>>(&db_conn)
+                .unwrap()
+        );
         // we get a little more coverage in the export tests
         // where we import then export back and compare the YAML
     }
+
+    #[test]
+    fn import_from_legacy_yaml() {
+        let db_conn = tests_load_yaml(LEGACY_YAML_PROJECT);
+        use projectpadsql::schema::project::dsl as prj;
+        use projectpadsql::schema::server::dsl as srv;
+        let p = prj::project.first::<Project>(&db_conn).unwrap();
+        let envs = EnvironmentRepo::new(&db_conn)
+            .list_for_project(p.id)
+            .unwrap();
+        assert_eq!(
+            vec![("Development", "DEV"), ("UAT", "UAT")],
+            envs.iter()
+                .map(|e| (e.name.as_str(), e.short_label.as_str()))
+                .collect::<Vec<_>>()
+        );
+        let server = srv::server.first::<Server>(&db_conn).unwrap();
+        assert_eq!("UAT", server.environment);
+        // the address was split in host and port
+        assert_eq!(
+            ("254.245.33.34", Some(2222)),
+            (server.host.as_str(), server.port)
+        );
+    }
+
+    #[test]
+    fn import_attachments() {
+        let yaml = r##"
+---
+project_name: Demo
+attachments:
+  - attachments/license.txt
+environments:
+  - name: Development
+    short_label: DEV
+    items:
+      project_notes:
+        - title: VPN
+          attachments:
+            - VPN_attachments/office.ovpn
+"##;
+        let folder = export::temp_folder().unwrap();
+        for (path, contents) in &[
+            ("attachments/license.txt", "licensed"),
+            ("VPN_attachments/office.ovpn", "remote vpn"),
+        ] {
+            let path = folder.folder.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let db_conn = SqliteConnection::establish(":memory:").unwrap();
+        projectpadsql::migrations::migrate_db_if_needed(&db_conn, None).unwrap();
+        import_projects(
+            &db_conn,
+            vec![(PathBuf::from(""), serde_yaml::from_str(yaml).unwrap())],
+            &folder.folder,
+        )
+        .unwrap();
+
+        use projectpadsql::schema::project::dsl as prj;
+        use projectpadsql::schema::project_note::dsl as prj_note;
+        let project_id = prj::project.select(prj::id).first::<i32>(&db_conn).unwrap();
+        let note_id = prj_note::project_note
+            .select(prj_note::id)
+            .first::<i32>(&db_conn)
+            .unwrap();
+        let repo = AttachmentRepo::new(&db_conn);
+        for (entity_type, entity_id, name, contents) in &[
+            (EntityType::Project, project_id, "license.txt", "licensed"),
+            (
+                EntityType::ProjectNote,
+                note_id,
+                "office.ovpn",
+                "remote vpn",
+            ),
+        ] {
+            let attachments = repo.list_for_item(*entity_type, *entity_id).unwrap();
+            assert_eq!(
+                vec![*name],
+                attachments
+                    .iter()
+                    .map(|a| a.name.as_str())
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                contents.as_bytes().to_vec(),
+                repo.contents(attachments[0].id).unwrap()
+            );
+        }
+    }
 }
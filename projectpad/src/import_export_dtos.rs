@@ -1,11 +1,12 @@
 use projectpadsql::models::{
-    EnvironmentType, InterestType, Server, ServerAccessType, ServerDatabase, ServerNote,
-    ServerPointOfInterest, ServerType,
+    CustomFieldType, Environment, InterestType, Server, ServerAccessType, ServerAddress,
+    ServerDatabase, ServerNote, ServerPointOfInterest, ServerType,
 };
 use serde::de;
 use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
 use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt;
@@ -35,6 +36,18 @@ where
     }
 }
 
+fn serialize_if_not_empty<T, V>(map: &mut T, key: &str, value: &[V]) -> Result<(), T::Error>
+where
+    T: SerializeMap,
+    V: Serialize,
+{
+    if !value.is_empty() {
+        map.serialize_entry(key, value)
+    } else {
+        Ok(())
+    }
+}
+
 // TODO less work not embedding Server? See what was done
 // for ExtraUser...
 pub struct ServerImportExport {
@@ -58,13 +71,17 @@ impl Serialize for ServerImportExport {
         } else {
             state.serialize_entry("desc", &s.desc)?;
         }
-        serialize_if_present(&mut state, "ip", &s.ip)?;
+        serialize_if_some(&mut state, "protocol", &s.protocol)?;
+        serialize_if_present(&mut state, "host", &s.host)?;
+        serialize_if_some(&mut state, "port", &s.port)?;
+        serialize_if_present(&mut state, "path", &s.path)?;
         serialize_if_present(&mut state, "text", &s.text)?;
         if s.is_retired {
             state.serialize_entry("is_retired", &s.is_retired)?;
         }
         serialize_if_present(&mut state, "username", &s.username)?;
         serialize_if_present(&mut state, "password", &s.password)?;
+        serialize_if_present(&mut state, "otp_secret", &s.otp_secret)?;
 
         serialize_if_some(&mut state, "data_folder", &self.data_path)?; // TODO rename?
         serialize_if_some(&mut state, "auth_key_filename", &s.auth_key_filename)?;
@@ -94,11 +111,31 @@ impl<'de> Visitor<'de> for ServerImportExportMapVisitor {
         while let Some((key, value)) = access.next_entry()? {
             map.insert(key, value);
         }
+        let address = match map.get("ip") {
+            // older exports had the whole address in a single field
+            Some(ip) => ip.parse().unwrap_or_else(|_| ServerAddress {
+                host: ip.clone(),
+                ..ServerAddress::default()
+            }),
+            None => ServerAddress {
+                protocol: map.get("protocol").cloned(),
+                host: map.get("host").cloned().unwrap_or_else(|| "".to_string()),
+                port: map
+                    .get("port")
+                    .map(|p| p.parse())
+                    .transpose()
+                    .map_err(|_| de::Error::custom("invalid port"))?,
+                path: map.get("path").cloned().unwrap_or_else(|| "".to_string()),
+            },
+        };
         Ok(ServerImportExport {
             server: Server {
                 id: 0,
                 desc: map.get("desc").cloned().unwrap_or_else(|| "".to_string()),
-                ip: map.get("ip").cloned().unwrap_or_else(|| "".to_string()),
+                host: address.host,
+                port: address.port,
+                protocol: address.protocol,
+                path: address.path,
                 text: map.get("text").cloned().unwrap_or_else(|| "".to_string()),
                 is_retired: map.get("is_retired").map(|r| r == "true").unwrap_or(false),
                 username: map
@@ -109,6 +146,11 @@ impl<'de> Visitor<'de> for ServerImportExportMapVisitor {
                     .get("password")
                     .cloned()
                     .unwrap_or_else(|| "".to_string()),
+                password_changed_at: None,
+                otp_secret: map
+                    .get("otp_secret")
+                    .cloned()
+                    .unwrap_or_else(|| "".to_string()),
                 auth_key: None,
                 auth_key_filename: map
                     .get("auth_key_filename")
@@ -124,9 +166,10 @@ impl<'de> Visitor<'de> for ServerImportExportMapVisitor {
                     .ok_or_else(|| de::Error::custom("missing or invalid access_type"))?,
                 ssh_tunnel_port: None,
                 ssh_tunnel_through_server_id: None,
-                environment: EnvironmentType::EnvDevelopment,
+                environment: "".to_string(),
                 group_name: None,
                 project_id: 0,
+                deleted_at: None,
             },
             data_path: map
                 .get("data_folder") // TODO rename? (path_folder vs data_folder)
@@ -146,14 +189,22 @@ impl<'de> Deserialize<'de> for ServerImportExport {
 }
 
 #[derive(Deserialize)]
-pub struct ServerDatabaseImportExport(pub ServerDatabase);
+pub struct ServerDatabaseImportExport {
+    #[serde(flatten)]
+    pub db: ServerDatabase,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// by custom field name
+    #[serde(default)]
+    pub custom_fields: BTreeMap<String, String>,
+}
 
 impl Serialize for ServerDatabaseImportExport {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let s = &self.0;
+        let s = &self.db;
         let mut state = serializer.serialize_map(None)?;
 
         // we want to allow to link to any server (ServerWebsite may need to)
@@ -168,15 +219,32 @@ impl Serialize for ServerDatabaseImportExport {
         serialize_if_present(&mut state, "text", &s.text)?;
         serialize_if_present(&mut state, "username", &s.username)?;
         serialize_if_present(&mut state, "password", &s.password)?;
+        serialize_if_not_empty(&mut state, "tags", &self.tags)?;
+        if !self.custom_fields.is_empty() {
+            state.serialize_entry("custom_fields", &self.custom_fields)?;
+        }
 
         state.end()
     }
 }
 
+/// environment names, also accepting the codes of the fixed environments
+/// which were used before environments could be configured (EnvProd...)
+fn deserialize_environment_name<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    Ok(Environment::legacy_name(&name)
+        .map(|n| n.to_string())
+        .unwrap_or(name))
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ServerDatabasePath {
     pub project_name: String,
-    pub environment: EnvironmentType,
+    #[serde(deserialize_with = "deserialize_environment_name")]
+    pub environment: String,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub server_id: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
@@ -194,6 +262,8 @@ pub struct ServerExtraUserImportExport {
     #[serde(skip_serializing_if = "String::is_empty", default)]
     pub password: String,
     #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub otp_secret: String,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
     pub desc: String,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub data_path: Option<PathBuf>,
@@ -213,8 +283,15 @@ pub struct ServerWebsiteImportExport {
     pub username: String,
     #[serde(skip_serializing_if = "String::is_empty", default)]
     pub password: String,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub otp_secret: String,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub server_database: Option<ServerDatabasePath>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
+    /// by custom field name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub custom_fields: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -227,7 +304,8 @@ pub struct ServerLinkImportExport {
 #[derive(Serialize, Deserialize)]
 pub struct ServerPath {
     pub project_name: String,
-    pub environment: EnvironmentType,
+    #[serde(deserialize_with = "deserialize_environment_name")]
+    pub environment: String,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub server_id: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
@@ -237,34 +315,65 @@ pub struct ServerPath {
 #[derive(Serialize, Deserialize)]
 pub struct ProjectImportExport {
     pub project_name: String,
-    #[serde(skip_serializing_if = "Option::is_none", default)]
+    /// in the order of the environments in the project
+    #[serde(default)]
+    pub environments: Vec<ProjectEnvImportExport>,
+    /// the custom fields of the servers, databases and websites, in their order
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub custom_fields: Vec<CustomFieldImportExport>,
+    /// the paths of the attached files in the archive, relative to the project folder
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub attachments: Vec<PathBuf>,
+    // the fixed environments, before environments could be configured.
+    // only read on import, for the files exported by older versions
+    #[serde(skip_serializing, default)]
     pub development_environment: Option<ProjectEnvImportExport>,
-    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(skip_serializing, default)]
     pub staging_environment: Option<ProjectEnvImportExport>,
-    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(skip_serializing, default)]
     pub uat_environment: Option<ProjectEnvImportExport>,
-    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(skip_serializing, default)]
     pub prod_environment: Option<ProjectEnvImportExport>,
 }
 
 impl ProjectImportExport {
     pub fn dependencies_project_names(&self) -> HashSet<String> {
         let mut deps = HashSet::new();
-
-        if let Some(env) = &self.development_environment {
-            deps.extend(env.dependencies_project_names());
-        }
-        if let Some(env) = &self.staging_environment {
-            deps.extend(env.dependencies_project_names());
-        }
-        if let Some(env) = &self.uat_environment {
-            deps.extend(env.dependencies_project_names());
-        }
-        if let Some(env) = &self.prod_environment {
+        for env in &self.environments {
             deps.extend(env.dependencies_project_names());
         }
         deps
     }
+
+    /// move the fixed environments of files exported by older versions
+    /// to `environments`, with the settings of the default environments
+    pub fn upgrade_legacy_environments(&mut self) {
+        let legacy_envs = vec![
+            self.development_environment.take(),
+            self.staging_environment.take(),
+            self.uat_environment.take(),
+            self.prod_environment.take(),
+        ];
+        for (env, default) in legacy_envs.into_iter().zip(Environment::defaults(0)) {
+            if let Some(env) = env {
+                self.environments.push(ProjectEnvImportExport {
+                    name: default.name,
+                    short_label: default.short_label,
+                    color: default.color,
+                    dangerous: default.dangerous,
+                    ..env
+                });
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CustomFieldImportExport {
+    /// the items which have the field: server, server_database or server_website
+    pub items: String,
+    pub name: String,
+    pub field_type: CustomFieldType,
 }
 
 /// currently project POIs are present for all environments,
@@ -287,6 +396,8 @@ pub struct ProjectPoiImportExport {
     #[serde(default)]
     pub interest_type: InterestType,
     #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
     pub shared_with_other_environments: Option<String>,
 }
 
@@ -301,6 +412,7 @@ impl Serialize for ProjectPoiImportExport {
             serialize_if_present(&mut state, "path", &self.path)?;
             serialize_if_present(&mut state, "text", &self.text)?;
             state.serialize_entry("interest_type", &self.interest_type)?;
+            serialize_if_not_empty(&mut state, "tags", &self.tags)?;
         } else {
             state.serialize_entry(
                 "shared_with_other_environments",
@@ -322,6 +434,10 @@ pub struct ProjectNoteImportExport {
     #[serde(default)]
     pub contents: String,
     #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub attachments: Vec<PathBuf>,
+    #[serde(default)]
     pub shared_with_other_environments: Option<String>,
 }
 
@@ -334,6 +450,8 @@ impl Serialize for ProjectNoteImportExport {
         if self.shared_with_other_environments.is_none() {
             serialize_if_present(&mut state, "title", &self.title)?;
             serialize_if_present(&mut state, "contents", &self.contents)?;
+            serialize_if_not_empty(&mut state, "tags", &self.tags)?;
+            serialize_if_not_empty(&mut state, "attachments", &self.attachments)?;
         } else {
             state.serialize_entry("shared_with_other_environments", &self.title)?;
         }
@@ -343,6 +461,16 @@ impl Serialize for ProjectNoteImportExport {
 
 #[derive(Serialize, Deserialize)]
 pub struct ProjectEnvImportExport {
+    // the settings of the environment are missing in the fixed
+    // environments of older versions
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub short_label: String,
+    #[serde(default)]
+    pub color: String,
+    #[serde(default)]
+    pub dangerous: bool,
     pub items: ProjectEnvGroupImportExport,
     #[serde(skip_serializing_if = "HashMap::is_empty", default)]
     pub items_in_groups: HashMap<String, ProjectEnvGroupImportExport>,
@@ -389,6 +517,13 @@ impl ProjectEnvGroupImportExport {
 #[derive(Serialize, Deserialize)]
 pub struct ServerWithItemsImportExport {
     pub server: ServerImportExport,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub attachments: Vec<PathBuf>,
+    /// by custom field name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub custom_fields: BTreeMap<String, String>,
     pub items: ServerGroupImportExport,
     #[serde(skip_serializing_if = "HashMap::is_empty", default)]
     pub items_in_groups: HashMap<String, ServerGroupImportExport>,
@@ -404,16 +539,34 @@ impl ServerWithItemsImportExport {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct ServerPoiImportExport {
+    #[serde(flatten)]
+    pub poi: ServerPointOfInterest,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ServerNoteImportExport {
+    #[serde(flatten)]
+    pub note: ServerNote,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub attachments: Vec<PathBuf>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ServerGroupImportExport {
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
-    pub server_pois: Vec<ServerPointOfInterest>,
+    pub server_pois: Vec<ServerPoiImportExport>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub server_websites: Vec<ServerWebsiteImportExport>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub server_databases: Vec<ServerDatabaseImportExport>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
-    pub server_notes: Vec<ServerNote>,
+    pub server_notes: Vec<ServerNoteImportExport>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub server_extra_users: Vec<ServerExtraUserImportExport>,
 }
@@ -1,12 +1,8 @@
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
-use std::str;
 use std::sync::mpsc;
 use std::thread;
 
-// https://github.com/tilpner/includedir
-include!(concat!(env!("OUT_DIR"), "/data.rs"));
-
 // we do sql requests in a separate thread not to block the GUI thread
 // - i considered that spawning a new thread everytime the GUI wants to fetch
 //   from SQL seems more heavyweight than reusing a thread
@@ -38,27 +34,3 @@ pub fn start_sql_thread() -> mpsc::Sender<SqlFunc> {
 
     tx
 }
-
-pub fn migrate_db_if_needed(db_conn: &SqliteConnection) -> Result<(), Box<dyn std::error::Error>> {
-    use projectpadsql::schema::db_version::dsl as ver;
-    let mut version = projectpadsql::get_db_version(db_conn).unwrap_or(0) + 1;
-    let get_migration_name = |version| format!("resources/migrations/{:03}.sql", version);
-    loop {
-        let migration_name = get_migration_name(version);
-        if !MIGRATIONS.is_available(&migration_name) {
-            break;
-        }
-        println!("applying migration {}", version);
-        let migration_bytes = MIGRATIONS.get(&migration_name)?;
-        let migration_str = str::from_utf8(&migration_bytes)?;
-        db_conn.execute(migration_str)?;
-        diesel::insert_into(ver::db_version)
-            .values((
-                ver::code.eq(version),
-                ver::upgrade_date.eq(diesel::dsl::now),
-            ))
-            .execute(db_conn)?;
-        version += 1;
-    }
-    Ok(())
-}
@@ -1,9 +1,7 @@
 use diesel::prelude::*;
-use diesel::query_builder::IntoUpdateTarget;
 use diesel::query_dsl::methods::ExecuteDsl;
-use diesel::query_dsl::methods::FindDsl;
 use diesel::sqlite::SqliteConnection;
-use diesel::{associations::HasTable, helper_types::Find, query_builder::DeleteStatement};
+use projectpadsql::models::EntityType;
 
 no_arg_sql_function!(
     last_insert_rowid,
@@ -24,8 +22,8 @@ pub fn insert_row(
             // https://github.com/diesel-rs/diesel/issues/771
             // http://www.sqlite.org/c3ref/last_insert_rowid.html
             // caveats of last_insert_rowid seem to be in case of multiple
-            // threads sharing a connection (which we don't do), and triggers
-            // and other things (which we don't have).
+            // threads sharing a connection (which we don't do). The inserts
+            // done by our triggers don't affect it once the trigger is over.
             diesel::select(last_insert_rowid)
                 .get_result::<i32>(sql_conn)
                 .map_err(|e| {
@@ -43,33 +41,14 @@ pub fn insert_row(
     }
 }
 
-// https://stackoverflow.com/a/55213728/516188
-pub type DeleteFindStatement<F> =
-    DeleteStatement<<F as HasTable>::Table, <F as IntoUpdateTarget>::WhereClause>;
-
-pub fn delete_row<Tbl, Pk>(
+/// move an entity and its children to the trash, see projectpadsql::trash
+pub fn move_to_trash(
     sql_conn: &SqliteConnection,
-    table: Tbl,
-    pk: Pk,
-) -> Result<(), (&'static str, Option<String>)>
-where
-    Tbl: FindDsl<Pk>,
-    Find<Tbl, Pk>: IntoUpdateTarget,
-    DeleteFindStatement<Find<Tbl, Pk>>: ExecuteDsl<SqliteConnection>,
-{
-    let find = table.find(pk);
-    let delete = diesel::delete(find);
-    match delete.execute(sql_conn) {
-        Ok(1) => Ok(()),
-        Ok(x) => Err((
-            "Entity deletion failed",
-            Some(format!(
-                "Expected 1 row to be modified, but {} rows were modified",
-                x
-            )),
-        )),
-        Err(e) => Err(("Entity deletion failed", Some(e.to_string()))),
-    }
+    entity_type: EntityType,
+    id: i32,
+) -> Result<(), (&'static str, Option<String>)> {
+    projectpadsql::trash::move_to_trash(sql_conn, entity_type, id)
+        .map_err(|e| ("Entity deletion failed", Some(e.to_string())))
 }
 
 // I tried to implement this with generics with diesel... gave up.
@@ -0,0 +1,315 @@
+// the files attached to projects, servers and notes (certificates,
+// VPN profiles, license files...), see projectpadsql::repo::AttachmentRepo.
+// Unlike the auth keys, attachments are not edited with the item, the
+// changes are saved immediately.
+use super::dialogs::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use gtk::prelude::*;
+use projectpadsql::models::{Attachment, EntityType};
+use projectpadsql::repo::AttachmentRepo;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+
+pub struct AttachmentsRow {
+    label: gtk::Label,
+    vbox: gtk::Box,
+    db_sender: mpsc::Sender<SqlFunc>,
+    entity_type: EntityType,
+    entity_id: i32,
+    hide_when_empty: bool,
+}
+
+impl AttachmentsRow {
+    /// add an "Attachments" row to an item grid, as populated by `populate_grid`.
+    /// The row is hidden while the item has no attachments.
+    pub fn attach_to_grid(
+        grid: &gtk::Grid,
+        row: i32,
+        db_sender: &mpsc::Sender<SqlFunc>,
+        entity_type: EntityType,
+        entity_id: i32,
+    ) -> Rc<AttachmentsRow> {
+        Self::new(grid, row, db_sender, entity_type, entity_id, true)
+    }
+
+    fn new(
+        grid: &gtk::Grid,
+        row: i32,
+        db_sender: &mpsc::Sender<SqlFunc>,
+        entity_type: EntityType,
+        entity_id: i32,
+        hide_when_empty: bool,
+    ) -> Rc<AttachmentsRow> {
+        let label = gtk::LabelBuilder::new()
+            .label("Attachments")
+            .halign(gtk::Align::End) // right align as per gnome HIG
+            .valign(gtk::Align::Start)
+            .no_show_all(hide_when_empty)
+            .build();
+        label.get_style_context().add_class("item_label");
+        let vbox = gtk::BoxBuilder::new()
+            .orientation(gtk::Orientation::Vertical)
+            .no_show_all(hide_when_empty)
+            .build();
+        grid.attach(&label, 0, row, 1, 1);
+        grid.attach(&vbox, 1, row, 1, 1);
+        let attachments_row = Rc::new(AttachmentsRow {
+            label,
+            vbox,
+            db_sender: db_sender.clone(),
+            entity_type,
+            entity_id,
+            hide_when_empty,
+        });
+        attachments_row.reload();
+        attachments_row
+    }
+
+    pub fn reload(self: &Rc<Self>) {
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let (entity_type, entity_id) = (self.entity_type, self.entity_id);
+        self.db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                sender
+                    .send(AttachmentRepo::new(sql_conn).list_for_item(entity_type, entity_id))
+                    .unwrap();
+            }))
+            .unwrap();
+        // weak reference: the grid may have been cleared in the meantime
+        let row = Rc::downgrade(self);
+        receiver.attach(None, move |r: projectpadsql::Result<Vec<Attachment>>| {
+            if let Some(row) = row.upgrade() {
+                match r {
+                    Ok(attachments) => row.populate(&attachments),
+                    Err(e) => standard_dialogs::display_error(
+                        "Error loading the attachments",
+                        Some(Box::new(e)),
+                    ),
+                }
+            }
+            glib::Continue(false)
+        });
+    }
+
+    fn populate(self: &Rc<Self>, attachments: &[Attachment]) {
+        for child in self.vbox.get_children() {
+            self.vbox.remove(&child);
+        }
+        for attachment in attachments {
+            self.vbox.add(&self.attachment_row(attachment));
+        }
+        if attachments.is_empty() && !self.hide_when_empty {
+            let none_label = gtk::LabelBuilder::new().label("None").xalign(0.0).build();
+            none_label.get_style_context().add_class("dim-label");
+            self.vbox.add(&none_label);
+        }
+        let visible = !attachments.is_empty() || !self.hide_when_empty;
+        self.label.set_visible(visible);
+        self.vbox.set_visible(visible);
+        self.vbox.show_all();
+        if visible {
+            // populate_grid hides the grid of the items without fields, like notes
+            if let Some(grid) = self.vbox.get_parent() {
+                grid.show();
+            }
+        }
+    }
+
+    fn attachment_row(self: &Rc<Self>, attachment: &Attachment) -> gtk::Box {
+        let hbox = gtk::BoxBuilder::new().spacing(5).build();
+        let name_label = gtk::LabelBuilder::new()
+            .label(&attachment.name)
+            .xalign(0.0)
+            .ellipsize(pango::EllipsizeMode::Middle)
+            .tooltip_text(&attachment.mime)
+            .build();
+        hbox.add(&name_label);
+        let size_label = gtk::LabelBuilder::new()
+            .label(&format_size(attachment.size))
+            .build();
+        size_label.get_style_context().add_class("dim-label");
+        hbox.add(&size_label);
+
+        let save_btn = gtk::ButtonBuilder::new()
+            .image(&gtk::Image::from_icon_name(
+                Some("document-save-symbolic"),
+                gtk::IconSize::Menu,
+            ))
+            .relief(gtk::ReliefStyle::None)
+            .tooltip_text("Save as...")
+            .build();
+        // weak references: the buttons are owned by the row
+        let row = Rc::downgrade(self);
+        let a = attachment.clone();
+        save_btn.connect_clicked(move |_| {
+            if let Some(row) = row.upgrade() {
+                row.save_as(&a);
+            }
+        });
+        hbox.add(&save_btn);
+
+        let delete_btn = gtk::ButtonBuilder::new()
+            .image(&gtk::Image::from_icon_name(
+                Some("edit-delete-symbolic"),
+                gtk::IconSize::Menu,
+            ))
+            .relief(gtk::ReliefStyle::None)
+            .tooltip_text("Delete")
+            .build();
+        let row = Rc::downgrade(self);
+        let a = attachment.clone();
+        delete_btn.connect_clicked(move |btn| {
+            let row = row.clone();
+            let attachment_id = a.id;
+            standard_dialogs::confirm_deletion(
+                "Delete attachment",
+                &format!(
+                    "Are you sure you want to delete the attachment {}? This action cannot be undone.",
+                    a.name
+                ),
+                btn.clone().upcast::<gtk::Widget>(),
+                move || {
+                    if let Some(row) = row.upgrade() {
+                        row.run_and_reload(move |repo| repo.delete(attachment_id));
+                    }
+                },
+            );
+        });
+        hbox.add(&delete_btn);
+        hbox
+    }
+
+    /// pick a file and attach it to the item
+    pub fn attach_file(self: &Rc<Self>) {
+        let dialog = gtk::FileChooserNativeBuilder::new()
+            .action(gtk::FileChooserAction::Open)
+            .title("Select the file to attach")
+            .modal(true)
+            .build();
+        if dialog.run() != gtk::ResponseType::Accept {
+            return;
+        }
+        let picked = dialog.get_filename().and_then(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            Some((name, std::fs::read(&path)))
+        });
+        match picked {
+            Some((name, Ok(contents))) => {
+                let (entity_type, entity_id) = (self.entity_type, self.entity_id);
+                self.run_and_reload(move |repo| {
+                    repo.add(entity_type, entity_id, &name, &contents)
+                        .map(|_| ())
+                });
+            }
+            Some((_, Err(e))) => {
+                standard_dialogs::display_error("Error reading the file", Some(Box::new(e)))
+            }
+            None => standard_dialogs::display_error("Invalid filename selected", None),
+        }
+    }
+
+    fn save_as(&self, attachment: &Attachment) {
+        let dialog = gtk::FileChooserNativeBuilder::new()
+            .action(gtk::FileChooserAction::Save)
+            .title("Save the attachment")
+            .accept_label("Save")
+            .do_overwrite_confirmation(true)
+            .modal(true)
+            .build();
+        dialog.set_current_name(&attachment.name);
+        if dialog.run() != gtk::ResponseType::Accept {
+            return;
+        }
+        let path: PathBuf = match dialog.get_filename() {
+            Some(p) => p,
+            None => {
+                standard_dialogs::display_error("Invalid filename selected", None);
+                return;
+            }
+        };
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let attachment_id = attachment.id;
+        self.db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                sender
+                    .send(
+                        AttachmentRepo::new(sql_conn)
+                            .write_to_file(attachment_id, Path::new(&path)),
+                    )
+                    .unwrap();
+            }))
+            .unwrap();
+        receiver.attach(None, move |r: projectpadsql::Result<()>| {
+            if let Err(e) = r {
+                standard_dialogs::display_error("Error writing the file", Some(Box::new(e)));
+            }
+            glib::Continue(false)
+        });
+    }
+
+    fn run_and_reload(
+        self: &Rc<Self>,
+        action: impl Fn(&AttachmentRepo) -> projectpadsql::Result<()> + Send + 'static,
+    ) {
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        self.db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                sender.send(action(&AttachmentRepo::new(sql_conn))).unwrap();
+            }))
+            .unwrap();
+        let row = Rc::downgrade(self);
+        receiver.attach(None, move |r: projectpadsql::Result<()>| {
+            if let Err(e) = r {
+                standard_dialogs::display_error("Error saving the attachment", Some(Box::new(e)));
+            }
+            if let Some(row) = row.upgrade() {
+                row.reload();
+            }
+            glib::Continue(false)
+        });
+    }
+}
+
+fn format_size(size: i32) -> String {
+    match size {
+        s if s < 1024 => format!("{} B", s),
+        s if s < 1024 * 1024 => format!("{:.1} KiB", s as f64 / 1024.0),
+        s => format!("{:.1} MiB", s as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// a dialog listing the attachments of an item, for items which have no
+/// grid to display them, such as projects
+pub fn show_dialog(
+    widget_for_window: gtk::Widget,
+    db_sender: &mpsc::Sender<SqlFunc>,
+    entity_type: EntityType,
+    entity_id: i32,
+    title: &str,
+) {
+    let dialog = standard_dialogs::modal_dialog(
+        widget_for_window,
+        450,
+        250,
+        format!("{} attachments", title),
+    );
+    let grid = gtk::GridBuilder::new()
+        .margin(10)
+        .row_spacing(5)
+        .column_spacing(10)
+        .build();
+    let row = AttachmentsRow::new(&grid, 0, db_sender, entity_type, entity_id, false);
+    dialog.get_content_area().pack_start(&grid, true, true, 0);
+    let attach_btn = gtk::ButtonBuilder::new().label("Attach file...").build();
+    attach_btn.connect_clicked(move |_| row.attach_file());
+    if let Some(header_bar) = dialog
+        .get_header_bar()
+        .and_then(|h| h.downcast::<gtk::HeaderBar>().ok())
+    {
+        header_bar.pack_start(&attach_btn);
+    }
+    dialog.add_button("Close", gtk::ResponseType::Close);
+    dialog.connect_response(|d, _| d.close());
+    dialog.show_all();
+}
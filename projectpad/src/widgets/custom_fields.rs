@@ -0,0 +1,166 @@
+// the custom fields of the servers, databases and websites, as defined by
+// their project (see projectpadsql::repo::CustomFieldRepo). The secret
+// fields are masked, and decrypted only when copied.
+use super::dialogs::standard_dialogs;
+use super::master_passphrase;
+use crate::sql_thread::SqlFunc;
+use gtk::prelude::*;
+use itertools::Itertools;
+use projectpadsql::models::{CustomField, CustomFieldType, EntityType};
+use projectpadsql::repo::CustomFieldRepo;
+use projectpadsql::secrets;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+pub type ItemCustomFields = HashMap<(EntityType, i32), Vec<CustomField>>;
+
+/// load the custom fields of items, by entity type and id.
+/// Items without custom field values are missing from the map.
+pub fn load_item_custom_fields(
+    sql_conn: &diesel::SqliteConnection,
+    items: impl Iterator<Item = (EntityType, i32)>,
+) -> ItemCustomFields {
+    let repo = CustomFieldRepo::new(sql_conn);
+    let mut result = HashMap::new();
+    let by_type = items.into_group_map();
+    for (entity_type, ids) in by_type {
+        for (id, fields) in repo.list_for_items(entity_type, &ids).unwrap() {
+            result.insert((entity_type, id), fields);
+        }
+    }
+    result
+}
+
+/// add a row per custom field to an item grid, as populated by `populate_grid`.
+/// Returns the number of rows added.
+pub fn attach_to_grid(
+    grid: &gtk::Grid,
+    row: i32,
+    db_sender: &mpsc::Sender<SqlFunc>,
+    fields: &[CustomField],
+    on_copied: impl Fn() + 'static,
+) -> i32 {
+    let on_copied = Rc::new(on_copied);
+    for (idx, field) in fields.iter().enumerate() {
+        let label = gtk::LabelBuilder::new()
+            .label(&field.definition.name)
+            .halign(gtk::Align::End) // right align as per gnome HIG
+            .build();
+        label.get_style_context().add_class("item_label");
+        grid.attach(&label, 0, row + idx as i32, 1, 1);
+        let on_copied = on_copied.clone();
+        grid.attach(
+            &value_box(db_sender, field, move || on_copied()),
+            1,
+            row + idx as i32,
+            1,
+            1,
+        );
+    }
+    if !fields.is_empty() {
+        grid.show_all();
+    }
+    fields.len() as i32
+}
+
+/// like `attach_to_grid`, but loads the custom fields of the item first.
+/// The rows are inserted at `row` once they're loaded.
+pub fn load_and_insert_in_grid(
+    grid: &gtk::Grid,
+    row: i32,
+    db_sender: &mpsc::Sender<SqlFunc>,
+    entity_type: EntityType,
+    entity_id: i32,
+    on_copied: impl Fn() + 'static,
+) {
+    // holds the row while we load the fields
+    let placeholder = gtk::Label::new(None);
+    grid.attach(&placeholder, 0, row, 1, 1);
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    db_sender
+        .send(SqlFunc::new(move |sql_conn| {
+            sender
+                .send(CustomFieldRepo::new(sql_conn).list_for_item(entity_type, entity_id))
+                .unwrap();
+        }))
+        .unwrap();
+    let grid = grid.clone();
+    let db_sender = db_sender.clone();
+    // weak reference: the grid may have been cleared in the meantime
+    let placeholder = placeholder.downgrade();
+    let mut on_copied = Some(on_copied);
+    receiver.attach(None, move |r: projectpadsql::Result<Vec<CustomField>>| {
+        let still_displayed = placeholder
+            .upgrade()
+            .map_or(false, |p| p.get_parent().is_some());
+        if let (true, Some(on_copied)) = (still_displayed, on_copied.take()) {
+            grid.remove_row(row);
+            match r {
+                Ok(fields) => {
+                    for _ in 0..fields.len() {
+                        grid.insert_row(row);
+                    }
+                    attach_to_grid(&grid, row, &db_sender, &fields, on_copied);
+                }
+                Err(e) => standard_dialogs::display_error(
+                    "Error loading the custom fields",
+                    Some(Box::new(e)),
+                ),
+            }
+        }
+        glib::Continue(false)
+    });
+}
+
+fn value_box(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    field: &CustomField,
+    on_copied: impl Fn() + 'static,
+) -> gtk::Box {
+    let hbox = gtk::BoxBuilder::new().spacing(5).build();
+    // a value still encrypted after its field became a text field is masked too
+    let is_secret = field.definition.field_type == CustomFieldType::Secret
+        || secrets::is_encrypted(&field.value);
+    let markup = match field.definition.field_type {
+        _ if is_secret => "●●●●●●".to_string(),
+        CustomFieldType::Url => format!(
+            "<a href=\"{}\">{}</a>",
+            glib::markup_escape_text(&field.value),
+            glib::markup_escape_text(&field.value)
+        ),
+        _ => glib::markup_escape_text(&field.value).to_string(),
+    };
+    hbox.add(
+        &gtk::LabelBuilder::new()
+            .use_markup(true)
+            .label(&markup)
+            .xalign(0.0)
+            .single_line_mode(true)
+            .ellipsize(pango::EllipsizeMode::End)
+            .build(),
+    );
+    let copy_btn = gtk::ButtonBuilder::new()
+        .image(&gtk::Image::from_icon_name(
+            Some("edit-copy-symbolic"),
+            gtk::IconSize::Menu,
+        ))
+        .relief(gtk::ReliefStyle::None)
+        .tooltip_text(&format!("Copy the {}", field.definition.name))
+        .build();
+    let db_sender = db_sender.clone();
+    let value = field.value.clone();
+    let on_copied = Rc::new(on_copied);
+    copy_btn.connect_clicked(move |btn| {
+        let display = btn.get_display();
+        let on_copied = on_copied.clone();
+        master_passphrase::reveal(&db_sender, btn.upcast_ref(), &value, move |plain| {
+            if let Some(clip) = gtk::Clipboard::get_default(&display) {
+                clip.set_text(&plain);
+            }
+            on_copied();
+        });
+    });
+    hbox.add(&copy_btn);
+    hbox
+}
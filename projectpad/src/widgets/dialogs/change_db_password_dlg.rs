@@ -46,9 +46,9 @@ pub struct Model {
 }
 
 pub fn check_db_password(pass: &str) -> OpResult {
-    let db_conn =
-        SqliteConnection::establish(&projectpadsql::database_path().to_string_lossy()).unwrap();
-    projectpadsql::try_unlock_db(&db_conn, pass)
+    let db_path = projectpadsql::database_path();
+    let db_conn = SqliteConnection::establish(&db_path.to_string_lossy()).unwrap();
+    projectpadsql::try_unlock_db_file(&db_conn, pass, &db_path).map_err(|e| e.to_string())
 }
 
 fn set_db_password(db_conn: &SqliteConnection, pass: &str) -> Result<(), String> {
@@ -0,0 +1,237 @@
+// the entries for the custom fields of a server, database or website in its
+// add/edit dialog. The fields are defined by the project, in the project
+// dialog. Like the passwords, the secret values are encrypted before saving
+// if the master passphrase is enabled.
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use crate::widgets::master_passphrase;
+use gtk::prelude::*;
+use projectpadsql::models::{CustomField, CustomFieldDefinition, CustomFieldType, EntityType};
+use projectpadsql::repo::CustomFieldRepo;
+use projectpadsql::secrets;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+/// where to find the definitions of the custom fields: the databases
+/// and websites know only their server
+#[derive(Clone, Copy)]
+pub enum DefinitionsOf {
+    Project(i32),
+    ServerProject(i32),
+}
+
+type LoadResult = projectpadsql::Result<(Vec<CustomFieldDefinition>, Vec<CustomField>)>;
+
+struct FieldEntry {
+    definition: CustomFieldDefinition,
+    entry: gtk::Entry,
+    /// the value as stored, encrypted for the secrets if the
+    /// master passphrase is enabled
+    value: String,
+    /// the plain text of `value`, if the user revealed it
+    revealed: Rc<RefCell<Option<String>>>,
+}
+
+pub struct CustomFieldsEditor {
+    db_sender: mpsc::Sender<SqlFunc>,
+    /// None until the fields are loaded
+    entries: RefCell<Option<Vec<FieldEntry>>>,
+}
+
+impl CustomFieldsEditor {
+    /// add a row per custom field to a dialog grid, starting at `row`,
+    /// once the fields are loaded. `row` must be the last row of the grid.
+    pub fn attach_to_grid(
+        grid: &gtk::Grid,
+        row: i32,
+        db_sender: &mpsc::Sender<SqlFunc>,
+        entity_type: EntityType,
+        definitions_of: DefinitionsOf,
+        item_id: Option<i32>,
+    ) -> Rc<CustomFieldsEditor> {
+        let editor = Rc::new(CustomFieldsEditor {
+            db_sender: db_sender.clone(),
+            entries: RefCell::new(None),
+        });
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                let repo = CustomFieldRepo::new(sql_conn);
+                let definitions = match definitions_of {
+                    DefinitionsOf::Project(id) => repo.list_definitions(id, entity_type),
+                    DefinitionsOf::ServerProject(id) => {
+                        repo.list_definitions_for_server(id, entity_type)
+                    }
+                };
+                let values = match item_id {
+                    Some(id) => repo.list_for_item(entity_type, id),
+                    None => Ok(vec![]),
+                };
+                let result: LoadResult = definitions.and_then(|d| values.map(|v| (d, v)));
+                sender.send(result).unwrap();
+            }))
+            .unwrap();
+        let grid = grid.clone();
+        let weak_editor = Rc::downgrade(&editor);
+        receiver.attach(None, move |r: LoadResult| {
+            match (r, weak_editor.upgrade()) {
+                (Ok((definitions, values)), Some(editor)) => {
+                    editor.populate(&grid, row, definitions, &values)
+                }
+                (Err(e), _) => standard_dialogs::display_error(
+                    "Error loading the custom fields",
+                    Some(Box::new(e)),
+                ),
+                (_, None) => {}
+            }
+            glib::Continue(false)
+        });
+        editor
+    }
+
+    fn populate(
+        &self,
+        grid: &gtk::Grid,
+        row: i32,
+        definitions: Vec<CustomFieldDefinition>,
+        values: &[CustomField],
+    ) {
+        let mut entries = vec![];
+        for (idx, definition) in definitions.into_iter().enumerate() {
+            let value = values
+                .iter()
+                .find(|v| v.definition.id == definition.id)
+                .map(|v| v.value.clone())
+                .unwrap_or_else(|| "".to_string());
+            grid.attach(
+                &gtk::LabelBuilder::new()
+                    .label(&definition.name)
+                    .halign(gtk::Align::End)
+                    .build(),
+                0,
+                row + idx as i32,
+                1,
+                1,
+            );
+            let entry = gtk::EntryBuilder::new()
+                .hexpand(true)
+                .activates_default(true)
+                .text(&value)
+                .build();
+            let revealed = Rc::new(RefCell::new(None));
+            match definition.field_type {
+                CustomFieldType::Secret => self.init_secret_entry(&entry, &value, &revealed),
+                CustomFieldType::Url => entry.set_placeholder_text(Some("https://")),
+                CustomFieldType::Date => entry.set_placeholder_text(Some("YYYY-MM-DD")),
+                CustomFieldType::Text => {}
+            }
+            grid.attach(&entry, 1, row + idx as i32, 1, 1);
+            entries.push(FieldEntry {
+                definition,
+                entry,
+                value,
+                revealed,
+            });
+        }
+        grid.show_all();
+        self.entries.replace(Some(entries));
+    }
+
+    fn init_secret_entry(
+        &self,
+        entry: &gtk::Entry,
+        value: &str,
+        revealed: &Rc<RefCell<Option<String>>>,
+    ) {
+        entry.set_input_purpose(gtk::InputPurpose::Password);
+        entry.set_visibility(false);
+        entry.set_icon_from_icon_name(
+            gtk::EntryIconPosition::Secondary,
+            Some("view-reveal-symbolic"),
+        );
+        entry.set_icon_tooltip_text(gtk::EntryIconPosition::Secondary, Some("Reveal"));
+        let db_sender = self.db_sender.clone();
+        let value = value.to_string();
+        let revealed = revealed.clone();
+        entry.connect_icon_release(move |entry, _, _| {
+            let text = entry.get_text().to_string();
+            if entry.get_visibility() || !secrets::is_encrypted(&text) {
+                entry.set_visibility(!entry.get_visibility());
+                return;
+            }
+            let entry = entry.clone();
+            let revealed = revealed.clone();
+            let is_stored_value = text == value;
+            master_passphrase::reveal(&db_sender, entry.upcast_ref(), &text, move |plain| {
+                entry.set_text(&plain);
+                entry.set_visibility(true);
+                if is_stored_value {
+                    revealed.replace(Some(plain));
+                }
+            });
+        });
+    }
+
+    /// calls the callback with the values to save, as (definition id, value),
+    /// the secret values encrypted if the master passphrase is enabled. With
+    /// `None` if the fields were not loaded yet: leave them alone. Displays
+    /// an error and doesn't call the callback if a value is not valid.
+    pub fn request_values(
+        &self,
+        widget: &gtk::Widget,
+        cb: impl FnOnce(Option<Vec<(i32, String)>>) + 'static,
+    ) {
+        let entries = self.entries.borrow();
+        let entries = match entries.as_ref() {
+            Some(e) => e,
+            None => {
+                cb(None);
+                return;
+            }
+        };
+        let mut values = vec![];
+        let mut to_encrypt = vec![];
+        for field in entries {
+            let text = field.entry.get_text().to_string();
+            let field_type = field.definition.field_type;
+            let value = if field_type == CustomFieldType::Secret {
+                if field.revealed.borrow().as_ref() == Some(&text) {
+                    // revealed but not modified: keep the encrypted value
+                    field.value.clone()
+                } else {
+                    text
+                }
+            } else {
+                text.trim().to_string()
+            };
+            if value.is_empty() {
+                continue;
+            }
+            if field_type == CustomFieldType::Secret {
+                if !secrets::is_encrypted(&value) {
+                    to_encrypt.push(values.len());
+                }
+            } else if let Err(e) = field_type.check_value(&value) {
+                standard_dialogs::display_error_str(
+                    &format!("Invalid {}", field.definition.name),
+                    Some(e.to_string()),
+                );
+                return;
+            }
+            values.push((field.definition.id, value));
+        }
+        if to_encrypt.is_empty() {
+            cb(Some(values));
+            return;
+        }
+        master_passphrase::key_if_enabled(&self.db_sender, widget, move |key| {
+            if let Some(key) = key {
+                for idx in to_encrypt {
+                    values[idx].1 = key.encrypt(&values[idx].1);
+                }
+            }
+            cb(Some(values));
+        });
+    }
+}
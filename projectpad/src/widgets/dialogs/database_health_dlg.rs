@@ -0,0 +1,236 @@
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use diesel::sqlite::SqliteConnection;
+use gtk::prelude::*;
+use projectpadsql::check;
+use projectpadsql::check::{Fix, Issue, IssueKind};
+use relm::Widget;
+use relm_derive::{widget, Msg};
+use std::sync::mpsc;
+
+// String for details, because I can't pass Error across threads
+type IssueListResult = Result<Vec<Issue>, (&'static str, Option<String>)>;
+type FixResult = Result<(), (&'static str, Option<String>)>;
+
+#[derive(Msg)]
+pub enum Msg {
+    KeyPress(gdk::EventKey),
+    GotIssues(IssueListResult),
+    FixIssue(usize),
+    FixAll,
+    FixApplied(FixResult),
+    IssuesFixed,
+}
+
+pub struct Model {
+    relm: relm::Relm<DatabaseHealthDialog>,
+    db_sender: mpsc::Sender<SqlFunc>,
+    issues: Vec<Issue>,
+    _issues_channel: relm::Channel<IssueListResult>,
+    issues_sender: relm::Sender<IssueListResult>,
+    _fix_applied_channel: relm::Channel<FixResult>,
+    fix_applied_sender: relm::Sender<FixResult>,
+}
+
+fn issue_kind_desc(kind: IssueKind) -> &'static str {
+    match kind {
+        IssueKind::DanglingServerLink => "Dangling server link",
+        IssueKind::DanglingWebsiteDatabase => "Dangling website database",
+        IssueKind::DanglingTunnel => "Dangling ssh tunnel",
+        IssueKind::TunnelCycle => "Ssh tunnel loop",
+        IssueKind::DuplicateDescription => "Duplicate server description",
+        IssueKind::EmptyGroupName => "Empty group name",
+        IssueKind::UnreadableAuthKey => "Unreadable authentication key",
+    }
+}
+
+fn fix_desc(fix: &Fix) -> String {
+    match fix {
+        Fix::Delete => "Delete".to_string(),
+        Fix::MoveToTrash => "Move to trash".to_string(),
+        Fix::ClearTunnel => "Remove tunnel".to_string(),
+        Fix::ClearDatabase => "Remove database".to_string(),
+        Fix::Rename(desc) => format!("Rename to '{}'", desc),
+        Fix::ClearGroupName => "Remove from group".to_string(),
+        Fix::RemoveAuthKey => "Remove key".to_string(),
+    }
+}
+
+#[widget]
+impl Widget for DatabaseHealthDialog {
+    fn init_view(&mut self) {
+        self.fetch_issues();
+    }
+
+    fn model(relm: &relm::Relm<Self>, db_sender: mpsc::Sender<SqlFunc>) -> Model {
+        let stream = relm.stream().clone();
+        let (_issues_channel, issues_sender) =
+            relm::Channel::new(move |r| stream.emit(Msg::GotIssues(r)));
+        let stream2 = relm.stream().clone();
+        let (_fix_applied_channel, fix_applied_sender) =
+            relm::Channel::new(move |r| stream2.emit(Msg::FixApplied(r)));
+        Model {
+            relm: relm.clone(),
+            db_sender,
+            issues: vec![],
+            _issues_channel,
+            issues_sender,
+            _fix_applied_channel,
+            fix_applied_sender,
+        }
+    }
+
+    fn fetch_issues(&self) {
+        let s = self.model.issues_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    projectpadsql::check(sql_conn)
+                        .map_err(|e| ("Error checking the database", Some(e.to_string()))),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn run_fix(
+        &self,
+        action: impl Fn(&SqliteConnection) -> projectpadsql::Result<()> + Send + 'static,
+    ) {
+        let s = self.model.fix_applied_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    action(sql_conn).map_err(|e| ("Error fixing the issue", Some(e.to_string()))),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn populate_issues(&self) {
+        for child in self.widgets.issue_list.get_children() {
+            self.widgets.issue_list.remove(&child);
+        }
+        if self.model.issues.is_empty() {
+            self.widgets.issue_list.add(
+                &gtk::LabelBuilder::new()
+                    .label("No issue found.")
+                    .margin(10)
+                    .build(),
+            );
+        }
+        for (idx, issue) in self.model.issues.iter().enumerate() {
+            let row = gtk::BoxBuilder::new().margin(5).spacing(10).build();
+            let labels = gtk::BoxBuilder::new()
+                .orientation(gtk::Orientation::Vertical)
+                .hexpand(true)
+                .build();
+            labels.add(
+                &gtk::LabelBuilder::new()
+                    .label(&format!("{} > {}", issue.project_name, issue.title))
+                    .xalign(0.0)
+                    .ellipsize(pango::EllipsizeMode::End)
+                    .build(),
+            );
+            let details = gtk::LabelBuilder::new()
+                .label(&format!(
+                    "{}: {}",
+                    issue_kind_desc(issue.kind),
+                    issue.detail
+                ))
+                .xalign(0.0)
+                .ellipsize(pango::EllipsizeMode::End)
+                .build();
+            details.get_style_context().add_class("dim-label");
+            labels.add(&details);
+            row.add(&labels);
+
+            let fix_btn = gtk::ButtonBuilder::new()
+                .label(&fix_desc(&issue.fix))
+                .valign(gtk::Align::Center)
+                .build();
+            if issue.fix == Fix::Delete {
+                fix_btn.get_style_context().add_class("destructive-action");
+            }
+            relm::connect!(
+                self.model.relm,
+                &fix_btn,
+                connect_clicked(_),
+                Msg::FixIssue(idx)
+            );
+            row.add(&fix_btn);
+            self.widgets.issue_list.add(&row);
+        }
+        self.widgets
+            .fix_all_btn
+            .set_sensitive(!self.model.issues.is_empty());
+        self.widgets.issue_list.show_all();
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::KeyPress(key) => {
+                if key.get_keyval() == gdk::keys::constants::Escape {
+                    self.widgets.database_health_win.close();
+                }
+            }
+            Msg::GotIssues(Ok(issues)) => {
+                self.model.issues = issues;
+                self.populate_issues();
+            }
+            Msg::GotIssues(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
+            Msg::FixIssue(idx) => {
+                if let Some(issue) = self.model.issues.get(idx).cloned() {
+                    self.run_fix(move |sql_conn| check::fix(sql_conn, &issue));
+                }
+            }
+            Msg::FixAll => {
+                let issues = self.model.issues.clone();
+                self.run_fix(move |sql_conn| check::fix_all(sql_conn, &issues));
+            }
+            Msg::FixApplied(r) => {
+                if let Err((msg, e)) = r {
+                    standard_dialogs::display_error_str(msg, e);
+                }
+                // a fix can solve other issues too, check again
+                self.fetch_issues();
+                self.model.relm.stream().emit(Msg::IssuesFixed);
+            }
+            // meant for my parent
+            Msg::IssuesFixed => {}
+        }
+    }
+
+    view! {
+        #[name="database_health_win"]
+        gtk::Window {
+            titlebar: view! {
+                gtk::HeaderBar {
+                    title: Some("Database health"),
+                    show_close_button: true,
+                    #[name="fix_all_btn"]
+                    #[style_class="suggested-action"]
+                    gtk::Button {
+                        label: "Fix all",
+                        sensitive: false,
+                        clicked => Msg::FixAll,
+                    },
+                }
+            },
+            property_default_width: 700,
+            property_default_height: 400,
+            gtk::ScrolledWindow {
+                #[name="issue_list"]
+                gtk::ListBox {
+                    selection_mode: gtk::SelectionMode::None,
+                },
+            },
+            key_press_event(_, key) => (Msg::KeyPress(key.clone()), Inhibit(false)), // just for the ESC key.. surely there's a better way..
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use super::standard_dialogs;
 use crate::sql_thread::SqlFunc;
 use gtk::prelude::*;
+use projectpadsql::models::{EntityType, Tag};
+use projectpadsql::repo::{CustomFieldRepo, TagRepo};
+use projectpadsql::secrets;
+use projectpadsql::totp::Totp;
 use std::sync::mpsc;
 
 pub fn init_group_control(groups_store: &gtk::ListStore, group: &gtk::ComboBoxText) {
@@ -46,8 +50,21 @@ pub fn style_grid(grid: &gtk::Grid) {
     grid.set_column_spacing(10);
 }
 
+pub type GroupsResult = projectpadsql::Result<Vec<String>>;
+
+/// the group names are loaded on the sql thread, but errors
+/// must be displayed from the GUI thread
+pub fn groups_channel(
+    mut on_groups: impl FnMut(Vec<String>) + 'static,
+) -> (relm::Channel<GroupsResult>, relm::Sender<GroupsResult>) {
+    relm::Channel::new(move |groups: GroupsResult| match groups {
+        Ok(g) => on_groups(g),
+        Err(e) => standard_dialogs::display_error("Error loading the groups", Some(Box::new(e))),
+    })
+}
+
 pub fn fetch_server_groups(
-    groups_sender: &relm::Sender<Vec<String>>,
+    groups_sender: &relm::Sender<GroupsResult>,
     server_id: i32,
     db_sender: &mpsc::Sender<SqlFunc>,
 ) {
@@ -60,6 +77,67 @@ pub fn fetch_server_groups(
         .unwrap();
 }
 
+/// save the tags of an item, once the item itself was saved.
+/// `None` if the tags were not loaded or edited: leave them alone.
+pub fn save_item_tags<T>(
+    sql_conn: &diesel::SqliteConnection,
+    item_result: Result<T, (String, Option<String>)>,
+    entity_type: EntityType,
+    get_id: impl Fn(&T) -> i32,
+    tags_text: Option<&str>,
+) -> Result<T, (String, Option<String>)> {
+    match tags_text {
+        Some(text) => item_result.and_then(|item| {
+            TagRepo::new(sql_conn)
+                .set_for_item(entity_type, get_id(&item), &Tag::parse_names(text))
+                .map(|_| item)
+                .map_err(|e| ("Error saving the tags".to_string(), Some(e.to_string())))
+        }),
+        None => item_result,
+    }
+}
+
+/// save the custom field values of an item, as (definition id, value),
+/// once the item itself was saved. `None` if the fields were not loaded:
+/// leave them alone.
+pub fn save_item_custom_fields<T>(
+    sql_conn: &diesel::SqliteConnection,
+    item_result: Result<T, (String, Option<String>)>,
+    entity_type: EntityType,
+    get_id: impl Fn(&T) -> i32,
+    values: Option<&[(i32, String)]>,
+) -> Result<T, (String, Option<String>)> {
+    match values {
+        Some(v) => item_result.and_then(|item| {
+            CustomFieldRepo::new(sql_conn)
+                .set_for_item(entity_type, get_id(&item), v)
+                .map(|_| item)
+                .map_err(|e| {
+                    (
+                        "Error saving the custom fields".to_string(),
+                        Some(e.to_string()),
+                    )
+                })
+        }),
+        None => item_result,
+    }
+}
+
+/// check the OTP secret the user typed, `None` if it wasn't edited.
+/// Displays an error and returns false if it's not valid.
+pub fn check_otp_secret(edited: Option<&str>) -> bool {
+    match edited.filter(|s| !s.is_empty() && !secrets::is_encrypted(s)) {
+        Some(s) => match s.parse::<Totp>() {
+            Ok(_) => true,
+            Err(e) => {
+                standard_dialogs::display_error_str("Invalid OTP secret", Some(e.to_string()));
+                false
+            }
+        },
+        None => true,
+    }
+}
+
 pub trait ServerItemDialogModelParam<T> {
     fn get_item(&self) -> Option<&T>;
     fn get_accel_group(&self) -> &gtk::AccelGroup;
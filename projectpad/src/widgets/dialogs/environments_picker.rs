@@ -1,68 +1,58 @@
 use gtk::prelude::*;
-use projectpadsql::models::EnvironmentType;
+use projectpadsql::models::Environment;
 use relm::Widget;
 use relm_derive::{widget, Msg};
 
 #[derive(Msg)]
 pub enum Msg {
-    EnvironmentToggled(EnvironmentType),
-}
-
-#[derive(Clone)]
-pub struct SelectedEnvironments {
-    pub has_dev: bool,
-    pub has_stg: bool,
-    pub has_uat: bool,
-    pub has_prod: bool,
+    /// the environments of the project, and the ids of the selected ones
+    SetEnvironments(Vec<Environment>, Vec<i32>),
+    EnvironmentToggled(i32),
 }
 
 pub struct Model {
-    selected_environments: SelectedEnvironments,
+    relm: relm::Relm<EnvironmentsPicker>,
 }
 
 #[widget]
 impl Widget for EnvironmentsPicker {
     fn init_view(&mut self) {}
 
-    fn model(_relm: &relm::Relm<Self>, selected_environments: SelectedEnvironments) -> Model {
-        Model {
-            selected_environments,
-        }
+    fn model(relm: &relm::Relm<Self>, _: ()) -> Model {
+        Model { relm: relm.clone() }
     }
 
-    fn update(&mut self, _event: Msg) {}
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::SetEnvironments(envs, selected_ids) => {
+                for child in self.widgets.envs_box.get_children() {
+                    self.widgets.envs_box.remove(&child);
+                }
+                for env in &envs {
+                    let btn = gtk::ToggleButtonBuilder::new()
+                        .label(&env.name)
+                        .hexpand(true)
+                        .active(selected_ids.contains(&env.id))
+                        .build();
+                    relm::connect!(
+                        self.model.relm,
+                        &btn,
+                        connect_toggled(_),
+                        Msg::EnvironmentToggled(env.id)
+                    );
+                    self.widgets.envs_box.add(&btn);
+                }
+                self.widgets.envs_box.show_all();
+            }
+            // meant for my parent
+            Msg::EnvironmentToggled(_) => {}
+        }
+    }
 
     view! {
+        #[name="envs_box"]
         #[style_class="linked"]
         gtk::Box {
-            #[name="radio_dev"]
-            gtk::ToggleButton {
-                label: "Dev",
-                hexpand: true,
-                active: self.model.selected_environments.has_dev,
-                toggled => Msg::EnvironmentToggled(EnvironmentType::EnvDevelopment),
-            },
-            #[name="radio_stg"]
-            gtk::ToggleButton {
-                label: "Stg",
-                hexpand: true,
-                active: self.model.selected_environments.has_stg,
-                toggled => Msg::EnvironmentToggled(EnvironmentType::EnvStage),
-            },
-            #[name="radio_uat"]
-            gtk::ToggleButton {
-                label: "Uat",
-                hexpand: true,
-                active: self.model.selected_environments.has_uat,
-                toggled => Msg::EnvironmentToggled(EnvironmentType::EnvUat),
-            },
-            #[name="radio_prd"]
-            gtk::ToggleButton {
-                label: "Prod",
-                hexpand: true,
-                active: self.model.selected_environments.has_prod,
-                toggled => Msg::EnvironmentToggled(EnvironmentType::EnvProd),
-            },
         }
     }
 }
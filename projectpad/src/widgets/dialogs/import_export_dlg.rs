@@ -3,6 +3,7 @@ use super::standard_dialogs;
 use crate::export;
 use crate::import;
 use crate::sql_thread::SqlFunc;
+use crate::widgets::master_passphrase;
 use crate::widgets::password_field;
 use crate::widgets::password_field::Msg::PublishPassword as PasswordFieldMsgPublishPassword;
 use crate::widgets::password_field::PasswordField;
@@ -11,6 +12,7 @@ use diesel::prelude::*;
 use gtk::prelude::*;
 use itertools::Itertools;
 use projectpadsql::models::Project;
+use projectpadsql::secrets;
 use relm::{Component, Widget};
 use relm_derive::{widget, Msg};
 use std::collections::HashSet;
@@ -310,6 +312,7 @@ impl Widget for ImportExportDialog {
                 sender
                     .send(
                         prj::project
+                            .filter(prj::deleted_at.is_null())
                             .order(prj::name.asc())
                             .load(sql_conn)
                             .map_err(|e| format!("Error loading projects: {:?}", e)),
@@ -340,10 +343,6 @@ impl Widget for ImportExportDialog {
     }
 
     fn do_import(&self, pass: String) {
-        self.model
-            .header
-            .stream()
-            .emit(HeaderMsg::EnableNext(false));
         match self.widgets.import_picker_btn.get_filename() {
             None => {
                 // shouldn't happen, but i don't want to crash
@@ -351,20 +350,39 @@ impl Widget for ImportExportDialog {
             }
             Some(fname) => {
                 let import_result_sender = self.model.import_result_sender.clone();
-                self.model
-                    .db_sender
-                    .send(SqlFunc::new(move |sql_conn| {
-                        import_result_sender
-                            .send(
-                                sql_conn
-                                    .transaction(|| {
-                                        import::do_import(sql_conn, &fname.to_string_lossy(), &pass)
-                                    })
-                                    .map_err(|e| e.to_string()),
-                            )
+                let db_sender = self.model.db_sender.clone();
+                let header_stream = self.model.header.stream().clone();
+                // the imported passwords must be encrypted if we have a master passphrase
+                master_passphrase::key_if_enabled(
+                    &self.model.db_sender,
+                    self.widgets.import_win.upcast_ref(),
+                    move |key| {
+                        header_stream.emit(HeaderMsg::EnableNext(false));
+                        db_sender
+                            .send(SqlFunc::new(move |sql_conn| {
+                                import_result_sender
+                                    .send(
+                                        sql_conn
+                                            .transaction(|| {
+                                                import::do_import(
+                                                    sql_conn,
+                                                    &fname.to_string_lossy(),
+                                                    &pass,
+                                                )?;
+                                                if let Some(key) = &key {
+                                                    secrets::encrypt_plain_passwords(
+                                                        sql_conn, key,
+                                                    )?;
+                                                }
+                                                Ok(())
+                                            })
+                                            .map_err(|e: Box<dyn std::error::Error>| e.to_string()),
+                                    )
+                                    .unwrap();
+                            }))
                             .unwrap();
-                    }))
-                    .unwrap();
+                    },
+                );
             }
         }
     }
@@ -382,21 +400,32 @@ impl Widget for ImportExportDialog {
                     .cloned()
             })
             .collect();
-        self.model
-            .header
-            .stream()
-            .emit(HeaderMsg::EnableNext(false));
+        let header_stream = self.model.header.stream().clone();
         let s = self.model.export_result_sender.clone();
-        self.model
-            .db_sender
-            .send(SqlFunc::new(move |sql_conn| {
-                s.send(
-                    export::export_projects(sql_conn, &selected_projects, &fname, &pass)
-                        .map_err(|e| e.to_string()),
-                )
-                .unwrap();
-            }))
-            .unwrap();
+        let db_sender = self.model.db_sender.clone();
+        // the exported passwords must be readable without our master passphrase
+        master_passphrase::key_if_enabled(
+            &self.model.db_sender,
+            self.widgets.import_win.upcast_ref(),
+            move |key| {
+                header_stream.emit(HeaderMsg::EnableNext(false));
+                db_sender
+                    .send(SqlFunc::new(move |sql_conn| {
+                        let export = || {
+                            export::export_projects(sql_conn, &selected_projects, &fname, &pass)
+                                .map_err(|e| e.to_string())
+                        };
+                        s.send(match &key {
+                            Some(key) => secrets::with_plain_passwords(sql_conn, key, export)
+                                .map_err(|e| e.to_string())
+                                .and_then(|r| r),
+                            None => export(),
+                        })
+                        .unwrap();
+                    }))
+                    .unwrap();
+            },
+        );
     }
 
     view! {
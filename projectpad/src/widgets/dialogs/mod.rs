@@ -3,10 +3,13 @@ pub mod dialog_helpers;
 #[macro_use]
 pub mod server_add_item_dlg;
 mod change_db_password_dlg;
+mod custom_fields_editor;
+pub mod database_health_dlg;
 mod environments_picker;
 mod file_contents_button;
 pub mod import_export_dlg;
 mod note_edit;
+pub mod password_audit_dlg;
 mod pick_projectpad_item_button;
 pub mod preferences;
 pub mod project_add_edit_dlg;
@@ -15,12 +18,17 @@ pub mod project_note_add_edit_dlg;
 pub mod project_poi_add_edit_dlg;
 pub mod server_add_edit_dlg;
 pub mod server_database_add_edit_dlg;
+pub mod server_dependencies_dlg;
 pub mod server_extra_user_add_edit_dlg;
 pub mod server_link_add_edit_dlg;
 pub mod server_note_add_edit_dlg;
 pub mod server_poi_add_edit_dlg;
+pub mod server_topology_dlg;
 pub mod server_website_add_edit_dlg;
 pub mod standard_dialogs;
+pub mod sync_dlg;
+mod tags_entry;
+pub mod trash_dlg;
 pub mod unlock_db_dlg;
 
 pub enum ServerAddEditDialogComponent {
@@ -0,0 +1,157 @@
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use chrono::prelude::*;
+use gtk::prelude::*;
+use projectpadsql::models::EntityType;
+use projectpadsql::password_audit;
+use projectpadsql::password_audit::OverduePassword;
+use relm::Widget;
+use relm_derive::{widget, Msg};
+use std::sync::mpsc;
+
+// String for details, because I can't pass Error across threads
+type OverdueListResult = Result<Vec<OverduePassword>, (&'static str, Option<String>)>;
+
+#[derive(Msg)]
+pub enum Msg {
+    KeyPress(gdk::EventKey),
+    GotOverduePasswords(OverdueListResult),
+}
+
+pub struct Model {
+    db_sender: mpsc::Sender<SqlFunc>,
+    _overdue_channel: relm::Channel<OverdueListResult>,
+    overdue_sender: relm::Sender<OverdueListResult>,
+}
+
+fn entity_type_desc(entity_type: EntityType) -> &'static str {
+    match entity_type {
+        EntityType::ServerDatabase => "Database",
+        EntityType::ServerWebsite => "Website",
+        EntityType::ServerExtraUserAccount => "Extra user",
+        _ => "Server",
+    }
+}
+
+fn age_desc(now: NaiveDateTime, overdue: &OverduePassword) -> String {
+    match overdue.password_changed_at {
+        Some(changed_at) => format!(
+            "changed on {}, {} days ago",
+            changed_at.format("%Y-%m-%d"),
+            (now - changed_at).num_days()
+        ),
+        None => "never recorded as changed".to_string(),
+    }
+}
+
+#[widget]
+impl Widget for PasswordAuditDialog {
+    fn init_view(&mut self) {
+        self.fetch_overdue_passwords();
+    }
+
+    fn model(relm: &relm::Relm<Self>, db_sender: mpsc::Sender<SqlFunc>) -> Model {
+        let stream = relm.stream().clone();
+        let (_overdue_channel, overdue_sender) =
+            relm::Channel::new(move |r| stream.emit(Msg::GotOverduePasswords(r)));
+        Model {
+            db_sender,
+            _overdue_channel,
+            overdue_sender,
+        }
+    }
+
+    fn fetch_overdue_passwords(&self) {
+        let s = self.model.overdue_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    password_audit::overdue_passwords(sql_conn, Utc::now().naive_utc())
+                        .map_err(|e| ("Error loading the overdue passwords", Some(e.to_string()))),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn populate_overdue_passwords(&self, items: Vec<OverduePassword>) {
+        let now = Utc::now().naive_utc();
+        for child in self.widgets.overdue_list.get_children() {
+            self.widgets.overdue_list.remove(&child);
+        }
+        if items.is_empty() {
+            self.widgets.overdue_list.add(
+                &gtk::LabelBuilder::new()
+                    .label("No password is overdue for rotation.")
+                    .margin(10)
+                    .build(),
+            );
+        }
+        for item in &items {
+            let labels = gtk::BoxBuilder::new()
+                .orientation(gtk::Orientation::Vertical)
+                .margin(5)
+                .build();
+            labels.add(
+                &gtk::LabelBuilder::new()
+                    .label(&item.title)
+                    .xalign(0.0)
+                    .ellipsize(pango::EllipsizeMode::End)
+                    .build(),
+            );
+            let details = gtk::LabelBuilder::new()
+                .label(&format!(
+                    "{} on {} ({}) in {}: {}, the policy is {} days",
+                    entity_type_desc(item.entity_type),
+                    item.server_desc,
+                    item.environment,
+                    item.project_name,
+                    age_desc(now, item),
+                    item.max_age_days
+                ))
+                .xalign(0.0)
+                .ellipsize(pango::EllipsizeMode::End)
+                .build();
+            details.get_style_context().add_class("dim-label");
+            labels.add(&details);
+            self.widgets.overdue_list.add(&labels);
+        }
+        self.widgets.overdue_list.show_all();
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::KeyPress(key) => {
+                if key.get_keyval() == gdk::keys::constants::Escape {
+                    self.widgets.password_audit_win.close();
+                }
+            }
+            Msg::GotOverduePasswords(Ok(items)) => self.populate_overdue_passwords(items),
+            Msg::GotOverduePasswords(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
+        }
+    }
+
+    view! {
+        #[name="password_audit_win"]
+        gtk::Window {
+            titlebar: view! {
+                gtk::HeaderBar {
+                    title: Some("Overdue passwords"),
+                    show_close_button: true,
+                }
+            },
+            property_default_width: 600,
+            property_default_height: 400,
+            gtk::ScrolledWindow {
+                #[name="overdue_list"]
+                gtk::ListBox {
+                    selection_mode: gtk::SelectionMode::None,
+                },
+            },
+            key_press_event(_, key) => (Msg::KeyPress(key.clone()), Inhibit(false)), // just for the ESC key.. surely there's a better way..
+        }
+    }
+}
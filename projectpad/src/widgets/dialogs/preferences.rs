@@ -1,6 +1,8 @@
 use super::super::keyring_helpers;
+use super::super::master_passphrase;
 use super::super::password_field;
 use super::super::password_field::Msg as PasswordFieldMsg;
+use super::super::wintitlebar::WinTitleBar;
 use super::change_db_password_dlg;
 use super::change_db_password_dlg::ChangeDbPasswordDialog;
 use super::change_db_password_dlg::Msg as MsgChangeDbPassword;
@@ -8,6 +10,7 @@ use super::standard_dialogs;
 use crate::config::Config;
 use crate::sql_thread::SqlFunc;
 use gtk::prelude::*;
+use projectpadsql::{backup, secrets};
 use relm::{Component, Widget};
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
@@ -15,6 +18,7 @@ use std::sync::mpsc;
 #[derive(Msg)]
 pub enum Msg {
     DarkThemeToggled(bool),
+    TrashRetentionDaysChanged(u32),
     GotStorePassInKeyring(bool),
     RemovePasswordFromKeyring,
     RemovePasswordFromKeyringConfigCheckPass(String),
@@ -23,12 +27,18 @@ pub enum Msg {
     KeyPress(gdk::EventKey),
     ConfigUpdated(Box<Config>),
     ChangedPass(gtk::Dialog),
+    GotMasterPassphraseState(Result<bool, String>),
+    EnableMasterPassphrase,
+    RemoveMasterPassphrase,
+    RestoreBackup,
+    BackupRestored(Result<i32, String>),
 }
 
 pub struct Model {
     relm: relm::Relm<Preferences>,
     db_sender: mpsc::Sender<SqlFunc>,
     prefer_dark_theme: bool,
+    trash_retention_days: u32,
     win: gtk::Window,
     config: Config,
     confirm_dialog: Option<gtk::MessageDialog>,
@@ -36,13 +46,19 @@ pub struct Model {
     pass_keyring_sender: relm::Sender<bool>,
     _pass_keyring_channel: relm::Channel<bool>,
     change_db_password_dlg: Option<Component<ChangeDbPasswordDialog>>,
+    // whether the master passphrase is enabled
+    master_passphrase_sender: relm::Sender<Result<bool, String>>,
+    _master_passphrase_channel: relm::Channel<Result<bool, String>>,
     remove_pass_from_keyring_spinner: gtk::Spinner,
+    restore_backup_sender: relm::Sender<Result<i32, String>>,
+    _restore_backup_channel: relm::Channel<Result<i32, String>>,
 }
 
 #[widget]
 impl Widget for Preferences {
     fn init_view(&mut self) {
         self.load_keyring_pass_state();
+        self.load_master_passphrase_state();
         let remove_pass_btn_contents = gtk::BoxBuilder::new().build();
         self.model.remove_pass_from_keyring_spinner.start();
         remove_pass_btn_contents.add(&self.model.remove_pass_from_keyring_spinner);
@@ -63,6 +79,14 @@ impl Widget for Preferences {
             "The database file is in <a href=\"file://{}\">{}</a>",
             &db_folder_path, &db_path
         ));
+        let backups_folder = backup::backups_folder();
+        self.widgets.backups_label.set_markup(&format!(
+            "A backup of the database is taken every day in <a href=\"file://{}\">{}</a>, \
+             the last {} are kept",
+            backups_folder.to_string_lossy(),
+            backups_folder.to_string_lossy(),
+            backup::DAILY_BACKUPS_KEPT
+        ));
     }
 
     fn model(relm: &relm::Relm<Self>, params: (gtk::Window, mpsc::Sender<SqlFunc>)) -> Model {
@@ -71,18 +95,31 @@ impl Widget for Preferences {
         let stream = relm.stream().clone();
         let (_pass_keyring_channel, pass_keyring_sender) =
             relm::Channel::new(move |r: bool| stream.emit(Msg::GotStorePassInKeyring(r)));
+        let stream2 = relm.stream().clone();
+        let (_master_passphrase_channel, master_passphrase_sender) =
+            relm::Channel::new(move |r: Result<bool, String>| {
+                stream2.emit(Msg::GotMasterPassphraseState(r))
+            });
+        let stream3 = relm.stream().clone();
+        let (_restore_backup_channel, restore_backup_sender) =
+            relm::Channel::new(move |r: Result<i32, String>| stream3.emit(Msg::BackupRestored(r)));
         Model {
             relm: relm.clone(),
             db_sender,
             prefer_dark_theme: config.prefer_dark_theme,
+            trash_retention_days: config.trash_retention_days,
             config,
             win,
             pass_keyring_sender,
             _pass_keyring_channel,
+            master_passphrase_sender,
+            _master_passphrase_channel,
             change_db_password_dlg: None,
             remove_pass_from_keyring_spinner: gtk::SpinnerBuilder::new().build(),
             confirm_dialog: None,
             confirm_ok_btn: None,
+            restore_backup_sender,
+            _restore_backup_channel,
         }
     }
 
@@ -99,6 +136,17 @@ impl Widget for Preferences {
             .unwrap();
     }
 
+    fn load_master_passphrase_state(&self) {
+        let s = self.model.master_passphrase_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(secrets::is_enabled(sql_conn).map_err(|e| e.to_string()))
+                    .unwrap();
+            }))
+            .unwrap();
+    }
+
     fn update_config(&self) {
         self.model.config.save_config(&self.model.win);
         self.model
@@ -123,6 +171,10 @@ impl Widget for Preferences {
                 self.model.config.prefer_dark_theme = t;
                 self.update_config();
             }
+            Msg::TrashRetentionDaysChanged(days) => {
+                self.model.config.trash_retention_days = days;
+                self.update_config();
+            }
             Msg::RemovePasswordFromKeyring => {
                 self.remove_pass_from_keyring();
             }
@@ -183,6 +235,36 @@ impl Widget for Preferences {
             Msg::ConfigUpdated(_) => {
                 // meant for my parent, not for me
             }
+            Msg::GotMasterPassphraseState(Ok(enabled)) => {
+                self.widgets
+                    .enable_master_passphrase
+                    .set_sensitive(!enabled);
+                self.widgets.remove_master_passphrase.set_sensitive(enabled);
+            }
+            Msg::GotMasterPassphraseState(Err(e)) => {
+                standard_dialogs::display_error_str(
+                    "Error updating the master passphrase",
+                    Some(e),
+                );
+                self.load_master_passphrase_state();
+            }
+            Msg::EnableMasterPassphrase => {
+                self.enable_master_passphrase();
+            }
+            Msg::RemoveMasterPassphrase => {
+                self.remove_master_passphrase();
+            }
+            Msg::RestoreBackup => {
+                self.restore_backup();
+            }
+            Msg::BackupRestored(Ok(_)) => {
+                // the connection of the sql thread still
+                // sees the file which was replaced
+                WinTitleBar::switch_profile(&projectpadsql::profile::current());
+            }
+            Msg::BackupRestored(Err(e)) => {
+                standard_dialogs::display_error_str("Error restoring the backup", Some(e));
+            }
         }
     }
 
@@ -227,6 +309,165 @@ impl Widget for Preferences {
         dialog.show();
     }
 
+    fn enable_master_passphrase(&self) {
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.widgets.prefs_win),
+            gtk::DialogFlags::all(),
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            "Protect the passwords with a master passphrase?",
+        );
+        dialog.set_property_secondary_text(Some(
+            "The passwords will be encrypted, and you'll need the master passphrase to read them. \
+             There is no way to recover them if you forget it.",
+        ));
+        let passphrase_entry = |placeholder: &str| {
+            gtk::EntryBuilder::new()
+                .input_purpose(gtk::InputPurpose::Password)
+                .visibility(false)
+                .placeholder_text(placeholder)
+                .margin_start(25)
+                .margin_end(25)
+                .build()
+        };
+        let pass = passphrase_entry("Master passphrase");
+        let confirm = passphrase_entry("Confirm the master passphrase");
+        confirm.set_activates_default(true);
+        dialog.get_content_area().add(&pass);
+        dialog.get_content_area().add(&confirm);
+
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        let ok_btn = dialog.add_button("Protect", gtk::ResponseType::Ok);
+        ok_btn.set_sensitive(false);
+        dialog.set_default_response(gtk::ResponseType::Ok);
+        let check_match = {
+            let pass = pass.clone();
+            let confirm = confirm.clone();
+            move |_: &gtk::Entry| {
+                ok_btn.set_sensitive(
+                    !pass.get_text().is_empty() && pass.get_text() == confirm.get_text(),
+                )
+            }
+        };
+        pass.connect_changed(check_match.clone());
+        confirm.connect_changed(check_match);
+
+        let db_sender = self.model.db_sender.clone();
+        let s = self.model.master_passphrase_sender.clone();
+        dialog.connect_response(move |dlg, resp| {
+            if resp == gtk::ResponseType::Ok {
+                let passphrase = pass.get_text().to_string();
+                let s = s.clone();
+                db_sender
+                    .send(SqlFunc::new(move |sql_conn| {
+                        s.send(
+                            secrets::enable(sql_conn, &passphrase)
+                                .map(|_| true)
+                                .map_err(|e| e.to_string()),
+                        )
+                        .unwrap();
+                    }))
+                    .unwrap();
+            }
+            dlg.close();
+        });
+        dialog.show_all();
+    }
+
+    fn remove_master_passphrase(&self) {
+        // ask for the passphrase even if we have the key, to confirm
+        master_passphrase::lock();
+        let db_sender = self.model.db_sender.clone();
+        let s = self.model.master_passphrase_sender.clone();
+        master_passphrase::with_key(
+            &self.model.db_sender,
+            self.widgets.prefs_win.upcast_ref(),
+            move |key| {
+                master_passphrase::lock();
+                db_sender
+                    .send(SqlFunc::new(move |sql_conn| {
+                        s.send(
+                            secrets::disable(sql_conn, &key)
+                                .map(|_| false)
+                                .map_err(|e| e.to_string()),
+                        )
+                        .unwrap();
+                    }))
+                    .unwrap();
+            },
+        );
+    }
+
+    fn restore_backup(&self) {
+        let chooser = gtk::FileChooserNativeBuilder::new()
+            .action(gtk::FileChooserAction::Open)
+            .title("Select the backup to restore")
+            .modal(true)
+            .build();
+        chooser.set_current_folder(backup::backups_folder());
+        if chooser.run() != gtk::ResponseType::Accept {
+            return;
+        }
+        let backup_path = match chooser.get_filename() {
+            Some(p) => p,
+            None => {
+                standard_dialogs::display_error("Invalid filename selected", None);
+                return;
+            }
+        };
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.widgets.prefs_win),
+            gtk::DialogFlags::all(),
+            gtk::MessageType::Warning,
+            gtk::ButtonsType::None,
+            "Replace the database with the backup?",
+        );
+        dialog.set_property_secondary_text(Some(&format!(
+            "The backup is checked before replacing the database, and a copy of the current \
+             database is kept in {}. Projectpad will restart afterwards.\n\n\
+             Please enter the password of the backup: the database password when the backup was taken.",
+            backup::before_restore_path(&projectpadsql::database_path()).display()
+        )));
+        let pass = gtk::EntryBuilder::new()
+            .input_purpose(gtk::InputPurpose::Password)
+            .visibility(false)
+            .placeholder_text("Password of the backup")
+            .activates_default(true)
+            .margin_start(25)
+            .margin_end(25)
+            .build();
+        dialog.get_content_area().add(&pass);
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        let restore_btn = dialog.add_button("Restore", gtk::ResponseType::Ok);
+        restore_btn
+            .get_style_context()
+            .add_class("destructive-action");
+        dialog.set_default_response(gtk::ResponseType::Ok);
+
+        let db_sender = self.model.db_sender.clone();
+        let s = self.model.restore_backup_sender.clone();
+        dialog.connect_response(move |dlg, resp| {
+            if resp == gtk::ResponseType::Ok {
+                let pass = pass.get_text().to_string();
+                let backup_path = backup_path.clone();
+                let s = s.clone();
+                // in the sql thread, so that nothing uses the
+                // database while the file is replaced
+                db_sender
+                    .send(SqlFunc::new(move |_| {
+                        s.send(
+                            backup::restore(&backup_path, &pass, &projectpadsql::database_path())
+                                .map_err(|e| e.to_string()),
+                        )
+                        .unwrap();
+                    }))
+                    .unwrap();
+            }
+            dlg.close();
+        });
+        dialog.show_all();
+    }
+
     view! {
         #[name="prefs_win"]
         gtk::Window {
@@ -256,6 +497,22 @@ impl Widget for Preferences {
                     toggled(t) => Msg::DarkThemeToggled(t.get_active()),
                 },
                 #[style_class="section_title"]
+                gtk::Label {
+                    text: "Trash",
+                    xalign: 0.0,
+                },
+                gtk::Box {
+                    spacing: 6,
+                    gtk::Label {
+                        text: "Delete items from the trash after (days):",
+                    },
+                    gtk::SpinButton {
+                        adjustment: &gtk::Adjustment::new(
+                            self.model.trash_retention_days as f64, 0.0, 3650.0, 1.0, 30.0, 0.0),
+                        value_changed(s) => Msg::TrashRetentionDaysChanged(s.get_value_as_int() as u32),
+                    },
+                },
+                #[style_class="section_title"]
                 gtk::Label {
                     text: "Database password",
                     xalign: 0.0,
@@ -274,6 +531,26 @@ impl Widget for Preferences {
                     clicked => Msg::ChangeDbPassword,
                 },
                 #[style_class="section_title"]
+                gtk::Label {
+                    text: "Master passphrase",
+                    xalign: 0.0,
+                },
+                #[name="enable_master_passphrase"]
+                gtk::Button {
+                    label: "Protect the passwords with a master passphrase",
+                    halign: gtk::Align::Start,
+                    sensitive: false,
+                    clicked => Msg::EnableMasterPassphrase,
+                },
+                #[name="remove_master_passphrase"]
+                #[style_class="destructive-action"]
+                gtk::Button {
+                    label: "Remove the master passphrase",
+                    halign: gtk::Align::Start,
+                    sensitive: false,
+                    clicked => Msg::RemoveMasterPassphrase,
+                },
+                #[style_class="section_title"]
                 gtk::Label {
                     text: "Database file",
                     xalign: 0.0,
@@ -282,7 +559,22 @@ impl Widget for Preferences {
                 gtk::Label {
                     xalign: 0.0,
                     ellipsize: pango::EllipsizeMode::Middle,
-                }
+                },
+                #[style_class="section_title"]
+                gtk::Label {
+                    text: "Backups",
+                    xalign: 0.0,
+                },
+                #[name="backups_label"]
+                gtk::Label {
+                    xalign: 0.0,
+                    line_wrap: true,
+                },
+                gtk::Button {
+                    label: "Restore a backup",
+                    halign: gtk::Align::Start,
+                    clicked => Msg::RestoreBackup,
+                },
             },
             key_press_event(_, key) => (Msg::KeyPress(key.clone()), Inhibit(false)), // just for the ESC key.. surely there's a better way..
         }
@@ -1,20 +1,31 @@
 use super::dialog_helpers;
-use super::environments_picker::Msg::EnvironmentToggled as EnvironmentsPickerMsgEnvToggled;
-use super::environments_picker::{EnvironmentsPicker, SelectedEnvironments};
 use super::file_contents_button::FileContentsButton;
 use super::file_contents_button::Msg::FileChanged as FileContentsButtonFileChanged;
 use super::standard_dialogs;
 use crate::sql_thread::SqlFunc;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::{EnvironmentType, Project};
+use projectpadsql::models::{
+    CustomFieldDefinition, CustomFieldType, EntityType, Environment, PasswordPolicy, Project,
+};
+use projectpadsql::password_audit;
+use projectpadsql::repo::{CustomFieldRepo, EnvironmentRepo};
 use relm::Widget;
 use relm_derive::{widget, Msg};
+use std::str::FromStr;
 use std::sync::mpsc;
+use strum::IntoEnumIterator;
 
 #[derive(Msg, Clone)]
 pub enum Msg {
-    EnvironmentToggled(EnvironmentType),
+    GotEnvironments(EnvironmentsResult),
+    AddEnvironment,
+    RemoveEnvironment(usize),
+    GotCustomFields(CustomFieldsResult),
+    AddCustomField,
+    RemoveCustomField(usize),
+    GotPasswordPolicy(PasswordPolicyResult),
+    PasswordRotationToggled(bool),
     IconChanged((Option<String>, Option<Vec<u8>>)),
     OkPressed,
     ProjectUpdated(Project),
@@ -23,21 +34,100 @@ pub enum Msg {
 
 // String for details, because I can't pass Error across threads
 type SaveResult = Result<Project, (String, Option<String>)>;
+type EnvironmentsResult = Result<Vec<Environment>, (&'static str, Option<String>)>;
+type CustomFieldsResult = Result<Vec<CustomFieldDefinition>, (&'static str, Option<String>)>;
+type PasswordPolicyResult = Result<Option<PasswordPolicy>, (&'static str, Option<String>)>;
+
+/// the widgets to edit one environment of the project
+struct EnvironmentRow {
+    /// to find the row back when it's removed
+    key: usize,
+    /// 0 for an environment which is not saved yet
+    id: i32,
+    row: gtk::Box,
+    name_entry: gtk::Entry,
+    short_label_entry: gtk::Entry,
+    color_button: gtk::ColorButton,
+    dangerous_check: gtk::CheckButton,
+}
+
+impl EnvironmentRow {
+    fn environment(&self, project_id: i32) -> Environment {
+        let rgba = self.color_button.get_rgba();
+        let component = |c: f64| (c * 255.0).round() as u8;
+        Environment {
+            id: self.id,
+            name: self.name_entry.get_text().trim().to_string(),
+            short_label: self.short_label_entry.get_text().trim().to_string(),
+            color: format!(
+                "#{:02x}{:02x}{:02x}",
+                component(rgba.red),
+                component(rgba.green),
+                component(rgba.blue)
+            ),
+            // set from the order of the rows when saving
+            ordering: 0,
+            dangerous: self.dangerous_check.get_active(),
+            project_id,
+        }
+    }
+}
+
+/// the widgets to edit the definition of one custom field of the project
+struct CustomFieldRow {
+    /// to find the row back when it's removed
+    key: usize,
+    /// 0 for a field which is not saved yet
+    id: i32,
+    row: gtk::Box,
+    entity_type_combo: gtk::ComboBoxText,
+    name_entry: gtk::Entry,
+    field_type_combo: gtk::ComboBoxText,
+}
+
+impl CustomFieldRow {
+    fn definition(&self, project_id: i32) -> CustomFieldDefinition {
+        CustomFieldDefinition {
+            id: self.id,
+            project_id,
+            entity_type: self
+                .entity_type_combo
+                .get_active_id()
+                .and_then(|id| EntityType::from_str(id.as_str()).ok())
+                .unwrap_or(EntityType::Server),
+            name: self.name_entry.get_text().trim().to_string(),
+            field_type: self
+                .field_type_combo
+                .get_active_id()
+                .and_then(|id| CustomFieldType::from_str(id.as_str()).ok())
+                .unwrap_or(CustomFieldType::Text),
+            // set from the order of the rows when saving
+            ordering: 0,
+        }
+    }
+}
 
 pub struct Model {
     relm: relm::Relm<ProjectAddEditDialog>,
     db_sender: mpsc::Sender<SqlFunc>,
     _project_updated_channel: relm::Channel<SaveResult>,
     project_updated_sender: relm::Sender<SaveResult>,
+    _environments_channel: relm::Channel<EnvironmentsResult>,
+    environments_sender: relm::Sender<EnvironmentsResult>,
+    _custom_fields_channel: relm::Channel<CustomFieldsResult>,
+    custom_fields_sender: relm::Sender<CustomFieldsResult>,
+    _password_policy_channel: relm::Channel<PasswordPolicyResult>,
+    password_policy_sender: relm::Sender<PasswordPolicyResult>,
     project_id: Option<i32>,
+    /// saving would overwrite what couldn't be loaded
+    load_failed: bool,
 
     name: String,
     icon: Option<Vec<u8>>,
     icon_desc: Option<String>,
-    has_dev: bool,
-    has_stg: bool,
-    has_uat: bool,
-    has_prod: bool,
+    environment_rows: Vec<EnvironmentRow>,
+    custom_field_rows: Vec<CustomFieldRow>,
+    next_row_key: usize,
 
     infobar: gtk::InfoBar,
     infobar_label: gtk::Label,
@@ -48,6 +138,238 @@ impl Widget for ProjectAddEditDialog {
     fn init_view(&mut self) {
         dialog_helpers::style_grid(&self.widgets.grid);
         self.init_infobar_overlay();
+        self.fetch_environments();
+        self.fetch_custom_fields();
+        self.fetch_password_policy();
+    }
+
+    fn fetch_environments(&self) {
+        match self.model.project_id {
+            Some(pid) => {
+                let s = self.model.environments_sender.clone();
+                self.model
+                    .db_sender
+                    .send(SqlFunc::new(move |sql_conn| {
+                        s.send(
+                            EnvironmentRepo::new(sql_conn)
+                                .list_for_project(pid)
+                                .map_err(|e| {
+                                    ("Error loading the environments", Some(e.to_string()))
+                                }),
+                        )
+                        .unwrap();
+                    }))
+                    .unwrap();
+            }
+            None => self
+                .model
+                .relm
+                .stream()
+                .emit(Msg::GotEnvironments(Ok(Environment::defaults(0)))),
+        }
+    }
+
+    fn add_environment_row(&mut self, env: &Environment) {
+        let key = self.model.next_row_key;
+        self.model.next_row_key += 1;
+        let row = gtk::BoxBuilder::new().spacing(5).build();
+        let name_entry = gtk::EntryBuilder::new()
+            .text(&env.name)
+            .placeholder_text("Name")
+            .hexpand(true)
+            .build();
+        row.add(&name_entry);
+        let short_label_entry = gtk::EntryBuilder::new()
+            .text(&env.short_label)
+            .placeholder_text("Label")
+            .width_chars(4)
+            .max_length(4)
+            .build();
+        row.add(&short_label_entry);
+        let (red, green, blue) = env.rgb().unwrap_or((0x88, 0x88, 0x88));
+        let color_button = gtk::ColorButton::with_rgba(&gdk::RGBA {
+            red: f64::from(red) / 255.0,
+            green: f64::from(green) / 255.0,
+            blue: f64::from(blue) / 255.0,
+            alpha: 1.0,
+        });
+        color_button.set_use_alpha(false);
+        row.add(&color_button);
+        let dangerous_check = gtk::CheckButtonBuilder::new()
+            .label("Dangerous")
+            .active(env.dangerous)
+            .tooltip_text("Ask for a confirmation before running commands there")
+            .build();
+        row.add(&dangerous_check);
+        let remove_btn = gtk::ButtonBuilder::new()
+            .always_show_image(true)
+            .image(&gtk::Image::from_icon_name(
+                Some("list-remove-symbolic"),
+                gtk::IconSize::Menu,
+            ))
+            .tooltip_text("Remove the environment")
+            .build();
+        relm::connect!(
+            self.model.relm,
+            &remove_btn,
+            connect_clicked(_),
+            Msg::RemoveEnvironment(key)
+        );
+        row.add(&remove_btn);
+        row.show_all();
+        self.widgets.environments_box.add(&row);
+        self.model.environment_rows.push(EnvironmentRow {
+            key,
+            id: env.id,
+            row,
+            name_entry,
+            short_label_entry,
+            color_button,
+            dangerous_check,
+        });
+    }
+
+    fn fetch_custom_fields(&self) {
+        if let Some(pid) = self.model.project_id {
+            let s = self.model.custom_fields_sender.clone();
+            self.model
+                .db_sender
+                .send(SqlFunc::new(move |sql_conn| {
+                    s.send(
+                        CustomFieldRepo::new(sql_conn)
+                            .list_definitions_for_project(pid)
+                            .map_err(|e| ("Error loading the custom fields", Some(e.to_string()))),
+                    )
+                    .unwrap();
+                }))
+                .unwrap();
+        }
+    }
+
+    fn fetch_password_policy(&self) {
+        if let Some(pid) = self.model.project_id {
+            let s = self.model.password_policy_sender.clone();
+            self.model
+                .db_sender
+                .send(SqlFunc::new(move |sql_conn| {
+                    s.send(password_audit::policy(sql_conn, pid).map_err(|e| {
+                        (
+                            "Error loading the password rotation policy",
+                            Some(e.to_string()),
+                        )
+                    }))
+                    .unwrap();
+                }))
+                .unwrap();
+        }
+    }
+
+    /// None if the passwords don't have to be changed regularly
+    fn password_policy(&self) -> Option<PasswordPolicy> {
+        Some(PasswordPolicy {
+            project_id: self.model.project_id.unwrap_or(0),
+            max_age_days: self.widgets.rotation_days_spin.get_value_as_int(),
+            dangerous_environments_only: self.widgets.rotation_dangerous_only_check.get_active(),
+        })
+        .filter(|_| self.widgets.rotation_check.get_active())
+    }
+
+    fn entity_type_desc(entity_type: EntityType) -> &'static str {
+        match entity_type {
+            EntityType::ServerDatabase => "Databases",
+            EntityType::ServerWebsite => "Websites",
+            _ => "Servers",
+        }
+    }
+
+    fn add_custom_field_row(&mut self, definition: &CustomFieldDefinition) {
+        let key = self.model.next_row_key;
+        self.model.next_row_key += 1;
+        let row = gtk::BoxBuilder::new().spacing(5).build();
+        let entity_type_combo = gtk::ComboBoxText::new();
+        for entity_type in CustomFieldDefinition::ENTITY_TYPES {
+            entity_type_combo.append(
+                Some(&entity_type.to_string()),
+                Self::entity_type_desc(*entity_type),
+            );
+        }
+        entity_type_combo.set_active_id(Some(&definition.entity_type.to_string()));
+        // the values of the items would be lost
+        entity_type_combo.set_sensitive(definition.id == 0);
+        row.add(&entity_type_combo);
+        let name_entry = gtk::EntryBuilder::new()
+            .text(&definition.name)
+            .placeholder_text("Name")
+            .hexpand(true)
+            .build();
+        row.add(&name_entry);
+        let field_type_combo = gtk::ComboBoxText::new();
+        for field_type in CustomFieldType::iter() {
+            let id = field_type.to_string();
+            field_type_combo.append(Some(&id), &id);
+        }
+        field_type_combo.set_active_id(Some(&definition.field_type.to_string()));
+        row.add(&field_type_combo);
+        let remove_btn = gtk::ButtonBuilder::new()
+            .always_show_image(true)
+            .image(&gtk::Image::from_icon_name(
+                Some("list-remove-symbolic"),
+                gtk::IconSize::Menu,
+            ))
+            .tooltip_text("Remove the custom field and its values")
+            .build();
+        relm::connect!(
+            self.model.relm,
+            &remove_btn,
+            connect_clicked(_),
+            Msg::RemoveCustomField(key)
+        );
+        row.add(&remove_btn);
+        row.show_all();
+        self.widgets.custom_fields_box.add(&row);
+        self.model.custom_field_rows.push(CustomFieldRow {
+            key,
+            id: definition.id,
+            row,
+            entity_type_combo,
+            name_entry,
+            field_type_combo,
+        });
+    }
+
+    /// None if the custom fields are valid, otherwise what's wrong with them
+    fn custom_fields_error(definitions: &[CustomFieldDefinition]) -> Option<&'static str> {
+        if definitions.iter().any(|d| d.name.is_empty()) {
+            Some("Please give a name to all the custom fields")
+        } else if definitions.iter().enumerate().any(|(i, d)| {
+            definitions[..i]
+                .iter()
+                .any(|o| o.entity_type == d.entity_type && o.name == d.name)
+        }) {
+            Some("The custom field names must be different for the same items")
+        } else {
+            None
+        }
+    }
+
+    /// None if the environments are valid, otherwise what's wrong with them
+    fn environments_error(envs: &[Environment]) -> Option<&'static str> {
+        if envs.is_empty() {
+            Some("Please add at least one environment")
+        } else if envs
+            .iter()
+            .any(|e| e.name.is_empty() || e.short_label.is_empty())
+        {
+            Some("Please give a name and a label to all the environments")
+        } else if envs
+            .iter()
+            .enumerate()
+            .any(|(i, e)| envs[..i].iter().any(|o| o.name == e.name))
+        {
+            Some("The environment names must be different")
+        } else {
+            None
+        }
     }
 
     fn init_infobar_overlay(&self) {
@@ -71,6 +393,21 @@ impl Widget for ProjectAddEditDialog {
                 Ok(prj) => stream.emit(Msg::ProjectUpdated(prj)),
                 Err((msg, e)) => standard_dialogs::display_error_str(&msg, e),
             });
+        let stream2 = relm.stream().clone();
+        let (environments_channel, environments_sender) =
+            relm::Channel::new(move |r: EnvironmentsResult| {
+                stream2.emit(Msg::GotEnvironments(r));
+            });
+        let stream3 = relm.stream().clone();
+        let (custom_fields_channel, custom_fields_sender) =
+            relm::Channel::new(move |r: CustomFieldsResult| {
+                stream3.emit(Msg::GotCustomFields(r));
+            });
+        let stream4 = relm.stream().clone();
+        let (password_policy_channel, password_policy_sender) =
+            relm::Channel::new(move |r: PasswordPolicyResult| {
+                stream4.emit(Msg::GotPasswordPolicy(r));
+            });
         let name = p.map(|p| p.name.clone()).unwrap_or_else(|| "".to_string());
         let icon = p.and_then(|p| p.icon.clone()).filter(|i| !i.is_empty());
         let infobar = gtk::InfoBarBuilder::new()
@@ -88,14 +425,20 @@ impl Widget for ProjectAddEditDialog {
             db_sender,
             project_updated_sender,
             _project_updated_channel: project_updated_channel,
+            _environments_channel: environments_channel,
+            environments_sender,
+            _custom_fields_channel: custom_fields_channel,
+            custom_fields_sender,
+            _password_policy_channel: password_policy_channel,
+            password_policy_sender,
             project_id: p.map(|p| p.id),
+            load_failed: false,
             icon_desc: Self::icon_desc(&name, &icon),
             name,
             icon,
-            has_dev: p.map(|p| p.has_dev).unwrap_or(false),
-            has_stg: p.map(|p| p.has_stage).unwrap_or(false),
-            has_uat: p.map(|p| p.has_uat).unwrap_or(false),
-            has_prod: p.map(|p| p.has_prod).unwrap_or(false),
+            environment_rows: vec![],
+            custom_field_rows: vec![],
+            next_row_key: 0,
             infobar,
             infobar_label,
         }
@@ -109,32 +452,119 @@ impl Widget for ProjectAddEditDialog {
 
     fn update(&mut self, event: Msg) {
         match event {
-            Msg::EnvironmentToggled(EnvironmentType::EnvDevelopment) => {
-                self.model.has_dev = !self.model.has_dev;
+            Msg::GotEnvironments(Ok(envs)) => {
+                for env in &envs {
+                    self.add_environment_row(env);
+                }
+            }
+            Msg::AddEnvironment => {
+                self.add_environment_row(&Environment {
+                    id: 0,
+                    name: "".to_string(),
+                    short_label: "".to_string(),
+                    color: "#888888".to_string(),
+                    ordering: 0,
+                    dangerous: false,
+                    project_id: 0,
+                });
+            }
+            Msg::RemoveEnvironment(key) => {
+                if let Some(idx) = self
+                    .model
+                    .environment_rows
+                    .iter()
+                    .position(|r| r.key == key)
+                {
+                    let row = self.model.environment_rows.remove(idx);
+                    self.widgets.environments_box.remove(&row.row);
+                }
+            }
+            Msg::GotCustomFields(Ok(definitions)) => {
+                for definition in &definitions {
+                    self.add_custom_field_row(definition);
+                }
             }
-            Msg::EnvironmentToggled(EnvironmentType::EnvStage) => {
-                self.model.has_stg = !self.model.has_stg;
+            Msg::AddCustomField => {
+                self.add_custom_field_row(&CustomFieldDefinition {
+                    id: 0,
+                    project_id: 0,
+                    entity_type: EntityType::Server,
+                    name: "".to_string(),
+                    field_type: CustomFieldType::Text,
+                    ordering: 0,
+                });
             }
-            Msg::EnvironmentToggled(EnvironmentType::EnvUat) => {
-                self.model.has_uat = !self.model.has_uat;
+            Msg::RemoveCustomField(key) => {
+                if let Some(idx) = self
+                    .model
+                    .custom_field_rows
+                    .iter()
+                    .position(|r| r.key == key)
+                {
+                    let row = self.model.custom_field_rows.remove(idx);
+                    self.widgets.custom_fields_box.remove(&row.row);
+                }
             }
-            Msg::EnvironmentToggled(EnvironmentType::EnvProd) => {
-                self.model.has_prod = !self.model.has_prod;
+            Msg::GotPasswordPolicy(Ok(policy)) => {
+                if let Some(p) = policy {
+                    self.widgets.rotation_check.set_active(true);
+                    self.widgets
+                        .rotation_days_spin
+                        .set_value(f64::from(p.max_age_days));
+                    self.widgets
+                        .rotation_dangerous_only_check
+                        .set_active(p.dangerous_environments_only);
+                }
+            }
+            Msg::GotEnvironments(Err((msg, e)))
+            | Msg::GotCustomFields(Err((msg, e)))
+            | Msg::GotPasswordPolicy(Err((msg, e))) => {
+                self.model.load_failed = true;
+                standard_dialogs::display_error_str(msg, e);
+            }
+            Msg::PasswordRotationToggled(active) => {
+                self.widgets.rotation_days_spin.set_sensitive(active);
+                self.widgets
+                    .rotation_dangerous_only_check
+                    .set_sensitive(active);
             }
             Msg::IconChanged((_, contents)) => {
                 self.model.icon = contents;
                 self.model.icon_desc = Self::icon_desc(&self.model.name, &self.model.icon);
             }
             Msg::OkPressed => {
-                if !(self.model.has_dev
-                    || self.model.has_stg
-                    || self.model.has_uat
-                    || self.model.has_prod)
-                {
-                    self.show_infobar("Please pick at least one environment");
+                if self.model.load_failed {
+                    standard_dialogs::display_error_str(
+                        "Cannot save the project",
+                        Some(
+                            "Its settings could not be loaded, close the dialog and try again"
+                                .to_string(),
+                        ),
+                    );
                     return;
                 }
-                self.update_project();
+                let envs: Vec<_> = self
+                    .model
+                    .environment_rows
+                    .iter()
+                    .map(|r| r.environment(self.model.project_id.unwrap_or(0)))
+                    .collect();
+                if let Some(msg) = Self::environments_error(&envs) {
+                    self.show_infobar(msg);
+                    return;
+                }
+                let custom_fields: Vec<_> = self
+                    .model
+                    .custom_field_rows
+                    .iter()
+                    .map(|r| r.definition(self.model.project_id.unwrap_or(0)))
+                    .collect();
+                if let Some(msg) = Self::custom_fields_error(&custom_fields) {
+                    self.show_infobar(msg);
+                    return;
+                }
+                let password_policy = self.password_policy();
+                self.update_project(envs, custom_fields, password_policy);
             }
             Msg::HideInfobar => {
                 self.model.infobar.set_revealed(false);
@@ -144,14 +574,15 @@ impl Widget for ProjectAddEditDialog {
         }
     }
 
-    fn update_project(&self) {
+    fn update_project(
+        &self,
+        new_envs: Vec<Environment>,
+        new_custom_fields: Vec<CustomFieldDefinition>,
+        new_password_policy: Option<PasswordPolicy>,
+    ) {
         let project_id = self.model.project_id;
         let new_name = self.widgets.name_entry.get_text();
         let new_icon = self.model.icon.clone();
-        let new_has_dev = self.model.has_dev;
-        let new_has_stg = self.model.has_stg;
-        let new_has_uat = self.model.has_uat;
-        let new_has_prod = self.model.has_prod;
         let s = self.model.project_updated_sender.clone();
         self.model
             .db_sender
@@ -159,10 +590,6 @@ impl Widget for ProjectAddEditDialog {
                 use projectpadsql::schema::project::dsl as prj;
                 let changeset = (
                     prj::name.eq(new_name.as_str()),
-                    prj::has_dev.eq(new_has_dev),
-                    prj::has_stage.eq(new_has_stg),
-                    prj::has_uat.eq(new_has_uat),
-                    prj::has_prod.eq(new_has_prod),
                     // TODO the icon is actually not-null in SQL...
                     prj::icon.eq(Some(new_icon.clone().unwrap_or_default())),
                 );
@@ -173,7 +600,39 @@ impl Widget for ProjectAddEditDialog {
                     prj::id,
                     changeset,
                     Project,
-                );
+                )
+                .and_then(|project| {
+                    EnvironmentRepo::new(sql_conn)
+                        .save_for_project(project.id, &new_envs)
+                        .map(|_| project)
+                        .map_err(|e| {
+                            (
+                                "Error saving the environments".to_string(),
+                                Some(e.to_string()),
+                            )
+                        })
+                })
+                .and_then(|project| {
+                    CustomFieldRepo::new(sql_conn)
+                        .save_definitions(project.id, &new_custom_fields)
+                        .map(|_| project)
+                        .map_err(|e| {
+                            (
+                                "Error saving the custom fields".to_string(),
+                                Some(e.to_string()),
+                            )
+                        })
+                })
+                .and_then(|project| {
+                    password_audit::set_policy(sql_conn, project.id, new_password_policy.as_ref())
+                        .map(|_| project)
+                        .map_err(|e| {
+                            (
+                                "Error saving the password rotation policy".to_string(),
+                                Some(e.to_string()),
+                            )
+                        })
+                });
                 s.send(project_after_result).unwrap();
             }))
             .unwrap();
@@ -211,22 +670,29 @@ impl Widget for ProjectAddEditDialog {
                 gtk::Label {
                     text: "Environments",
                     halign: gtk::Align::End,
+                    valign: gtk::Align::Start,
                     cell: {
                         left_attach: 0,
                         top_attach: 2,
                     },
                 },
-                EnvironmentsPicker(SelectedEnvironments {
-                    has_dev: self.model.has_dev,
-                    has_stg: self.model.has_stg,
-                    has_uat: self.model.has_uat,
-                    has_prod: self.model.has_prod,
-                }) {
+                gtk::Box {
+                    orientation: gtk::Orientation::Vertical,
+                    spacing: 5,
                     cell: {
                         left_attach: 1,
                         top_attach: 2,
                     },
-                    EnvironmentsPickerMsgEnvToggled(env_type) => Msg::EnvironmentToggled(env_type)
+                    #[name="environments_box"]
+                    gtk::Box {
+                        orientation: gtk::Orientation::Vertical,
+                        spacing: 5,
+                    },
+                    gtk::Button {
+                        label: "Add environment",
+                        halign: gtk::Align::Start,
+                        clicked => Msg::AddEnvironment,
+                    },
                 },
                 gtk::Label {
                     text: "Icon",
@@ -247,6 +713,68 @@ impl Widget for ProjectAddEditDialog {
                         top_attach: 3,
                     },
                 },
+                gtk::Label {
+                    text: "Custom fields",
+                    halign: gtk::Align::End,
+                    valign: gtk::Align::Start,
+                    cell: {
+                        left_attach: 0,
+                        top_attach: 4,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Vertical,
+                    spacing: 5,
+                    cell: {
+                        left_attach: 1,
+                        top_attach: 4,
+                    },
+                    #[name="custom_fields_box"]
+                    gtk::Box {
+                        orientation: gtk::Orientation::Vertical,
+                        spacing: 5,
+                    },
+                    gtk::Button {
+                        label: "Add custom field",
+                        halign: gtk::Align::Start,
+                        clicked => Msg::AddCustomField,
+                    },
+                },
+                gtk::Label {
+                    text: "Password rotation",
+                    halign: gtk::Align::End,
+                    valign: gtk::Align::Start,
+                    cell: {
+                        left_attach: 0,
+                        top_attach: 5,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Vertical,
+                    spacing: 5,
+                    cell: {
+                        left_attach: 1,
+                        top_attach: 5,
+                    },
+                    gtk::Box {
+                        spacing: 6,
+                        #[name="rotation_check"]
+                        gtk::CheckButton {
+                            label: "Change the passwords every (days):",
+                            toggled(t) => Msg::PasswordRotationToggled(t.get_active()),
+                        },
+                        #[name="rotation_days_spin"]
+                        gtk::SpinButton {
+                            adjustment: &gtk::Adjustment::new(90.0, 1.0, 3650.0, 1.0, 30.0, 0.0),
+                            sensitive: false,
+                        },
+                    },
+                    #[name="rotation_dangerous_only_check"]
+                    gtk::CheckButton {
+                        label: "Only in the dangerous environments",
+                        sensitive: false,
+                    },
+                },
             }
         }
     }
@@ -15,7 +15,6 @@ use super::ProjectAddEditDialogComponent;
 use crate::sql_thread::SqlFunc;
 use crate::widgets::project_items_list::ProjectItem;
 use gtk::prelude::*;
-use projectpadsql::models::EnvironmentType;
 use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
@@ -32,7 +31,7 @@ pub struct Model {
     relm: relm::Relm<ProjectAddItemDialog>,
     db_sender: mpsc::Sender<SqlFunc>,
     project_id: i32,
-    environment_type: EnvironmentType,
+    environment: String,
     dialog_component: Option<ProjectAddEditDialogComponent>,
 }
 
@@ -50,16 +49,13 @@ impl Widget for ProjectAddItemDialog {
             .join_group(Some(&self.widgets.add_server));
     }
 
-    fn model(
-        relm: &relm::Relm<Self>,
-        params: (mpsc::Sender<SqlFunc>, i32, EnvironmentType),
-    ) -> Model {
-        let (db_sender, project_id, environment_type) = params;
+    fn model(relm: &relm::Relm<Self>, params: (mpsc::Sender<SqlFunc>, i32, String)) -> Model {
+        let (db_sender, project_id, environment) = params;
         Model {
             relm: relm.clone(),
             db_sender,
             project_id,
-            environment_type,
+            environment,
             dialog_component: None,
         }
     }
@@ -125,16 +121,13 @@ impl Widget for ProjectAddItemDialog {
                 match self.model.dialog_component.as_ref() {
                     Some(ProjectAddEditDialogComponent::ServerLink(lnk)) => {
                         lnk.stream()
-                            .emit(MsgServerLinkAddEditDialog::SetEnvironmentType(
-                                self.model.environment_type,
-                            ))
-                    }
-                    Some(ProjectAddEditDialogComponent::Server(srv)) => {
-                        srv.stream()
-                            .emit(MsgServerAddEditDialog::SetEnvironmentType(
-                                self.model.environment_type,
+                            .emit(MsgServerLinkAddEditDialog::SetEnvironment(
+                                self.model.environment.clone(),
                             ))
                     }
+                    Some(ProjectAddEditDialogComponent::Server(srv)) => srv.stream().emit(
+                        MsgServerAddEditDialog::SetEnvironment(self.model.environment.clone()),
+                    ),
                     _ => {}
                 };
                 self.model.relm.stream().emit(Msg::ChangeDialogTitle(title));
@@ -6,10 +6,14 @@ use super::note_edit;
 use super::note_edit::Msg::PublishContents as NotePublishContents;
 use super::note_edit::NoteEdit;
 use super::standard_dialogs;
+use super::tags_entry::Msg::TagsChanged as TagsEntryMsgTagsChanged;
+use super::tags_entry::TagsEntry;
 use crate::sql_thread::SqlFunc;
+use crate::widgets::master_passphrase;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::{EnvironmentType, Project, ProjectNote};
+use projectpadsql::models::{EntityType, Environment, ProjectNote};
+use projectpadsql::repo::EnvironmentRepo;
 use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
@@ -19,37 +23,40 @@ pub enum Msg {
     GotGroups(Vec<String>),
     OkPressed,
     UpdateProjectNote(String),
+    SaveProjectNote(String),
     ProjectNoteUpdated(ProjectNote),
-    GotProjectEnvironments(environments_picker::SelectedEnvironments),
-    EnvironmentToggled(EnvironmentType),
+    GotProjectEnvironments(ProjectEnvironments),
+    EnvironmentToggled(i32),
+    TagsChanged(String),
 }
 
 // String for details, because I can't pass Error across threads
 type SaveResult = Result<ProjectNote, (String, Option<String>)>;
 
+/// the environments of the project, and the ids of the ones of the note
+type ProjectEnvironments = (Vec<Environment>, Vec<i32>);
+
 pub struct Model {
+    relm: relm::Relm<ProjectNoteAddEditDialog>,
     db_sender: mpsc::Sender<SqlFunc>,
     accel_group: gtk::AccelGroup,
     project_id: i32,
     project_note_id: Option<i32>,
 
     groups_store: gtk::ListStore,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
 
-    _project_environments_channel: relm::Channel<environments_picker::SelectedEnvironments>,
-    project_environments_sender: relm::Sender<environments_picker::SelectedEnvironments>,
+    _project_environments_channel: relm::Channel<ProjectEnvironments>,
+    project_environments_sender: relm::Sender<ProjectEnvironments>,
 
     _project_note_updated_channel: relm::Channel<SaveResult>,
     project_note_updated_sender: relm::Sender<SaveResult>,
 
     title: String,
-    has_dev: bool,
-    has_stg: bool,
-    has_uat: bool,
-    has_prod: bool,
     group_name: Option<String>,
-    project_environments: Option<environments_picker::SelectedEnvironments>,
+    environment_ids: Vec<i32>,
+    tags: Option<String>,
     contents: String,
 }
 
@@ -63,7 +70,7 @@ impl Widget for ProjectNoteAddEditDialog {
         self.widgets.grid.set_property_height_request(500);
 
         let no_envs_error_label = gtk::LabelBuilder::new()
-            .label("You must select at least one environment which is active on the parent project")
+            .label("You must select at least one environment")
             .build();
         no_envs_error_label.show();
         self.widgets
@@ -88,18 +95,19 @@ impl Widget for ProjectNoteAddEditDialog {
     fn fetch_project_environments(&self) {
         let s = self.model.project_environments_sender.clone();
         let pid = self.model.project_id;
+        let project_note_id = self.model.project_note_id;
         self.model
             .db_sender
             .send(SqlFunc::new(move |sql_conn| {
-                use projectpadsql::schema::project::dsl as prj;
-                let project: Project = prj::project.find(pid).first(sql_conn).unwrap();
-                s.send(environments_picker::SelectedEnvironments {
-                    has_dev: project.has_dev,
-                    has_stg: project.has_stage,
-                    has_uat: project.has_uat,
-                    has_prod: project.has_prod,
-                })
-                .unwrap();
+                let repo = EnvironmentRepo::new(sql_conn);
+                let note_env_ids = project_note_id
+                    .map(|id| repo.list_for_note(id).unwrap())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|env| env.id)
+                    .collect();
+                s.send((repo.list_for_project(pid).unwrap(), note_env_ids))
+                    .unwrap();
             }))
             .unwrap();
     }
@@ -116,7 +124,7 @@ impl Widget for ProjectNoteAddEditDialog {
         let (db_sender, project_id, project_note, accel_group) = params;
         let pn = project_note.as_ref();
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -126,12 +134,12 @@ impl Widget for ProjectNoteAddEditDialog {
                 Err((msg, e)) => standard_dialogs::display_error_str(&msg, e),
             });
         let stream3 = relm.stream().clone();
-        let (project_environments_channel, project_environments_sender) = relm::Channel::new(
-            move |project_environments: environments_picker::SelectedEnvironments| {
+        let (project_environments_channel, project_environments_sender) =
+            relm::Channel::new(move |project_environments: ProjectEnvironments| {
                 stream3.emit(Msg::GotProjectEnvironments(project_environments));
-            },
-        );
+            });
         Model {
+            relm: relm.clone(),
             db_sender,
             accel_group,
             project_id,
@@ -143,14 +151,11 @@ impl Widget for ProjectNoteAddEditDialog {
             project_note_updated_sender,
             _project_environments_channel: project_environments_channel,
             project_environments_sender,
-            has_dev: pn.map(|d| d.has_dev).unwrap_or(false),
-            has_stg: pn.map(|d| d.has_stage).unwrap_or(false),
-            has_uat: pn.map(|d| d.has_uat).unwrap_or(false),
-            has_prod: pn.map(|d| d.has_prod).unwrap_or(false),
             title: pn
                 .map(|d| d.title.clone())
                 .unwrap_or_else(|| "".to_string()),
-            project_environments: None,
+            environment_ids: vec![],
+            tags: None,
             contents: pn
                 .map(|d| d.contents.clone())
                 .unwrap_or_else(|| "".to_string()),
@@ -168,25 +173,44 @@ impl Widget for ProjectNoteAddEditDialog {
                     &self.model.group_name,
                 );
             }
-            Msg::GotProjectEnvironments(prj_envs) => {
-                self.model.project_environments = Some(prj_envs);
+            Msg::GotProjectEnvironments((prj_envs, note_env_ids)) => {
+                self.model.environment_ids = note_env_ids.clone();
+                self.streams
+                    .environments_picker
+                    .emit(environments_picker::Msg::SetEnvironments(
+                        prj_envs,
+                        note_env_ids,
+                    ));
             }
             Msg::OkPressed => {
                 self.streams.note_edit.emit(note_edit::Msg::RequestContents);
             }
-            Msg::EnvironmentToggled(EnvironmentType::EnvDevelopment) => {
-                self.model.has_dev = !self.model.has_dev;
-            }
-            Msg::EnvironmentToggled(EnvironmentType::EnvStage) => {
-                self.model.has_stg = !self.model.has_stg;
+            Msg::EnvironmentToggled(env_id) => {
+                if let Some(idx) = self
+                    .model
+                    .environment_ids
+                    .iter()
+                    .position(|id| *id == env_id)
+                {
+                    self.model.environment_ids.remove(idx);
+                } else {
+                    self.model.environment_ids.push(env_id);
+                }
             }
-            Msg::EnvironmentToggled(EnvironmentType::EnvUat) => {
-                self.model.has_uat = !self.model.has_uat;
-            }
-            Msg::EnvironmentToggled(EnvironmentType::EnvProd) => {
-                self.model.has_prod = !self.model.has_prod;
+            Msg::TagsChanged(tags) => {
+                self.model.tags = Some(tags);
             }
             Msg::UpdateProjectNote(new_contents) => {
+                // encrypt the new passwords if there is a master passphrase
+                let stream = self.model.relm.stream().clone();
+                master_passphrase::protect_note(
+                    &self.model.db_sender,
+                    self.widgets.grid.upcast_ref(),
+                    new_contents,
+                    move |contents| stream.emit(Msg::SaveProjectNote(contents)),
+                );
+            }
+            Msg::SaveProjectNote(new_contents) => {
                 self.update_project_note(new_contents);
             }
             // for my parent
@@ -195,20 +219,8 @@ impl Widget for ProjectNoteAddEditDialog {
     }
 
     fn update_project_note(&self, new_contents: String) {
-        let new_has_dev = self.model.has_dev;
-        let new_has_stg = self.model.has_stg;
-        let new_has_uat = self.model.has_uat;
-        let new_has_prod = self.model.has_prod;
-
-        let has_envs = if let Some(prj_envs) = &self.model.project_environments {
-            (prj_envs.has_dev && new_has_dev)
-                || (prj_envs.has_stg && new_has_stg)
-                || (prj_envs.has_uat && new_has_uat)
-                || (prj_envs.has_prod && new_has_prod)
-        } else {
-            false
-        };
-        if !has_envs {
+        let new_environment_ids = self.model.environment_ids.clone();
+        if new_environment_ids.is_empty() {
             self.widgets.no_envs_error.set_visible(true);
             return;
         }
@@ -217,6 +229,7 @@ impl Widget for ProjectNoteAddEditDialog {
         let project_note_id = self.model.project_note_id;
         let new_title = self.widgets.title_entry.get_text();
         let new_group = self.widgets.group.get_active_text();
+        let new_tags = self.model.tags.clone();
         let s = self.model.project_note_updated_sender.clone();
         self.model
             .db_sender
@@ -230,10 +243,6 @@ impl Widget for ProjectNoteAddEditDialog {
                         .map(|s| s.as_str())
                         .filter(|s| !s.is_empty())),
                     prj_note::contents.eq(new_contents.as_str()),
-                    prj_note::has_dev.eq(new_has_dev),
-                    prj_note::has_stage.eq(new_has_stg),
-                    prj_note::has_uat.eq(new_has_uat),
-                    prj_note::has_prod.eq(new_has_prod),
                     prj_note::project_id.eq(project_id),
                 );
                 let project_note_after_result = perform_insert_or_update!(
@@ -243,8 +252,26 @@ impl Widget for ProjectNoteAddEditDialog {
                     prj_note::id,
                     changeset,
                     ProjectNote,
-                );
-                s.send(project_note_after_result).unwrap();
+                )
+                .and_then(|note| {
+                    EnvironmentRepo::new(sql_conn)
+                        .set_for_note(note.id, &new_environment_ids)
+                        .map(|_| note)
+                        .map_err(|e| {
+                            (
+                                "Error saving the note environments".to_string(),
+                                Some(e.to_string()),
+                            )
+                        })
+                });
+                s.send(dialog_helpers::save_item_tags(
+                    sql_conn,
+                    project_note_after_result,
+                    EntityType::ProjectNote,
+                    |note| note.id,
+                    new_tags.as_deref(),
+                ))
+                .unwrap();
             }))
             .unwrap();
     }
@@ -304,23 +331,38 @@ impl Widget for ProjectNoteAddEditDialog {
                     top_attach: 3,
                 },
             },
-            EnvironmentsPicker(environments_picker::SelectedEnvironments {
-                has_dev: self.model.has_dev,
-                has_stg: self.model.has_stg,
-                has_uat: self.model.has_uat,
-                has_prod: self.model.has_prod,
-            }) {
+            #[name="environments_picker"]
+            EnvironmentsPicker(()) {
                 cell: {
                     left_attach: 1,
                     top_attach: 3,
                 },
                 EnvironmentsPickerMsgEnvToggled(env_type) => Msg::EnvironmentToggled(env_type)
             },
+            gtk::Label {
+                text: "Tags",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 4,
+                },
+            },
+            TagsEntry((
+                self.model.db_sender.clone(),
+                EntityType::ProjectNote,
+                self.model.project_note_id,
+            )) {
+                cell: {
+                    left_attach: 1,
+                    top_attach: 4,
+                },
+                TagsEntryMsgTagsChanged(ref tags) => Msg::TagsChanged(tags.clone())
+            },
             #[name="note_edit"]
             NoteEdit((self.model.contents.clone(), self.model.accel_group.clone())) {
                 cell: {
                     left_attach: 0,
-                    top_attach: 4,
+                    top_attach: 5,
                     width: 2,
                 },
                 NotePublishContents(ref contents) => Msg::UpdateProjectNote(contents.clone())
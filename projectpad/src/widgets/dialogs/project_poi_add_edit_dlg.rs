@@ -1,10 +1,12 @@
 use super::dialog_helpers;
 use super::server_poi_add_edit_dlg::{init_interest_type_combo, poi_get_text_label};
 use super::standard_dialogs;
+use super::tags_entry::Msg::TagsChanged as TagsEntryMsgTagsChanged;
+use super::tags_entry::TagsEntry;
 use crate::sql_thread::SqlFunc;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::{InterestType, ProjectPointOfInterest};
+use projectpadsql::models::{EntityType, InterestType, ProjectPointOfInterest};
 use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::str::FromStr;
@@ -14,6 +16,7 @@ use std::sync::mpsc;
 pub enum Msg {
     GotGroups(Vec<String>),
     OkPressed,
+    TagsChanged(String),
     InterestTypeChanged,
     PoiUpdated(ProjectPointOfInterest),
 }
@@ -25,8 +28,8 @@ pub struct Model {
     db_sender: mpsc::Sender<SqlFunc>,
     _project_poi_updated_channel: relm::Channel<SaveResult>,
     project_poi_updated_sender: relm::Sender<SaveResult>,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
     groups_store: gtk::ListStore,
     project_id: i32,
     project_poi_id: Option<i32>,
@@ -36,6 +39,7 @@ pub struct Model {
     text: String,
     group_name: Option<String>,
     interest_type: InterestType,
+    tags: Option<String>,
 }
 
 #[widget]
@@ -77,7 +81,7 @@ impl Widget for ProjectPoiAddEditDialog {
     ) -> Model {
         let (db_sender, project_id, project_poi, _) = params;
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -111,6 +115,7 @@ impl Widget for ProjectPoiAddEditDialog {
                 .unwrap_or_else(|| "".to_string()),
             group_name: poi.and_then(|s| s.group_name.clone()),
             interest_type,
+            tags: None,
         }
     }
 
@@ -128,6 +133,9 @@ impl Widget for ProjectPoiAddEditDialog {
                 // need to update so the 'text' label gets updated
                 self.model.interest_type = self.combo_read_interest_type();
             }
+            Msg::TagsChanged(tags) => {
+                self.model.tags = Some(tags);
+            }
             Msg::OkPressed => {
                 self.update_project_poi();
             }
@@ -151,6 +159,7 @@ impl Widget for ProjectPoiAddEditDialog {
         let new_text = self.widgets.text_entry.get_text();
         let new_group = self.widgets.group.get_active_text();
         let new_interest_type = self.combo_read_interest_type();
+        let new_tags = self.model.tags.clone();
         let s = self.model.project_poi_updated_sender.clone();
         self.model
             .db_sender
@@ -176,7 +185,14 @@ impl Widget for ProjectPoiAddEditDialog {
                     changeset,
                     ProjectPointOfInterest,
                 );
-                s.send(project_poi_after_result).unwrap();
+                s.send(dialog_helpers::save_item_tags(
+                    sql_conn,
+                    project_poi_after_result,
+                    EntityType::ProjectPoi,
+                    |poi| poi.id,
+                    new_tags.as_deref(),
+                ))
+                .unwrap();
             }))
             .unwrap();
     }
@@ -272,6 +288,25 @@ impl Widget for ProjectPoiAddEditDialog {
                 },
                 changed(_) => Msg::InterestTypeChanged
             },
+            gtk::Label {
+                text: "Tags",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 6,
+                },
+            },
+            TagsEntry((
+                self.model.db_sender.clone(),
+                EntityType::ProjectPoi,
+                self.model.project_poi_id,
+            )) {
+                cell: {
+                    left_attach: 1,
+                    top_attach: 6,
+                },
+                TagsEntryMsgTagsChanged(ref tags) => Msg::TagsChanged(tags.clone())
+            },
         }
     }
 }
@@ -1,28 +1,37 @@
+use super::custom_fields_editor::{CustomFieldsEditor, DefinitionsOf};
 use super::dialog_helpers;
 use super::file_contents_button::FileContentsButton;
 use super::file_contents_button::Msg::FileChanged as AuthFileChanged;
 use super::standard_dialogs;
+use super::tags_entry::Msg::TagsChanged as TagsEntryMsgTagsChanged;
+use super::tags_entry::TagsEntry;
 use crate::sql_thread::SqlFunc;
 use crate::widgets::password_field;
 use crate::widgets::password_field::Msg as PasswordFieldMsg;
+use crate::widgets::password_field::Msg::PasswordChanged as PasswordFieldMsgPasswordChanged;
 use crate::widgets::password_field::Msg::PublishPassword as PasswordFieldMsgPublishPassword;
 use crate::widgets::password_field::PasswordField;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::{EnvironmentType, Server, ServerAccessType, ServerType};
+use projectpadsql::models::{EntityType, Server, ServerAccessType, ServerAddress, ServerType};
 use relm::Widget;
 use relm_derive::{widget, Msg};
+use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::mpsc;
 use strum::IntoEnumIterator;
 
 #[derive(Msg, Debug, Clone)]
 pub enum Msg {
-    SetEnvironmentType(EnvironmentType),
+    SetEnvironment(String),
     GotGroups(Vec<String>),
     AuthFileChanged((Option<String>, Option<Vec<u8>>)),
     OkPressed,
+    TagsChanged(String),
     GotPassword(String),
+    OtpSecretChanged(String),
+    GotOtpSecret(String),
+    GotCustomFields(Option<Vec<(i32, String)>>),
     ServerUpdated(Server),
 }
 
@@ -30,15 +39,16 @@ pub enum Msg {
 type SaveResult = Result<Server, (String, Option<String>)>;
 
 pub struct Model {
+    relm: relm::Relm<ServerAddEditDialog>,
     db_sender: mpsc::Sender<SqlFunc>,
     _server_updated_channel: relm::Channel<SaveResult>,
     server_updated_sender: relm::Sender<SaveResult>,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
     groups_store: gtk::ListStore,
     project_id: i32,
     server_id: Option<i32>,
-    environment_type: Option<EnvironmentType>,
+    environment: Option<String>,
 
     description: String,
     is_retired: bool,
@@ -47,6 +57,13 @@ pub struct Model {
     group_name: Option<String>,
     username: String,
     password: String,
+    otp_secret: String,
+    // the OTP secret as typed by the user, if it was edited
+    otp_secret_edited: Option<String>,
+    // the password, while we wait for the OTP secret
+    new_password: Option<String>,
+    // the OTP secret, while we wait for the custom fields
+    new_otp_secret: Option<String>,
     server_type: ServerType,
     server_access_type: ServerAccessType,
     auth_key_filename: Option<String>,
@@ -54,15 +71,35 @@ pub struct Model {
     // when reading from SQL. So by storing it also when adding a new
     // server, I have the same data for add & edit.
     auth_key: Option<Vec<u8>>,
+    tags: Option<String>,
+    custom_fields_editor: Option<Rc<CustomFieldsEditor>>,
 }
 
 #[widget]
 impl Widget for ServerAddEditDialog {
     fn init_view(&mut self) {
         dialog_helpers::style_grid(&self.widgets.grid);
+        self.streams
+            .password_entry
+            .emit(PasswordFieldMsg::ProtectWithMasterPassphrase(
+                self.model.db_sender.clone(),
+            ));
+        self.streams
+            .otp_secret_entry
+            .emit(PasswordFieldMsg::ProtectWithMasterPassphrase(
+                self.model.db_sender.clone(),
+            ));
         self.init_server_type();
         self.init_server_access_type();
         self.init_group();
+        self.model.custom_fields_editor = Some(CustomFieldsEditor::attach_to_grid(
+            &self.widgets.grid,
+            12,
+            &self.model.db_sender,
+            EntityType::Server,
+            DefinitionsOf::Project(self.model.project_id),
+            self.model.server_id,
+        ));
     }
 
     fn server_type_desc(server_type: ServerType) -> &'static str {
@@ -136,7 +173,7 @@ impl Widget for ServerAddEditDialog {
     ) -> Model {
         let (db_sender, project_id, server, _) = params;
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -147,7 +184,8 @@ impl Widget for ServerAddEditDialog {
             });
         let srv = server.as_ref();
         Model {
-            environment_type: srv.map(|s| s.environment),
+            relm: relm.clone(),
+            environment: srv.map(|s| s.environment.clone()),
             db_sender,
             _groups_channel: groups_channel,
             groups_sender,
@@ -160,7 +198,9 @@ impl Widget for ServerAddEditDialog {
                 .map(|s| s.desc.clone())
                 .unwrap_or_else(|| "".to_string()),
             is_retired: srv.map(|s| s.is_retired).unwrap_or(false),
-            address: srv.map(|s| s.ip.clone()).unwrap_or_else(|| "".to_string()),
+            address: srv
+                .map(|s| s.address().to_string())
+                .unwrap_or_else(|| "".to_string()),
             text: srv
                 .map(|s| s.text.clone())
                 .unwrap_or_else(|| "".to_string()),
@@ -171,6 +211,12 @@ impl Widget for ServerAddEditDialog {
             password: srv
                 .map(|s| s.password.clone())
                 .unwrap_or_else(|| "".to_string()),
+            otp_secret: srv
+                .map(|s| s.otp_secret.clone())
+                .unwrap_or_else(|| "".to_string()),
+            otp_secret_edited: None,
+            new_password: None,
+            new_otp_secret: None,
             server_type: srv
                 .map(|s| s.server_type)
                 .unwrap_or(ServerType::SrvApplication),
@@ -179,12 +225,14 @@ impl Widget for ServerAddEditDialog {
                 .unwrap_or(ServerAccessType::SrvAccessSsh),
             auth_key_filename: srv.and_then(|s| s.auth_key_filename.clone()),
             auth_key: srv.and_then(|s| s.auth_key.clone()),
+            tags: None,
+            custom_fields_editor: None,
         }
     }
 
     fn update(&mut self, event: Msg) {
         match event {
-            Msg::SetEnvironmentType(env) => self.model.environment_type = Some(env),
+            Msg::SetEnvironment(env) => self.model.environment = Some(env),
             Msg::GotGroups(groups) => {
                 dialog_helpers::fill_groups(
                     &self.model.groups_store,
@@ -197,25 +245,64 @@ impl Widget for ServerAddEditDialog {
                 self.model.auth_key_filename = kv.0.clone();
                 self.model.auth_key = kv.1.clone();
             }
+            Msg::TagsChanged(tags) => {
+                self.model.tags = Some(tags);
+            }
             Msg::OkPressed => {
+                if dialog_helpers::check_otp_secret(self.model.otp_secret_edited.as_deref()) {
+                    self.streams
+                        .password_entry
+                        .emit(PasswordFieldMsg::RequestPassword);
+                }
+            }
+            Msg::GotPassword(pass) => {
+                self.model.new_password = Some(pass);
                 self.streams
-                    .password_entry
+                    .otp_secret_entry
                     .emit(PasswordFieldMsg::RequestPassword);
             }
-            Msg::GotPassword(pass) => {
-                self.update_server(pass);
+            Msg::OtpSecretChanged(otp_secret) => {
+                self.model.otp_secret_edited = Some(otp_secret);
+            }
+            Msg::GotOtpSecret(otp_secret) => {
+                self.model.new_otp_secret = Some(otp_secret);
+                if let Some(editor) = self.model.custom_fields_editor.as_ref() {
+                    let stream = self.model.relm.stream().clone();
+                    editor.request_values(self.widgets.grid.upcast_ref(), move |fields| {
+                        stream.emit(Msg::GotCustomFields(fields))
+                    });
+                }
+            }
+            Msg::GotCustomFields(fields) => {
+                if let (Some(pass), Some(otp_secret)) = (
+                    self.model.new_password.take(),
+                    self.model.new_otp_secret.take(),
+                ) {
+                    self.update_server(pass, otp_secret, fields);
+                }
             }
             Msg::ServerUpdated(_) => {} // meant for my parent, not me
         }
     }
 
-    fn update_server(&self, new_password: String) {
-        let new_env_type = self.model.environment_type.unwrap();
+    fn update_server(
+        &self,
+        new_password: String,
+        new_otp_secret: String,
+        new_fields: Option<Vec<(i32, String)>>,
+    ) {
+        let new_env = self.model.environment.clone().unwrap();
         let server_id = self.model.server_id;
         let project_id = self.model.project_id;
         let new_desc = self.widgets.desc_entry.get_text();
         let new_is_retired = self.widgets.is_retired_check.get_active();
-        let new_address = self.widgets.address_entry.get_text();
+        let new_address = match ServerAddress::from_str(&self.widgets.address_entry.get_text()) {
+            Ok(address) => address,
+            Err(e) => {
+                standard_dialogs::display_error_str("Invalid server address", Some(e.to_string()));
+                return;
+            }
+        };
         let new_text = self.widgets.text_entry.get_text();
         let new_group = self.widgets.group.get_active_text();
         let new_username = self.widgets.username_entry.get_text();
@@ -236,6 +323,7 @@ impl Widget for ServerAddEditDialog {
                     .expect("Error parsing the server access type!?")
             })
             .expect("server access type not specified!?");
+        let new_tags = self.model.tags.clone();
         let s = self.model.server_updated_sender.clone();
         self.model
             .db_sender
@@ -244,7 +332,10 @@ impl Widget for ServerAddEditDialog {
                 let changeset = (
                     srv::desc.eq(new_desc.as_str()),
                     srv::is_retired.eq(new_is_retired),
-                    srv::ip.eq(new_address.as_str()),
+                    srv::host.eq(new_address.host.as_str()),
+                    srv::port.eq(new_address.port),
+                    srv::protocol.eq(new_address.protocol.as_ref()),
+                    srv::path.eq(new_address.path.as_str()),
                     srv::text.eq(new_text.as_str()),
                     // never store Some("") for group, we want None then.
                     srv::group_name.eq(new_group
@@ -253,11 +344,12 @@ impl Widget for ServerAddEditDialog {
                         .filter(|s| !s.is_empty())),
                     srv::username.eq(new_username.as_str()),
                     srv::password.eq(new_password.as_str()),
+                    srv::otp_secret.eq(new_otp_secret.as_str()),
                     srv::auth_key.eq(new_authkey.as_ref()),
                     srv::auth_key_filename.eq(new_authkey_filename.as_ref()),
                     srv::server_type.eq(new_servertype),
                     srv::access_type.eq(new_server_accesstype),
-                    srv::environment.eq(new_env_type),
+                    srv::environment.eq(new_env.as_str()),
                     srv::project_id.eq(project_id),
                 );
                 let server_after_result = perform_insert_or_update!(
@@ -268,7 +360,21 @@ impl Widget for ServerAddEditDialog {
                     changeset,
                     Server,
                 );
-                s.send(server_after_result).unwrap();
+                let server_after_result = dialog_helpers::save_item_tags(
+                    sql_conn,
+                    server_after_result,
+                    EntityType::Server,
+                    |srv| srv.id,
+                    new_tags.as_deref(),
+                );
+                s.send(dialog_helpers::save_item_custom_fields(
+                    sql_conn,
+                    server_after_result,
+                    EntityType::Server,
+                    |srv| srv.id,
+                    new_fields.as_deref(),
+                ))
+                .unwrap();
             }))
             .unwrap();
     }
@@ -324,7 +430,7 @@ impl Widget for ServerAddEditDialog {
                 hexpand: true,
                 text: &self.model.address,
                 activates_default: true,
-                placeholder_text: Some("Address or address:port"),
+                placeholder_text: Some("host, host:port or URL"),
                 cell: {
                     left_attach: 1,
                     top_attach: 2,
@@ -400,13 +506,31 @@ impl Widget for ServerAddEditDialog {
                 PasswordFieldMsgPublishPassword(ref pass) => Msg::GotPassword(pass.clone())
             },
             gtk::Label {
-                text: "Authentication key",
+                text: "OTP secret",
                 halign: gtk::Align::End,
                 cell: {
                     left_attach: 0,
                     top_attach: 7,
                 },
             },
+            #[name="otp_secret_entry"]
+            PasswordField((self.model.otp_secret.clone(), password_field::ActivatesDefault::Yes)) {
+                hexpand: true,
+                cell: {
+                    left_attach: 1,
+                    top_attach: 7,
+                },
+                PasswordFieldMsgPasswordChanged(ref s) => Msg::OtpSecretChanged(s.clone()),
+                PasswordFieldMsgPublishPassword(ref s) => Msg::GotOtpSecret(s.clone())
+            },
+            gtk::Label {
+                text: "Authentication key",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 8,
+                },
+            },
             FileContentsButton((
                 self.model.auth_key_filename.clone(),
                 self.model.auth_key.clone(),
@@ -415,7 +539,7 @@ impl Widget for ServerAddEditDialog {
                 AuthFileChanged(ref val) => Msg::AuthFileChanged(val.clone()),
                 cell: {
                     left_attach: 1,
-                    top_attach: 7,
+                    top_attach: 8,
                 },
             },
             gtk::Label {
@@ -423,7 +547,7 @@ impl Widget for ServerAddEditDialog {
                 halign: gtk::Align::End,
                 cell: {
                     left_attach: 0,
-                    top_attach: 8,
+                    top_attach: 9,
                 },
             },
             #[name="server_type"]
@@ -431,7 +555,7 @@ impl Widget for ServerAddEditDialog {
                 hexpand: true,
                 cell: {
                     left_attach: 1,
-                    top_attach: 8,
+                    top_attach: 9,
                 },
             },
             gtk::Label {
@@ -439,7 +563,7 @@ impl Widget for ServerAddEditDialog {
                 halign: gtk::Align::End,
                 cell: {
                     left_attach: 0,
-                    top_attach: 9,
+                    top_attach: 10,
                 },
             },
             #[name="server_access_type"]
@@ -447,8 +571,27 @@ impl Widget for ServerAddEditDialog {
                 hexpand: true,
                 cell: {
                     left_attach: 1,
-                    top_attach: 9,
+                    top_attach: 10,
+                },
+            },
+            gtk::Label {
+                text: "Tags",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 11,
+                },
+            },
+            TagsEntry((
+                self.model.db_sender.clone(),
+                EntityType::Server,
+                self.model.server_id,
+            )) {
+                cell: {
+                    left_attach: 1,
+                    top_attach: 11,
                 },
+                TagsEntryMsgTagsChanged(ref tags) => Msg::TagsChanged(tags.clone())
             },
         }
     }
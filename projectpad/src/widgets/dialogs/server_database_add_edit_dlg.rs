@@ -1,5 +1,8 @@
+use super::custom_fields_editor::{CustomFieldsEditor, DefinitionsOf};
 use super::dialog_helpers;
 use super::standard_dialogs;
+use super::tags_entry::Msg::TagsChanged as TagsEntryMsgTagsChanged;
+use super::tags_entry::TagsEntry;
 use crate::sql_thread::SqlFunc;
 use crate::widgets::password_field;
 use crate::widgets::password_field::Msg as PasswordFieldMsg;
@@ -7,16 +10,19 @@ use crate::widgets::password_field::Msg::PublishPassword as PasswordFieldMsgPubl
 use crate::widgets::password_field::PasswordField;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::ServerDatabase;
+use projectpadsql::models::{EntityType, ServerDatabase};
 use relm::Widget;
 use relm_derive::{widget, Msg};
+use std::rc::Rc;
 use std::sync::mpsc;
 
 #[derive(Msg, Clone)]
 pub enum Msg {
     GotGroups(Vec<String>),
     OkPressed,
+    TagsChanged(String),
     GotPassword(String),
+    GotCustomFields(Option<Vec<(i32, String)>>),
     ServerDbUpdated(ServerDatabase),
 }
 
@@ -24,13 +30,14 @@ pub enum Msg {
 type SaveResult = Result<ServerDatabase, (String, Option<String>)>;
 
 pub struct Model {
+    relm: relm::Relm<ServerDatabaseAddEditDialog>,
     db_sender: mpsc::Sender<SqlFunc>,
     server_id: i32,
     server_db_id: Option<i32>,
 
     groups_store: gtk::ListStore,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
 
     _server_db_updated_channel: relm::Channel<SaveResult>,
     server_db_updated_sender: relm::Sender<SaveResult>,
@@ -41,13 +48,30 @@ pub struct Model {
     text: String,
     username: String,
     password: String,
+    tags: Option<String>,
+    custom_fields_editor: Option<Rc<CustomFieldsEditor>>,
+    // the password, while we wait for the custom fields
+    new_password: Option<String>,
 }
 
 #[widget]
 impl Widget for ServerDatabaseAddEditDialog {
     fn init_view(&mut self) {
         dialog_helpers::style_grid(&self.widgets.root);
+        self.streams
+            .password_entry
+            .emit(PasswordFieldMsg::ProtectWithMasterPassphrase(
+                self.model.db_sender.clone(),
+            ));
         self.init_group();
+        self.model.custom_fields_editor = Some(CustomFieldsEditor::attach_to_grid(
+            &self.widgets.root,
+            7,
+            &self.model.db_sender,
+            EntityType::ServerDatabase,
+            DefinitionsOf::ServerProject(self.model.server_id),
+            self.model.server_db_id,
+        ));
     }
 
     fn init_group(&self) {
@@ -71,7 +95,7 @@ impl Widget for ServerDatabaseAddEditDialog {
         let (db_sender, server_id, server_db, _) = params;
         let sd = server_db.as_ref();
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -81,6 +105,7 @@ impl Widget for ServerDatabaseAddEditDialog {
                 Err((msg, e)) => standard_dialogs::display_error_str(&msg, e),
             });
         Model {
+            relm: relm.clone(),
             db_sender,
             server_id,
             server_db_id: sd.map(|d| d.id),
@@ -99,6 +124,9 @@ impl Widget for ServerDatabaseAddEditDialog {
             password: sd
                 .map(|d| d.password.clone())
                 .unwrap_or_else(|| "".to_string()),
+            tags: None,
+            custom_fields_editor: None,
+            new_password: None,
         }
     }
 
@@ -112,20 +140,34 @@ impl Widget for ServerDatabaseAddEditDialog {
                     &self.model.group_name,
                 );
             }
+            Msg::TagsChanged(tags) => {
+                self.model.tags = Some(tags);
+            }
             Msg::OkPressed => {
                 self.streams
                     .password_entry
                     .emit(PasswordFieldMsg::RequestPassword);
             }
             Msg::GotPassword(pass) => {
-                self.update_server_db(pass);
+                self.model.new_password = Some(pass);
+                if let Some(editor) = self.model.custom_fields_editor.as_ref() {
+                    let stream = self.model.relm.stream().clone();
+                    editor.request_values(self.widgets.root.upcast_ref(), move |fields| {
+                        stream.emit(Msg::GotCustomFields(fields))
+                    });
+                }
+            }
+            Msg::GotCustomFields(fields) => {
+                if let Some(pass) = self.model.new_password.take() {
+                    self.update_server_db(pass, fields);
+                }
             }
             // meant for my parent
             Msg::ServerDbUpdated(_) => {}
         }
     }
 
-    fn update_server_db(&self, new_password: String) {
+    fn update_server_db(&self, new_password: String, new_fields: Option<Vec<(i32, String)>>) {
         let server_id = self.model.server_id;
         let server_db_id = self.model.server_db_id;
         let new_desc = self.widgets.desc_entry.get_text();
@@ -133,6 +175,7 @@ impl Widget for ServerDatabaseAddEditDialog {
         let new_group = self.widgets.group.get_active_text();
         let new_text = self.widgets.text_entry.get_text();
         let new_username = self.widgets.username_entry.get_text();
+        let new_tags = self.model.tags.clone();
         let s = self.model.server_db_updated_sender.clone();
         self.model
             .db_sender
@@ -159,7 +202,21 @@ impl Widget for ServerDatabaseAddEditDialog {
                     changeset,
                     ServerDatabase,
                 );
-                s.send(server_db_after_result).unwrap();
+                let server_db_after_result = dialog_helpers::save_item_tags(
+                    sql_conn,
+                    server_db_after_result,
+                    EntityType::ServerDatabase,
+                    |db| db.id,
+                    new_tags.as_deref(),
+                );
+                s.send(dialog_helpers::save_item_custom_fields(
+                    sql_conn,
+                    server_db_after_result,
+                    EntityType::ServerDatabase,
+                    |db| db.id,
+                    new_fields.as_deref(),
+                ))
+                .unwrap();
             }))
             .unwrap();
     }
@@ -272,6 +329,25 @@ impl Widget for ServerDatabaseAddEditDialog {
                 },
                 PasswordFieldMsgPublishPassword(ref pass) => Msg::GotPassword(pass.clone())
             },
+            gtk::Label {
+                text: "Tags",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 6,
+                },
+            },
+            TagsEntry((
+                self.model.db_sender.clone(),
+                EntityType::ServerDatabase,
+                self.model.server_db_id,
+            )) {
+                cell: {
+                    left_attach: 1,
+                    top_attach: 6,
+                },
+                TagsEntryMsgTagsChanged(ref tags) => Msg::TagsChanged(tags.clone())
+            },
         }
     }
 }
@@ -0,0 +1,412 @@
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use diesel::prelude::*;
+use gtk::prelude::*;
+use projectpadsql::dependency_graph;
+use projectpadsql::dependency_graph::Dependent;
+use projectpadsql::models::{DependencyType, Server, ServerDependency};
+use projectpadsql::repo::ServerDependencyRepo;
+use projectpadsql::Error;
+use relm::Widget;
+use relm_derive::{widget, Msg};
+use std::str::FromStr;
+use std::sync::mpsc;
+use strum::IntoEnumIterator;
+
+/// the servers which can be depended on, with the name of their project,
+/// the dependencies of the server, and the servers which depend on it
+type DependenciesLoaded = (Vec<(Server, String)>, Vec<ServerDependency>, Vec<Dependent>);
+
+// String for details, because I can't pass Error across threads
+type LoadResult = Result<DependenciesLoaded, (&'static str, Option<String>)>;
+type ActionResult = Result<(), (&'static str, Option<String>)>;
+
+#[derive(Msg)]
+pub enum Msg {
+    KeyPress(gdk::EventKey),
+    GotDependencies(LoadResult),
+    AddDependency,
+    RemoveDependency(i32),
+    ActionApplied(ActionResult),
+}
+
+pub struct Model {
+    relm: relm::Relm<ServerDependenciesDialog>,
+    db_sender: mpsc::Sender<SqlFunc>,
+    server: Server,
+    servers: Vec<(Server, String)>,
+    _load_channel: relm::Channel<LoadResult>,
+    load_sender: relm::Sender<LoadResult>,
+    _action_applied_channel: relm::Channel<ActionResult>,
+    action_applied_sender: relm::Sender<ActionResult>,
+}
+
+pub fn dependency_type_desc(dependency_type: DependencyType) -> &'static str {
+    match dependency_type {
+        DependencyType::Database => "Database",
+        DependencyType::Proxy => "Proxy",
+        DependencyType::Cache => "Cache",
+        DependencyType::MessageQueue => "Message queue",
+        DependencyType::Storage => "Storage",
+        DependencyType::Authentication => "Authentication",
+        DependencyType::Other => "Other",
+    }
+}
+
+fn server_desc(server: &Server, project_name: &str) -> String {
+    format!("{} ({}, {})", server.desc, project_name, server.environment)
+}
+
+fn dim_label(text: &str) -> gtk::Label {
+    let label = gtk::LabelBuilder::new()
+        .label(text)
+        .xalign(0.0)
+        .ellipsize(pango::EllipsizeMode::End)
+        .build();
+    label.get_style_context().add_class("dim-label");
+    label
+}
+
+fn load_dependencies(
+    sql_conn: &SqliteConnection,
+    server_id: i32,
+) -> projectpadsql::Result<DependenciesLoaded> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    let servers = srv::server
+        .inner_join(prj::project)
+        .filter(srv::deleted_at.is_null())
+        .filter(srv::id.ne(server_id))
+        .order((prj::name.asc(), srv::environment.asc(), srv::desc.asc()))
+        .select((srv::server::all_columns(), prj::name))
+        .load(sql_conn)?;
+    Ok((
+        servers,
+        ServerDependencyRepo::new(sql_conn).list_for_server(server_id)?,
+        dependency_graph::dependents(sql_conn, server_id)?,
+    ))
+}
+
+#[widget]
+impl Widget for ServerDependenciesDialog {
+    fn init_view(&mut self) {
+        self.widgets
+            .header_bar
+            .set_title(Some(&format!("Dependencies of {}", self.model.server.desc)));
+        let mut types: Vec<_> = DependencyType::iter()
+            .map(|t| (t, dependency_type_desc(t)))
+            .collect();
+        types.sort_by_key(|p| p.1);
+        for (dependency_type, desc) in types {
+            self.widgets
+                .type_combo
+                .append(Some(&dependency_type.to_string()), desc);
+        }
+        self.widgets
+            .type_combo
+            .set_active_id(Some(&DependencyType::Database.to_string()));
+        self.fetch_dependencies();
+    }
+
+    fn model(relm: &relm::Relm<Self>, params: (mpsc::Sender<SqlFunc>, Server)) -> Model {
+        let (db_sender, server) = params;
+        let stream = relm.stream().clone();
+        let (_load_channel, load_sender) =
+            relm::Channel::new(move |r| stream.emit(Msg::GotDependencies(r)));
+        let stream2 = relm.stream().clone();
+        let (_action_applied_channel, action_applied_sender) =
+            relm::Channel::new(move |r| stream2.emit(Msg::ActionApplied(r)));
+        Model {
+            relm: relm.clone(),
+            db_sender,
+            server,
+            servers: vec![],
+            _load_channel,
+            load_sender,
+            _action_applied_channel,
+            action_applied_sender,
+        }
+    }
+
+    fn fetch_dependencies(&self) {
+        let s = self.model.load_sender.clone();
+        let server_id = self.model.server.id;
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    load_dependencies(sql_conn, server_id)
+                        .map_err(|e| ("Error loading the dependencies", Some(e.to_string()))),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn run_action(
+        &self,
+        error_msg: &'static str,
+        action: impl Fn(&ServerDependencyRepo) -> projectpadsql::Result<()> + Send + 'static,
+    ) {
+        let s = self.model.action_applied_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    action(&ServerDependencyRepo::new(sql_conn)).map_err(|e| match e {
+                        Error::Constraint(_) => (
+                            error_msg,
+                            Some("The server already has this dependency".to_string()),
+                        ),
+                        _ => (error_msg, Some(e.to_string())),
+                    }),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn populate(&mut self, loaded: DependenciesLoaded) {
+        let (servers, dependencies, dependents) = loaded;
+        let active_server_id = self.widgets.server_combo.get_active_id();
+        self.widgets.server_combo.remove_all();
+        for (server, project_name) in &servers {
+            self.widgets.server_combo.append(
+                Some(&server.id.to_string()),
+                &server_desc(server, project_name),
+            );
+        }
+        if let Some(id) = active_server_id {
+            self.widgets.server_combo.set_active_id(Some(&id));
+        }
+        self.model.servers = servers;
+
+        for child in self.widgets.dependencies_list.get_children() {
+            self.widgets.dependencies_list.remove(&child);
+        }
+        if dependencies.is_empty() {
+            self.widgets
+                .dependencies_list
+                .add(&dim_label("This server depends on no other server."));
+        }
+        for dependency in &dependencies {
+            self.widgets
+                .dependencies_list
+                .add(&self.dependency_row(dependency));
+        }
+        self.widgets.dependencies_list.show_all();
+
+        for child in self.widgets.dependents_list.get_children() {
+            self.widgets.dependents_list.remove(&child);
+        }
+        if dependents.is_empty() {
+            self.widgets
+                .dependents_list
+                .add(&dim_label("No server depends on this server."));
+        }
+        for dependent in &dependents {
+            self.widgets
+                .dependents_list
+                .add(&Self::dependent_row(dependent, &dependents));
+        }
+        self.widgets.dependents_list.show_all();
+    }
+
+    fn dependency_row(&self, dependency: &ServerDependency) -> gtk::Box {
+        let hbox = gtk::BoxBuilder::new().spacing(10).margin(5).build();
+        let target = self
+            .model
+            .servers
+            .iter()
+            .find(|(s, _)| s.id == dependency.depends_on_server_id)
+            .map(|(s, project_name)| server_desc(s, project_name))
+            .unwrap_or_else(|| "a server in the trash".to_string());
+        hbox.add(
+            &gtk::LabelBuilder::new()
+                .label(&format!(
+                    "{}: {}",
+                    dependency_type_desc(dependency.dependency_type),
+                    target
+                ))
+                .xalign(0.0)
+                .ellipsize(pango::EllipsizeMode::End)
+                .build(),
+        );
+        let desc = dim_label(&dependency.desc);
+        desc.set_hexpand(true);
+        hbox.add(&desc);
+        let remove_btn = gtk::ButtonBuilder::new()
+            .image(&gtk::Image::from_icon_name(
+                Some("edit-delete-symbolic"),
+                gtk::IconSize::Menu,
+            ))
+            .relief(gtk::ReliefStyle::None)
+            .tooltip_text("Remove the dependency")
+            .build();
+        relm::connect!(
+            self.model.relm,
+            &remove_btn,
+            connect_clicked(_),
+            Msg::RemoveDependency(dependency.id)
+        );
+        hbox.add(&remove_btn);
+        hbox
+    }
+
+    fn dependent_row(dependent: &Dependent, dependents: &[Dependent]) -> gtk::Box {
+        let vbox = gtk::BoxBuilder::new()
+            .orientation(gtk::Orientation::Vertical)
+            .margin(5)
+            .margin_start(5 + 20 * (dependent.distance as i32 - 1))
+            .build();
+        vbox.add(
+            &gtk::LabelBuilder::new()
+                .label(&server_desc(&dependent.server, &dependent.project_name))
+                .xalign(0.0)
+                .ellipsize(pango::EllipsizeMode::End)
+                .build(),
+        );
+        let through = dependents
+            .iter()
+            .find(|d| d.server.id == dependent.edge.depends_on_server_id)
+            .map(|d| format!(", through {}", d.server.desc))
+            .unwrap_or_else(|| "".to_string());
+        let desc = if dependent.edge.dependency_id.is_none() {
+            format!("websites {}", dependent.edge.desc)
+        } else {
+            dependent.edge.desc.clone()
+        };
+        vbox.add(&dim_label(&format!(
+            "{}{}{}{}",
+            dependency_type_desc(dependent.edge.dependency_type),
+            if desc.is_empty() { "" } else { " - " },
+            desc,
+            through
+        )));
+        vbox
+    }
+
+    fn add_dependency(&mut self) {
+        let depends_on_server_id = match self
+            .widgets
+            .server_combo
+            .get_active_id()
+            .and_then(|id| id.parse().ok())
+        {
+            Some(id) => id,
+            None => return,
+        };
+        let dependency_type = self
+            .widgets
+            .type_combo
+            .get_active_id()
+            .and_then(|t| DependencyType::from_str(&t).ok())
+            .unwrap_or(DependencyType::Other);
+        let dependency = ServerDependency {
+            id: 0,
+            server_id: self.model.server.id,
+            depends_on_server_id,
+            dependency_type,
+            desc: self.widgets.desc_entry.get_text().trim().to_string(),
+        };
+        self.widgets.desc_entry.set_text("");
+        self.run_action("Error adding the dependency", move |repo| {
+            repo.insert(&dependency).map(|_| ())
+        });
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::KeyPress(key) => {
+                if key.get_keyval() == gdk::keys::constants::Escape {
+                    self.widgets.dependencies_win.close();
+                }
+            }
+            Msg::GotDependencies(Ok(loaded)) => self.populate(loaded),
+            Msg::GotDependencies(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
+            Msg::AddDependency => self.add_dependency(),
+            Msg::RemoveDependency(dependency_id) => {
+                self.run_action("Error removing the dependency", move |repo| {
+                    repo.delete(dependency_id)
+                });
+            }
+            Msg::ActionApplied(r) => {
+                if let Err((msg, e)) = r {
+                    standard_dialogs::display_error_str(msg, e);
+                }
+                self.fetch_dependencies();
+            }
+        }
+    }
+
+    view! {
+        #[name="dependencies_win"]
+        gtk::Window {
+            titlebar: view! {
+                #[name="header_bar"]
+                gtk::HeaderBar {
+                    show_close_button: true,
+                }
+            },
+            property_default_width: 650,
+            property_default_height: 500,
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+                margin_top: 10,
+                margin_start: 10,
+                margin_end: 10,
+                margin_bottom: 10,
+                spacing: 10,
+                gtk::Label {
+                    markup: "<b>Depends on</b>",
+                    xalign: 0.0,
+                },
+                gtk::ScrolledWindow {
+                    vexpand: true,
+                    #[name="dependencies_list"]
+                    gtk::ListBox {
+                        selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+                gtk::Box {
+                    spacing: 10,
+                    #[name="server_combo"]
+                    gtk::ComboBoxText {
+                        hexpand: true,
+                    },
+                    #[name="type_combo"]
+                    gtk::ComboBoxText {
+                    },
+                    #[name="desc_entry"]
+                    gtk::Entry {
+                        placeholder_text: Some("Description"),
+                        activate => Msg::AddDependency,
+                    },
+                    gtk::Button {
+                        label: "Add",
+                        clicked => Msg::AddDependency,
+                    },
+                },
+                gtk::Label {
+                    markup: "<b>Needed by</b>",
+                    xalign: 0.0,
+                },
+                gtk::Label {
+                    text: "The servers affected when this server is down, directly or not.",
+                    xalign: 0.0,
+                    line_wrap: true,
+                },
+                gtk::ScrolledWindow {
+                    vexpand: true,
+                    #[name="dependents_list"]
+                    gtk::ListBox {
+                        selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+            },
+            key_press_event(_, key) => (Msg::KeyPress(key.clone()), Inhibit(false)), // just for the ESC key.. surely there's a better way..
+        }
+    }
+}
@@ -5,6 +5,7 @@ use super::standard_dialogs;
 use crate::sql_thread::SqlFunc;
 use crate::widgets::password_field;
 use crate::widgets::password_field::Msg as PasswordFieldMsg;
+use crate::widgets::password_field::Msg::PasswordChanged as PasswordFieldMsgPasswordChanged;
 use crate::widgets::password_field::Msg::PublishPassword as PasswordFieldMsgPublishPassword;
 use crate::widgets::password_field::PasswordField;
 use diesel::prelude::*;
@@ -20,6 +21,8 @@ pub enum Msg {
     AuthFileChanged((Option<String>, Option<Vec<u8>>)),
     OkPressed,
     GotPassword(String),
+    OtpSecretChanged(String),
+    GotOtpSecret(String),
     ServerUserUpdated(ServerExtraUserAccount),
 }
 
@@ -32,8 +35,8 @@ pub struct Model {
     server_user_id: Option<i32>,
 
     groups_store: gtk::ListStore,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
 
     _server_user_updated_channel: relm::Channel<SaveResult>,
     server_user_updated_sender: relm::Sender<SaveResult>,
@@ -42,6 +45,11 @@ pub struct Model {
     group_name: Option<String>,
     username: String,
     password: String,
+    otp_secret: String,
+    // the OTP secret as typed by the user, if it was edited
+    otp_secret_edited: Option<String>,
+    // the password, while we wait for the OTP secret
+    new_password: Option<String>,
     auth_key_filename: Option<String>,
     // store the auth key & not the Path, because it's what I have
     // when reading from SQL. So by storing it also when adding a new
@@ -53,6 +61,16 @@ pub struct Model {
 impl Widget for ServerExtraUserAddEditDialog {
     fn init_view(&mut self) {
         dialog_helpers::style_grid(&self.widgets.grid);
+        self.streams
+            .password_entry
+            .emit(PasswordFieldMsg::ProtectWithMasterPassphrase(
+                self.model.db_sender.clone(),
+            ));
+        self.streams
+            .otp_secret_entry
+            .emit(PasswordFieldMsg::ProtectWithMasterPassphrase(
+                self.model.db_sender.clone(),
+            ));
         self.init_group();
     }
 
@@ -77,7 +95,7 @@ impl Widget for ServerExtraUserAddEditDialog {
         let (db_sender, server_id, server_db, _) = params;
         let sd = server_db.as_ref();
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -103,6 +121,11 @@ impl Widget for ServerExtraUserAddEditDialog {
             password: sd
                 .map(|d| d.password.clone())
                 .unwrap_or_else(|| "".to_string()),
+            otp_secret: sd
+                .map(|d| d.otp_secret.clone())
+                .unwrap_or_else(|| "".to_string()),
+            otp_secret_edited: None,
+            new_password: None,
             auth_key_filename: sd.and_then(|s| s.auth_key_filename.clone()),
             auth_key: sd.and_then(|s| s.auth_key.clone()),
         }
@@ -123,19 +146,32 @@ impl Widget for ServerExtraUserAddEditDialog {
                 self.model.auth_key = kv.1.clone();
             }
             Msg::OkPressed => {
+                if dialog_helpers::check_otp_secret(self.model.otp_secret_edited.as_deref()) {
+                    self.streams
+                        .password_entry
+                        .emit(PasswordFieldMsg::RequestPassword);
+                }
+            }
+            Msg::GotPassword(pass) => {
+                self.model.new_password = Some(pass);
                 self.streams
-                    .password_entry
+                    .otp_secret_entry
                     .emit(PasswordFieldMsg::RequestPassword);
             }
-            Msg::GotPassword(pass) => {
-                self.update_server_user(pass);
+            Msg::OtpSecretChanged(otp_secret) => {
+                self.model.otp_secret_edited = Some(otp_secret);
+            }
+            Msg::GotOtpSecret(otp_secret) => {
+                if let Some(pass) = self.model.new_password.take() {
+                    self.update_server_user(pass, otp_secret);
+                }
             }
             // meant for my parent
             Msg::ServerUserUpdated(_) => {}
         }
     }
 
-    fn update_server_user(&self, new_password: String) {
+    fn update_server_user(&self, new_password: String, new_otp_secret: String) {
         let server_id = self.model.server_id;
         let server_user_id = self.model.server_user_id;
         let new_desc = self.widgets.desc_entry.get_text();
@@ -157,6 +193,7 @@ impl Widget for ServerExtraUserAddEditDialog {
                         .filter(|s| !s.is_empty())),
                     srv_usr::username.eq(new_username.as_str()),
                     srv_usr::password.eq(new_password.as_str()),
+                    srv_usr::otp_secret.eq(new_otp_secret.as_str()),
                     srv_usr::auth_key.eq(new_authkey.as_ref()),
                     srv_usr::auth_key_filename.eq(new_authkey_filename.as_ref()),
                     srv_usr::server_id.eq(server_id),
@@ -247,13 +284,31 @@ impl Widget for ServerExtraUserAddEditDialog {
                 PasswordFieldMsgPublishPassword(ref pass) => Msg::GotPassword(pass.clone())
             },
             gtk::Label {
-                text: "Authentication key",
+                text: "OTP secret",
                 halign: gtk::Align::End,
                 cell: {
                     left_attach: 0,
                     top_attach: 6,
                 },
             },
+            #[name="otp_secret_entry"]
+            PasswordField((self.model.otp_secret.clone(), password_field::ActivatesDefault::Yes)) {
+                hexpand: true,
+                cell: {
+                    left_attach: 1,
+                    top_attach: 6,
+                },
+                PasswordFieldMsgPasswordChanged(ref s) => Msg::OtpSecretChanged(s.clone()),
+                PasswordFieldMsgPublishPassword(ref s) => Msg::GotOtpSecret(s.clone())
+            },
+            gtk::Label {
+                text: "Authentication key",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 7,
+                },
+            },
             FileContentsButton((
                 self.model.auth_key_filename.clone(),
                 self.model.auth_key.clone(),
@@ -262,7 +317,7 @@ impl Widget for ServerExtraUserAddEditDialog {
                 FileContentsButtonFileChanged(ref val) => Msg::AuthFileChanged(val.clone()),
                 cell: {
                     left_attach: 1,
-                    top_attach: 6,
+                    top_attach: 7,
                 },
             },
         }
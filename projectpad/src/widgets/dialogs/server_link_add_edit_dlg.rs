@@ -7,14 +7,14 @@ use super::standard_dialogs;
 use crate::sql_thread::SqlFunc;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::{EnvironmentType, ServerLink};
+use projectpadsql::models::ServerLink;
 use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
 
 #[derive(Msg, Clone)]
 pub enum Msg {
-    SetEnvironmentType(EnvironmentType),
+    SetEnvironment(String),
     GotGroups(Vec<String>),
     GotLinkedGroups(Vec<String>),
     GotProjectNameAndId((String, i32)),
@@ -31,15 +31,15 @@ pub struct Model {
     db_sender: mpsc::Sender<SqlFunc>,
     project_id: i32,
     server_link_id: Option<i32>,
-    environment_type: Option<EnvironmentType>,
+    environment: Option<String>,
 
     groups_store: gtk::ListStore,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
 
     linked_groups_store: gtk::ListStore,
-    _linked_groups_channel: relm::Channel<Vec<String>>,
-    linked_groups_sender: relm::Sender<Vec<String>>,
+    _linked_groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    linked_groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
 
     _projectname_id_channel: relm::Channel<(String, i32)>,
     projectname_id_sender: relm::Sender<(String, i32)>,
@@ -136,7 +136,7 @@ impl Widget for ServerLinkAddEditDialog {
         let (db_sender, project_id, server_link, _accel_group) = params;
         let sl = server_link.as_ref();
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -152,13 +152,13 @@ impl Widget for ServerLinkAddEditDialog {
             });
         let stream4 = relm.stream().clone();
         let (linked_groups_channel, linked_groups_sender) =
-            relm::Channel::new(move |groups: Vec<String>| {
+            dialog_helpers::groups_channel(move |groups| {
                 stream4.emit(Msg::GotLinkedGroups(groups));
             });
         Model {
             db_sender,
             project_id,
-            environment_type: sl.map(|s| s.environment),
+            environment: sl.map(|s| s.environment.clone()),
             server_link_id: sl.map(|s| s.id),
             projectname_id_sender,
             _projectname_id_channel: projectname_id_channel,
@@ -179,7 +179,7 @@ impl Widget for ServerLinkAddEditDialog {
 
     fn update(&mut self, event: Msg) {
         match event {
-            Msg::SetEnvironmentType(env) => self.model.environment_type = Some(env),
+            Msg::SetEnvironment(env) => self.model.environment = Some(env),
             Msg::GotGroups(groups) => {
                 dialog_helpers::fill_groups(
                     &self.model.groups_store,
@@ -228,7 +228,7 @@ impl Widget for ServerLinkAddEditDialog {
         let new_desc = self.widgets.desc_entry.get_text();
         let new_group = self.widgets.group.get_active_text();
         let new_linked_group = self.widgets.linked_group.get_active_text();
-        let new_env_type = self.model.environment_type.unwrap();
+        let new_env = self.model.environment.clone().unwrap();
         let s = self.model.server_link_updated_sender.clone();
         self.model
             .db_sender
@@ -248,7 +248,7 @@ impl Widget for ServerLinkAddEditDialog {
                         .filter(|s| !s.is_empty())),
                     srv_link::linked_server_id.eq(new_linked_server_id),
                     srv_link::project_id.eq(project_id),
-                    srv_link::environment.eq(new_env_type),
+                    srv_link::environment.eq(new_env.as_str()),
                 );
                 let server_link_after_result = perform_insert_or_update!(
                     sql_conn,
@@ -3,10 +3,13 @@ use super::note_edit;
 use super::note_edit::Msg::PublishContents as NotePublishContents;
 use super::note_edit::NoteEdit;
 use super::standard_dialogs;
+use super::tags_entry::Msg::TagsChanged as TagsEntryMsgTagsChanged;
+use super::tags_entry::TagsEntry;
 use crate::sql_thread::SqlFunc;
+use crate::widgets::master_passphrase;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::ServerNote;
+use projectpadsql::models::{EntityType, ServerNote};
 use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
@@ -15,7 +18,9 @@ use std::sync::mpsc;
 pub enum Msg {
     GotGroups(Vec<String>),
     OkPressed,
+    TagsChanged(String),
     UpdateServerNote(String),
+    SaveServerNote(String),
     ServerNoteUpdated(ServerNote),
 }
 
@@ -23,14 +28,15 @@ pub enum Msg {
 type SaveResult = Result<ServerNote, (String, Option<String>)>;
 
 pub struct Model {
+    relm: relm::Relm<ServerNoteAddEditDialog>,
     db_sender: mpsc::Sender<SqlFunc>,
     accel_group: gtk::AccelGroup,
     server_id: i32,
     server_note_id: Option<i32>,
 
     groups_store: gtk::ListStore,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
 
     _server_note_updated_channel: relm::Channel<SaveResult>,
     server_note_updated_sender: relm::Sender<SaveResult>,
@@ -38,6 +44,7 @@ pub struct Model {
     title: String,
     group_name: Option<String>,
     contents: String,
+    tags: Option<String>,
 }
 
 #[widget]
@@ -70,7 +77,7 @@ impl Widget for ServerNoteAddEditDialog {
         let (db_sender, server_id, server_note, accel_group) = params;
         let sn = server_note.as_ref();
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -80,6 +87,7 @@ impl Widget for ServerNoteAddEditDialog {
                 Err((msg, e)) => standard_dialogs::display_error_str(&msg, e),
             });
         Model {
+            relm: relm.clone(),
             db_sender,
             accel_group,
             server_id,
@@ -96,6 +104,7 @@ impl Widget for ServerNoteAddEditDialog {
                 .map(|d| d.contents.clone())
                 .unwrap_or_else(|| "".to_string()),
             group_name: sn.and_then(|s| s.group_name.clone()),
+            tags: None,
         }
     }
 
@@ -109,10 +118,23 @@ impl Widget for ServerNoteAddEditDialog {
                     &self.model.group_name,
                 );
             }
+            Msg::TagsChanged(tags) => {
+                self.model.tags = Some(tags);
+            }
             Msg::OkPressed => {
                 self.streams.note_edit.emit(note_edit::Msg::RequestContents);
             }
             Msg::UpdateServerNote(new_contents) => {
+                // encrypt the new passwords if there is a master passphrase
+                let stream = self.model.relm.stream().clone();
+                master_passphrase::protect_note(
+                    &self.model.db_sender,
+                    self.widgets.grid.upcast_ref(),
+                    new_contents,
+                    move |contents| stream.emit(Msg::SaveServerNote(contents)),
+                );
+            }
+            Msg::SaveServerNote(new_contents) => {
                 self.update_server_note(new_contents);
             }
             // meant for my parent
@@ -125,6 +147,7 @@ impl Widget for ServerNoteAddEditDialog {
         let server_note_id = self.model.server_note_id;
         let new_title = self.widgets.title_entry.get_text();
         let new_group = self.widgets.group.get_active_text();
+        let new_tags = self.model.tags.clone();
         let s = self.model.server_note_updated_sender.clone();
         self.model
             .db_sender
@@ -148,7 +171,14 @@ impl Widget for ServerNoteAddEditDialog {
                     changeset,
                     ServerNote,
                 );
-                s.send(server_note_after_result).unwrap();
+                s.send(dialog_helpers::save_item_tags(
+                    sql_conn,
+                    server_note_after_result,
+                    EntityType::ServerNote,
+                    |note| note.id,
+                    new_tags.as_deref(),
+                ))
+                .unwrap();
             }))
             .unwrap();
     }
@@ -190,11 +220,30 @@ impl Widget for ServerNoteAddEditDialog {
                     top_attach: 1,
                 },
             },
+            gtk::Label {
+                text: "Tags",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 2,
+                },
+            },
+            TagsEntry((
+                self.model.db_sender.clone(),
+                EntityType::ServerNote,
+                self.model.server_note_id,
+            )) {
+                cell: {
+                    left_attach: 1,
+                    top_attach: 2,
+                },
+                TagsEntryMsgTagsChanged(ref tags) => Msg::TagsChanged(tags.clone())
+            },
             #[name="note_edit"]
             NoteEdit((self.model.contents.clone(), self.model.accel_group.clone())) {
                 cell: {
                     left_attach: 0,
-                    top_attach: 2,
+                    top_attach: 3,
                     width: 2,
                 },
                 NotePublishContents(ref contents) => Msg::UpdateServerNote(contents.clone())
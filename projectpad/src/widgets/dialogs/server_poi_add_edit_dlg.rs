@@ -1,9 +1,11 @@
 use super::dialog_helpers;
 use super::standard_dialogs;
+use super::tags_entry::Msg::TagsChanged as TagsEntryMsgTagsChanged;
+use super::tags_entry::TagsEntry;
 use crate::sql_thread::SqlFunc;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::{InterestType, RunOn, ServerPointOfInterest};
+use projectpadsql::models::{EntityType, InterestType, RunOn, ServerPointOfInterest};
 use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::str::FromStr;
@@ -14,6 +16,7 @@ use strum::IntoEnumIterator;
 pub enum Msg {
     GotGroups(Vec<String>),
     InterestTypeChanged,
+    TagsChanged(String),
     OkPressed,
     ServerPoiUpdated(ServerPointOfInterest),
 }
@@ -53,8 +56,8 @@ pub fn init_interest_type_combo(combo: &gtk::ComboBoxText, interest_type: &str)
 pub struct Model {
     db_sender: mpsc::Sender<SqlFunc>,
     groups_store: gtk::ListStore,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
     _server_poi_updated_channel: relm::Channel<SaveResult>,
     server_poi_updated_sender: relm::Sender<SaveResult>,
     server_id: i32,
@@ -67,6 +70,7 @@ pub struct Model {
     is_run_on_visible: bool,
     group_name: Option<String>,
     interest_type: InterestType,
+    tags: Option<String>,
 }
 
 #[widget]
@@ -128,7 +132,7 @@ impl Widget for ServerPoiAddEditDialog {
         let (db_sender, server_id, server_poi, _) = params;
         let stream = relm.stream().clone();
         let stream2 = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream2.emit(Msg::GotGroups(groups));
         });
         let (server_poi_updated_channel, server_poi_updated_sender) =
@@ -161,6 +165,7 @@ impl Widget for ServerPoiAddEditDialog {
             interest_type,
             is_run_on_visible: Self::is_run_on_visible(interest_type),
             run_on: poi.map(|s| s.run_on).unwrap_or(RunOn::RunOnServer),
+            tags: None,
             _server_poi_updated_channel: server_poi_updated_channel,
             server_poi_updated_sender,
         }
@@ -187,6 +192,9 @@ impl Widget for ServerPoiAddEditDialog {
                 // need to update so the 'text' label gets updated
                 self.model.interest_type = self.combo_read_interest_type();
             }
+            Msg::TagsChanged(tags) => {
+                self.model.tags = Some(tags);
+            }
             Msg::OkPressed => {
                 self.update_server_poi();
             }
@@ -216,6 +224,7 @@ impl Widget for ServerPoiAddEditDialog {
             .get_active_id()
             .map(|s| RunOn::from_str(s.as_str()).expect("Error parsing the run_on!?"))
             .expect("run_on not specified!?");
+        let new_tags = self.model.tags.clone();
         let s = self.model.server_poi_updated_sender.clone();
         self.model
             .db_sender
@@ -242,7 +251,14 @@ impl Widget for ServerPoiAddEditDialog {
                     changeset,
                     ServerPointOfInterest,
                 );
-                s.send(server_poi_after_result).unwrap();
+                s.send(dialog_helpers::save_item_tags(
+                    sql_conn,
+                    server_poi_after_result,
+                    EntityType::ServerPoi,
+                    |poi| poi.id,
+                    new_tags.as_deref(),
+                ))
+                .unwrap();
             }))
             .unwrap();
     }
@@ -357,6 +373,25 @@ impl Widget for ServerPoiAddEditDialog {
                 },
                 changed(_) => Msg::InterestTypeChanged
             },
+            gtk::Label {
+                text: "Tags",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 6,
+                },
+            },
+            TagsEntry((
+                self.model.db_sender.clone(),
+                EntityType::ServerPoi,
+                self.model.server_poi_id,
+            )) {
+                cell: {
+                    left_attach: 1,
+                    top_attach: 6,
+                },
+                TagsEntryMsgTagsChanged(ref tags) => Msg::TagsChanged(tags.clone())
+            },
         }
     }
 }
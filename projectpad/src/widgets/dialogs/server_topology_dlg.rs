@@ -0,0 +1,398 @@
+use super::server_dependencies_dlg::dependency_type_desc;
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use gtk::prelude::*;
+use projectpadsql::dependency_graph;
+use projectpadsql::dependency_graph::DependencyGraph;
+use projectpadsql::models::{Environment, Project};
+use projectpadsql::repo::EnvironmentRepo;
+use relm::Widget;
+use relm_derive::{widget, Msg};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+const NODE_WIDTH: f64 = 180.0;
+const NODE_HEIGHT: f64 = 50.0;
+const NODE_SPACING: f64 = 40.0;
+const LAYER_SPACING: f64 = 80.0;
+const MARGIN: f64 = 20.0;
+const PADDING: f64 = 8.0;
+const ARROW_SIZE: f64 = 8.0;
+const ENVIRONMENT_COLOR_WIDTH: f64 = 5.0;
+
+/// the environments of all the projects, for their colors, and the graph
+type GraphLoaded = (Vec<Environment>, DependencyGraph);
+
+// String for details, because I can't pass Error across threads
+type GraphResult = Result<GraphLoaded, (&'static str, Option<String>)>;
+
+#[derive(Msg)]
+pub enum Msg {
+    KeyPress(gdk::EventKey),
+    EnvironmentChanged,
+    GotGraph(GraphResult),
+}
+
+pub struct Model {
+    db_sender: mpsc::Sender<SqlFunc>,
+    project: Project,
+    environment: String,
+    /// shared with the draw handler
+    diagram: Rc<RefCell<Option<Diagram>>>,
+    _graph_channel: relm::Channel<GraphResult>,
+    graph_sender: relm::Sender<GraphResult>,
+}
+
+/// the graph, and where its servers are drawn
+pub struct Diagram {
+    project_id: i32,
+    graph: DependencyGraph,
+    environments: Vec<Environment>,
+    /// the top-left corner of the box of every server of the graph
+    positions: Vec<(f64, f64)>,
+    width: f64,
+    height: f64,
+}
+
+impl Diagram {
+    fn new(project_id: i32, graph: DependencyGraph, environments: Vec<Environment>) -> Diagram {
+        let layers = graph.layers();
+        let widest = layers.iter().map(Vec::len).max().unwrap_or(0) as f64;
+        let width = 2.0 * MARGIN + widest * NODE_WIDTH + (widest - 1.0).max(0.0) * NODE_SPACING;
+        let height = 2.0 * MARGIN
+            + layers.len() as f64 * NODE_HEIGHT
+            + (layers.len() as f64 - 1.0).max(0.0) * LAYER_SPACING;
+        let mut positions = vec![(0.0, 0.0); graph.servers.len()];
+        let mut placed = vec![false; graph.servers.len()];
+        for (layer_idx, layer) in layers.iter().enumerate() {
+            // fewer crossings: the servers under the servers which depend on them
+            let dependents_x = |idx: usize| {
+                let xs: Vec<f64> = graph
+                    .edges
+                    .iter()
+                    .filter(|e| e.depends_on_server_id == graph.servers[idx].server.id)
+                    .filter_map(|e| {
+                        graph
+                            .servers
+                            .iter()
+                            .position(|s| s.server.id == e.server_id)
+                    })
+                    .filter(|from| placed[*from])
+                    .map(|from| positions[from].0)
+                    .collect();
+                if xs.is_empty() {
+                    f64::MAX
+                } else {
+                    xs.iter().sum::<f64>() / xs.len() as f64
+                }
+            };
+            let mut keyed: Vec<(f64, usize)> =
+                layer.iter().map(|idx| (dependents_x(*idx), *idx)).collect();
+            // stable: the servers without dependents stay sorted by description
+            keyed.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            let count = keyed.len() as f64;
+            let layer_width = count * NODE_WIDTH + (count - 1.0) * NODE_SPACING;
+            let x = (width - layer_width) / 2.0;
+            let y = MARGIN + layer_idx as f64 * (NODE_HEIGHT + LAYER_SPACING);
+            for (i, (_, idx)) in keyed.into_iter().enumerate() {
+                positions[idx] = (x + i as f64 * (NODE_WIDTH + NODE_SPACING), y);
+                placed[idx] = true;
+            }
+        }
+        Diagram {
+            project_id,
+            graph,
+            environments,
+            positions,
+            width,
+            height,
+        }
+    }
+
+    fn draw(&self, drawing_area: &gtk::DrawingArea, context: &cairo::Context) {
+        let style_context = drawing_area.get_style_context();
+        let pango_context = drawing_area.create_pango_context();
+        let fg_color = style_context.lookup_color("theme_fg_color").unwrap();
+        if self.graph.servers.is_empty() {
+            let layout = pango::Layout::new(&pango_context);
+            layout.set_text("No servers in this environment.");
+            gtk::render_layout(&style_context, context, MARGIN, MARGIN, &layout);
+            return;
+        }
+        context.set_antialias(cairo::Antialias::Best);
+        self.draw_edges(&style_context, &pango_context, context, &fg_color);
+        for idx in 0..self.graph.servers.len() {
+            self.draw_server(&style_context, &pango_context, context, &fg_color, idx);
+        }
+    }
+
+    fn server_index(&self, server_id: i32) -> Option<usize> {
+        self.graph
+            .servers
+            .iter()
+            .position(|s| s.server.id == server_id)
+    }
+
+    fn draw_edges(
+        &self,
+        style_context: &gtk::StyleContext,
+        pango_context: &pango::Context,
+        context: &cairo::Context,
+        fg_color: &gdk::RGBA,
+    ) {
+        // one line for the dependencies of several types between two servers
+        let mut labels: BTreeMap<(usize, usize), Vec<&str>> = BTreeMap::new();
+        for edge in &self.graph.edges {
+            if let (Some(from), Some(to)) = (
+                self.server_index(edge.server_id),
+                self.server_index(edge.depends_on_server_id),
+            ) {
+                labels
+                    .entry((from, to))
+                    .or_default()
+                    .push(dependency_type_desc(edge.dependency_type));
+            }
+        }
+        for ((from, to), types) in labels {
+            let (from_x, from_y) = self.positions[from];
+            let (to_x, to_y) = self.positions[to];
+            let (x0, y0) = (from_x + NODE_WIDTH / 2.0, from_y + NODE_HEIGHT);
+            let (x3, y3) = (to_x + NODE_WIDTH / 2.0, to_y);
+            let (x1, y1) = (x0, y0 + LAYER_SPACING / 2.0);
+            let (x2, y2) = (x3, y3 - LAYER_SPACING / 2.0);
+            context.set_source_rgba(fg_color.red, fg_color.green, fg_color.blue, 0.6);
+            context.set_line_width(1.5);
+            context.move_to(x0, y0);
+            context.curve_to(x1, y1, x2, y2, x3, y3);
+            context.stroke();
+            // the curve arrives vertically
+            context.move_to(x3, y3);
+            context.line_to(x3 - ARROW_SIZE / 2.0, y3 - ARROW_SIZE);
+            context.line_to(x3 + ARROW_SIZE / 2.0, y3 - ARROW_SIZE);
+            context.close_path();
+            context.fill();
+
+            let layout = pango::Layout::new(pango_context);
+            layout.set_markup(&format!(
+                "<small>{}</small>",
+                glib::markup_escape_text(&types.join(", "))
+            ));
+            let label_height = (layout.get_extents().1.height / pango::SCALE) as f64;
+            // the middle of the bezier curve
+            let mid_x = (x0 + 3.0 * x1 + 3.0 * x2 + x3) / 8.0;
+            let mid_y = (y0 + 3.0 * y1 + 3.0 * y2 + y3) / 8.0;
+            gtk::render_layout(
+                style_context,
+                context,
+                mid_x + 4.0,
+                mid_y - label_height / 2.0,
+                &layout,
+            );
+        }
+    }
+
+    fn draw_server(
+        &self,
+        style_context: &gtk::StyleContext,
+        pango_context: &pango::Context,
+        context: &cairo::Context,
+        fg_color: &gdk::RGBA,
+        idx: usize,
+    ) {
+        let graph_server = &self.graph.servers[idx];
+        let server = &graph_server.server;
+        let (x, y) = self.positions[idx];
+
+        let base_color = style_context.lookup_color("theme_base_color").unwrap();
+        context.rectangle(x, y, NODE_WIDTH, NODE_HEIGHT);
+        context.set_source_rgb(base_color.red, base_color.green, base_color.blue);
+        context.fill_preserve();
+        let alpha = if server.is_retired { 0.4 } else { 0.8 };
+        context.set_source_rgba(fg_color.red, fg_color.green, fg_color.blue, alpha);
+        context.set_line_width(1.0);
+        if graph_server.external {
+            context.set_dash(&[4.0, 3.0], 0.0);
+        }
+        context.stroke();
+        context.set_dash(&[], 0.0);
+
+        // the environment color, on the left of the box
+        let (red, green, blue) = self
+            .environments
+            .iter()
+            .find(|e| e.project_id == server.project_id && e.name == server.environment)
+            .and_then(|e| e.rgb())
+            .unwrap_or((0x88, 0x88, 0x88));
+        context.rectangle(x, y, ENVIRONMENT_COLOR_WIDTH, NODE_HEIGHT);
+        context.set_source_rgb(
+            f64::from(red) / 255.0,
+            f64::from(green) / 255.0,
+            f64::from(blue) / 255.0,
+        );
+        context.fill();
+
+        let subtitle = if graph_server.external || server.project_id != self.project_id {
+            format!("{}, {}", graph_server.project_name, server.environment)
+        } else {
+            server.host.clone()
+        };
+        let title = glib::markup_escape_text(&server.desc);
+        let layout = pango::Layout::new(pango_context);
+        layout.set_markup(&format!(
+            "<b>{}</b>\n<small>{}</small>",
+            if server.is_retired {
+                format!("<s>{}</s>", title)
+            } else {
+                title.to_string()
+            },
+            glib::markup_escape_text(&subtitle)
+        ));
+        layout.set_width(
+            ((NODE_WIDTH - ENVIRONMENT_COLOR_WIDTH - 2.0 * PADDING) * pango::SCALE as f64) as i32,
+        );
+        layout.set_ellipsize(pango::EllipsizeMode::End);
+        let text_height = (layout.get_extents().1.height / pango::SCALE) as f64;
+        gtk::render_layout(
+            style_context,
+            context,
+            x + ENVIRONMENT_COLOR_WIDTH + PADDING,
+            y + (NODE_HEIGHT - text_height) / 2.0,
+            &layout,
+        );
+    }
+}
+
+#[widget]
+impl Widget for ServerTopologyDialog {
+    fn init_view(&mut self) {
+        self.widgets
+            .header_bar
+            .set_title(Some(&format!("Topology of {}", self.model.project.name)));
+        let diagram = self.model.diagram.clone();
+        self.widgets
+            .drawing_area
+            .connect_draw(move |drawing_area, context| {
+                if let Some(d) = diagram.borrow().as_ref() {
+                    d.draw(drawing_area, context);
+                }
+                Inhibit(false)
+            });
+        self.fetch_graph();
+    }
+
+    fn model(relm: &relm::Relm<Self>, params: (mpsc::Sender<SqlFunc>, Project, String)) -> Model {
+        let (db_sender, project, environment) = params;
+        let stream = relm.stream().clone();
+        let (_graph_channel, graph_sender) =
+            relm::Channel::new(move |r| stream.emit(Msg::GotGraph(r)));
+        Model {
+            db_sender,
+            project,
+            environment,
+            diagram: Rc::new(RefCell::new(None)),
+            _graph_channel,
+            graph_sender,
+        }
+    }
+
+    fn fetch_graph(&self) {
+        let s = self.model.graph_sender.clone();
+        let project_id = self.model.project.id;
+        let environment = self.model.environment.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    EnvironmentRepo::new(sql_conn)
+                        .list()
+                        .and_then(|envs| {
+                            Ok((
+                                envs,
+                                dependency_graph::for_environment(
+                                    sql_conn,
+                                    project_id,
+                                    &environment,
+                                )?,
+                            ))
+                        })
+                        .map_err(|e| ("Error loading the topology", Some(e.to_string()))),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn populate_environments(&self, environments: &[Environment]) {
+        // the environments of the project don't change while the dialog is open
+        if self.widgets.environment_combo.get_active_id().is_some() {
+            return;
+        }
+        for env in environments
+            .iter()
+            .filter(|e| e.project_id == self.model.project.id)
+        {
+            self.widgets
+                .environment_combo
+                .append(Some(&env.name), &env.name);
+        }
+        self.widgets
+            .environment_combo
+            .set_active_id(Some(&self.model.environment));
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::KeyPress(key) => {
+                if key.get_keyval() == gdk::keys::constants::Escape {
+                    self.widgets.topology_win.close();
+                }
+            }
+            Msg::EnvironmentChanged => {
+                if let Some(env) = self.widgets.environment_combo.get_active_id() {
+                    if env.as_str() != self.model.environment {
+                        self.model.environment = env.to_string();
+                        self.fetch_graph();
+                    }
+                }
+            }
+            Msg::GotGraph(Ok((environments, graph))) => {
+                self.populate_environments(&environments);
+                let diagram = Diagram::new(self.model.project.id, graph, environments);
+                self.widgets
+                    .drawing_area
+                    .set_size_request(diagram.width as i32, diagram.height as i32);
+                self.model.diagram.replace(Some(diagram));
+                self.widgets.drawing_area.queue_draw();
+            }
+            Msg::GotGraph(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
+        }
+    }
+
+    view! {
+        #[name="topology_win"]
+        gtk::Window {
+            titlebar: view! {
+                #[name="header_bar"]
+                gtk::HeaderBar {
+                    show_close_button: true,
+                    #[name="environment_combo"]
+                    gtk::ComboBoxText {
+                        changed => Msg::EnvironmentChanged,
+                    },
+                }
+            },
+            property_default_width: 800,
+            property_default_height: 600,
+            gtk::ScrolledWindow {
+                #[name="drawing_area"]
+                gtk::DrawingArea {
+                },
+            },
+            key_press_event(_, key) => (Msg::KeyPress(key.clone()), Inhibit(false)), // just for the ESC key.. surely there's a better way..
+        }
+    }
+}
@@ -1,19 +1,24 @@
+use super::custom_fields_editor::{CustomFieldsEditor, DefinitionsOf};
 use super::dialog_helpers;
 use super::pick_projectpad_item_button;
 use super::pick_projectpad_item_button::Msg::ItemSelected as PickPpItemSelected;
 use super::pick_projectpad_item_button::Msg::RemoveItem as PickPpItemRemoved;
 use super::pick_projectpad_item_button::{PickProjectpadItemButton, PickProjectpadItemParams};
 use super::standard_dialogs;
+use super::tags_entry::Msg::TagsChanged as TagsEntryMsgTagsChanged;
+use super::tags_entry::TagsEntry;
 use crate::sql_thread::SqlFunc;
 use crate::widgets::password_field;
 use crate::widgets::password_field::Msg as PasswordFieldMsg;
+use crate::widgets::password_field::Msg::PasswordChanged as PasswordFieldMsgPasswordChanged;
 use crate::widgets::password_field::Msg::PublishPassword as PasswordFieldMsgPublishPassword;
 use crate::widgets::password_field::PasswordField;
 use diesel::prelude::*;
 use gtk::prelude::*;
-use projectpadsql::models::{ServerDatabase, ServerWebsite};
+use projectpadsql::models::{EntityType, ServerDatabase, ServerWebsite};
 use relm::Widget;
 use relm_derive::{widget, Msg};
+use std::rc::Rc;
 use std::sync::mpsc;
 
 #[derive(Msg, Clone)]
@@ -23,7 +28,11 @@ pub enum Msg {
     ServerDbSelected(i32),
     ServerDbRemoved,
     OkPressed,
+    TagsChanged(String),
     GotPassword(String),
+    OtpSecretChanged(String),
+    GotOtpSecret(String),
+    GotCustomFields(Option<Vec<(i32, String)>>),
     ServerWwwUpdated(Box<(ServerWebsite, Option<ServerDatabase>)>),
 }
 
@@ -31,6 +40,7 @@ pub enum Msg {
 type SaveResult = Result<(ServerWebsite, Option<ServerDatabase>), (String, Option<String>)>;
 
 pub struct Model {
+    relm: relm::Relm<ServerWebsiteAddEditDialog>,
     db_sender: mpsc::Sender<SqlFunc>,
     server_id: i32,
     server_www_id: Option<i32>,
@@ -39,8 +49,8 @@ pub struct Model {
     projectname_id_sender: relm::Sender<(String, i32)>,
 
     groups_store: gtk::ListStore,
-    _groups_channel: relm::Channel<Vec<String>>,
-    groups_sender: relm::Sender<Vec<String>>,
+    _groups_channel: relm::Channel<dialog_helpers::GroupsResult>,
+    groups_sender: relm::Sender<dialog_helpers::GroupsResult>,
 
     _server_www_updated_channel: relm::Channel<SaveResult>,
     server_www_updated_sender: relm::Sender<SaveResult>,
@@ -52,14 +62,41 @@ pub struct Model {
     group_name: Option<String>,
     username: String,
     password: String,
+    otp_secret: String,
+    // the OTP secret as typed by the user, if it was edited
+    otp_secret_edited: Option<String>,
+    // the password, while we wait for the OTP secret
+    new_password: Option<String>,
+    // the OTP secret, while we wait for the custom fields
+    new_otp_secret: Option<String>,
+    tags: Option<String>,
+    custom_fields_editor: Option<Rc<CustomFieldsEditor>>,
 }
 
 #[widget]
 impl Widget for ServerWebsiteAddEditDialog {
     fn init_view(&mut self) {
         dialog_helpers::style_grid(&self.widgets.grid);
+        self.streams
+            .password_entry
+            .emit(PasswordFieldMsg::ProtectWithMasterPassphrase(
+                self.model.db_sender.clone(),
+            ));
+        self.streams
+            .otp_secret_entry
+            .emit(PasswordFieldMsg::ProtectWithMasterPassphrase(
+                self.model.db_sender.clone(),
+            ));
         self.init_group();
         self.fetch_project_name_and_id();
+        self.model.custom_fields_editor = Some(CustomFieldsEditor::attach_to_grid(
+            &self.widgets.grid,
+            9,
+            &self.model.db_sender,
+            EntityType::ServerWebsite,
+            DefinitionsOf::ServerProject(self.model.server_id),
+            self.model.server_www_id,
+        ));
     }
 
     fn init_group(&self) {
@@ -102,7 +139,7 @@ impl Widget for ServerWebsiteAddEditDialog {
         let (db_sender, server_id, server_www, _) = params;
         let sw = server_www.as_ref();
         let stream = relm.stream().clone();
-        let (groups_channel, groups_sender) = relm::Channel::new(move |groups: Vec<String>| {
+        let (groups_channel, groups_sender) = dialog_helpers::groups_channel(move |groups| {
             stream.emit(Msg::GotGroups(groups));
         });
         let stream2 = relm.stream().clone();
@@ -117,6 +154,7 @@ impl Widget for ServerWebsiteAddEditDialog {
                 stream3.emit(Msg::GotProjectNameAndId(projectname_id));
             });
         Model {
+            relm: relm.clone(),
             db_sender,
             server_id,
             server_www_id: sw.map(|d| d.id),
@@ -138,6 +176,14 @@ impl Widget for ServerWebsiteAddEditDialog {
             password: sw
                 .map(|d| d.password.clone())
                 .unwrap_or_else(|| "".to_string()),
+            otp_secret: sw
+                .map(|d| d.otp_secret.clone())
+                .unwrap_or_else(|| "".to_string()),
+            otp_secret_edited: None,
+            new_password: None,
+            new_otp_secret: None,
+            tags: None,
+            custom_fields_editor: None,
         }
     }
 
@@ -162,20 +208,53 @@ impl Widget for ServerWebsiteAddEditDialog {
             Msg::ServerDbRemoved => {
                 self.model.server_database_id = None;
             }
+            Msg::TagsChanged(tags) => {
+                self.model.tags = Some(tags);
+            }
             Msg::OkPressed => {
+                if dialog_helpers::check_otp_secret(self.model.otp_secret_edited.as_deref()) {
+                    self.streams
+                        .password_entry
+                        .emit(PasswordFieldMsg::RequestPassword);
+                }
+            }
+            Msg::GotPassword(pass) => {
+                self.model.new_password = Some(pass);
                 self.streams
-                    .password_entry
+                    .otp_secret_entry
                     .emit(PasswordFieldMsg::RequestPassword);
             }
-            Msg::GotPassword(pass) => {
-                self.update_server_www(pass);
+            Msg::OtpSecretChanged(otp_secret) => {
+                self.model.otp_secret_edited = Some(otp_secret);
+            }
+            Msg::GotOtpSecret(otp_secret) => {
+                self.model.new_otp_secret = Some(otp_secret);
+                if let Some(editor) = self.model.custom_fields_editor.as_ref() {
+                    let stream = self.model.relm.stream().clone();
+                    editor.request_values(self.widgets.grid.upcast_ref(), move |fields| {
+                        stream.emit(Msg::GotCustomFields(fields))
+                    });
+                }
+            }
+            Msg::GotCustomFields(fields) => {
+                if let (Some(pass), Some(otp_secret)) = (
+                    self.model.new_password.take(),
+                    self.model.new_otp_secret.take(),
+                ) {
+                    self.update_server_www(pass, otp_secret, fields);
+                }
             }
             // meant for my parent
             Msg::ServerWwwUpdated(_) => {}
         }
     }
 
-    fn update_server_www(&self, new_password: String) {
+    fn update_server_www(
+        &self,
+        new_password: String,
+        new_otp_secret: String,
+        new_fields: Option<Vec<(i32, String)>>,
+    ) {
         let server_id = self.model.server_id;
         let server_www_id = self.model.server_www_id;
         let new_desc = self.widgets.desc_entry.get_text();
@@ -184,6 +263,7 @@ impl Widget for ServerWebsiteAddEditDialog {
         let new_group = self.widgets.group.get_active_text();
         let new_username = self.widgets.username_entry.get_text();
         let new_databaseid = self.model.server_database_id;
+        let new_tags = self.model.tags.clone();
         let s = self.model.server_www_updated_sender.clone();
         self.model
             .db_sender
@@ -201,6 +281,7 @@ impl Widget for ServerWebsiteAddEditDialog {
                         .filter(|s| !s.is_empty())),
                     srv_www::username.eq(new_username.as_str()),
                     srv_www::password.eq(new_password.as_str()),
+                    srv_www::otp_secret.eq(new_otp_secret.as_str()),
                     srv_www::server_database_id.eq(new_databaseid),
                     srv_www::server_id.eq(server_id),
                 );
@@ -212,6 +293,20 @@ impl Widget for ServerWebsiteAddEditDialog {
                     changeset,
                     ServerWebsite,
                 );
+                let server_www_after_result = dialog_helpers::save_item_tags(
+                    sql_conn,
+                    server_www_after_result,
+                    EntityType::ServerWebsite,
+                    |www| www.id,
+                    new_tags.as_deref(),
+                );
+                let server_www_after_result = dialog_helpers::save_item_custom_fields(
+                    sql_conn,
+                    server_www_after_result,
+                    EntityType::ServerWebsite,
+                    |www| www.id,
+                    new_fields.as_deref(),
+                );
                 let server_db = server_www_after_result
                     .as_ref()
                     .ok()
@@ -332,13 +427,31 @@ impl Widget for ServerWebsiteAddEditDialog {
                 PasswordFieldMsgPublishPassword(ref pass) => Msg::GotPassword(pass.clone())
             },
             gtk::Label {
-                text: "Database",
+                text: "OTP secret",
                 halign: gtk::Align::End,
                 cell: {
                     left_attach: 0,
                     top_attach: 6,
                 },
             },
+            #[name="otp_secret_entry"]
+            PasswordField((self.model.otp_secret.clone(), password_field::ActivatesDefault::Yes)) {
+                hexpand: true,
+                cell: {
+                    left_attach: 1,
+                    top_attach: 6,
+                },
+                PasswordFieldMsgPasswordChanged(ref s) => Msg::OtpSecretChanged(s.clone()),
+                PasswordFieldMsgPublishPassword(ref s) => Msg::GotOtpSecret(s.clone())
+            },
+            gtk::Label {
+                text: "Database",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 7,
+                },
+            },
             #[name="pick_db_button"]
             PickProjectpadItemButton(PickProjectpadItemParams {
                 db_sender: self.model.db_sender.clone(),
@@ -348,11 +461,30 @@ impl Widget for ServerWebsiteAddEditDialog {
             }) {
                 cell: {
                     left_attach: 1,
-                    top_attach: 6,
+                    top_attach: 7,
                 },
                 PickPpItemSelected(ref v) => Msg::ServerDbSelected(v.1),
                 PickPpItemRemoved => Msg::ServerDbRemoved
-            }
+            },
+            gtk::Label {
+                text: "Tags",
+                halign: gtk::Align::End,
+                cell: {
+                    left_attach: 0,
+                    top_attach: 8,
+                },
+            },
+            TagsEntry((
+                self.model.db_sender.clone(),
+                EntityType::ServerWebsite,
+                self.model.server_www_id,
+            )) {
+                cell: {
+                    left_attach: 1,
+                    top_attach: 8,
+                },
+                TagsEntryMsgTagsChanged(ref tags) => Msg::TagsChanged(tags.clone())
+            },
         }
     }
 }
@@ -0,0 +1,605 @@
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use diesel::sqlite::SqliteConnection;
+use gtk::prelude::*;
+use projectpadsql::models::EntityType;
+use projectpadsql::sync;
+use projectpadsql::sync::{BackendKind, Conflict, Resolution, SyncReport, SyncSettings};
+use relm::Widget;
+use relm_derive::{widget, Msg};
+use std::str::FromStr;
+use std::sync::mpsc;
+
+// String for details, because I can't pass Error across threads
+type SettingsResult = Result<Option<SyncSettings>, (&'static str, Option<String>)>;
+type SyncResult = Result<SyncReport, (&'static str, Option<String>)>;
+type ConflictsResult = Result<Vec<Conflict>, (&'static str, Option<String>)>;
+type SyncActionResult = Result<(), (&'static str, Option<String>)>;
+
+#[derive(Msg)]
+pub enum Msg {
+    KeyPress(gdk::EventKey),
+    GotSettings(SettingsResult),
+    BrowseLocation,
+    SaveSettings,
+    AskDisableSync,
+    DisableSync,
+    SyncNow,
+    SyncDone(SyncResult),
+    GotConflicts(ConflictsResult),
+    Resolve(i32, Resolution),
+    ActionApplied(SyncActionResult),
+    SyncApplied,
+}
+
+pub struct Model {
+    relm: relm::Relm<SyncDialog>,
+    db_sender: mpsc::Sender<SqlFunc>,
+    _settings_channel: relm::Channel<SettingsResult>,
+    settings_sender: relm::Sender<SettingsResult>,
+    _sync_channel: relm::Channel<SyncResult>,
+    sync_sender: relm::Sender<SyncResult>,
+    _conflicts_channel: relm::Channel<ConflictsResult>,
+    conflicts_sender: relm::Sender<ConflictsResult>,
+    _action_applied_channel: relm::Channel<SyncActionResult>,
+    action_applied_sender: relm::Sender<SyncActionResult>,
+}
+
+fn entity_type_desc(entity_type: EntityType) -> &'static str {
+    match entity_type {
+        EntityType::Project => "Project",
+        EntityType::ProjectNote => "Project note",
+        EntityType::ProjectPoi => "Project point of interest",
+        EntityType::ServerLink => "Server link",
+        EntityType::Server => "Server",
+        EntityType::ServerDatabase => "Server database",
+        EntityType::ServerExtraUserAccount => "Server extra user",
+        EntityType::ServerNote => "Server note",
+        EntityType::ServerPoi => "Server point of interest",
+        EntityType::ServerWebsite => "Server website",
+    }
+}
+
+fn report_desc(report: &SyncReport) -> String {
+    let mut desc = format!(
+        "Sent {} changes, received {}, applied {}.",
+        report.pushed, report.received, report.applied
+    );
+    if report.waiting > 0 {
+        desc.push_str(&format!(
+            " {} changes wait for entities which the other devices didn't share yet.",
+            report.waiting
+        ));
+    }
+    desc
+}
+
+/// the sync can take a while: the dialog may be closed before the sql
+/// thread is done, then nobody is waiting for the result anymore
+fn send_result<T>(sender: &relm::Sender<T>, result: T) {
+    let _ = sender.send(result);
+}
+
+fn dim_label(text: &str) -> gtk::Label {
+    let label = gtk::LabelBuilder::new()
+        .label(text)
+        .xalign(0.0)
+        .ellipsize(pango::EllipsizeMode::End)
+        .build();
+    label.get_style_context().add_class("dim-label");
+    label
+}
+
+#[widget]
+impl Widget for SyncDialog {
+    fn init_view(&mut self) {
+        self.widgets
+            .backend_combo
+            .append(Some(&BackendKind::Folder.to_string()), "Shared folder");
+        self.widgets
+            .backend_combo
+            .append(Some(&BackendKind::Git.to_string()), "Git repository");
+        self.widgets
+            .backend_combo
+            .set_active_id(Some(&BackendKind::Folder.to_string()));
+        self.fetch_settings();
+        self.fetch_conflicts();
+    }
+
+    fn model(relm: &relm::Relm<Self>, db_sender: mpsc::Sender<SqlFunc>) -> Model {
+        let stream = relm.stream().clone();
+        let (_settings_channel, settings_sender) =
+            relm::Channel::new(move |r| stream.emit(Msg::GotSettings(r)));
+        let stream2 = relm.stream().clone();
+        let (_sync_channel, sync_sender) =
+            relm::Channel::new(move |r| stream2.emit(Msg::SyncDone(r)));
+        let stream3 = relm.stream().clone();
+        let (_conflicts_channel, conflicts_sender) =
+            relm::Channel::new(move |r| stream3.emit(Msg::GotConflicts(r)));
+        let stream4 = relm.stream().clone();
+        let (_action_applied_channel, action_applied_sender) =
+            relm::Channel::new(move |r| stream4.emit(Msg::ActionApplied(r)));
+        Model {
+            relm: relm.clone(),
+            db_sender,
+            _settings_channel,
+            settings_sender,
+            _sync_channel,
+            sync_sender,
+            _conflicts_channel,
+            conflicts_sender,
+            _action_applied_channel,
+            action_applied_sender,
+        }
+    }
+
+    fn fetch_settings(&self) {
+        let s = self.model.settings_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                send_result(
+                    &s,
+                    sync::settings(sql_conn)
+                        .map_err(|e| ("Error loading the sync settings", Some(e.to_string()))),
+                );
+            }))
+            .unwrap();
+    }
+
+    fn fetch_conflicts(&self) {
+        let s = self.model.conflicts_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                send_result(
+                    &s,
+                    sync::conflicts(sql_conn)
+                        .map_err(|e| ("Error loading the conflicts", Some(e.to_string()))),
+                );
+            }))
+            .unwrap();
+    }
+
+    fn run_sync_action(
+        &self,
+        error_msg: &'static str,
+        action: impl Fn(&SqliteConnection) -> projectpadsql::Result<()> + Send + 'static,
+    ) {
+        let s = self.model.action_applied_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                send_result(
+                    &s,
+                    action(sql_conn).map_err(|e| (error_msg, Some(e.to_string()))),
+                );
+            }))
+            .unwrap();
+    }
+
+    fn populate_settings(&self, settings: Option<SyncSettings>) {
+        let enabled = settings.is_some();
+        if let Some(settings) = settings {
+            self.widgets
+                .device_name_entry
+                .set_text(&settings.device_name);
+            self.widgets
+                .backend_combo
+                .set_active_id(Some(&settings.backend.to_string()));
+            self.widgets.location_entry.set_text(&settings.location);
+            self.widgets.passphrase_entry.set_text(&settings.passphrase);
+        } else if self.widgets.device_name_entry.get_text().is_empty() {
+            self.widgets.device_name_entry.set_text(
+                &glib::get_host_name()
+                    .map(|h| h.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        self.widgets.sync_now_btn.set_sensitive(enabled);
+        self.widgets.disable_btn.set_visible(enabled);
+        self.widgets.save_btn.set_label(if enabled {
+            "Save settings"
+        } else {
+            "Enable sync"
+        });
+    }
+
+    fn browse_location(&self) {
+        let chooser = gtk::FileChooserNativeBuilder::new()
+            .action(gtk::FileChooserAction::SelectFolder)
+            .title("Select the shared folder, or the clone of the git repository")
+            .modal(true)
+            .transient_for(&self.widgets.sync_win)
+            .build();
+        if chooser.run() == gtk::ResponseType::Accept {
+            if let Some(folder) = chooser.get_filename() {
+                self.widgets
+                    .location_entry
+                    .set_text(&folder.to_string_lossy());
+            }
+        }
+    }
+
+    fn save_settings(&self) {
+        let device_name = self.widgets.device_name_entry.get_text().trim().to_string();
+        let location = self.widgets.location_entry.get_text().trim().to_string();
+        let passphrase = self.widgets.passphrase_entry.get_text().to_string();
+        let backend = self
+            .widgets
+            .backend_combo
+            .get_active_id()
+            .and_then(|id| BackendKind::from_str(&id).ok())
+            .unwrap_or(BackendKind::Folder);
+        if device_name.is_empty() || location.is_empty() || passphrase.is_empty() {
+            standard_dialogs::display_error_str(
+                "Cannot enable the sync",
+                Some("The device name, the location and the passphrase are required".to_string()),
+            );
+            return;
+        }
+        self.run_sync_action("Error saving the sync settings", move |sql_conn| {
+            sync::enable(sql_conn, &device_name, backend, &location, &passphrase).map(|_| ())
+        });
+    }
+
+    fn sync_now(&self) {
+        self.widgets.sync_now_btn.set_sensitive(false);
+        self.widgets.status_label.set_text("Syncing...");
+        let s = self.model.sync_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                let result = sync::settings(sql_conn).and_then(|settings| {
+                    let settings = settings.ok_or_else(|| {
+                        projectpadsql::Error::Invalid("The sync is not set up".to_string())
+                    })?;
+                    sync::sync(sql_conn, settings.backend().as_ref())
+                });
+                send_result(
+                    &s,
+                    result.map_err(|e| ("Error syncing", Some(e.to_string()))),
+                );
+            }))
+            .unwrap();
+    }
+
+    fn conflict_row(&self, conflict: &Conflict) -> gtk::Box {
+        let row = gtk::BoxBuilder::new()
+            .orientation(gtk::Orientation::Vertical)
+            .margin(5)
+            .spacing(5)
+            .build();
+        row.add(
+            &gtk::LabelBuilder::new()
+                .label(&conflict.title)
+                .xalign(0.0)
+                .ellipsize(pango::EllipsizeMode::End)
+                .build(),
+        );
+        row.add(&dim_label(&format!(
+            "{}, edited here and on {} (detected on {})",
+            entity_type_desc(conflict.entity_type),
+            conflict.device_name,
+            conflict.detected_at.format("%Y-%m-%d %H:%M")
+        )));
+
+        let grid = gtk::GridBuilder::new()
+            .column_spacing(10)
+            .row_spacing(3)
+            .build();
+        grid.attach(&dim_label("Field"), 0, 0, 1, 1);
+        grid.attach(&dim_label("Mine"), 1, 0, 1, 1);
+        grid.attach(
+            &dim_label(&format!("Theirs ({})", conflict.device_name)),
+            2,
+            0,
+            1,
+            1,
+        );
+        let value_label = |value: &Option<String>| {
+            gtk::LabelBuilder::new()
+                .label(value.as_deref().unwrap_or(""))
+                .xalign(0.0)
+                .hexpand(true)
+                .ellipsize(pango::EllipsizeMode::End)
+                .build()
+        };
+        let deleted = Some("(deleted)".to_string());
+        if conflict.deleted_here || conflict.deleted_there {
+            grid.attach(&gtk::Label::new(Some("")), 0, 1, 1, 1);
+            grid.attach(
+                &value_label(if conflict.deleted_here {
+                    &deleted
+                } else {
+                    &None
+                }),
+                1,
+                1,
+                1,
+                1,
+            );
+            grid.attach(
+                &value_label(if conflict.deleted_there {
+                    &deleted
+                } else {
+                    &None
+                }),
+                2,
+                1,
+                1,
+                1,
+            );
+        }
+        for (i, field) in conflict.fields.iter().enumerate() {
+            let top = i as i32 + 2;
+            grid.attach(
+                &gtk::LabelBuilder::new()
+                    .label(&field.field)
+                    .xalign(1.0)
+                    .build(),
+                0,
+                top,
+                1,
+                1,
+            );
+            grid.attach(&value_label(&field.mine), 1, top, 1, 1);
+            grid.attach(&value_label(&field.theirs), 2, top, 1, 1);
+        }
+        row.add(&grid);
+
+        let buttons = gtk::BoxBuilder::new()
+            .spacing(10)
+            .halign(gtk::Align::End)
+            .build();
+        let keep_btn = gtk::ButtonBuilder::new().label("Keep mine").build();
+        relm::connect!(
+            self.model.relm,
+            &keep_btn,
+            connect_clicked(_),
+            Msg::Resolve(conflict.id, Resolution::KeepMine)
+        );
+        buttons.add(&keep_btn);
+        let take_btn = gtk::ButtonBuilder::new().label("Take theirs").build();
+        relm::connect!(
+            self.model.relm,
+            &take_btn,
+            connect_clicked(_),
+            Msg::Resolve(conflict.id, Resolution::TakeTheirs)
+        );
+        buttons.add(&take_btn);
+        row.add(&buttons);
+        row
+    }
+
+    fn populate_conflicts(&self, conflicts: Vec<Conflict>) {
+        for child in self.widgets.conflicts_list.get_children() {
+            self.widgets.conflicts_list.remove(&child);
+        }
+        for conflict in &conflicts {
+            self.widgets
+                .conflicts_list
+                .add(&self.conflict_row(conflict));
+        }
+        self.widgets
+            .conflicts_label
+            .set_text(&if conflicts.is_empty() {
+                "No conflicting edits.".to_string()
+            } else {
+                format!(
+                    "{} conflicting edits: pick the version to keep on all the devices.",
+                    conflicts.len()
+                )
+            });
+        self.widgets.conflicts_list.show_all();
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::KeyPress(key) => {
+                if key.get_keyval() == gdk::keys::constants::Escape {
+                    self.widgets.sync_win.close();
+                }
+            }
+            Msg::GotSettings(Ok(settings)) => self.populate_settings(settings),
+            Msg::GotSettings(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
+            Msg::BrowseLocation => self.browse_location(),
+            Msg::SaveSettings => self.save_settings(),
+            Msg::AskDisableSync => {
+                let relm = self.model.relm.clone();
+                standard_dialogs::confirm_deletion(
+                    "Disable the sync",
+                    "Are you sure you want to stop syncing this database? The changes made meanwhile will be shared if you enable the sync again.",
+                    self.widgets.sync_win.clone().upcast::<gtk::Widget>(),
+                    move || relm.stream().emit(Msg::DisableSync),
+                );
+            }
+            Msg::DisableSync => {
+                self.run_sync_action("Error disabling the sync", sync::disable);
+            }
+            Msg::SyncNow => self.sync_now(),
+            Msg::SyncDone(r) => {
+                self.widgets.sync_now_btn.set_sensitive(true);
+                match r {
+                    Ok(report) => {
+                        self.widgets.status_label.set_text(&report_desc(&report));
+                        if report.applied > 0 {
+                            self.model.relm.stream().emit(Msg::SyncApplied);
+                        }
+                    }
+                    Err((msg, e)) => {
+                        self.widgets.status_label.set_text("");
+                        standard_dialogs::display_error_str(msg, e);
+                    }
+                }
+                self.fetch_conflicts();
+            }
+            Msg::GotConflicts(Ok(conflicts)) => self.populate_conflicts(conflicts),
+            Msg::GotConflicts(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
+            Msg::Resolve(conflict_id, resolution) => {
+                self.run_sync_action("Error resolving the conflict", move |sql_conn| {
+                    sync::resolve(sql_conn, conflict_id, resolution)
+                });
+            }
+            Msg::ActionApplied(r) => {
+                match r {
+                    // taking their version of a conflict changes the entities
+                    Ok(()) => self.model.relm.stream().emit(Msg::SyncApplied),
+                    Err((msg, e)) => standard_dialogs::display_error_str(msg, e),
+                }
+                self.fetch_settings();
+                self.fetch_conflicts();
+            }
+            // meant for my parent
+            Msg::SyncApplied => {}
+        }
+    }
+
+    view! {
+        #[name="sync_win"]
+        gtk::Window {
+            titlebar: view! {
+                gtk::HeaderBar {
+                    title: Some("Sync"),
+                    show_close_button: true,
+                    #[name="sync_now_btn"]
+                    #[style_class="suggested-action"]
+                    gtk::Button {
+                        label: "Sync now",
+                        sensitive: false,
+                        clicked => Msg::SyncNow,
+                    },
+                }
+            },
+            property_default_width: 700,
+            property_default_height: 550,
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+                margin_top: 10,
+                margin_start: 10,
+                margin_end: 10,
+                margin_bottom: 10,
+                spacing: 10,
+                gtk::Grid {
+                    row_spacing: 5,
+                    column_spacing: 10,
+                    gtk::Label {
+                        text: "Device name",
+                        halign: gtk::Align::End,
+                        cell: {
+                            left_attach: 0,
+                            top_attach: 0,
+                        },
+                    },
+                    #[name="device_name_entry"]
+                    gtk::Entry {
+                        hexpand: true,
+                        cell: {
+                            left_attach: 1,
+                            top_attach: 0,
+                            width: 2,
+                        },
+                    },
+                    gtk::Label {
+                        text: "Through",
+                        halign: gtk::Align::End,
+                        cell: {
+                            left_attach: 0,
+                            top_attach: 1,
+                        },
+                    },
+                    #[name="backend_combo"]
+                    gtk::ComboBoxText {
+                        cell: {
+                            left_attach: 1,
+                            top_attach: 1,
+                            width: 2,
+                        },
+                    },
+                    gtk::Label {
+                        text: "Location",
+                        halign: gtk::Align::End,
+                        cell: {
+                            left_attach: 0,
+                            top_attach: 2,
+                        },
+                    },
+                    #[name="location_entry"]
+                    gtk::Entry {
+                        hexpand: true,
+                        tooltip_text: Some("The shared folder, or the folder of the clone of the git repository"),
+                        cell: {
+                            left_attach: 1,
+                            top_attach: 2,
+                        },
+                    },
+                    gtk::Button {
+                        label: "Browse...",
+                        clicked => Msg::BrowseLocation,
+                        cell: {
+                            left_attach: 2,
+                            top_attach: 2,
+                        },
+                    },
+                    gtk::Label {
+                        text: "Passphrase",
+                        halign: gtk::Align::End,
+                        cell: {
+                            left_attach: 0,
+                            top_attach: 3,
+                        },
+                    },
+                    #[name="passphrase_entry"]
+                    gtk::Entry {
+                        hexpand: true,
+                        visibility: false,
+                        input_purpose: gtk::InputPurpose::Password,
+                        tooltip_text: Some("Shared by all the devices, it encrypts the changes"),
+                        cell: {
+                            left_attach: 1,
+                            top_attach: 3,
+                            width: 2,
+                        },
+                    },
+                },
+                gtk::Box {
+                    spacing: 10,
+                    halign: gtk::Align::End,
+                    #[name="disable_btn"]
+                    #[style_class="destructive-action"]
+                    gtk::Button {
+                        label: "Disable sync",
+                        visible: false,
+                        clicked => Msg::AskDisableSync,
+                    },
+                    #[name="save_btn"]
+                    gtk::Button {
+                        label: "Enable sync",
+                        clicked => Msg::SaveSettings,
+                    },
+                },
+                #[name="status_label"]
+                gtk::Label {
+                    xalign: 0.0,
+                    line_wrap: true,
+                },
+                #[name="conflicts_label"]
+                gtk::Label {
+                    xalign: 0.0,
+                },
+                gtk::ScrolledWindow {
+                    vexpand: true,
+                    #[name="conflicts_list"]
+                    gtk::ListBox {
+                        selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+            },
+            key_press_event(_, key) => (Msg::KeyPress(key.clone()), Inhibit(false)), // just for the ESC key.. surely there's a better way..
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use gtk::prelude::*;
+use projectpadsql::models::EntityType;
+use projectpadsql::repo::TagRepo;
+use relm::Widget;
+use relm_derive::{widget, Msg};
+use std::sync::mpsc;
+
+#[derive(Msg)]
+pub enum Msg {
+    GotTags(Vec<String>),
+    TextChanged,
+    /// the text of the entry, see `Tag::parse_names`. Not sent
+    /// before the tags of the item are loaded, so that pressing
+    /// OK too early doesn't clear them.
+    TagsChanged(String),
+}
+
+type TagsResult = projectpadsql::Result<Vec<String>>;
+
+pub struct Model {
+    relm: relm::Relm<TagsEntry>,
+    db_sender: mpsc::Sender<SqlFunc>,
+    entity_type: EntityType,
+    item_id: Option<i32>,
+    _tags_channel: relm::Channel<TagsResult>,
+    tags_sender: relm::Sender<TagsResult>,
+    loaded: bool,
+}
+
+#[widget]
+impl Widget for TagsEntry {
+    fn init_view(&mut self) {
+        match self.model.item_id {
+            Some(item_id) => self.fetch_tags(item_id),
+            None => self.model.loaded = true,
+        }
+    }
+
+    fn fetch_tags(&self, item_id: i32) {
+        let s = self.model.tags_sender.clone();
+        let entity_type = self.model.entity_type;
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    TagRepo::new(sql_conn)
+                        .list_for_item(entity_type, item_id)
+                        .map(|tags| tags.into_iter().map(|t| t.name).collect()),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn model(
+        relm: &relm::Relm<Self>,
+        params: (mpsc::Sender<SqlFunc>, EntityType, Option<i32>),
+    ) -> Model {
+        let (db_sender, entity_type, item_id) = params;
+        let stream = relm.stream().clone();
+        let (tags_channel, tags_sender) = relm::Channel::new(move |r: TagsResult| match r {
+            Ok(tags) => stream.emit(Msg::GotTags(tags)),
+            Err(e) => standard_dialogs::display_error("Error loading the tags", Some(Box::new(e))),
+        });
+        Model {
+            relm: relm.clone(),
+            db_sender,
+            entity_type,
+            item_id,
+            _tags_channel: tags_channel,
+            tags_sender,
+            loaded: false,
+        }
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::GotTags(tags) => {
+                self.model.loaded = true;
+                self.widgets.tags_entry.set_text(&tags.join(", "));
+            }
+            Msg::TextChanged => {
+                if self.model.loaded {
+                    self.model.relm.stream().emit(Msg::TagsChanged(
+                        self.widgets.tags_entry.get_text().to_string(),
+                    ));
+                }
+            }
+            // meant for my parent
+            Msg::TagsChanged(_) => {}
+        }
+    }
+
+    view! {
+        #[name="tags_entry"]
+        gtk::Entry {
+            hexpand: true,
+            activates_default: true,
+            placeholder_text: Some("separated by commas or spaces"),
+            changed(_) => Msg::TextChanged,
+        }
+    }
+}
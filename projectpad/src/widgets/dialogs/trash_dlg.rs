@@ -0,0 +1,243 @@
+use super::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use diesel::sqlite::SqliteConnection;
+use gtk::prelude::*;
+use projectpadsql::models::EntityType;
+use projectpadsql::trash;
+use projectpadsql::trash::TrashItem;
+use relm::Widget;
+use relm_derive::{widget, Msg};
+use std::sync::mpsc;
+
+// String for details, because I can't pass Error across threads
+type TrashListResult = Result<Vec<TrashItem>, (&'static str, Option<String>)>;
+type TrashActionResult = Result<(), (&'static str, Option<String>)>;
+
+#[derive(Msg)]
+pub enum Msg {
+    KeyPress(gdk::EventKey),
+    GotTrashItems(TrashListResult),
+    Restore(EntityType, i32),
+    Purge(EntityType, i32),
+    AskEmptyTrash,
+    EmptyTrash,
+    ActionApplied(TrashActionResult),
+    TrashChanged,
+}
+
+pub struct Model {
+    relm: relm::Relm<TrashDialog>,
+    db_sender: mpsc::Sender<SqlFunc>,
+    _trash_items_channel: relm::Channel<TrashListResult>,
+    trash_items_sender: relm::Sender<TrashListResult>,
+    _action_applied_channel: relm::Channel<TrashActionResult>,
+    action_applied_sender: relm::Sender<TrashActionResult>,
+}
+
+fn entity_type_desc(entity_type: EntityType) -> &'static str {
+    match entity_type {
+        EntityType::Project => "Project",
+        EntityType::ProjectNote => "Project note",
+        EntityType::ProjectPoi => "Project point of interest",
+        EntityType::ServerLink => "Server link",
+        EntityType::Server => "Server",
+        EntityType::ServerDatabase => "Server database",
+        EntityType::ServerExtraUserAccount => "Server extra user",
+        EntityType::ServerNote => "Server note",
+        EntityType::ServerPoi => "Server point of interest",
+        EntityType::ServerWebsite => "Server website",
+    }
+}
+
+#[widget]
+impl Widget for TrashDialog {
+    fn init_view(&mut self) {
+        self.fetch_trash_items();
+    }
+
+    fn model(relm: &relm::Relm<Self>, db_sender: mpsc::Sender<SqlFunc>) -> Model {
+        let stream = relm.stream().clone();
+        let (_trash_items_channel, trash_items_sender) =
+            relm::Channel::new(move |r| stream.emit(Msg::GotTrashItems(r)));
+        let stream2 = relm.stream().clone();
+        let (_action_applied_channel, action_applied_sender) =
+            relm::Channel::new(move |r| stream2.emit(Msg::ActionApplied(r)));
+        Model {
+            relm: relm.clone(),
+            db_sender,
+            _trash_items_channel,
+            trash_items_sender,
+            _action_applied_channel,
+            action_applied_sender,
+        }
+    }
+
+    fn fetch_trash_items(&self) {
+        let s = self.model.trash_items_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(
+                    trash::list(sql_conn)
+                        .map_err(|e| ("Error loading the trash", Some(e.to_string()))),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn run_trash_action(
+        &self,
+        error_msg: &'static str,
+        action: impl Fn(&SqliteConnection) -> projectpadsql::Result<()> + Send + 'static,
+    ) {
+        let s = self.model.action_applied_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                s.send(action(sql_conn).map_err(|e| (error_msg, Some(e.to_string()))))
+                    .unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn populate_trash_items(&self, items: Vec<TrashItem>) {
+        for child in self.widgets.trash_list.get_children() {
+            self.widgets.trash_list.remove(&child);
+        }
+        for item in &items {
+            let row = gtk::BoxBuilder::new().margin(5).spacing(10).build();
+            let labels = gtk::BoxBuilder::new()
+                .orientation(gtk::Orientation::Vertical)
+                .hexpand(true)
+                .build();
+            labels.add(
+                &gtk::LabelBuilder::new()
+                    .label(&item.title)
+                    .xalign(0.0)
+                    .ellipsize(pango::EllipsizeMode::End)
+                    .build(),
+            );
+            let details = gtk::LabelBuilder::new()
+                .label(&format!(
+                    "{} in {}, deleted on {}",
+                    entity_type_desc(item.entity_type),
+                    item.project_name,
+                    item.deleted_at.format("%Y-%m-%d %H:%M")
+                ))
+                .xalign(0.0)
+                .ellipsize(pango::EllipsizeMode::End)
+                .build();
+            details.get_style_context().add_class("dim-label");
+            labels.add(&details);
+            row.add(&labels);
+
+            let restore_btn = gtk::ButtonBuilder::new()
+                .label("Restore")
+                .valign(gtk::Align::Center)
+                .build();
+            relm::connect!(
+                self.model.relm,
+                &restore_btn,
+                connect_clicked(_),
+                Msg::Restore(item.entity_type, item.entity_id)
+            );
+            row.add(&restore_btn);
+
+            let purge_btn = gtk::ButtonBuilder::new()
+                .label("Delete")
+                .valign(gtk::Align::Center)
+                .build();
+            purge_btn
+                .get_style_context()
+                .add_class("destructive-action");
+            relm::connect!(
+                self.model.relm,
+                &purge_btn,
+                connect_clicked(_),
+                Msg::Purge(item.entity_type, item.entity_id)
+            );
+            row.add(&purge_btn);
+            self.widgets.trash_list.add(&row);
+        }
+        self.widgets
+            .empty_trash_btn
+            .set_sensitive(!items.is_empty());
+        self.widgets.trash_list.show_all();
+    }
+
+    fn update(&mut self, event: Msg) {
+        match event {
+            Msg::KeyPress(key) => {
+                if key.get_keyval() == gdk::keys::constants::Escape {
+                    self.widgets.trash_win.close();
+                }
+            }
+            Msg::GotTrashItems(Ok(items)) => self.populate_trash_items(items),
+            Msg::GotTrashItems(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
+            Msg::Restore(entity_type, id) => {
+                self.run_trash_action("Error restoring from the trash", move |sql_conn| {
+                    trash::restore(sql_conn, entity_type, id)
+                });
+            }
+            Msg::Purge(entity_type, id) => {
+                self.run_trash_action("Error deleting from the trash", move |sql_conn| {
+                    trash::purge(sql_conn, entity_type, id)
+                });
+            }
+            Msg::AskEmptyTrash => {
+                let relm = self.model.relm.clone();
+                standard_dialogs::confirm_deletion(
+                    "Empty the trash",
+                    "Are you sure you want to delete for good all the items in the trash? This action cannot be undone.",
+                    self.widgets.trash_win.clone().upcast::<gtk::Widget>(),
+                    move || relm.stream().emit(Msg::EmptyTrash),
+                );
+            }
+            Msg::EmptyTrash => {
+                self.run_trash_action("Error emptying the trash", |sql_conn| {
+                    trash::purge_older_than(sql_conn, 0).map(|_| ())
+                });
+            }
+            Msg::ActionApplied(r) => {
+                if let Err((msg, e)) = r {
+                    standard_dialogs::display_error_str(msg, e);
+                }
+                self.fetch_trash_items();
+                self.model.relm.stream().emit(Msg::TrashChanged);
+            }
+            // meant for my parent
+            Msg::TrashChanged => {}
+        }
+    }
+
+    view! {
+        #[name="trash_win"]
+        gtk::Window {
+            titlebar: view! {
+                gtk::HeaderBar {
+                    title: Some("Trash"),
+                    show_close_button: true,
+                    #[name="empty_trash_btn"]
+                    #[style_class="destructive-action"]
+                    gtk::Button {
+                        label: "Empty trash",
+                        sensitive: false,
+                        clicked => Msg::AskEmptyTrash,
+                    },
+                }
+            },
+            property_default_width: 600,
+            property_default_height: 400,
+            gtk::ScrolledWindow {
+                #[name="trash_list"]
+                gtk::ListBox {
+                    selection_mode: gtk::SelectionMode::None,
+                },
+            },
+            key_press_event(_, key) => (Msg::KeyPress(key.clone()), Inhibit(false)), // just for the ESC key.. surely there's a better way..
+        }
+    }
+}
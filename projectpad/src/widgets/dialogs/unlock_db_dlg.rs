@@ -11,7 +11,7 @@ use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
 
-type CheckPassResult = Result<(), String>;
+type CheckPassResult = projectpadsql::Result<()>;
 
 #[derive(Msg)]
 pub enum Msg {
@@ -92,7 +92,11 @@ impl Widget for UnlockDbDialog {
                     self.model
                         .db_sender
                         .send(SqlFunc::new(move |db_conn| {
-                            let r = projectpadsql::try_unlock_db(db_conn, &p);
+                            let r = projectpadsql::try_unlock_db_file(
+                                db_conn,
+                                &p,
+                                &projectpadsql::database_path(),
+                            );
                             if r.is_ok() && is_save_to_keyring {
                                 if let Err(msg) = keyring_helpers::set_pass_in_keyring(&p) {
                                     standard_dialogs::display_error_str(
@@ -106,8 +110,8 @@ impl Widget for UnlockDbDialog {
                         .unwrap();
                 }
             }
-            Msg::CheckedPassword(Err(msg)) => {
-                standard_dialogs::display_error_str("Error checking the password", Some(msg));
+            Msg::CheckedPassword(Err(e)) => {
+                standard_dialogs::display_error("Error checking the password", Some(Box::new(e)));
             }
             Msg::CheckedPassword(Ok(_)) => {}
         }
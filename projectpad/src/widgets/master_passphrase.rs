@@ -0,0 +1,201 @@
+// the master passphrase (see projectpadsql::secrets): when it's enabled, the
+// passwords are stored encrypted, and we ask for the passphrase the first time
+// one of them is needed. The key is then kept in memory for a few minutes, so
+// that the user doesn't have to type the passphrase for every password.
+use super::dialogs::standard_dialogs;
+use crate::sql_thread::SqlFunc;
+use gtk::prelude::*;
+use projectpadsql::secrets::{self, SecretKey, SecretSession};
+use std::cell::RefCell;
+use std::sync::mpsc;
+use std::time::Duration;
+
+const UNLOCK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+thread_local! {
+    // only the GUI thread uses the session
+    static SESSION: RefCell<SecretSession> = RefCell::new(SecretSession::new(UNLOCK_TIMEOUT));
+}
+
+/// remember the key, for instance after enabling the master passphrase
+pub fn unlocked(key: SecretKey) {
+    SESSION.with(|s| s.borrow_mut().unlocked(key));
+}
+
+/// forget the key, the passphrase will be asked again the next time
+pub fn lock() {
+    SESSION.with(|s| s.borrow_mut().lock());
+}
+
+/// calls the callback with the key, asking for the passphrase if we don't
+/// have the key in memory. The callback is not called if the user cancels.
+pub fn with_key(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    widget: &gtk::Widget,
+    cb: impl FnOnce(SecretKey) + 'static,
+) {
+    if let Some(key) = SESSION.with(|s| s.borrow_mut().key()) {
+        cb(key);
+        return;
+    }
+    let dialog = gtk::MessageDialogBuilder::new()
+        .title("Master passphrase")
+        .text("The passwords are protected by a master passphrase")
+        .secondary_text("Please enter the master passphrase")
+        .message_type(gtk::MessageType::Question)
+        .transient_for(&standard_dialogs::get_main_window(widget.clone()))
+        .modal(true)
+        .build();
+    let entry = gtk::EntryBuilder::new()
+        .input_purpose(gtk::InputPurpose::Password)
+        .visibility(false)
+        .activates_default(true)
+        .margin_start(25)
+        .margin_end(25)
+        .build();
+    dialog.get_content_area().add(&entry);
+    dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+    dialog.add_button("Unlock", gtk::ResponseType::Ok);
+    dialog.set_default_response(gtk::ResponseType::Ok);
+    let db_sender = db_sender.clone();
+    // connect_response wants a Fn, but we call the callback only once
+    let cb = RefCell::new(Some(cb));
+    dialog.connect_response(move |dlg, resp| {
+        if resp == gtk::ResponseType::Ok {
+            if let Some(cb) = cb.borrow_mut().take() {
+                unlock(&db_sender, entry.get_text().to_string(), cb);
+            }
+        }
+        dlg.close();
+    });
+    dialog.show_all();
+}
+
+fn unlock(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    passphrase: String,
+    cb: impl FnOnce(SecretKey) + 'static,
+) {
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    db_sender
+        .send(SqlFunc::new(move |sql_conn| {
+            sender.send(secrets::unlock(sql_conn, &passphrase)).unwrap();
+        }))
+        .unwrap();
+    let mut cb = Some(cb);
+    receiver.attach(None, move |r: projectpadsql::Result<SecretKey>| {
+        match r {
+            Ok(key) => {
+                unlocked(key.clone());
+                if let Some(cb) = cb.take() {
+                    cb(key);
+                }
+            }
+            Err(e) => standard_dialogs::display_error_str(
+                "Cannot unlock the passwords",
+                Some(e.to_string()),
+            ),
+        }
+        glib::Continue(false)
+    });
+}
+
+/// calls the callback with the password in plain text, asking for the
+/// master passphrase if the password is encrypted
+pub fn reveal(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    widget: &gtk::Widget,
+    value: &str,
+    cb: impl FnOnce(String) + 'static,
+) {
+    if !secrets::is_encrypted(value) {
+        cb(value.to_string());
+        return;
+    }
+    let value = value.to_string();
+    with_key(db_sender, widget, move |key| match key.decrypt(&value) {
+        Ok(plain) => cb(plain),
+        Err(e) => {
+            standard_dialogs::display_error_str("Cannot decrypt the password", Some(e.to_string()))
+        }
+    });
+}
+
+/// calls the callback with the password as it must be saved: encrypted
+/// if the master passphrase is enabled, in plain text otherwise
+pub fn protect(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    widget: &gtk::Widget,
+    value: String,
+    cb: impl FnOnce(String) + 'static,
+) {
+    if value.is_empty() || secrets::is_encrypted(&value) {
+        cb(value);
+        return;
+    }
+    protect_with(db_sender, widget, value, |key, v| key.encrypt(v), cb);
+}
+
+/// like `protect`, for the passwords in a note
+pub fn protect_note(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    widget: &gtk::Widget,
+    contents: String,
+    cb: impl FnOnce(String) + 'static,
+) {
+    if !secrets::note_has_plain_passwords(&contents) {
+        cb(contents);
+        return;
+    }
+    protect_with(
+        db_sender,
+        widget,
+        contents,
+        |key, c| key.encrypt_note(c),
+        cb,
+    );
+}
+
+fn protect_with(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    widget: &gtk::Widget,
+    value: String,
+    encrypt: impl FnOnce(&SecretKey, &str) -> String + 'static,
+    cb: impl FnOnce(String) + 'static,
+) {
+    key_if_enabled(db_sender, widget, move |key| match key {
+        Some(key) => cb(encrypt(&key, &value)),
+        None => cb(value),
+    });
+}
+
+/// calls the callback with the key if the master passphrase is enabled,
+/// with None if it isn't
+pub fn key_if_enabled(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    widget: &gtk::Widget,
+    cb: impl FnOnce(Option<SecretKey>) + 'static,
+) {
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    db_sender
+        .send(SqlFunc::new(move |sql_conn| {
+            sender.send(secrets::is_enabled(sql_conn)).unwrap();
+        }))
+        .unwrap();
+    let db_sender = db_sender.clone();
+    let widget = widget.clone();
+    let mut cb = Some(cb);
+    receiver.attach(None, move |enabled: projectpadsql::Result<bool>| {
+        if let Some(cb) = cb.take() {
+            match enabled {
+                Ok(true) => with_key(&db_sender, &widget, move |key| cb(Some(key))),
+                Ok(false) => cb(None),
+                Err(e) => standard_dialogs::display_error_str(
+                    "Error reading the master passphrase settings",
+                    Some(e.to_string()),
+                ),
+            }
+        }
+        glib::Continue(false)
+    });
+}
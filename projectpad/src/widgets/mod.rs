@@ -1,5 +1,9 @@
+mod attachments;
+mod custom_fields;
 mod dialogs;
 mod keyring_helpers;
+mod master_passphrase;
+mod otp_code;
 pub mod password_field;
 mod project_badge;
 mod project_items_list;
@@ -16,6 +20,7 @@ pub mod search_view;
 mod search_view_render;
 mod server_item_list_item;
 mod server_poi_contents;
+mod tag_chips;
 mod tooltips_overlay;
 pub mod win;
 mod wintitlebar;
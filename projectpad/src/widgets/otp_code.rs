@@ -0,0 +1,126 @@
+// the one-time code of the items with an OTP secret (see projectpadsql::totp),
+// refreshed every second, with the seconds left before the next code.
+// If the secret is protected by the master passphrase, the code is only
+// displayed once the user asks for it.
+use super::dialogs::standard_dialogs;
+use super::master_passphrase;
+use crate::sql_thread::SqlFunc;
+use gtk::prelude::*;
+use projectpadsql::secrets;
+use projectpadsql::totp::Totp;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+/// add a "One-time code" row to an item grid, as populated by `populate_grid`
+pub fn attach_to_grid(
+    grid: &gtk::Grid,
+    row: i32,
+    db_sender: &mpsc::Sender<SqlFunc>,
+    otp_secret: &str,
+    on_copied: impl Fn() + 'static,
+) {
+    let label = gtk::LabelBuilder::new()
+        .label("One-time code")
+        .halign(gtk::Align::End) // right align as per gnome HIG
+        .build();
+    label.get_style_context().add_class("item_label");
+    grid.attach(&label, 0, row, 1, 1);
+    grid.attach(&code_box(db_sender, otp_secret, on_copied), 1, row, 1, 1);
+    grid.show_all();
+}
+
+fn code_box(
+    db_sender: &mpsc::Sender<SqlFunc>,
+    otp_secret: &str,
+    on_copied: impl Fn() + 'static,
+) -> gtk::Box {
+    let hbox = gtk::BoxBuilder::new().spacing(5).build();
+    let code_label = gtk::LabelBuilder::new().xalign(0.0).build();
+    let remaining_label = gtk::LabelBuilder::new().build();
+    remaining_label.get_style_context().add_class("dim-label");
+    hbox.add(&code_label);
+    hbox.add(&remaining_label);
+
+    if secrets::is_encrypted(otp_secret) {
+        code_label.set_text("●●●●●●");
+        let show_btn = gtk::ButtonBuilder::new()
+            .label("Show")
+            .relief(gtk::ReliefStyle::None)
+            .build();
+        let db_sender = db_sender.clone();
+        let secret = otp_secret.to_string();
+        let code_label = code_label.clone();
+        let remaining_label = remaining_label.clone();
+        show_btn.connect_clicked(move |btn| {
+            let btn = btn.clone();
+            let code_label = code_label.clone();
+            let remaining_label = remaining_label.clone();
+            master_passphrase::reveal(&db_sender, btn.upcast_ref(), &secret, move |plain| {
+                btn.hide();
+                start_refreshing(&code_label, &remaining_label, &plain);
+            });
+        });
+        hbox.add(&show_btn);
+    } else {
+        start_refreshing(&code_label, &remaining_label, otp_secret);
+    }
+
+    let copy_btn = gtk::ButtonBuilder::new()
+        .image(&gtk::Image::from_icon_name(
+            Some("edit-copy-symbolic"),
+            gtk::IconSize::Menu,
+        ))
+        .relief(gtk::ReliefStyle::None)
+        .tooltip_text("Copy the one-time code")
+        .build();
+    let db_sender = db_sender.clone();
+    let secret = otp_secret.to_string();
+    let on_copied = Rc::new(on_copied);
+    copy_btn.connect_clicked(move |btn| {
+        let display = btn.get_display();
+        let on_copied = on_copied.clone();
+        master_passphrase::reveal(
+            &db_sender,
+            btn.upcast_ref(),
+            &secret,
+            move |plain| match plain.parse::<Totp>() {
+                Ok(totp) => {
+                    if let Some(clip) = gtk::Clipboard::get_default(&display) {
+                        clip.set_text(&totp.current_code().0);
+                    }
+                    on_copied();
+                }
+                Err(e) => {
+                    standard_dialogs::display_error_str("Invalid OTP secret", Some(e.to_string()))
+                }
+            },
+        );
+    });
+    hbox.add(&copy_btn);
+    hbox
+}
+
+fn start_refreshing(code_label: &gtk::Label, remaining_label: &gtk::Label, otp_secret: &str) {
+    let totp = match otp_secret.parse::<Totp>() {
+        Ok(totp) => totp,
+        Err(e) => {
+            code_label.set_text("Invalid OTP secret");
+            code_label.set_tooltip_text(Some(&e.to_string()));
+            return;
+        }
+    };
+    // weak references: we stop refreshing when the grid is cleared
+    let code_label = code_label.downgrade();
+    let remaining_label = remaining_label.downgrade();
+    let refresh = move || match (code_label.upgrade(), remaining_label.upgrade()) {
+        (Some(code_label), Some(remaining_label)) => {
+            let (code, remaining_secs) = totp.current_code();
+            code_label.set_text(&code);
+            remaining_label.set_text(&format!("{}s", remaining_secs));
+            glib::Continue(true)
+        }
+        _ => glib::Continue(false),
+    };
+    refresh();
+    glib::timeout_add_seconds_local(1, refresh);
+}
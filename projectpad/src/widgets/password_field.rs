@@ -1,12 +1,20 @@
+use super::master_passphrase;
 use crate::icons::Icon;
+use crate::sql_thread::SqlFunc;
 use gtk::prelude::*;
+use projectpadsql::secrets;
 use relm::Widget;
 use relm_derive::{widget, Msg};
+use std::sync::mpsc;
 
 #[derive(Msg)]
 pub enum Msg {
     RevealPassword(gtk::ModelButton),
+    GotRevealedPassword(String, gtk::ModelButton),
     CopyPassword,
+    /// for the passwords of the items: they're encrypted if the master
+    /// passphrase is enabled
+    ProtectWithMasterPassphrase(mpsc::Sender<SqlFunc>),
     RequestPassword,
     PublishPassword(String),
     PasswordChanged(String),
@@ -24,6 +32,9 @@ pub struct Model {
     text: String,
     activates_default: ActivatesDefault,
     popover: Option<gtk::Popover>,
+    db_sender: Option<mpsc::Sender<SqlFunc>>,
+    // the plain text of `text`, if it's encrypted and the user revealed it
+    revealed: Option<String>,
 }
 
 #[widget]
@@ -91,6 +102,8 @@ impl Widget for PasswordField {
             text,
             activates_default,
             popover: None,
+            db_sender: None,
+            revealed: None,
         }
     }
 
@@ -98,20 +111,68 @@ impl Widget for PasswordField {
         match event {
             Msg::RevealPassword(popover_reveal_btn) => {
                 let new_reveal = !self.widgets.password_entry.get_visibility();
-                self.widgets.password_entry.set_visibility(new_reveal);
-                popover_reveal_btn.set_property_active(new_reveal);
+                let text = self.widgets.password_entry.get_text();
+                match &self.model.db_sender {
+                    Some(db_sender) if new_reveal && secrets::is_encrypted(&text) => {
+                        let stream = self.model.relm.stream().clone();
+                        master_passphrase::reveal(
+                            db_sender,
+                            self.widgets.password_entry.upcast_ref(),
+                            &text,
+                            move |plain| {
+                                stream.emit(Msg::GotRevealedPassword(plain, popover_reveal_btn))
+                            },
+                        );
+                    }
+                    _ => {
+                        self.widgets.password_entry.set_visibility(new_reveal);
+                        popover_reveal_btn.set_property_active(new_reveal);
+                    }
+                }
+            }
+            Msg::GotRevealedPassword(plain, popover_reveal_btn) => {
+                self.widgets.password_entry.set_text(&plain);
+                self.model.revealed = Some(plain);
+                self.widgets.password_entry.set_visibility(true);
+                popover_reveal_btn.set_property_active(true);
             }
             Msg::CopyPassword => {
-                if let Some(clip) =
-                    gtk::Clipboard::get_default(&self.widgets.password_entry.get_display())
-                {
-                    clip.set_text(self.widgets.password_entry.get_text().as_str());
+                let password_entry = self.widgets.password_entry.clone();
+                let copy = move |text: String| {
+                    if let Some(clip) = gtk::Clipboard::get_default(&password_entry.get_display()) {
+                        clip.set_text(&text);
+                    }
+                };
+                let text = self.widgets.password_entry.get_text().to_string();
+                match &self.model.db_sender {
+                    Some(db_sender) => master_passphrase::reveal(
+                        db_sender,
+                        self.widgets.password_entry.upcast_ref(),
+                        &text,
+                        copy,
+                    ),
+                    None => copy(text),
                 }
             }
+            Msg::ProtectWithMasterPassphrase(db_sender) => {
+                self.model.db_sender = Some(db_sender);
+            }
             Msg::RequestPassword => {
-                self.model.relm.stream().emit(Msg::PublishPassword(
-                    self.widgets.password_entry.get_text().to_string(),
-                ));
+                let text = self.widgets.password_entry.get_text().to_string();
+                let stream = self.model.relm.stream().clone();
+                match &self.model.db_sender {
+                    // revealed but not modified: keep the encrypted value
+                    _ if self.model.revealed.as_ref() == Some(&text) => {
+                        stream.emit(Msg::PublishPassword(self.model.text.clone()))
+                    }
+                    Some(db_sender) => master_passphrase::protect(
+                        db_sender,
+                        self.widgets.password_entry.upcast_ref(),
+                        text,
+                        move |p| stream.emit(Msg::PublishPassword(p)),
+                    ),
+                    None => stream.emit(Msg::PublishPassword(text)),
+                }
             }
             Msg::PublishPassword(_) => {}
             Msg::PasswordChanged(_) => {}
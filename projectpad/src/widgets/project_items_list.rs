@@ -1,12 +1,13 @@
 use super::project_poi_list_item::Model as PrjPoiItemModel;
 use super::project_poi_list_item::ProjectPoiListItem;
+use super::tag_chips::{self, ItemTags};
 use crate::icons::*;
 use crate::sql_thread::SqlFunc;
 use diesel::prelude::*;
 use gtk::prelude::*;
 use itertools::Itertools;
 use projectpadsql::models::{
-    EnvironmentType, InterestType, Project, ProjectNote, ProjectPointOfInterest, Server,
+    EntityType, InterestType, Project, ProjectNote, ProjectPointOfInterest, Server,
     ServerAccessType, ServerLink, ServerType,
 };
 use relm::{ContainerWidget, Widget};
@@ -15,8 +16,8 @@ use std::collections::{BTreeSet, HashMap};
 use std::sync::mpsc;
 
 type ChannelData = (
-    (Vec<ProjectItem>, HashMap<i32, String>),
-    Option<EnvironmentType>,
+    (Vec<ProjectItem>, HashMap<i32, String>, ItemTags),
+    Option<String>,
     Option<ProjectItem>,
 );
 
@@ -28,14 +29,26 @@ pub enum ProjectItem {
     ProjectPointOfInterest(ProjectPointOfInterest),
 }
 
+impl ProjectItem {
+    /// the entity type and id of the item, for the items which can have tags
+    fn taggable_entity(&self) -> Option<(EntityType, i32)> {
+        match self {
+            ProjectItem::Server(srv) => Some((EntityType::Server, srv.id)),
+            ProjectItem::ServerLink(_) => None,
+            ProjectItem::ProjectNote(note) => Some((EntityType::ProjectNote, note.id)),
+            ProjectItem::ProjectPointOfInterest(poi) => Some((EntityType::ProjectPoi, poi.id)),
+        }
+    }
+}
+
 #[derive(Msg)]
 pub enum Msg {
     ActiveProjectChanged(Project),
-    ActiveEnvironmentChanged(EnvironmentType),
+    ActiveEnvironmentChanged(String),
     GotProjectItems(Box<ChannelData>), // large variant size hence boxed
     ProjectItemIndexSelected(Option<usize>),
     ProjectItemSelected(Option<ProjectItem>),
-    ProjectItemSelectedFromElsewhere((Project, Option<EnvironmentType>, Option<ProjectItem>)),
+    ProjectItemSelectedFromElsewhere((Project, Option<String>, Option<ProjectItem>)),
     RefreshItemList(Option<ProjectItem>),
 }
 
@@ -43,9 +56,11 @@ pub struct Model {
     db_sender: mpsc::Sender<SqlFunc>,
     relm: relm::Relm<ProjectItemsList>,
     project: Option<Project>,
-    environment: EnvironmentType,
+    /// the name of the environment
+    environment: String,
     project_items: Vec<ProjectItem>,
     project_item_groups_start_indexes: HashMap<i32, String>,
+    project_item_tags: ItemTags,
     _channel: relm::Channel<ChannelData>,
     sender: relm::Sender<ChannelData>,
 }
@@ -67,9 +82,10 @@ impl Widget for ProjectItemsList {
         Model {
             relm: relm.clone(),
             project: None,
-            environment: EnvironmentType::EnvProd,
+            environment: "".to_string(),
             project_items: Vec::new(),
             project_item_groups_start_indexes: HashMap::new(),
+            project_item_tags: HashMap::new(),
             sender,
             _channel: channel,
             db_sender,
@@ -108,7 +124,7 @@ impl Widget for ProjectItemsList {
 
     fn fetch_project_items_sql(
         sql_conn: &diesel::SqliteConnection,
-        env: EnvironmentType,
+        env_name: &str,
         cur_project_id: Option<i32>,
     ) -> (
         Vec<Server>,
@@ -116,37 +132,50 @@ impl Widget for ProjectItemsList {
         Vec<ProjectNote>,
         Vec<ProjectPointOfInterest>,
     ) {
+        use projectpadsql::schema::environment::dsl as env;
         use projectpadsql::schema::project_note::dsl as pnt;
+        use projectpadsql::schema::project_note_environment::dsl as pnt_env;
         use projectpadsql::schema::project_point_of_interest::dsl as ppoi;
         use projectpadsql::schema::server::dsl as srv;
         use projectpadsql::schema::server_link::dsl as lsrv;
         match cur_project_id {
             Some(pid) => {
                 let srvs = srv::server
-                    .filter(srv::project_id.eq(pid).and(srv::environment.eq(env)))
+                    .filter(
+                        srv::project_id
+                            .eq(pid)
+                            .and(srv::environment.eq(env_name))
+                            .and(srv::deleted_at.is_null()),
+                    )
                     .order((srv::group_name.asc(), srv::desc.asc()))
                     .load::<Server>(sql_conn)
                     .unwrap();
                 let lsrvs = lsrv::server_link
-                    .filter(lsrv::project_id.eq(pid).and(lsrv::environment.eq(env)))
+                    .filter(
+                        lsrv::project_id
+                            .eq(pid)
+                            .and(lsrv::environment.eq(env_name))
+                            .and(lsrv::deleted_at.is_null()),
+                    )
                     .order((lsrv::group_name.asc(), lsrv::desc.asc()))
                     .load::<ServerLink>(sql_conn)
                     .unwrap();
-                let mut prj_query = pnt::project_note
-                    .filter(pnt::project_id.eq(pid))
-                    .into_boxed();
-                prj_query = match env {
-                    EnvironmentType::EnvProd => prj_query.filter(pnt::has_prod.eq(true)),
-                    EnvironmentType::EnvUat => prj_query.filter(pnt::has_uat.eq(true)),
-                    EnvironmentType::EnvStage => prj_query.filter(pnt::has_stage.eq(true)),
-                    EnvironmentType::EnvDevelopment => prj_query.filter(pnt::has_dev.eq(true)),
-                };
-                let prj_notes = prj_query
+                let prj_notes = pnt::project_note
+                    .filter(
+                        pnt::project_id.eq(pid).and(pnt::deleted_at.is_null()).and(
+                            pnt::id.eq_any(
+                                pnt_env::project_note_environment
+                                    .inner_join(env::environment)
+                                    .filter(env::project_id.eq(pid).and(env::name.eq(env_name)))
+                                    .select(pnt_env::project_note_id),
+                            ),
+                        ),
+                    )
                     .order((pnt::group_name.asc(), pnt::title.asc()))
                     .load::<ProjectNote>(sql_conn)
                     .unwrap();
                 let prj_pois = ppoi::project_point_of_interest
-                    .filter(ppoi::project_id.eq(pid))
+                    .filter(ppoi::project_id.eq(pid).and(ppoi::deleted_at.is_null()))
                     .order((ppoi::group_name.asc(), ppoi::desc.asc()))
                     .load::<ProjectPointOfInterest>(sql_conn)
                     .unwrap();
@@ -158,17 +187,17 @@ impl Widget for ProjectItemsList {
 
     fn fetch_project_items(
         &mut self,
-        env_to_select: Option<EnvironmentType>,
+        env_to_select: Option<String>,
         pi_to_select: Option<ProjectItem>,
     ) {
         let s = self.model.sender.clone();
         let cur_project_id = self.model.project.as_ref().map(|p| p.id);
-        let env = self.model.environment;
+        let env = self.model.environment.clone();
         self.model
             .db_sender
             .send(SqlFunc::new(move |sql_conn| {
                 let (servers, lsrvs, prj_notes, prj_pois) =
-                    Self::fetch_project_items_sql(sql_conn, env, cur_project_id);
+                    Self::fetch_project_items_sql(sql_conn, &env, cur_project_id);
 
                 let mut group_names: BTreeSet<&String> = servers
                     .iter()
@@ -209,8 +238,12 @@ impl Widget for ProjectItemsList {
                         Some(group_name),
                     );
                 }
+                let item_tags = tag_chips::load_item_tags(
+                    sql_conn,
+                    items.iter().filter_map(|i| i.taggable_entity()),
+                );
                 s.send((
-                    (items, group_start_indexes),
+                    (items, group_start_indexes, item_tags),
                     env_to_select,
                     pi_to_select.as_ref().cloned(),
                 ))
@@ -232,6 +265,7 @@ impl Widget for ProjectItemsList {
                 }
                 self.model.project_items = items.0;
                 self.model.project_item_groups_start_indexes = items.1;
+                self.model.project_item_tags = items.2;
                 self.update_items_list();
                 let row_idx = self
                     .model
@@ -273,7 +307,7 @@ impl Widget for ProjectItemsList {
                 }
             }
             Msg::ActiveEnvironmentChanged(env) => {
-                self.model.environment = env;
+                self.model.environment = env.clone();
                 self.fetch_project_items(Some(env), None);
             }
             Msg::ProjectItemIndexSelected(row_idx) => {
@@ -286,18 +320,22 @@ impl Widget for ProjectItemsList {
             }
             Msg::ProjectItemSelectedFromElsewhere((project, env, pi)) => {
                 self.model.project = Some(project);
-                if let Some(e) = env {
-                    self.model.environment = e;
+                if let Some(e) = &env {
+                    self.model.environment = e.clone();
                 }
                 self.fetch_project_items(env, pi);
             }
             Msg::RefreshItemList(selected_pi) => {
-                self.fetch_project_items(Some(self.model.environment), selected_pi);
+                self.fetch_project_items(Some(self.model.environment.clone()), selected_pi);
             }
         }
     }
 
-    fn get_item_model(project_item: &ProjectItem) -> PrjPoiItemModel {
+    fn get_item_model(project_item: &ProjectItem, item_tags: &ItemTags) -> PrjPoiItemModel {
+        let tags = project_item
+            .taggable_entity()
+            .and_then(|e| item_tags.get(&e).cloned())
+            .unwrap_or_else(Vec::new);
         match project_item {
             ProjectItem::Server(srv) => PrjPoiItemModel {
                 markup: if srv.is_retired {
@@ -306,6 +344,7 @@ impl Widget for ProjectItemsList {
                     glib::markup_escape_text(&srv.desc).to_string()
                 },
                 group_name: srv.group_name.as_ref().cloned(),
+                tags,
                 icon: match (srv.server_type, srv.access_type) {
                     (ServerType::SrvDatabase, _) => Icon::DATABASE,
                     (ServerType::SrvReporting, _) => Icon::REPORTING,
@@ -318,16 +357,19 @@ impl Widget for ProjectItemsList {
             ProjectItem::ServerLink(link) => PrjPoiItemModel {
                 markup: glib::markup_escape_text(&link.desc).to_string(),
                 group_name: link.group_name.as_ref().cloned(),
+                tags,
                 icon: Icon::SERVER_LINK,
             },
             ProjectItem::ProjectNote(note) => PrjPoiItemModel {
                 markup: glib::markup_escape_text(&note.title).to_string(),
                 group_name: note.group_name.as_ref().cloned(),
+                tags,
                 icon: Icon::NOTE,
             },
             ProjectItem::ProjectPointOfInterest(poi) => PrjPoiItemModel {
                 markup: glib::markup_escape_text(&poi.desc).to_string(),
                 group_name: poi.group_name.as_ref().cloned(),
+                tags,
                 icon: match poi.interest_type {
                     InterestType::PoiLogFile => Icon::LOG_FILE,
                     InterestType::PoiConfigFile => Icon::CONFIG_FILE,
@@ -348,7 +390,10 @@ impl Widget for ProjectItemsList {
             let _child = self
                 .widgets
                 .project_items_list
-                .add_widget::<ProjectPoiListItem>(Self::get_item_model(project_item));
+                .add_widget::<ProjectPoiListItem>(Self::get_item_model(
+                    project_item,
+                    &self.model.project_item_tags,
+                ));
         }
         let indexes = self.model.project_item_groups_start_indexes.clone();
         self.widgets
@@ -1,14 +1,19 @@
+use super::dialogs::standard_dialogs;
 use super::project_badge::Msg as ProjectBadgeMsg;
 use super::project_badge::ProjectBadge;
 use crate::sql_thread::SqlFunc;
 use diesel::prelude::*;
 use gtk::prelude::*;
 use projectpadsql::models::Project;
+use projectpadsql::repo::ProjectRepo;
 use relm::ContainerWidget;
 use relm::{Component, Widget};
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
 
+// String for details, because I can't pass Error across threads
+type ProjectsResult = Result<Vec<Project>, (&'static str, Option<String>)>;
+
 #[derive(Clone)]
 pub enum UpdateParents {
     Yes,
@@ -19,7 +24,7 @@ pub enum UpdateParents {
 pub enum Msg {
     DbPrepared,
     ProjectActivated((Project, UpdateParents)),
-    GotProjects(Vec<Project>),
+    GotProjects(ProjectsResult),
     ProjectSelectedFromElsewhere(i32),
     ForceReload,
     ProjectListChanged,
@@ -37,8 +42,8 @@ pub struct Model {
     active_project_id: Option<i32>,
     // need to keep hold of the children widgets
     children_widgets: Vec<Component<ProjectBadge>>,
-    _channel: relm::Channel<Vec<Project>>,
-    sender: relm::Sender<Vec<Project>>,
+    _channel: relm::Channel<ProjectsResult>,
+    sender: relm::Sender<ProjectsResult>,
 }
 
 #[widget]
@@ -47,7 +52,7 @@ impl Widget for ProjectList {
 
     fn model(relm: &relm::Relm<Self>, db_sender: mpsc::Sender<SqlFunc>) -> Model {
         let stream = relm.stream().clone();
-        let (channel, sender) = relm::Channel::new(move |prjs: Vec<Project>| {
+        let (channel, sender) = relm::Channel::new(move |prjs: ProjectsResult| {
             stream.emit(Msg::GotProjects(prjs));
         });
         Model {
@@ -69,10 +74,13 @@ impl Widget for ProjectList {
             Msg::ProjectActivated((ref project, _)) => {
                 self.model.active_project_id = Some(project.id);
             }
-            Msg::GotProjects(prjs) => {
+            Msg::GotProjects(Ok(prjs)) => {
                 self.model.projects = prjs;
                 self.update_projects_list();
             }
+            Msg::GotProjects(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
+            }
             Msg::ProjectSelectedFromElsewhere(pid) => {
                 if let Some(prj) = self.model.projects.iter().find(|p| p.id == pid) {
                     self.model
@@ -130,9 +138,10 @@ impl Widget for ProjectList {
         }
     }
 
-    fn load_projects(db_conn: &SqliteConnection) -> Vec<Project> {
-        use projectpadsql::schema::project::dsl::*;
-        project.order(name.asc()).load::<Project>(db_conn).unwrap()
+    fn load_projects(db_conn: &SqliteConnection) -> ProjectsResult {
+        ProjectRepo::new(db_conn)
+            .list()
+            .map_err(|e| ("Error loading the projects", Some(e.to_string())))
     }
 
     fn fetch_projects(&mut self) {
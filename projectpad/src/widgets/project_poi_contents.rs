@@ -1,3 +1,4 @@
+use super::master_passphrase;
 use super::project_items_list::ProjectItem;
 use super::search_bar;
 use super::search_bar::Msg as SearchBarMsg;
@@ -371,12 +372,17 @@ impl Widget for ProjectPoiContents {
         let textview = self.widgets.note_textview.clone();
         let p = password.to_string();
         let r = self.model.relm.clone();
+        let db_sender = self.model.db_sender.clone();
         popover_copy_btn.connect_clicked(move |_| {
-            if let Some(clip) = gtk::Clipboard::get_default(&textview.get_display()) {
-                clip.set_text(&p);
-                r.stream()
-                    .emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
-            }
+            let textview = textview.clone();
+            let r = r.clone();
+            master_passphrase::reveal(&db_sender, textview.upcast_ref(), &p, move |p| {
+                if let Some(clip) = gtk::Clipboard::get_default(&textview.get_display()) {
+                    clip.set_text(&p);
+                    r.stream()
+                        .emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
+                }
+            });
         });
         left_align_menu(&popover_copy_btn);
         popover_vbox.add(&popover_copy_btn);
@@ -385,9 +391,14 @@ impl Widget for ProjectPoiContents {
             .build();
         let p2 = password.to_string();
         let r2 = self.model.relm.clone();
+        let db_sender2 = self.model.db_sender.clone();
+        let textview2 = self.widgets.note_textview.clone();
         popover_reveal_btn.connect_clicked(move |_| {
-            r2.stream()
-                .emit(Msg::ShowInfoBar(format!("The password is: {}", p2.clone())));
+            let r2 = r2.clone();
+            master_passphrase::reveal(&db_sender2, textview2.upcast_ref(), &p2, move |p2| {
+                r2.stream()
+                    .emit(Msg::ShowInfoBar(format!("The password is: {}", p2)));
+            });
         });
         left_align_menu(&popover_reveal_btn);
         popover_vbox.add(&popover_reveal_btn);
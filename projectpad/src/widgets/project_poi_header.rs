@@ -1,3 +1,5 @@
+use super::attachments::AttachmentsRow;
+use super::custom_fields;
 use super::dialogs;
 use super::dialogs::dialog_helpers;
 use super::dialogs::project_note_add_edit_dlg;
@@ -8,10 +10,13 @@ use super::dialogs::server_add_edit_dlg;
 use super::dialogs::server_add_edit_dlg::Msg as MsgServerAddEditDialog;
 use super::dialogs::server_add_item_dlg;
 use super::dialogs::server_add_item_dlg::ServerAddItemDialog;
+use super::dialogs::server_dependencies_dlg::ServerDependenciesDialog;
 use super::dialogs::server_link_add_edit_dlg;
 use super::dialogs::server_link_add_edit_dlg::Msg as MsgServerLinkAddEditDialog;
 use super::dialogs::server_poi_add_edit_dlg;
 use super::dialogs::standard_dialogs;
+use super::master_passphrase;
+use super::otp_code;
 use super::project_items_list::ProjectItem;
 use super::wintitlebar::left_align_menu;
 use crate::icons::Icon;
@@ -21,11 +26,13 @@ use diesel::prelude::*;
 use gdk::prelude::*;
 use gtk::prelude::*;
 use projectpadsql::models::{
-    Project, ProjectNote, ProjectPointOfInterest, Server, ServerAccessType, ServerDatabase,
-    ServerLink, ServerWebsite,
+    EntityType, Project, ProjectNote, ProjectPointOfInterest, Server, ServerAccessType,
+    ServerDatabase, ServerLink, ServerWebsite,
 };
+use projectpadsql::repo::ServerRepo;
 use relm::Widget;
 use relm_derive::{widget, Msg};
+use std::rc::Rc;
 use std::sync::mpsc;
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -35,6 +42,8 @@ pub enum ActionTypes {
     Delete,
     AddItem,
     GotoItem,
+    Attach,
+    Dependencies,
 }
 
 #[derive(Msg, Clone)]
@@ -73,12 +82,14 @@ pub struct Model {
     project_add_edit_dialog: Option<(dialogs::ProjectAddEditDialogComponent, gtk::Dialog)>,
     server_add_item_dialog_component: Option<relm::Component<ServerAddItemDialog>>,
     server_add_item_dialog: Option<gtk::Dialog>,
+    server_dependencies_win: Option<relm::Component<ServerDependenciesDialog>>,
     _project_item_deleted_channel: relm::Channel<DeleteResult>,
     project_item_deleted_sender: relm::Sender<DeleteResult>,
     _goto_server_channel: relm::Channel<GotoResult>,
     goto_server_sender: relm::Sender<GotoResult>,
     _load_linkedserver_channel: relm::Channel<Server>,
     load_linkedserver_sender: relm::Sender<Server>,
+    attachments_row: Option<Rc<AttachmentsRow>>,
 }
 
 #[derive(Debug)]
@@ -239,8 +250,8 @@ pub fn get_project_item_fields(
             GridItem::new(
                 "Address",
                 Some(server_access_icon(&srv)),
-                server_ip_display(&srv),
-                srv.ip.clone(),
+                server_address_display(&srv),
+                srv.address().to_string(),
                 None,
             ),
             GridItem::new(
@@ -318,15 +329,16 @@ pub fn get_project_item_fields(
     }
 }
 
-fn server_ip_display(srv: &Server) -> LabelText {
+fn server_address_display(srv: &Server) -> LabelText {
+    let address = srv.address().to_string();
     if srv.access_type == ServerAccessType::SrvAccessWww {
         LabelText::Markup(format!(
             "<a href=\"{}\">{}</a>",
-            &glib::markup_escape_text(&srv.ip),
-            &glib::markup_escape_text(&srv.ip)
+            &glib::markup_escape_text(&address),
+            &glib::markup_escape_text(&address)
         ))
     } else {
-        LabelText::PlainText(srv.ip.clone())
+        LabelText::PlainText(address)
     }
 }
 
@@ -393,17 +405,25 @@ impl Widget for ProjectPoiHeader {
             _load_linkedserver_channel,
             load_linkedserver_sender,
             server_link_target: None,
+            attachments_row: None,
+            server_dependencies_win: None,
         }
     }
 
     fn copy_to_clipboard(&self, val: &str) {
-        if let Some(clip) = gtk::Clipboard::get_default(&self.widgets.header_grid.get_display()) {
-            clip.set_text(&val);
-        }
-        self.model
-            .relm
-            .stream()
-            .emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
+        let header_grid = self.widgets.header_grid.clone();
+        let stream = self.model.relm.stream().clone();
+        master_passphrase::reveal(
+            &self.model.db_sender,
+            header_grid.upcast_ref(),
+            val,
+            move |val| {
+                if let Some(clip) = gtk::Clipboard::get_default(&header_grid.get_display()) {
+                    clip.set_text(&val);
+                }
+                stream.emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
+            },
+        );
     }
 
     fn update(&mut self, event: Msg) {
@@ -415,6 +435,11 @@ impl Widget for ProjectPoiHeader {
             Msg::HeaderActionClicked((ActionTypes::Copy, val)) => {
                 self.copy_to_clipboard(&val);
             }
+            Msg::HeaderActionClicked((ActionTypes::Attach, _val)) => {
+                if let Some(row) = &self.model.attachments_row {
+                    row.attach_file();
+                }
+            }
             Msg::HeaderActionClicked((ActionTypes::GotoItem, _val)) => {
                 if let Some(ProjectItem::ServerLink(l)) = &self.model.project_item {
                     let s = self.model.goto_server_sender.clone();
@@ -545,6 +570,11 @@ impl Widget for ProjectPoiHeader {
             Msg::HeaderActionClicked((ActionTypes::AddItem, _)) => {
                 self.show_server_add_item_dialog();
             }
+            Msg::HeaderActionClicked((ActionTypes::Dependencies, _)) => {
+                if let Some(ProjectItem::Server(srv)) = self.model.project_item.clone() {
+                    self.show_server_dependencies_dialog(srv);
+                }
+            }
             Msg::ProjectItemRefresh(project_item) => {
                 if let Some((_, dialog)) = self.model.project_add_edit_dialog.as_ref() {
                     dialog.close();
@@ -562,14 +592,9 @@ impl Widget for ProjectPoiHeader {
                 self.model
                     .db_sender
                     .send(SqlFunc::new(move |sql_conn| {
-                        use projectpadsql::schema::project_point_of_interest::dsl as prj_poi;
                         s.send(
-                            sql_util::delete_row(
-                                sql_conn,
-                                prj_poi::project_point_of_interest,
-                                poi_id,
-                            )
-                            .map(|_| ProjectItem::ProjectPointOfInterest(poi.clone())),
+                            sql_util::move_to_trash(sql_conn, EntityType::ProjectPoi, poi_id)
+                                .map(|_| ProjectItem::ProjectPointOfInterest(poi.clone())),
                         )
                         .unwrap();
                     }))
@@ -581,9 +606,8 @@ impl Widget for ProjectPoiHeader {
                 self.model
                     .db_sender
                     .send(SqlFunc::new(move |sql_conn| {
-                        use projectpadsql::schema::project_note::dsl as prj_note;
                         s.send(
-                            sql_util::delete_row(sql_conn, prj_note::project_note, note_id)
+                            sql_util::move_to_trash(sql_conn, EntityType::ProjectNote, note_id)
                                 .map(|_| ProjectItem::ProjectNote(note.clone())),
                         )
                         .unwrap();
@@ -596,9 +620,8 @@ impl Widget for ProjectPoiHeader {
                 self.model
                     .db_sender
                     .send(SqlFunc::new(move |sql_conn| {
-                        use projectpadsql::schema::server_link::dsl as srv_link;
                         s.send(
-                            sql_util::delete_row(sql_conn, srv_link::server_link, link_id)
+                            sql_util::move_to_trash(sql_conn, EntityType::ServerLink, link_id)
                                 .map(|_| ProjectItem::ServerLink(srv_link.clone())),
                         )
                         .unwrap();
@@ -634,10 +657,13 @@ impl Widget for ProjectPoiHeader {
             Msg::OpenLinkOrEditProjectNote => {
                 match self.model.project_item.as_ref() {
                     Some(ProjectItem::Server(srv)) => {
-                        if srv.access_type == ServerAccessType::SrvAccessWww && !srv.ip.is_empty() {
-                            if let Result::Err(e) =
-                                gtk::show_uri_on_window(None::<&gtk::Window>, &srv.ip, 0)
-                            {
+                        if srv.access_type == ServerAccessType::SrvAccessWww && !srv.host.is_empty()
+                        {
+                            if let Result::Err(e) = gtk::show_uri_on_window(
+                                None::<&gtk::Window>,
+                                &srv.address().to_string(),
+                                0,
+                            ) {
                                 eprintln!("Error opening link: {}", e);
                             }
                         } else {
@@ -693,7 +719,7 @@ impl Widget for ProjectPoiHeader {
         standard_dialogs::confirm_deletion(
             &format!("Delete {}", item_type_desc),
             &format!(
-                "Are you sure you want to delete the {} {}? This action cannot be undone.",
+                "Are you sure you want to move the {} {} to the trash?",
                 item_type_desc, item_desc
             ),
             self.widgets.items_frame.clone().upcast::<gtk::Widget>(),
@@ -701,6 +727,26 @@ impl Widget for ProjectPoiHeader {
         );
     }
 
+    fn show_server_dependencies_dialog(&mut self, srv: Server) {
+        let main_win = standard_dialogs::get_main_window(
+            self.widgets
+                .header_actions_btn
+                .clone()
+                .upcast::<gtk::Widget>(),
+        );
+        self.model.server_dependencies_win = Some(
+            relm::init::<ServerDependenciesDialog>((self.model.db_sender.clone(), srv))
+                .expect("error initializing the server dependencies dialog"),
+        );
+        let dependencies_win = self.model.server_dependencies_win.as_ref().unwrap();
+        dependencies_win.widget().set_transient_for(Some(&main_win));
+        dependencies_win
+            .widget()
+            .set_position(gtk::WindowPosition::CenterOnParent);
+        dependencies_win.widget().set_modal(true);
+        dependencies_win.widget().show();
+    }
+
     fn show_server_add_item_dialog(&mut self) {
         let dialog_contents = relm::init::<ServerAddItemDialog>((
             self.model.db_sender.clone(),
@@ -751,7 +797,6 @@ impl Widget for ProjectPoiHeader {
         self.model
             .db_sender
             .send(SqlFunc::new(move |sql_conn| {
-                use projectpadsql::schema::server::dsl as srv;
                 use projectpadsql::schema::server_database::dsl as db;
                 use projectpadsql::schema::server_link::dsl as srv_link;
                 use projectpadsql::schema::server_website::dsl as srvw;
@@ -760,11 +805,15 @@ impl Widget for ProjectPoiHeader {
                 // is being used elsewhere
                 let dependent_websites = srvw::server_website
                     .inner_join(db::server_database)
-                    .filter(db::server_id.eq(server_id))
+                    .filter(db::server_id.eq(server_id).and(srvw::deleted_at.is_null()))
                     .load::<(ServerWebsite, ServerDatabase)>(sql_conn)
                     .unwrap();
                 let dependent_serverlinks = srv_link::server_link
-                    .filter(srv_link::linked_server_id.eq(server_id))
+                    .filter(
+                        srv_link::linked_server_id
+                            .eq(server_id)
+                            .and(srv_link::deleted_at.is_null()),
+                    )
                     .load::<ServerLink>(sql_conn)
                     .unwrap();
                 if !dependent_websites.is_empty() {
@@ -786,7 +835,7 @@ impl Widget for ProjectPoiHeader {
                     )))
                 } else {
                     s.send(
-                        sql_util::delete_row(sql_conn, srv::server, server_id)
+                        sql_util::move_to_trash(sql_conn, EntityType::Server, server_id)
                             .map(|_| ProjectItem::Server(server.clone())),
                     )
                 }
@@ -795,7 +844,7 @@ impl Widget for ProjectPoiHeader {
             .unwrap();
     }
 
-    fn load_project_item(&self) {
+    fn load_project_item(&mut self) {
         self.populate_header();
         self.model.title.set_text(
             self.model
@@ -811,9 +860,7 @@ impl Widget for ProjectPoiHeader {
             self.model
                 .db_sender
                 .send(SqlFunc::new(move |sql_conn| {
-                    use projectpadsql::schema::server::dsl as srv;
-                    let server: Server =
-                        srv::server.find(linked_server_id).first(sql_conn).unwrap();
+                    let server = ServerRepo::new(sql_conn).get(linked_server_id).unwrap();
                     s.send(server).unwrap();
                 }))
                 .unwrap();
@@ -829,7 +876,7 @@ impl Widget for ProjectPoiHeader {
         }
     }
 
-    fn populate_header(&self) {
+    fn populate_header(&mut self) {
         let fields = self
             .model
             .project_item
@@ -874,9 +921,30 @@ impl Widget for ProjectPoiHeader {
             connect_clicked(_),
             Msg::HeaderActionClicked((ActionTypes::Delete, "".to_string()))
         );
+        let attach_btn = gtk::ModelButtonBuilder::new()
+            .label("Attach file...")
+            .build();
+        relm::connect!(
+            self.model.relm,
+            &attach_btn,
+            connect_clicked(_),
+            Msg::HeaderActionClicked((ActionTypes::Attach, "".to_string()))
+        );
+        let dependencies_btn = gtk::ModelButtonBuilder::new()
+            .label("Dependencies...")
+            .build();
+        relm::connect!(
+            self.model.relm,
+            &dependencies_btn,
+            connect_clicked(_),
+            Msg::HeaderActionClicked((ActionTypes::Dependencies, "".to_string()))
+        );
         let extra_btns = match &self.model.project_item {
-            Some(ProjectItem::Server(_)) => vec![add_btn, edit_btn, delete_btn],
+            Some(ProjectItem::Server(_)) => {
+                vec![add_btn, edit_btn, attach_btn, dependencies_btn, delete_btn]
+            }
             Some(ProjectItem::ServerLink(_)) => vec![edit_btn, goto_btn, delete_btn],
+            Some(ProjectItem::ProjectNote(_)) => vec![edit_btn, attach_btn, delete_btn],
             Some(_) => vec![edit_btn, delete_btn],
             _ => vec![],
         };
@@ -908,6 +976,44 @@ impl Widget for ProjectPoiHeader {
                 );
             },
         );
+        let mut row = fields.iter().filter(|f| !f.markup.is_empty()).count() as i32;
+        if let Some(ProjectItem::Server(srv)) = &self.model.project_item {
+            if !srv.otp_secret.is_empty() {
+                let stream = self.model.relm.stream().clone();
+                otp_code::attach_to_grid(
+                    &self.widgets.header_grid,
+                    row,
+                    &self.model.db_sender,
+                    &srv.otp_secret,
+                    move || stream.emit(Msg::ShowInfoBar("Copied to the clipboard".to_string())),
+                );
+                row += 1;
+            }
+            let stream = self.model.relm.stream().clone();
+            custom_fields::load_and_insert_in_grid(
+                &self.widgets.header_grid,
+                row,
+                &self.model.db_sender,
+                EntityType::Server,
+                srv.id,
+                move || stream.emit(Msg::ShowInfoBar("Copied to the clipboard".to_string())),
+            );
+            row += 1;
+        }
+        let attachments_of = match &self.model.project_item {
+            Some(ProjectItem::Server(srv)) => Some((EntityType::Server, srv.id)),
+            Some(ProjectItem::ProjectNote(note)) => Some((EntityType::ProjectNote, note.id)),
+            _ => None,
+        };
+        self.model.attachments_row = attachments_of.map(|(entity_type, entity_id)| {
+            AttachmentsRow::attach_to_grid(
+                &self.widgets.header_grid,
+                row,
+                &self.model.db_sender,
+                entity_type,
+                entity_id,
+            )
+        });
     }
 
     view! {
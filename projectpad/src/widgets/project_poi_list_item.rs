@@ -1,3 +1,4 @@
+use super::tag_chips;
 use crate::icons::Icon;
 use gtk::prelude::*;
 use relm::Widget;
@@ -10,10 +11,19 @@ pub struct Model {
     pub icon: Icon,
     pub markup: String,
     pub group_name: Option<String>,
+    pub tags: Vec<String>,
 }
 
 #[widget]
 impl Widget for ProjectPoiListItem {
+    fn init_view(&mut self) {
+        if !self.model.tags.is_empty() {
+            self.widgets
+                .title_box
+                .add(&tag_chips::tags_box(&self.model.tags));
+        }
+    }
+
     fn model(_relm: &relm::Relm<Self>, model: Model) -> Model {
         model
     }
@@ -40,8 +50,10 @@ impl Widget for ProjectPoiListItem {
                     // property_icon_size: 4, // gtk::IconSize::Dnd
                     pixel_size: 24,
                 },
+                #[name="title_box"]
                 gtk::Box {
                     orientation: gtk::Orientation::Vertical,
+                    spacing: 3,
                     gtk::Label {
                         markup: &self.model.markup,
                         ellipsize: pango::EllipsizeMode::End,
@@ -1,8 +1,10 @@
+use super::attachments;
 use super::dialogs::dialog_helpers;
 use super::dialogs::project_add_edit_dlg::Msg as MsgProjectAddEditDialog;
 use super::dialogs::project_add_edit_dlg::ProjectAddEditDialog;
 use super::dialogs::project_add_item_dlg;
 use super::dialogs::project_add_item_dlg::ProjectAddItemDialog;
+use super::dialogs::server_topology_dlg::ServerTopologyDialog;
 use super::dialogs::standard_dialogs;
 use super::project_items_list::ProjectItem;
 use super::wintitlebar::left_align_menu;
@@ -12,8 +14,9 @@ use crate::sql_util;
 use diesel::prelude::*;
 use gtk::prelude::*;
 use projectpadsql::models::{
-    EnvironmentType, Project, Server, ServerDatabase, ServerLink, ServerWebsite,
+    EntityType, Environment, Project, Server, ServerDatabase, ServerLink, ServerWebsite,
 };
+use projectpadsql::repo::EnvironmentRepo;
 use relm::Widget;
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
@@ -22,11 +25,14 @@ use std::sync::mpsc;
 pub enum Msg {
     ProjectActivated(Project),
     ProjectUpdated(Project),
-    EnvironmentToggled(EnvironmentType), // implementation detail
-    EnvironmentChanged(EnvironmentType),
-    ProjectEnvironmentSelectedFromElsewhere((Project, EnvironmentType)),
+    EnvironmentToggled(String),          // implementation detail
+    GotEnvironments(EnvironmentsLoaded), // implementation detail
+    EnvironmentChanged(String),
+    ProjectEnvironmentSelectedFromElsewhere((Project, String)),
     AddProjectItem,
     EditProject,
+    ShowAttachments,
+    ShowTopology,
     AskDeleteProject,
     DeleteProject,
     ProjectDeleted(Project),
@@ -38,17 +44,25 @@ pub enum Msg {
 // String for details, because I can't pass Error across threads
 type DeleteResult = Result<Project, (&'static str, Option<String>)>;
 
+/// the id of the project, its environments, and the environment to select
+/// without notifying, when it was selected from elsewhere
+type EnvironmentsLoaded = (i32, Vec<Environment>, Option<String>);
+
 pub struct Model {
     relm: relm::Relm<ProjectSummary>,
     db_sender: mpsc::Sender<SqlFunc>,
     project: Option<Project>,
     title: gtk::Label,
-    btn_and_handler: Vec<(gtk::RadioButton, glib::SignalHandlerId)>,
+    /// the environment name, its button and the button's toggled handler
+    env_buttons: Vec<(String, gtk::RadioButton, glib::SignalHandlerId)>,
     header_popover: gtk::Popover,
     project_add_edit_dialog: Option<(relm::Component<ProjectAddEditDialog>, gtk::Dialog)>,
     project_add_item_component: Option<relm::Component<ProjectAddItemDialog>>,
     project_add_item_dialog: Option<gtk::Dialog>,
-    cur_environment: EnvironmentType,
+    topology_win: Option<relm::Component<ServerTopologyDialog>>,
+    cur_environment: String,
+    _environments_channel: relm::Channel<EnvironmentsLoaded>,
+    environments_sender: relm::Sender<EnvironmentsLoaded>,
     _project_deleted_channel: relm::Channel<DeleteResult>,
     project_deleted_sender: relm::Sender<DeleteResult>,
 }
@@ -58,50 +72,6 @@ impl Widget for ProjectSummary {
     fn init_view(&mut self) {
         self.model.title.show_all();
 
-        self.widgets
-            .radio_stg
-            .join_group(Some(&self.widgets.radio_dev));
-        self.widgets
-            .radio_uat
-            .join_group(Some(&self.widgets.radio_stg));
-        self.widgets
-            .radio_prd
-            .join_group(Some(&self.widgets.radio_uat));
-
-        // must tie the signal handlers manually so i can block emission when we get
-        // updated from outside
-        let relm = self.model.relm.clone();
-        self.model.btn_and_handler.push((
-            self.widgets.radio_dev.clone(),
-            self.widgets.radio_dev.connect_toggled(move |_| {
-                relm.stream()
-                    .emit(Msg::EnvironmentToggled(EnvironmentType::EnvDevelopment))
-            }),
-        ));
-        let relm = self.model.relm.clone();
-        self.model.btn_and_handler.push((
-            self.widgets.radio_stg.clone(),
-            self.widgets.radio_stg.connect_toggled(move |_| {
-                relm.stream()
-                    .emit(Msg::EnvironmentToggled(EnvironmentType::EnvStage))
-            }),
-        ));
-        let relm = self.model.relm.clone();
-        self.model.btn_and_handler.push((
-            self.widgets.radio_uat.clone(),
-            self.widgets.radio_uat.connect_toggled(move |_| {
-                relm.stream()
-                    .emit(Msg::EnvironmentToggled(EnvironmentType::EnvUat))
-            }),
-        ));
-        let relm = self.model.relm.clone();
-        self.model.btn_and_handler.push((
-            self.widgets.radio_prd.clone(),
-            self.widgets.radio_prd.connect_toggled(move |_| {
-                relm.stream()
-                    .emit(Msg::EnvironmentToggled(EnvironmentType::EnvProd))
-            }),
-        ));
         self.init_actions_popover();
     }
 
@@ -130,6 +100,26 @@ impl Widget for ProjectSummary {
             Msg::EditProject
         );
         popover_vbox.add(&popover_edit_btn);
+        let popover_attachments_btn = gtk::ModelButtonBuilder::new()
+            .label("Attachments...")
+            .build();
+        left_align_menu(&popover_attachments_btn);
+        relm::connect!(
+            self.model.relm,
+            popover_attachments_btn,
+            connect_clicked(_),
+            Msg::ShowAttachments
+        );
+        popover_vbox.add(&popover_attachments_btn);
+        let popover_topology_btn = gtk::ModelButtonBuilder::new().label("Topology...").build();
+        left_align_menu(&popover_topology_btn);
+        relm::connect!(
+            self.model.relm,
+            popover_topology_btn,
+            connect_clicked(_),
+            Msg::ShowTopology
+        );
+        popover_vbox.add(&popover_topology_btn);
         let popover_delete_btn = gtk::ModelButtonBuilder::new().label("Delete").build();
         left_align_menu(&popover_delete_btn);
         relm::connect!(
@@ -153,6 +143,9 @@ impl Widget for ProjectSummary {
                 Ok(p) => stream.emit(Msg::ProjectDeleted(p)),
                 Err((msg, e)) => standard_dialogs::display_error_str(&msg, e),
             });
+        let stream2 = relm.stream().clone();
+        let (_environments_channel, environments_sender) =
+            relm::Channel::new(move |r: EnvironmentsLoaded| stream2.emit(Msg::GotEnvironments(r)));
         Model {
             project: None,
             db_sender,
@@ -161,17 +154,46 @@ impl Widget for ProjectSummary {
                 .margin_top(8)
                 .margin_bottom(8)
                 .build(),
-            btn_and_handler: vec![],
+            env_buttons: vec![],
             header_popover: gtk::Popover::new(None::<&gtk::Button>),
             project_add_item_dialog: None,
             project_add_item_component: None,
             project_add_edit_dialog: None,
-            cur_environment: EnvironmentType::EnvDevelopment,
+            topology_win: None,
+            cur_environment: "".to_string(),
+            _environments_channel,
+            environments_sender,
             _project_deleted_channel,
             project_deleted_sender,
         }
     }
 
+    fn show_topology(&mut self) {
+        if let Some(prj) = self.model.project.clone() {
+            let main_win = standard_dialogs::get_main_window(
+                self.widgets
+                    .header_actions_btn
+                    .clone()
+                    .upcast::<gtk::Widget>(),
+            );
+            self.model.topology_win = Some(
+                relm::init::<ServerTopologyDialog>((
+                    self.model.db_sender.clone(),
+                    prj,
+                    self.model.cur_environment.clone(),
+                ))
+                .expect("error initializing the topology dialog"),
+            );
+            let topology_win = self.model.topology_win.as_ref().unwrap();
+            topology_win.widget().set_transient_for(Some(&main_win));
+            topology_win
+                .widget()
+                .set_position(gtk::WindowPosition::CenterOnParent);
+            topology_win.widget().set_modal(true);
+            topology_win.widget().show();
+        }
+    }
+
     fn set_project(&mut self, project: Project) {
         self.model.project = Some(project);
         self.model.title.set_markup(
@@ -184,6 +206,48 @@ impl Widget for ProjectSummary {
         );
     }
 
+    fn fetch_environments(&self, project_id: i32, env_to_select: Option<String>) {
+        let s = self.model.environments_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                let envs = EnvironmentRepo::new(sql_conn)
+                    .list_for_project(project_id)
+                    .unwrap();
+                s.send((project_id, envs, env_to_select)).unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn populate_environments(&mut self, envs: Vec<Environment>) {
+        for (_, btn, _) in self.model.env_buttons.drain(..) {
+            self.widgets.environments_box.remove(&btn);
+        }
+        let mut first_btn: Option<gtk::RadioButton> = None;
+        for env in envs {
+            let btn = gtk::RadioButtonBuilder::new()
+                .label(&env.short_label)
+                .tooltip_text(&env.name)
+                .draw_indicator(false)
+                .build();
+            if let Some(first) = &first_btn {
+                btn.join_group(Some(first));
+            } else {
+                first_btn = Some(btn.clone());
+            }
+            // must tie the signal handlers manually so i can block emission when we get
+            // updated from outside
+            let relm = self.model.relm.clone();
+            let name = env.name.clone();
+            let handler_id = btn.connect_toggled(move |_| {
+                relm.stream().emit(Msg::EnvironmentToggled(name.clone()))
+            });
+            self.widgets.environments_box.add(&btn);
+            self.model.env_buttons.push((env.name, btn, handler_id));
+        }
+        self.widgets.environments_box.show_all();
+    }
+
     fn update(&mut self, event: Msg) {
         match event {
             Msg::ProjectUpdated(prj) => {
@@ -197,91 +261,70 @@ impl Widget for ProjectSummary {
                 self.model.relm.stream().emit(Msg::ProjectActivated(prj));
             }
             Msg::ProjectActivated(prj) => {
-                self.widgets.radio_dev.set_sensitive(prj.has_dev);
-                self.widgets.radio_stg.set_sensitive(prj.has_stage);
-                self.widgets.radio_uat.set_sensitive(prj.has_uat);
-                self.widgets.radio_prd.set_sensitive(prj.has_prod);
-                if prj.has_prod {
-                    self.widgets.radio_prd.set_active(true);
-                    self.model
-                        .relm
-                        .stream()
-                        .emit(Msg::EnvironmentChanged(EnvironmentType::EnvProd));
-                } else if prj.has_uat {
-                    self.widgets.radio_uat.set_active(true);
-                    self.model
-                        .relm
-                        .stream()
-                        .emit(Msg::EnvironmentChanged(EnvironmentType::EnvUat));
-                } else if prj.has_stage {
-                    self.widgets.radio_stg.set_active(true);
-                    self.model
-                        .relm
-                        .stream()
-                        .emit(Msg::EnvironmentChanged(EnvironmentType::EnvStage));
-                } else if prj.has_dev {
-                    self.widgets.radio_dev.set_active(true);
-                    self.model
-                        .relm
-                        .stream()
-                        .emit(Msg::EnvironmentChanged(EnvironmentType::EnvDevelopment));
-                }
+                self.fetch_environments(prj.id, None);
                 self.set_project(prj);
             }
-            Msg::EnvironmentToggled(env) => match env {
+            Msg::ProjectEnvironmentSelectedFromElsewhere((prj, env)) => {
+                self.fetch_environments(prj.id, Some(env));
+                self.set_project(prj);
+            }
+            Msg::GotEnvironments((project_id, envs, env_to_select)) => {
+                if self.model.project.as_ref().map(|p| p.id) != Some(project_id) {
+                    // the user moved on to another project in the meantime
+                    return;
+                }
+                self.populate_environments(envs);
+                match env_to_select {
+                    Some(env) => {
+                        for (_, btn, handler_id) in &self.model.env_buttons {
+                            // block the event handlers so that we don't spuriously notify
+                            // others of this change
+                            btn.block_signal(handler_id);
+                        }
+                        if let Some((_, btn, _)) =
+                            self.model.env_buttons.iter().find(|(n, _, _)| *n == env)
+                        {
+                            btn.set_active(true);
+                        }
+                        for (_, btn, handler_id) in &self.model.env_buttons {
+                            // unblock the event handlers
+                            btn.unblock_signal(handler_id);
+                        }
+                        self.model.cur_environment = env;
+                    }
+                    None => {
+                        // by default select the last environment, usually production
+                        if let Some((name, btn, handler_id)) = self.model.env_buttons.last() {
+                            btn.block_signal(handler_id);
+                            btn.set_active(true);
+                            btn.unblock_signal(handler_id);
+                            self.model
+                                .relm
+                                .stream()
+                                .emit(Msg::EnvironmentChanged(name.clone()));
+                        }
+                    }
+                }
+            }
+            Msg::EnvironmentToggled(env) => {
                 // sadly the radio button api is a bit of mess, toggled is emitted
                 // on both the one that gets de-activated and the one that gets
                 // activated. 'clicked' does the same, too.
                 // => must filter to re-emit only the one that gets activated.
                 // https://stackoverflow.com/questions/13385024/read-gtk-radio-button-signal-only-when-selected
-                EnvironmentType::EnvDevelopment if self.widgets.radio_dev.get_active() => self
-                    .model
-                    .relm
-                    .stream()
-                    .emit(Msg::EnvironmentChanged(EnvironmentType::EnvDevelopment)),
-                EnvironmentType::EnvStage if self.widgets.radio_stg.get_active() => self
+                let is_active = self
                     .model
-                    .relm
-                    .stream()
-                    .emit(Msg::EnvironmentChanged(EnvironmentType::EnvStage)),
-                EnvironmentType::EnvUat if self.widgets.radio_uat.get_active() => self
-                    .model
-                    .relm
-                    .stream()
-                    .emit(Msg::EnvironmentChanged(EnvironmentType::EnvUat)),
-                EnvironmentType::EnvProd if self.widgets.radio_prd.get_active() => self
-                    .model
-                    .relm
-                    .stream()
-                    .emit(Msg::EnvironmentChanged(EnvironmentType::EnvProd)),
-                _ => {}
-            },
+                    .env_buttons
+                    .iter()
+                    .any(|(n, btn, _)| *n == env && btn.get_active());
+                if is_active {
+                    self.model.relm.stream().emit(Msg::EnvironmentChanged(env));
+                }
+            }
             Msg::EnvironmentChanged(env) => {
                 /* also meant for my parent */
                 self.model.cur_environment = env;
             }
-            Msg::ProjectEnvironmentSelectedFromElsewhere((prj, env)) => {
-                for (btn, handler_id) in &self.model.btn_and_handler {
-                    // block the event handlers so that we don't spuriously notify
-                    // others of this change
-                    btn.block_signal(handler_id);
-                }
-                match env {
-                    EnvironmentType::EnvProd => self.widgets.radio_prd.set_active(true),
-                    EnvironmentType::EnvUat => self.widgets.radio_uat.set_active(true),
-                    EnvironmentType::EnvStage => self.widgets.radio_stg.set_active(true),
-                    EnvironmentType::EnvDevelopment => self.widgets.radio_dev.set_active(true),
-                }
-                self.widgets.radio_dev.set_sensitive(prj.has_dev);
-                self.widgets.radio_stg.set_sensitive(prj.has_stage);
-                self.widgets.radio_uat.set_sensitive(prj.has_uat);
-                self.widgets.radio_prd.set_sensitive(prj.has_prod);
-                self.set_project(prj);
-                for (btn, handler_id) in &self.model.btn_and_handler {
-                    // unblock the event handlers
-                    btn.unblock_signal(handler_id);
-                }
-            }
             Msg::ProjectAddItemActionCompleted(project_item) => {
                 self.model.project_add_item_dialog.as_ref().unwrap().close();
                 self.model.project_add_item_dialog = None;
@@ -305,6 +348,23 @@ impl Widget for ProjectSummary {
             Msg::EditProject => {
                 self.show_project_edit_dialog();
             }
+            Msg::ShowAttachments => {
+                if let Some(prj) = &self.model.project {
+                    attachments::show_dialog(
+                        self.widgets
+                            .header_actions_btn
+                            .clone()
+                            .upcast::<gtk::Widget>(),
+                        &self.model.db_sender,
+                        EntityType::Project,
+                        prj.id,
+                        &prj.name,
+                    );
+                }
+            }
+            Msg::ShowTopology => {
+                self.show_topology();
+            }
             Msg::AskDeleteProject => {
                 self.handle_project_delete();
             }
@@ -326,7 +386,6 @@ impl Widget for ProjectSummary {
         self.model
             .db_sender
             .send(SqlFunc::new(move |sql_conn| {
-                use projectpadsql::schema::project::dsl as prj;
                 use projectpadsql::schema::server::dsl as srv;
                 use projectpadsql::schema::server_database::dsl as db;
                 use projectpadsql::schema::server_link::dsl as srv_link;
@@ -339,7 +398,8 @@ impl Widget for ProjectSummary {
                     .filter(
                         srv::project_id
                             .eq(prj_id)
-                            .and(srv_link::project_id.ne(prj_id)),
+                            .and(srv_link::project_id.ne(prj_id))
+                            .and(srv_link::deleted_at.is_null()),
                     )
                     .load::<(ServerLink, Server)>(sql_conn)
                     .unwrap();
@@ -356,10 +416,13 @@ impl Widget for ProjectSummary {
                 let dependent_websites: Vec<_> = srvw::server_website
                     .inner_join(srv::server)
                     .filter(
-                        srv::project_id.ne(prj_id).and(
-                            srvw::server_database_id
-                                .eq_any(contained_dbs.iter().map(|d| d.id).collect::<Vec<_>>()),
-                        ),
+                        srv::project_id
+                            .ne(prj_id)
+                            .and(srvw::deleted_at.is_null())
+                            .and(
+                                srvw::server_database_id
+                                    .eq_any(contained_dbs.iter().map(|d| d.id).collect::<Vec<_>>()),
+                            ),
                     )
                     .load::<(ServerWebsite, Server)>(sql_conn)
                     .unwrap()
@@ -399,7 +462,8 @@ impl Widget for ProjectSummary {
                     )))
                 } else {
                     s.send(
-                        sql_util::delete_row(sql_conn, prj::project, prj_id).map(|_| prj.clone()),
+                        sql_util::move_to_trash(sql_conn, EntityType::Project, prj_id)
+                            .map(|_| prj.clone()),
                     )
                 }
                 .unwrap();
@@ -413,7 +477,7 @@ impl Widget for ProjectSummary {
             standard_dialogs::confirm_deletion(
                 &format!("Delete {}", prj.name),
                 &format!(
-                    "Are you sure you want to delete the project {}? This action cannot be undone.",
+                    "Are you sure you want to move the project {} to the trash?",
                     prj.name
                 ),
                 self.widgets
@@ -452,7 +516,7 @@ impl Widget for ProjectSummary {
         let dialog_contents = relm::init::<ProjectAddItemDialog>((
             self.model.db_sender.clone(),
             self.model.project.as_ref().unwrap().id,
-            self.model.cur_environment,
+            self.model.cur_environment.clone(),
         ))
         .expect("error initializing the server add item modal");
         let d_c = dialog_contents.stream();
@@ -513,6 +577,8 @@ impl Widget for ProjectSummary {
                     margin_end: 5,
                 },
             },
+            // the environment radio buttons are added in populate_environments
+            #[name="environments_box"]
             #[style_class="linked"]
             gtk::Box {
                 homogeneous: true,
@@ -521,32 +587,6 @@ impl Widget for ProjectSummary {
                 child: {
                     padding: 5,
                 },
-                #[name="radio_dev"]
-                gtk::RadioButton {
-                    label: "Dev",
-                    mode: false,
-                    sensitive: false,
-                    // must tie the event handlers in the init_view so i can temporarily block them sometimes
-                    // toggled => Msg::EnvironmentToggled(EnvironmentType::EnvDevelopment)
-                },
-                #[name="radio_stg"]
-                gtk::RadioButton {
-                    label: "Stg",
-                    mode: false,
-                    sensitive: false,
-                },
-                #[name="radio_uat"]
-                gtk::RadioButton {
-                    label: "Uat",
-                    mode: false,
-                    sensitive: false,
-                },
-                #[name="radio_prd"]
-                gtk::RadioButton {
-                    label: "Prod",
-                    mode: false,
-                    sensitive: false,
-                },
             }
         }
     }
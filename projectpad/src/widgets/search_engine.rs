@@ -1,11 +1,13 @@
 use diesel::prelude::*;
 use projectpadsql::models::{
-    Project, ProjectNote, ProjectPointOfInterest, Server, ServerDatabase, ServerExtraUserAccount,
-    ServerLink, ServerNote, ServerPointOfInterest, ServerWebsite,
+    EntityType, Environment, Project, ProjectNote, ProjectPointOfInterest, Server, ServerDatabase,
+    ServerExtraUserAccount, ServerLink, ServerNote, ServerPointOfInterest, ServerWebsite,
 };
-use std::collections::HashSet;
+use projectpadsql::search::{SearchFilters, SearchHit};
+use std::collections::{HashMap, HashSet};
 
 pub const PROJECT_FILTER_PREFIX: &str = "prj:";
+pub const TAG_FILTER_PREFIX: &str = "tag:";
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum SearchItemsType {
@@ -25,6 +27,8 @@ pub struct SearchResult {
     pub server_notes: Vec<ServerNote>,
     pub server_pois: Vec<ServerPointOfInterest>,
     pub server_websites: Vec<ServerWebsite>,
+    /// the environments of the projects, to display the servers' environment
+    pub environments: Vec<Environment>,
     pub reset_scroll: bool,
 }
 
@@ -33,59 +37,71 @@ pub fn run_search_filter(
     search_item_types: SearchItemsType,
     search_pattern: &str,
     project_pattern: &Option<String>,
+    tags: &[String],
     reset_scroll: bool,
-) -> SearchResult {
-    // find all the leaves...
-    let servers = if search_item_types == SearchItemsType::ServersOnly
-        || search_item_types == SearchItemsType::All
-    {
-        filter_servers(sql_conn, search_pattern)
-    } else {
-        vec![]
-    };
-    let server_databases = if search_item_types == SearchItemsType::ServerDbsOnly
-        || search_item_types == SearchItemsType::All
-    {
-        filter_server_databases(sql_conn, search_pattern)
-    } else {
-        vec![]
+) -> projectpadsql::error::Result<SearchResult> {
+    let filters = SearchFilters {
+        entity_types: match search_item_types {
+            SearchItemsType::All => vec![],
+            SearchItemsType::ServerDbsOnly => vec![EntityType::ServerDatabase],
+            SearchItemsType::ServersOnly => vec![EntityType::Server],
+        },
+        project_name: project_pattern.clone(),
+        tags: tags.to_vec(),
+        limit: None,
     };
+    // find all the leaves, best matches first...
+    let hits = projectpadsql::search(sql_conn, search_pattern, &filters)?;
 
-    let (
-        prjs,
-        project_pois,
-        project_notes,
-        server_notes,
-        server_links,
-        server_pois,
-        server_extra_users,
-        server_websites,
-    ) = if search_item_types == SearchItemsType::All {
-        (
-            filter_projects(sql_conn, search_pattern),
-            filter_project_pois(sql_conn, search_pattern),
-            filter_project_notes(sql_conn, search_pattern),
-            filter_server_notes(sql_conn, search_pattern),
-            filter_server_links(sql_conn, search_pattern),
-            filter_server_pois(sql_conn, search_pattern),
-            filter_server_extra_users(sql_conn, search_pattern),
-            filter_server_websites(sql_conn, search_pattern)
-                .into_iter()
-                .map(|p| p.0)
-                .collect::<Vec<_>>(),
-        )
-    } else {
-        (
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-        )
-    };
+    let prjs: Vec<Project> = load_hits(&hits, EntityType::Project, |ids| {
+        use projectpadsql::schema::project::dsl::*;
+        project.filter(id.eq_any(ids)).load(sql_conn)
+    })?;
+    let project_pois: Vec<ProjectPointOfInterest> =
+        load_hits(&hits, EntityType::ProjectPoi, |ids| {
+            use projectpadsql::schema::project_point_of_interest::dsl::*;
+            project_point_of_interest
+                .filter(id.eq_any(ids))
+                .load(sql_conn)
+        })?;
+    let project_notes: Vec<ProjectNote> = load_hits(&hits, EntityType::ProjectNote, |ids| {
+        use projectpadsql::schema::project_note::dsl::*;
+        project_note.filter(id.eq_any(ids)).load(sql_conn)
+    })?;
+    let server_links: Vec<ServerLink> = load_hits(&hits, EntityType::ServerLink, |ids| {
+        use projectpadsql::schema::server_link::dsl::*;
+        server_link.filter(id.eq_any(ids)).load(sql_conn)
+    })?;
+    let servers: Vec<Server> = load_hits(&hits, EntityType::Server, |ids| {
+        use projectpadsql::schema::server::dsl::*;
+        server.filter(id.eq_any(ids)).load(sql_conn)
+    })?;
+    let server_databases: Vec<ServerDatabase> =
+        load_hits(&hits, EntityType::ServerDatabase, |ids| {
+            use projectpadsql::schema::server_database::dsl::*;
+            server_database.filter(id.eq_any(ids)).load(sql_conn)
+        })?;
+    let server_extra_users: Vec<ServerExtraUserAccount> =
+        load_hits(&hits, EntityType::ServerExtraUserAccount, |ids| {
+            use projectpadsql::schema::server_extra_user_account::dsl::*;
+            server_extra_user_account
+                .filter(id.eq_any(ids))
+                .load(sql_conn)
+        })?;
+    let server_notes: Vec<ServerNote> = load_hits(&hits, EntityType::ServerNote, |ids| {
+        use projectpadsql::schema::server_note::dsl::*;
+        server_note.filter(id.eq_any(ids)).load(sql_conn)
+    })?;
+    let server_pois: Vec<ServerPointOfInterest> = load_hits(&hits, EntityType::ServerPoi, |ids| {
+        use projectpadsql::schema::server_point_of_interest::dsl::*;
+        server_point_of_interest
+            .filter(id.eq_any(ids))
+            .load(sql_conn)
+    })?;
+    let server_websites: Vec<ServerWebsite> = load_hits(&hits, EntityType::ServerWebsite, |ids| {
+        use projectpadsql::schema::server_website::dsl::*;
+        server_website.filter(id.eq_any(ids)).load(sql_conn)
+    })?;
 
     // bubble up to the toplevel...
     let mut all_server_ids = servers.iter().map(|s| s.id).collect::<HashSet<_>>();
@@ -95,7 +111,7 @@ pub fn run_search_filter(
     all_server_ids.extend(server_extra_users.iter().map(|sl| sl.server_id));
     all_server_ids.extend(server_pois.iter().map(|sl| sl.server_id));
     all_server_ids.extend(server_databases.iter().map(|sl| sl.server_id));
-    let all_servers = load_servers_by_id(sql_conn, &all_server_ids);
+    let mut all_servers = load_servers_by_id(sql_conn, &all_server_ids)?;
 
     let mut all_project_ids = all_servers
         .iter()
@@ -105,7 +121,36 @@ pub fn run_search_filter(
     all_project_ids.extend(project_pois.iter().map(|ppoi| ppoi.project_id));
     all_project_ids.extend(project_notes.iter().map(|pn| pn.project_id));
     all_project_ids.extend(server_links.iter().map(|pn| pn.project_id));
-    let all_projects = load_projects_by_id(sql_conn, &all_project_ids);
+    let mut all_projects = load_projects_by_id(sql_conn, &all_project_ids)?;
+    let environments = {
+        use projectpadsql::schema::environment::dsl::*;
+        environment
+            .filter(project_id.eq_any(&all_project_ids))
+            .load(sql_conn)?
+    };
+
+    // ...and display first the projects and servers containing the best matches
+    let mut best_server_hits = HashMap::new();
+    let mut best_project_hits = HashMap::new();
+    for (idx, hit) in hits.iter().enumerate() {
+        if let Some(server_id) = hit.server_id {
+            best_server_hits.entry(server_id).or_insert(idx);
+        }
+        best_project_hits.entry(hit.project_id).or_insert(idx);
+    }
+    for link in &server_links {
+        if let Some(idx) = hits
+            .iter()
+            .position(|h| h.entity_type == EntityType::ServerLink && h.entity_id == link.id)
+        {
+            let best = best_server_hits.entry(link.linked_server_id).or_insert(idx);
+            *best = (*best).min(idx);
+        }
+    }
+    // the sorts are stable, keeping the order by name for ties
+    all_servers.sort_by_key(|s| best_server_hits.get(&s.id).copied().unwrap_or(usize::MAX));
+    all_projects.sort_by_key(|p| best_project_hits.get(&p.id).copied().unwrap_or(usize::MAX));
+
     let filtered_projects = match &project_pattern {
         None => all_projects,
         Some(prj) => all_projects
@@ -113,7 +158,7 @@ pub fn run_search_filter(
             .filter(|p| p.name.to_lowercase().contains(prj))
             .collect(),
     };
-    SearchResult {
+    Ok(SearchResult {
         projects: filtered_projects,
         project_notes,
         project_pois,
@@ -124,160 +169,83 @@ pub fn run_search_filter(
         server_databases,
         server_extra_users,
         server_websites,
+        environments,
         reset_scroll,
-    }
-}
-
-fn filter_projects(db_conn: &SqliteConnection, filter: &str) -> Vec<Project> {
-    use projectpadsql::schema::project::dsl::*;
-    project
-        .filter(name.like(filter).escape('\\'))
-        .load::<Project>(db_conn)
-        .unwrap()
-}
-
-fn filter_project_pois(db_conn: &SqliteConnection, filter: &str) -> Vec<ProjectPointOfInterest> {
-    use projectpadsql::schema::project_point_of_interest::dsl::*;
-    project_point_of_interest
-        .filter(
-            desc.like(filter)
-                .escape('\\')
-                .or(text.like(filter).escape('\\'))
-                .or(path.like(filter).escape('\\')),
-        )
-        .load::<ProjectPointOfInterest>(db_conn)
-        .unwrap()
-}
-
-fn filter_project_notes(db_conn: &SqliteConnection, filter: &str) -> Vec<ProjectNote> {
-    use projectpadsql::schema::project_note::dsl::*;
-    project_note
-        .filter(
-            title
-                .like(filter)
-                .escape('\\')
-                .or(contents.like(filter).escape('\\')),
-        )
-        .load::<ProjectNote>(db_conn)
-        .unwrap()
+    })
 }
 
-fn filter_server_notes(db_conn: &SqliteConnection, filter: &str) -> Vec<ServerNote> {
-    use projectpadsql::schema::server_note::dsl::*;
-    server_note
-        .filter(
-            title
-                .like(filter)
-                .escape('\\')
-                .or(contents.like(filter).escape('\\')),
-        )
-        .load::<ServerNote>(db_conn)
-        .unwrap()
-}
-
-fn filter_server_links(db_conn: &SqliteConnection, filter: &str) -> Vec<ServerLink> {
-    use projectpadsql::schema::server_link::dsl::*;
-    server_link
-        .filter(desc.like(filter).escape('\\'))
-        .load::<ServerLink>(db_conn)
-        .unwrap()
+trait EntityId {
+    fn entity_id(&self) -> i32;
 }
 
-fn filter_server_extra_users(
-    db_conn: &SqliteConnection,
-    filter: &str,
-) -> Vec<ServerExtraUserAccount> {
-    use projectpadsql::schema::server_extra_user_account::dsl::*;
-    server_extra_user_account
-        .filter(
-            desc.like(filter)
-                .escape('\\')
-                .or(username.like(filter).escape('\\')),
-        )
-        .load::<ServerExtraUserAccount>(db_conn)
-        .unwrap()
-}
-
-fn filter_server_pois(db_conn: &SqliteConnection, filter: &str) -> Vec<ServerPointOfInterest> {
-    use projectpadsql::schema::server_point_of_interest::dsl::*;
-    server_point_of_interest
-        .filter(
-            desc.like(filter)
-                .escape('\\')
-                .or(path.like(filter).escape('\\'))
-                .or(text.like(filter).escape('\\')),
-        )
-        .load::<ServerPointOfInterest>(db_conn)
-        .unwrap()
+macro_rules! impl_entity_id {
+    ($($t:ty),*) => {
+        $(impl EntityId for $t {
+            fn entity_id(&self) -> i32 {
+                self.id
+            }
+        })*
+    };
 }
 
-fn filter_server_databases(db_conn: &SqliteConnection, filter: &str) -> Vec<ServerDatabase> {
-    use projectpadsql::schema::server_database::dsl::*;
-    server_database
-        .filter(
-            desc.like(filter)
-                .escape('\\')
-                .or(name.like(filter).escape('\\'))
-                .or(text.like(filter).escape('\\')),
-        )
-        .load::<ServerDatabase>(db_conn)
-        .unwrap()
-}
+impl_entity_id!(
+    Project,
+    ProjectNote,
+    ProjectPointOfInterest,
+    ServerLink,
+    Server,
+    ServerDatabase,
+    ServerExtraUserAccount,
+    ServerNote,
+    ServerPointOfInterest,
+    ServerWebsite
+);
 
-fn filter_servers(db_conn: &SqliteConnection, filter: &str) -> Vec<Server> {
-    use projectpadsql::schema::server::dsl::*;
-    server
-        .filter(
-            desc.like(filter)
-                .escape('\\')
-                .or(ip.like(filter).escape('\\'))
-                .or(text.like(filter).escape('\\')),
-        )
-        .load::<Server>(db_conn)
-        .unwrap()
+/// loads the entities of that type which were found, in the order of the search hits
+fn load_hits<T: EntityId>(
+    hits: &[SearchHit],
+    entity_type: EntityType,
+    load: impl Fn(&[i32]) -> QueryResult<Vec<T>>,
+) -> QueryResult<Vec<T>> {
+    let positions: HashMap<i32, usize> = hits
+        .iter()
+        .filter(|h| h.entity_type == entity_type)
+        .enumerate()
+        .map(|(idx, h)| (h.entity_id, idx))
+        .collect();
+    if positions.is_empty() {
+        return Ok(vec![]);
+    }
+    let ids: Vec<i32> = positions.keys().copied().collect();
+    let mut items = load(&ids)?;
+    items.sort_by_key(|i| positions.get(&i.entity_id()).copied());
+    Ok(items)
 }
 
-fn filter_server_websites(
+fn load_projects_by_id(
     db_conn: &SqliteConnection,
-    filter: &str,
-) -> Vec<(ServerWebsite, Option<ServerDatabase>)> {
-    use projectpadsql::schema::server_database::dsl as db;
-    use projectpadsql::schema::server_website::dsl::*;
-    server_website
-        .left_outer_join(db::server_database)
-        .filter(
-            desc.like(filter)
-                .escape('\\')
-                .or(url.like(filter).escape('\\'))
-                .or(text.like(filter).escape('\\'))
-                .or(db::desc.like(filter).escape('\\'))
-                .or(db::name.like(filter).escape('\\')),
-        )
-        .load::<(ServerWebsite, Option<ServerDatabase>)>(db_conn)
-        .unwrap()
-}
-
-fn load_projects_by_id(db_conn: &SqliteConnection, ids: &HashSet<i32>) -> Vec<Project> {
+    ids: &HashSet<i32>,
+) -> QueryResult<Vec<Project>> {
     use projectpadsql::schema::project::dsl::*;
     project
         .filter(id.eq_any(ids))
         .order(name.asc())
         .load::<Project>(db_conn)
-        .unwrap()
 }
 
-fn load_servers_by_id(db_conn: &SqliteConnection, ids: &HashSet<i32>) -> Vec<Server> {
+fn load_servers_by_id(db_conn: &SqliteConnection, ids: &HashSet<i32>) -> QueryResult<Vec<Server>> {
     use projectpadsql::schema::server::dsl::*;
     server
         .filter(id.eq_any(ids))
+        .order(desc.asc())
         .load::<Server>(db_conn)
-        .unwrap()
 }
 
 #[derive(PartialEq, Eq, Debug)]
 pub struct SearchSpec {
     pub search_pattern: String,
     pub project_pattern: Option<String>,
+    pub tags: Vec<String>,
 }
 
 #[derive(PartialEq, Eq)]
@@ -286,23 +254,34 @@ enum SearchParseState {
     Normal,
 }
 
+fn has_filter_prefix(search: &str, prefix: &str) -> bool {
+    search.starts_with(prefix) || search.contains(&(" ".to_string() + prefix))
+}
+
 pub fn search_parse(search: &str) -> SearchSpec {
-    let fmt = |t: &str| format!("%{}%", t.replace('%', "\\%"));
-    if search.starts_with(PROJECT_FILTER_PREFIX)
-        || search.contains(&(" ".to_string() + PROJECT_FILTER_PREFIX))
+    if has_filter_prefix(search, PROJECT_FILTER_PREFIX)
+        || has_filter_prefix(search, TAG_FILTER_PREFIX)
     {
-        let (search, project, _) = search.split(' ').fold(
-            ("".to_string(), None, SearchParseState::Normal),
-            |(search, project, parse_state), fragment| match parse_state {
+        let (search, project, tags, _) = search.split(' ').fold(
+            ("".to_string(), None, vec![], SearchParseState::Normal),
+            |(search, project, mut tags, parse_state), fragment| match parse_state {
                 SearchParseState::Normal if fragment.starts_with(PROJECT_FILTER_PREFIX) => (
                     search,
-                    Some(fragment[4..].to_lowercase()),
+                    Some(fragment[PROJECT_FILTER_PREFIX.len()..].to_lowercase()),
+                    tags,
                     if fragment.chars().filter(|c| *c == '"').count() % 2 != 0 {
                         SearchParseState::InProject
                     } else {
                         SearchParseState::Normal
                     },
                 ),
+                SearchParseState::Normal if fragment.starts_with(TAG_FILTER_PREFIX) => {
+                    let tag = &fragment[TAG_FILTER_PREFIX.len()..];
+                    if !tag.is_empty() {
+                        tags.push(tag.to_lowercase());
+                    }
+                    (search, project, tags, SearchParseState::Normal)
+                }
                 SearchParseState::Normal => (
                     if search.is_empty() {
                         fragment.to_owned()
@@ -310,11 +289,13 @@ pub fn search_parse(search: &str) -> SearchSpec {
                         search + " " + fragment
                     },
                     project,
+                    tags,
                     SearchParseState::Normal,
                 ),
                 SearchParseState::InProject => (
                     search,
                     Some(project.unwrap() + " " + &fragment.to_lowercase()[..]),
+                    tags,
                     if fragment.contains('\"') {
                         SearchParseState::Normal
                     } else {
@@ -324,16 +305,18 @@ pub fn search_parse(search: &str) -> SearchSpec {
             },
         );
         SearchSpec {
-            search_pattern: fmt(&search),
+            search_pattern: search,
             project_pattern: match project {
                 Some(p) if p.starts_with('"') => Some(p.replace('"', "")),
                 _ => project,
             },
+            tags,
         }
     } else {
         SearchSpec {
-            search_pattern: fmt(search),
+            search_pattern: search.to_string(),
             project_pattern: None,
+            tags: vec![],
         }
     }
 }
@@ -347,8 +330,9 @@ mod tests {
     fn search_parse_no_project() {
         assert_eq!(
             SearchSpec {
-                search_pattern: "%test no project%".to_string(),
-                project_pattern: None
+                search_pattern: "test no project".to_string(),
+                project_pattern: None,
+                tags: vec![],
             },
             search_parse("test no project")
         );
@@ -358,8 +342,9 @@ mod tests {
     fn search_parse_with_project() {
         assert_eq!(
             SearchSpec {
-                search_pattern: "%item1 test item3%".to_string(),
-                project_pattern: Some("project".to_string())
+                search_pattern: "item1 test item3".to_string(),
+                project_pattern: Some("project".to_string()),
+                tags: vec![],
             },
             search_parse("item1 test prj:prOject item3")
         );
@@ -369,8 +354,9 @@ mod tests {
     fn search_parse_with_quoted_project() {
         assert_eq!(
             SearchSpec {
-                search_pattern: "%item1 test item3%".to_string(),
-                project_pattern: Some("project with spaces".to_string())
+                search_pattern: "item1 test item3".to_string(),
+                project_pattern: Some("project with spaces".to_string()),
+                tags: vec![],
             },
             search_parse("item1 test prj:\"prOject with spaces\" item3")
         );
@@ -380,18 +366,50 @@ mod tests {
     fn search_parse_with_unnecessarily_quoted_project() {
         assert_eq!(
             SearchSpec {
-                search_pattern: "%item1 test item3%".to_string(),
-                project_pattern: Some("project".to_string())
+                search_pattern: "item1 test item3".to_string(),
+                project_pattern: Some("project".to_string()),
+                tags: vec![],
             },
             search_parse("item1 test prj:\"prOject\" item3")
         );
     }
 
+    #[test]
+    fn search_parse_with_tags() {
+        assert_eq!(
+            SearchSpec {
+                search_pattern: "item1 item3".to_string(),
+                project_pattern: Some("project".to_string()),
+                tags: vec!["k8s".to_string(), "on-call".to_string()],
+            },
+            search_parse("tag:K8s item1 prj:prOject tag:on-call item3")
+        );
+    }
+
+    #[test]
+    fn search_finds_tagged_items() {
+        let db_conn = tests_load_yaml(SAMPLE_YAML_PROJECT);
+        let search_result = run_search_filter(
+            &db_conn,
+            SearchItemsType::All,
+            "",
+            &None,
+            &["reports".to_string()],
+            false,
+        )
+        .unwrap();
+        assert_eq!(1, search_result.server_databases.len());
+        // the parents are there, but not the items without the tag
+        assert_eq!(1, search_result.servers.len());
+        assert!(search_result.server_pois.is_empty());
+    }
+
     #[test]
     fn search_finds_users() {
         let db_conn = tests_load_yaml(SAMPLE_YAML_PROJECT);
         let search_result =
-            run_search_filter(&db_conn, SearchItemsType::All, "monitor", &None, false);
+            run_search_filter(&db_conn, SearchItemsType::All, "monitor", &None, &[], false)
+                .unwrap();
         // we should find the user...
         assert_eq!(1, search_result.server_extra_users.len());
         assert_eq!(
@@ -20,6 +20,7 @@ use super::dialogs::server_note_add_edit_dlg::Msg as MsgServerNoteAddEditDialog;
 use super::dialogs::server_poi_add_edit_dlg::Msg as MsgServerPoiAddEditDialog;
 use super::dialogs::server_website_add_edit_dlg::Msg as MsgServerWebsiteAddEditDialog;
 use super::dialogs::{ProjectAddEditDialogComponent, ServerAddEditDialogComponent};
+use super::master_passphrase;
 use super::project_items_list::ProjectItem;
 use super::project_poi_header;
 pub use super::search_engine::SearchItemsType;
@@ -31,8 +32,9 @@ use crate::sql_thread::SqlFunc;
 use gdk::prelude::*;
 use gtk::prelude::*;
 use projectpadsql::models::{
-    Project, ProjectNote, ProjectPointOfInterest, Server, ServerAccessType, ServerDatabase,
-    ServerExtraUserAccount, ServerLink, ServerNote, ServerPointOfInterest, ServerWebsite,
+    Environment, Project, ProjectNote, ProjectPointOfInterest, Server, ServerAccessType,
+    ServerDatabase, ServerExtraUserAccount, ServerLink, ServerNote, ServerPointOfInterest,
+    ServerWebsite,
 };
 use relm::Widget;
 use relm_derive::{widget, Msg};
@@ -74,11 +76,14 @@ impl Area {
     }
 }
 
+// the error message, displayed in place of the search results
+type SearchResultResult = Result<SearchResult, String>;
+
 #[derive(Msg)]
 pub enum Msg {
     FilterChanged(Option<String>),
     SelectItem(Option<ProjectPadItem>),
-    GotSearchResult(SearchResult),
+    GotSearchResult(SearchResultResult),
     MouseScroll(gdk::ScrollDirection, (f64, f64)),
     ScrollChanged,
     CopyClicked(String),
@@ -137,10 +142,12 @@ pub struct Model {
     show_shortcuts: Rc<Cell<bool>>,
     search_item_types: SearchItemsType,
     operation_mode: OperationMode,
-    sender: relm::Sender<SearchResult>,
+    sender: relm::Sender<SearchResultResult>,
+    search_error: Rc<RefCell<Option<String>>>,
     selected_item: Rc<RefCell<Option<ProjectPadItem>>>,
     // as of 2020-07-08 "the drawing module of relm is not ready" -- have to RefCell
     search_items: Rc<RefCell<Vec<ProjectPadItem>>>,
+    environments: Rc<RefCell<Vec<Environment>>>,
     links: Rc<RefCell<Vec<(Area, String)>>>,
     action_areas: Rc<RefCell<Vec<(Area, ProjectPadItem)>>>,
     item_link_areas: Rc<RefCell<Vec<(Area, ProjectPadItem)>>>,
@@ -181,6 +188,8 @@ impl Widget for SearchView {
             .search_result_area
             .set_events(gdk::EventMask::ALL_EVENTS_MASK);
         let si = self.model.search_items.clone();
+        let envs = self.model.environments.clone();
+        let search_error = self.model.search_error.clone();
         let sel = self.model.selected_item.clone();
         let search_scroll = self.widgets.search_scroll.clone();
         let links = self.model.links.clone();
@@ -199,6 +208,8 @@ impl Widget for SearchView {
                     &action_areas,
                     &item_link_areas,
                     &si,
+                    &envs.borrow(),
+                    &search_error.borrow(),
                     &search_result_area,
                     &search_scroll,
                     &item_with_depressed.borrow(),
@@ -341,6 +352,8 @@ impl Widget for SearchView {
         action_areas: &Rc<RefCell<Vec<(Area, ProjectPadItem)>>>,
         item_link_areas: &Rc<RefCell<Vec<(Area, ProjectPadItem)>>>,
         si: &Rc<RefCell<Vec<ProjectPadItem>>>,
+        environments: &[Environment],
+        search_error: &Option<String>,
         search_result_area: &gtk::DrawingArea,
         search_scroll: &gtk::Scrollbar,
         item_with_depressed_action: &Option<ProjectPadItem>,
@@ -365,6 +378,10 @@ impl Widget for SearchView {
             search_result_area.get_allocation().width.into(),
             search_result_area.get_allocation().height.into(),
         );
+        if let Some(msg) = search_error {
+            search_view_render::draw_error(msg, context, search_result_area);
+            return;
+        }
         let mut y = 0;
         let mut item_idx = 0;
         let mut cur_server = None;
@@ -404,6 +421,7 @@ impl Widget for SearchView {
                 action_areas: &mut action_areas,
                 item_with_depressed_action: item_with_depressed_action.clone(),
                 operation_mode: op_mode,
+                environments,
             };
             search_view_render::draw_child(&drawing_context, &mut item_context, item, cur_server);
             if show_shortcuts && item_idx < 10 {
@@ -436,7 +454,7 @@ impl Widget for SearchView {
         let (db_sender, filter, search_item_types, operation_mode, save_btn, selected_item) =
             params;
         let stream = relm.stream().clone();
-        let (_channel, sender) = relm::Channel::new(move |search_r: SearchResult| {
+        let (_channel, sender) = relm::Channel::new(move |search_r: SearchResultResult| {
             stream.emit(Msg::GotSearchResult(search_r));
         });
         if let (None, Some(btn)) = (&selected_item, &save_btn) {
@@ -450,7 +468,9 @@ impl Widget for SearchView {
             operation_mode,
             db_sender,
             sender,
+            search_error: Rc::new(RefCell::new(None)),
             search_items: Rc::new(RefCell::new(vec![])),
+            environments: Rc::new(RefCell::new(vec![])),
             links: Rc::new(RefCell::new(vec![])),
             action_areas: Rc::new(RefCell::new(vec![])),
             item_link_areas: Rc::new(RefCell::new(vec![])),
@@ -477,9 +497,14 @@ impl Widget for SearchView {
                 self.model.selected_item.replace(item);
                 self.widgets.search_result_area.queue_draw();
             }
-            Msg::GotSearchResult(search_result) => {
+            Msg::GotSearchResult(Ok(search_result)) => {
+                self.model.search_error.replace(None);
                 self.refresh_display(Some(&search_result));
             }
+            Msg::GotSearchResult(Err(msg)) => {
+                self.model.search_error.replace(Some(msg));
+                self.refresh_display(None);
+            }
             Msg::MouseScroll(direction, (_dx, dy)) => {
                 let old_val = self.widgets.search_scroll.get_value();
                 let new_val = old_val
@@ -568,15 +593,19 @@ impl Widget for SearchView {
     }
 
     fn copy_to_clipboard(&self, val: &str) {
-        if let Some(clip) =
-            gtk::Clipboard::get_default(&self.widgets.search_result_area.get_display())
-        {
-            clip.set_text(val);
-            self.model
-                .relm
-                .stream()
-                .emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
-        }
+        let search_result_area = self.widgets.search_result_area.clone();
+        let stream = self.model.relm.stream().clone();
+        master_passphrase::reveal(
+            &self.model.db_sender,
+            search_result_area.upcast_ref(),
+            val,
+            move |val| {
+                if let Some(clip) = gtk::Clipboard::get_default(&search_result_area.get_display()) {
+                    clip.set_text(&val);
+                    stream.emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
+                }
+            },
+        );
     }
 
     fn handle_keypress(&self, e: gdk::EventKey) {
@@ -611,9 +640,9 @@ impl Widget for SearchView {
                 .filter_map(|i| match i {
                     ProjectPadItem::Server(srv)
                         if srv.access_type == ServerAccessType::SrvAccessWww
-                            && !srv.ip.is_empty() =>
+                            && !srv.host.is_empty() =>
                     {
-                        Some(srv.ip.clone())
+                        Some(srv.address().to_string())
                     }
                     ProjectPadItem::ServerWebsite(www) if !www.url.is_empty() => {
                         Some(www.url.clone())
@@ -1008,6 +1037,9 @@ impl Widget for SearchView {
         let mut search_items = self.model.search_items.borrow_mut();
         search_items.clear();
         if let Some(search_result) = &search_result {
+            self.model
+                .environments
+                .replace(search_result.environments.clone());
             for project in &search_result.projects {
                 search_items.push(ProjectPadItem::Project(project.clone()));
                 for server in search_result
@@ -1097,7 +1129,7 @@ impl Widget for SearchView {
             None => self
                 .model
                 .sender
-                .send(SearchResult {
+                .send(Ok(SearchResult {
                     projects: vec![],
                     project_notes: vec![],
                     project_pois: vec![],
@@ -1108,25 +1140,31 @@ impl Widget for SearchView {
                     server_notes: vec![],
                     server_pois: vec![],
                     server_websites: vec![],
+                    environments: vec![],
                     reset_scroll: true,
-                })
+                }))
                 .unwrap(),
             Some(filter) => {
                 let s = self.model.sender.clone();
                 let search_spec = search_parse(filter);
                 let f = search_spec.search_pattern;
                 let project_pattern = search_spec.project_pattern;
+                let tags = search_spec.tags;
                 let search_item_types = self.model.search_item_types;
                 self.model
                     .db_sender
                     .send(SqlFunc::new(move |sql_conn| {
-                        s.send(run_search_filter(
-                            &sql_conn,
-                            search_item_types,
-                            &f,
-                            &project_pattern,
-                            reset_scroll,
-                        ))
+                        s.send(
+                            run_search_filter(
+                                &sql_conn,
+                                search_item_types,
+                                &f,
+                                &project_pattern,
+                                &tags,
+                                reset_scroll,
+                            )
+                            .map_err(|e| format!("Error running the search: {}", e)),
+                        )
                         .unwrap();
                     }))
                     .unwrap()
@@ -3,7 +3,7 @@ use crate::icons::*;
 use gdk::prelude::GdkContextExt;
 use gtk::prelude::*;
 use projectpadsql::models::{
-    EnvironmentType, Project, ProjectNote, ProjectPointOfInterest, Server, ServerAccessType,
+    Environment, Project, ProjectNote, ProjectPointOfInterest, Server, ServerAccessType,
     ServerDatabase, ServerExtraUserAccount, ServerLink, ServerNote, ServerPointOfInterest,
     ServerWebsite,
 };
@@ -12,6 +12,7 @@ const ACTION_ICON_SIZE: i32 = 16;
 const PROJECT_ICON_SIZE: i32 = 56;
 const ACTION_ICON_OFFSET_FROM_RIGHT: f64 = 50.0;
 const KEYBOARD_SHORTCUT_HINT_LEFT_MARGIN: i32 = 20;
+const ENVIRONMENT_COLOR_WIDTH: f64 = 5.0;
 
 #[derive(PartialEq, Eq)]
 enum ItemType {
@@ -34,6 +35,8 @@ pub struct ItemContext<'a> {
     pub action_areas: &'a mut Vec<(Area, ProjectPadItem)>,
     pub item_with_depressed_action: Option<ProjectPadItem>,
     pub operation_mode: OperationMode,
+    /// the environments of the projects in the search results
+    pub environments: &'a [Environment],
 }
 
 fn draw_button(
@@ -183,6 +186,27 @@ pub fn draw_shortcut(
     );
 }
 
+/// displayed instead of the search results when the search failed
+pub fn draw_error(message: &str, context: &cairo::Context, search_result_area: &gtk::DrawingArea) {
+    let pango_context = search_result_area.create_pango_context();
+    let layout = pango::Layout::new(&pango_context);
+    layout.set_text(message);
+    layout.set_wrap(pango::WrapMode::WordChar);
+    layout.set_width(
+        i32::max(
+            search_result_area.get_allocation().width - 2 * LEFT_RIGHT_MARGIN,
+            LEFT_RIGHT_MARGIN,
+        ) * pango::SCALE,
+    );
+    gtk::render_layout(
+        &search_result_area.get_style_context(),
+        context,
+        LEFT_RIGHT_MARGIN as f64,
+        (SEARCH_RESULT_WIDGET_HEIGHT / 2) as f64,
+        &layout,
+    );
+}
+
 pub fn draw_child(
     drawing_context: &DrawingContext,
     item_context: &mut ItemContext,
@@ -577,21 +601,22 @@ fn draw_server(
     {
         let padding = &item_context.padding;
         drawing_context.style_context.remove_class("title");
+        let env = item_context
+            .environments
+            .iter()
+            .find(|e| e.project_id == server.project_id && e.name == server.environment);
         let env_rect = draw_environment(
             drawing_context,
             x + padding.left as f64,
             y + (title_rect.height / pango::SCALE) as f64 + padding.top as f64 + margin.top as f64,
-            &match server.environment {
-                EnvironmentType::EnvUat => "uat",
-                EnvironmentType::EnvProd => "prod",
-                EnvironmentType::EnvStage => "stg",
-                EnvironmentType::EnvDevelopment => "dev",
-            },
+            env.map(|e| e.short_label.clone())
+                .unwrap_or_else(|| server.environment.to_uppercase()),
+            env.and_then(|e| e.rgb()),
         );
-        if server.access_type == ServerAccessType::SrvAccessWww && !server.ip.is_empty() {
+        if server.access_type == ServerAccessType::SrvAccessWww && !server.host.is_empty() {
             draw_link(
                 drawing_context,
-                &server.ip,
+                &server.address().to_string(),
                 (env_rect.x + env_rect.width) as f64,
                 y + (title_rect.height / pango::SCALE) as f64 + padding.top as f64,
                 item_context.links,
@@ -613,16 +638,16 @@ fn draw_environment(
     drawing_context: &DrawingContext,
     x: f64,
     y: f64,
-    env_name: &str,
+    label: String,
+    rgb: Option<(u8, u8, u8)>,
 ) -> gtk::Rectangle {
     let context = &drawing_context.context;
     let style_context = &drawing_context.style_context;
-    let label_classname = format!("environment_label_{}", env_name);
-    style_context.add_class(&label_classname);
+    style_context.add_class("environment_label");
     let padding = style_context.get_padding(gtk::StateFlags::NORMAL);
     let pango_context = drawing_context.search_result_area.create_pango_context();
     let layout = pango::Layout::new(&pango_context);
-    layout.set_text(&env_name.to_uppercase());
+    layout.set_text(&label);
     let rect = layout.get_extents().1;
     let text_w = (rect.width / pango::SCALE) as f64;
     let text_h = (rect.height / pango::SCALE) as f64;
@@ -634,6 +659,16 @@ fn draw_environment(
 
     gtk::render_frame(style_context, context, x, y, total_width, total_height);
 
+    // the environment color, on the left of the label
+    let (red, green, blue) = rgb.unwrap_or((0x88, 0x88, 0x88));
+    context.rectangle(x, y, ENVIRONMENT_COLOR_WIDTH, total_height);
+    context.set_source_rgb(
+        f64::from(red) / 255.0,
+        f64::from(green) / 255.0,
+        f64::from(blue) / 255.0,
+    );
+    context.fill();
+
     gtk::render_layout(
         style_context,
         context,
@@ -641,7 +676,7 @@ fn draw_environment(
         y + padding.top as f64,
         &layout,
     );
-    style_context.remove_class(&label_classname);
+    style_context.remove_class("environment_label");
     gtk::Rectangle {
         x: x as i32,
         y: y as i32,
@@ -1,3 +1,5 @@
+use super::attachments::AttachmentsRow;
+use super::custom_fields;
 use super::dialogs::dialog_helpers;
 use super::dialogs::server_database_add_edit_dlg::Msg as MsgServerDatabaseAddEditDialog;
 use super::dialogs::server_extra_user_add_edit_dlg::Msg as MsgServerExtraUserAddEditDialog;
@@ -7,25 +9,24 @@ use super::dialogs::server_poi_add_edit_dlg::Msg as MsgServerPoiAddEditDialog;
 use super::dialogs::server_website_add_edit_dlg::Msg as MsgServerWebsiteAddEditDialog;
 use super::dialogs::standard_dialogs;
 use super::dialogs::ServerAddEditDialogComponent;
+use super::master_passphrase;
+use super::otp_code;
 use super::project_poi_header::{populate_grid, GridItem, LabelText};
 use super::server_poi_contents::ServerItem;
+use super::tag_chips;
 use crate::icons::*;
 use crate::notes;
 use crate::sql_thread::SqlFunc;
 use crate::sql_util;
-use diesel::helper_types::Find;
 use diesel::prelude::*;
-use diesel::query_builder::IntoUpdateTarget;
-use diesel::query_dsl::methods::ExecuteDsl;
-use diesel::query_dsl::methods::FindDsl;
-use diesel::sqlite::SqliteConnection;
 use gtk::prelude::*;
 use projectpadsql::models::{
-    InterestType, ServerDatabase, ServerExtraUserAccount, ServerNote, ServerPointOfInterest,
-    ServerWebsite,
+    CustomField, EntityType, InterestType, ServerDatabase, ServerExtraUserAccount, ServerNote,
+    ServerPointOfInterest, ServerWebsite,
 };
 use relm::Widget;
 use relm_derive::{widget, Msg};
+use std::rc::Rc;
 use std::sync::mpsc;
 
 #[derive(Msg, Clone)]
@@ -33,6 +34,7 @@ pub enum Msg {
     CopyClicked(String),
     ViewNote(ServerNote),
     EditNote(ServerNote),
+    AttachFile,
     EditPoi(ServerPointOfInterest),
     EditDb(ServerDatabase),
     EditUser(ServerExtraUserAccount),
@@ -48,7 +50,7 @@ pub enum Msg {
     ServerItemDeleted(ServerItem),
     RequestDisplayServerItem(ServerItem),
     ShowInfoBar(String),
-    ItemGroupChanged,
+    ItemListChanged,
 }
 
 // String for details, because I can't pass Error across threads
@@ -61,10 +63,13 @@ pub struct Model {
     server_item: ServerItem,
     database_for_item: Option<ServerDatabase>,
     websites_for_item: Vec<ServerWebsite>,
+    tags: Vec<String>,
+    custom_fields: Vec<CustomField>,
     header_popover: gtk::Popover,
     title: (String, Icon),
     _server_item_deleted_channel: relm::Channel<DeleteResult>,
     server_item_deleted_sender: relm::Sender<DeleteResult>,
+    attachments_row: Option<Rc<AttachmentsRow>>,
 }
 
 pub fn get_server_item_grid_items(
@@ -222,10 +227,16 @@ impl Widget for ServerItemListItem {
         self.widgets
             .header_actions_btn
             .set_popover(Some(&self.model.header_popover));
+        if !self.model.tags.is_empty() {
+            let chips = tag_chips::tags_box(&self.model.tags);
+            chips.set_margin_start(10);
+            chips.set_valign(gtk::Align::Center);
+            self.widgets.title_box.add(&chips);
+        }
         self.load_server_item();
     }
 
-    fn load_server_item(&self) {
+    fn load_server_item(&mut self) {
         let fields =
             get_server_item_grid_items(&self.model.server_item, &self.model.database_for_item);
         // TODO drop the clone
@@ -258,7 +269,16 @@ impl Widget for ServerItemListItem {
                         Box::new(Msg::DeleteServerNote(n.clone()))
                     ))
                 );
-                vec![view_btn, edit_btn, delete_btn]
+                let attach_btn = gtk::ModelButtonBuilder::new()
+                    .label("Attach file...")
+                    .build();
+                relm::connect!(
+                    self.model.relm,
+                    &attach_btn,
+                    connect_clicked(_),
+                    Msg::AttachFile
+                );
+                vec![view_btn, edit_btn, attach_btn, delete_btn]
             }
             ServerItem::PointOfInterest(poi) => {
                 let edit_btn = gtk::ModelButtonBuilder::new().label("Edit").build();
@@ -393,6 +413,31 @@ impl Widget for ServerItemListItem {
                 );
             },
         );
+        let otp_secret = match &self.model.server_item {
+            ServerItem::Website(www) => Some(&www.otp_secret),
+            ServerItem::ExtraUserAccount(usr) => Some(&usr.otp_secret),
+            _ => None,
+        };
+        let mut row = fields.iter().filter(|f| !f.markup.is_empty()).count() as i32;
+        if let Some(otp_secret) = otp_secret.filter(|s| !s.is_empty()) {
+            let stream = self.model.relm.stream().clone();
+            otp_code::attach_to_grid(
+                &self.widgets.items_grid,
+                row,
+                &self.model.db_sender,
+                otp_secret,
+                move || stream.emit(Msg::ShowInfoBar("Copied to the clipboard".to_string())),
+            );
+            row += 1;
+        }
+        let stream = self.model.relm.stream().clone();
+        custom_fields::attach_to_grid(
+            &self.widgets.items_grid,
+            row,
+            &self.model.db_sender,
+            &self.model.custom_fields,
+            move || stream.emit(Msg::ShowInfoBar("Copied to the clipboard".to_string())),
+        );
         // TODO i don't like that note is special-cased here.
         if let ServerItem::Note(ref srv_n) = self.model.server_item {
             let truncated_contents = notes::note_markdown_to_quick_preview(&srv_n.contents)
@@ -415,6 +460,15 @@ impl Widget for ServerItemListItem {
                 1,
             );
             self.widgets.items_grid.show_all();
+            self.model.attachments_row = Some(AttachmentsRow::attach_to_grid(
+                &self.widgets.items_grid,
+                fields.len() as i32 + 1,
+                &self.model.db_sender,
+                EntityType::ServerNote,
+                srv_n.id,
+            ));
+        } else {
+            self.model.attachments_row = None;
         }
     }
 
@@ -448,9 +502,12 @@ impl Widget for ServerItemListItem {
             ServerItem,
             Option<ServerDatabase>,
             Vec<ServerWebsite>,
+            Vec<String>,
+            Vec<CustomField>,
         ),
     ) -> Model {
-        let (db_sender, server_item, database_for_item, websites_for_item) = params;
+        let (db_sender, server_item, database_for_item, websites_for_item, tags, custom_fields) =
+            params;
         let stream = relm.stream().clone();
         let (_server_item_deleted_channel, server_item_deleted_sender) =
             relm::Channel::new(move |r: DeleteResult| match r {
@@ -467,25 +524,27 @@ impl Widget for ServerItemListItem {
             server_item,
             database_for_item,
             websites_for_item,
+            tags,
+            custom_fields,
             header_popover: gtk::Popover::new(None::<&gtk::Button>),
             _server_item_deleted_channel,
             server_item_deleted_sender,
+            attachments_row: None,
         }
     }
 
-    fn run_delete_action<Tbl>(&self, table: Tbl, server_item: ServerItem)
-    where
-        Tbl: FindDsl<i32> + Send + 'static + Copy,
-        Find<Tbl, i32>: IntoUpdateTarget,
-        sql_util::DeleteFindStatement<Find<Tbl, i32>>: ExecuteDsl<SqliteConnection>,
-    {
+    fn run_delete_action(&self, server_item: ServerItem) {
         let s = self.model.server_item_deleted_sender.clone();
         self.model
             .db_sender
             .send(SqlFunc::new(move |sql_conn| {
                 s.send(
-                    sql_util::delete_row(sql_conn, table, server_item.get_id())
-                        .map(|_| server_item.clone()),
+                    sql_util::move_to_trash(
+                        sql_conn,
+                        server_item.entity_type(),
+                        server_item.get_id(),
+                    )
+                    .map(|_| server_item.clone()),
                 )
                 .unwrap();
             }))
@@ -494,16 +553,25 @@ impl Widget for ServerItemListItem {
 
     fn update(&mut self, event: Msg) {
         match event {
-            Msg::CopyClicked(val) => {
-                if let Some(clip) =
-                    gtk::Clipboard::get_default(&self.widgets.items_grid.get_display())
-                {
-                    clip.set_text(&val);
+            Msg::AttachFile => {
+                if let Some(row) = &self.model.attachments_row {
+                    row.attach_file();
                 }
-                self.model
-                    .relm
-                    .stream()
-                    .emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
+            }
+            Msg::CopyClicked(val) => {
+                let items_grid = self.widgets.items_grid.clone();
+                let stream = self.model.relm.stream().clone();
+                master_passphrase::reveal(
+                    &self.model.db_sender,
+                    items_grid.upcast_ref(),
+                    &val,
+                    move |val| {
+                        if let Some(clip) = gtk::Clipboard::get_default(&items_grid.get_display()) {
+                            clip.set_text(&val);
+                        }
+                        stream.emit(Msg::ShowInfoBar("Copied to the clipboard".to_string()));
+                    },
+                );
             }
             // meant for my parent
             Msg::ViewNote(_) => {}
@@ -628,22 +696,17 @@ impl Widget for ServerItemListItem {
                     .1
                     .close();
                 self.model.server_add_edit_dialog = None;
-                if self.model.server_item.group_name() != server_item.group_name() {
-                    self.model.relm.stream().emit(Msg::ItemGroupChanged);
-                }
+                // the group, the tags or the custom fields may have
+                // changed, the list must be reloaded
+                self.model.relm.stream().emit(Msg::ItemListChanged);
                 self.model.server_item = server_item;
                 self.model.title = Self::get_title(&self.model.server_item);
                 self.load_server_item();
             }
             Msg::DeleteServerPoi(poi) => {
-                use projectpadsql::schema::server_point_of_interest::dsl as srv_poi;
-                self.run_delete_action(
-                    srv_poi::server_point_of_interest,
-                    ServerItem::PointOfInterest(poi),
-                );
+                self.run_delete_action(ServerItem::PointOfInterest(poi));
             }
             Msg::DeleteServerDb(db) => {
-                use projectpadsql::schema::server_database::dsl as srv_db;
                 let s = self.model.server_item_deleted_sender.clone();
                 self.model
                     .db_sender
@@ -652,7 +715,7 @@ impl Widget for ServerItemListItem {
                         use projectpadsql::schema::server_website::dsl as srvw;
                         let dependent_websites = srvw::server_website
                             .inner_join(db::server_database)
-                            .filter(db::id.eq(db.id))
+                            .filter(db::id.eq(db.id).and(srvw::deleted_at.is_null()))
                             .load::<(ServerWebsite, ServerDatabase)>(sql_conn)
                             .unwrap();
                         if !dependent_websites.is_empty() {
@@ -668,8 +731,12 @@ impl Widget for ServerItemListItem {
                             )))
                         } else {
                             s.send(
-                                sql_util::delete_row(sql_conn, srv_db::server_database, db.id)
-                                    .map(|_| ServerItem::Database(db.clone())),
+                                sql_util::move_to_trash(
+                                    sql_conn,
+                                    EntityType::ServerDatabase,
+                                    db.id,
+                                )
+                                .map(|_| ServerItem::Database(db.clone())),
                             )
                         }
                         .unwrap();
@@ -681,31 +748,28 @@ impl Widget for ServerItemListItem {
                 let evt = *delete_evt;
                 standard_dialogs::confirm_deletion(
                     &format!("Delete server {}", message),
-                    &format!("Are you sure you want to delete the server {} {}? This action cannot be undone.", message, &item_desc),
+                    &format!(
+                        "Are you sure you want to move the server {} {} to the trash?",
+                        message, &item_desc
+                    ),
                     self.widgets.items_frame.clone().upcast::<gtk::Widget>(),
                     move || relm.stream().emit(evt.clone()),
                 );
             }
             Msg::DeleteServerExtraUser(user) => {
-                use projectpadsql::schema::server_extra_user_account::dsl as srv_usr;
-                self.run_delete_action(
-                    srv_usr::server_extra_user_account,
-                    ServerItem::ExtraUserAccount(user),
-                );
+                self.run_delete_action(ServerItem::ExtraUserAccount(user));
             }
             Msg::DeleteServerWebsite(website) => {
-                use projectpadsql::schema::server_website::dsl as srv_www;
-                self.run_delete_action(srv_www::server_website, ServerItem::Website(website));
+                self.run_delete_action(ServerItem::Website(website));
             }
             Msg::DeleteServerNote(note) => {
-                use projectpadsql::schema::server_note::dsl as srv_note;
-                self.run_delete_action(srv_note::server_note, ServerItem::Note(note));
+                self.run_delete_action(ServerItem::Note(note));
             }
             // for my parent
             Msg::ShowInfoBar(_) => {}
             Msg::ServerItemDeleted(_) => {}
             Msg::RequestDisplayServerItem(_) => {}
-            Msg::ItemGroupChanged => {}
+            Msg::ItemListChanged => {}
         }
     }
 
@@ -718,6 +782,7 @@ impl Widget for ServerItemListItem {
             margin_top: 20,
             gtk::Box {
                 orientation: gtk::Orientation::Vertical,
+                #[name="title_box"]
                 #[style_class="items_frame_title"]
                 gtk::Box {
                     orientation: gtk::Orientation::Horizontal,
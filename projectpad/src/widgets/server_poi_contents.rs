@@ -1,12 +1,14 @@
+use super::custom_fields::{self, ItemCustomFields};
 use super::server_item_list_item::Msg as ServerItemListItemMsg;
 use super::server_item_list_item::ServerItemListItem;
+use super::tag_chips::{self, ItemTags};
 use crate::sql_thread::SqlFunc;
 use diesel::prelude::*;
 use gtk::prelude::*;
 use itertools::Itertools;
 use projectpadsql::models::{
-    Server, ServerDatabase, ServerExtraUserAccount, ServerLink, ServerNote, ServerPointOfInterest,
-    ServerWebsite,
+    EntityType, Server, ServerDatabase, ServerExtraUserAccount, ServerLink, ServerNote,
+    ServerPointOfInterest, ServerWebsite,
 };
 use relm::{Component, ContainerWidget, Widget};
 use relm_derive::{widget, Msg};
@@ -18,6 +20,8 @@ pub struct ChannelData {
     group_start_indexes: HashMap<i32, String>,
     databases_for_websites: HashMap<i32, ServerDatabase>,
     websites_for_databases: HashMap<i32, Vec<ServerWebsite>>,
+    item_tags: ItemTags,
+    item_custom_fields: ItemCustomFields,
 }
 
 #[derive(Msg)]
@@ -72,6 +76,16 @@ impl ServerItem {
             ServerItem::Database(d) => d.server_id,
         }
     }
+
+    pub fn entity_type(&self) -> EntityType {
+        match self {
+            ServerItem::Website(_) => EntityType::ServerWebsite,
+            ServerItem::PointOfInterest(_) => EntityType::ServerPoi,
+            ServerItem::Note(_) => EntityType::ServerNote,
+            ServerItem::ExtraUserAccount(_) => EntityType::ServerExtraUserAccount,
+            ServerItem::Database(_) => EntityType::ServerDatabase,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -90,6 +104,8 @@ pub struct Model {
     server_item_groups_start_indexes: HashMap<i32, String>,
     databases_for_websites: HashMap<i32, ServerDatabase>,
     websites_for_databases: HashMap<i32, Vec<ServerWebsite>>,
+    item_tags: ItemTags,
+    item_custom_fields: ItemCustomFields,
     _children_components: Vec<Component<ServerItemListItem>>,
     scroll_to_item_request: Option<ScrollTarget>,
 }
@@ -118,6 +134,8 @@ impl Widget for ServerPoiContents {
             server_item_groups_start_indexes: HashMap::new(),
             databases_for_websites: HashMap::new(),
             websites_for_databases: HashMap::new(),
+            item_tags: HashMap::new(),
+            item_custom_fields: HashMap::new(),
             _children_components: vec![],
             scroll_to_item_request: None,
         }
@@ -240,6 +258,8 @@ impl Widget for ServerPoiContents {
                 self.model.server_item_groups_start_indexes = items.group_start_indexes;
                 self.model.databases_for_websites = items.databases_for_websites;
                 self.model.websites_for_databases = items.websites_for_databases;
+                self.model.item_tags = items.item_tags;
+                self.model.item_custom_fields = items.item_custom_fields;
                 self.update_contents_list();
                 // do we have a pending request to scroll to a certain item?
                 if let Some(st) = self.model.scroll_to_item_request.take() {
@@ -324,6 +344,16 @@ impl Widget for ServerPoiContents {
                     item.clone(),
                     self.database_for_item(&item),
                     self.websites_for_item(&item),
+                    self.model
+                        .item_tags
+                        .get(&(item.entity_type(), item.get_id()))
+                        .cloned()
+                        .unwrap_or_else(Vec::new),
+                    self.model
+                        .item_custom_fields
+                        .get(&(item.entity_type(), item.get_id()))
+                        .cloned()
+                        .unwrap_or_else(Vec::new),
                 ));
             relm::connect!(
                 component@ServerItemListItemMsg::ViewNote(ref n),
@@ -338,7 +368,7 @@ impl Widget for ServerPoiContents {
                 component@ServerItemListItemMsg::ShowInfoBar(ref msg),
                            self.model.relm, Msg::ShowInfoBar(msg.clone()));
             relm::connect!(
-                component@ServerItemListItemMsg::ItemGroupChanged,
+                component@ServerItemListItemMsg::ItemListChanged,
                            self.model.relm, Msg::RefreshItems);
             children_components.push(component);
         }
@@ -434,7 +464,11 @@ impl Widget for ServerPoiContents {
                 let (items, databases_for_websites, websites_for_databases) = match cur_server_id {
                     Some(sid) => {
                         let server_websites = srv_www::server_website
-                            .filter(srv_www::server_id.eq(sid))
+                            .filter(
+                                srv_www::server_id
+                                    .eq(sid)
+                                    .and(srv_www::deleted_at.is_null()),
+                            )
                             .order(srv_www::desc.asc())
                             .load::<ServerWebsite>(sql_conn)
                             .unwrap();
@@ -456,7 +490,11 @@ impl Widget for ServerPoiContents {
 
                         servers.extend(
                             srv_poi::server_point_of_interest
-                                .filter(srv_poi::server_id.eq(sid))
+                                .filter(
+                                    srv_poi::server_id
+                                        .eq(sid)
+                                        .and(srv_poi::deleted_at.is_null()),
+                                )
                                 .order(srv_poi::desc.asc())
                                 .load::<ServerPointOfInterest>(sql_conn)
                                 .unwrap()
@@ -465,7 +503,11 @@ impl Widget for ServerPoiContents {
                         );
                         servers.extend(
                             srv_note::server_note
-                                .filter(srv_note::server_id.eq(sid))
+                                .filter(
+                                    srv_note::server_id
+                                        .eq(sid)
+                                        .and(srv_note::deleted_at.is_null()),
+                                )
                                 .order(srv_note::title.asc())
                                 .load::<ServerNote>(sql_conn)
                                 .unwrap()
@@ -474,7 +516,11 @@ impl Widget for ServerPoiContents {
                         );
                         servers.extend(
                             &mut srv_usr::server_extra_user_account
-                                .filter(srv_usr::server_id.eq(sid))
+                                .filter(
+                                    srv_usr::server_id
+                                        .eq(sid)
+                                        .and(srv_usr::deleted_at.is_null()),
+                                )
                                 .order(srv_usr::desc.asc())
                                 .load::<ServerExtraUserAccount>(sql_conn)
                                 .unwrap()
@@ -483,7 +529,7 @@ impl Widget for ServerPoiContents {
                         );
 
                         let databases = srv_db::server_database
-                            .filter(srv_db::server_id.eq(sid))
+                            .filter(srv_db::server_id.eq(sid).and(srv_db::deleted_at.is_null()))
                             .order(srv_db::desc.asc())
                             .load::<ServerDatabase>(sql_conn)
                             .unwrap();
@@ -492,7 +538,8 @@ impl Widget for ServerPoiContents {
                         for (key, group) in &srv_www::server_website
                             .filter(
                                 srv_www::server_database_id
-                                    .eq_any(databases.iter().map(|db| db.id)),
+                                    .eq_any(databases.iter().map(|db| db.id))
+                                    .and(srv_www::deleted_at.is_null()),
                             )
                             .order(srv_www::server_database_id.asc())
                             .load::<ServerWebsite>(sql_conn)
@@ -526,11 +573,31 @@ impl Widget for ServerPoiContents {
                     );
                 }
 
+                // extra users can't have tags
+                let item_tags = tag_chips::load_item_tags(
+                    sql_conn,
+                    items
+                        .iter()
+                        .filter(|i| !matches!(i, ServerItem::ExtraUserAccount(_)))
+                        .map(|i| (i.entity_type(), i.get_id())),
+                );
+
+                // only the databases and websites have custom fields
+                let item_custom_fields = custom_fields::load_item_custom_fields(
+                    sql_conn,
+                    items
+                        .iter()
+                        .filter(|i| matches!(i, ServerItem::Database(_) | ServerItem::Website(_)))
+                        .map(|i| (i.entity_type(), i.get_id())),
+                );
+
                 s.send(ChannelData {
                     server_items: grouped_items.into_iter().cloned().collect(),
                     group_start_indexes,
                     databases_for_websites,
                     websites_for_databases,
+                    item_tags,
+                    item_custom_fields,
                 })
                 .unwrap();
             }))
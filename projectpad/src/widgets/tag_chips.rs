@@ -0,0 +1,39 @@
+use gtk::prelude::*;
+use itertools::Itertools;
+use projectpadsql::models::EntityType;
+use projectpadsql::repo::TagRepo;
+use std::collections::HashMap;
+
+pub type ItemTags = HashMap<(EntityType, i32), Vec<String>>;
+
+/// load the tag names of items, by entity type and id.
+/// Items without tags are missing from the map.
+pub fn load_item_tags(
+    sql_conn: &diesel::SqliteConnection,
+    items: impl Iterator<Item = (EntityType, i32)>,
+) -> ItemTags {
+    let repo = TagRepo::new(sql_conn);
+    let mut result = HashMap::new();
+    let by_type = items.into_group_map();
+    for (entity_type, ids) in by_type {
+        for (id, tags) in repo.list_for_items(entity_type, &ids).unwrap() {
+            result.insert(
+                (entity_type, id),
+                tags.into_iter().map(|t| t.name).collect(),
+            );
+        }
+    }
+    result
+}
+
+/// a row of small labels, one per tag
+pub fn tags_box(tags: &[String]) -> gtk::Box {
+    let hbox = gtk::BoxBuilder::new().spacing(4).build();
+    for tag in tags {
+        let label = gtk::LabelBuilder::new().label(tag).build();
+        label.get_style_context().add_class("tag_chip");
+        hbox.add(&label);
+    }
+    hbox.show_all();
+    hbox
+}
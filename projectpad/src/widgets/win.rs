@@ -40,7 +40,6 @@ use super::tooltips_overlay::TooltipsOverlay;
 use super::wintitlebar::Msg as WinTitleBarMsg;
 use super::wintitlebar::WinTitleBar;
 use crate::config::Config;
-use crate::sql_thread::migrate_db_if_needed;
 use crate::sql_thread::SqlFunc;
 use crate::widgets::project_items_list::Msg::ProjectItemSelected;
 use crate::widgets::project_summary::Msg::EnvironmentChanged;
@@ -48,7 +47,9 @@ use diesel::prelude::*;
 use gdk::ModifierType;
 use gdk::WindowExt;
 use gtk::prelude::*;
-use projectpadsql::models::{EnvironmentType, Project, Server};
+use projectpadsql::models::{EntityType, Project, Server};
+use projectpadsql::repo::EnvironmentRepo;
+use projectpadsql::{backup, trash};
 use relm::{Component, Widget};
 use relm_derive::{widget, Msg};
 use std::sync::mpsc;
@@ -70,34 +71,54 @@ const CSS_DATA: &[u8] = include_bytes!("../../resources/style.css");
 
 type DisplayItemParams = (Project, Option<ProjectItem>, Option<ServerItem>);
 
+/// the item to display, and the name of the environment to display it in
+type DisplayItemInEnvParams = (DisplayItemParams, Option<String>);
+
+// String for details, because I can't pass Error across threads
+type UndoDeleteResult = Result<(), (&'static str, Option<String>)>;
+
+/// the errors of the maintenance done when opening the database,
+/// they don't prevent using the database
+type DbMaintenanceErrors = Vec<(&'static str, Option<String>)>;
+
+/// the details of the error if the database can't be migrated
+type DbPreparedResult = Result<DbMaintenanceErrors, String>;
+
 #[derive(Msg)]
 pub enum Msg {
     Quit,
     CloseUnlockDb,
     DbUnlockAttempted(bool),
     DbUnlocked,
-    DbPrepared,
+    DbPrepared(DbMaintenanceErrors),
+    DbPrepareFailed(String),
     DarkThemeToggled,
     ProjectActivated(Project),
-    EnvironmentChanged(EnvironmentType),
+    EnvironmentChanged(String),
     ProjectItemSelected(Option<ProjectItem>),
     SearchActiveChanged(bool),
     SearchTextChanged(String),
     DisplayItem(Box<DisplayItemParams>), // large enum variant, hence boxed
+    DisplayItemInEnvironment(Box<DisplayItemInEnvParams>),
     KeyPress(gdk::EventKey),
     KeyRelease(gdk::EventKey),
     ProjectItemUpdated(ProjectItem),
     ProjectItemDeleted(ProjectItem),
+    ProjectDeleted(Project),
+    UndoLastDelete,
+    UndoDeleteApplied(UndoDeleteResult),
     RequestDisplayItem(ServerItem),
     AddProject,
     ProjectListChanged,
     ProjectCountChanged(usize),
     UpdateProjectTooltip(Option<(String, i32)>),
     ShowInfoBar(String),
+    ShowUndoDeleteInfoBar(String, (EntityType, i32)),
     HideInfobar,
     SearchResultsModified,
     OpenSingleWebsiteLink,
     ImportApplied,
+    TrashChanged,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -113,17 +134,24 @@ pub struct Model {
     is_db_unlocked: bool,
     _display_item_channel: relm::Channel<DisplayItemParams>,
     display_item_sender: relm::Sender<DisplayItemParams>,
+    _display_item_in_env_channel: relm::Channel<DisplayItemInEnvParams>,
+    display_item_in_env_sender: relm::Sender<DisplayItemInEnvParams>,
     project_add_dialog: Option<(relm::Component<ProjectAddEditDialog>, gtk::Dialog)>,
     tooltips_overlay: Component<TooltipsOverlay>,
     _db_unlock_attempted_channel: relm::Channel<bool>,
     db_unlock_attempted_sender: relm::Sender<bool>,
-    _db_prepared_channel: relm::Channel<()>,
-    db_prepared_sender: relm::Sender<()>,
+    _db_prepared_channel: relm::Channel<DbPreparedResult>,
+    db_prepared_sender: relm::Sender<DbPreparedResult>,
     _project_count_channel: relm::Channel<usize>,
     project_count_sender: relm::Sender<usize>,
     unlock_db_component_dialog: Option<(gtk::Dialog, Component<UnlockDbDialog>)>,
     infobar: gtk::InfoBar,
     infobar_label: gtk::Label,
+    infobar_undo_btn: gtk::Button,
+    // the entity whose delete the infobar offers to undo
+    undo_delete_target: Option<(EntityType, i32)>,
+    _undo_delete_channel: relm::Channel<UndoDeleteResult>,
+    undo_delete_sender: relm::Sender<UndoDeleteResult>,
 }
 
 const CHILD_NAME_NORMAL: &str = "normal";
@@ -151,7 +179,15 @@ impl Widget for Win {
                                self.model.relm, Msg::DarkThemeToggled);
         relm::connect!(titlebar@WinTitleBarMsg::ImportApplied,
                                self.model.relm, Msg::ImportApplied);
+        relm::connect!(titlebar@WinTitleBarMsg::TrashChanged,
+                               self.model.relm, Msg::TrashChanged);
         self.init_infobar_overlay();
+        relm::connect!(
+            self.model.relm,
+            self.model.infobar_undo_btn,
+            connect_clicked(_),
+            Msg::UndoLastDelete
+        );
 
         self.unlock_db();
 
@@ -201,15 +237,22 @@ impl Widget for Win {
             relm::Channel::new(move |ch_data: DisplayItemParams| {
                 stream.emit(Msg::DisplayItem(Box::new(ch_data)));
             });
+        let stream_env = relm.stream().clone();
+        let (display_item_in_env_channel, display_item_in_env_sender) =
+            relm::Channel::new(move |ch_data: DisplayItemInEnvParams| {
+                stream_env.emit(Msg::DisplayItemInEnvironment(Box::new(ch_data)));
+            });
         let stream2 = relm.stream().clone();
         let (db_unlock_attempted_channel, db_unlock_attempted_sender) =
             relm::Channel::new(move |val| {
                 stream2.emit(Msg::DbUnlockAttempted(val));
             });
         let stream3 = relm.stream().clone();
-        let (db_prepared_channel, db_prepared_sender) = relm::Channel::new(move |_| {
-            stream3.emit(Msg::DbPrepared);
-        });
+        let (db_prepared_channel, db_prepared_sender) =
+            relm::Channel::new(move |r: DbPreparedResult| match r {
+                Ok(errors) => stream3.emit(Msg::DbPrepared(errors)),
+                Err(details) => stream3.emit(Msg::DbPrepareFailed(details)),
+            });
         let stream4 = relm.stream().clone();
         let (project_count_channel, project_count_sender) = relm::Channel::new(move |count| {
             stream4.emit(Msg::ProjectCountChanged(count));
@@ -223,7 +266,14 @@ impl Widget for Win {
         let infobar_label = gtk::LabelBuilder::new().label("").build();
         infobar_label.show();
         infobar.get_content_area().add(&infobar_label);
+        let infobar_undo_btn = gtk::ButtonBuilder::new().label("Undo").build();
+        infobar.get_content_area().add(&infobar_undo_btn);
         infobar.show();
+        let stream5 = relm.stream().clone();
+        let (undo_delete_channel, undo_delete_sender) =
+            relm::Channel::new(move |r: UndoDeleteResult| {
+                stream5.emit(Msg::UndoDeleteApplied(r));
+            });
         Model {
             relm: relm.clone(),
             db_sender,
@@ -233,6 +283,8 @@ impl Widget for Win {
             tooltips_overlay,
             display_item_sender,
             _display_item_channel: display_item_channel,
+            display_item_in_env_sender,
+            _display_item_in_env_channel: display_item_in_env_channel,
             db_unlock_attempted_sender,
             _db_unlock_attempted_channel: db_unlock_attempted_channel,
             db_prepared_sender,
@@ -243,6 +295,10 @@ impl Widget for Win {
             unlock_db_component_dialog: None,
             infobar,
             infobar_label,
+            infobar_undo_btn,
+            undo_delete_target: None,
+            _undo_delete_channel: undo_delete_channel,
+            undo_delete_sender,
         }
     }
 
@@ -252,7 +308,12 @@ impl Widget for Win {
             self.model
                 .db_sender
                 .send(SqlFunc::new(move |sql_conn| {
-                    let unlock_success = projectpadsql::try_unlock_db(sql_conn, &pass).is_ok();
+                    let unlock_success = projectpadsql::try_unlock_db_file(
+                        sql_conn,
+                        &pass,
+                        &projectpadsql::database_path(),
+                    )
+                    .is_ok();
                     s.send(unlock_success).unwrap();
                 }))
                 .unwrap();
@@ -315,13 +376,46 @@ impl Widget for Win {
 
     fn run_prepare_db(&self) {
         let s = self.model.db_prepared_sender.clone();
+        let trash_retention_days = Config::read_config().trash_retention_days;
 
         self.model
             .db_sender
             .send(SqlFunc::new(move |db_conn| {
-                migrate_db_if_needed(&db_conn).unwrap();
+                let db_path = projectpadsql::database_path();
+                let version_before = projectpadsql::get_db_version(&db_conn).unwrap_or(0);
+                if let Err(e) =
+                    projectpadsql::migrations::migrate_db_if_needed(&db_conn, Some(&db_path))
+                {
+                    // the migrations run in a transaction, the database is unchanged
+                    let backup_path =
+                        projectpadsql::migrations::pre_migration_backup_path(&db_path, version_before);
+                    s.send(Err(if backup_path.exists() {
+                        format!(
+                            "{}\n\nThe database was not modified. A copy of it made before the upgrade is in {}",
+                            e,
+                            backup_path.display()
+                        )
+                    } else {
+                        e.to_string()
+                    }))
+                    .unwrap();
+                    return;
+                }
                 db_conn.execute("PRAGMA foreign_keys = ON").unwrap();
-                s.send(()).unwrap();
+                let mut errors = vec![];
+                if let Err(e) = trash::purge_older_than(&db_conn, trash_retention_days) {
+                    errors.push(("Error purging the trash", Some(e.to_string())));
+                }
+                if let Err(e) = backup::daily_backup(
+                    &db_conn,
+                    &backup::backups_folder(),
+                    &projectpadsql::profile::current(),
+                    chrono::Local::today().naive_local(),
+                    backup::DAILY_BACKUPS_KEPT,
+                ) {
+                    errors.push(("Error backing up the database", Some(e.to_string())));
+                }
+                s.send(Ok(errors)).unwrap();
             }))
             .unwrap();
     }
@@ -334,6 +428,7 @@ impl Widget for Win {
                 use projectpadsql::schema::project::dsl as prj;
                 s.send(
                     prj::project
+                        .filter(prj::deleted_at.is_null())
                         .select(diesel::dsl::count(prj::id))
                         .first::<i64>(sql_conn)
                         .unwrap() as usize,
@@ -343,6 +438,41 @@ impl Widget for Win {
             .unwrap();
     }
 
+    fn show_infobar(&mut self, msg: &str, undo_delete_target: Option<(EntityType, i32)>) {
+        self.model.infobar_label.set_text(msg);
+        self.model
+            .infobar_undo_btn
+            .set_visible(undo_delete_target.is_some());
+        // the infobar lets clicks through, unless it has an undo button
+        self.widgets
+            .infobar_overlay
+            .set_overlay_pass_through(&self.model.infobar, undo_delete_target.is_none());
+        self.model.undo_delete_target = undo_delete_target;
+        self.model.infobar.set_revealed(true);
+    }
+
+    fn hide_infobar(&mut self) {
+        self.model.infobar.set_revealed(false);
+        self.widgets
+            .infobar_overlay
+            .set_overlay_pass_through(&self.model.infobar, true);
+    }
+
+    fn undo_last_delete(&self, target: (EntityType, i32)) {
+        let s = self.model.undo_delete_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                let (entity_type, id) = target;
+                s.send(
+                    trash::restore(sql_conn, entity_type, id)
+                        .map_err(|e| ("Failed to undo the delete", Some(e.to_string()))),
+                )
+                .unwrap();
+            }))
+            .unwrap();
+    }
+
     fn update(&mut self, event: Msg) {
         match event {
             Msg::Quit => gtk::main_quit(),
@@ -358,7 +488,7 @@ impl Widget for Win {
             Msg::DbUnlocked => {
                 self.run_prepare_db();
             }
-            Msg::DbPrepared => {
+            Msg::DbPrepared(errors) => {
                 self.model.is_db_unlocked = true;
                 if let Some((dialog, _)) = &self.model.unlock_db_component_dialog {
                     dialog.close();
@@ -366,6 +496,24 @@ impl Widget for Win {
                 }
                 self.streams.project_list.emit(ProjectListMsg::DbPrepared);
                 self.request_update_welcome_status();
+                for (msg, e) in errors {
+                    standard_dialogs::display_error_str(msg, e);
+                }
+            }
+            Msg::DbPrepareFailed(details) => {
+                // we can't use a database we couldn't migrate
+                let dialog = gtk::MessageDialogBuilder::new()
+                    .buttons(gtk::ButtonsType::Ok)
+                    .message_type(gtk::MessageType::Error)
+                    .modal(true)
+                    .text("Error upgrading the database")
+                    .secondary_text(&details)
+                    .build();
+                if let Some((unlock_dialog, _)) = &self.model.unlock_db_component_dialog {
+                    dialog.set_transient_for(Some(unlock_dialog));
+                }
+                dialog.connect_response(|_, _| gtk::main_quit());
+                dialog.show_all();
             }
             Msg::CloseUnlockDb => {
                 if !self.model.is_db_unlocked {
@@ -415,7 +563,25 @@ impl Widget for Win {
                     .emit(SearchViewMsg::FilterChanged(Some(search_text)));
             }
             Msg::DisplayItem(di) => {
-                self.display_item(di);
+                let (project, project_item, server_item) = *di;
+                match &project_item {
+                    Some(ProjectItem::ProjectNote(n)) => {
+                        self.request_note_environment(n.id, (project, project_item, server_item))
+                    }
+                    Some(ProjectItem::Server(s)) => {
+                        let env = Some(s.environment.clone());
+                        self.display_item(project, project_item, server_item, env);
+                    }
+                    Some(ProjectItem::ServerLink(s)) => {
+                        let env = Some(s.environment.clone());
+                        self.display_item(project, project_item, server_item, env);
+                    }
+                    _ => self.display_item(project, project_item, server_item, None),
+                }
+            }
+            Msg::DisplayItemInEnvironment(di) => {
+                let ((project, project_item, server_item), env) = *di;
+                self.display_item(project, project_item, server_item, env);
             }
             Msg::RequestDisplayItem(server_item) => {
                 self.request_display_item(server_item);
@@ -436,10 +602,48 @@ impl Widget for Win {
                         project_item.clone(),
                     )));
             }
-            Msg::ProjectItemDeleted(ref _srv) => {
+            Msg::ProjectItemDeleted(ref project_item) => {
                 self.streams
                     .project_items_list
                     .emit(ProjectItemsListMsg::RefreshItemList(None));
+                let (desc, target) = match project_item {
+                    ProjectItem::Server(srv) => (&srv.desc, (EntityType::Server, srv.id)),
+                    ProjectItem::ServerLink(link) => {
+                        (&link.desc, (EntityType::ServerLink, link.id))
+                    }
+                    ProjectItem::ProjectNote(note) => {
+                        (&note.title, (EntityType::ProjectNote, note.id))
+                    }
+                    ProjectItem::ProjectPointOfInterest(poi) => {
+                        (&poi.desc, (EntityType::ProjectPoi, poi.id))
+                    }
+                };
+                self.model.relm.stream().emit(Msg::ShowUndoDeleteInfoBar(
+                    format!("'{}' was moved to the trash", desc),
+                    target,
+                ));
+            }
+            Msg::ProjectDeleted(ref project) => {
+                self.model.relm.stream().emit(Msg::ProjectListChanged);
+                self.model.relm.stream().emit(Msg::ShowUndoDeleteInfoBar(
+                    format!("Project '{}' was moved to the trash", project.name),
+                    (EntityType::Project, project.id),
+                ));
+            }
+            Msg::UndoLastDelete => {
+                self.hide_infobar();
+                if let Some(target) = self.model.undo_delete_target.take() {
+                    self.undo_last_delete(target);
+                }
+            }
+            Msg::UndoDeleteApplied(Ok(())) => {
+                self.model.relm.stream().emit(Msg::ProjectListChanged);
+                self.streams
+                    .project_items_list
+                    .emit(ProjectItemsListMsg::RefreshItemList(None));
+            }
+            Msg::UndoDeleteApplied(Err((msg, e))) => {
+                standard_dialogs::display_error_str(msg, e);
             }
             Msg::ProjectListChanged => {
                 if let Some((_, dlg)) = &self.model.project_add_dialog {
@@ -482,12 +686,15 @@ impl Widget for Win {
                     .emit(tooltips_overlay::Msg::UpdateProjectTooltip(params));
             }
             Msg::ShowInfoBar(msg) => {
-                self.model.infobar_label.set_text(&msg);
-                self.model.infobar.set_revealed(true);
+                self.show_infobar(&msg, None);
                 relm::timeout(self.model.relm.stream(), 1500, || Msg::HideInfobar);
             }
+            Msg::ShowUndoDeleteInfoBar(msg, target) => {
+                self.show_infobar(&msg, Some(target));
+                relm::timeout(self.model.relm.stream(), 8000, || Msg::HideInfobar);
+            }
             Msg::HideInfobar => {
-                self.model.infobar.set_revealed(false);
+                self.hide_infobar();
             }
             Msg::DarkThemeToggled => {
                 self.streams
@@ -510,6 +717,14 @@ impl Widget for Win {
                 self.streams.project_list.emit(ProjectListMsg::ForceReload);
                 self.request_update_welcome_status();
             }
+            Msg::TrashChanged => {
+                // entities were restored from the trash
+                self.streams.project_list.emit(ProjectListMsg::ForceReload);
+                self.streams
+                    .project_items_list
+                    .emit(ProjectItemsListMsg::RefreshItemList(None));
+                self.request_update_welcome_status();
+            }
         }
     }
 
@@ -535,21 +750,34 @@ impl Widget for Win {
             .unwrap();
     }
 
-    fn display_item(&self, di: Box<DisplayItemParams>) {
-        let (project, project_item, server_item) = *di;
+    /// a note can be in several environments: display it in the last one,
+    /// usually production
+    fn request_note_environment(&self, project_note_id: i32, di: DisplayItemParams) {
+        let s = self.model.display_item_in_env_sender.clone();
+        self.model
+            .db_sender
+            .send(SqlFunc::new(move |sql_conn| {
+                let env = EnvironmentRepo::new(sql_conn)
+                    .list_for_note(project_note_id)
+                    .unwrap()
+                    .pop()
+                    .map(|e| e.name);
+                s.send((di.clone(), env)).unwrap();
+            }))
+            .unwrap();
+    }
+
+    fn display_item(
+        &self,
+        project: Project,
+        project_item: Option<ProjectItem>,
+        server_item: Option<ServerItem>,
+        env: Option<String>,
+    ) {
         self.components
             .project_list
             .emit(ProjectListMsg::ProjectSelectedFromElsewhere(project.id));
-        let env = match &project_item {
-            Some(ProjectItem::Server(s)) => Some(s.environment),
-            Some(ProjectItem::ServerLink(s)) => Some(s.environment),
-            Some(ProjectItem::ProjectNote(n)) if n.has_prod => Some(EnvironmentType::EnvProd),
-            Some(ProjectItem::ProjectNote(n)) if n.has_uat => Some(EnvironmentType::EnvUat),
-            Some(ProjectItem::ProjectNote(n)) if n.has_stage => Some(EnvironmentType::EnvStage),
-            Some(ProjectItem::ProjectNote(n)) if n.has_dev => Some(EnvironmentType::EnvDevelopment),
-            _ => None,
-        };
-        if let Some(e) = env {
+        if let Some(e) = env.clone() {
             self.streams.project_summary.emit(
                 ProjectSummaryMsg::ProjectEnvironmentSelectedFromElsewhere((project.clone(), e)),
             );
@@ -767,7 +995,7 @@ impl Widget for Win {
                                             EnvironmentChanged(env) => Msg::EnvironmentChanged(env),
                                             ProjectSummaryItemAddedMsg(ref pi) => Msg::ProjectItemUpdated(pi.clone()),
                                             ProjectSummaryProjectUpdated(_) => Msg::ProjectListChanged,
-                                            ProjectSummaryProjectDeleted(_) => Msg::ProjectListChanged
+                                            ProjectSummaryProjectDeleted(ref p) => Msg::ProjectDeleted(p.clone())
                                         },
                                         gtk::Separator {},
                                         gtk::Box {
@@ -876,12 +1104,12 @@ impl Widget for Win {
                                     yalign: 0.1,
                                     line_wrap: true,
                                     markup: "<big><b>Welcome to Projectpad!</b></big>\n\n\nTo get started, you must create your first project. Use the <tt>+</tt> button on the top-left.\n\n\
-                                             Projects get subdivided in environments, and specifically:\n\n\
-                                             • <u>Prod</u> - the production environment;\n\
-                                             • <u>Uat</u> - User Acceptance Testing, an environment used by the customer, which is not Prod;\n\
-                                             • <u>Stg</u> - Staging, the last testing environment before showing the product to the customer;\n\
-                                             • <u>Dev</u> - the development environment.\n\n\
-                                             A project should have at least one environment. If unsure, use Prod.\n\n\
+                                             Projects get subdivided in environments. New projects start with these ones:\n\n\
+                                             • <u>Production</u> - the production environment;\n\
+                                             • <u>UAT</u> - User Acceptance Testing, an environment used by the customer, which is not Production;\n\
+                                             • <u>Staging</u> - the last testing environment before showing the product to the customer;\n\
+                                             • <u>Development</u> - the development environment.\n\n\
+                                             You can rename, remove or add environments when editing the project. A project should have at least one environment.\n\n\
                                              Once you have a project and environments for it, you'll be able to manage notes, points of interests, servers, and so on, for that project,\n\
                                              for each environment."
                                 }
@@ -1,8 +1,15 @@
+use super::dialogs::database_health_dlg::DatabaseHealthDialog;
+use super::dialogs::database_health_dlg::Msg as DatabaseHealthMsg;
 use super::dialogs::import_export_dlg::ImportExportDialog;
 use super::dialogs::import_export_dlg::Msg as ImportExportMsg;
+use super::dialogs::password_audit_dlg::PasswordAuditDialog;
 use super::dialogs::preferences::Msg as PreferencesMsg;
 use super::dialogs::preferences::Preferences;
 use super::dialogs::standard_dialogs;
+use super::dialogs::sync_dlg::Msg as SyncMsg;
+use super::dialogs::sync_dlg::SyncDialog;
+use super::dialogs::trash_dlg::Msg as TrashMsg;
+use super::dialogs::trash_dlg::TrashDialog;
 use super::search_engine::PROJECT_FILTER_PREFIX;
 use crate::config::Config;
 use crate::icons::Icon;
@@ -14,10 +21,17 @@ use std::sync::mpsc;
 
 const SHORTCUTS_UI: &str = include_str!("shortcuts.ui");
 
+/// the id of the last entry of the profile combo, to create a profile
+const NEW_PROFILE_ID: &str = "<new profile>";
+
 #[derive(Msg)]
 pub enum Msg {
     DisplayPreferences,
     DisplayImport,
+    DisplayTrash,
+    DisplayPasswordAudit,
+    DisplayDatabaseHealth,
+    DisplaySync,
     DisplayShortcuts,
     DisplayHelp,
     DisplayAbout,
@@ -30,6 +44,8 @@ pub enum Msg {
     SearchTextChangedFromElsewhere((String, gdk::EventKey)),
     EnterOrUpdateSearchProject,
     ImportApplied,
+    TrashChanged,
+    ProfileSelected(Option<String>),
 }
 
 pub struct Model {
@@ -39,6 +55,10 @@ pub struct Model {
     menu_popover: gtk::Popover,
     prefs_win: Option<Component<Preferences>>,
     import_win: Option<Component<ImportExportDialog>>,
+    trash_win: Option<Component<TrashDialog>>,
+    password_audit_win: Option<Component<PasswordAuditDialog>>,
+    database_health_win: Option<Component<DatabaseHealthDialog>>,
+    sync_win: Option<Component<SyncDialog>>,
 }
 
 pub fn left_align_menu(menu: &gtk::ModelButton) {
@@ -60,6 +80,87 @@ impl Widget for WinTitleBar {
                 relm.stream().emit(Msg::SearchClicked);
             }));
         self.init_menu_popover();
+        self.init_profile_combo();
+    }
+
+    fn init_profile_combo(&self) {
+        let combo = &self.widgets.profile_combo;
+        for profile in projectpadsql::profile::list() {
+            combo.append(Some(&profile), &profile);
+        }
+        combo.append(Some(NEW_PROFILE_ID), "New profile…");
+        combo.set_active_id(Some(&projectpadsql::profile::current()));
+        relm::connect!(
+            self.model.relm,
+            combo,
+            connect_changed(c),
+            Msg::ProfileSelected(c.get_active_id().map(|id| id.to_string()))
+        );
+    }
+
+    fn ask_new_profile_name(&self) {
+        let dialog = standard_dialogs::modal_dialog(
+            self.widgets.header_bar.clone().upcast::<gtk::Widget>(),
+            400,
+            100,
+            "New profile".to_string(),
+        );
+        let entry = gtk::EntryBuilder::new()
+            .margin(10)
+            .activates_default(true)
+            .placeholder_text("Profile name, for instance work")
+            .build();
+        dialog.get_content_area().add(&entry);
+        let create_btn = dialog
+            .add_button("Create", gtk::ResponseType::Ok)
+            .downcast::<gtk::Button>()
+            .expect("error reading the dialog create button");
+        create_btn.set_property_has_default(true);
+        create_btn.get_style_context().add_class("suggested-action");
+        let combo = self.widgets.profile_combo.clone();
+        dialog.connect_response(move |d, r| {
+            if r != gtk::ResponseType::Ok {
+                // back to the current profile
+                combo.set_active_id(Some(&projectpadsql::profile::current()));
+                d.close();
+                return;
+            }
+            let name = entry.get_text().trim().to_string();
+            let error = projectpadsql::profile::check_name(&name)
+                .err()
+                .map(|e| e.to_string())
+                .or_else(|| {
+                    Some(format!("the profile '{}' already exists", name))
+                        .filter(|_| projectpadsql::profile::list().contains(&name))
+                });
+            match error {
+                Some(e) => {
+                    standard_dialogs::display_error_str("Cannot create the profile", Some(e))
+                }
+                None => {
+                    d.close();
+                    Self::switch_profile(&name);
+                }
+            }
+        });
+        dialog.show_all();
+    }
+
+    /// the sql thread, the password and all the widgets are tied
+    /// to the database: start over on the database of the other profile.
+    /// Also reopens the database after it was restored from a backup.
+    pub fn switch_profile(profile: &str) {
+        let spawned = std::env::current_exe().and_then(|exe| {
+            std::process::Command::new(exe)
+                .env(projectpadsql::profile::PROFILE_ENV_VAR, profile)
+                .spawn()
+        });
+        match spawned {
+            Ok(_) => gtk::main_quit(),
+            Err(e) => {
+                standard_dialogs::display_error("Error switching the profile", Some(Box::new(e)))
+            }
+        }
     }
 
     fn init_menu_popover(&mut self) {
@@ -90,6 +191,50 @@ impl Widget for WinTitleBar {
         );
         vbox.add(&import_btn);
 
+        let trash_btn = gtk::ModelButtonBuilder::new().label("Trash").build();
+        left_align_menu(&trash_btn);
+        relm::connect!(
+            self.model.relm,
+            &trash_btn,
+            connect_clicked(_),
+            Msg::DisplayTrash
+        );
+        vbox.add(&trash_btn);
+
+        let password_audit_btn = gtk::ModelButtonBuilder::new()
+            .label("Overdue passwords")
+            .build();
+        left_align_menu(&password_audit_btn);
+        relm::connect!(
+            self.model.relm,
+            &password_audit_btn,
+            connect_clicked(_),
+            Msg::DisplayPasswordAudit
+        );
+        vbox.add(&password_audit_btn);
+
+        let database_health_btn = gtk::ModelButtonBuilder::new()
+            .label("Database health")
+            .build();
+        left_align_menu(&database_health_btn);
+        relm::connect!(
+            self.model.relm,
+            &database_health_btn,
+            connect_clicked(_),
+            Msg::DisplayDatabaseHealth
+        );
+        vbox.add(&database_health_btn);
+
+        let sync_btn = gtk::ModelButtonBuilder::new().label("Sync").build();
+        left_align_menu(&sync_btn);
+        relm::connect!(
+            self.model.relm,
+            &sync_btn,
+            connect_clicked(_),
+            Msg::DisplaySync
+        );
+        vbox.add(&sync_btn);
+
         let shortcuts_btn = gtk::ModelButtonBuilder::new()
             .label("Keyboard Shortcuts")
             .build();
@@ -142,6 +287,10 @@ impl Widget for WinTitleBar {
             menu_popover: gtk::Popover::new(None::<&gtk::MenuButton>),
             prefs_win: None,
             import_win: None,
+            trash_win: None,
+            password_audit_win: None,
+            database_health_win: None,
+            sync_win: None,
         }
     }
 
@@ -149,6 +298,10 @@ impl Widget for WinTitleBar {
         match event {
             Msg::DisplayPreferences => self.display_preferences(),
             Msg::DisplayImport => self.display_import(),
+            Msg::DisplayTrash => self.display_trash(),
+            Msg::DisplayPasswordAudit => self.display_password_audit(),
+            Msg::DisplayDatabaseHealth => self.display_database_health(),
+            Msg::DisplaySync => self.display_sync(),
             Msg::DisplayShortcuts => self.display_shortcuts(),
             Msg::DisplayAbout => Self::display_about(),
             Msg::SearchClicked => {
@@ -209,6 +362,14 @@ impl Widget for WinTitleBar {
             Msg::ConfigUpdated(_) => {}
             Msg::DarkThemeToggled => {}
             Msg::ImportApplied => {}
+            Msg::TrashChanged => {}
+            Msg::ProfileSelected(Some(id)) if id == NEW_PROFILE_ID => self.ask_new_profile_name(),
+            Msg::ProfileSelected(Some(profile)) => {
+                if profile != projectpadsql::profile::current() {
+                    Self::switch_profile(&profile);
+                }
+            }
+            Msg::ProfileSelected(None) => {}
         }
     }
 
@@ -252,6 +413,87 @@ impl Widget for WinTitleBar {
         import_win.widget().show();
     }
 
+    fn display_trash(&mut self) {
+        let main_win = standard_dialogs::get_main_window(
+            self.widgets.header_bar.clone().upcast::<gtk::Widget>(),
+        );
+        self.model.trash_win = Some(
+            init::<TrashDialog>(self.model.db_sender.clone())
+                .expect("error initializing the trash dialog"),
+        );
+        let trash_win = self.model.trash_win.as_ref().unwrap();
+        relm::connect!(trash_win@TrashMsg::TrashChanged,
+                               self.model.relm, Msg::TrashChanged);
+        trash_win.widget().set_transient_for(Some(&main_win));
+        trash_win
+            .widget()
+            .set_position(gtk::WindowPosition::CenterOnParent);
+        trash_win.widget().set_modal(true);
+        trash_win.widget().show();
+    }
+
+    fn display_password_audit(&mut self) {
+        let main_win = standard_dialogs::get_main_window(
+            self.widgets.header_bar.clone().upcast::<gtk::Widget>(),
+        );
+        self.model.password_audit_win = Some(
+            init::<PasswordAuditDialog>(self.model.db_sender.clone())
+                .expect("error initializing the password audit dialog"),
+        );
+        let password_audit_win = self.model.password_audit_win.as_ref().unwrap();
+        password_audit_win
+            .widget()
+            .set_transient_for(Some(&main_win));
+        password_audit_win
+            .widget()
+            .set_position(gtk::WindowPosition::CenterOnParent);
+        password_audit_win.widget().set_modal(true);
+        password_audit_win.widget().show();
+    }
+
+    fn display_database_health(&mut self) {
+        let main_win = standard_dialogs::get_main_window(
+            self.widgets.header_bar.clone().upcast::<gtk::Widget>(),
+        );
+        self.model.database_health_win = Some(
+            init::<DatabaseHealthDialog>(self.model.db_sender.clone())
+                .expect("error initializing the database health dialog"),
+        );
+        let database_health_win = self.model.database_health_win.as_ref().unwrap();
+        // the fixes move items to the trash or change them, like
+        // restoring from the trash: the lists must be reloaded
+        relm::connect!(database_health_win@DatabaseHealthMsg::IssuesFixed,
+                               self.model.relm, Msg::TrashChanged);
+        database_health_win
+            .widget()
+            .set_transient_for(Some(&main_win));
+        database_health_win
+            .widget()
+            .set_position(gtk::WindowPosition::CenterOnParent);
+        database_health_win.widget().set_modal(true);
+        database_health_win.widget().show();
+    }
+
+    fn display_sync(&mut self) {
+        let main_win = standard_dialogs::get_main_window(
+            self.widgets.header_bar.clone().upcast::<gtk::Widget>(),
+        );
+        self.model.sync_win = Some(
+            init::<SyncDialog>(self.model.db_sender.clone())
+                .expect("error initializing the sync dialog"),
+        );
+        let sync_win = self.model.sync_win.as_ref().unwrap();
+        // the other devices may have added projects, like an import
+        relm::connect!(sync_win@SyncMsg::SyncApplied,
+                               self.model.relm, Msg::ImportApplied);
+        sync_win.widget().set_transient_for(Some(&main_win));
+        sync_win
+            .widget()
+            .set_position(gtk::WindowPosition::CenterOnParent);
+        sync_win.widget().set_modal(true);
+        sync_win.widget().show();
+    }
+
     fn display_about() {
         let dlg = gtk::AboutDialogBuilder::new()
             .name("Projectpad")
@@ -316,6 +558,10 @@ impl Widget for WinTitleBar {
         gtk::HeaderBar {
             show_close_button: true,
             title: Some("Projectpad"),
+            #[name="profile_combo"]
+            gtk::ComboBoxText {
+                tooltip_text: Some("Database profile"),
+            },
             #[name="menu_button"]
             gtk::MenuButton {
                 image: Some(&gtk::Image::from_icon_name(Some("open-menu-symbolic"), gtk::IconSize::Menu)),
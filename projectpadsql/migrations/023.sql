@@ -0,0 +1,179 @@
+-- full-text search over the descriptions and contents of all the entities.
+-- entity_type is the name of the table of the entity, entity_id its id.
+-- the index is maintained by the triggers below.
+CREATE VIRTUAL TABLE search_index USING fts5(
+       entity_type UNINDEXED,
+       entity_id UNINDEXED,
+       title,
+       body,
+       tokenize = 'unicode61');
+
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'project', id, name, '' FROM project;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'server', id, desc, ip || ' ' || text FROM server;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'server_point_of_interest', id, desc, path || ' ' || text FROM server_point_of_interest;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'project_point_of_interest', id, desc, path || ' ' || text FROM project_point_of_interest;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'project_note', id, title, contents FROM project_note;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'server_note', id, title, contents FROM server_note;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'server_database', id, desc, name || ' ' || text FROM server_database;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'server_website', id, desc, url || ' ' || text FROM server_website;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'server_link', id, desc, '' FROM server_link;
+INSERT INTO search_index (entity_type, entity_id, title, body)
+       SELECT 'server_extra_user_account', id, desc, username FROM server_extra_user_account;
+
+CREATE TRIGGER search_index_project_insert AFTER INSERT ON project BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project', NEW.id, NEW.name, '');
+END;
+CREATE TRIGGER search_index_project_update AFTER UPDATE ON project BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project', NEW.id, NEW.name, '');
+END;
+CREATE TRIGGER search_index_project_delete AFTER DELETE ON project BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_server_insert AFTER INSERT ON server BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server', NEW.id, NEW.desc, NEW.ip || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_update AFTER UPDATE ON server BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server', NEW.id, NEW.desc, NEW.ip || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_delete AFTER DELETE ON server BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_server_point_of_interest_insert AFTER INSERT ON server_point_of_interest BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_point_of_interest', NEW.id, NEW.desc, NEW.path || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_point_of_interest_update AFTER UPDATE ON server_point_of_interest BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_point_of_interest' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_point_of_interest', NEW.id, NEW.desc, NEW.path || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_point_of_interest_delete AFTER DELETE ON server_point_of_interest BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_point_of_interest' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_project_point_of_interest_insert AFTER INSERT ON project_point_of_interest BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project_point_of_interest', NEW.id, NEW.desc, NEW.path || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_project_point_of_interest_update AFTER UPDATE ON project_point_of_interest BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_point_of_interest' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project_point_of_interest', NEW.id, NEW.desc, NEW.path || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_project_point_of_interest_delete AFTER DELETE ON project_point_of_interest BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_point_of_interest' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_project_note_insert AFTER INSERT ON project_note BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project_note', NEW.id, NEW.title, NEW.contents);
+END;
+CREATE TRIGGER search_index_project_note_update AFTER UPDATE ON project_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_note' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project_note', NEW.id, NEW.title, NEW.contents);
+END;
+CREATE TRIGGER search_index_project_note_delete AFTER DELETE ON project_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_note' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_server_note_insert AFTER INSERT ON server_note BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_note', NEW.id, NEW.title, NEW.contents);
+END;
+CREATE TRIGGER search_index_server_note_update AFTER UPDATE ON server_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_note' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_note', NEW.id, NEW.title, NEW.contents);
+END;
+CREATE TRIGGER search_index_server_note_delete AFTER DELETE ON server_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_note' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_server_database_insert AFTER INSERT ON server_database BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_database', NEW.id, NEW.desc, NEW.name || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_database_update AFTER UPDATE ON server_database BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_database' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_database', NEW.id, NEW.desc, NEW.name || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_database_delete AFTER DELETE ON server_database BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_database' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_server_website_insert AFTER INSERT ON server_website BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_website', NEW.id, NEW.desc, NEW.url || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_website_update AFTER UPDATE ON server_website BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_website' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_website', NEW.id, NEW.desc, NEW.url || ' ' || NEW.text);
+END;
+CREATE TRIGGER search_index_server_website_delete AFTER DELETE ON server_website BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_website' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_server_link_insert AFTER INSERT ON server_link BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_link', NEW.id, NEW.desc, '');
+END;
+CREATE TRIGGER search_index_server_link_update AFTER UPDATE ON server_link BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_link' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_link', NEW.id, NEW.desc, '');
+END;
+CREATE TRIGGER search_index_server_link_delete AFTER DELETE ON server_link BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_link' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER search_index_server_extra_user_account_insert AFTER INSERT ON server_extra_user_account BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_extra_user_account', NEW.id, NEW.desc, NEW.username);
+END;
+CREATE TRIGGER search_index_server_extra_user_account_update AFTER UPDATE ON server_extra_user_account BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_extra_user_account' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('server_extra_user_account', NEW.id, NEW.desc, NEW.username);
+END;
+CREATE TRIGGER search_index_server_extra_user_account_delete AFTER DELETE ON server_extra_user_account BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_extra_user_account' AND entity_id = OLD.id;
+END;
+
+-- the project and server each indexed entity belongs to.
+-- server_id is null for entities which are not under a server.
+CREATE VIEW search_entity_parent AS
+       SELECT 'project' AS entity_type, id AS entity_id, id AS project_id, NULL AS server_id FROM project
+       UNION ALL SELECT 'server', id, project_id, id FROM server
+       UNION ALL SELECT 'project_point_of_interest', id, project_id, NULL FROM project_point_of_interest
+       UNION ALL SELECT 'project_note', id, project_id, NULL FROM project_note
+       UNION ALL SELECT 'server_link', id, project_id, NULL FROM server_link
+       UNION ALL SELECT 'server_point_of_interest', i.id, s.project_id, s.id
+                 FROM server_point_of_interest i JOIN server s ON s.id = i.server_id
+       UNION ALL SELECT 'server_note', i.id, s.project_id, s.id
+                 FROM server_note i JOIN server s ON s.id = i.server_id
+       UNION ALL SELECT 'server_database', i.id, s.project_id, s.id
+                 FROM server_database i JOIN server s ON s.id = i.server_id
+       UNION ALL SELECT 'server_website', i.id, s.project_id, s.id
+                 FROM server_website i JOIN server s ON s.id = i.server_id
+       UNION ALL SELECT 'server_extra_user_account', i.id, s.project_id, s.id
+                 FROM server_extra_user_account i JOIN server s ON s.id = i.server_id;
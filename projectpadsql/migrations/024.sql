@@ -0,0 +1,568 @@
+-- history of the changes to all the entities, so that deletes and edits
+-- can be undone. entity_type is the name of the table of the entity.
+-- old_row and new_row are the row before and after the change, as JSON
+-- objects with the table columns as keys. Blobs are stored hex-encoded.
+-- a delete and the deletes that it cascades to share the same changed_at.
+-- the table is maintained by the triggers below.
+CREATE TABLE change_log (
+       id INTEGER PRIMARY KEY,
+       changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+       operation TEXT NOT NULL CHECK (operation IN ('insert', 'update', 'delete')),
+       entity_type TEXT NOT NULL,
+       entity_id INTEGER NOT NULL,
+       old_row TEXT,
+       new_row TEXT,
+       -- set when the change was undone
+       undone_at TEXT
+);
+CREATE INDEX change_log_changed_at ON change_log (changed_at);
+
+CREATE TRIGGER change_log_project_insert AFTER INSERT ON project BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project', NEW.id,
+                      json_object('id', NEW.id,
+                                  'name', NEW.name,
+                                  'icon', CASE WHEN NEW.icon IS NULL THEN NULL ELSE hex(NEW.icon) END,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod));
+END;
+CREATE TRIGGER change_log_project_update AFTER UPDATE ON project
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.name IS NOT NEW.name
+            OR OLD.icon IS NOT NEW.icon
+            OR OLD.has_dev IS NOT NEW.has_dev
+            OR OLD.has_uat IS NOT NEW.has_uat
+            OR OLD.has_stage IS NOT NEW.has_stage
+            OR OLD.has_prod IS NOT NEW.has_prod BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project', NEW.id,
+                      json_object('id', OLD.id,
+                                  'name', OLD.name,
+                                  'icon', CASE WHEN OLD.icon IS NULL THEN NULL ELSE hex(OLD.icon) END,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod),
+                      json_object('id', NEW.id,
+                                  'name', NEW.name,
+                                  'icon', CASE WHEN NEW.icon IS NULL THEN NULL ELSE hex(NEW.icon) END,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod));
+END;
+CREATE TRIGGER change_log_project_delete AFTER DELETE ON project BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project', OLD.id,
+                      json_object('id', OLD.id,
+                                  'name', OLD.name,
+                                  'icon', CASE WHEN OLD.icon IS NULL THEN NULL ELSE hex(OLD.icon) END,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod));
+END;
+
+CREATE TRIGGER change_log_server_insert AFTER INSERT ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_server_update AFTER UPDATE ON server
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.ip IS NOT NEW.ip
+            OR OLD.text IS NOT NEW.text
+            OR OLD.is_retired IS NOT NEW.is_retired
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.auth_key IS NOT NEW.auth_key
+            OR OLD.auth_key_filename IS NOT NEW.auth_key_filename
+            OR OLD.type IS NOT NEW.type
+            OR OLD.access_type IS NOT NEW.access_type
+            OR OLD.ssh_tunnel_port IS NOT NEW.ssh_tunnel_port
+            OR OLD.ssh_tunnel_through_server_id IS NOT NEW.ssh_tunnel_through_server_id
+            OR OLD.environment IS NOT NEW.environment
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_server_delete AFTER DELETE ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id));
+END;
+
+CREATE TRIGGER change_log_server_point_of_interest_insert AFTER INSERT ON server_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_point_of_interest', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'run_on', NEW.run_on,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_point_of_interest_update AFTER UPDATE ON server_point_of_interest
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.path IS NOT NEW.path
+            OR OLD.text IS NOT NEW.text
+            OR OLD.interest_type IS NOT NEW.interest_type
+            OR OLD.run_on IS NOT NEW.run_on
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_point_of_interest', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'run_on', OLD.run_on,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'run_on', NEW.run_on,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_point_of_interest_delete AFTER DELETE ON server_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_point_of_interest', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'run_on', OLD.run_on,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id));
+END;
+
+CREATE TRIGGER change_log_project_point_of_interest_insert AFTER INSERT ON project_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project_point_of_interest', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_project_point_of_interest_update AFTER UPDATE ON project_point_of_interest
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.path IS NOT NEW.path
+            OR OLD.text IS NOT NEW.text
+            OR OLD.interest_type IS NOT NEW.interest_type
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project_point_of_interest', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_project_point_of_interest_delete AFTER DELETE ON project_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project_point_of_interest', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id));
+END;
+
+CREATE TRIGGER change_log_project_note_insert AFTER INSERT ON project_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project_note', NEW.id,
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_project_note_update AFTER UPDATE ON project_note
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.title IS NOT NEW.title
+            OR OLD.contents IS NOT NEW.contents
+            OR OLD.has_dev IS NOT NEW.has_dev
+            OR OLD.has_uat IS NOT NEW.has_uat
+            OR OLD.has_stage IS NOT NEW.has_stage
+            OR OLD.has_prod IS NOT NEW.has_prod
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project_note', NEW.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id),
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_project_note_delete AFTER DELETE ON project_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project_note', OLD.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id));
+END;
+
+CREATE TRIGGER change_log_server_note_insert AFTER INSERT ON server_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_note', NEW.id,
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_note_update AFTER UPDATE ON server_note
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.title IS NOT NEW.title
+            OR OLD.contents IS NOT NEW.contents
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_note', NEW.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id),
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_note_delete AFTER DELETE ON server_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_note', OLD.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id));
+END;
+
+CREATE TRIGGER change_log_server_database_insert AFTER INSERT ON server_database BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_database', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'name', NEW.name,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_database_update AFTER UPDATE ON server_database
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.name IS NOT NEW.name
+            OR OLD.text IS NOT NEW.text
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_database', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'name', OLD.name,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'name', NEW.name,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_database_delete AFTER DELETE ON server_database BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_database', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'name', OLD.name,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id));
+END;
+
+CREATE TRIGGER change_log_server_website_insert AFTER INSERT ON server_website BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_website', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'url', NEW.url,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'server_database_id', NEW.server_database_id,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_website_update AFTER UPDATE ON server_website
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.url IS NOT NEW.url
+            OR OLD.text IS NOT NEW.text
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.server_database_id IS NOT NEW.server_database_id
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_website', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'url', OLD.url,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'server_database_id', OLD.server_database_id,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'url', NEW.url,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'server_database_id', NEW.server_database_id,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_website_delete AFTER DELETE ON server_website BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_website', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'url', OLD.url,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'server_database_id', OLD.server_database_id,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id));
+END;
+
+CREATE TRIGGER change_log_server_link_insert AFTER INSERT ON server_link BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_link', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'linked_server_id', NEW.linked_server_id,
+                                  'linked_group_name', NEW.linked_group_name,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_server_link_update AFTER UPDATE ON server_link
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.linked_server_id IS NOT NEW.linked_server_id
+            OR OLD.linked_group_name IS NOT NEW.linked_group_name
+            OR OLD.environment IS NOT NEW.environment
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_link', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'linked_server_id', OLD.linked_server_id,
+                                  'linked_group_name', OLD.linked_group_name,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'linked_server_id', NEW.linked_server_id,
+                                  'linked_group_name', NEW.linked_group_name,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id));
+END;
+CREATE TRIGGER change_log_server_link_delete AFTER DELETE ON server_link BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_link', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'linked_server_id', OLD.linked_server_id,
+                                  'linked_group_name', OLD.linked_group_name,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id));
+END;
+
+CREATE TRIGGER change_log_server_extra_user_account_insert AFTER INSERT ON server_extra_user_account BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_extra_user_account', NEW.id,
+                      json_object('id', NEW.id,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'desc', NEW.desc,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_extra_user_account_update AFTER UPDATE ON server_extra_user_account
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.auth_key IS NOT NEW.auth_key
+            OR OLD.auth_key_filename IS NOT NEW.auth_key_filename
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_extra_user_account', NEW.id,
+                      json_object('id', OLD.id,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'desc', OLD.desc,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id),
+                      json_object('id', NEW.id,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'desc', NEW.desc,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id));
+END;
+CREATE TRIGGER change_log_server_extra_user_account_delete AFTER DELETE ON server_extra_user_account BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_extra_user_account', OLD.id,
+                      json_object('id', OLD.id,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'desc', OLD.desc,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id));
+END;
@@ -0,0 +1,788 @@
+-- soft delete: the entities with a deleted_at are in the trash.
+-- moving an entity to the trash or restoring it also moves its children,
+-- like the ON DELETE CASCADE of the foreign keys. Children that were
+-- moved to the trash earlier on their own keep their own deleted_at.
+
+ALTER TABLE project ADD COLUMN deleted_at TEXT;
+ALTER TABLE server ADD COLUMN deleted_at TEXT;
+ALTER TABLE server_point_of_interest ADD COLUMN deleted_at TEXT;
+ALTER TABLE project_point_of_interest ADD COLUMN deleted_at TEXT;
+ALTER TABLE project_note ADD COLUMN deleted_at TEXT;
+ALTER TABLE server_note ADD COLUMN deleted_at TEXT;
+ALTER TABLE server_database ADD COLUMN deleted_at TEXT;
+ALTER TABLE server_website ADD COLUMN deleted_at TEXT;
+ALTER TABLE server_link ADD COLUMN deleted_at TEXT;
+ALTER TABLE server_extra_user_account ADD COLUMN deleted_at TEXT;
+
+CREATE TRIGGER trash_project_children AFTER UPDATE OF deleted_at ON project BEGIN
+       UPDATE server SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE project_point_of_interest SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE project_note SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE server_link SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+END;
+
+CREATE TRIGGER trash_server_children AFTER UPDATE OF deleted_at ON server BEGIN
+       UPDATE server_point_of_interest SET deleted_at = NEW.deleted_at
+              WHERE server_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE server_website SET deleted_at = NEW.deleted_at
+              WHERE server_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE server_database SET deleted_at = NEW.deleted_at
+              WHERE server_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE server_extra_user_account SET deleted_at = NEW.deleted_at
+              WHERE server_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE server_note SET deleted_at = NEW.deleted_at
+              WHERE server_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE server_link SET deleted_at = NEW.deleted_at
+              WHERE linked_server_id = NEW.id AND deleted_at IS OLD.deleted_at;
+END;
+
+-- the top-level entities in the trash: the entities in the trash whose
+-- parents are not in the trash. Restoring or purging them also restores
+-- or purges their children.
+CREATE VIEW trash (entity_type, entity_id, title, project_id, deleted_at) AS
+       SELECT 'project', id, name, id, deleted_at FROM project
+              WHERE deleted_at IS NOT NULL
+       UNION ALL
+       SELECT 'server', t.id, t.desc, t.project_id, t.deleted_at FROM server t
+              JOIN project p ON p.id = t.project_id
+              WHERE t.deleted_at IS NOT NULL AND p.deleted_at IS NULL
+       UNION ALL
+       SELECT 'server_point_of_interest', t.id, t.desc, s.project_id, t.deleted_at FROM server_point_of_interest t
+              JOIN server s ON s.id = t.server_id
+              WHERE t.deleted_at IS NOT NULL AND s.deleted_at IS NULL
+       UNION ALL
+       SELECT 'project_point_of_interest', t.id, t.desc, t.project_id, t.deleted_at FROM project_point_of_interest t
+              JOIN project p ON p.id = t.project_id
+              WHERE t.deleted_at IS NOT NULL AND p.deleted_at IS NULL
+       UNION ALL
+       SELECT 'project_note', t.id, t.title, t.project_id, t.deleted_at FROM project_note t
+              JOIN project p ON p.id = t.project_id
+              WHERE t.deleted_at IS NOT NULL AND p.deleted_at IS NULL
+       UNION ALL
+       SELECT 'server_note', t.id, t.title, s.project_id, t.deleted_at FROM server_note t
+              JOIN server s ON s.id = t.server_id
+              WHERE t.deleted_at IS NOT NULL AND s.deleted_at IS NULL
+       UNION ALL
+       SELECT 'server_database', t.id, t.desc, s.project_id, t.deleted_at FROM server_database t
+              JOIN server s ON s.id = t.server_id
+              WHERE t.deleted_at IS NOT NULL AND s.deleted_at IS NULL
+       UNION ALL
+       SELECT 'server_website', t.id, t.desc, s.project_id, t.deleted_at FROM server_website t
+              JOIN server s ON s.id = t.server_id
+              WHERE t.deleted_at IS NOT NULL AND s.deleted_at IS NULL
+       UNION ALL
+       SELECT 'server_link', t.id, t.desc, t.project_id, t.deleted_at FROM server_link t
+              JOIN project p ON p.id = t.project_id
+              JOIN server s ON s.id = t.linked_server_id
+              WHERE t.deleted_at IS NOT NULL AND p.deleted_at IS NULL AND s.deleted_at IS NULL
+       UNION ALL
+       SELECT 'server_extra_user_account', t.id, t.desc, s.project_id, t.deleted_at FROM server_extra_user_account t
+              JOIN server s ON s.id = t.server_id
+              WHERE t.deleted_at IS NOT NULL AND s.deleted_at IS NULL;
+
+-- the entities in the trash are not in the search index
+DROP TRIGGER search_index_project_update;
+CREATE TRIGGER search_index_project_update AFTER UPDATE ON project BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'project', NEW.id, NEW.name, ''
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_update;
+CREATE TRIGGER search_index_server_update AFTER UPDATE ON server BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server', NEW.id, NEW.desc, NEW.ip || ' ' || NEW.text
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_point_of_interest_update;
+CREATE TRIGGER search_index_server_point_of_interest_update AFTER UPDATE ON server_point_of_interest BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_point_of_interest' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_point_of_interest', NEW.id, NEW.desc, NEW.path || ' ' || NEW.text
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_project_point_of_interest_update;
+CREATE TRIGGER search_index_project_point_of_interest_update AFTER UPDATE ON project_point_of_interest BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_point_of_interest' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'project_point_of_interest', NEW.id, NEW.desc, NEW.path || ' ' || NEW.text
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_project_note_update;
+CREATE TRIGGER search_index_project_note_update AFTER UPDATE ON project_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_note' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'project_note', NEW.id, NEW.title, NEW.contents
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_note_update;
+CREATE TRIGGER search_index_server_note_update AFTER UPDATE ON server_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_note' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_note', NEW.id, NEW.title, NEW.contents
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_database_update;
+CREATE TRIGGER search_index_server_database_update AFTER UPDATE ON server_database BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_database' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_database', NEW.id, NEW.desc, NEW.name || ' ' || NEW.text
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_website_update;
+CREATE TRIGGER search_index_server_website_update AFTER UPDATE ON server_website BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_website' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_website', NEW.id, NEW.desc, NEW.url || ' ' || NEW.text
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_link_update;
+CREATE TRIGGER search_index_server_link_update AFTER UPDATE ON server_link BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_link' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_link', NEW.id, NEW.desc, ''
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_extra_user_account_update;
+CREATE TRIGGER search_index_server_extra_user_account_update AFTER UPDATE ON server_extra_user_account BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_extra_user_account' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_extra_user_account', NEW.id, NEW.desc, NEW.username
+              WHERE NEW.deleted_at IS NULL;
+END;
+
+-- the change log now also records deleted_at
+DROP TRIGGER change_log_project_insert;
+DROP TRIGGER change_log_project_update;
+DROP TRIGGER change_log_project_delete;
+CREATE TRIGGER change_log_project_insert AFTER INSERT ON project BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project', NEW.id,
+                      json_object('id', NEW.id,
+                                  'name', NEW.name,
+                                  'icon', CASE WHEN NEW.icon IS NULL THEN NULL ELSE hex(NEW.icon) END,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_update AFTER UPDATE ON project
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.name IS NOT NEW.name
+            OR OLD.icon IS NOT NEW.icon
+            OR OLD.has_dev IS NOT NEW.has_dev
+            OR OLD.has_uat IS NOT NEW.has_uat
+            OR OLD.has_stage IS NOT NEW.has_stage
+            OR OLD.has_prod IS NOT NEW.has_prod
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project', NEW.id,
+                      json_object('id', OLD.id,
+                                  'name', OLD.name,
+                                  'icon', CASE WHEN OLD.icon IS NULL THEN NULL ELSE hex(OLD.icon) END,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'name', NEW.name,
+                                  'icon', CASE WHEN NEW.icon IS NULL THEN NULL ELSE hex(NEW.icon) END,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_delete AFTER DELETE ON project BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project', OLD.id,
+                      json_object('id', OLD.id,
+                                  'name', OLD.name,
+                                  'icon', CASE WHEN OLD.icon IS NULL THEN NULL ELSE hex(OLD.icon) END,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_insert;
+DROP TRIGGER change_log_server_update;
+DROP TRIGGER change_log_server_delete;
+CREATE TRIGGER change_log_server_insert AFTER INSERT ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_update AFTER UPDATE ON server
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.ip IS NOT NEW.ip
+            OR OLD.text IS NOT NEW.text
+            OR OLD.is_retired IS NOT NEW.is_retired
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.auth_key IS NOT NEW.auth_key
+            OR OLD.auth_key_filename IS NOT NEW.auth_key_filename
+            OR OLD.type IS NOT NEW.type
+            OR OLD.access_type IS NOT NEW.access_type
+            OR OLD.ssh_tunnel_port IS NOT NEW.ssh_tunnel_port
+            OR OLD.ssh_tunnel_through_server_id IS NOT NEW.ssh_tunnel_through_server_id
+            OR OLD.environment IS NOT NEW.environment
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_delete AFTER DELETE ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_point_of_interest_insert;
+DROP TRIGGER change_log_server_point_of_interest_update;
+DROP TRIGGER change_log_server_point_of_interest_delete;
+CREATE TRIGGER change_log_server_point_of_interest_insert AFTER INSERT ON server_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_point_of_interest', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'run_on', NEW.run_on,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_point_of_interest_update AFTER UPDATE ON server_point_of_interest
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.path IS NOT NEW.path
+            OR OLD.text IS NOT NEW.text
+            OR OLD.interest_type IS NOT NEW.interest_type
+            OR OLD.run_on IS NOT NEW.run_on
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_point_of_interest', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'run_on', OLD.run_on,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'run_on', NEW.run_on,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_point_of_interest_delete AFTER DELETE ON server_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_point_of_interest', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'run_on', OLD.run_on,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_project_point_of_interest_insert;
+DROP TRIGGER change_log_project_point_of_interest_update;
+DROP TRIGGER change_log_project_point_of_interest_delete;
+CREATE TRIGGER change_log_project_point_of_interest_insert AFTER INSERT ON project_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project_point_of_interest', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_point_of_interest_update AFTER UPDATE ON project_point_of_interest
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.path IS NOT NEW.path
+            OR OLD.text IS NOT NEW.text
+            OR OLD.interest_type IS NOT NEW.interest_type
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project_point_of_interest', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'interest_type', NEW.interest_type,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_point_of_interest_delete AFTER DELETE ON project_point_of_interest BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project_point_of_interest', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'interest_type', OLD.interest_type,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_project_note_insert;
+DROP TRIGGER change_log_project_note_update;
+DROP TRIGGER change_log_project_note_delete;
+CREATE TRIGGER change_log_project_note_insert AFTER INSERT ON project_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project_note', NEW.id,
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_note_update AFTER UPDATE ON project_note
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.title IS NOT NEW.title
+            OR OLD.contents IS NOT NEW.contents
+            OR OLD.has_dev IS NOT NEW.has_dev
+            OR OLD.has_uat IS NOT NEW.has_uat
+            OR OLD.has_stage IS NOT NEW.has_stage
+            OR OLD.has_prod IS NOT NEW.has_prod
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project_note', NEW.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'has_dev', NEW.has_dev,
+                                  'has_uat', NEW.has_uat,
+                                  'has_stage', NEW.has_stage,
+                                  'has_prod', NEW.has_prod,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_note_delete AFTER DELETE ON project_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project_note', OLD.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'has_dev', OLD.has_dev,
+                                  'has_uat', OLD.has_uat,
+                                  'has_stage', OLD.has_stage,
+                                  'has_prod', OLD.has_prod,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_note_insert;
+DROP TRIGGER change_log_server_note_update;
+DROP TRIGGER change_log_server_note_delete;
+CREATE TRIGGER change_log_server_note_insert AFTER INSERT ON server_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_note', NEW.id,
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_note_update AFTER UPDATE ON server_note
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.title IS NOT NEW.title
+            OR OLD.contents IS NOT NEW.contents
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_note', NEW.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_note_delete AFTER DELETE ON server_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_note', OLD.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_database_insert;
+DROP TRIGGER change_log_server_database_update;
+DROP TRIGGER change_log_server_database_delete;
+CREATE TRIGGER change_log_server_database_insert AFTER INSERT ON server_database BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_database', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'name', NEW.name,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_database_update AFTER UPDATE ON server_database
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.name IS NOT NEW.name
+            OR OLD.text IS NOT NEW.text
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_database', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'name', OLD.name,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'name', NEW.name,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_database_delete AFTER DELETE ON server_database BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_database', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'name', OLD.name,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_website_insert;
+DROP TRIGGER change_log_server_website_update;
+DROP TRIGGER change_log_server_website_delete;
+CREATE TRIGGER change_log_server_website_insert AFTER INSERT ON server_website BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_website', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'url', NEW.url,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'server_database_id', NEW.server_database_id,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_website_update AFTER UPDATE ON server_website
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.url IS NOT NEW.url
+            OR OLD.text IS NOT NEW.text
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.server_database_id IS NOT NEW.server_database_id
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_website', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'url', OLD.url,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'server_database_id', OLD.server_database_id,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'url', NEW.url,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'server_database_id', NEW.server_database_id,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_website_delete AFTER DELETE ON server_website BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_website', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'url', OLD.url,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'server_database_id', OLD.server_database_id,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_link_insert;
+DROP TRIGGER change_log_server_link_update;
+DROP TRIGGER change_log_server_link_delete;
+CREATE TRIGGER change_log_server_link_insert AFTER INSERT ON server_link BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_link', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'linked_server_id', NEW.linked_server_id,
+                                  'linked_group_name', NEW.linked_group_name,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_link_update AFTER UPDATE ON server_link
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.linked_server_id IS NOT NEW.linked_server_id
+            OR OLD.linked_group_name IS NOT NEW.linked_group_name
+            OR OLD.environment IS NOT NEW.environment
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_link', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'linked_server_id', OLD.linked_server_id,
+                                  'linked_group_name', OLD.linked_group_name,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'linked_server_id', NEW.linked_server_id,
+                                  'linked_group_name', NEW.linked_group_name,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_link_delete AFTER DELETE ON server_link BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_link', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'linked_server_id', OLD.linked_server_id,
+                                  'linked_group_name', OLD.linked_group_name,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_extra_user_account_insert;
+DROP TRIGGER change_log_server_extra_user_account_update;
+DROP TRIGGER change_log_server_extra_user_account_delete;
+CREATE TRIGGER change_log_server_extra_user_account_insert AFTER INSERT ON server_extra_user_account BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_extra_user_account', NEW.id,
+                      json_object('id', NEW.id,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'desc', NEW.desc,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_extra_user_account_update AFTER UPDATE ON server_extra_user_account
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.auth_key IS NOT NEW.auth_key
+            OR OLD.auth_key_filename IS NOT NEW.auth_key_filename
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_extra_user_account', NEW.id,
+                      json_object('id', OLD.id,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'desc', OLD.desc,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'desc', NEW.desc,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_extra_user_account_delete AFTER DELETE ON server_extra_user_account BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_extra_user_account', OLD.id,
+                      json_object('id', OLD.id,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'desc', OLD.desc,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
@@ -0,0 +1,246 @@
+-- configurable environments: each project has its own environments,
+-- instead of the fixed development, staging, uat and production.
+-- servers and server links refer to an environment of their project
+-- through its name. Renaming an environment renames it in the servers
+-- and links too, see the environment_rename trigger.
+CREATE TABLE environment (
+       id INTEGER PRIMARY KEY,
+       name TEXT NOT NULL CHECK(LENGTH(name) > 0),
+       short_label TEXT NOT NULL CHECK(LENGTH(short_label) > 0),
+       -- #rrggbb
+       color TEXT NOT NULL,
+       ordering INTEGER NOT NULL,
+       -- ask for a confirmation before running commands there
+       dangerous INTEGER NOT NULL DEFAULT 0,
+       project_id INTEGER NOT NULL,
+       UNIQUE(project_id, name),
+       FOREIGN KEY(project_id) REFERENCES project(id) ON DELETE CASCADE);
+
+-- the environments a project note is shown in
+CREATE TABLE project_note_environment (
+       project_note_id INTEGER NOT NULL,
+       environment_id INTEGER NOT NULL,
+       PRIMARY KEY(project_note_id, environment_id),
+       FOREIGN KEY(project_note_id) REFERENCES project_note(id) ON DELETE CASCADE,
+       FOREIGN KEY(environment_id) REFERENCES environment(id) ON DELETE CASCADE);
+
+CREATE TRIGGER environment_rename AFTER UPDATE OF name ON environment BEGIN
+       UPDATE server SET environment = NEW.name
+              WHERE project_id = NEW.project_id AND environment = OLD.name;
+       UPDATE server_link SET environment = NEW.name
+              WHERE project_id = NEW.project_id AND environment = OLD.name;
+END;
+
+-- the fixed environments, as they were stored in the servers and links
+CREATE TEMP TABLE legacy_environment (
+       code TEXT NOT NULL,
+       name TEXT NOT NULL,
+       short_label TEXT NOT NULL,
+       color TEXT NOT NULL,
+       ordering INTEGER NOT NULL,
+       dangerous INTEGER NOT NULL);
+INSERT INTO legacy_environment VALUES
+       ('EnvDevelopment', 'Development', 'DEV', '#46a046', 0, 0),
+       ('EnvStage', 'Staging', 'STG', '#7590ae', 1, 0),
+       ('EnvUat', 'UAT', 'UAT', '#eed680', 2, 0),
+       ('EnvProd', 'Production', 'PRD', '#df421e', 3, 1);
+
+-- a project gets the environments it had enabled, and also the ones which
+-- are used by its servers, links and notes, even if they were disabled
+INSERT INTO environment (name, short_label, color, ordering, dangerous, project_id)
+       SELECT l.name, l.short_label, l.color, l.ordering, l.dangerous, p.id
+         FROM project p, legacy_environment l
+        WHERE (l.code = 'EnvDevelopment' AND p.has_dev)
+           OR (l.code = 'EnvStage' AND p.has_stage)
+           OR (l.code = 'EnvUat' AND p.has_uat)
+           OR (l.code = 'EnvProd' AND p.has_prod)
+           OR EXISTS (SELECT 1 FROM server s
+                       WHERE s.project_id = p.id AND s.environment = l.code)
+           OR EXISTS (SELECT 1 FROM server_link s
+                       WHERE s.project_id = p.id AND s.environment = l.code)
+           OR EXISTS (SELECT 1 FROM project_note n
+                       WHERE n.project_id = p.id
+                         AND ((l.code = 'EnvDevelopment' AND n.has_dev)
+                           OR (l.code = 'EnvStage' AND n.has_stage)
+                           OR (l.code = 'EnvUat' AND n.has_uat)
+                           OR (l.code = 'EnvProd' AND n.has_prod)))
+        ORDER BY p.id, l.ordering;
+
+INSERT INTO project_note_environment (project_note_id, environment_id)
+       SELECT n.id, e.id
+         FROM project_note n
+         JOIN legacy_environment l
+              ON (l.code = 'EnvDevelopment' AND n.has_dev)
+              OR (l.code = 'EnvStage' AND n.has_stage)
+              OR (l.code = 'EnvUat' AND n.has_uat)
+              OR (l.code = 'EnvProd' AND n.has_prod)
+         JOIN environment e ON e.project_id = n.project_id AND e.name = l.name;
+
+-- the servers and links now store the environment name. That's not a
+-- change made by the user: leave it out of the change log.
+CREATE TEMP TABLE migration_change_log_start AS
+       SELECT COALESCE(MAX(id), 0) AS id FROM change_log;
+UPDATE server SET environment =
+       (SELECT name FROM legacy_environment WHERE code = server.environment);
+UPDATE server_link SET environment =
+       (SELECT name FROM legacy_environment WHERE code = server_link.environment);
+DELETE FROM change_log WHERE id > (SELECT id FROM migration_change_log_start);
+DROP TABLE migration_change_log_start;
+-- the rows already in the change log can be restored: store the
+-- environment name there too
+UPDATE change_log SET old_row = json_set(old_row, '$.environment',
+       (SELECT name FROM legacy_environment
+         WHERE code = json_extract(change_log.old_row, '$.environment')))
+ WHERE entity_type IN ('server', 'server_link')
+   AND json_extract(old_row, '$.environment') IN (SELECT code FROM legacy_environment);
+UPDATE change_log SET new_row = json_set(new_row, '$.environment',
+       (SELECT name FROM legacy_environment
+         WHERE code = json_extract(change_log.new_row, '$.environment')))
+ WHERE entity_type IN ('server', 'server_link')
+   AND json_extract(new_row, '$.environment') IN (SELECT code FROM legacy_environment);
+DROP TABLE legacy_environment;
+
+-- drop the has_* columns of project and project_note. The triggers of the
+-- tables are dropped with them, they're re-created below.
+PRAGMA legacy_alter_table = true;
+
+ALTER TABLE project RENAME TO temp_project;
+
+CREATE TABLE project (
+       id INTEGER PRIMARY KEY,
+       name TEXT NOT NULL COLLATE NOCASE,
+       icon BLOB NOT NULL,
+       deleted_at TEXT);
+
+INSERT INTO project SELECT id, name, icon, deleted_at FROM temp_project;
+
+DROP TABLE temp_project;
+
+ALTER TABLE project_note RENAME TO temp_project_note;
+
+CREATE TABLE project_note (
+       id INTEGER PRIMARY KEY,
+       title TEXT NOT NULL,
+       contents TEXT NOT NULL,
+       group_name TEXT CHECK(LENGTH(group_name) > 0),
+       project_id INTEGER NOT NULL,
+       deleted_at TEXT,
+       FOREIGN KEY(project_id) REFERENCES project(id) ON DELETE CASCADE);
+
+INSERT INTO project_note
+       SELECT id, title, contents, group_name, project_id, deleted_at FROM temp_project_note;
+
+DROP TABLE temp_project_note;
+
+PRAGMA legacy_alter_table = false;
+
+CREATE TRIGGER search_index_project_insert AFTER INSERT ON project BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project', NEW.id, NEW.name, '');
+END;
+CREATE TRIGGER search_index_project_update AFTER UPDATE ON project BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'project', NEW.id, NEW.name, ''
+              WHERE NEW.deleted_at IS NULL;
+END;
+CREATE TRIGGER search_index_project_delete AFTER DELETE ON project BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER change_log_project_insert AFTER INSERT ON project BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project', NEW.id,
+                      json_object('id', NEW.id,
+                                  'name', NEW.name,
+                                  'icon', CASE WHEN NEW.icon IS NULL THEN NULL ELSE hex(NEW.icon) END,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_update AFTER UPDATE ON project
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.name IS NOT NEW.name
+            OR OLD.icon IS NOT NEW.icon
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project', NEW.id,
+                      json_object('id', OLD.id,
+                                  'name', OLD.name,
+                                  'icon', CASE WHEN OLD.icon IS NULL THEN NULL ELSE hex(OLD.icon) END,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'name', NEW.name,
+                                  'icon', CASE WHEN NEW.icon IS NULL THEN NULL ELSE hex(NEW.icon) END,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_delete AFTER DELETE ON project BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project', OLD.id,
+                      json_object('id', OLD.id,
+                                  'name', OLD.name,
+                                  'icon', CASE WHEN OLD.icon IS NULL THEN NULL ELSE hex(OLD.icon) END,
+                                  'deleted_at', OLD.deleted_at));
+END;
+CREATE TRIGGER search_index_project_note_insert AFTER INSERT ON project_note BEGIN
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              VALUES ('project_note', NEW.id, NEW.title, NEW.contents);
+END;
+CREATE TRIGGER search_index_project_note_update AFTER UPDATE ON project_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_note' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'project_note', NEW.id, NEW.title, NEW.contents
+              WHERE NEW.deleted_at IS NULL;
+END;
+CREATE TRIGGER search_index_project_note_delete AFTER DELETE ON project_note BEGIN
+       DELETE FROM search_index WHERE entity_type = 'project_note' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER change_log_project_note_insert AFTER INSERT ON project_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'project_note', NEW.id,
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_note_update AFTER UPDATE ON project_note
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.title IS NOT NEW.title
+            OR OLD.contents IS NOT NEW.contents
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'project_note', NEW.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'title', NEW.title,
+                                  'contents', NEW.contents,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_project_note_delete AFTER DELETE ON project_note BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'project_note', OLD.id,
+                      json_object('id', OLD.id,
+                                  'title', OLD.title,
+                                  'contents', OLD.contents,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+CREATE TRIGGER trash_project_children AFTER UPDATE OF deleted_at ON project BEGIN
+       UPDATE server SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE project_point_of_interest SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE project_note SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+       UPDATE server_link SET deleted_at = NEW.deleted_at
+              WHERE project_id = NEW.id AND deleted_at IS OLD.deleted_at;
+END;
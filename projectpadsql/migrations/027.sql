@@ -0,0 +1,46 @@
+-- tags, shared by all the projects. Unlike the group name, an item
+-- can have several tags. The items are servers, POIs, notes, databases
+-- and websites: entity_type is the name of the table of the item,
+-- entity_id its id, as in the search index.
+CREATE TABLE tag (
+       id INTEGER PRIMARY KEY,
+       -- lowercase, without spaces, see Tag::normalize_name
+       name TEXT NOT NULL UNIQUE CHECK(LENGTH(name) > 0));
+
+CREATE TABLE item_tag (
+       tag_id INTEGER NOT NULL,
+       entity_type TEXT NOT NULL,
+       entity_id INTEGER NOT NULL,
+       PRIMARY KEY(tag_id, entity_type, entity_id),
+       FOREIGN KEY(tag_id) REFERENCES tag(id) ON DELETE CASCADE);
+CREATE INDEX item_tag_entity ON item_tag(entity_type, entity_id);
+
+-- the join rows can't have a foreign key to the items, they
+-- go away with the items. Items in the trash keep their tags.
+CREATE TRIGGER item_tag_server_delete AFTER DELETE ON server BEGIN
+       DELETE FROM item_tag WHERE entity_type = 'server' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER item_tag_server_point_of_interest_delete AFTER DELETE ON server_point_of_interest BEGIN
+       DELETE FROM item_tag WHERE entity_type = 'server_point_of_interest' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER item_tag_project_point_of_interest_delete AFTER DELETE ON project_point_of_interest BEGIN
+       DELETE FROM item_tag WHERE entity_type = 'project_point_of_interest' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER item_tag_project_note_delete AFTER DELETE ON project_note BEGIN
+       DELETE FROM item_tag WHERE entity_type = 'project_note' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER item_tag_server_note_delete AFTER DELETE ON server_note BEGIN
+       DELETE FROM item_tag WHERE entity_type = 'server_note' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER item_tag_server_database_delete AFTER DELETE ON server_database BEGIN
+       DELETE FROM item_tag WHERE entity_type = 'server_database' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER item_tag_server_website_delete AFTER DELETE ON server_website BEGIN
+       DELETE FROM item_tag WHERE entity_type = 'server_website' AND entity_id = OLD.id;
+END;
+
+-- a tag exists as long as an item has it
+CREATE TRIGGER tag_delete_unused AFTER DELETE ON item_tag
+       WHEN NOT EXISTS (SELECT 1 FROM item_tag WHERE tag_id = OLD.tag_id) BEGIN
+       DELETE FROM tag WHERE id = OLD.tag_id;
+END;
@@ -0,0 +1,207 @@
+-- the address of the servers was free text: host, host:port, or a URL
+-- for the www servers. Split it in protocol, host, port and path, see
+-- ServerAddress. The ip column keeps the host only.
+ALTER TABLE server ADD COLUMN port INTEGER CHECK(port BETWEEN 1 AND 65535);
+ALTER TABLE server ADD COLUMN protocol TEXT CHECK(LENGTH(protocol) > 0);
+-- what follows the port in a URL, starting with a slash
+ALTER TABLE server ADD COLUMN path TEXT NOT NULL DEFAULT '';
+
+-- parse every address once, those of the servers and those of the
+-- rows in the change log, so that deleted servers can still be restored.
+-- Addresses which can't be parsed stay in the host.
+CREATE TEMP TABLE server_address (
+       address TEXT PRIMARY KEY,
+       protocol TEXT,
+       rest TEXT,
+       host_port TEXT,
+       path TEXT,
+       host TEXT,
+       port_text TEXT);
+INSERT OR IGNORE INTO server_address (address) SELECT ip FROM server;
+INSERT OR IGNORE INTO server_address (address)
+       SELECT json_extract(old_row, '$.ip') FROM change_log
+        WHERE entity_type = 'server' AND json_extract(old_row, '$.ip') IS NOT NULL
+       UNION
+       SELECT json_extract(new_row, '$.ip') FROM change_log
+        WHERE entity_type = 'server' AND json_extract(new_row, '$.ip') IS NOT NULL;
+
+-- https://host:8443/app => protocol https, rest host:8443/app
+UPDATE server_address SET
+       protocol = CASE WHEN instr(trim(address), '://') > 1
+                       THEN substr(trim(address), 1, instr(trim(address), '://') - 1) END,
+       rest = CASE WHEN instr(trim(address), '://') > 1
+                   THEN substr(trim(address), instr(trim(address), '://') + 3)
+                   ELSE trim(address) END;
+-- host:8443/app => host_port host:8443, path /app
+UPDATE server_address SET
+       host_port = CASE WHEN instr(rest, '/') > 0 THEN substr(rest, 1, instr(rest, '/') - 1) ELSE rest END,
+       path = CASE WHEN instr(rest, '/') > 0 THEN substr(rest, instr(rest, '/')) ELSE '' END;
+-- [::1]:2222, host:22, or a host without port. An IPv6 address without
+-- brackets has several colons: it has no port.
+UPDATE server_address SET
+       host = CASE
+              WHEN substr(host_port, 1, 1) = '[' AND instr(host_port, ']') > 0
+                   THEN substr(host_port, 2, instr(host_port, ']') - 2)
+              WHEN length(host_port) - length(replace(host_port, ':', '')) = 1
+                   THEN substr(host_port, 1, instr(host_port, ':') - 1)
+              ELSE host_port END,
+       port_text = CASE
+              WHEN substr(host_port, 1, 1) = '[' AND instr(host_port, ']') > 0
+                   THEN CASE WHEN substr(host_port, instr(host_port, ']') + 1, 1) = ':'
+                             THEN substr(host_port, instr(host_port, ']') + 2)
+                             ELSE substr(host_port, instr(host_port, ']') + 1) END
+              WHEN length(host_port) - length(replace(host_port, ':', '')) = 1
+                   THEN substr(host_port, instr(host_port, ':') + 1)
+              ELSE '' END;
+-- a port which is not a number: keep the address as it was in the host
+UPDATE server_address SET protocol = NULL, host = address, port_text = '', path = ''
+ WHERE (port_text <> ''
+        AND (port_text GLOB '*[^0-9]*' OR CAST(port_text AS INTEGER) NOT BETWEEN 1 AND 65535))
+    OR (host = '' AND (protocol IS NOT NULL OR port_text <> ''));
+
+-- parsing the addresses is not a change made by the user:
+-- leave it out of the change log.
+CREATE TEMP TABLE migration_change_log_start AS
+       SELECT COALESCE(MAX(id), 0) AS id FROM change_log;
+UPDATE server SET
+       protocol = (SELECT protocol FROM server_address WHERE address = server.ip),
+       port = (SELECT CASE WHEN port_text = '' THEN NULL ELSE CAST(port_text AS INTEGER) END
+                 FROM server_address WHERE address = server.ip),
+       path = (SELECT path FROM server_address WHERE address = server.ip),
+       ip = (SELECT host FROM server_address WHERE address = server.ip);
+DELETE FROM change_log WHERE id > (SELECT id FROM migration_change_log_start);
+DROP TABLE migration_change_log_start;
+
+UPDATE change_log SET old_row = (
+       SELECT json_set(change_log.old_row,
+                       '$.ip', a.host,
+                       '$.port', CASE WHEN a.port_text = '' THEN NULL ELSE CAST(a.port_text AS INTEGER) END,
+                       '$.protocol', a.protocol,
+                       '$.path', a.path)
+         FROM server_address a WHERE a.address = json_extract(change_log.old_row, '$.ip'))
+ WHERE entity_type = 'server' AND json_extract(old_row, '$.ip') IS NOT NULL;
+UPDATE change_log SET new_row = (
+       SELECT json_set(change_log.new_row,
+                       '$.ip', a.host,
+                       '$.port', CASE WHEN a.port_text = '' THEN NULL ELSE CAST(a.port_text AS INTEGER) END,
+                       '$.protocol', a.protocol,
+                       '$.path', a.path)
+         FROM server_address a WHERE a.address = json_extract(change_log.new_row, '$.ip'))
+ WHERE entity_type = 'server' AND json_extract(new_row, '$.ip') IS NOT NULL;
+DROP TABLE server_address;
+
+DROP TRIGGER change_log_server_insert;
+DROP TRIGGER change_log_server_update;
+DROP TRIGGER change_log_server_delete;
+CREATE TRIGGER change_log_server_insert AFTER INSERT ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'port', NEW.port,
+                                  'protocol', NEW.protocol,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_update AFTER UPDATE ON server
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.ip IS NOT NEW.ip
+            OR OLD.port IS NOT NEW.port
+            OR OLD.protocol IS NOT NEW.protocol
+            OR OLD.path IS NOT NEW.path
+            OR OLD.text IS NOT NEW.text
+            OR OLD.is_retired IS NOT NEW.is_retired
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.auth_key IS NOT NEW.auth_key
+            OR OLD.auth_key_filename IS NOT NEW.auth_key_filename
+            OR OLD.type IS NOT NEW.type
+            OR OLD.access_type IS NOT NEW.access_type
+            OR OLD.ssh_tunnel_port IS NOT NEW.ssh_tunnel_port
+            OR OLD.ssh_tunnel_through_server_id IS NOT NEW.ssh_tunnel_through_server_id
+            OR OLD.environment IS NOT NEW.environment
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'port', OLD.port,
+                                  'protocol', OLD.protocol,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'port', NEW.port,
+                                  'protocol', NEW.protocol,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_delete AFTER DELETE ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'port', OLD.port,
+                                  'protocol', OLD.protocol,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
@@ -0,0 +1,11 @@
+-- the master passphrase protecting the passwords, on top of the
+-- database password, see secrets.rs. No row means that the passwords
+-- are stored in plain text. The passphrase itself is not stored:
+-- check_value is a known text encrypted with the key derived from it.
+-- Not in the change log: undoing a change there would make the
+-- passwords unreadable.
+CREATE TABLE secret_settings (
+       id INTEGER PRIMARY KEY CHECK(id = 1),
+       salt BLOB NOT NULL,
+       iterations INTEGER NOT NULL CHECK(iterations > 0),
+       check_value TEXT NOT NULL);
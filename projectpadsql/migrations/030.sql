@@ -0,0 +1,264 @@
+-- an optional TOTP secret, to generate the 2FA codes: an otpauth:// URI,
+-- or only the base32 secret. See totp::Totp. Like the passwords, it's
+-- encrypted if the master passphrase is enabled.
+ALTER TABLE server ADD COLUMN otp_secret TEXT NOT NULL DEFAULT '';
+ALTER TABLE server_website ADD COLUMN otp_secret TEXT NOT NULL DEFAULT '';
+ALTER TABLE server_extra_user_account ADD COLUMN otp_secret TEXT NOT NULL DEFAULT '';
+
+DROP TRIGGER change_log_server_insert;
+DROP TRIGGER change_log_server_update;
+DROP TRIGGER change_log_server_delete;
+CREATE TRIGGER change_log_server_insert AFTER INSERT ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'port', NEW.port,
+                                  'protocol', NEW.protocol,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'otp_secret', NEW.otp_secret,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_update AFTER UPDATE ON server
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.ip IS NOT NEW.ip
+            OR OLD.port IS NOT NEW.port
+            OR OLD.protocol IS NOT NEW.protocol
+            OR OLD.path IS NOT NEW.path
+            OR OLD.text IS NOT NEW.text
+            OR OLD.is_retired IS NOT NEW.is_retired
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.otp_secret IS NOT NEW.otp_secret
+            OR OLD.auth_key IS NOT NEW.auth_key
+            OR OLD.auth_key_filename IS NOT NEW.auth_key_filename
+            OR OLD.type IS NOT NEW.type
+            OR OLD.access_type IS NOT NEW.access_type
+            OR OLD.ssh_tunnel_port IS NOT NEW.ssh_tunnel_port
+            OR OLD.ssh_tunnel_through_server_id IS NOT NEW.ssh_tunnel_through_server_id
+            OR OLD.environment IS NOT NEW.environment
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.project_id IS NOT NEW.project_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'port', OLD.port,
+                                  'protocol', OLD.protocol,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'otp_secret', OLD.otp_secret,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'ip', NEW.ip,
+                                  'port', NEW.port,
+                                  'protocol', NEW.protocol,
+                                  'path', NEW.path,
+                                  'text', NEW.text,
+                                  'is_retired', NEW.is_retired,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'otp_secret', NEW.otp_secret,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'type', NEW.type,
+                                  'access_type', NEW.access_type,
+                                  'ssh_tunnel_port', NEW.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', NEW.ssh_tunnel_through_server_id,
+                                  'environment', NEW.environment,
+                                  'group_name', NEW.group_name,
+                                  'project_id', NEW.project_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_delete AFTER DELETE ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'port', OLD.port,
+                                  'protocol', OLD.protocol,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'otp_secret', OLD.otp_secret,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_website_insert;
+DROP TRIGGER change_log_server_website_update;
+DROP TRIGGER change_log_server_website_delete;
+CREATE TRIGGER change_log_server_website_insert AFTER INSERT ON server_website BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_website', NEW.id,
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'url', NEW.url,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'otp_secret', NEW.otp_secret,
+                                  'server_database_id', NEW.server_database_id,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_website_update AFTER UPDATE ON server_website
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.url IS NOT NEW.url
+            OR OLD.text IS NOT NEW.text
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.otp_secret IS NOT NEW.otp_secret
+            OR OLD.server_database_id IS NOT NEW.server_database_id
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_website', NEW.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'url', OLD.url,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'otp_secret', OLD.otp_secret,
+                                  'server_database_id', OLD.server_database_id,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'desc', NEW.desc,
+                                  'url', NEW.url,
+                                  'text', NEW.text,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'otp_secret', NEW.otp_secret,
+                                  'server_database_id', NEW.server_database_id,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_website_delete AFTER DELETE ON server_website BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_website', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'url', OLD.url,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'otp_secret', OLD.otp_secret,
+                                  'server_database_id', OLD.server_database_id,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+DROP TRIGGER change_log_server_extra_user_account_insert;
+DROP TRIGGER change_log_server_extra_user_account_update;
+DROP TRIGGER change_log_server_extra_user_account_delete;
+CREATE TRIGGER change_log_server_extra_user_account_insert AFTER INSERT ON server_extra_user_account BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, new_row)
+              VALUES ('insert', 'server_extra_user_account', NEW.id,
+                      json_object('id', NEW.id,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'otp_secret', NEW.otp_secret,
+                                  'desc', NEW.desc,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_extra_user_account_update AFTER UPDATE ON server_extra_user_account
+       WHEN OLD.id IS NOT NEW.id
+            OR OLD.username IS NOT NEW.username
+            OR OLD.password IS NOT NEW.password
+            OR OLD.otp_secret IS NOT NEW.otp_secret
+            OR OLD.desc IS NOT NEW.desc
+            OR OLD.auth_key IS NOT NEW.auth_key
+            OR OLD.auth_key_filename IS NOT NEW.auth_key_filename
+            OR OLD.group_name IS NOT NEW.group_name
+            OR OLD.server_id IS NOT NEW.server_id
+            OR OLD.deleted_at IS NOT NEW.deleted_at BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row, new_row)
+              VALUES ('update', 'server_extra_user_account', NEW.id,
+                      json_object('id', OLD.id,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'otp_secret', OLD.otp_secret,
+                                  'desc', OLD.desc,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at),
+                      json_object('id', NEW.id,
+                                  'username', NEW.username,
+                                  'password', NEW.password,
+                                  'otp_secret', NEW.otp_secret,
+                                  'desc', NEW.desc,
+                                  'auth_key', CASE WHEN NEW.auth_key IS NULL THEN NULL ELSE hex(NEW.auth_key) END,
+                                  'auth_key_filename', NEW.auth_key_filename,
+                                  'group_name', NEW.group_name,
+                                  'server_id', NEW.server_id,
+                                  'deleted_at', NEW.deleted_at));
+END;
+CREATE TRIGGER change_log_server_extra_user_account_delete AFTER DELETE ON server_extra_user_account BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_extra_user_account', OLD.id,
+                      json_object('id', OLD.id,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'otp_secret', OLD.otp_secret,
+                                  'desc', OLD.desc,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
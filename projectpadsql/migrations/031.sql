@@ -0,0 +1,30 @@
+-- files attached to the projects, servers and notes: TLS certificates,
+-- VPN profiles, kubeconfigs, license files... As for the tags, entity_type
+-- is the name of the table of the item and entity_id its id. The contents
+-- are loaded only when they're needed, see AttachmentRepo.
+CREATE TABLE attachment (
+       id INTEGER PRIMARY KEY,
+       entity_type TEXT NOT NULL
+           CHECK(entity_type IN ('project', 'server', 'project_note', 'server_note')),
+       entity_id INTEGER NOT NULL,
+       name TEXT NOT NULL CHECK(LENGTH(name) > 0),
+       mime TEXT NOT NULL,
+       size INTEGER NOT NULL,
+       contents BLOB NOT NULL,
+       created_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')));
+CREATE INDEX attachment_entity ON attachment(entity_type, entity_id);
+
+-- like the tags, the attachments go away with the items,
+-- and items in the trash keep their attachments.
+CREATE TRIGGER attachment_project_delete AFTER DELETE ON project BEGIN
+       DELETE FROM attachment WHERE entity_type = 'project' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER attachment_server_delete AFTER DELETE ON server BEGIN
+       DELETE FROM attachment WHERE entity_type = 'server' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER attachment_project_note_delete AFTER DELETE ON project_note BEGIN
+       DELETE FROM attachment WHERE entity_type = 'project_note' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER attachment_server_note_delete AFTER DELETE ON server_note BEGIN
+       DELETE FROM attachment WHERE entity_type = 'server_note' AND entity_id = OLD.id;
+END;
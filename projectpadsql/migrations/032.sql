@@ -0,0 +1,122 @@
+-- custom fields: each project defines extra fields for its servers, databases
+-- and websites (rack location, contract number, license key...). The values
+-- are typed, see CustomFieldType. entity_type is the name of the table of
+-- the items the field applies to, as for the tags.
+CREATE TABLE custom_field_definition (
+       id INTEGER PRIMARY KEY,
+       project_id INTEGER NOT NULL,
+       entity_type TEXT NOT NULL
+           CHECK(entity_type IN ('server', 'server_database', 'server_website')),
+       name TEXT NOT NULL CHECK(LENGTH(name) > 0),
+       field_type TEXT NOT NULL CHECK(field_type IN ('Text', 'Secret', 'Url', 'Date')),
+       ordering INTEGER NOT NULL,
+       UNIQUE(project_id, entity_type, name),
+       FOREIGN KEY(project_id) REFERENCES project(id) ON DELETE CASCADE);
+
+-- only the fields which have a value for the item have a row. Like the
+-- passwords, the values of the secret fields are encrypted if the master
+-- passphrase is enabled.
+CREATE TABLE custom_field_value (
+       id INTEGER PRIMARY KEY,
+       definition_id INTEGER NOT NULL,
+       entity_id INTEGER NOT NULL,
+       value TEXT NOT NULL,
+       UNIQUE(definition_id, entity_id),
+       FOREIGN KEY(definition_id) REFERENCES custom_field_definition(id) ON DELETE CASCADE);
+CREATE INDEX custom_field_value_entity ON custom_field_value(entity_id);
+
+-- the values go away with the items, items in the trash keep them
+CREATE TRIGGER custom_field_value_server_delete AFTER DELETE ON server BEGIN
+       DELETE FROM custom_field_value WHERE entity_id = OLD.id AND definition_id IN
+              (SELECT id FROM custom_field_definition WHERE entity_type = 'server');
+END;
+CREATE TRIGGER custom_field_value_server_database_delete AFTER DELETE ON server_database BEGIN
+       DELETE FROM custom_field_value WHERE entity_id = OLD.id AND definition_id IN
+              (SELECT id FROM custom_field_definition WHERE entity_type = 'server_database');
+END;
+CREATE TRIGGER custom_field_value_server_website_delete AFTER DELETE ON server_website BEGIN
+       DELETE FROM custom_field_value WHERE entity_id = OLD.id AND definition_id IN
+              (SELECT id FROM custom_field_definition WHERE entity_type = 'server_website');
+END;
+
+-- the values of the custom fields are searchable, except the secret ones.
+-- They're appended to the body of the item in the search index. New items
+-- have no values yet: only the update triggers change.
+CREATE VIEW custom_field_search_text AS
+       SELECT d.entity_type, v.entity_id, group_concat(v.value, ' ') AS text
+         FROM custom_field_value v
+         JOIN custom_field_definition d ON d.id = v.definition_id
+        WHERE d.field_type != 'Secret'
+        GROUP BY d.entity_type, v.entity_id;
+
+DROP TRIGGER search_index_server_update;
+CREATE TRIGGER search_index_server_update AFTER UPDATE ON server BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server', NEW.id, NEW.desc, NEW.ip || ' ' || NEW.text || IFNULL(
+                     (SELECT ' ' || text FROM custom_field_search_text
+                       WHERE entity_type = 'server' AND entity_id = NEW.id), '')
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_database_update;
+CREATE TRIGGER search_index_server_database_update AFTER UPDATE ON server_database BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_database' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_database', NEW.id, NEW.desc, NEW.name || ' ' || NEW.text || IFNULL(
+                     (SELECT ' ' || text FROM custom_field_search_text
+                       WHERE entity_type = 'server_database' AND entity_id = NEW.id), '')
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_website_update;
+CREATE TRIGGER search_index_server_website_update AFTER UPDATE ON server_website BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_website' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_website', NEW.id, NEW.desc, NEW.url || ' ' || NEW.text || IFNULL(
+                     (SELECT ' ' || text FROM custom_field_search_text
+                       WHERE entity_type = 'server_website' AND entity_id = NEW.id), '')
+              WHERE NEW.deleted_at IS NULL;
+END;
+
+-- a change in the values reindexes the item, through its update trigger.
+-- Setting a column to itself doesn't log a change in the change_log.
+CREATE TRIGGER custom_field_value_reindex_insert AFTER INSERT ON custom_field_value BEGIN
+       UPDATE server SET id = id WHERE id = NEW.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = NEW.definition_id) = 'server';
+       UPDATE server_database SET id = id WHERE id = NEW.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = NEW.definition_id) = 'server_database';
+       UPDATE server_website SET id = id WHERE id = NEW.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = NEW.definition_id) = 'server_website';
+END;
+CREATE TRIGGER custom_field_value_reindex_update AFTER UPDATE ON custom_field_value BEGIN
+       UPDATE server SET id = id WHERE id = NEW.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = NEW.definition_id) = 'server';
+       UPDATE server_database SET id = id WHERE id = NEW.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = NEW.definition_id) = 'server_database';
+       UPDATE server_website SET id = id WHERE id = NEW.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = NEW.definition_id) = 'server_website';
+END;
+-- when a definition is deleted, it's already gone when its values are
+-- deleted by the cascade: the definition delete trigger reindexes the items
+CREATE TRIGGER custom_field_value_reindex_delete AFTER DELETE ON custom_field_value BEGIN
+       UPDATE server SET id = id WHERE id = OLD.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = OLD.definition_id) = 'server';
+       UPDATE server_database SET id = id WHERE id = OLD.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = OLD.definition_id) = 'server_database';
+       UPDATE server_website SET id = id WHERE id = OLD.entity_id AND
+              (SELECT entity_type FROM custom_field_definition WHERE id = OLD.definition_id) = 'server_website';
+END;
+CREATE TRIGGER custom_field_definition_reindex AFTER UPDATE OF field_type ON custom_field_definition BEGIN
+       UPDATE server SET id = id WHERE NEW.entity_type = 'server' AND id IN
+              (SELECT entity_id FROM custom_field_value WHERE definition_id = NEW.id);
+       UPDATE server_database SET id = id WHERE NEW.entity_type = 'server_database' AND id IN
+              (SELECT entity_id FROM custom_field_value WHERE definition_id = NEW.id);
+       UPDATE server_website SET id = id WHERE NEW.entity_type = 'server_website' AND id IN
+              (SELECT entity_id FROM custom_field_value WHERE definition_id = NEW.id);
+END;
+CREATE TRIGGER custom_field_definition_delete_reindex AFTER DELETE ON custom_field_definition BEGIN
+       UPDATE server SET id = id WHERE OLD.entity_type = 'server' AND project_id = OLD.project_id;
+       UPDATE server_database SET id = id WHERE OLD.entity_type = 'server_database' AND server_id IN
+              (SELECT id FROM server WHERE project_id = OLD.project_id);
+       UPDATE server_website SET id = id WHERE OLD.entity_type = 'server_website' AND server_id IN
+              (SELECT id FROM server WHERE project_id = OLD.project_id);
+END;
@@ -0,0 +1,201 @@
+-- when the password of an item was last changed, to enforce the rotation
+-- policies of the projects, see password_audit.rs. NULL means that it's
+-- unknown: the password was set before we recorded the changes, or there
+-- is no password. Set by the triggers below, not by the application.
+ALTER TABLE server ADD COLUMN password_changed_at TIMESTAMP;
+ALTER TABLE server_database ADD COLUMN password_changed_at TIMESTAMP;
+ALTER TABLE server_website ADD COLUMN password_changed_at TIMESTAMP;
+ALTER TABLE server_extra_user_account ADD COLUMN password_changed_at TIMESTAMP;
+
+-- the deleted items are restored with their date. The updates of the date
+-- alone are not logged, see the triggers below.
+DROP TRIGGER change_log_server_delete;
+CREATE TRIGGER change_log_server_delete AFTER DELETE ON server BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'ip', OLD.ip,
+                                  'port', OLD.port,
+                                  'protocol', OLD.protocol,
+                                  'path', OLD.path,
+                                  'text', OLD.text,
+                                  'is_retired', OLD.is_retired,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'password_changed_at', OLD.password_changed_at,
+                                  'otp_secret', OLD.otp_secret,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'type', OLD.type,
+                                  'access_type', OLD.access_type,
+                                  'ssh_tunnel_port', OLD.ssh_tunnel_port,
+                                  'ssh_tunnel_through_server_id', OLD.ssh_tunnel_through_server_id,
+                                  'environment', OLD.environment,
+                                  'group_name', OLD.group_name,
+                                  'project_id', OLD.project_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+DROP TRIGGER change_log_server_database_delete;
+CREATE TRIGGER change_log_server_database_delete AFTER DELETE ON server_database BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_database', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'name', OLD.name,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'password_changed_at', OLD.password_changed_at,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+DROP TRIGGER change_log_server_website_delete;
+CREATE TRIGGER change_log_server_website_delete AFTER DELETE ON server_website BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_website', OLD.id,
+                      json_object('id', OLD.id,
+                                  'desc', OLD.desc,
+                                  'url', OLD.url,
+                                  'text', OLD.text,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'password_changed_at', OLD.password_changed_at,
+                                  'otp_secret', OLD.otp_secret,
+                                  'server_database_id', OLD.server_database_id,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+DROP TRIGGER change_log_server_extra_user_account_delete;
+CREATE TRIGGER change_log_server_extra_user_account_delete AFTER DELETE ON server_extra_user_account BEGIN
+       INSERT INTO change_log (operation, entity_type, entity_id, old_row)
+              VALUES ('delete', 'server_extra_user_account', OLD.id,
+                      json_object('id', OLD.id,
+                                  'username', OLD.username,
+                                  'password', OLD.password,
+                                  'password_changed_at', OLD.password_changed_at,
+                                  'otp_secret', OLD.otp_secret,
+                                  'desc', OLD.desc,
+                                  'auth_key', CASE WHEN OLD.auth_key IS NULL THEN NULL ELSE hex(OLD.auth_key) END,
+                                  'auth_key_filename', OLD.auth_key_filename,
+                                  'group_name', OLD.group_name,
+                                  'server_id', OLD.server_id,
+                                  'deleted_at', OLD.deleted_at));
+END;
+
+-- only the indexed columns reindex the items, not the date set by the
+-- triggers below: it would index a new item a second time. The reindexing
+-- by custom_field_value sets the id to itself.
+DROP TRIGGER search_index_server_update;
+CREATE TRIGGER search_index_server_update AFTER UPDATE OF id, desc, ip, text, deleted_at ON server BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server', NEW.id, NEW.desc, NEW.ip || ' ' || NEW.text || IFNULL(
+                     (SELECT ' ' || text FROM custom_field_search_text
+                       WHERE entity_type = 'server' AND entity_id = NEW.id), '')
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_database_update;
+CREATE TRIGGER search_index_server_database_update AFTER UPDATE OF id, desc, name, text, deleted_at ON server_database BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_database' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_database', NEW.id, NEW.desc, NEW.name || ' ' || NEW.text || IFNULL(
+                     (SELECT ' ' || text FROM custom_field_search_text
+                       WHERE entity_type = 'server_database' AND entity_id = NEW.id), '')
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_website_update;
+CREATE TRIGGER search_index_server_website_update AFTER UPDATE OF id, desc, url, text, deleted_at ON server_website BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_website' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_website', NEW.id, NEW.desc, NEW.url || ' ' || NEW.text || IFNULL(
+                     (SELECT ' ' || text FROM custom_field_search_text
+                       WHERE entity_type = 'server_website' AND entity_id = NEW.id), '')
+              WHERE NEW.deleted_at IS NULL;
+END;
+DROP TRIGGER search_index_server_extra_user_account_update;
+CREATE TRIGGER search_index_server_extra_user_account_update AFTER UPDATE OF id, desc, username, deleted_at ON server_extra_user_account BEGIN
+       DELETE FROM search_index WHERE entity_type = 'server_extra_user_account' AND entity_id = OLD.id;
+       INSERT INTO search_index (entity_type, entity_id, title, body)
+              SELECT 'server_extra_user_account', NEW.id, NEW.desc, NEW.username
+              WHERE NEW.deleted_at IS NULL;
+END;
+
+-- encrypting or decrypting the passwords when the master passphrase is
+-- enabled or disabled is not a change: only the updates which keep the
+-- password encrypted, or plain, count. 'ppsecret:v1:' is
+-- secrets::ENCRYPTED_PREFIX.
+CREATE TRIGGER password_changed_server_insert AFTER INSERT ON server
+       WHEN NEW.password != '' AND NEW.password_changed_at IS NULL BEGIN
+       UPDATE server SET password_changed_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+              WHERE id = NEW.id;
+END;
+CREATE TRIGGER password_changed_server_update AFTER UPDATE OF password ON server
+       WHEN OLD.password IS NOT NEW.password
+            AND (OLD.password = '' OR NEW.password = ''
+                 OR (substr(OLD.password, 1, 12) = 'ppsecret:v1:')
+                    = (substr(NEW.password, 1, 12) = 'ppsecret:v1:')) BEGIN
+       UPDATE server SET password_changed_at =
+              CASE WHEN NEW.password = '' THEN NULL
+                   ELSE strftime('%Y-%m-%d %H:%M:%f', 'now') END
+              WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER password_changed_server_database_insert AFTER INSERT ON server_database
+       WHEN NEW.password != '' AND NEW.password_changed_at IS NULL BEGIN
+       UPDATE server_database SET password_changed_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+              WHERE id = NEW.id;
+END;
+CREATE TRIGGER password_changed_server_database_update AFTER UPDATE OF password ON server_database
+       WHEN OLD.password IS NOT NEW.password
+            AND (OLD.password = '' OR NEW.password = ''
+                 OR (substr(OLD.password, 1, 12) = 'ppsecret:v1:')
+                    = (substr(NEW.password, 1, 12) = 'ppsecret:v1:')) BEGIN
+       UPDATE server_database SET password_changed_at =
+              CASE WHEN NEW.password = '' THEN NULL
+                   ELSE strftime('%Y-%m-%d %H:%M:%f', 'now') END
+              WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER password_changed_server_website_insert AFTER INSERT ON server_website
+       WHEN NEW.password != '' AND NEW.password_changed_at IS NULL BEGIN
+       UPDATE server_website SET password_changed_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+              WHERE id = NEW.id;
+END;
+CREATE TRIGGER password_changed_server_website_update AFTER UPDATE OF password ON server_website
+       WHEN OLD.password IS NOT NEW.password
+            AND (OLD.password = '' OR NEW.password = ''
+                 OR (substr(OLD.password, 1, 12) = 'ppsecret:v1:')
+                    = (substr(NEW.password, 1, 12) = 'ppsecret:v1:')) BEGIN
+       UPDATE server_website SET password_changed_at =
+              CASE WHEN NEW.password = '' THEN NULL
+                   ELSE strftime('%Y-%m-%d %H:%M:%f', 'now') END
+              WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER password_changed_server_extra_user_account_insert AFTER INSERT ON server_extra_user_account
+       WHEN NEW.password != '' AND NEW.password_changed_at IS NULL BEGIN
+       UPDATE server_extra_user_account SET password_changed_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+              WHERE id = NEW.id;
+END;
+CREATE TRIGGER password_changed_server_extra_user_account_update AFTER UPDATE OF password ON server_extra_user_account
+       WHEN OLD.password IS NOT NEW.password
+            AND (OLD.password = '' OR NEW.password = ''
+                 OR (substr(OLD.password, 1, 12) = 'ppsecret:v1:')
+                    = (substr(NEW.password, 1, 12) = 'ppsecret:v1:')) BEGIN
+       UPDATE server_extra_user_account SET password_changed_at =
+              CASE WHEN NEW.password = '' THEN NULL
+                   ELSE strftime('%Y-%m-%d %H:%M:%f', 'now') END
+              WHERE id = NEW.id;
+END;
+
+-- how often the passwords of a project must be changed, for instance
+-- every 90 days for the production servers. No row means no policy.
+-- The environments are dangerous as per environment.dangerous.
+CREATE TABLE password_policy (
+       project_id INTEGER PRIMARY KEY,
+       max_age_days INTEGER NOT NULL CHECK(max_age_days > 0),
+       dangerous_environments_only BOOLEAN NOT NULL DEFAULT 0,
+       FOREIGN KEY(project_id) REFERENCES project(id) ON DELETE CASCADE);
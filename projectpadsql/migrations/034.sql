@@ -0,0 +1,72 @@
+-- sync between the databases of several devices, see sync/mod.rs.
+-- The changes to the entities of the change log are exchanged as
+-- encrypted bundles, through a shared folder or a git repository.
+
+-- the settings of this device. No row means that sync is disabled.
+CREATE TABLE sync_settings (
+       id INTEGER PRIMARY KEY CHECK(id = 1),
+       -- random, identifies this database in the vector clocks
+       device_id TEXT NOT NULL,
+       device_name TEXT NOT NULL,
+       backend TEXT NOT NULL CHECK (backend IN ('folder', 'git')),
+       -- the shared folder, or the clone of the git repository
+       location TEXT NOT NULL,
+       -- shared by the team, the bundles are encrypted with a key derived from it
+       passphrase TEXT NOT NULL,
+       -- the changes of the change log up to this id were collected to sync_outbox
+       change_log_id INTEGER NOT NULL DEFAULT 0,
+       -- the counter of the latest change of this device
+       counter INTEGER NOT NULL DEFAULT 0);
+
+-- the ids of the entities are different in every database:
+-- the bundles refer to the entities by a random global id.
+CREATE TABLE sync_entity (
+       entity_type TEXT NOT NULL,
+       global_id TEXT NOT NULL,
+       -- NULL once the entity is deleted
+       entity_id INTEGER,
+       -- the vector clock of the latest change to the entity:
+       -- a json object, the counter of the change of every device
+       clock TEXT NOT NULL,
+       PRIMARY KEY (entity_type, global_id),
+       UNIQUE (entity_type, entity_id));
+
+-- the changes of this device which were not written to the backend yet
+CREATE TABLE sync_outbox (
+       counter INTEGER PRIMARY KEY,
+       entity_type TEXT NOT NULL,
+       global_id TEXT NOT NULL,
+       -- the entity as a json object, with the global ids of the
+       -- entities it references. NULL when it was deleted.
+       row TEXT,
+       clock TEXT NOT NULL);
+
+-- the devices which we received changes from
+CREATE TABLE sync_peer (
+       device_id TEXT PRIMARY KEY,
+       device_name TEXT NOT NULL,
+       -- the counter of the latest change received from the device
+       counter INTEGER NOT NULL);
+
+-- the changes received from the other devices, until they're applied:
+-- the entities they reference may come later, from another device
+CREATE TABLE sync_inbox (
+       device_id TEXT NOT NULL,
+       counter INTEGER NOT NULL,
+       entity_type TEXT NOT NULL,
+       global_id TEXT NOT NULL,
+       row TEXT,
+       clock TEXT NOT NULL,
+       PRIMARY KEY (device_id, counter));
+
+-- changes made concurrently here and on another device, for the user to
+-- resolve. The local version is the entity as it is in the database.
+CREATE TABLE sync_conflict (
+       id INTEGER PRIMARY KEY,
+       entity_type TEXT NOT NULL,
+       global_id TEXT NOT NULL,
+       device_name TEXT NOT NULL,
+       row TEXT,
+       clock TEXT NOT NULL,
+       detected_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+       UNIQUE (entity_type, global_id));
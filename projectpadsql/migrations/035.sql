@@ -0,0 +1,18 @@
+-- typed dependencies between servers: the application server depends on
+-- the database server and on the proxy, see DependencyType. The servers
+-- may be in different projects. The dependencies of the websites on their
+-- databases (server_website.server_database_id) are not repeated here,
+-- see dependency_graph.rs. Servers in the trash keep their dependencies.
+CREATE TABLE server_dependency (
+       id INTEGER PRIMARY KEY,
+       server_id INTEGER NOT NULL,
+       depends_on_server_id INTEGER NOT NULL,
+       dependency_type TEXT NOT NULL
+           CHECK(dependency_type IN ('Database', 'Proxy', 'Cache', 'MessageQueue',
+                                     'Storage', 'Authentication', 'Other')),
+       desc TEXT NOT NULL DEFAULT '',
+       UNIQUE(server_id, depends_on_server_id, dependency_type),
+       CHECK(server_id != depends_on_server_id),
+       FOREIGN KEY(server_id) REFERENCES server(id) ON DELETE CASCADE,
+       FOREIGN KEY(depends_on_server_id) REFERENCES server(id) ON DELETE CASCADE);
+CREATE INDEX server_dependency_depends_on ON server_dependency(depends_on_server_id);
@@ -0,0 +1,269 @@
+// backups of the whole database, taken while it's open and in use. Unlike
+// the 7z/yaml export, they keep everything: the ids, the change log, the
+// trash, the links between servers. The backups are encrypted with the
+// password the database had when they were taken, in its format (see `cipher`).
+use crate::error::{Error, Result};
+use crate::{cipher, config_path, migrations, profile};
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// how many daily backups the applications keep, see `daily_backup`
+pub const DAILY_BACKUPS_KEPT: usize = 7;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// VACUUM INTO appeared in sqlite 3.27
+const VACUUM_INTO_SINCE: (u32, u32) = (3, 27);
+
+/// copy the database to `dest`, which must not exist yet.
+/// VACUUM INTO takes a consistent snapshot in a read transaction, like the
+/// sqlite online backup API (which diesel doesn't expose), so other
+/// connections can keep using the database meanwhile. sqlcipher encrypts
+/// the copy with the key and cipher settings of the database.
+/// With older sqlite versions, see `backup_by_copy`.
+pub fn backup(conn: &SqliteConnection, dest: &Path) -> Result<()> {
+    backup_as(conn, dest, dest)
+}
+
+/// like `backup`, but `dest` gets the format of `final_dest`,
+/// where the backup will be moved
+fn backup_as(conn: &SqliteConnection, dest: &Path, final_dest: &Path) -> Result<()> {
+    check_backup_dest(dest)?;
+    cipher::copy_sidecar(crate::main_db_file(conn).as_deref(), final_dest)?;
+    if supports_vacuum_into(&sqlite_version(conn)?) {
+        diesel::sql_query("VACUUM INTO ?")
+            .bind::<diesel::sql_types::Text, _>(dest.to_string_lossy())
+            .execute(conn)?;
+        Ok(())
+    } else {
+        copy_db_file(conn, dest)
+    }
+}
+
+/// the backup of `backup` for sqlite before 3.27: the file of the database
+/// is copied while holding a read transaction, so that the other connections
+/// can't write to it meanwhile. The copy has the key and cipher settings of
+/// the database, but in-memory databases can't be backed up this way.
+pub fn backup_by_copy(conn: &SqliteConnection, dest: &Path) -> Result<()> {
+    check_backup_dest(dest)?;
+    cipher::copy_sidecar(crate::main_db_file(conn).as_deref(), dest)?;
+    copy_db_file(conn, dest)
+}
+
+fn check_backup_dest(dest: &Path) -> Result<()> {
+    if dest.exists() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", dest.display()),
+        )));
+    }
+    Ok(())
+}
+
+fn copy_db_file(conn: &SqliteConnection, dest: &Path) -> Result<()> {
+    let db_file = crate::main_db_file(conn).ok_or_else(|| {
+        Error::Invalid(
+            "This version of sqlite can only back up databases stored in a file".to_string(),
+        )
+    })?;
+    conn.transaction::<_, Error, _>(|| {
+        // the read lock is taken by the first read in the transaction.
+        // the databases are not in WAL mode, all the commits are in the file
+        crate::get_db_version(conn)?;
+        std::fs::copy(&db_file, dest)?;
+        Ok(())
+    })
+}
+
+#[derive(QueryableByName)]
+struct SqliteVersionRow {
+    #[sql_type = "diesel::sql_types::Text"]
+    version: String,
+}
+
+fn sqlite_version(conn: &SqliteConnection) -> Result<String> {
+    Ok(diesel::sql_query("SELECT sqlite_version() AS version")
+        .get_result::<SqliteVersionRow>(conn)?
+        .version)
+}
+
+/// whether this version of sqlite, for instance "3.31.1", has VACUUM INTO
+pub fn supports_vacuum_into(sqlite_version: &str) -> bool {
+    let mut numbers = sqlite_version
+        .split('.')
+        .map(|n| n.parse::<u32>().unwrap_or(0));
+    let major_minor = (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0));
+    major_minor >= VACUUM_INTO_SINCE
+}
+
+/// the folder of the daily backups, in the configuration folder
+pub fn backups_folder() -> PathBuf {
+    config_path().join("backups")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyBackup {
+    pub date: NaiveDate,
+    pub path: PathBuf,
+}
+
+/// projectpad-2021-03-14.db for the default profile,
+/// projectpad-2021-03-14-work.db for the work profile
+fn daily_backup_path(folder: &Path, profile: &str, date: NaiveDate) -> PathBuf {
+    profile::file_path_in(
+        folder,
+        profile,
+        &format!("projectpad-{}.db", date.format(DATE_FORMAT)),
+    )
+}
+
+/// the daily backups of a profile in `folder`, the newest first
+pub fn list_daily_backups(folder: &Path, profile: &str) -> Vec<DailyBackup> {
+    let mut backups: Vec<DailyBackup> = std::fs::read_dir(folder)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    let date = name
+                        .strip_prefix("projectpad-")
+                        .and_then(|n| n.get(..10))
+                        .and_then(|d| NaiveDate::parse_from_str(d, DATE_FORMAT).ok())?;
+                    // the other profiles have the same prefix, compare the whole path
+                    Some(DailyBackup {
+                        date,
+                        path: e.path(),
+                    })
+                    .filter(|b| b.path == daily_backup_path(folder, profile, date))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    backups.sort_by(|a, b| b.date.cmp(&a.date));
+    backups
+}
+
+/// take today's backup of the database in `folder`, unless it was taken
+/// already, then delete the oldest backups to keep only `keep` of them.
+/// Returns the path of the new backup, if one was taken.
+pub fn daily_backup(
+    conn: &SqliteConnection,
+    folder: &Path,
+    profile: &str,
+    today: NaiveDate,
+    keep: usize,
+) -> Result<Option<PathBuf>> {
+    let dest = daily_backup_path(folder, profile, today);
+    let taken = if dest.exists() {
+        None
+    } else {
+        std::fs::create_dir_all(folder)?;
+        // don't leave a half-written backup under the final name
+        // if the application is stopped in the middle
+        let tmp = dest.with_extension("db.tmp");
+        if tmp.exists() {
+            std::fs::remove_file(&tmp)?;
+        }
+        backup_as(conn, &tmp, &dest)?;
+        std::fs::rename(&tmp, &dest)?;
+        Some(dest)
+    };
+    for old in list_daily_backups(folder, profile).iter().skip(keep) {
+        std::fs::remove_file(&old.path)?;
+        let sidecar = cipher::sidecar_path(&old.path);
+        if sidecar.exists() {
+            std::fs::remove_file(sidecar)?;
+        }
+    }
+    Ok(taken)
+}
+
+#[derive(QueryableByName)]
+struct CipherIntegrityRow {
+    #[sql_type = "diesel::sql_types::Text"]
+    cipher_integrity_check: String,
+}
+
+#[derive(QueryableByName)]
+struct IntegrityRow {
+    #[sql_type = "diesel::sql_types::Text"]
+    integrity_check: String,
+}
+
+/// open the backup with `pass` and check that it's intact, see `check_integrity`.
+/// Returns the schema version of the backup.
+pub fn check_backup(backup_path: &Path, pass: &str) -> Result<i32> {
+    if !backup_path.is_file() {
+        // establish() would create an empty database
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} doesn't exist", backup_path.display()),
+        )));
+    }
+    let conn = SqliteConnection::establish(&backup_path.to_string_lossy())
+        .map_err(|e| Error::Corrupt(e.to_string()))?;
+    crate::try_unlock_db_file(&conn, pass, backup_path)?;
+    check_integrity(&conn)?;
+    let db_version = crate::get_db_version(&conn)?;
+    if db_version > migrations::SCHEMA_VERSION {
+        return Err(Error::SchemaTooNew {
+            db_version,
+            max_supported: migrations::SCHEMA_VERSION,
+        });
+    }
+    Ok(db_version)
+}
+
+/// the HMAC of every page (sqlcipher), then the structure of the database (sqlite)
+pub(crate) fn check_integrity(conn: &SqliteConnection) -> Result<()> {
+    let cipher_errors =
+        diesel::sql_query("PRAGMA cipher_integrity_check").load::<CipherIntegrityRow>(conn)?;
+    if !cipher_errors.is_empty() {
+        return Err(Error::Corrupt(
+            cipher_errors
+                .into_iter()
+                .map(|r| r.cipher_integrity_check)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ));
+    }
+    let integrity = diesel::sql_query("PRAGMA integrity_check").load::<IntegrityRow>(conn)?;
+    if integrity.len() != 1 || integrity[0].integrity_check != "ok" {
+        return Err(Error::Corrupt(
+            integrity
+                .into_iter()
+                .map(|r| r.integrity_check)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ));
+    }
+    Ok(())
+}
+
+/// where `restore` keeps the database it replaced
+pub fn before_restore_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".before-restore.bak");
+    PathBuf::from(name)
+}
+
+/// replace the database at `db_path` with the backup, after checking the
+/// backup with `check_backup`. The database is first copied to
+/// `before_restore_path`. The connections to the database must be
+/// reopened afterwards: they still see the file which was replaced.
+/// Returns the schema version of the backup, which may have to be migrated.
+pub fn restore(backup_path: &Path, pass: &str, db_path: &Path) -> Result<i32> {
+    let db_version = check_backup(backup_path, pass)?;
+    if db_path.exists() {
+        cipher::copy_database(db_path, &before_restore_path(db_path))?;
+    }
+    // copy then rename, so that db_path is always a complete database
+    let mut tmp_name = db_path.as_os_str().to_owned();
+    tmp_name.push(".restore.tmp");
+    let tmp = PathBuf::from(tmp_name);
+    std::fs::copy(backup_path, &tmp)?;
+    cipher::switch_database(db_path, &tmp, cipher::format_of(backup_path)?)?;
+    Ok(db_version)
+}
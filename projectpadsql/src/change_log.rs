@@ -0,0 +1,414 @@
+// history of the changes to all the entities, so that deletes and edits
+// can be undone. The change_log table is maintained by triggers (see migration 24).
+use crate::error::{Error, Result};
+use crate::models::{ChangeOperation, EntityType};
+use crate::repo::{check_modified, insert_row};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text, Timestamp};
+use std::collections::{HashMap, HashSet};
+
+/// (entity, column, referenced entity, whether deletes cascade)
+pub(crate) const REFERENCES: &[(EntityType, &str, EntityType, bool)] = &[
+    (EntityType::Server, "project_id", EntityType::Project, true),
+    (
+        EntityType::ProjectPoi,
+        "project_id",
+        EntityType::Project,
+        true,
+    ),
+    (
+        EntityType::ProjectNote,
+        "project_id",
+        EntityType::Project,
+        true,
+    ),
+    (
+        EntityType::ServerLink,
+        "project_id",
+        EntityType::Project,
+        true,
+    ),
+    (
+        EntityType::ServerLink,
+        "linked_server_id",
+        EntityType::Server,
+        true,
+    ),
+    (EntityType::ServerPoi, "server_id", EntityType::Server, true),
+    (
+        EntityType::ServerWebsite,
+        "server_id",
+        EntityType::Server,
+        true,
+    ),
+    (
+        EntityType::ServerDatabase,
+        "server_id",
+        EntityType::Server,
+        true,
+    ),
+    (
+        EntityType::ServerExtraUserAccount,
+        "server_id",
+        EntityType::Server,
+        true,
+    ),
+    (
+        EntityType::ServerNote,
+        "server_id",
+        EntityType::Server,
+        true,
+    ),
+    (
+        EntityType::ServerWebsite,
+        "server_database_id",
+        EntityType::ServerDatabase,
+        false,
+    ),
+    (
+        EntityType::Server,
+        "ssh_tunnel_through_server_id",
+        EntityType::Server,
+        false,
+    ),
+];
+
+/// the blob columns are stored hex-encoded in the change log
+pub(crate) const BLOB_COLUMNS: &[(EntityType, &str)] = &[
+    (EntityType::Project, "icon"),
+    (EntityType::Server, "auth_key"),
+    (EntityType::ServerExtraUserAccount, "auth_key"),
+];
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct Change {
+    #[sql_type = "Integer"]
+    pub id: i32,
+    #[sql_type = "Timestamp"]
+    pub changed_at: NaiveDateTime,
+    #[sql_type = "Text"]
+    pub operation: ChangeOperation,
+    #[sql_type = "Text"]
+    pub entity_type: EntityType,
+    #[sql_type = "Integer"]
+    pub entity_id: i32,
+    /// the description, name or title of the entity
+    #[sql_type = "Text"]
+    pub entity_desc: String,
+    #[sql_type = "Nullable<Timestamp>"]
+    pub undone_at: Option<NaiveDateTime>,
+}
+
+/// a field modified by an update. The values are displayable
+/// text, blobs are hex-encoded.
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct FieldChange {
+    #[sql_type = "Text"]
+    pub field: String,
+    #[sql_type = "Nullable<Text>"]
+    pub old_value: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    pub new_value: Option<String>,
+}
+
+const CHANGE_SELECT: &str = "SELECT id, changed_at, operation, entity_type, entity_id, \
+        coalesce(json_extract(coalesce(old_row, new_row), '$.desc'), \
+                 json_extract(coalesce(old_row, new_row), '$.name'), \
+                 json_extract(coalesce(old_row, new_row), '$.title'), '') AS entity_desc, \
+        undone_at \
+   FROM change_log";
+
+/// the most recent changes first
+pub fn list_changes(conn: &SqliteConnection, limit: i64) -> Result<Vec<Change>> {
+    Ok(
+        diesel::sql_query(format!("{} ORDER BY id DESC LIMIT ?", CHANGE_SELECT))
+            .bind::<diesel::sql_types::BigInt, _>(limit)
+            .load(conn)?,
+    )
+}
+
+pub fn get_change(conn: &SqliteConnection, change_id: i32) -> Result<Change> {
+    diesel::sql_query(format!("{} WHERE id = ?", CHANGE_SELECT))
+        .bind::<Integer, _>(change_id)
+        .load(conn)?
+        .into_iter()
+        .next()
+        .ok_or(Error::NotFound)
+}
+
+/// the most recent delete which was not undone yet. When a delete
+/// cascades, the deletes of the children are logged first, so this
+/// is the delete that the user requested.
+pub fn last_delete(conn: &SqliteConnection) -> Result<Option<Change>> {
+    Ok(diesel::sql_query(format!(
+        "{} WHERE operation = 'delete' AND undone_at IS NULL ORDER BY id DESC LIMIT 1",
+        CHANGE_SELECT
+    ))
+    .load(conn)?
+    .into_iter()
+    .next())
+}
+
+/// the fields that an update modified, in the column order of the table
+pub fn field_changes(conn: &SqliteConnection, change_id: i32) -> Result<Vec<FieldChange>> {
+    Ok(diesel::sql_query(
+        "SELECT o.key AS field, CAST(o.value AS TEXT) AS old_value, \
+                CAST(n.value AS TEXT) AS new_value \
+           FROM change_log \
+           JOIN json_each(change_log.old_row) o \
+           JOIN json_each(change_log.new_row) n ON n.key = o.key \
+          WHERE change_log.id = ? AND o.value IS NOT n.value \
+          ORDER BY o.id",
+    )
+    .bind::<Integer, _>(change_id)
+    .load(conn)?)
+}
+
+#[derive(QueryableByName)]
+struct RowValue {
+    #[sql_type = "Text"]
+    key: String,
+    // the value, quoted as a SQL literal
+    #[sql_type = "Text"]
+    literal: String,
+}
+
+#[derive(QueryableByName)]
+struct DeletedEntity {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    entity_type: EntityType,
+    #[sql_type = "Integer"]
+    entity_id: i32,
+}
+
+struct DeletedRow {
+    change_id: i32,
+    entity_type: EntityType,
+    entity_id: i32,
+    values: Vec<(String, String)>,
+}
+
+impl DeletedRow {
+    fn reference(&self, column: &str) -> Option<i32> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == column)
+            .and_then(|(_, v)| v.parse().ok())
+    }
+}
+
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// the columns of a row stored in the change log, with their values as SQL literals
+fn row_values(
+    conn: &SqliteConnection,
+    change_id: i32,
+    entity_type: EntityType,
+    row_column: &str,
+) -> Result<Vec<(String, String)>> {
+    let values = diesel::sql_query(format!(
+        "SELECT key, quote(value) AS literal FROM change_log, json_each(change_log.{}) \
+          WHERE change_log.id = ? \
+            AND key IN (SELECT name FROM pragma_table_info('{}')) \
+          ORDER BY json_each.id",
+        row_column, entity_type
+    ))
+    .bind::<Integer, _>(change_id)
+    .load::<RowValue>(conn)?;
+    Ok(values
+        .into_iter()
+        .map(|v| {
+            let is_blob = BLOB_COLUMNS.contains(&(entity_type, v.key.as_str()));
+            let literal = if is_blob && v.literal != "NULL" {
+                // 'ABCD' => X'ABCD'
+                format!("X{}", v.literal)
+            } else {
+                v.literal
+            };
+            (v.key, literal)
+        })
+        .collect())
+}
+
+fn mark_undone(conn: &SqliteConnection, change_ids: &[i32]) -> Result<()> {
+    diesel::sql_query(format!(
+        "UPDATE change_log SET undone_at = strftime('%Y-%m-%d %H:%M:%f', 'now') \
+          WHERE id IN ({})",
+        change_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+    .execute(conn)?;
+    Ok(())
+}
+
+fn insert_deleted_row(
+    conn: &SqliteConnection,
+    row: &DeletedRow,
+    new_ids: &HashMap<(EntityType, i32), i32>,
+) -> Result<i32> {
+    let id_taken = diesel::sql_query(format!(
+        "SELECT id AS key, id AS literal FROM {} WHERE id = ?",
+        row.entity_type
+    ))
+    .bind::<Integer, _>(row.entity_id)
+    .load::<RowValue>(conn)?
+    .into_iter()
+    .next()
+    .is_some();
+    let mut columns = vec![];
+    let mut literals = vec![];
+    for (column, literal) in &row.values {
+        if column == "id" && id_taken {
+            // let sqlite pick a new id
+            continue;
+        }
+        let remapped = REFERENCES
+            .iter()
+            .find(|(t, c, _, _)| *t == row.entity_type && c == column)
+            .and_then(|(_, _, target, _)| {
+                row.reference(column)
+                    .and_then(|old_id| new_ids.get(&(*target, old_id)))
+            });
+        columns.push(quote_identifier(column));
+        literals.push(
+            remapped
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| literal.clone()),
+        );
+    }
+    insert_row(
+        conn,
+        diesel::sql_query(format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            row.entity_type,
+            columns.join(", "),
+            literals.join(", ")
+        )),
+    )
+}
+
+/// re-create a deleted entity, together with the children that
+/// its delete cascaded to. Returns the id of the restored entity,
+/// which is its original id, unless that id was reused in the meantime.
+pub fn restore_deleted(conn: &SqliteConnection, change_id: i32) -> Result<i32> {
+    conn.transaction(|| {
+        // the deletes of the same statement share the timestamp
+        let deleted = diesel::sql_query(
+            "SELECT id, entity_type, entity_id FROM change_log \
+              WHERE operation = 'delete' AND undone_at IS NULL \
+                AND changed_at = (SELECT changed_at FROM change_log WHERE id = ?) \
+              ORDER BY id",
+        )
+        .bind::<Integer, _>(change_id)
+        .load::<DeletedEntity>(conn)?;
+        if !deleted.iter().any(|d| d.id == change_id) {
+            return Err(match get_change(conn, change_id)? {
+                c if c.undone_at.is_some() => {
+                    Error::CannotUndo("it was undone already".to_string())
+                }
+                _ => Error::CannotUndo("it is not a delete".to_string()),
+            });
+        }
+        let root_key = deleted
+            .iter()
+            .find(|d| d.id == change_id)
+            .map(|d| (d.entity_type, d.entity_id))
+            .unwrap();
+        let mut group = deleted
+            .into_iter()
+            .map(|d| {
+                Ok(DeletedRow {
+                    change_id: d.id,
+                    entity_type: d.entity_type,
+                    entity_id: d.entity_id,
+                    values: row_values(conn, d.id, d.entity_type, "old_row")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // the rows to restore: the requested one and the children it cascaded to
+        let mut to_restore = vec![];
+        let mut pending = vec![];
+        loop {
+            let (matching, rest): (Vec<_>, Vec<_>) = group.into_iter().partition(|r| {
+                r.change_id == change_id
+                    || REFERENCES.iter().any(|(t, column, target, cascade)| {
+                        *cascade
+                            && *t == r.entity_type
+                            && r.reference(column)
+                                .map_or(false, |id| to_restore.contains(&(*target, id)))
+                    })
+            });
+            group = rest;
+            if matching.is_empty() {
+                break;
+            }
+            to_restore.extend(matching.iter().map(|r| (r.entity_type, r.entity_id)));
+            pending.extend(matching);
+        }
+
+        // restore the rows after the rows they reference
+        let mut new_ids = HashMap::new();
+        while !pending.is_empty() {
+            let pending_keys: HashSet<_> = pending
+                .iter()
+                .map(|r| (r.entity_type, r.entity_id))
+                .collect();
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|r| {
+                REFERENCES.iter().all(|(t, column, target, _)| {
+                    *t != r.entity_type
+                        || r.reference(column)
+                            .map_or(true, |id| !pending_keys.contains(&(*target, id)))
+                })
+            });
+            if ready.is_empty() {
+                return Err(Error::CannotUndo(
+                    "the deleted entities reference each other".to_string(),
+                ));
+            }
+            for row in &ready {
+                let new_id = insert_deleted_row(conn, row, &new_ids)?;
+                new_ids.insert((row.entity_type, row.entity_id), new_id);
+            }
+            mark_undone(conn, &ready.iter().map(|r| r.change_id).collect::<Vec<_>>())?;
+            pending = waiting;
+        }
+        Ok(new_ids[&root_key])
+    })
+}
+
+/// set a field modified by an update back to its previous value
+pub fn revert_field(conn: &SqliteConnection, change_id: i32, field: &str) -> Result<()> {
+    let change = get_change(conn, change_id)?;
+    if change.operation != ChangeOperation::Update {
+        return Err(Error::CannotUndo(
+            "only edits can be reverted field by field".to_string(),
+        ));
+    }
+    if field == "id" {
+        return Err(Error::CannotUndo("the id can't be reverted".to_string()));
+    }
+    let literal = row_values(conn, change_id, change.entity_type, "old_row")?
+        .into_iter()
+        .find(|(k, _)| k == field)
+        .map(|(_, v)| v)
+        .ok_or_else(|| Error::CannotUndo(format!("unknown field {}", field)))?;
+    check_modified(
+        diesel::sql_query(format!(
+            "UPDATE {} SET {} = {} WHERE id = ?",
+            change.entity_type,
+            quote_identifier(field),
+            literal
+        ))
+        .bind::<Integer, _>(change.entity_id)
+        .execute(conn)?,
+    )
+}
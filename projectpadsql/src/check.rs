@@ -0,0 +1,519 @@
+// referential checks: the database can contain references which
+// the foreign keys don't catch, because old versions of ppcli and
+// projectpad didn't enforce them. We report them, with a fix for
+// each of them. Items in the trash are ignored.
+use crate::error::Result;
+use crate::models::EntityType;
+use crate::repo::check_modified;
+use crate::schema::server::dsl as srv;
+use crate::schema::server_link::dsl as srv_link;
+use crate::schema::server_website::dsl as srv_www;
+use diesel::prelude::*;
+use diesel::sql_types::{Binary, Integer, Nullable, Text};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IssueKind {
+    /// a server link to a server which doesn't exist or is in the trash
+    DanglingServerLink,
+    /// a website using a database which doesn't exist or is in the trash
+    DanglingWebsiteDatabase,
+    /// a server tunneling through a server which doesn't exist or is in the trash
+    DanglingTunnel,
+    /// servers tunneling through each other
+    TunnelCycle,
+    /// servers with the same description in the same environment of a
+    /// project: the export refers to the servers by description
+    DuplicateDescription,
+    /// a group name with only spaces
+    EmptyGroupName,
+    /// an authentication key which is not a private key in text format
+    UnreadableAuthKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// delete the item, it refers to something which doesn't exist anymore
+    Delete,
+    /// move the server link to the trash with its server,
+    /// so that restoring the server restores the link
+    MoveToTrash,
+    /// connect to the server directly, without ssh tunnel
+    ClearTunnel,
+    /// the website doesn't use a database anymore
+    ClearDatabase,
+    /// give the server this unique description
+    Rename(String),
+    /// move the item out of its group
+    ClearGroupName,
+    /// remove the authentication key
+    RemoveAuthKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub entity_type: EntityType,
+    pub entity_id: i32,
+    pub project_name: String,
+    /// the description of the item, preceded by the
+    /// description of its server for the server items
+    pub title: String,
+    /// what's wrong
+    pub detail: String,
+    pub fix: Fix,
+}
+
+/// an item referring to another, which doesn't exist or is in the trash
+#[derive(QueryableByName)]
+struct ReferenceRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    project_name: String,
+    #[sql_type = "Text"]
+    title: String,
+    #[sql_type = "Integer"]
+    target_id: i32,
+    /// None if the target doesn't exist
+    #[sql_type = "Nullable<Text>"]
+    target_desc: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct ServerRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Integer"]
+    project_id: i32,
+    #[sql_type = "Text"]
+    project_name: String,
+    #[sql_type = "Text"]
+    environment: String,
+    #[sql_type = "Text"]
+    desc: String,
+    #[sql_type = "Nullable<Integer>"]
+    ssh_tunnel_through_server_id: Option<i32>,
+}
+
+#[derive(QueryableByName)]
+struct GroupRow {
+    #[sql_type = "Text"]
+    entity_type: EntityType,
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    project_name: String,
+    #[sql_type = "Text"]
+    title: String,
+}
+
+#[derive(QueryableByName)]
+struct AuthKeyRow {
+    #[sql_type = "Text"]
+    entity_type: EntityType,
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    project_name: String,
+    #[sql_type = "Text"]
+    title: String,
+    #[sql_type = "Nullable<Binary>"]
+    auth_key: Option<Vec<u8>>,
+    #[sql_type = "Nullable<Text>"]
+    auth_key_filename: Option<String>,
+}
+
+/// all the issues of the database, by kind, then by project and title
+pub fn check(conn: &SqliteConnection) -> Result<Vec<Issue>> {
+    let servers: Vec<ServerRow> = diesel::sql_query(
+        "SELECT server.id, server.project_id, project.name AS project_name, \
+                server.environment, server.desc, server.ssh_tunnel_through_server_id \
+           FROM server JOIN project ON project.id = server.project_id \
+          WHERE server.deleted_at IS NULL \
+          ORDER BY project.name, server.desc, server.id",
+    )
+    .load(conn)?;
+    let mut issues = vec![];
+    issues.extend(dangling_server_links(conn)?);
+    issues.extend(dangling_website_databases(conn)?);
+    issues.extend(dangling_tunnels(conn)?);
+    issues.extend(tunnel_cycles(&servers));
+    issues.extend(duplicate_descriptions(&servers));
+    issues.extend(empty_group_names(conn)?);
+    issues.extend(unreadable_auth_keys(conn)?);
+    Ok(issues)
+}
+
+fn missing_target_detail(what: &str, row: &ReferenceRow) -> String {
+    match &row.target_desc {
+        Some(desc) => format!("{} '{}' is in the trash", what, desc),
+        None => format!("{} {} doesn't exist", what, row.target_id),
+    }
+}
+
+fn dangling_server_links(conn: &SqliteConnection) -> Result<Vec<Issue>> {
+    let rows: Vec<ReferenceRow> = diesel::sql_query(
+        "SELECT link.id, project.name AS project_name, link.desc AS title, \
+                link.linked_server_id AS target_id, server.desc AS target_desc \
+           FROM server_link link \
+           JOIN project ON project.id = link.project_id \
+           LEFT JOIN server ON server.id = link.linked_server_id \
+          WHERE link.deleted_at IS NULL \
+            AND (server.id IS NULL OR server.deleted_at IS NOT NULL) \
+          ORDER BY project.name, link.desc",
+    )
+    .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Issue {
+            kind: IssueKind::DanglingServerLink,
+            entity_type: EntityType::ServerLink,
+            entity_id: row.id,
+            detail: missing_target_detail("the linked server", &row),
+            fix: if row.target_desc.is_some() {
+                Fix::MoveToTrash
+            } else {
+                Fix::Delete
+            },
+            project_name: row.project_name,
+            title: row.title,
+        })
+        .collect())
+}
+
+fn dangling_website_databases(conn: &SqliteConnection) -> Result<Vec<Issue>> {
+    let rows: Vec<ReferenceRow> = diesel::sql_query(
+        "SELECT www.id, project.name AS project_name, server.desc || ' > ' || www.desc AS title, \
+                www.server_database_id AS target_id, db.desc AS target_desc \
+           FROM server_website www \
+           JOIN server ON server.id = www.server_id \
+           JOIN project ON project.id = server.project_id \
+           LEFT JOIN server_database db ON db.id = www.server_database_id \
+          WHERE www.deleted_at IS NULL AND www.server_database_id IS NOT NULL \
+            AND (db.id IS NULL OR db.deleted_at IS NOT NULL) \
+          ORDER BY project.name, server.desc, www.desc",
+    )
+    .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Issue {
+            kind: IssueKind::DanglingWebsiteDatabase,
+            entity_type: EntityType::ServerWebsite,
+            entity_id: row.id,
+            detail: missing_target_detail("the database", &row),
+            fix: Fix::ClearDatabase,
+            project_name: row.project_name,
+            title: row.title,
+        })
+        .collect())
+}
+
+fn dangling_tunnels(conn: &SqliteConnection) -> Result<Vec<Issue>> {
+    let rows: Vec<ReferenceRow> = diesel::sql_query(
+        "SELECT server.id, project.name AS project_name, server.desc AS title, \
+                server.ssh_tunnel_through_server_id AS target_id, tunnel.desc AS target_desc \
+           FROM server \
+           JOIN project ON project.id = server.project_id \
+           LEFT JOIN server tunnel ON tunnel.id = server.ssh_tunnel_through_server_id \
+          WHERE server.deleted_at IS NULL AND server.ssh_tunnel_through_server_id IS NOT NULL \
+            AND (tunnel.id IS NULL OR tunnel.deleted_at IS NOT NULL) \
+          ORDER BY project.name, server.desc",
+    )
+    .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Issue {
+            kind: IssueKind::DanglingTunnel,
+            entity_type: EntityType::Server,
+            entity_id: row.id,
+            detail: missing_target_detail("the ssh tunnel server", &row),
+            fix: Fix::ClearTunnel,
+            project_name: row.project_name,
+            title: row.title,
+        })
+        .collect())
+}
+
+/// one issue per cycle, on one of its servers: removing
+/// the tunnel of any server of the cycle breaks it
+fn tunnel_cycles(servers: &[ServerRow]) -> Vec<Issue> {
+    let by_id: HashMap<i32, &ServerRow> = servers.iter().map(|s| (s.id, s)).collect();
+    let mut reported: HashSet<i32> = HashSet::new();
+    let mut issues = vec![];
+    for server in servers {
+        // follow the tunnels until we leave the servers or loop
+        let mut path = vec![server.id];
+        let mut cur = server;
+        while let Some(next) = cur
+            .ssh_tunnel_through_server_id
+            .and_then(|id| by_id.get(&id))
+        {
+            if let Some(pos) = path.iter().position(|id| *id == next.id) {
+                let cycle = &path[pos..];
+                if cycle.iter().all(|id| !reported.contains(id)) {
+                    reported.extend(cycle.iter().copied());
+                    // the server through which we entered the cycle
+                    let first = by_id[&cycle[0]];
+                    let mut descs: Vec<&str> =
+                        cycle.iter().map(|id| by_id[id].desc.as_str()).collect();
+                    descs.push(&first.desc);
+                    issues.push(Issue {
+                        kind: IssueKind::TunnelCycle,
+                        entity_type: EntityType::Server,
+                        entity_id: first.id,
+                        project_name: first.project_name.clone(),
+                        title: first.desc.clone(),
+                        detail: if cycle.len() == 1 {
+                            "the server tunnels through itself".to_string()
+                        } else {
+                            format!("the ssh tunnels loop: {}", descs.join(" -> "))
+                        },
+                        fix: Fix::ClearTunnel,
+                    });
+                }
+                break;
+            }
+            path.push(next.id);
+            cur = next;
+        }
+    }
+    issues
+}
+
+/// the first server keeps its description, the others get a number
+fn duplicate_descriptions(servers: &[ServerRow]) -> Vec<Issue> {
+    let mut used: HashMap<(i32, &str), HashSet<String>> = HashMap::new();
+    for server in servers {
+        used.entry((server.project_id, &server.environment))
+            .or_default()
+            .insert(server.desc.clone());
+    }
+    let mut seen = HashSet::new();
+    let mut issues = vec![];
+    // the servers are sorted by id within a description
+    for server in servers {
+        // servers without description are referred to by id
+        if server.desc.is_empty()
+            || seen.insert((server.project_id, &server.environment, &server.desc))
+        {
+            continue;
+        }
+        let descs = used
+            .get_mut(&(server.project_id, &server.environment))
+            .unwrap();
+        let new_desc = (2..)
+            .map(|n| format!("{} ({})", server.desc, n))
+            .find(|d| !descs.contains(d))
+            .unwrap();
+        descs.insert(new_desc.clone());
+        issues.push(Issue {
+            kind: IssueKind::DuplicateDescription,
+            entity_type: EntityType::Server,
+            entity_id: server.id,
+            project_name: server.project_name.clone(),
+            title: server.desc.clone(),
+            detail: format!(
+                "another server of the {} environment has the same description",
+                server.environment
+            ),
+            fix: Fix::Rename(new_desc),
+        });
+    }
+    issues
+}
+
+fn empty_group_names(conn: &SqliteConnection) -> Result<Vec<Issue>> {
+    let empty = |column: &str| format!("TRIM(t.{}) = ''", column);
+    let project_items = [
+        (EntityType::Server, "t.desc", empty("group_name")),
+        (EntityType::ProjectPoi, "t.desc", empty("group_name")),
+        (EntityType::ProjectNote, "t.title", empty("group_name")),
+        (
+            EntityType::ServerLink,
+            "t.desc",
+            format!(
+                "({} OR {})",
+                empty("group_name"),
+                empty("linked_group_name")
+            ),
+        ),
+    ];
+    let server_items = [
+        (EntityType::ServerPoi, "t.desc"),
+        (EntityType::ServerWebsite, "t.desc"),
+        (EntityType::ServerDatabase, "t.desc"),
+        (EntityType::ServerNote, "t.title"),
+        (
+            EntityType::ServerExtraUserAccount,
+            "CASE WHEN t.desc = '' THEN t.username ELSE t.desc END",
+        ),
+    ];
+    let selects: Vec<String> = project_items
+        .iter()
+        .map(|(entity_type, title, condition)| {
+            format!(
+                "SELECT '{0}' AS entity_type, t.id, p.name AS project_name, {1} AS title \
+                   FROM {0} t JOIN project p ON p.id = t.project_id \
+                  WHERE t.deleted_at IS NULL AND {2}",
+                entity_type, title, condition
+            )
+        })
+        .chain(server_items.iter().map(|(entity_type, title)| {
+            format!(
+                "SELECT '{0}' AS entity_type, t.id, p.name AS project_name, \
+                        s.desc || ' > ' || {1} AS title \
+                   FROM {0} t JOIN server s ON s.id = t.server_id \
+                   JOIN project p ON p.id = s.project_id \
+                  WHERE t.deleted_at IS NULL AND {2}",
+                entity_type,
+                title,
+                empty("group_name")
+            )
+        }))
+        .collect();
+    let rows: Vec<GroupRow> = diesel::sql_query(format!(
+        "{} ORDER BY project_name, title",
+        selects.join(" UNION ALL ")
+    ))
+    .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Issue {
+            kind: IssueKind::EmptyGroupName,
+            entity_type: row.entity_type,
+            entity_id: row.id,
+            project_name: row.project_name,
+            title: row.title,
+            detail: "the group name is empty".to_string(),
+            fix: Fix::ClearGroupName,
+        })
+        .collect())
+}
+
+/// why the key can't be used, if it can't
+fn auth_key_problem(auth_key: Option<&[u8]>) -> Option<&'static str> {
+    match auth_key.map(std::str::from_utf8) {
+        None => Some("the authentication key has a file name, but no contents"),
+        Some(Ok(key)) if key.trim().is_empty() => Some("the authentication key is empty"),
+        Some(Ok(key)) if key.starts_with("ssh-") || key.starts_with("ecdsa-") => {
+            Some("the authentication key is a public key, not a private key")
+        }
+        Some(Ok(key))
+            if key.contains("PRIVATE KEY-----") || key.starts_with("PuTTY-User-Key-File") =>
+        {
+            None
+        }
+        _ => Some("the authentication key is not a private key in text format"),
+    }
+}
+
+fn unreadable_auth_keys(conn: &SqliteConnection) -> Result<Vec<Issue>> {
+    let rows: Vec<AuthKeyRow> = diesel::sql_query(
+        "SELECT 'server' AS entity_type, server.id, project.name AS project_name, \
+                server.desc AS title, server.auth_key, server.auth_key_filename \
+           FROM server JOIN project ON project.id = server.project_id \
+          WHERE server.deleted_at IS NULL \
+            AND (server.auth_key IS NOT NULL OR server.auth_key_filename IS NOT NULL) \
+         UNION ALL \
+         SELECT 'server_extra_user_account', usr.id, project.name, \
+                server.desc || ' > ' || CASE WHEN usr.desc = '' THEN usr.username ELSE usr.desc END, \
+                usr.auth_key, usr.auth_key_filename \
+           FROM server_extra_user_account usr \
+           JOIN server ON server.id = usr.server_id \
+           JOIN project ON project.id = server.project_id \
+          WHERE usr.deleted_at IS NULL \
+            AND (usr.auth_key IS NOT NULL OR usr.auth_key_filename IS NOT NULL) \
+          ORDER BY project_name, title",
+    )
+    .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let problem = auth_key_problem(row.auth_key.as_deref())?;
+            Some(Issue {
+                kind: IssueKind::UnreadableAuthKey,
+                entity_type: row.entity_type,
+                entity_id: row.id,
+                project_name: row.project_name,
+                title: row.title,
+                detail: match &row.auth_key_filename {
+                    Some(fname) => format!("{} ({})", problem, fname),
+                    None => problem.to_string(),
+                },
+                fix: Fix::RemoveAuthKey,
+            })
+        })
+        .collect())
+}
+
+/// apply the fix of an issue
+pub fn fix(conn: &SqliteConnection, issue: &Issue) -> Result<()> {
+    let id = issue.entity_id;
+    match &issue.fix {
+        Fix::Delete => {
+            check_modified(diesel::delete(srv_link::server_link.find(id)).execute(conn)?)
+        }
+        // like the trash_server_children trigger of migration 25
+        Fix::MoveToTrash => check_modified(
+            diesel::sql_query(
+                "UPDATE server_link SET deleted_at = \
+                     (SELECT deleted_at FROM server WHERE server.id = linked_server_id) \
+                  WHERE id = ?",
+            )
+            .bind::<Integer, _>(id)
+            .execute(conn)?,
+        ),
+        Fix::ClearTunnel => check_modified(
+            diesel::update(srv::server.find(id))
+                .set((
+                    srv::ssh_tunnel_through_server_id.eq(None::<i32>),
+                    srv::ssh_tunnel_port.eq(None::<i32>),
+                ))
+                .execute(conn)?,
+        ),
+        Fix::ClearDatabase => check_modified(
+            diesel::update(srv_www::server_website.find(id))
+                .set(srv_www::server_database_id.eq(None::<i32>))
+                .execute(conn)?,
+        ),
+        Fix::Rename(desc) => check_modified(
+            diesel::update(srv::server.find(id))
+                .set(srv::desc.eq(desc))
+                .execute(conn)?,
+        ),
+        Fix::ClearGroupName => {
+            let linked_group = if issue.entity_type == EntityType::ServerLink {
+                ", linked_group_name = NULLIF(TRIM(linked_group_name), '')"
+            } else {
+                ""
+            };
+            check_modified(
+                diesel::sql_query(format!(
+                    "UPDATE {} SET group_name = NULLIF(TRIM(group_name), ''){} WHERE id = ?",
+                    issue.entity_type, linked_group
+                ))
+                .bind::<Integer, _>(id)
+                .execute(conn)?,
+            )
+        }
+        Fix::RemoveAuthKey => check_modified(
+            diesel::sql_query(format!(
+                "UPDATE {} SET auth_key = NULL, auth_key_filename = NULL WHERE id = ?",
+                issue.entity_type
+            ))
+            .bind::<Integer, _>(id)
+            .execute(conn)?,
+        ),
+    }
+}
+
+/// apply the fixes of all the issues, all or none of them
+pub fn fix_all(conn: &SqliteConnection, issues: &[Issue]) -> Result<()> {
+    conn.transaction(|| {
+        for issue in issues {
+            fix(conn, issue)?;
+        }
+        Ok(())
+    })
+}
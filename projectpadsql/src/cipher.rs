@@ -0,0 +1,274 @@
+// the encryption settings of the database files. The databases were always
+// created with the sqlcipher 3 settings (see `try_unlock_db_with_format`),
+// `upgrade` converts a database to the stronger sqlcipher 4 defaults. The
+// format is recorded in a small unencrypted file next to the database,
+// projectpad.db.cipher, so that both applications know which PRAGMAs to
+// use. No such file means the sqlcipher 3 settings.
+//
+// Switching a database to the other format means replacing the file and
+// updating that sidecar file, which can't happen atomically. So the new
+// file is first renamed to projectpad.db.sqlcipher<new format>.tmp, then
+// the sidecar is updated, then the file is renamed to projectpad.db.
+// While the pending file of the recorded format exists, the database still
+// has the other format: the format is known at every step, and running the
+// operation again completes an interrupted switch.
+use crate::error::{Error, Result};
+use crate::key_escape_param_value;
+use diesel::prelude::*;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherFormat {
+    /// 1024-byte pages, 64000 iterations of PBKDF2-HMAC-SHA1, HMAC-SHA1
+    Sqlcipher3Compat,
+    /// the sqlcipher 4 defaults: 4096-byte pages, 256000 iterations
+    /// of PBKDF2-HMAC-SHA512, HMAC-SHA512
+    Sqlcipher4,
+}
+
+impl CipherFormat {
+    fn version(self) -> u32 {
+        match self {
+            CipherFormat::Sqlcipher3Compat => 3,
+            CipherFormat::Sqlcipher4 => 4,
+        }
+    }
+
+    fn from_version(version: &str) -> Option<CipherFormat> {
+        match version {
+            "3" => Some(CipherFormat::Sqlcipher3Compat),
+            "4" => Some(CipherFormat::Sqlcipher4),
+            _ => None,
+        }
+    }
+
+    fn other(self) -> CipherFormat {
+        match self {
+            CipherFormat::Sqlcipher3Compat => CipherFormat::Sqlcipher4,
+            CipherFormat::Sqlcipher4 => CipherFormat::Sqlcipher3Compat,
+        }
+    }
+
+    /// the PRAGMAs to run after PRAGMA key. `schema` is empty for the main
+    /// database, or the name of an attached database followed by a dot.
+    pub(crate) fn pragmas(self, schema: &str) -> String {
+        let (page_size, kdf_iter, algorithm) = match self {
+            CipherFormat::Sqlcipher3Compat => (1024, 64000, "SHA1"),
+            CipherFormat::Sqlcipher4 => (4096, 256_000, "SHA512"),
+        };
+        format!(
+            "PRAGMA {0}cipher_page_size = {1}; PRAGMA {0}kdf_iter = {2}; \
+             PRAGMA {0}cipher_hmac_algorithm = HMAC_{3}; PRAGMA {0}cipher_kdf_algorithm = PBKDF2_HMAC_{3};",
+            schema, page_size, kdf_iter, algorithm
+        )
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// the unencrypted file which records the format of the database
+pub fn sidecar_path(db_path: &Path) -> PathBuf {
+    with_suffix(db_path, ".cipher")
+}
+
+/// the database as it was before `upgrade`, for `rollback`
+pub fn rollback_path(db_path: &Path) -> PathBuf {
+    with_suffix(db_path, ".sqlcipher3.bak")
+}
+
+fn pending_path(db_path: &Path, format: CipherFormat) -> PathBuf {
+    with_suffix(db_path, &format!(".sqlcipher{}.tmp", format.version()))
+}
+
+fn invalid_sidecar(sidecar: &Path) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unknown cipher format in {}", sidecar.display()),
+    ))
+}
+
+fn recorded_format(db_path: &Path) -> Result<CipherFormat> {
+    let sidecar = sidecar_path(db_path);
+    let contents = match fs::read_to_string(&sidecar) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(CipherFormat::Sqlcipher3Compat),
+        Err(e) => return Err(e.into()),
+    };
+    contents
+        .lines()
+        .map(str::trim)
+        .find_map(|l| l.strip_prefix("format="))
+        .and_then(|v| CipherFormat::from_version(v.trim()))
+        .ok_or_else(|| invalid_sidecar(&sidecar))
+}
+
+/// the format of the database file at `db_path`, see the module comment
+pub fn format_of(db_path: &Path) -> Result<CipherFormat> {
+    let recorded = recorded_format(db_path)?;
+    Ok(if pending_path(db_path, recorded).exists() {
+        recorded.other()
+    } else {
+        recorded
+    })
+}
+
+fn write_sidecar(db_path: &Path, format: CipherFormat) -> Result<()> {
+    let sidecar = sidecar_path(db_path);
+    let tmp = with_suffix(&sidecar, ".tmp");
+    fs::write(
+        &tmp,
+        format!(
+            "# the encryption settings of {}, written by projectpad\nformat={}\n",
+            db_path
+                .file_name()
+                .map(OsStr::to_string_lossy)
+                .unwrap_or_default(),
+            format.version()
+        ),
+    )?;
+    fs::rename(&tmp, &sidecar)?;
+    Ok(())
+}
+
+/// give the database at `to_db` the format of the database at `from_db`,
+/// for instance after copying it. `from_db` is None for in-memory databases.
+pub(crate) fn copy_sidecar(from_db: Option<&Path>, to_db: &Path) -> Result<()> {
+    let format = match from_db {
+        Some(from) => format_of(from)?,
+        None => CipherFormat::Sqlcipher3Compat,
+    };
+    let sidecar = sidecar_path(to_db);
+    if format != CipherFormat::Sqlcipher3Compat {
+        write_sidecar(to_db, format)
+    } else if sidecar.exists() {
+        Ok(fs::remove_file(sidecar)?)
+    } else {
+        Ok(())
+    }
+}
+
+/// copy the database file, and its format
+pub(crate) fn copy_database(from_db: &Path, to_db: &Path) -> Result<()> {
+    copy_sidecar(Some(from_db), to_db)?;
+    fs::copy(from_db, to_db)?;
+    Ok(())
+}
+
+/// complete a switch which was interrupted after updating the sidecar file,
+/// drop one which was interrupted before
+pub(crate) fn finish_pending_switch(db_path: &Path) -> Result<()> {
+    let recorded = recorded_format(db_path)?;
+    let pending = pending_path(db_path, recorded);
+    if pending.exists() {
+        fs::rename(&pending, db_path)?;
+    }
+    let aborted = pending_path(db_path, recorded.other());
+    if aborted.exists() {
+        fs::remove_file(aborted)?;
+    }
+    Ok(())
+}
+
+/// replace the database at `db_path` with `new_file`, which has the format `format`
+pub(crate) fn switch_database(db_path: &Path, new_file: &Path, format: CipherFormat) -> Result<()> {
+    finish_pending_switch(db_path)?;
+    if recorded_format(db_path)? == format {
+        fs::rename(new_file, db_path)?;
+        return Ok(());
+    }
+    let pending = pending_path(db_path, format);
+    fs::rename(new_file, &pending)?;
+    write_sidecar(db_path, format)?;
+    fs::rename(&pending, db_path)?;
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct CipherVersionRow {
+    #[sql_type = "diesel::sql_types::Text"]
+    cipher_version: String,
+}
+
+fn open(db_path: &Path, pass: &str, format: CipherFormat) -> Result<SqliteConnection> {
+    let conn = SqliteConnection::establish(&db_path.to_string_lossy())
+        .map_err(|e| Error::Corrupt(e.to_string()))?;
+    crate::try_unlock_db_with_format(&conn, pass, format)?;
+    Ok(conn)
+}
+
+/// convert the database at `db_path` to the sqlcipher 4 format, keeping
+/// the original file for `rollback`. The applications must not be using the
+/// database: the changes made during the conversion would be lost.
+/// Returns false if the database had the sqlcipher 4 format already.
+pub fn upgrade(db_path: &Path, pass: &str) -> Result<bool> {
+    finish_pending_switch(db_path)?;
+    if format_of(db_path)? == CipherFormat::Sqlcipher4 {
+        return Ok(false);
+    }
+    let conn = open(db_path, pass, CipherFormat::Sqlcipher3Compat)?;
+    // sqlcipher 3 ignores the PRAGMAs it doesn't know, it would
+    // export to a file with the sqlcipher 3 settings
+    let version = diesel::sql_query("PRAGMA cipher_version")
+        .load::<CipherVersionRow>(&conn)?
+        .into_iter()
+        .next()
+        .map(|r| r.cipher_version)
+        .unwrap_or_default();
+    if !version.starts_with("4.") {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "the sqlcipher 4 format requires sqlcipher 4, this application uses '{}'",
+                version
+            ),
+        )));
+    }
+    let export = with_suffix(db_path, ".sqlcipher4.export");
+    if export.exists() {
+        fs::remove_file(&export)?;
+    }
+    // https://www.zetetic.net/sqlcipher/sqlcipher-api/#sqlcipher_export
+    conn.execute(&format!(
+        "ATTACH DATABASE '{}' AS sqlcipher4 KEY '{}'; {} \
+         SELECT sqlcipher_export('sqlcipher4'); DETACH DATABASE sqlcipher4;",
+        key_escape_param_value(&export.to_string_lossy()),
+        key_escape_param_value(pass),
+        CipherFormat::Sqlcipher4.pragmas("sqlcipher4.")
+    ))?;
+    drop(conn);
+    let export_conn = open(&export, pass, CipherFormat::Sqlcipher4)?;
+    crate::backup::check_integrity(&export_conn)?;
+    drop(export_conn);
+
+    copy_database(db_path, &rollback_path(db_path))?;
+    switch_database(db_path, &export, CipherFormat::Sqlcipher4)?;
+    Ok(true)
+}
+
+/// go back to the database from before `upgrade`. The changes made
+/// since the upgrade are lost. Returns false if the database
+/// had the sqlcipher 3 format already.
+pub fn rollback(db_path: &Path) -> Result<bool> {
+    finish_pending_switch(db_path)?;
+    if format_of(db_path)? == CipherFormat::Sqlcipher3Compat {
+        return Ok(false);
+    }
+    let original = rollback_path(db_path);
+    if !original.is_file() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} doesn't exist", original.display()),
+        )));
+    }
+    let restored = with_suffix(db_path, ".sqlcipher3.export");
+    fs::copy(&original, &restored)?;
+    switch_database(db_path, &restored, CipherFormat::Sqlcipher3Compat)?;
+    Ok(true)
+}
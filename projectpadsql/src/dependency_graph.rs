@@ -0,0 +1,234 @@
+// the dependencies between the servers, as a graph: the topology of an
+// environment of a project, and the servers affected when a server is
+// down for maintenance. The dependencies are the rows of server_dependency,
+// and the dependencies implied by the websites using a database: the
+// server of the website depends on the server of the database.
+// The servers in the trash are left out.
+use crate::error::Result;
+use crate::models::{DependencyType, Server};
+use crate::schema::project::dsl as prj;
+use crate::schema::server::dsl as srv;
+use crate::schema::server_link::dsl as srv_link;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// the server `server_id` depends on the server `depends_on_server_id`
+#[derive(Debug, Clone, PartialEq, Eq, QueryableByName)]
+pub struct DependencyEdge {
+    /// the id of the `ServerDependency`, None for a dependency
+    /// implied by websites using a database
+    #[sql_type = "Nullable<Integer>"]
+    pub dependency_id: Option<i32>,
+    #[sql_type = "Integer"]
+    pub server_id: i32,
+    #[sql_type = "Integer"]
+    pub depends_on_server_id: i32,
+    #[sql_type = "Text"]
+    pub dependency_type: DependencyType,
+    /// for the implied dependencies, the websites using the database
+    #[sql_type = "Text"]
+    pub desc: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphServer {
+    pub server: Server,
+    pub project_name: String,
+    /// a server of another environment or project, which
+    /// depends on a server of the graph or is needed by one
+    pub external: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// sorted by description, the external servers last
+    pub servers: Vec<GraphServer>,
+    pub edges: Vec<DependencyEdge>,
+}
+
+impl DependencyGraph {
+    /// the indexes of the servers by layer, for the diagram: the servers
+    /// nothing depends on first, then the servers they depend on, and so
+    /// on. A server is in the layer after the deepest server which depends
+    /// on it. The dependency cycles are cut arbitrarily.
+    pub fn layers(&self) -> Vec<Vec<usize>> {
+        let index: HashMap<i32, usize> = self
+            .servers
+            .iter()
+            .enumerate()
+            .map(|(idx, s)| (s.server.id, idx))
+            .collect();
+        let links: Vec<(usize, usize)> = self
+            .edges
+            .iter()
+            .filter_map(|e| {
+                Some((
+                    *index.get(&e.server_id)?,
+                    *index.get(&e.depends_on_server_id)?,
+                ))
+            })
+            .collect();
+        let mut depth = vec![0; self.servers.len()];
+        // a path without cycle visits every server at most once,
+        // after that we're going around a cycle
+        for _ in 0..self.servers.len() {
+            let mut changed = false;
+            for (from, to) in &links {
+                if depth[*to] < depth[*from] + 1 && depth[*from] + 1 < self.servers.len() {
+                    depth[*to] = depth[*from] + 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let mut layers = vec![vec![]; depth.iter().max().map_or(0, |d| d + 1)];
+        for (idx, d) in depth.into_iter().enumerate() {
+            layers[d].push(idx);
+        }
+        layers
+    }
+}
+
+/// a server which depends on another, directly or not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependent {
+    pub server: Server,
+    pub project_name: String,
+    /// 1 for the servers which depend directly on the server,
+    /// 2 for the servers which depend on those, and so on
+    pub distance: usize,
+    /// the dependency through which the server depends on the server,
+    /// or on a server between them
+    pub edge: DependencyEdge,
+}
+
+/// all the dependencies between servers which are not in the trash
+fn all_edges(conn: &SqliteConnection) -> Result<Vec<DependencyEdge>> {
+    Ok(diesel::sql_query(
+        "SELECT dep.id AS dependency_id, dep.server_id, dep.depends_on_server_id,
+                dep.dependency_type, dep.desc
+           FROM server_dependency dep
+           JOIN server s ON s.id = dep.server_id
+           JOIN server t ON t.id = dep.depends_on_server_id
+          WHERE s.deleted_at IS NULL AND t.deleted_at IS NULL
+         UNION ALL
+         SELECT NULL, w.server_id, db.server_id, 'Database', group_concat(w.desc, ', ')
+           FROM server_website w
+           JOIN server_database db ON db.id = w.server_database_id
+           JOIN server s ON s.id = w.server_id
+           JOIN server t ON t.id = db.server_id
+          WHERE w.deleted_at IS NULL AND db.deleted_at IS NULL
+            AND s.deleted_at IS NULL AND t.deleted_at IS NULL
+            AND w.server_id != db.server_id
+            AND NOT EXISTS (SELECT 1 FROM server_dependency dep
+                             WHERE dep.server_id = w.server_id
+                               AND dep.depends_on_server_id = db.server_id
+                               AND dep.dependency_type = 'Database')
+          GROUP BY w.server_id, db.server_id
+          ORDER BY 2, 3, 4",
+    )
+    .load(conn)?)
+}
+
+fn load_servers(conn: &SqliteConnection, ids: &[i32]) -> Result<Vec<(Server, String)>> {
+    Ok(srv::server
+        .inner_join(prj::project)
+        .filter(srv::id.eq_any(ids))
+        .order(srv::desc.asc())
+        .select((srv::server::all_columns(), prj::name))
+        .load(conn)?)
+}
+
+/// the servers of an environment of a project, and the servers of
+/// other projects linked in that environment (see `ServerLink`),
+/// with their dependencies. The servers of other environments or
+/// projects which they depend on, or which depend on them, are
+/// also in the graph, as external servers.
+pub fn for_environment(
+    conn: &SqliteConnection,
+    project_id: i32,
+    environment: &str,
+) -> Result<DependencyGraph> {
+    let mut member_ids: Vec<i32> = srv::server
+        .filter(srv::deleted_at.is_null())
+        .filter(srv::project_id.eq(project_id))
+        .filter(srv::environment.eq(environment))
+        .select(srv::id)
+        .load(conn)?;
+    member_ids.extend(
+        srv_link::server_link
+            .inner_join(srv::server)
+            .filter(srv_link::deleted_at.is_null())
+            .filter(srv::deleted_at.is_null())
+            .filter(srv_link::project_id.eq(project_id))
+            .filter(srv_link::environment.eq(environment))
+            .select(srv_link::linked_server_id)
+            .load::<i32>(conn)?,
+    );
+    let members: HashSet<i32> = member_ids.into_iter().collect();
+    let edges: Vec<DependencyEdge> = all_edges(conn)?
+        .into_iter()
+        .filter(|e| members.contains(&e.server_id) || members.contains(&e.depends_on_server_id))
+        .collect();
+    let mut ids: HashSet<i32> = members.clone();
+    ids.extend(
+        edges
+            .iter()
+            .flat_map(|e| vec![e.server_id, e.depends_on_server_id]),
+    );
+    let mut servers: Vec<GraphServer> = load_servers(conn, &ids.into_iter().collect::<Vec<_>>())?
+        .into_iter()
+        .map(|(server, project_name)| GraphServer {
+            external: !members.contains(&server.id),
+            server,
+            project_name,
+        })
+        .collect();
+    // stable: the servers stay sorted by description
+    servers.sort_by_key(|s| s.external);
+    Ok(DependencyGraph { servers, edges })
+}
+
+/// the servers which depend on a server, directly or not: the servers
+/// affected by a maintenance of the server. Sorted by distance then
+/// description, the closest first.
+pub fn dependents(conn: &SqliteConnection, server_id: i32) -> Result<Vec<Dependent>> {
+    let mut dependents_of: HashMap<i32, Vec<DependencyEdge>> = HashMap::new();
+    for edge in all_edges(conn)? {
+        dependents_of
+            .entry(edge.depends_on_server_id)
+            .or_default()
+            .push(edge);
+    }
+    // breadth-first, so that every server is found at its shortest distance
+    let mut found: HashMap<i32, (usize, DependencyEdge)> = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back((server_id, 0));
+    while let Some((id, distance)) = queue.pop_front() {
+        for edge in dependents_of.get(&id).into_iter().flatten() {
+            if edge.server_id != server_id && !found.contains_key(&edge.server_id) {
+                found.insert(edge.server_id, (distance + 1, edge.clone()));
+                queue.push_back((edge.server_id, distance + 1));
+            }
+        }
+    }
+    let mut result: Vec<Dependent> =
+        load_servers(conn, &found.keys().copied().collect::<Vec<_>>())?
+            .into_iter()
+            .filter_map(|(server, project_name)| {
+                let (distance, edge) = found.remove(&server.id)?;
+                Some(Dependent {
+                    server,
+                    project_name,
+                    distance,
+                    edge,
+                })
+            })
+            .collect();
+    // stable: the servers stay sorted by description
+    result.sort_by_key(|d| d.distance);
+    Ok(result)
+}
@@ -0,0 +1,116 @@
+use diesel::result::DatabaseErrorKind;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The errors returned by projectpadsql. Unlike diesel errors, they're meant
+/// to be displayed to the user. Send + Sync, so that they can be sent from the
+/// sql thread to the GUI thread.
+#[derive(Debug)]
+pub enum Error {
+    /// The database is encrypted with another password
+    WrongPassword,
+    /// The passwords are encrypted with another master passphrase, see `secrets`
+    WrongPassphrase,
+    /// The file is not an encrypted projectpad database
+    NotADatabase,
+    /// The password is correct, but the database file is damaged
+    Corrupt(String),
+    /// The database must be migrated before this application can use it
+    SchemaTooOld {
+        db_version: i32,
+        min_supported: i32,
+    },
+    /// The database was migrated by a newer version of the application
+    SchemaTooNew {
+        db_version: i32,
+        max_supported: i32,
+    },
+    /// The requested row doesn't exist
+    NotFound,
+    /// A unique, foreign key, not null or check constraint was violated
+    Constraint(String),
+    /// The change can't be undone, for instance because it was undone already
+    CannotUndo(String),
+    /// The entity can't be deleted, because other entities refer to it
+    InUse(String),
+    /// A value entered by the user is not valid, the message explains why
+    Invalid(String),
+    Io(std::io::Error),
+    /// Any other database error
+    Sql(diesel::result::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::WrongPassword => write!(f, "Wrong database password"),
+            Error::WrongPassphrase => write!(f, "Wrong master passphrase"),
+            Error::NotADatabase => write!(f, "The file is not an encrypted projectpad database"),
+            Error::Corrupt(msg) => write!(f, "The database is corrupt: {}", msg),
+            Error::SchemaTooOld {
+                db_version,
+                min_supported,
+            } => write!(
+                f,
+                "The database version ({}) is older than the oldest version supported by this application ({})",
+                db_version, min_supported
+            ),
+            Error::SchemaTooNew {
+                db_version,
+                max_supported,
+            } => write!(
+                f,
+                "The database version ({}) is newer than the newest version supported by this application ({})",
+                db_version, max_supported
+            ),
+            Error::NotFound => write!(f, "The entity was not found"),
+            Error::Constraint(msg) => write!(f, "Constraint violation: {}", msg),
+            Error::CannotUndo(msg) => write!(f, "The change can't be undone: {}", msg),
+            Error::InUse(msg) => write!(f, "The entity is in use: {}", msg),
+            Error::Invalid(msg) => write!(f, "{}", msg),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Sql(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Sql(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        match e {
+            diesel::result::Error::NotFound => Error::NotFound,
+            // sqlite reports NOT NULL and CHECK failures without a specific kind
+            diesel::result::Error::DatabaseError(kind, ref info)
+                if matches!(
+                    kind,
+                    DatabaseErrorKind::UniqueViolation | DatabaseErrorKind::ForeignKeyViolation
+                ) || info.message().contains("constraint failed") =>
+            {
+                Error::Constraint(info.message().to_string())
+            }
+            // SQLITE_CORRUPT
+            diesel::result::Error::DatabaseError(_, ref info)
+                if info.message() == "database disk image is malformed" =>
+            {
+                Error::Corrupt(info.message().to_string())
+            }
+            e => Error::Sql(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
@@ -1,12 +1,34 @@
 #[macro_use]
 extern crate diesel;
 
+pub mod backup;
+pub mod change_log;
+pub mod check;
+pub mod cipher;
+pub mod dependency_graph;
+pub mod error;
+pub mod migrations;
 pub mod models;
+pub mod password_audit;
+pub mod profile;
+pub mod repo;
 pub mod schema;
+pub mod search;
+pub mod secrets;
+pub mod sync;
+pub mod totp;
+pub mod trash;
+
+pub use backup::backup;
+pub use check::check;
+pub use error::{Error, Result};
+pub use search::search;
 
 use diesel::expression::AsExpression;
+use cipher::CipherFormat;
 use diesel::prelude::*;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 // http://docs.diesel.rs/diesel/macro.diesel_infix_operator.html
 diesel_infix_operator!(SqliteIs, " IS ", backend: diesel::sqlite::Sqlite);
@@ -50,10 +72,9 @@ pub fn config_path() -> PathBuf {
     path
 }
 
+/// the database of the current profile, see `profile::current`
 pub fn database_path() -> PathBuf {
-    let mut path = config_path();
-    path.push("projectpad.db");
-    path
+    profile::database_path(&profile::current())
 }
 
 // escape quote by doubling it
@@ -62,147 +83,218 @@ pub fn key_escape_param_value(key: &str) -> String {
     key.replace('\'', "''")
 }
 
-pub fn try_unlock_db(db_conn: &SqliteConnection, pass: &str) -> Result<(), String> {
+// the sqlcipher 3 page size. The sqlcipher 4 pages are 4096 bytes, a multiple of it
+const CIPHER_PAGE_SIZE: u64 = 1024;
+
+/// unlock a database with the sqlcipher 3 settings, like the sessions
+/// database of ppcli. For the projectpad databases, which can be
+/// upgraded to the sqlcipher 4 format, use `try_unlock_db_file`.
+pub fn try_unlock_db(db_conn: &SqliteConnection, pass: &str) -> Result<()> {
+    try_unlock_db_with_format(db_conn, pass, CipherFormat::Sqlcipher3Compat)
+}
+
+/// unlock the database at `db_path`, with the cipher settings
+/// recorded next to it, see `cipher::format_of`.
+/// sqlcipher reads the file right away, so the connection can't tell us its path.
+pub fn try_unlock_db_file(db_conn: &SqliteConnection, pass: &str, db_path: &Path) -> Result<()> {
+    try_unlock_db_with_format(db_conn, pass, cipher::format_of(db_path)?)
+}
+
+pub(crate) fn try_unlock_db_with_format(
+    db_conn: &SqliteConnection,
+    pass: &str,
+    format: CipherFormat,
+) -> Result<()> {
     // https://www.zetetic.net/sqlcipher/sqlcipher-api/#PRAGMA_key
     db_conn
         // https://www.zetetic.net/blog/2018/11/30/sqlcipher-400-release/ on my machine at least, the
-        // GUI app is built with sqlcipher3 and the CLI app with sqlcipher4, so I need these compatibility
+        // GUI app is built with sqlcipher3 and the CLI app with sqlcipher4, so I need the compatibility
         // parameters for the sqlcipher4 version to read the DB
         // I considered using the sqlcipher4 format, but many distributions ship only the sqlcipher3
         // command-line tools (the latest ubuntu, suse and fedora, as I write this), and these can be
-        // handy for the user.
+        // handy for the user. So the sqlcipher4 format is opt-in, see `cipher::upgrade`.
         .execute(&format!(
-            "PRAGMA key='{}'; PRAGMA cipher_page_size = 1024; PRAGMA kdf_iter = 64000; PRAGMA cipher_hmac_algorithm = HMAC_SHA1; PRAGMA cipher_kdf_algorithm = PBKDF2_HMAC_SHA1; SELECT count(*) FROM sqlite_master;",
-            &key_escape_param_value(pass)
+            "PRAGMA key='{}'; {} SELECT count(*) FROM sqlite_master;",
+            &key_escape_param_value(pass),
+            format.pragmas("")
         ))
         .map(|_| ())
-        .map_err(|x| x.to_string())
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(_, ref info)
+                if info.message() == "file is not a database" =>
+            {
+                classify_unreadable_db(db_conn).unwrap_or_else(|| e.into())
+            }
+            e => e.into(),
+        })
 }
 
-pub fn get_db_version(db_conn: &SqliteConnection) -> QueryResult<i32> {
+#[derive(QueryableByName)]
+struct DatabaseListRow {
+    #[sql_type = "diesel::sql_types::Text"]
+    name: String,
+    #[sql_type = "diesel::sql_types::Text"]
+    file: String,
+}
+
+/// the file of the main database of the connection, None for
+/// in-memory databases
+pub(crate) fn main_db_file(db_conn: &SqliteConnection) -> Option<PathBuf> {
+    let rows = diesel::sql_query("PRAGMA database_list")
+        .load::<DatabaseListRow>(db_conn)
+        .ok()?;
+    Some(rows.into_iter().find(|r| r.name == "main")?.file)
+        .filter(|f| !f.is_empty())
+        .map(PathBuf::from)
+}
+
+/// sqlcipher reports "file is not a database" for a wrong password,
+/// but also for files which are not sqlcipher databases. Look at
+/// the file itself to tell these apart.
+fn classify_unreadable_db(db_conn: &SqliteConnection) -> Option<Error> {
+    let file = main_db_file(db_conn)?;
+    let path = file.as_path();
+    let len = std::fs::metadata(path).ok()?.len();
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .ok()?;
+    // sqlcipher encrypts the whole file, including the header, in pages
+    // of cipher_page_size bytes
+    Some(
+        if &header == b"SQLite format 3\0" || len % CIPHER_PAGE_SIZE != 0 {
+            Error::NotADatabase
+        } else {
+            Error::WrongPassword
+        },
+    )
+}
+
+pub fn get_db_version(db_conn: &SqliteConnection) -> Result<i32> {
     use schema::db_version::dsl as ver;
-    ver::db_version
+    Ok(ver::db_version
         .order(ver::code.desc())
         .select(ver::code)
-        .first::<i32>(db_conn)
+        .first::<i32>(db_conn)?)
 }
 
 pub fn get_project_group_names(
     sql_conn: &diesel::SqliteConnection,
     project_id: i32,
-) -> Vec<String> {
+) -> Result<Vec<String>> {
     use schema::project_note::dsl as pnote;
     use schema::project_point_of_interest::dsl as ppoi;
     use schema::server::dsl as srv;
-    let server_group_names = srv::server
+    let server_group_names: Vec<Option<String>> = srv::server
         .filter(
             srv::project_id
                 .eq(project_id)
-                .and(srv::group_name.is_not_null()),
+                .and(srv::group_name.is_not_null())
+                .and(srv::deleted_at.is_null()),
         )
         .order(srv::group_name.asc())
         .select(srv::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
     let mut prj_poi_group_names = ppoi::project_point_of_interest
         .filter(
             ppoi::project_id
                 .eq(project_id)
-                .and(ppoi::group_name.is_not_null()),
+                .and(ppoi::group_name.is_not_null())
+                .and(ppoi::deleted_at.is_null()),
         )
         .order(ppoi::group_name.asc())
         .select(ppoi::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
     let mut prj_note_group_names = pnote::project_note
         .filter(
             pnote::project_id
                 .eq(project_id)
-                .and(pnote::group_name.is_not_null()),
+                .and(pnote::group_name.is_not_null())
+                .and(pnote::deleted_at.is_null()),
         )
         .order(pnote::group_name.asc())
         .select(pnote::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
 
     let mut project_group_names = server_group_names;
     project_group_names.append(&mut prj_poi_group_names);
     project_group_names.append(&mut prj_note_group_names);
-    let mut project_group_names_no_options: Vec<_> = project_group_names
-        .into_iter()
-        .map(|n: Option<String>| n.unwrap())
-        .collect();
+    // the queries filter out nulls
+    let mut project_group_names_no_options: Vec<String> =
+        project_group_names.into_iter().flatten().collect();
     project_group_names_no_options.sort();
     project_group_names_no_options.dedup();
-    project_group_names_no_options
+    Ok(project_group_names_no_options)
 }
 
-pub fn get_server_group_names(sql_conn: &diesel::SqliteConnection, server_id: i32) -> Vec<String> {
+pub fn get_server_group_names(
+    sql_conn: &diesel::SqliteConnection,
+    server_id: i32,
+) -> Result<Vec<String>> {
     use schema::server_database::dsl as db;
     use schema::server_extra_user_account::dsl as usr;
     use schema::server_note::dsl as not;
     use schema::server_point_of_interest::dsl as poi;
     use schema::server_website::dsl as www;
-    let server_poi_group_names = poi::server_point_of_interest
+    let server_poi_group_names: Vec<Option<String>> = poi::server_point_of_interest
         .filter(
             poi::server_id
                 .eq(server_id)
-                .and(poi::group_name.is_not_null()),
+                .and(poi::group_name.is_not_null())
+                .and(poi::deleted_at.is_null()),
         )
         .order(poi::group_name.asc())
         .select(poi::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
     let mut server_www_group_names = www::server_website
         .filter(
             www::server_id
                 .eq(server_id)
-                .and(www::group_name.is_not_null()),
+                .and(www::group_name.is_not_null())
+                .and(www::deleted_at.is_null()),
         )
         .order(www::group_name.asc())
         .select(www::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
     let mut server_db_group_names = db::server_database
         .filter(
             db::server_id
                 .eq(server_id)
-                .and(db::group_name.is_not_null()),
+                .and(db::group_name.is_not_null())
+                .and(db::deleted_at.is_null()),
         )
         .order(db::group_name.asc())
         .select(db::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
     let mut server_usr_group_names = usr::server_extra_user_account
         .filter(
             usr::server_id
                 .eq(server_id)
-                .and(usr::group_name.is_not_null()),
+                .and(usr::group_name.is_not_null())
+                .and(usr::deleted_at.is_null()),
         )
         .order(usr::group_name.asc())
         .select(usr::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
     let mut server_notes_group_names = not::server_note
         .filter(
             not::server_id
                 .eq(server_id)
-                .and(not::group_name.is_not_null()),
+                .and(not::group_name.is_not_null())
+                .and(not::deleted_at.is_null()),
         )
         .order(not::group_name.asc())
         .select(not::group_name)
-        .load(sql_conn)
-        .unwrap();
+        .load(sql_conn)?;
     let mut server_group_names = server_poi_group_names;
     server_group_names.append(&mut server_www_group_names);
     server_group_names.append(&mut server_db_group_names);
     server_group_names.append(&mut server_usr_group_names);
     server_group_names.append(&mut server_notes_group_names);
-    let mut server_group_names_no_options: Vec<_> = server_group_names
-        .into_iter()
-        .map(|n: Option<String>| n.unwrap())
-        .collect();
+    // the queries filter out nulls
+    let mut server_group_names_no_options: Vec<String> =
+        server_group_names.into_iter().flatten().collect();
     server_group_names_no_options.sort();
     server_group_names_no_options.dedup();
-    server_group_names_no_options
+    Ok(server_group_names_no_options)
 }
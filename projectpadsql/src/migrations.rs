@@ -0,0 +1,112 @@
+// the schema migrations, shared by the GUI and ppcli: the schema version
+// of a database is the number of migrations that were applied to it.
+use crate::error::{Error, Result};
+use diesel::prelude::*;
+use std::path::{Path, PathBuf};
+
+// migrations[0] upgrades to schema version 1, and so on.
+// never modify a migration that was released, add a new one.
+const MIGRATIONS: &[&str] = &[
+    include_str!("../migrations/001.sql"),
+    include_str!("../migrations/002.sql"),
+    include_str!("../migrations/003.sql"),
+    include_str!("../migrations/004.sql"),
+    include_str!("../migrations/005.sql"),
+    include_str!("../migrations/006.sql"),
+    include_str!("../migrations/007.sql"),
+    include_str!("../migrations/008.sql"),
+    include_str!("../migrations/009.sql"),
+    include_str!("../migrations/010.sql"),
+    include_str!("../migrations/011.sql"),
+    include_str!("../migrations/012.sql"),
+    include_str!("../migrations/013.sql"),
+    include_str!("../migrations/014.sql"),
+    include_str!("../migrations/015.sql"),
+    include_str!("../migrations/016.sql"),
+    include_str!("../migrations/017.sql"),
+    include_str!("../migrations/018.sql"),
+    include_str!("../migrations/019.sql"),
+    include_str!("../migrations/020.sql"),
+    include_str!("../migrations/021.sql"),
+    include_str!("../migrations/022.sql"),
+    include_str!("../migrations/023.sql"),
+    include_str!("../migrations/024.sql"),
+    include_str!("../migrations/025.sql"),
+    include_str!("../migrations/026.sql"),
+    include_str!("../migrations/027.sql"),
+    include_str!("../migrations/028.sql"),
+    include_str!("../migrations/029.sql"),
+    include_str!("../migrations/030.sql"),
+    include_str!("../migrations/031.sql"),
+    include_str!("../migrations/032.sql"),
+    include_str!("../migrations/033.sql"),
+    include_str!("../migrations/034.sql"),
+    include_str!("../migrations/035.sql"),
+];
+
+/// the schema version of a database with all the migrations applied
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+/// where we copy the database before migrating it
+pub fn pre_migration_backup_path(db_path: &Path, from_version: i32) -> PathBuf {
+    let mut fname = db_path.file_name().unwrap_or_default().to_os_string();
+    fname.push(format!(".v{}.bak", from_version));
+    db_path.with_file_name(fname)
+}
+
+/// applies the pending migrations in a single transaction: either
+/// all of them are applied, or, in case of error, none.
+/// If `db_path` is given, the database file is first copied next to itself,
+/// see `pre_migration_backup_path`. Returns the versions that were applied.
+pub fn migrate_db_if_needed(
+    db_conn: &SqliteConnection,
+    db_path: Option<&Path>,
+) -> Result<Vec<i32>> {
+    use crate::schema::db_version::dsl as ver;
+    let current_version = crate::get_db_version(db_conn).unwrap_or(0);
+    if current_version > SCHEMA_VERSION {
+        return Err(Error::SchemaTooNew {
+            db_version: current_version,
+            max_supported: SCHEMA_VERSION,
+        });
+    }
+    let pending: Vec<i32> = (current_version + 1..=SCHEMA_VERSION).collect();
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+    if let Some(path) = db_path.filter(|_| current_version > 0) {
+        crate::cipher::copy_database(path, &pre_migration_backup_path(path, current_version))?;
+    }
+    db_conn.transaction::<_, Error, _>(|| {
+        for version in &pending {
+            db_conn.execute(MIGRATIONS[(*version - 1) as usize])?;
+            diesel::insert_into(ver::db_version)
+                .values((
+                    ver::code.eq(version),
+                    ver::upgrade_date.eq(diesel::dsl::now),
+                ))
+                .execute(db_conn)?;
+        }
+        Ok(())
+    })?;
+    Ok(pending)
+}
+
+/// checks that this application can use the database without migrating it.
+/// Returns the schema version of the database.
+pub fn check_schema_version(db_conn: &SqliteConnection, min_supported: i32) -> Result<i32> {
+    let db_version = crate::get_db_version(db_conn)?;
+    if db_version < min_supported {
+        return Err(Error::SchemaTooOld {
+            db_version,
+            min_supported,
+        });
+    }
+    if db_version > SCHEMA_VERSION {
+        return Err(Error::SchemaTooNew {
+            db_version,
+            max_supported: SCHEMA_VERSION,
+        });
+    }
+    Ok(db_version)
+}
@@ -1,3 +1,4 @@
+use crate::error::Error;
 use chrono::naive::NaiveDateTime;
 use diesel::backend::Backend;
 use diesel::deserialize::*;
@@ -5,6 +6,7 @@ use diesel::serialize::Output;
 use diesel::serialize::*;
 use diesel::sql_types::*;
 use serde_derive::{Deserialize, Serialize};
+use std::fmt;
 use std::io::Write;
 use std::str::FromStr;
 use std::string::ToString;
@@ -15,10 +17,8 @@ pub struct Project {
     pub id: i32,
     pub name: String,
     pub icon: Option<Vec<u8>>,
-    pub has_dev: bool,
-    pub has_uat: bool,
-    pub has_stage: bool,
-    pub has_prod: bool,
+    /// set when the entity is in the trash, see the trash module
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(
@@ -70,35 +70,6 @@ pub enum ServerAccessType {
     SrvAccessSshTunnel,
 }
 
-#[derive(
-    Debug,
-    Clone,
-    Copy,
-    PartialEq,
-    Eq,
-    EnumString,
-    AsExpression,
-    FromSqlRow,
-    Display,
-    PartialOrd,
-    Ord,
-    Serialize,
-    Deserialize,
-)]
-#[sql_type = "Varchar"]
-pub enum EnvironmentType {
-    EnvDevelopment,
-    EnvUat,
-    EnvStage,
-    EnvProd,
-}
-
-impl Default for EnvironmentType {
-    fn default() -> Self {
-        EnvironmentType::EnvDevelopment
-    }
-}
-
 #[derive(
     Debug,
     Clone,
@@ -151,6 +122,45 @@ pub enum RunOn {
     RunOnClient,
 }
 
+/// the projectpad entities. Serialized as their table name.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, EnumIter, AsExpression, FromSqlRow, Display,
+)]
+#[sql_type = "Varchar"]
+pub enum EntityType {
+    #[strum(serialize = "project")]
+    Project,
+    #[strum(serialize = "project_note")]
+    ProjectNote,
+    #[strum(serialize = "project_point_of_interest")]
+    ProjectPoi,
+    #[strum(serialize = "server_link")]
+    ServerLink,
+    #[strum(serialize = "server")]
+    Server,
+    #[strum(serialize = "server_database")]
+    ServerDatabase,
+    #[strum(serialize = "server_extra_user_account")]
+    ServerExtraUserAccount,
+    #[strum(serialize = "server_note")]
+    ServerNote,
+    #[strum(serialize = "server_point_of_interest")]
+    ServerPoi,
+    #[strum(serialize = "server_website")]
+    ServerWebsite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsExpression, FromSqlRow, Display)]
+#[sql_type = "Varchar"]
+pub enum ChangeOperation {
+    #[strum(serialize = "insert")]
+    Insert,
+    #[strum(serialize = "update")]
+    Update,
+    #[strum(serialize = "delete")]
+    Delete,
+}
+
 macro_rules! simple_enum {
     ($x:ty) => {
         impl<DB> FromSql<Varchar, DB> for $x
@@ -175,30 +185,267 @@ macro_rules! simple_enum {
     };
 }
 
-simple_enum!(EnvironmentType);
 simple_enum!(ServerType);
 simple_enum!(ServerAccessType);
 simple_enum!(InterestType);
 simple_enum!(RunOn);
+simple_enum!(EntityType);
+simple_enum!(ChangeOperation);
+
+/// an environment of a project (development, production...).
+/// Servers and server links refer to it through its name.
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    pub id: i32,
+    pub name: String,
+    /// a few letters, displayed next to the servers
+    pub short_label: String,
+    /// #rrggbb
+    pub color: String,
+    pub ordering: i32,
+    /// ask for a confirmation before running commands there
+    pub dangerous: bool,
+    pub project_id: i32,
+}
+
+/// the environments of new projects. Before environments could be configured,
+/// they were the only environments, stored in the database with these codes.
+/// (code, name, short label, color, dangerous)
+const DEFAULT_ENVIRONMENTS: &[(&str, &str, &str, &str, bool)] = &[
+    ("EnvDevelopment", "Development", "DEV", "#46a046", false),
+    ("EnvStage", "Staging", "STG", "#7590ae", false),
+    ("EnvUat", "UAT", "UAT", "#eed680", false),
+    ("EnvProd", "Production", "PRD", "#df421e", true),
+];
+
+impl Environment {
+    /// the environments of a new project, not saved yet (their id is 0)
+    pub fn defaults(project_id: i32) -> Vec<Environment> {
+        DEFAULT_ENVIRONMENTS
+            .iter()
+            .enumerate()
+            .map(|(idx, (_, name, short_label, color, dangerous))| Environment {
+                id: 0,
+                name: name.to_string(),
+                short_label: short_label.to_string(),
+                color: color.to_string(),
+                ordering: idx as i32,
+                dangerous: *dangerous,
+                project_id,
+            })
+            .collect()
+    }
+
+    /// the name of a default environment, from the code it was stored with
+    /// before environments could be configured (EnvDevelopment, EnvProd...)
+    pub fn legacy_name(code: &str) -> Option<&'static str> {
+        DEFAULT_ENVIRONMENTS
+            .iter()
+            .find(|e| e.0 == code)
+            .map(|e| e.1)
+    }
+
+    /// the color as (red, green, blue), between 0 and 255
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        let hex = self.color.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let component = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok();
+        Some((component(0)?, component(2)?, component(4)?))
+    }
+}
+
+/// a tag, shared by all the projects. Servers, POIs, notes, databases
+/// and websites can have several tags.
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+impl Tag {
+    /// the entity types which can have tags
+    pub const ENTITY_TYPES: &'static [EntityType] = &[
+        EntityType::Server,
+        EntityType::ServerPoi,
+        EntityType::ProjectPoi,
+        EntityType::ProjectNote,
+        EntityType::ServerNote,
+        EntityType::ServerDatabase,
+        EntityType::ServerWebsite,
+    ];
+
+    /// tag names are lowercase and without spaces or commas, so that
+    /// they can be typed in a search (tag:on-call). Spaces become dashes.
+    pub fn normalize_name(name: &str) -> String {
+        name.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase()
+    }
+
+    /// the tag names in a text where they're separated by spaces or commas
+    pub fn parse_names(text: &str) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for name in text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .map(Tag::normalize_name)
+            .filter(|name| !name.is_empty())
+        {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
 pub struct Server {
     pub id: i32,
     pub desc: String,
-    pub ip: String,
+    /// the host name or IP address, see `Server::address`
+    pub host: String,
+    pub port: Option<i32>,
+    /// for the www servers, for instance https
+    pub protocol: Option<String>,
+    /// what follows the port in a URL, starting with a slash
+    pub path: String,
     pub text: String,
     pub is_retired: bool,
     pub username: String,
     pub password: String,
+    /// when the password was last changed, set by the database.
+    /// None if unknown, or if there is no password
+    pub password_changed_at: Option<NaiveDateTime>,
+    /// an otpauth:// URI or a base32 secret, see `totp::Totp`
+    pub otp_secret: String,
     pub auth_key: Option<Vec<u8>>, //
     pub auth_key_filename: Option<String>,
     pub server_type: ServerType,
     pub access_type: ServerAccessType,
     pub ssh_tunnel_port: Option<i32>,
     pub ssh_tunnel_through_server_id: Option<i32>,
-    pub environment: EnvironmentType,
+    /// the name of an environment of the project
+    pub environment: String,
     pub group_name: Option<String>,
     pub project_id: i32,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl Server {
+    pub fn address(&self) -> ServerAddress {
+        ServerAddress {
+            protocol: self.protocol.clone(),
+            host: self.host.clone(),
+            port: self.port,
+            path: self.path.clone(),
+        }
+    }
+}
+
+/// the address of a server, as the user types it: `host`, `host:port`,
+/// `[ipv6]:port`, or a URL for the www servers. An IPv6 address with a
+/// port must be between brackets: `::1` has no port.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServerAddress {
+    pub protocol: Option<String>,
+    pub host: String,
+    pub port: Option<i32>,
+    /// what follows the port in a URL, starting with a slash
+    pub path: String,
+}
+
+impl FromStr for ServerAddress {
+    /// the error is meant to be displayed to the user
+    type Err = Error;
+
+    fn from_str(address: &str) -> std::result::Result<Self, Self::Err> {
+        let address = address.trim();
+        if address.chars().any(char::is_whitespace) {
+            return Err(Error::Invalid(
+                "The address can't contain spaces".to_string(),
+            ));
+        }
+        let (protocol, rest) = match address.find("://") {
+            Some(idx) => {
+                let protocol = &address[..idx];
+                if protocol.is_empty()
+                    || !protocol
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                {
+                    return Err(Error::Invalid(format!("Invalid protocol: '{}'", protocol)));
+                }
+                (Some(protocol.to_string()), &address[idx + 3..])
+            }
+            None => (None, address),
+        };
+        let (host_port, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, ""),
+        };
+        let (host, port) = if let Some(bracketed) = host_port.strip_prefix('[') {
+            let end = bracketed
+                .find(']')
+                .ok_or_else(|| Error::Invalid("Missing ']' after the IPv6 address".to_string()))?;
+            let port = &bracketed[end + 1..];
+            let port = match port.strip_prefix(':') {
+                Some(p) => Some(p),
+                None if port.is_empty() => None,
+                None => {
+                    return Err(Error::Invalid(format!(
+                        "Expected ':' and a port after ']', got '{}'",
+                        port
+                    )))
+                }
+            };
+            (&bracketed[..end], port)
+        } else if host_port.matches(':').count() == 1 {
+            let idx = host_port.find(':').unwrap();
+            (&host_port[..idx], Some(&host_port[idx + 1..]))
+        } else {
+            // no port, or an IPv6 address without brackets
+            (host_port, None)
+        };
+        let port = match port {
+            Some(p) => match p.parse::<u16>() {
+                Ok(p) if p > 0 => Some(p as i32),
+                _ => return Err(Error::Invalid(format!("Invalid port: '{}'", p))),
+            },
+            None => None,
+        };
+        if host.is_empty() && (protocol.is_some() || port.is_some() || !path.is_empty()) {
+            return Err(Error::Invalid(
+                "Missing host name or IP address".to_string(),
+            ));
+        }
+        Ok(ServerAddress {
+            protocol,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for ServerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(protocol) = &self.protocol {
+            write!(f, "{}://", protocol)?;
+        }
+        if self.host.contains(':') && (self.port.is_some() || self.protocol.is_some()) {
+            write!(f, "[{}]", self.host)?;
+        } else {
+            write!(f, "{}", self.host)?;
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "{}", self.path)
+    }
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
@@ -206,12 +453,9 @@ pub struct ProjectNote {
     pub id: i32,
     pub title: String,
     pub contents: String,
-    pub has_dev: bool,
-    pub has_uat: bool,
-    pub has_stage: bool,
-    pub has_prod: bool,
     pub group_name: Option<String>,
     pub project_id: i32,
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
@@ -223,6 +467,7 @@ pub struct ProjectPointOfInterest {
     pub interest_type: InterestType,
     pub group_name: Option<String>,
     pub project_id: i32,
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
@@ -231,9 +476,11 @@ pub struct ServerLink {
     pub desc: String,
     pub linked_server_id: i32,
     pub linked_group_name: Option<String>,
-    pub environment: EnvironmentType,
+    /// the name of an environment of the project
+    pub environment: String,
     pub group_name: Option<String>,
     pub project_id: i32,
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
@@ -244,9 +491,15 @@ pub struct ServerWebsite {
     pub text: String,
     pub username: String,
     pub password: String,
+    /// when the password was last changed, set by the database.
+    /// None if unknown, or if there is no password
+    pub password_changed_at: Option<NaiveDateTime>,
+    /// an otpauth:// URI or a base32 secret, see `totp::Totp`
+    pub otp_secret: String,
     pub server_database_id: Option<i32>,
     pub group_name: Option<String>,
     pub server_id: i32,
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -265,6 +518,8 @@ pub struct ServerPointOfInterest {
     pub group_name: Option<String>,
     #[serde(skip)]
     pub server_id: i32,
+    #[serde(skip)]
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -279,6 +534,8 @@ pub struct ServerNote {
     pub group_name: Option<String>,
     #[serde(skip)]
     pub server_id: i32,
+    #[serde(skip)]
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
@@ -286,11 +543,142 @@ pub struct ServerExtraUserAccount {
     pub id: i32,
     pub username: String,
     pub password: String,
+    /// when the password was last changed, set by the database.
+    /// None if unknown, or if there is no password
+    pub password_changed_at: Option<NaiveDateTime>,
+    /// an otpauth:// URI or a base32 secret, see `totp::Totp`
+    pub otp_secret: String,
     pub desc: String,
     pub auth_key: Option<Vec<u8>>,
     pub auth_key_filename: Option<String>,
     pub group_name: Option<String>,
     pub server_id: i32,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+/// a file attached to a project, server or note. The contents are not
+/// loaded with the rest, see `AttachmentRepo::contents`.
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub id: i32,
+    pub entity_type: EntityType,
+    pub entity_id: i32,
+    pub name: String,
+    pub mime: String,
+    /// in bytes
+    pub size: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl Attachment {
+    /// the entity types which can have attachments
+    pub const ENTITY_TYPES: &'static [EntityType] = &[
+        EntityType::Project,
+        EntityType::Server,
+        EntityType::ProjectNote,
+        EntityType::ServerNote,
+    ];
+
+    /// the mime type of a file, from the extension of its name. Only the
+    /// types we commonly attach, application/octet-stream for the others.
+    pub fn guess_mime(file_name: &str) -> &'static str {
+        let extension = file_name
+            .rsplit('.')
+            .next()
+            .filter(|e| e.len() < file_name.len())
+            .map(|e| e.to_lowercase());
+        match extension.as_deref() {
+            Some("pem") | Some("crt") | Some("cer") | Some("key") => "application/x-pem-file",
+            Some("p12") | Some("pfx") => "application/x-pkcs12",
+            Some("ovpn") => "application/x-openvpn-profile",
+            Some("yaml") | Some("yml") => "application/x-yaml",
+            Some("json") => "application/json",
+            Some("xml") => "application/xml",
+            Some("txt") | Some("conf") | Some("cfg") | Some("ini") | Some("lic") => "text/plain",
+            Some("pdf") => "application/pdf",
+            Some("zip") => "application/zip",
+            Some("png") => "image/png",
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+/// the type of a custom field, see `CustomFieldDefinition`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    EnumString,
+    EnumIter,
+    AsExpression,
+    FromSqlRow,
+    Display,
+    Serialize,
+    Deserialize,
+)]
+#[sql_type = "Varchar"]
+pub enum CustomFieldType {
+    Text,
+    /// displayed masked, not searchable, and encrypted
+    /// if the master passphrase is enabled
+    Secret,
+    Url,
+    /// YYYY-MM-DD
+    Date,
+}
+
+simple_enum!(CustomFieldType);
+
+impl CustomFieldType {
+    /// check a value before saving it. Empty values are never saved.
+    pub fn check_value(self, value: &str) -> crate::error::Result<()> {
+        match self {
+            CustomFieldType::Text | CustomFieldType::Secret => Ok(()),
+            CustomFieldType::Url => match value.find("://") {
+                Some(idx) if idx > 0 && idx + 3 < value.len() => Ok(()),
+                _ => Err(Error::Invalid(format!(
+                    "{} is not a URL, such as https://example.com",
+                    value
+                ))),
+            },
+            CustomFieldType::Date => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|_| ())
+                .map_err(|_| {
+                    Error::Invalid(format!("{} is not a date in the YYYY-MM-DD format", value))
+                }),
+        }
+    }
+}
+
+/// a field the user added to the servers, databases or websites of a project
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct CustomFieldDefinition {
+    pub id: i32,
+    pub project_id: i32,
+    /// the items which have the field: servers, databases or websites
+    pub entity_type: EntityType,
+    pub name: String,
+    pub field_type: CustomFieldType,
+    pub ordering: i32,
+}
+
+impl CustomFieldDefinition {
+    /// the entity types which can have custom fields
+    pub const ENTITY_TYPES: &'static [EntityType] = &[
+        EntityType::Server,
+        EntityType::ServerDatabase,
+        EntityType::ServerWebsite,
+    ];
+}
+
+/// the value of a custom field for an item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomField {
+    pub definition: CustomFieldDefinition,
+    pub value: String,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq, Deserialize)]
@@ -307,10 +695,64 @@ pub struct ServerDatabase {
     pub username: String,
     #[serde(default)]
     pub password: String,
+    #[serde(skip)]
+    pub password_changed_at: Option<NaiveDateTime>,
     #[serde(default)]
     pub group_name: Option<String>,
     #[serde(default)]
     pub server_id: i32,
+    #[serde(skip)]
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+/// how often the passwords of a project must be changed,
+/// see `password_audit::overdue_passwords`
+#[derive(Queryable, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub project_id: i32,
+    pub max_age_days: i32,
+    /// only the passwords of the servers in the dangerous environments
+    pub dangerous_environments_only: bool,
+}
+
+/// what a server needs another server for, see `ServerDependency`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumString,
+    EnumIter,
+    AsExpression,
+    FromSqlRow,
+    Display,
+    PartialOrd,
+    Ord,
+)]
+#[sql_type = "Varchar"]
+pub enum DependencyType {
+    Database,
+    Proxy,
+    Cache,
+    MessageQueue,
+    Storage,
+    Authentication,
+    Other,
+}
+
+simple_enum!(DependencyType);
+
+/// the server `server_id` needs the server `depends_on_server_id`,
+/// which may be in another project
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct ServerDependency {
+    pub id: i32,
+    pub server_id: i32,
+    pub depends_on_server_id: i32,
+    pub dependency_type: DependencyType,
+    pub desc: String,
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
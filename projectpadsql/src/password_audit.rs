@@ -0,0 +1,107 @@
+// password rotation: projects may require their passwords to be changed
+// regularly, for instance every 90 days for the production servers.
+// The database records when each password was last changed (see
+// migration 33), we report the passwords which are overdue.
+use crate::error::Result;
+use crate::models::{EntityType, PasswordPolicy};
+use crate::schema::password_policy::dsl as pol;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text, Timestamp};
+
+/// a password older than the rotation policy of its project allows
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct OverduePassword {
+    #[sql_type = "Text"]
+    pub entity_type: EntityType,
+    #[sql_type = "Integer"]
+    pub entity_id: i32,
+    /// the description of the item, or the username for
+    /// the extra users without description
+    #[sql_type = "Text"]
+    pub title: String,
+    #[sql_type = "Text"]
+    pub server_desc: String,
+    #[sql_type = "Text"]
+    pub project_name: String,
+    #[sql_type = "Text"]
+    pub environment: String,
+    /// None if we don't know when the password was last changed
+    #[sql_type = "Nullable<Timestamp>"]
+    pub password_changed_at: Option<NaiveDateTime>,
+    #[sql_type = "Integer"]
+    pub max_age_days: i32,
+}
+
+/// the rotation policy of a project, if it has one
+pub fn policy(conn: &SqliteConnection, project_id: i32) -> Result<Option<PasswordPolicy>> {
+    Ok(pol::password_policy
+        .find(project_id)
+        .first(conn)
+        .optional()?)
+}
+
+/// set or remove the rotation policy of a project
+pub fn set_policy(
+    conn: &SqliteConnection,
+    project_id: i32,
+    policy: Option<&PasswordPolicy>,
+) -> Result<()> {
+    match policy {
+        Some(p) => diesel::replace_into(pol::password_policy)
+            .values((
+                pol::project_id.eq(project_id),
+                pol::max_age_days.eq(p.max_age_days),
+                pol::dangerous_environments_only.eq(p.dangerous_environments_only),
+            ))
+            .execute(conn)?,
+        None => diesel::delete(pol::password_policy.find(project_id)).execute(conn)?,
+    };
+    Ok(())
+}
+
+/// the passwords which were not changed since more days than the policy of
+/// their project allows, as of `now` (UTC, like the timestamps of the database).
+/// The passwords for which we don't know when they were last changed are
+/// overdue too. Items in the trash are ignored.
+pub fn overdue_passwords(
+    conn: &SqliteConnection,
+    now: NaiveDateTime,
+) -> Result<Vec<OverduePassword>> {
+    Ok(diesel::sql_query(
+        "WITH credential AS ( \
+             SELECT 'server' AS entity_type, id AS entity_id, desc AS title, \
+                    id AS server_id, password, password_changed_at \
+               FROM server WHERE deleted_at IS NULL \
+             UNION ALL \
+             SELECT 'server_database', id, desc, server_id, password, password_changed_at \
+               FROM server_database WHERE deleted_at IS NULL \
+             UNION ALL \
+             SELECT 'server_website', id, desc, server_id, password, password_changed_at \
+               FROM server_website WHERE deleted_at IS NULL \
+             UNION ALL \
+             SELECT 'server_extra_user_account', id, \
+                    CASE WHEN desc = '' THEN username ELSE desc END, \
+                    server_id, password, password_changed_at \
+               FROM server_extra_user_account WHERE deleted_at IS NULL) \
+         SELECT credential.entity_type, credential.entity_id, credential.title, \
+                server.desc AS server_desc, project.name AS project_name, \
+                server.environment, credential.password_changed_at, \
+                password_policy.max_age_days \
+           FROM credential \
+           JOIN server ON server.id = credential.server_id AND server.deleted_at IS NULL \
+           JOIN project ON project.id = server.project_id AND project.deleted_at IS NULL \
+           JOIN password_policy ON password_policy.project_id = project.id \
+           LEFT JOIN environment ON environment.project_id = project.id \
+                                AND environment.name = server.environment \
+          WHERE credential.password != '' \
+            AND (NOT password_policy.dangerous_environments_only \
+                 OR IFNULL(environment.dangerous, 0)) \
+            AND (credential.password_changed_at IS NULL \
+                 OR credential.password_changed_at < strftime('%Y-%m-%d %H:%M:%f', ?, \
+                        '-' || password_policy.max_age_days || ' days')) \
+          ORDER BY project.name, server.desc, credential.title",
+    )
+    .bind::<Timestamp, _>(now)
+    .load(conn)?)
+}
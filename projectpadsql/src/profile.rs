@@ -0,0 +1,105 @@
+// database profiles: separate databases, for instance for work and personal
+// infrastructure, each with its own password in the OS keyring. The default
+// profile uses the files from before profiles existed (projectpad.db and the
+// "projectpad-cli" keyring entry), the other profiles append their name:
+// projectpad-work.db and "projectpad-cli-work".
+use crate::config_path;
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// selects the profile when none is given explicitly
+pub const PROFILE_ENV_VAR: &str = "PROJECTPAD_PROFILE";
+
+const KEYRING_SERVICE: &str = "projectpad-cli";
+
+/// the profile selected by the PROJECTPAD_PROFILE environment
+/// variable, or the default profile
+pub fn current() -> String {
+    std::env::var(PROFILE_ENV_VAR)
+        .ok()
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// the profile names end up in file names: only letters, digits, - and _
+pub fn check_name(name: &str) -> Result<()> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(Error::Invalid(format!(
+            "invalid profile name '{}': use only letters, digits, - and _",
+            name
+        )))
+    }
+}
+
+/// a file of a profile in `folder`: `file_name` for the default profile,
+/// otherwise with the profile name appended to its stem
+pub fn file_path_in(folder: &Path, profile: &str, file_name: &str) -> PathBuf {
+    let mut path = folder.to_path_buf();
+    if profile == DEFAULT_PROFILE {
+        path.push(file_name);
+        return path;
+    }
+    let name_path = Path::new(file_name);
+    let stem = name_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.push(match name_path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, profile, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, profile),
+    });
+    path
+}
+
+/// a file of a profile in the configuration folder, see `file_path_in`
+pub fn file_path(profile: &str, file_name: &str) -> PathBuf {
+    file_path_in(&config_path(), profile, file_name)
+}
+
+pub fn database_path(profile: &str) -> PathBuf {
+    file_path(profile, "projectpad.db")
+}
+
+/// the service and username of the keyring entry with the database password
+pub fn keyring_service(profile: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        KEYRING_SERVICE.to_string()
+    } else {
+        format!("{}-{}", KEYRING_SERVICE, profile)
+    }
+}
+
+/// the profiles which have a database in `folder`, sorted by name,
+/// the default profile first. The default profile is always listed.
+pub fn list_in(folder: &Path) -> Vec<String> {
+    let mut profiles: Vec<String> = std::fs::read_dir(folder)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    name.strip_prefix("projectpad-")
+                        .and_then(|n| n.strip_suffix(".db"))
+                        .filter(|n| check_name(n).is_ok() && *n != DEFAULT_PROFILE)
+                        .map(|n| n.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    profiles.sort();
+    profiles.insert(0, DEFAULT_PROFILE.to_string());
+    profiles
+}
+
+/// the profiles which have a database, see `list_in`
+pub fn list() -> Vec<String> {
+    list_in(&config_path())
+}
@@ -0,0 +1,139 @@
+use super::{check_modified, insert_row};
+use crate::error::{Error, Result};
+use crate::models::{Attachment, EntityType};
+use crate::schema::attachment::dsl as att;
+use diesel::prelude::*;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// the columns of `Attachment`: all but the contents
+const ATTACHMENT_COLUMNS: (
+    att::id,
+    att::entity_type,
+    att::entity_id,
+    att::name,
+    att::mime,
+    att::size,
+    att::created_at,
+) = (
+    att::id,
+    att::entity_type,
+    att::entity_id,
+    att::name,
+    att::mime,
+    att::size,
+    att::created_at,
+);
+
+pub struct AttachmentRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+impl<'a> AttachmentRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> AttachmentRepo<'a> {
+        AttachmentRepo { conn }
+    }
+
+    pub fn get(&self, id: i32) -> Result<Attachment> {
+        Ok(att::attachment
+            .select(ATTACHMENT_COLUMNS)
+            .find(id)
+            .first(self.conn)?)
+    }
+
+    /// the attachments of an item, sorted by name
+    pub fn list_for_item(
+        &self,
+        entity_type: EntityType,
+        entity_id: i32,
+    ) -> Result<Vec<Attachment>> {
+        Ok(att::attachment
+            .select(ATTACHMENT_COLUMNS)
+            .filter(att::entity_type.eq(entity_type))
+            .filter(att::entity_id.eq(entity_id))
+            .order(att::name.asc())
+            .load(self.conn)?)
+    }
+
+    /// the ids of the items of that type which have at least one attachment
+    pub fn item_ids_with_attachments(&self, entity_type: EntityType) -> Result<HashSet<i32>> {
+        Ok(att::attachment
+            .select(att::entity_id)
+            .filter(att::entity_type.eq(entity_type))
+            .distinct()
+            .load::<i32>(self.conn)?
+            .into_iter()
+            .collect())
+    }
+
+    pub fn contents(&self, id: i32) -> Result<Vec<u8>> {
+        Ok(att::attachment
+            .select(att::contents)
+            .find(id)
+            .first(self.conn)?)
+    }
+
+    /// attach a file to an item, the mime type is guessed from the name
+    pub fn add(
+        &self,
+        entity_type: EntityType,
+        entity_id: i32,
+        name: &str,
+        contents: &[u8],
+    ) -> Result<Attachment> {
+        let size = i32::try_from(contents.len())
+            .map_err(|_| Error::Constraint(format!("{} is too large to attach", name)))?;
+        let id = insert_row(
+            self.conn,
+            diesel::insert_into(att::attachment).values((
+                att::entity_type.eq(entity_type),
+                att::entity_id.eq(entity_id),
+                att::name.eq(name),
+                att::mime.eq(Attachment::guess_mime(name)),
+                att::size.eq(size),
+                att::contents.eq(contents),
+            )),
+        )?;
+        self.get(id)
+    }
+
+    pub fn rename(&self, id: i32, name: &str) -> Result<Attachment> {
+        check_modified(
+            diesel::update(att::attachment.find(id))
+                .set((
+                    att::name.eq(name),
+                    att::mime.eq(Attachment::guess_mime(name)),
+                ))
+                .execute(self.conn)?,
+        )?;
+        self.get(id)
+    }
+
+    pub fn delete(&self, id: i32) -> Result<()> {
+        check_modified(diesel::delete(att::attachment.find(id)).execute(self.conn)?)
+    }
+
+    /// write the contents of an attachment to a file, overwriting it.
+    /// The attachments are often keys or certificates: on unix, only
+    /// the owner can read and write the file.
+    pub fn write_to_file(&self, id: i32, path: &Path) -> Result<()> {
+        let contents = self.contents(id)?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            let mut file = options.open(path)?;
+            // the mode applies only when the file is created
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            file.write_all(&contents)?;
+        }
+        #[cfg(not(unix))]
+        options.open(path)?.write_all(&contents)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use super::{check_modified, insert_row};
+use crate::error::{Error, Result};
+use crate::models::{CustomField, CustomFieldDefinition, EntityType};
+use crate::schema::custom_field_definition::dsl as def;
+use crate::schema::custom_field_value::dsl as val;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+pub struct CustomFieldRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+impl<'a> CustomFieldRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> CustomFieldRepo<'a> {
+        CustomFieldRepo { conn }
+    }
+
+    /// the custom fields a project defines for an entity type,
+    /// sorted by their ordering
+    pub fn list_definitions(
+        &self,
+        project_id: i32,
+        entity_type: EntityType,
+    ) -> Result<Vec<CustomFieldDefinition>> {
+        Ok(def::custom_field_definition
+            .filter(def::project_id.eq(project_id))
+            .filter(def::entity_type.eq(entity_type))
+            .order((def::ordering.asc(), def::name.asc()))
+            .load(self.conn)?)
+    }
+
+    /// the custom fields a project defines, for all the entity
+    /// types, sorted by entity type and ordering
+    pub fn list_definitions_for_project(
+        &self,
+        project_id: i32,
+    ) -> Result<Vec<CustomFieldDefinition>> {
+        Ok(def::custom_field_definition
+            .filter(def::project_id.eq(project_id))
+            .order((def::entity_type.asc(), def::ordering.asc(), def::name.asc()))
+            .load(self.conn)?)
+    }
+
+    /// like `list_definitions`, for the project of a server: the databases
+    /// and websites know only their server
+    pub fn list_definitions_for_server(
+        &self,
+        server_id: i32,
+        entity_type: EntityType,
+    ) -> Result<Vec<CustomFieldDefinition>> {
+        let project_id = self.item_project_id(EntityType::Server, server_id)?;
+        self.list_definitions(project_id, entity_type)
+    }
+
+    /// saves the custom fields a project defines: inserts the definitions
+    /// with id 0, updates the others, and deletes the definitions of the project
+    /// which are not in the list, with their values. The ordering follows the
+    /// order of the list. The entity type of an existing definition can't change.
+    pub fn save_definitions(
+        &self,
+        project_id: i32,
+        definitions: &[CustomFieldDefinition],
+    ) -> Result<Vec<CustomFieldDefinition>> {
+        self.conn.transaction(|| {
+            for existing in self.list_definitions_for_project(project_id)? {
+                if !definitions.iter().any(|d| d.id == existing.id) {
+                    check_modified(
+                        diesel::delete(def::custom_field_definition.find(existing.id))
+                            .execute(self.conn)?,
+                    )?;
+                }
+            }
+            for (idx, definition) in definitions.iter().enumerate() {
+                if definition.id == 0 {
+                    insert_row(
+                        self.conn,
+                        diesel::insert_into(def::custom_field_definition).values((
+                            def::project_id.eq(project_id),
+                            def::entity_type.eq(definition.entity_type),
+                            def::name.eq(&definition.name),
+                            def::field_type.eq(definition.field_type),
+                            def::ordering.eq(idx as i32),
+                        )),
+                    )?;
+                } else {
+                    check_modified(
+                        diesel::update(
+                            def::custom_field_definition
+                                .find(definition.id)
+                                .filter(def::project_id.eq(project_id)),
+                        )
+                        .set((
+                            def::name.eq(&definition.name),
+                            def::field_type.eq(definition.field_type),
+                            def::ordering.eq(idx as i32),
+                        ))
+                        .execute(self.conn)?,
+                    )?;
+                }
+            }
+            self.list_definitions_for_project(project_id)
+        })
+    }
+
+    /// the custom fields which have a value for an item, sorted by their ordering
+    pub fn list_for_item(
+        &self,
+        entity_type: EntityType,
+        entity_id: i32,
+    ) -> Result<Vec<CustomField>> {
+        Ok(self
+            .list_for_items(entity_type, &[entity_id])?
+            .remove(&entity_id)
+            .unwrap_or_default())
+    }
+
+    /// the custom fields of items of the same type, sorted by their ordering,
+    /// by item id. Items without custom field values are missing from the map.
+    pub fn list_for_items(
+        &self,
+        entity_type: EntityType,
+        entity_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<CustomField>>> {
+        let rows = def::custom_field_definition
+            .inner_join(val::custom_field_value)
+            .filter(def::entity_type.eq(entity_type))
+            .filter(val::entity_id.eq_any(entity_ids))
+            .order((def::ordering.asc(), def::name.asc()))
+            .select((
+                val::entity_id,
+                def::custom_field_definition::all_columns(),
+                val::value,
+            ))
+            .load::<(i32, CustomFieldDefinition, String)>(self.conn)?;
+        let mut result: HashMap<i32, Vec<CustomField>> = HashMap::new();
+        for (entity_id, definition, value) in rows {
+            result
+                .entry(entity_id)
+                .or_default()
+                .push(CustomField { definition, value });
+        }
+        Ok(result)
+    }
+
+    /// replaces the custom field values of an item, as (definition id, value).
+    /// Empty values are not stored. The values are checked against the type
+    /// of their field, the secret values must be encrypted by the caller if
+    /// the master passphrase is enabled.
+    pub fn set_for_item(
+        &self,
+        entity_type: EntityType,
+        entity_id: i32,
+        values: &[(i32, String)],
+    ) -> Result<Vec<CustomField>> {
+        self.conn.transaction(|| {
+            let project_id = self.item_project_id(entity_type, entity_id)?;
+            let mut kept_ids = vec![];
+            for (definition_id, value) in values.iter().filter(|(_, v)| !v.is_empty()) {
+                let definition = def::custom_field_definition
+                    .find(definition_id)
+                    .first::<CustomFieldDefinition>(self.conn)
+                    .optional()?
+                    .filter(|d| d.entity_type == entity_type && d.project_id == project_id)
+                    .ok_or_else(|| {
+                        Error::Constraint(format!(
+                            "no custom field {} for the {} items of project {}",
+                            definition_id, entity_type, project_id
+                        ))
+                    })?;
+                definition.field_type.check_value(value).map_err(|e| {
+                    Error::Constraint(format!("invalid {}: {}", definition.name, e))
+                })?;
+                kept_ids.push(definition.id);
+                let current = val::custom_field_value
+                    .filter(val::definition_id.eq(definition.id))
+                    .filter(val::entity_id.eq(entity_id))
+                    .select(val::value)
+                    .first::<String>(self.conn)
+                    .optional()?;
+                match current {
+                    Some(v) if &v == value => {}
+                    Some(_) => {
+                        diesel::update(
+                            val::custom_field_value
+                                .filter(val::definition_id.eq(definition.id))
+                                .filter(val::entity_id.eq(entity_id)),
+                        )
+                        .set(val::value.eq(value))
+                        .execute(self.conn)?;
+                    }
+                    None => {
+                        diesel::insert_into(val::custom_field_value)
+                            .values((
+                                val::definition_id.eq(definition.id),
+                                val::entity_id.eq(entity_id),
+                                val::value.eq(value),
+                            ))
+                            .execute(self.conn)?;
+                    }
+                }
+            }
+            let definitions_of_type = def::custom_field_definition
+                .filter(def::entity_type.eq(entity_type))
+                .select(def::id);
+            diesel::delete(
+                val::custom_field_value
+                    .filter(val::entity_id.eq(entity_id))
+                    .filter(val::definition_id.eq_any(definitions_of_type))
+                    .filter(val::definition_id.ne_all(&kept_ids)),
+            )
+            .execute(self.conn)?;
+            self.list_for_item(entity_type, entity_id)
+        })
+    }
+
+    /// the project of a server, database or website
+    fn item_project_id(&self, entity_type: EntityType, entity_id: i32) -> Result<i32> {
+        use crate::schema::server::dsl as srv;
+        use crate::schema::server_database::dsl as srv_db;
+        use crate::schema::server_website::dsl as srv_www;
+        Ok(match entity_type {
+            EntityType::Server => srv::server
+                .find(entity_id)
+                .select(srv::project_id)
+                .first(self.conn)?,
+            EntityType::ServerDatabase => srv_db::server_database
+                .inner_join(srv::server)
+                .filter(srv_db::id.eq(entity_id))
+                .select(srv::project_id)
+                .first(self.conn)?,
+            EntityType::ServerWebsite => srv_www::server_website
+                .inner_join(srv::server)
+                .filter(srv_www::id.eq(entity_id))
+                .select(srv::project_id)
+                .first(self.conn)?,
+            _ => {
+                return Err(Error::Constraint(format!(
+                    "the {} items have no custom fields",
+                    entity_type
+                )))
+            }
+        })
+    }
+}
@@ -0,0 +1,163 @@
+use super::{check_modified, insert_row};
+use crate::error::{Error, Result};
+use crate::models::Environment;
+use crate::schema::environment::dsl as env;
+use crate::schema::project_note_environment::dsl as note_env;
+use diesel::prelude::*;
+
+pub struct EnvironmentRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+macro_rules! environment_values {
+    ($environment:expr) => {
+        (
+            env::name.eq(&$environment.name),
+            env::short_label.eq(&$environment.short_label),
+            env::color.eq(&$environment.color),
+            env::ordering.eq($environment.ordering),
+            env::dangerous.eq($environment.dangerous),
+            env::project_id.eq($environment.project_id),
+        )
+    };
+}
+
+impl<'a> EnvironmentRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> EnvironmentRepo<'a> {
+        EnvironmentRepo { conn }
+    }
+
+    /// the environments of the project, sorted by their ordering
+    pub fn list_for_project(&self, project_id: i32) -> Result<Vec<Environment>> {
+        Ok(env::environment
+            .filter(env::project_id.eq(project_id))
+            .order((env::ordering.asc(), env::name.asc()))
+            .load(self.conn)?)
+    }
+
+    /// the environments of all the projects, sorted by project and ordering
+    pub fn list(&self) -> Result<Vec<Environment>> {
+        Ok(env::environment
+            .order((env::project_id.asc(), env::ordering.asc(), env::name.asc()))
+            .load(self.conn)?)
+    }
+
+    pub fn get(&self, id: i32) -> Result<Environment> {
+        Ok(env::environment.find(id).first(self.conn)?)
+    }
+
+    /// the id of the environment passed in is ignored, the inserted
+    /// environment is returned with its new id
+    pub fn insert(&self, environment: &Environment) -> Result<Environment> {
+        let id = insert_row(
+            self.conn,
+            diesel::insert_into(env::environment).values(environment_values!(environment)),
+        )?;
+        self.get(id)
+    }
+
+    /// renaming an environment also renames it in the servers and server links
+    pub fn update(&self, environment: &Environment) -> Result<Environment> {
+        check_modified(
+            diesel::update(env::environment.find(environment.id))
+                .set(environment_values!(environment))
+                .execute(self.conn)?,
+        )?;
+        self.get(environment.id)
+    }
+
+    /// fails with `Error::InUse` if servers or server links, even in
+    /// the trash, are in that environment
+    pub fn delete(&self, id: i32) -> Result<()> {
+        use crate::schema::server::dsl as srv;
+        use crate::schema::server_link::dsl as srv_link;
+        let environment = self.get(id)?;
+        let mut users: Vec<String> = srv::server
+            .filter(srv::project_id.eq(environment.project_id))
+            .filter(srv::environment.eq(&environment.name))
+            .order(srv::desc.asc())
+            .select(srv::desc)
+            .load(self.conn)?;
+        users.extend(
+            srv_link::server_link
+                .filter(srv_link::project_id.eq(environment.project_id))
+                .filter(srv_link::environment.eq(&environment.name))
+                .order(srv_link::desc.asc())
+                .select(srv_link::desc)
+                .load::<String>(self.conn)?,
+        );
+        if !users.is_empty() {
+            return Err(Error::InUse(format!(
+                "the environment {} is used by {}",
+                environment.name,
+                users.join(", ")
+            )));
+        }
+        check_modified(diesel::delete(env::environment.find(id)).execute(self.conn)?)
+    }
+
+    /// saves the environments of a project: inserts the environments with
+    /// id 0, updates the others, and deletes the environments of the project
+    /// which are not in the list. The ordering follows the order of the list.
+    pub fn save_for_project(
+        &self,
+        project_id: i32,
+        environments: &[Environment],
+    ) -> Result<Vec<Environment>> {
+        self.conn.transaction(|| {
+            for existing in self.list_for_project(project_id)? {
+                if !environments.iter().any(|e| e.id == existing.id) {
+                    self.delete(existing.id)?;
+                }
+            }
+            for (idx, environment) in environments.iter().enumerate() {
+                let environment = Environment {
+                    ordering: idx as i32,
+                    project_id,
+                    ..environment.clone()
+                };
+                if environment.id == 0 {
+                    self.insert(&environment)?;
+                } else {
+                    self.update(&environment)?;
+                }
+            }
+            self.list_for_project(project_id)
+        })
+    }
+
+    /// the environments a project note is shown in, sorted by their ordering
+    pub fn list_for_note(&self, project_note_id: i32) -> Result<Vec<Environment>> {
+        Ok(env::environment
+            .inner_join(note_env::project_note_environment)
+            .filter(note_env::project_note_id.eq(project_note_id))
+            .order((env::ordering.asc(), env::name.asc()))
+            .select(env::environment::all_columns())
+            .load(self.conn)?)
+    }
+
+    /// replaces the environments a project note is shown in
+    pub fn set_for_note(&self, project_note_id: i32, environment_ids: &[i32]) -> Result<()> {
+        self.conn.transaction(|| {
+            diesel::delete(
+                note_env::project_note_environment
+                    .filter(note_env::project_note_id.eq(project_note_id)),
+            )
+            .execute(self.conn)?;
+            diesel::insert_into(note_env::project_note_environment)
+                .values(
+                    environment_ids
+                        .iter()
+                        .map(|id| {
+                            (
+                                note_env::project_note_id.eq(project_note_id),
+                                note_env::environment_id.eq(id),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .execute(self.conn)?;
+            Ok(())
+        })
+    }
+}
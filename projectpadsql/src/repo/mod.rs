@@ -0,0 +1,63 @@
+// typed access to the database, shared by the GUI and ppcli, so that
+// the queries are not duplicated in each binary, and so that errors are
+// reported instead of panicking.
+mod attachment;
+mod custom_field;
+mod environment;
+mod project;
+mod server;
+mod server_dependency;
+mod server_poi;
+mod tag;
+
+pub use attachment::AttachmentRepo;
+pub use custom_field::CustomFieldRepo;
+pub use environment::EnvironmentRepo;
+pub use project::ProjectRepo;
+pub use server::ServerRepo;
+pub use server_dependency::ServerDependencyRepo;
+pub use server_poi::ServerPoiRepo;
+pub use tag::TagRepo;
+
+use crate::error::{Error, Result};
+use diesel::prelude::*;
+use diesel::query_dsl::methods::ExecuteDsl;
+
+no_arg_sql_function!(
+    last_insert_rowid,
+    diesel::sql_types::Integer,
+    "Represents the SQL last_insert_row() function"
+);
+
+/// insert a row and get back the id of the newly inserted row
+/// unfortunately sqlite doesn't support sql RETURNING
+pub(crate) fn insert_row(
+    conn: &SqliteConnection,
+    insert_statement: impl ExecuteDsl<SqliteConnection>,
+) -> Result<i32> {
+    match ExecuteDsl::execute(insert_statement, conn)? {
+        1 => Ok(diesel::select(last_insert_rowid).get_result::<i32>(conn)?),
+        x => Err(Error::Sql(diesel::result::Error::QueryBuilderError(
+            format!("Expected 1 row modified after insert, got {}", x).into(),
+        ))),
+    }
+}
+
+/// update and delete statements modify no rows when the id doesn't exist
+pub(crate) fn check_modified(count: usize) -> Result<()> {
+    if count == 0 {
+        Err(Error::NotFound)
+    } else {
+        Ok(())
+    }
+}
+
+/// sql LIKE pattern, matching the text anywhere
+pub(crate) fn like_pattern(text: &str) -> String {
+    format!(
+        "%{}%",
+        text.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
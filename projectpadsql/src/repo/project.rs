@@ -0,0 +1,67 @@
+use super::{check_modified, insert_row, like_pattern};
+use crate::error::Result;
+use crate::models::Project;
+use crate::schema::project::dsl as prj;
+use diesel::prelude::*;
+
+pub struct ProjectRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+impl<'a> ProjectRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> ProjectRepo<'a> {
+        ProjectRepo { conn }
+    }
+
+    /// all the projects not in the trash, sorted by name
+    pub fn list(&self) -> Result<Vec<Project>> {
+        Ok(prj::project
+            .filter(prj::deleted_at.is_null())
+            .order(prj::name.asc())
+            .load(self.conn)?)
+    }
+
+    pub fn get(&self, id: i32) -> Result<Project> {
+        Ok(prj::project.find(id).first(self.conn)?)
+    }
+
+    /// the id of the project passed in is ignored, the inserted
+    /// project is returned with its new id
+    pub fn insert(&self, project: &Project) -> Result<Project> {
+        let id = insert_row(
+            self.conn,
+            diesel::insert_into(prj::project).values((
+                prj::name.eq(&project.name),
+                prj::icon.eq(&project.icon),
+            )),
+        )?;
+        self.get(id)
+    }
+
+    pub fn update(&self, project: &Project) -> Result<Project> {
+        check_modified(
+            diesel::update(prj::project.find(project.id))
+                .set((
+                    prj::name.eq(&project.name),
+                    prj::icon.eq(&project.icon),
+                ))
+                .execute(self.conn)?,
+        )?;
+        self.get(project.id)
+    }
+
+    /// deletes the project for good, without going through the trash.
+    /// Also deletes the contents of the project (servers, notes...)
+    pub fn delete(&self, id: i32) -> Result<()> {
+        check_modified(diesel::delete(prj::project.find(id)).execute(self.conn)?)
+    }
+
+    /// the projects whose name contains the text, case-insensitive
+    pub fn search(&self, text: &str) -> Result<Vec<Project>> {
+        Ok(prj::project
+            .filter(prj::deleted_at.is_null())
+            .filter(prj::name.like(like_pattern(text)).escape('\\'))
+            .order(prj::name.asc())
+            .load(self.conn)?)
+    }
+}
@@ -0,0 +1,106 @@
+use super::{check_modified, insert_row, like_pattern};
+use crate::error::Result;
+use crate::models::Server;
+use crate::schema::server::dsl as srv;
+use diesel::prelude::*;
+
+pub struct ServerRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+// I tried to share the column list between insert and update with a
+// function returning the tuple, but the diesel types get unwieldy.
+macro_rules! server_values {
+    ($server:expr) => {
+        (
+            srv::desc.eq(&$server.desc),
+            srv::host.eq(&$server.host),
+            srv::port.eq($server.port),
+            srv::protocol.eq(&$server.protocol),
+            srv::path.eq(&$server.path),
+            srv::text.eq(&$server.text),
+            srv::is_retired.eq($server.is_retired),
+            srv::username.eq(&$server.username),
+            srv::password.eq(&$server.password),
+            srv::otp_secret.eq(&$server.otp_secret),
+            srv::auth_key.eq(&$server.auth_key),
+            srv::auth_key_filename.eq(&$server.auth_key_filename),
+            srv::server_type.eq($server.server_type),
+            srv::access_type.eq($server.access_type),
+            srv::ssh_tunnel_port.eq($server.ssh_tunnel_port),
+            srv::ssh_tunnel_through_server_id.eq($server.ssh_tunnel_through_server_id),
+            srv::environment.eq(&$server.environment),
+            srv::group_name.eq(&$server.group_name),
+            srv::project_id.eq($server.project_id),
+        )
+    };
+}
+
+impl<'a> ServerRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> ServerRepo<'a> {
+        ServerRepo { conn }
+    }
+
+    /// all the servers not in the trash, sorted by description
+    pub fn list(&self) -> Result<Vec<Server>> {
+        Ok(srv::server
+            .filter(srv::deleted_at.is_null())
+            .order(srv::desc.asc())
+            .load(self.conn)?)
+    }
+
+    pub fn list_for_project(&self, project_id: i32) -> Result<Vec<Server>> {
+        Ok(srv::server
+            .filter(srv::deleted_at.is_null())
+            .filter(srv::project_id.eq(project_id))
+            .order(srv::desc.asc())
+            .load(self.conn)?)
+    }
+
+    pub fn get(&self, id: i32) -> Result<Server> {
+        Ok(srv::server.find(id).first(self.conn)?)
+    }
+
+    /// the id of the server passed in is ignored, the inserted
+    /// server is returned with its new id
+    pub fn insert(&self, server: &Server) -> Result<Server> {
+        let id = insert_row(
+            self.conn,
+            diesel::insert_into(srv::server).values(server_values!(server)),
+        )?;
+        self.get(id)
+    }
+
+    pub fn update(&self, server: &Server) -> Result<Server> {
+        check_modified(
+            diesel::update(srv::server.find(server.id))
+                .set(server_values!(server))
+                .execute(self.conn)?,
+        )?;
+        self.get(server.id)
+    }
+
+    /// deletes the server for good, without going through the trash.
+    /// Also deletes the contents of the server (points of interest, notes...)
+    pub fn delete(&self, id: i32) -> Result<()> {
+        check_modified(diesel::delete(srv::server.find(id)).execute(self.conn)?)
+    }
+
+    /// the servers whose description, address, text or username
+    /// contain the text, case-insensitive
+    pub fn search(&self, text: &str) -> Result<Vec<Server>> {
+        let pattern = like_pattern(text);
+        Ok(srv::server
+            .filter(srv::deleted_at.is_null())
+            .filter(
+                srv::desc
+                    .like(&pattern)
+                    .escape('\\')
+                    .or(srv::host.like(&pattern).escape('\\'))
+                    .or(srv::text.like(&pattern).escape('\\'))
+                    .or(srv::username.like(&pattern).escape('\\')),
+            )
+            .order(srv::desc.asc())
+            .load(self.conn)?)
+    }
+}
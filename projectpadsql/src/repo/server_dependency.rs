@@ -0,0 +1,93 @@
+use super::{check_modified, insert_row};
+use crate::error::Result;
+use crate::models::ServerDependency;
+use crate::schema::server_dependency::dsl as dep;
+use diesel::prelude::*;
+
+pub struct ServerDependencyRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+macro_rules! dependency_values {
+    ($dependency:expr) => {
+        (
+            dep::server_id.eq($dependency.server_id),
+            dep::depends_on_server_id.eq($dependency.depends_on_server_id),
+            dep::dependency_type.eq($dependency.dependency_type),
+            dep::desc.eq(&$dependency.desc),
+        )
+    };
+}
+
+impl<'a> ServerDependencyRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> ServerDependencyRepo<'a> {
+        ServerDependencyRepo { conn }
+    }
+
+    /// the servers a server depends on, even in the trash, sorted by type.
+    /// For the servers which depend on a server, see `dependency_graph::dependents`
+    pub fn list_for_server(&self, server_id: i32) -> Result<Vec<ServerDependency>> {
+        Ok(dep::server_dependency
+            .filter(dep::server_id.eq(server_id))
+            .order((dep::dependency_type.asc(), dep::id.asc()))
+            .load(self.conn)?)
+    }
+
+    pub fn get(&self, id: i32) -> Result<ServerDependency> {
+        Ok(dep::server_dependency.find(id).first(self.conn)?)
+    }
+
+    /// the id of the dependency passed in is ignored, the inserted
+    /// dependency is returned with its new id. Fails with
+    /// `Error::Constraint` if the server would depend on itself, or
+    /// if it already has the same dependency.
+    pub fn insert(&self, dependency: &ServerDependency) -> Result<ServerDependency> {
+        let id = insert_row(
+            self.conn,
+            diesel::insert_into(dep::server_dependency).values(dependency_values!(dependency)),
+        )?;
+        self.get(id)
+    }
+
+    pub fn update(&self, dependency: &ServerDependency) -> Result<ServerDependency> {
+        check_modified(
+            diesel::update(dep::server_dependency.find(dependency.id))
+                .set(dependency_values!(dependency))
+                .execute(self.conn)?,
+        )?;
+        self.get(dependency.id)
+    }
+
+    pub fn delete(&self, id: i32) -> Result<()> {
+        check_modified(diesel::delete(dep::server_dependency.find(id)).execute(self.conn)?)
+    }
+
+    /// saves the dependencies of a server: inserts the dependencies with
+    /// id 0, updates the others, and deletes the dependencies of the server
+    /// which are not in the list
+    pub fn save_for_server(
+        &self,
+        server_id: i32,
+        dependencies: &[ServerDependency],
+    ) -> Result<Vec<ServerDependency>> {
+        self.conn.transaction(|| {
+            for existing in self.list_for_server(server_id)? {
+                if !dependencies.iter().any(|d| d.id == existing.id) {
+                    self.delete(existing.id)?;
+                }
+            }
+            for dependency in dependencies {
+                let dependency = ServerDependency {
+                    server_id,
+                    ..dependency.clone()
+                };
+                if dependency.id == 0 {
+                    self.insert(&dependency)?;
+                } else {
+                    self.update(&dependency)?;
+                }
+            }
+            self.list_for_server(server_id)
+        })
+    }
+}
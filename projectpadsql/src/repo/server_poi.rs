@@ -0,0 +1,90 @@
+use super::{check_modified, insert_row, like_pattern};
+use crate::error::Result;
+use crate::models::ServerPointOfInterest;
+use crate::schema::server_point_of_interest::dsl as poi;
+use diesel::prelude::*;
+
+pub struct ServerPoiRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+macro_rules! poi_values {
+    ($poi:expr) => {
+        (
+            poi::desc.eq(&$poi.desc),
+            poi::path.eq(&$poi.path),
+            poi::text.eq(&$poi.text),
+            poi::interest_type.eq($poi.interest_type),
+            poi::run_on.eq($poi.run_on),
+            poi::group_name.eq(&$poi.group_name),
+            poi::server_id.eq($poi.server_id),
+        )
+    };
+}
+
+impl<'a> ServerPoiRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> ServerPoiRepo<'a> {
+        ServerPoiRepo { conn }
+    }
+
+    /// all the server points of interest not in the trash, sorted by description
+    pub fn list(&self) -> Result<Vec<ServerPointOfInterest>> {
+        Ok(poi::server_point_of_interest
+            .filter(poi::deleted_at.is_null())
+            .order(poi::desc.asc())
+            .load(self.conn)?)
+    }
+
+    pub fn list_for_server(&self, server_id: i32) -> Result<Vec<ServerPointOfInterest>> {
+        Ok(poi::server_point_of_interest
+            .filter(poi::deleted_at.is_null())
+            .filter(poi::server_id.eq(server_id))
+            .order(poi::desc.asc())
+            .load(self.conn)?)
+    }
+
+    pub fn get(&self, id: i32) -> Result<ServerPointOfInterest> {
+        Ok(poi::server_point_of_interest.find(id).first(self.conn)?)
+    }
+
+    /// the id of the point of interest passed in is ignored, the inserted
+    /// point of interest is returned with its new id
+    pub fn insert(&self, server_poi: &ServerPointOfInterest) -> Result<ServerPointOfInterest> {
+        let id = insert_row(
+            self.conn,
+            diesel::insert_into(poi::server_point_of_interest).values(poi_values!(server_poi)),
+        )?;
+        self.get(id)
+    }
+
+    pub fn update(&self, server_poi: &ServerPointOfInterest) -> Result<ServerPointOfInterest> {
+        check_modified(
+            diesel::update(poi::server_point_of_interest.find(server_poi.id))
+                .set(poi_values!(server_poi))
+                .execute(self.conn)?,
+        )?;
+        self.get(server_poi.id)
+    }
+
+    /// deletes the point of interest for good, without going through the trash
+    pub fn delete(&self, id: i32) -> Result<()> {
+        check_modified(diesel::delete(poi::server_point_of_interest.find(id)).execute(self.conn)?)
+    }
+
+    /// the points of interest whose description, path or text
+    /// contain the text, case-insensitive
+    pub fn search(&self, text: &str) -> Result<Vec<ServerPointOfInterest>> {
+        let pattern = like_pattern(text);
+        Ok(poi::server_point_of_interest
+            .filter(poi::deleted_at.is_null())
+            .filter(
+                poi::desc
+                    .like(&pattern)
+                    .escape('\\')
+                    .or(poi::path.like(&pattern).escape('\\'))
+                    .or(poi::text.like(&pattern).escape('\\')),
+            )
+            .order(poi::desc.asc())
+            .load(self.conn)?)
+    }
+}
@@ -0,0 +1,104 @@
+use super::insert_row;
+use crate::error::Result;
+use crate::models::{EntityType, Tag};
+use crate::schema::item_tag::dsl as item_tag;
+use crate::schema::tag::dsl as tag;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+pub struct TagRepo<'a> {
+    conn: &'a SqliteConnection,
+}
+
+impl<'a> TagRepo<'a> {
+    pub fn new(conn: &'a SqliteConnection) -> TagRepo<'a> {
+        TagRepo { conn }
+    }
+
+    /// all the tags, sorted by name. Tags exist only as long
+    /// as some item has them.
+    pub fn list(&self) -> Result<Vec<Tag>> {
+        Ok(tag::tag.order(tag::name.asc()).load(self.conn)?)
+    }
+
+    /// the tags of an item, sorted by name
+    pub fn list_for_item(&self, entity_type: EntityType, entity_id: i32) -> Result<Vec<Tag>> {
+        Ok(tag::tag
+            .inner_join(item_tag::item_tag)
+            .filter(item_tag::entity_type.eq(entity_type))
+            .filter(item_tag::entity_id.eq(entity_id))
+            .order(tag::name.asc())
+            .select(tag::tag::all_columns())
+            .load(self.conn)?)
+    }
+
+    /// the tags of items of the same type, sorted by name, by item id.
+    /// Items without tags are missing from the map.
+    pub fn list_for_items(
+        &self,
+        entity_type: EntityType,
+        entity_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<Tag>>> {
+        let rows = tag::tag
+            .inner_join(item_tag::item_tag)
+            .filter(item_tag::entity_type.eq(entity_type))
+            .filter(item_tag::entity_id.eq_any(entity_ids))
+            .order(tag::name.asc())
+            .select((item_tag::entity_id, tag::tag::all_columns()))
+            .load::<(i32, Tag)>(self.conn)?;
+        let mut result: HashMap<i32, Vec<Tag>> = HashMap::new();
+        for (entity_id, t) in rows {
+            result.entry(entity_id).or_default().push(t);
+        }
+        Ok(result)
+    }
+
+    /// replaces the tags of an item. The names are normalized with
+    /// `Tag::normalize_name`, and the tags which don't exist yet are created.
+    pub fn set_for_item(
+        &self,
+        entity_type: EntityType,
+        entity_id: i32,
+        names: &[String],
+    ) -> Result<Vec<Tag>> {
+        self.conn.transaction(|| {
+            let mut tag_ids = vec![];
+            for name in names.iter().map(|n| Tag::normalize_name(n)) {
+                if name.is_empty() {
+                    continue;
+                }
+                let existing_id = tag::tag
+                    .filter(tag::name.eq(&name))
+                    .select(tag::id)
+                    .first::<i32>(self.conn)
+                    .optional()?;
+                tag_ids.push(match existing_id {
+                    Some(id) => id,
+                    None => insert_row(
+                        self.conn,
+                        diesel::insert_into(tag::tag).values(tag::name.eq(&name)),
+                    )?,
+                });
+            }
+            // delete only the tags the item loses: deleting the last
+            // item of a tag deletes the tag
+            diesel::delete(
+                item_tag::item_tag
+                    .filter(item_tag::entity_type.eq(entity_type))
+                    .filter(item_tag::entity_id.eq(entity_id))
+                    .filter(item_tag::tag_id.ne_all(&tag_ids)),
+            )
+            .execute(self.conn)?;
+            for tag_id in &tag_ids {
+                diesel::insert_or_ignore_into(item_tag::item_tag)
+                    .values((
+                        item_tag::tag_id.eq(tag_id),
+                        item_tag::entity_type.eq(entity_type),
+                        item_tag::entity_id.eq(entity_id),
+                    ))
+                    .execute(self.conn)?;
+            }
+            self.list_for_item(entity_type, entity_id)
+        })
+    }
+}
@@ -4,10 +4,7 @@ table! {
         id -> Integer,
         name -> Varchar,
         icon -> Nullable<Binary>,
-        has_dev -> Bool,
-        has_uat -> Bool,
-        has_stage -> Bool,
-        has_prod -> Bool,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -15,11 +12,17 @@ table! {
     server {
         id -> Integer,
         desc -> Varchar,
-        ip -> Varchar,
+        #[sql_name="ip"]
+        host -> Varchar,
+        port -> Nullable<Integer>,
+        protocol -> Nullable<Varchar>,
+        path -> Varchar,
         text -> Varchar,
         is_retired -> Bool,
         username -> Varchar,
         password -> Varchar,
+        password_changed_at -> Nullable<Timestamp>,
+        otp_secret -> Varchar,
         auth_key -> Nullable<Binary>,
         auth_key_filename -> Nullable<Varchar>,
         #[sql_name="type"]
@@ -30,6 +33,7 @@ table! {
         environment -> Varchar,
         group_name -> Nullable<Varchar>,
         project_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -38,12 +42,9 @@ table! {
         id -> Integer,
         title -> Varchar,
         contents -> Varchar,
-        has_dev -> Bool,
-        has_uat -> Bool,
-        has_stage -> Bool,
-        has_prod -> Bool,
         group_name -> Nullable<Varchar>,
         project_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -56,6 +57,7 @@ table! {
         interest_type -> Varchar,
         group_name -> Nullable<Varchar>,
         project_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -68,6 +70,7 @@ table! {
         environment -> Varchar,
         group_name -> Nullable<Varchar>,
         project_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -79,9 +82,12 @@ table! {
         text -> Varchar,
         username -> Varchar,
         password -> Varchar,
+        password_changed_at -> Nullable<Timestamp>,
+        otp_secret -> Varchar,
         server_database_id -> Nullable<Integer>,
         group_name -> Nullable<Varchar>,
         server_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -95,6 +101,7 @@ table! {
         run_on -> Varchar,
         group_name -> Nullable<Varchar>,
         server_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -105,6 +112,7 @@ table! {
         contents -> Varchar,
         group_name -> Nullable<Varchar>,
         server_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -113,11 +121,14 @@ table! {
         id -> Integer,
         username -> Varchar,
         password -> Varchar,
+        password_changed_at -> Nullable<Timestamp>,
+        otp_secret -> Varchar,
         desc -> Varchar,
         auth_key -> Nullable<Binary>,
         auth_key_filename -> Nullable<Varchar>,
         group_name -> Nullable<Varchar>,
         server_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -129,8 +140,95 @@ table! {
         text -> Varchar,
         username -> Varchar,
         password -> Varchar,
+        password_changed_at -> Nullable<Timestamp>,
         group_name -> Nullable<Varchar>,
         server_id -> Integer,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    environment {
+        id -> Integer,
+        name -> Varchar,
+        short_label -> Varchar,
+        color -> Varchar,
+        ordering -> Integer,
+        dangerous -> Bool,
+        project_id -> Integer,
+    }
+}
+
+table! {
+    project_note_environment (project_note_id, environment_id) {
+        project_note_id -> Integer,
+        environment_id -> Integer,
+    }
+}
+
+table! {
+    tag {
+        id -> Integer,
+        name -> Varchar,
+    }
+}
+
+table! {
+    item_tag (tag_id, entity_type, entity_id) {
+        tag_id -> Integer,
+        entity_type -> Varchar,
+        entity_id -> Integer,
+    }
+}
+
+table! {
+    attachment {
+        id -> Integer,
+        entity_type -> Varchar,
+        entity_id -> Integer,
+        name -> Varchar,
+        mime -> Varchar,
+        size -> Integer,
+        contents -> Binary,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    custom_field_definition {
+        id -> Integer,
+        project_id -> Integer,
+        entity_type -> Varchar,
+        name -> Varchar,
+        field_type -> Varchar,
+        ordering -> Integer,
+    }
+}
+
+table! {
+    custom_field_value {
+        id -> Integer,
+        definition_id -> Integer,
+        entity_id -> Integer,
+        value -> Varchar,
+    }
+}
+
+table! {
+    password_policy (project_id) {
+        project_id -> Integer,
+        max_age_days -> Integer,
+        dangerous_environments_only -> Bool,
+    }
+}
+
+table! {
+    server_dependency {
+        id -> Integer,
+        server_id -> Integer,
+        depends_on_server_id -> Integer,
+        dependency_type -> Varchar,
+        desc -> Varchar,
     }
 }
 
@@ -142,6 +240,15 @@ table! {
     }
 }
 
+table! {
+    secret_settings (id) {
+        id -> Integer,
+        salt -> Binary,
+        iterations -> Integer,
+        check_value -> Varchar,
+    }
+}
+
 joinable!(server_website -> server_database (server_database_id));
 joinable!(server_website -> server (server_id));
 
@@ -162,3 +269,15 @@ allow_tables_to_appear_in_same_query!(
     server_database,
     server
 );
+
+joinable!(environment -> project (project_id));
+joinable!(project_note_environment -> environment (environment_id));
+joinable!(project_note_environment -> project_note (project_note_id));
+allow_tables_to_appear_in_same_query!(environment, project_note_environment, project_note);
+allow_tables_to_appear_in_same_query!(environment, project);
+
+joinable!(item_tag -> tag (tag_id));
+allow_tables_to_appear_in_same_query!(tag, item_tag);
+
+joinable!(custom_field_value -> custom_field_definition (definition_id));
+allow_tables_to_appear_in_same_query!(custom_field_definition, custom_field_value);
@@ -0,0 +1,170 @@
+// full-text search over all the entities, using the sqlite FTS5
+// search_index table, which is maintained by triggers (see migration 23).
+use crate::error::Result;
+use crate::models::{EntityType, Tag};
+use crate::repo::like_pattern;
+use diesel::prelude::*;
+use diesel::sql_types::{Double, Integer, Nullable, Text};
+
+/// the snippets surround the matches with these markers
+pub const SNIPPET_MATCH_START: char = '\u{2}';
+pub const SNIPPET_MATCH_END: char = '\u{3}';
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    /// only search these entity types. Search all of them if empty.
+    pub entity_types: Vec<EntityType>,
+    /// only search the projects whose name contains this text, case-insensitive
+    pub project_name: Option<String>,
+    /// only search the entities which have all these tags
+    pub tags: Vec<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub entity_type: EntityType,
+    pub entity_id: i32,
+    pub project_id: i32,
+    /// None for entities which are not under a server
+    pub server_id: Option<i32>,
+    pub title: String,
+    /// an extract of the contents, with the matches surrounded by
+    /// SNIPPET_MATCH_START and SNIPPET_MATCH_END
+    pub snippet: String,
+    /// lower is better
+    pub rank: f64,
+}
+
+#[derive(QueryableByName)]
+struct SearchRow {
+    #[sql_type = "Text"]
+    entity_type: EntityType,
+    #[sql_type = "Integer"]
+    entity_id: i32,
+    #[sql_type = "Integer"]
+    project_id: i32,
+    #[sql_type = "Nullable<Integer>"]
+    server_id: Option<i32>,
+    #[sql_type = "Text"]
+    title: String,
+    #[sql_type = "Text"]
+    snippet: String,
+    // "rank" is a hidden column of FTS5 tables
+    #[sql_type = "Double"]
+    #[column_name = "score"]
+    rank: f64,
+}
+
+/// every word of the query must match the start of a word in the index.
+/// The words are quoted so that the FTS5 query syntax is not interpreted,
+/// and so that for instance "10.0.1" matches as a phrase.
+fn fts_match_expression(query: &str) -> Option<String> {
+    let terms: Vec<_> = query
+        .split_whitespace()
+        .map(|t| format!("\"{}\"*", t.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+fn search_sql(has_match: bool, filters: &SearchFilters) -> String {
+    // an empty query matches everything, without ranking
+    let (snippet, rank, match_clause) = if has_match {
+        (
+            "snippet(search_index, 3, char(2), char(3), '…', 12)",
+            // matches in the title count more than in the body
+            "bm25(search_index, 0.0, 0.0, 10.0, 1.0)",
+            "search_index MATCH ?",
+        )
+    } else {
+        ("substr(search_index.body, 1, 80)", "0.0", "1")
+    };
+    let types_clause = if filters.entity_types.is_empty() {
+        "".to_string()
+    } else {
+        format!(
+            " AND search_index.entity_type IN ({})",
+            filters
+                .entity_types
+                .iter()
+                .map(|t| format!("'{}'", t))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+    // the tag names are normalized, but may still contain quotes
+    let tags_clause: String = filters
+        .tags
+        .iter()
+        .map(|t| {
+            format!(
+                " AND EXISTS (SELECT 1 FROM item_tag JOIN tag ON tag.id = item_tag.tag_id \
+                              WHERE item_tag.entity_type = search_index.entity_type \
+                              AND item_tag.entity_id = search_index.entity_id \
+                              AND tag.name = '{}')",
+                Tag::normalize_name(t).replace('\'', "''")
+            )
+        })
+        .collect();
+    format!(
+        "SELECT search_index.entity_type, search_index.entity_id, \
+                parent.project_id, parent.server_id, search_index.title, \
+                {} AS snippet, {} AS score \
+         FROM search_index \
+         JOIN search_entity_parent parent \
+              ON parent.entity_type = search_index.entity_type \
+              AND parent.entity_id = search_index.entity_id \
+         JOIN project ON project.id = parent.project_id \
+         WHERE {}{}{} AND (? IS NULL OR project.name LIKE ? ESCAPE '\\') \
+         ORDER BY score, search_index.title{}",
+        snippet,
+        rank,
+        match_clause,
+        types_clause,
+        tags_clause,
+        filters
+            .limit
+            .map(|l| format!(" LIMIT {}", l))
+            .unwrap_or_default()
+    )
+}
+
+/// search all the entities for the words of the query, best matches first.
+/// An empty query returns all the entities matching the filters.
+pub fn search(
+    conn: &SqliteConnection,
+    query: &str,
+    filters: &SearchFilters,
+) -> Result<Vec<SearchHit>> {
+    let match_expr = fts_match_expression(query);
+    let sql = search_sql(match_expr.is_some(), filters);
+    let project_pattern = filters.project_name.as_deref().map(like_pattern);
+    let rows = match match_expr {
+        Some(m) => diesel::sql_query(sql)
+            .bind::<Text, _>(m)
+            .bind::<Nullable<Text>, _>(&project_pattern)
+            .bind::<Nullable<Text>, _>(&project_pattern)
+            .load::<SearchRow>(conn)?,
+        None => diesel::sql_query(sql)
+            .bind::<Nullable<Text>, _>(&project_pattern)
+            .bind::<Nullable<Text>, _>(&project_pattern)
+            .load::<SearchRow>(conn)?,
+    };
+    rows.into_iter()
+        .map(|row| {
+            Ok(SearchHit {
+                entity_type: row.entity_type,
+                entity_id: row.entity_id,
+                project_id: row.project_id,
+                server_id: row.server_id,
+                title: row.title,
+                snippet: row.snippet,
+                rank: row.rank,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,506 @@
+// a second layer of protection for the passwords, on top of the database
+// password: with a master passphrase, the passwords of the servers, websites,
+// databases and extra users, their TOTP secrets, the secret custom fields,
+// and the [pass`...`] spans of the notes, are stored encrypted and only
+// decrypted on demand. Unlocking the database is then not enough to read
+// them. Without a master passphrase (the default), they are stored in plain
+// text. The settings are in the secret_settings table (see migration 29).
+use crate::error::{Error, Result};
+use crate::models::EntityType;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, Text};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{aead, pbkdf2};
+use std::fmt;
+use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
+
+/// the encrypted values start with this prefix, followed by the base64
+/// of the nonce and of the AES-256-GCM ciphertext
+pub const ENCRYPTED_PREFIX: &str = "ppsecret:v1:";
+
+pub(crate) const PBKDF2_ITERATIONS: u32 = 100_000;
+pub(crate) const SALT_LEN: usize = 16;
+/// encrypted with the key in secret_settings.check_value, to check the passphrase
+pub(crate) const CHECK_TEXT: &str = "projectpad";
+
+/// the passwords, and the TOTP secrets which are as sensitive
+const PASSWORD_COLUMNS: &[(EntityType, &str)] = &[
+    (EntityType::Server, "password"),
+    (EntityType::Server, "otp_secret"),
+    (EntityType::ServerWebsite, "password"),
+    (EntityType::ServerWebsite, "otp_secret"),
+    (EntityType::ServerDatabase, "password"),
+    (EntityType::ServerExtraUserAccount, "password"),
+    (EntityType::ServerExtraUserAccount, "otp_secret"),
+];
+
+/// the notes can contain passwords, see `map_note_passwords`
+const NOTE_COLUMNS: &[(EntityType, &str)] = &[
+    (EntityType::ProjectNote, "contents"),
+    (EntityType::ServerNote, "contents"),
+];
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// the key derived from the master passphrase
+#[derive(Clone)]
+pub struct SecretKey([u8; 32]);
+
+// don't leak the key in logs
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKey(..)")
+    }
+}
+
+impl SecretKey {
+    pub(crate) fn derive(passphrase: &str, salt: &[u8], iterations: NonZeroU32) -> SecretKey {
+        let mut key = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            salt,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        SecretKey(key)
+    }
+
+    fn aead_key(&self) -> aead::LessSafeKey {
+        aead::LessSafeKey::new(
+            aead::UnboundKey::new(&aead::AES_256_GCM, &self.0).expect("invalid AES-256 key"),
+        )
+    }
+
+    /// the empty string stays empty: it's still visible that there
+    /// is no password
+    pub fn encrypt(&self, plain: &str) -> String {
+        if plain.is_empty() {
+            return "".to_string();
+        }
+        let mut nonce = [0u8; aead::NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .expect("failed generating a nonce");
+        let mut in_out = plain.as_bytes().to_vec();
+        self.aead_key()
+            .seal_in_place_append_tag(
+                aead::Nonce::assume_unique_for_key(nonce),
+                aead::Aad::empty(),
+                &mut in_out,
+            )
+            .expect("failed encrypting a secret");
+        let mut bytes = nonce.to_vec();
+        bytes.extend(in_out);
+        format!("{}{}", ENCRYPTED_PREFIX, base64::encode(&bytes))
+    }
+
+    /// the values which are not encrypted are returned as they are
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let encoded = match value.strip_prefix(ENCRYPTED_PREFIX) {
+            Some(e) => e,
+            None => return Ok(value.to_string()),
+        };
+        let undecryptable = || Error::Corrupt("a password can't be decrypted".to_string());
+        let mut nonce = base64::decode(encoded).map_err(|_| undecryptable())?;
+        if nonce.len() < aead::NONCE_LEN {
+            return Err(undecryptable());
+        }
+        let mut in_out = nonce.split_off(aead::NONCE_LEN);
+        let plain = self
+            .aead_key()
+            .open_in_place(
+                aead::Nonce::try_assume_unique_for_key(&nonce).map_err(|_| undecryptable())?,
+                aead::Aad::empty(),
+                &mut in_out,
+            )
+            .map_err(|_| undecryptable())?;
+        String::from_utf8(plain.to_vec()).map_err(|_| undecryptable())
+    }
+
+    /// encrypts the passwords of a note which are in plain text
+    pub fn encrypt_note(&self, contents: &str) -> String {
+        map_note_passwords(contents, |p| {
+            Ok(if is_encrypted(p) {
+                p.to_string()
+            } else {
+                self.encrypt(p)
+            })
+        })
+        .expect("encrypting can't fail")
+    }
+
+    pub fn decrypt_note(&self, contents: &str) -> Result<String> {
+        map_note_passwords(contents, |p| self.decrypt(p))
+    }
+}
+
+/// whether a note has passwords in plain text, which must be encrypted
+/// before saving it if the passwords are protected
+pub fn note_has_plain_passwords(contents: &str) -> bool {
+    let mut found = false;
+    let _ = map_note_passwords(contents, |p| {
+        found |= !p.is_empty() && !is_encrypted(p);
+        Ok(p.to_string())
+    });
+    found
+}
+
+/// calls `f` on the passwords of a note and replaces them with the
+/// result. The passwords are written [pass`...`], the value being a markdown
+/// code span: it can be delimited by several backticks, if it contains some.
+fn map_note_passwords(contents: &str, mut f: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    const START: &str = "[pass`";
+    let mut result = String::with_capacity(contents.len());
+    let mut rest = contents;
+    while let Some(idx) = rest.find(START) {
+        // from the first backtick
+        let code = &rest[idx + START.len() - 1..];
+        let fence = code.len() - code.trim_start_matches('`').len();
+        match find_closing_fence(&code[fence..], fence) {
+            Some(end) => {
+                let span = &code[..fence + end + fence];
+                let value = code_span_contents(&span[fence..fence + end]);
+                let new_value = f(value)?;
+                result.push_str(&rest[..idx]);
+                result.push_str("[pass");
+                if new_value == value {
+                    result.push_str(span);
+                } else {
+                    result.push_str(&code_span(&new_value));
+                }
+                result.push(']');
+                rest = &code[span.len() + 1..];
+            }
+            None => {
+                result.push_str(&rest[..idx + START.len()]);
+                rest = &rest[idx + START.len()..];
+            }
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// the position of a run of exactly `fence` backticks followed by ']'
+fn find_closing_fence(body: &str, fence: usize) -> Option<usize> {
+    let bytes = body.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'`' {
+            let run = bytes[i..].iter().take_while(|b| **b == b'`').count();
+            if run == fence && bytes.get(i + run) == Some(&b']') {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// as in commonmark, a space is stripped on both sides if there's one
+/// on both sides, so that `` `x` `` is the code `x`
+fn code_span_contents(body: &str) -> &str {
+    if body.len() >= 2 && body.starts_with(' ') && body.ends_with(' ') && !body.trim().is_empty() {
+        &body[1..body.len() - 1]
+    } else {
+        body
+    }
+}
+
+fn code_span(value: &str) -> String {
+    let longest_run = value.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run + 1);
+    if value.starts_with('`') || value.ends_with('`') || code_span_contents(value) != value {
+        format!("{0} {1} {0}", fence, value)
+    } else {
+        format!("{0}{1}{0}", fence, value)
+    }
+}
+
+#[derive(Queryable)]
+struct SecretSettings {
+    _id: i32,
+    salt: Vec<u8>,
+    iterations: i32,
+    check_value: String,
+}
+
+fn load_settings(conn: &SqliteConnection) -> Result<Option<SecretSettings>> {
+    use crate::schema::secret_settings::dsl as sec;
+    Ok(sec::secret_settings
+        .first::<SecretSettings>(conn)
+        .optional()?)
+}
+
+/// whether the passwords are protected by a master passphrase
+pub fn is_enabled(conn: &SqliteConnection) -> Result<bool> {
+    Ok(load_settings(conn)?.is_some())
+}
+
+/// the key for the master passphrase. Error::NotFound if the passwords
+/// are not protected by a master passphrase.
+pub fn unlock(conn: &SqliteConnection, passphrase: &str) -> Result<SecretKey> {
+    let settings = load_settings(conn)?.ok_or(Error::NotFound)?;
+    let iterations = NonZeroU32::new(settings.iterations as u32)
+        .ok_or_else(|| Error::Corrupt("invalid master passphrase settings".to_string()))?;
+    let key = SecretKey::derive(passphrase, &settings.salt, iterations);
+    match key.decrypt(&settings.check_value) {
+        Ok(check) if check == CHECK_TEXT => Ok(key),
+        _ => Err(Error::WrongPassphrase),
+    }
+}
+
+/// protect the passwords with a master passphrase: encrypts all of them,
+/// including those in the trash and in the change log
+pub fn enable(conn: &SqliteConnection, passphrase: &str) -> Result<SecretKey> {
+    use crate::schema::secret_settings::dsl as sec;
+    conn.transaction(|| {
+        if is_enabled(conn)? {
+            return Err(Error::Constraint(
+                "the passwords are already protected by a master passphrase".to_string(),
+            ));
+        }
+        let mut salt = [0u8; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .expect("failed generating a salt");
+        let key = SecretKey::derive(
+            passphrase,
+            &salt,
+            NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+        );
+        diesel::insert_into(sec::secret_settings)
+            .values((
+                sec::id.eq(1),
+                sec::salt.eq(&salt[..]),
+                sec::iterations.eq(PBKDF2_ITERATIONS as i32),
+                sec::check_value.eq(key.encrypt(CHECK_TEXT)),
+            ))
+            .execute(conn)?;
+        encrypt_plain_passwords(conn, &key)?;
+        Ok(key)
+    })
+}
+
+/// encrypts the passwords which are still in plain text, for instance
+/// after importing projects
+pub fn encrypt_plain_passwords(conn: &SqliteConnection, key: &SecretKey) -> Result<()> {
+    conn.transaction(|| {
+        rewrite_secrets(
+            conn,
+            |p| {
+                Ok(if is_encrypted(p) {
+                    p.to_string()
+                } else {
+                    key.encrypt(p)
+                })
+            },
+            |n| Ok(key.encrypt_note(n)),
+        )
+    })
+}
+
+/// remove the master passphrase: the passwords are stored in plain text again
+pub fn disable(conn: &SqliteConnection, key: &SecretKey) -> Result<()> {
+    use crate::schema::secret_settings::dsl as sec;
+    conn.transaction(|| {
+        rewrite_secrets(conn, |p| key.decrypt(p), |n| key.decrypt_note(n))?;
+        diesel::delete(sec::secret_settings).execute(conn)?;
+        Ok(())
+    })
+}
+
+/// calls `f` with the passwords in plain text in the database, for instance to
+/// export them to a file which must be readable without the master passphrase.
+/// The passwords are decrypted in a transaction which is then rolled back.
+pub fn with_plain_passwords<T>(
+    conn: &SqliteConnection,
+    key: &SecretKey,
+    f: impl FnOnce() -> T,
+) -> Result<T> {
+    let mut result = None;
+    let outcome = conn.transaction::<(), Error, _>(|| {
+        disable(conn, key)?;
+        result = Some(f());
+        Err(Error::Sql(diesel::result::Error::RollbackTransaction))
+    });
+    match (outcome, result) {
+        (Err(Error::Sql(diesel::result::Error::RollbackTransaction)), Some(r)) => Ok(r),
+        (Err(e), _) => Err(e),
+        (Ok(()), _) => unreachable!("the transaction is always rolled back"),
+    }
+}
+
+#[derive(QueryableByName)]
+struct IdValue {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    value: String,
+}
+
+#[derive(QueryableByName)]
+struct ChangeLogValues {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Nullable<Text>"]
+    old_value: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    new_value: Option<String>,
+}
+
+/// apply `password_fn` to the passwords and `note_fn` to the notes, in the
+/// tables and in the change log, which would otherwise keep the old values.
+/// Rewriting them is not a change made by the user: it's not logged.
+fn rewrite_secrets(
+    conn: &SqliteConnection,
+    mut password_fn: impl FnMut(&str) -> Result<String>,
+    mut note_fn: impl FnMut(&str) -> Result<String>,
+) -> Result<()> {
+    let change_log_start =
+        diesel::sql_query("SELECT COALESCE(MAX(id), 0) AS id, '' AS value FROM change_log")
+            .get_result::<IdValue>(conn)?
+            .id;
+    for (entity_type, column) in PASSWORD_COLUMNS {
+        rewrite_column(conn, *entity_type, column, &mut password_fn)?;
+    }
+    for (entity_type, column) in NOTE_COLUMNS {
+        rewrite_column(conn, *entity_type, column, &mut note_fn)?;
+    }
+    rewrite_secret_custom_fields(conn, &mut password_fn)?;
+    diesel::sql_query("DELETE FROM change_log WHERE id > ?")
+        .bind::<Integer, _>(change_log_start)
+        .execute(conn)?;
+    for (entity_type, column) in PASSWORD_COLUMNS {
+        rewrite_change_log(conn, *entity_type, column, &mut password_fn)?;
+    }
+    for (entity_type, column) in NOTE_COLUMNS {
+        rewrite_change_log(conn, *entity_type, column, &mut note_fn)?;
+    }
+    // the full-text index of the notes may still have the old
+    // passwords in its segments: rebuild them
+    conn.execute("INSERT INTO search_index(search_index) VALUES('optimize')")?;
+    Ok(())
+}
+
+fn rewrite_column(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    column: &str,
+    f: &mut impl FnMut(&str) -> Result<String>,
+) -> Result<()> {
+    let rows = diesel::sql_query(format!(
+        "SELECT id, {} AS value FROM {}",
+        column, entity_type
+    ))
+    .load::<IdValue>(conn)?;
+    for row in rows {
+        let new_value = f(&row.value)?;
+        if new_value != row.value {
+            diesel::sql_query(format!(
+                "UPDATE {} SET {} = ? WHERE id = ?",
+                entity_type, column
+            ))
+            .bind::<Text, _>(new_value)
+            .bind::<Integer, _>(row.id)
+            .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// the values of the custom fields of type Secret. They're
+/// not in the change log.
+fn rewrite_secret_custom_fields(
+    conn: &SqliteConnection,
+    f: &mut impl FnMut(&str) -> Result<String>,
+) -> Result<()> {
+    let rows = diesel::sql_query(
+        "SELECT v.id, v.value FROM custom_field_value v \
+           JOIN custom_field_definition d ON d.id = v.definition_id \
+          WHERE d.field_type = 'Secret'",
+    )
+    .load::<IdValue>(conn)?;
+    for row in rows {
+        let new_value = f(&row.value)?;
+        if new_value != row.value {
+            diesel::sql_query("UPDATE custom_field_value SET value = ? WHERE id = ?")
+                .bind::<Text, _>(new_value)
+                .bind::<Integer, _>(row.id)
+                .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+fn rewrite_change_log(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    column: &str,
+    f: &mut impl FnMut(&str) -> Result<String>,
+) -> Result<()> {
+    let path = format!("$.{}", column);
+    let changes = diesel::sql_query(
+        "SELECT id, json_extract(old_row, ?) AS old_value, \
+                json_extract(new_row, ?) AS new_value \
+           FROM change_log WHERE entity_type = ?",
+    )
+    .bind::<Text, _>(&path)
+    .bind::<Text, _>(&path)
+    .bind::<Text, _>(entity_type.to_string())
+    .load::<ChangeLogValues>(conn)?;
+    for change in changes {
+        let values = [("old_row", change.old_value), ("new_row", change.new_value)];
+        for (row_column, value) in values.iter() {
+            if let Some(value) = value {
+                let new_value = f(value)?;
+                if &new_value != value {
+                    diesel::sql_query(format!(
+                        "UPDATE change_log SET {0} = json_set({0}, ?, ?) WHERE id = ?",
+                        row_column
+                    ))
+                    .bind::<Text, _>(&path)
+                    .bind::<Text, _>(new_value)
+                    .bind::<Integer, _>(change.id)
+                    .execute(conn)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the key of an unlocked master passphrase, forgotten after a timeout
+/// so that the passphrase must be typed again
+pub struct SecretSession {
+    timeout: Duration,
+    key: Option<(SecretKey, Instant)>,
+}
+
+impl SecretSession {
+    pub fn new(timeout: Duration) -> SecretSession {
+        SecretSession { timeout, key: None }
+    }
+
+    /// the key, if it was unlocked less than the timeout ago
+    pub fn key(&mut self) -> Option<SecretKey> {
+        if matches!(&self.key, Some((_, unlocked_at)) if unlocked_at.elapsed() >= self.timeout) {
+            self.key = None;
+        }
+        self.key.as_ref().map(|(k, _)| k.clone())
+    }
+
+    pub fn unlocked(&mut self, key: SecretKey) {
+        self.key = Some((key, Instant::now()));
+    }
+
+    pub fn lock(&mut self) {
+        self.key = None;
+    }
+}
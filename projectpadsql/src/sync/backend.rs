@@ -0,0 +1,191 @@
+// where the devices exchange their changes. Every device only writes its
+// own files, which are never modified afterwards, so a folder shared with
+// syncthing or NFS works as well as a git repository: there is nothing to merge.
+use crate::error::{Error, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub trait SyncBackend {
+    /// bring the local view of the shared files up to date
+    fn fetch(&self) -> Result<()>;
+
+    /// the shared files, as paths relative to the root, separated by /
+    fn list_files(&self) -> Result<Vec<String>>;
+
+    fn read_file(&self, name: &str) -> Result<Vec<u8>>;
+
+    fn write_file(&self, name: &str, contents: &[u8]) -> Result<()>;
+
+    /// share the files written since the previous call
+    fn publish(&self, message: &str) -> Result<()>;
+}
+
+/// a local folder, which something else shares between the devices
+pub struct FolderBackend {
+    root: PathBuf,
+}
+
+impl FolderBackend {
+    pub fn new(root: impl Into<PathBuf>) -> FolderBackend {
+        FolderBackend { root: root.into() }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        name.split('/')
+            .fold(self.root.clone(), |path, part| path.join(part))
+    }
+}
+
+fn list_folder(folder: &Path, prefix: &str, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        // .git, .stfolder, the temporary files of the file sharing tools...
+        if name.starts_with('.') {
+            continue;
+        }
+        let relative = format!("{}{}", prefix, name);
+        if entry.file_type()?.is_dir() {
+            list_folder(&entry.path(), &format!("{}/", relative), files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+impl SyncBackend for FolderBackend {
+    fn fetch(&self) -> Result<()> {
+        if self.root.is_dir() {
+            Ok(())
+        } else {
+            Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the sync folder {} doesn't exist", self.root.display()),
+            )))
+        }
+    }
+
+    fn list_files(&self) -> Result<Vec<String>> {
+        let mut files = vec![];
+        list_folder(&self.root, "", &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    fn read_file(&self, name: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.path(name))?)
+    }
+
+    fn write_file(&self, name: &str, contents: &[u8]) -> Result<()> {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // the other devices must never see a half-written file.
+        // The dot hides the temporary file from list_files
+        let tmp = path.with_file_name(format!(
+            ".{}.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn publish(&self, _message: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// a clone of a git repository, which the devices pull from and push to
+pub struct GitBackend {
+    folder: FolderBackend,
+    work_tree: PathBuf,
+}
+
+impl GitBackend {
+    pub fn new(work_tree: impl Into<PathBuf>) -> GitBackend {
+        let work_tree = work_tree.into();
+        GitBackend {
+            folder: FolderBackend::new(work_tree.clone()),
+            work_tree,
+        }
+    }
+
+    fn git_status(&self, args: &[&str]) -> Result<(bool, String)> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.work_tree)
+            .args(args)
+            .output()?;
+        Ok((
+            output.status.success(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+
+    fn git(&self, args: &[&str]) -> Result<()> {
+        match self.git_status(args)? {
+            (true, _) => Ok(()),
+            (false, stderr) => Err(Error::Io(io::Error::new(
+                io::ErrorKind::Other,
+                format!("git {} failed: {}", args.join(" "), stderr),
+            ))),
+        }
+    }
+
+    /// false until the first push to an empty repository
+    fn has_upstream(&self) -> Result<bool> {
+        Ok(self
+            .git_status(&["rev-parse", "--verify", "--quiet", "@{u}"])?
+            .0)
+    }
+}
+
+impl SyncBackend for GitBackend {
+    fn fetch(&self) -> Result<()> {
+        self.folder.fetch()?;
+        self.git(&["fetch", "--quiet"])?;
+        if !self.has_upstream()? {
+            return Ok(());
+        }
+        if self
+            .git_status(&["rev-parse", "--verify", "--quiet", "HEAD"])?
+            .0
+        {
+            self.git(&["rebase", "--quiet", "@{u}"])
+        } else {
+            // a clone of the repository made before the first push: nothing to rebase
+            self.git(&["reset", "--quiet", "--hard", "@{u}"])
+        }
+    }
+
+    fn list_files(&self) -> Result<Vec<String>> {
+        self.folder.list_files()
+    }
+
+    fn read_file(&self, name: &str) -> Result<Vec<u8>> {
+        self.folder.read_file(name)
+    }
+
+    fn write_file(&self, name: &str, contents: &[u8]) -> Result<()> {
+        self.folder.write_file(name, contents)
+    }
+
+    fn publish(&self, message: &str) -> Result<()> {
+        self.git(&["add", "--all"])?;
+        // exits with 1 when there are staged changes
+        if !self.git_status(&["diff", "--cached", "--quiet"])?.0 {
+            self.git(&["commit", "--quiet", "--message", message])?;
+        }
+        // also pushes the commits of the previous syncs, if the push failed then
+        if self.has_upstream()? {
+            self.git(&["push", "--quiet"])
+        } else {
+            self.git(&["push", "--quiet", "--set-upstream", "origin", "HEAD"])
+        }
+    }
+}
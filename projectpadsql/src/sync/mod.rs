@@ -0,0 +1,1248 @@
+// sync of the database between several devices. The changes recorded in
+// the change log are collected to an outbox (`collect_local_changes`), then
+// written to the backend as an encrypted bundle, in a folder of the device.
+// The devices read the bundles of the others and apply the changes.
+//
+// A change is the whole entity, or a tombstone when it was deleted, with a
+// vector clock: the counter of the latest change of every device which
+// contributed to that version. A change whose clock is not after or before
+// the clock of the local version was made concurrently on another device:
+// it's stored as a conflict, for the user to pick a version (`resolve`).
+//
+// The ids of the entities are different in every database: the changes
+// refer to the entities by random global ids, see the sync_entity table.
+// The entities of the change log are synced, without their environments
+// (they're created as needed), tags, attachments, custom fields and
+// server dependencies.
+// The passwords encrypted with the master passphrase (see `secrets`) are
+// synced as they are: they're only readable on the devices which share
+// the secret settings, so better enable the master passphrase on one
+// device only, or on none.
+pub mod backend;
+
+pub use backend::{FolderBackend, GitBackend, SyncBackend};
+
+use crate::change_log::{quote_identifier, BLOB_COLUMNS, REFERENCES};
+use crate::error::{Error, Result};
+use crate::models::EntityType;
+use crate::repo::insert_row;
+use crate::secrets::{SecretKey, CHECK_TEXT, PBKDF2_ITERATIONS, SALT_LEN};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Integer, Nullable, Text, Timestamp};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::num::NonZeroU32;
+use std::str::FromStr;
+use strum_macros::{Display, EnumString};
+
+/// the counter of every device, by device id
+type Clock = BTreeMap<String, i64>;
+
+/// an entity as a json object, without its id and with the global
+/// ids of the entities it references. Blobs are hex-encoded.
+type Row = serde_json::Map<String, Value>;
+
+/// the salt of the key and a value to check the passphrase, at the root of the backend
+const KEY_FILE: &str = "projectpad-sync.json";
+
+const BUNDLE_EXTENSION: &str = ".bundle";
+
+/// the synced entity types, the referenced entities first
+const SYNCED_TYPES: &[EntityType] = &[
+    EntityType::Project,
+    EntityType::Server,
+    EntityType::ServerDatabase,
+    EntityType::ServerWebsite,
+    EntityType::ServerExtraUserAccount,
+    EntityType::ServerNote,
+    EntityType::ServerPoi,
+    EntityType::ServerLink,
+    EntityType::ProjectNote,
+    EntityType::ProjectPoi,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+pub enum BackendKind {
+    /// a folder shared by syncthing, NFS, a cloud drive...
+    #[strum(serialize = "folder")]
+    Folder,
+    /// a clone of a git repository
+    #[strum(serialize = "git")]
+    Git,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncSettings {
+    pub device_id: String,
+    pub device_name: String,
+    pub backend: BackendKind,
+    /// the shared folder, or the work tree of the git clone
+    pub location: String,
+    pub passphrase: String,
+}
+
+impl SyncSettings {
+    pub fn backend(&self) -> Box<dyn SyncBackend> {
+        match self.backend {
+            BackendKind::Folder => Box::new(FolderBackend::new(&self.location)),
+            BackendKind::Git => Box::new(GitBackend::new(&self.location)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SyncReport {
+    /// the changes of this device written to the backend
+    pub pushed: usize,
+    /// the changes read from the other devices
+    pub received: usize,
+    pub applied: usize,
+    /// the new conflicts
+    pub conflicts: usize,
+    /// the changes waiting for the entities they reference,
+    /// which other devices didn't share yet
+    pub waiting: usize,
+}
+
+/// the field of an entity which differs between the two versions of a
+/// conflict. None when the field is empty, or the version is deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictField {
+    pub field: String,
+    pub mine: Option<String>,
+    pub theirs: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub id: i32,
+    pub entity_type: EntityType,
+    /// None if the entity is deleted on this device
+    pub entity_id: Option<i32>,
+    /// the description, name or title of the entity
+    pub title: String,
+    /// the device which made the other version
+    pub device_name: String,
+    pub detected_at: NaiveDateTime,
+    pub deleted_here: bool,
+    pub deleted_there: bool,
+    pub fields: Vec<ConflictField>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    KeepMine,
+    TakeTheirs,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    salt: String,
+    iterations: u32,
+    check_value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    device_id: String,
+    device_name: String,
+    changes: Vec<BundleChange>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundleChange {
+    counter: i64,
+    entity_type: String,
+    global_id: String,
+    /// None for a delete
+    row: Option<Row>,
+    clock: Clock,
+}
+
+#[derive(QueryableByName)]
+struct SettingsRow {
+    #[sql_type = "Text"]
+    device_id: String,
+    #[sql_type = "Text"]
+    device_name: String,
+    #[sql_type = "Text"]
+    backend: String,
+    #[sql_type = "Text"]
+    location: String,
+    #[sql_type = "Text"]
+    passphrase: String,
+}
+
+#[derive(QueryableByName)]
+struct IdRow {
+    #[sql_type = "Integer"]
+    id: i32,
+}
+
+#[derive(QueryableByName)]
+struct CounterRow {
+    #[sql_type = "BigInt"]
+    counter: i64,
+}
+
+#[derive(QueryableByName)]
+struct TextRow {
+    #[sql_type = "Text"]
+    value: String,
+}
+
+#[derive(QueryableByName)]
+struct SyncedEntity {
+    #[sql_type = "Text"]
+    global_id: String,
+    #[sql_type = "Nullable<Integer>"]
+    entity_id: Option<i32>,
+    #[sql_type = "Text"]
+    clock: String,
+}
+
+#[derive(QueryableByName)]
+struct TouchedEntity {
+    #[sql_type = "Text"]
+    entity_type: EntityType,
+    #[sql_type = "Integer"]
+    entity_id: i32,
+    #[sql_type = "Bool"]
+    was_deleted: bool,
+}
+
+#[derive(QueryableByName)]
+struct ChangeRow {
+    #[sql_type = "Text"]
+    device_id: String,
+    #[sql_type = "BigInt"]
+    counter: i64,
+    #[sql_type = "Text"]
+    entity_type: String,
+    #[sql_type = "Text"]
+    global_id: String,
+    #[sql_type = "Nullable<Text>"]
+    row: Option<String>,
+    #[sql_type = "Text"]
+    clock: String,
+}
+
+#[derive(QueryableByName)]
+struct ConflictRow {
+    #[sql_type = "Integer"]
+    id: i32,
+    #[sql_type = "Text"]
+    entity_type: EntityType,
+    #[sql_type = "Text"]
+    global_id: String,
+    #[sql_type = "Text"]
+    device_name: String,
+    #[sql_type = "Nullable<Text>"]
+    row: Option<String>,
+    #[sql_type = "Text"]
+    clock: String,
+    #[sql_type = "Timestamp"]
+    detected_at: NaiveDateTime,
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("failed serializing to json")
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T> {
+    serde_json::from_str(json).map_err(|e| invalid_data(format!("invalid sync data: {}", e)))
+}
+
+/// whether every change known to `a` is known to `b`
+fn clock_le(a: &Clock, b: &Clock) -> bool {
+    a.iter()
+        .all(|(device, counter)| b.get(device).map_or(false, |c| c >= counter))
+}
+
+fn clock_merge(a: &Clock, b: &Clock) -> Clock {
+    let mut merged = a.clone();
+    for (device, counter) in b {
+        let c = merged.entry(device.clone()).or_insert(0);
+        *c = (*c).max(*counter);
+    }
+    merged
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("failed generating random bytes");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// the settings of this device, None if sync is disabled
+pub fn settings(conn: &SqliteConnection) -> Result<Option<SyncSettings>> {
+    diesel::sql_query(
+        "SELECT device_id, device_name, backend, location, passphrase FROM sync_settings",
+    )
+    .load::<SettingsRow>(conn)?
+    .into_iter()
+    .next()
+    .map(|s| {
+        Ok(SyncSettings {
+            backend: BackendKind::from_str(&s.backend)
+                .map_err(|_| Error::Corrupt(format!("unknown sync backend {}", s.backend)))?,
+            device_id: s.device_id,
+            device_name: s.device_name,
+            location: s.location,
+            passphrase: s.passphrase,
+        })
+    })
+    .transpose()
+}
+
+fn max_change_log_id(conn: &SqliteConnection) -> Result<i32> {
+    Ok(
+        diesel::sql_query("SELECT coalesce(max(id), 0) AS id FROM change_log")
+            .get_result::<IdRow>(conn)?
+            .id,
+    )
+}
+
+/// enable sync, or change the settings if it's enabled already. When
+/// enabling, all the entities are queued, to be shared with the other devices.
+pub fn enable(
+    conn: &SqliteConnection,
+    device_name: &str,
+    backend: BackendKind,
+    location: &str,
+    passphrase: &str,
+) -> Result<SyncSettings> {
+    conn.transaction(|| {
+        if settings(conn)?.is_some() {
+            diesel::sql_query(
+                "UPDATE sync_settings SET device_name = ?, backend = ?, location = ?, passphrase = ?",
+            )
+            .bind::<Text, _>(device_name)
+            .bind::<Text, _>(backend.to_string())
+            .bind::<Text, _>(location)
+            .bind::<Text, _>(passphrase)
+            .execute(conn)?;
+        } else {
+            diesel::sql_query(
+                "INSERT INTO sync_settings \
+                 (id, device_id, device_name, backend, location, passphrase, change_log_id) \
+                 VALUES (1, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind::<Text, _>(random_hex(16))
+            .bind::<Text, _>(device_name)
+            .bind::<Text, _>(backend.to_string())
+            .bind::<Text, _>(location)
+            .bind::<Text, _>(passphrase)
+            .bind::<Integer, _>(max_change_log_id(conn)?)
+            .execute(conn)?;
+            let device_id = settings(conn)?.ok_or(Error::NotFound)?.device_id;
+            for entity_type in SYNCED_TYPES {
+                let ids = diesel::sql_query(format!("SELECT id FROM {} ORDER BY id", entity_type))
+                    .load::<IdRow>(conn)?;
+                for IdRow { id } in ids {
+                    enqueue_entity(conn, &device_id, *entity_type, id, false)?;
+                }
+            }
+        }
+        settings(conn)?.ok_or(Error::NotFound)
+    })
+}
+
+/// stop syncing. The global ids of the entities are kept: if sync is
+/// enabled again, the other devices recognize the entities.
+pub fn disable(conn: &SqliteConnection) -> Result<()> {
+    conn.transaction(|| {
+        conn.execute("DELETE FROM sync_outbox; DELETE FROM sync_settings;")?;
+        Ok(())
+    })
+}
+
+/// the columns of the table of the entity, in their order
+fn columns(conn: &SqliteConnection, entity_type: EntityType) -> Result<Vec<String>> {
+    Ok(diesel::sql_query(format!(
+        "SELECT name AS value FROM pragma_table_info('{}') ORDER BY cid",
+        entity_type
+    ))
+    .load::<TextRow>(conn)?
+    .into_iter()
+    .map(|r| r.value)
+    .collect())
+}
+
+fn reference(
+    entity_type: EntityType,
+    column: &str,
+) -> Option<&'static (EntityType, &'static str, EntityType, bool)> {
+    REFERENCES
+        .iter()
+        .find(|(t, c, _, _)| *t == entity_type && *c == column)
+}
+
+fn synced_entity(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    global_id: &str,
+) -> Result<Option<SyncedEntity>> {
+    Ok(diesel::sql_query(
+        "SELECT global_id, entity_id, clock FROM sync_entity \
+          WHERE entity_type = ? AND global_id = ?",
+    )
+    .bind::<Text, _>(entity_type.to_string())
+    .bind::<Text, _>(global_id)
+    .load::<SyncedEntity>(conn)?
+    .into_iter()
+    .next())
+}
+
+fn synced_entity_by_id(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+) -> Result<Option<SyncedEntity>> {
+    Ok(diesel::sql_query(
+        "SELECT global_id, entity_id, clock FROM sync_entity \
+          WHERE entity_type = ? AND entity_id = ?",
+    )
+    .bind::<Text, _>(entity_type.to_string())
+    .bind::<Integer, _>(entity_id)
+    .load::<SyncedEntity>(conn)?
+    .into_iter()
+    .next())
+}
+
+fn save_entity(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    global_id: &str,
+    entity_id: Option<i32>,
+    clock: &Clock,
+) -> Result<()> {
+    // also drops the stale entry of an entity which had that id, if any
+    diesel::sql_query(
+        "INSERT OR REPLACE INTO sync_entity (entity_type, global_id, entity_id, clock) \
+         VALUES (?, ?, ?, ?)",
+    )
+    .bind::<Text, _>(entity_type.to_string())
+    .bind::<Text, _>(global_id)
+    .bind::<Nullable<Integer>, _>(entity_id)
+    .bind::<Text, _>(to_json(clock))
+    .execute(conn)?;
+    Ok(())
+}
+
+/// the global id of the entity, a new one if it was never synced
+fn global_id_for(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+) -> Result<String> {
+    if let Some(e) = synced_entity_by_id(conn, entity_type, entity_id)? {
+        return Ok(e.global_id);
+    }
+    let global_id = random_hex(16);
+    save_entity(
+        conn,
+        entity_type,
+        &global_id,
+        Some(entity_id),
+        &Clock::new(),
+    )?;
+    Ok(global_id)
+}
+
+/// the entity as it's shared with the other devices, None if it doesn't exist
+fn export_row(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+) -> Result<Option<Row>> {
+    let fields = columns(conn, entity_type)?
+        .iter()
+        .filter(|c| c.as_str() != "id")
+        .map(|c| {
+            let column = quote_identifier(c);
+            let value = if BLOB_COLUMNS.contains(&(entity_type, c.as_str())) {
+                format!("CASE WHEN {0} IS NULL THEN NULL ELSE hex({0}) END", column)
+            } else {
+                column
+            };
+            format!("'{}', {}", c.replace('\'', "''"), value)
+        })
+        .collect::<Vec<_>>();
+    let json = diesel::sql_query(format!(
+        "SELECT json_object({}) AS value FROM {} WHERE id = ?",
+        fields.join(", "),
+        entity_type
+    ))
+    .bind::<Integer, _>(entity_id)
+    .load::<TextRow>(conn)?
+    .into_iter()
+    .next();
+    let mut row: Row = match json {
+        Some(j) => parse_json(&j.value)?,
+        None => return Ok(None),
+    };
+    for (column, value) in row.iter_mut() {
+        if let (Some((_, _, target, _)), Some(id)) =
+            (reference(entity_type, column), value.as_i64())
+        {
+            *value = Value::String(global_id_for(conn, *target, id as i32)?);
+        }
+    }
+    Ok(Some(row))
+}
+
+fn row_exists(conn: &SqliteConnection, entity_type: EntityType, entity_id: i32) -> Result<bool> {
+    Ok(
+        diesel::sql_query(format!("SELECT id FROM {} WHERE id = ?", entity_type))
+            .bind::<Integer, _>(entity_id)
+            .load::<IdRow>(conn)?
+            .into_iter()
+            .next()
+            .is_some(),
+    )
+}
+
+fn next_counter(conn: &SqliteConnection) -> Result<i64> {
+    diesel::sql_query("UPDATE sync_settings SET counter = counter + 1").execute(conn)?;
+    Ok(diesel::sql_query("SELECT counter FROM sync_settings")
+        .get_result::<CounterRow>(conn)?
+        .counter)
+}
+
+/// queue a new version of the entity
+fn enqueue(
+    conn: &SqliteConnection,
+    device_id: &str,
+    entity_type: EntityType,
+    global_id: &str,
+    entity_id: Option<i32>,
+    row: Option<&Row>,
+) -> Result<()> {
+    let mut clock = match synced_entity(conn, entity_type, global_id)? {
+        Some(e) => parse_json(&e.clock)?,
+        None => Clock::new(),
+    };
+    let counter = next_counter(conn)?;
+    clock.insert(device_id.to_string(), counter);
+    diesel::sql_query(
+        "INSERT INTO sync_outbox (counter, entity_type, global_id, row, clock) \
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind::<BigInt, _>(counter)
+    .bind::<Text, _>(entity_type.to_string())
+    .bind::<Text, _>(global_id)
+    .bind::<Nullable<Text>, _>(row.map(to_json))
+    .bind::<Text, _>(to_json(&clock))
+    .execute(conn)?;
+    save_entity(conn, entity_type, global_id, entity_id, &clock)
+}
+
+/// queue the entity as it is in the database. `was_deleted` tells that an
+/// entity with that id was deleted: a new entity with the same id is
+/// another entity, with another global id.
+fn enqueue_entity(
+    conn: &SqliteConnection,
+    device_id: &str,
+    entity_type: EntityType,
+    entity_id: i32,
+    was_deleted: bool,
+) -> Result<()> {
+    let row = export_row(conn, entity_type, entity_id)?;
+    let known = synced_entity_by_id(conn, entity_type, entity_id)?;
+    if let Some(previous) = known.filter(|_| was_deleted || row.is_none()) {
+        enqueue(
+            conn,
+            device_id,
+            entity_type,
+            &previous.global_id,
+            None,
+            None,
+        )?;
+    }
+    if let Some(row) = row {
+        // export_row gave it a global id if it had none
+        let global_id = global_id_for(conn, entity_type, entity_id)?;
+        enqueue(
+            conn,
+            device_id,
+            entity_type,
+            &global_id,
+            Some(entity_id),
+            Some(&row),
+        )?;
+    }
+    Ok(())
+}
+
+/// queue the entities changed since the previous call, except `skip`,
+/// the changes received from the other devices
+fn collect_local_changes(
+    conn: &SqliteConnection,
+    device_id: &str,
+    skip: &HashSet<(EntityType, i32)>,
+) -> Result<()> {
+    let touched = diesel::sql_query(
+        "SELECT entity_type, entity_id, max(operation = 'delete') AS was_deleted \
+           FROM change_log \
+          WHERE id > (SELECT change_log_id FROM sync_settings) \
+          GROUP BY entity_type, entity_id \
+          ORDER BY max(id)",
+    )
+    .load::<TouchedEntity>(conn)?;
+    for entity in touched {
+        if !skip.contains(&(entity.entity_type, entity.entity_id)) {
+            enqueue_entity(
+                conn,
+                device_id,
+                entity.entity_type,
+                entity.entity_id,
+                entity.was_deleted,
+            )?;
+        }
+    }
+    diesel::sql_query("UPDATE sync_settings SET change_log_id = ?")
+        .bind::<Integer, _>(max_change_log_id(conn)?)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// the key which encrypts the bundles, derived from the passphrase. The
+/// first device to sync creates the key file.
+fn backend_key(backend: &dyn SyncBackend, passphrase: &str) -> Result<SecretKey> {
+    let existing = backend.list_files()?.iter().any(|f| f == KEY_FILE);
+    if !existing {
+        let mut salt = [0u8; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .expect("failed generating a salt");
+        let key = SecretKey::derive(
+            passphrase,
+            &salt,
+            NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+        );
+        let key_file = KeyFile {
+            salt: base64::encode(&salt),
+            iterations: PBKDF2_ITERATIONS,
+            check_value: key.encrypt(CHECK_TEXT),
+        };
+        backend.write_file(KEY_FILE, to_json(&key_file).as_bytes())?;
+        return Ok(key);
+    }
+    let key_file: KeyFile = parse_json(&String::from_utf8_lossy(&backend.read_file(KEY_FILE)?))?;
+    let salt = base64::decode(&key_file.salt)
+        .map_err(|_| invalid_data(format!("invalid salt in {}", KEY_FILE)))?;
+    let iterations = NonZeroU32::new(key_file.iterations)
+        .ok_or_else(|| invalid_data(format!("invalid iterations in {}", KEY_FILE)))?;
+    let key = SecretKey::derive(passphrase, &salt, iterations);
+    match key.decrypt(&key_file.check_value) {
+        Ok(check) if check == CHECK_TEXT => Ok(key),
+        _ => Err(Error::Io(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the sync passphrase is not the one of the other devices",
+        ))),
+    }
+}
+
+/// the first and last counters of a bundle, from its file name:
+/// <device id>/<first counter>-<last counter>.bundle
+fn bundle_range(file_name: &str) -> Option<(&str, i64, i64)> {
+    let mut parts = file_name.splitn(2, '/');
+    let device_id = parts.next()?;
+    let mut counters = parts.next()?.strip_suffix(BUNDLE_EXTENSION)?.splitn(2, '-');
+    let first = counters.next()?.parse().ok()?;
+    let last = counters.next()?.parse().ok()?;
+    Some((device_id, first, last))
+}
+
+/// write the outbox to a bundle. Returns the number of changes written.
+fn push_outbox(
+    conn: &SqliteConnection,
+    backend: &dyn SyncBackend,
+    key: &SecretKey,
+    settings: &SyncSettings,
+) -> Result<usize> {
+    let changes = diesel::sql_query(
+        "SELECT '' AS device_id, counter, entity_type, global_id, row, clock \
+           FROM sync_outbox ORDER BY counter",
+    )
+    .load::<ChangeRow>(conn)?;
+    let (first, last) = match (changes.first(), changes.last()) {
+        (Some(f), Some(l)) => (f.counter, l.counter),
+        _ => return Ok(0),
+    };
+    let bundle = Bundle {
+        device_id: settings.device_id.clone(),
+        device_name: settings.device_name.clone(),
+        changes: changes
+            .iter()
+            .map(|c| {
+                Ok(BundleChange {
+                    counter: c.counter,
+                    entity_type: c.entity_type.clone(),
+                    global_id: c.global_id.clone(),
+                    row: c.row.as_deref().map(parse_json).transpose()?,
+                    clock: parse_json(&c.clock)?,
+                })
+            })
+            .collect::<Result<_>>()?,
+    };
+    backend.write_file(
+        &format!(
+            "{}/{:010}-{:010}{}",
+            settings.device_id, first, last, BUNDLE_EXTENSION
+        ),
+        key.encrypt(&to_json(&bundle)).as_bytes(),
+    )?;
+    conn.execute("DELETE FROM sync_outbox")?;
+    Ok(changes.len())
+}
+
+/// copy the changes of the other devices which we didn't read yet to the inbox.
+/// Returns the number of changes read.
+fn fetch_bundles(
+    conn: &SqliteConnection,
+    backend: &dyn SyncBackend,
+    key: &SecretKey,
+    settings: &SyncSettings,
+) -> Result<usize> {
+    let mut received = 0;
+    for file in backend.list_files()? {
+        let (device_id, first, last) = match bundle_range(&file) {
+            Some(r) => r,
+            None => continue,
+        };
+        let known = diesel::sql_query(
+            "SELECT coalesce(max(counter), 0) AS counter FROM \
+             (SELECT counter FROM sync_peer WHERE device_id = ? \
+              UNION ALL SELECT counter FROM sync_settings WHERE device_id = ?)",
+        )
+        .bind::<Text, _>(device_id)
+        .bind::<Text, _>(device_id)
+        .get_result::<CounterRow>(conn)?
+        .counter;
+        if last <= known {
+            continue;
+        }
+        if device_id == settings.device_id {
+            // the database was restored from a backup, and we wrote
+            // bundles since then: don't reuse their counters
+            diesel::sql_query("UPDATE sync_settings SET counter = ?")
+                .bind::<BigInt, _>(last)
+                .execute(conn)?;
+            continue;
+        }
+        if first > known + 1 {
+            // the previous bundle of the device didn't reach the shared
+            // folder yet. The files are sorted: the next ones are skipped too
+            continue;
+        }
+        let encrypted = String::from_utf8_lossy(&backend.read_file(&file)?).to_string();
+        let bundle: Bundle = parse_json(
+            &key.decrypt(&encrypted)
+                .map_err(|_| invalid_data(format!("{} can't be decrypted", file)))?,
+        )?;
+        for change in bundle.changes.iter().filter(|c| c.counter > known) {
+            received += diesel::sql_query(
+                "INSERT OR IGNORE INTO sync_inbox \
+                 (device_id, counter, entity_type, global_id, row, clock) \
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind::<Text, _>(device_id)
+            .bind::<BigInt, _>(change.counter)
+            .bind::<Text, _>(&change.entity_type)
+            .bind::<Text, _>(&change.global_id)
+            .bind::<Nullable<Text>, _>(change.row.as_ref().map(to_json))
+            .bind::<Text, _>(to_json(&change.clock))
+            .execute(conn)?;
+        }
+        diesel::sql_query(
+            "INSERT OR REPLACE INTO sync_peer (device_id, device_name, counter) VALUES (?, ?, ?)",
+        )
+        .bind::<Text, _>(device_id)
+        .bind::<Text, _>(&bundle.device_name)
+        .bind::<BigInt, _>(last)
+        .execute(conn)?;
+    }
+    Ok(received)
+}
+
+enum Literals {
+    /// the columns and their values, as SQL literals
+    Ready(Vec<(String, String)>),
+    /// an entity it references was not received yet
+    Waiting,
+    /// the entity is deleted, or an entity it belongs to was deleted
+    Deleted,
+}
+
+fn sql_literal(value: &Value, is_blob: bool) -> Result<String> {
+    Ok(match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) if is_blob => {
+            if s.len() % 2 != 0 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid_data("invalid blob in sync data".to_string()));
+            }
+            format!("X'{}'", s)
+        }
+        Value::String(s) => format!("'{}'", s.replace('\'', "''")),
+        _ => return Err(invalid_data("unexpected value in sync data".to_string())),
+    })
+}
+
+fn row_literals(conn: &SqliteConnection, entity_type: EntityType, row: &Row) -> Result<Literals> {
+    let mut literals = vec![];
+    // the columns this version of the application doesn't know are ignored
+    for column in columns(conn, entity_type)? {
+        let value = match row.get(&column) {
+            Some(v) if column != "id" => v,
+            _ => continue,
+        };
+        let literal = match (reference(entity_type, &column), value) {
+            (Some((_, _, target, cascade)), Value::String(global_id)) => {
+                match synced_entity(conn, *target, global_id)? {
+                    None => return Ok(Literals::Waiting),
+                    Some(SyncedEntity {
+                        entity_id: Some(id),
+                        ..
+                    }) => id.to_string(),
+                    Some(_) if *cascade => return Ok(Literals::Deleted),
+                    Some(_) => "NULL".to_string(),
+                }
+            }
+            _ => sql_literal(
+                value,
+                BLOB_COLUMNS.contains(&(entity_type, column.as_str())),
+            )?,
+        };
+        literals.push((column, literal));
+    }
+    Ok(Literals::Ready(literals))
+}
+
+/// the servers and links refer to the environments of their project by name
+fn create_missing_environment(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    entity_id: i32,
+) -> Result<()> {
+    if entity_type != EntityType::Server && entity_type != EntityType::ServerLink {
+        return Ok(());
+    }
+    diesel::sql_query(format!(
+        "INSERT INTO environment (name, short_label, color, ordering, project_id) \
+         SELECT s.environment, upper(substr(s.environment, 1, 3)), '#888a85', \
+                (SELECT coalesce(max(ordering), -1) + 1 FROM environment \
+                  WHERE project_id = s.project_id), s.project_id \
+           FROM {} s \
+          WHERE s.id = ? AND NOT EXISTS \
+                (SELECT 1 FROM environment e \
+                  WHERE e.project_id = s.project_id AND e.name = s.environment)",
+        entity_type
+    ))
+    .bind::<Integer, _>(entity_id)
+    .execute(conn)?;
+    Ok(())
+}
+
+/// write the version of an entity which was received from another device.
+/// Returns false if it references entities which were not received yet.
+fn write_remote(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    global_id: &str,
+    row: Option<&Row>,
+    clock: &Clock,
+    written: &mut HashSet<(EntityType, i32)>,
+) -> Result<bool> {
+    let local_id = synced_entity(conn, entity_type, global_id)?.and_then(|e| e.entity_id);
+    let literals = match row {
+        Some(row) => row_literals(conn, entity_type, row)?,
+        None => Literals::Deleted,
+    };
+    let new_id = match literals {
+        Literals::Waiting => return Ok(false),
+        Literals::Deleted => {
+            if let Some(id) = local_id {
+                diesel::sql_query(format!("DELETE FROM {} WHERE id = ?", entity_type))
+                    .bind::<Integer, _>(id)
+                    .execute(conn)?;
+                written.insert((entity_type, id));
+            }
+            None
+        }
+        Literals::Ready(literals) => {
+            let exists = match local_id {
+                Some(id) => row_exists(conn, entity_type, id)?,
+                None => false,
+            };
+            let id = match local_id.filter(|_| exists) {
+                Some(id) => {
+                    diesel::sql_query(format!(
+                        "UPDATE {} SET {} WHERE id = ?",
+                        entity_type,
+                        literals
+                            .iter()
+                            .map(|(c, l)| format!("{} = {}", quote_identifier(c), l))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                    .bind::<Integer, _>(id)
+                    .execute(conn)?;
+                    id
+                }
+                None => insert_row(
+                    conn,
+                    diesel::sql_query(format!(
+                        "INSERT INTO {} ({}) VALUES ({})",
+                        entity_type,
+                        literals
+                            .iter()
+                            .map(|(c, _)| quote_identifier(c))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        literals
+                            .iter()
+                            .map(|(_, l)| l.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                )?,
+            };
+            create_missing_environment(conn, entity_type, id)?;
+            written.insert((entity_type, id));
+            Some(id)
+        }
+    };
+    save_entity(conn, entity_type, global_id, new_id, clock)?;
+    Ok(true)
+}
+
+fn save_conflict(
+    conn: &SqliteConnection,
+    change: &ChangeRow,
+    entity_type: EntityType,
+) -> Result<()> {
+    let device_name = diesel::sql_query(
+        "SELECT coalesce((SELECT device_name FROM sync_peer WHERE device_id = ?), ?) AS value",
+    )
+    .bind::<Text, _>(&change.device_id)
+    .bind::<Text, _>(&change.device_id)
+    .get_result::<TextRow>(conn)?
+    .value;
+    diesel::sql_query(
+        "INSERT OR REPLACE INTO sync_conflict \
+         (entity_type, global_id, device_name, row, clock) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind::<Text, _>(entity_type.to_string())
+    .bind::<Text, _>(&change.global_id)
+    .bind::<Text, _>(device_name)
+    .bind::<Nullable<Text>, _>(&change.row)
+    .bind::<Text, _>(&change.clock)
+    .execute(conn)?;
+    Ok(())
+}
+
+enum Applied {
+    Done,
+    Conflict,
+    Waiting,
+}
+
+fn apply_change(
+    conn: &SqliteConnection,
+    change: &ChangeRow,
+    written: &mut HashSet<(EntityType, i32)>,
+) -> Result<Applied> {
+    let entity_type = match EntityType::from_str(&change.entity_type) {
+        Ok(t) => t,
+        // from a newer version of the application
+        Err(_) => return Ok(Applied::Done),
+    };
+    let remote_clock: Clock = parse_json(&change.clock)?;
+    let remote_row: Option<Row> = change.row.as_deref().map(parse_json).transpose()?;
+    let local = synced_entity(conn, entity_type, &change.global_id)?;
+    let local_clock: Clock = match &local {
+        Some(e) => parse_json(&e.clock)?,
+        None => Clock::new(),
+    };
+    if clock_le(&remote_clock, &local_clock) {
+        // we have this version, or a later one
+        return Ok(Applied::Done);
+    }
+    if !clock_le(&local_clock, &remote_clock) {
+        let local_row = match local.as_ref().and_then(|e| e.entity_id) {
+            Some(id) => export_row(conn, entity_type, id)?,
+            None => None,
+        };
+        if local_row == remote_row {
+            let entity_id = local.and_then(|e| e.entity_id);
+            save_entity(
+                conn,
+                entity_type,
+                &change.global_id,
+                entity_id,
+                &clock_merge(&local_clock, &remote_clock),
+            )?;
+            return Ok(Applied::Done);
+        }
+        save_conflict(conn, change, entity_type)?;
+        return Ok(Applied::Conflict);
+    }
+    // a savepoint, to keep the database as it was if the write fails
+    let result = conn.transaction(|| {
+        write_remote(
+            conn,
+            entity_type,
+            &change.global_id,
+            remote_row.as_ref(),
+            &remote_clock,
+            written,
+        )
+    });
+    match result {
+        Ok(true) => {
+            // the conflicts this version supersedes
+            let conflicts = diesel::sql_query(
+                "SELECT id, entity_type, global_id, device_name, row, clock, detected_at \
+                   FROM sync_conflict WHERE entity_type = ? AND global_id = ?",
+            )
+            .bind::<Text, _>(entity_type.to_string())
+            .bind::<Text, _>(&change.global_id)
+            .load::<ConflictRow>(conn)?;
+            for conflict in conflicts {
+                if clock_le(&parse_json(&conflict.clock)?, &remote_clock) {
+                    diesel::sql_query("DELETE FROM sync_conflict WHERE id = ?")
+                        .bind::<Integer, _>(conflict.id)
+                        .execute(conn)?;
+                }
+            }
+            Ok(Applied::Done)
+        }
+        Ok(false) => Ok(Applied::Waiting),
+        // for instance the other device created a project with the name
+        // of a project which was created here
+        Err(Error::Constraint(_)) => {
+            save_conflict(conn, change, entity_type)?;
+            Ok(Applied::Conflict)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// apply the changes of the inbox, until only changes which wait
+/// for the entities they reference remain
+fn apply_inbox(conn: &SqliteConnection, report: &mut SyncReport) -> Result<()> {
+    let mut written = HashSet::new();
+    loop {
+        let pending = diesel::sql_query(
+            "SELECT device_id, counter, entity_type, global_id, row, clock \
+               FROM sync_inbox ORDER BY device_id, counter",
+        )
+        .load::<ChangeRow>(conn)?;
+        let mut progress = false;
+        report.waiting = 0;
+        for change in &pending {
+            match apply_change(conn, change, &mut written)? {
+                Applied::Waiting => {
+                    report.waiting += 1;
+                    continue;
+                }
+                Applied::Done => report.applied += 1,
+                Applied::Conflict => report.conflicts += 1,
+            }
+            progress = true;
+            diesel::sql_query("DELETE FROM sync_inbox WHERE device_id = ? AND counter = ?")
+                .bind::<Text, _>(&change.device_id)
+                .bind::<BigInt, _>(change.counter)
+                .execute(conn)?;
+        }
+        if !progress || report.waiting == 0 {
+            break;
+        }
+    }
+    // the deletes which cascaded from the changes we applied are local
+    // changes: the other devices may not have these entities yet
+    let device_id = settings(conn)?.ok_or(Error::NotFound)?.device_id;
+    collect_local_changes(conn, &device_id, &written)
+}
+
+/// share the local changes, and apply the changes of the other devices
+pub fn sync(conn: &SqliteConnection, backend: &dyn SyncBackend) -> Result<SyncReport> {
+    let settings = settings(conn)?.ok_or(Error::NotFound)?;
+    backend.fetch()?;
+    let key = backend_key(backend, &settings.passphrase)?;
+    let mut report = SyncReport::default();
+    conn.transaction::<_, Error, _>(|| {
+        collect_local_changes(conn, &settings.device_id, &HashSet::new())?;
+        report.received = fetch_bundles(conn, backend, &key, &settings)?;
+        apply_inbox(conn, &mut report)?;
+        report.pushed = push_outbox(conn, backend, &key, &settings)?;
+        Ok(())
+    })?;
+    backend.publish(&format!(
+        "projectpad: {} changes from {}",
+        report.pushed, settings.device_name
+    ))?;
+    Ok(report)
+}
+
+fn display_value(
+    conn: &SqliteConnection,
+    entity_type: EntityType,
+    field: &str,
+    value: Option<&Value>,
+) -> Result<Option<String>> {
+    Ok(match value {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => match reference(entity_type, field) {
+            // show the title of the referenced entity
+            Some((_, _, target, _)) => Some(
+                match synced_entity(conn, *target, s)?.and_then(|e| e.entity_id) {
+                    Some(id) => export_row(conn, *target, id)?
+                        .map(|r| title(&r))
+                        .unwrap_or_default(),
+                    None => s.clone(),
+                },
+            ),
+            None => Some(s.clone()),
+        },
+        Some(v) => Some(v.to_string()),
+    })
+    .map(|v| v.filter(|s| !s.is_empty()))
+}
+
+fn title(row: &Row) -> String {
+    ["desc", "name", "title", "username"]
+        .iter()
+        .find_map(|f| row.get(*f).and_then(Value::as_str))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// the conflicts to resolve, the oldest first
+pub fn conflicts(conn: &SqliteConnection) -> Result<Vec<Conflict>> {
+    diesel::sql_query(
+        "SELECT id, entity_type, global_id, device_name, row, clock, detected_at \
+           FROM sync_conflict ORDER BY id",
+    )
+    .load::<ConflictRow>(conn)?
+    .into_iter()
+    .map(|c| {
+        let entity_id = synced_entity(conn, c.entity_type, &c.global_id)?.and_then(|e| e.entity_id);
+        let mine = match entity_id {
+            Some(id) => export_row(conn, c.entity_type, id)?,
+            None => None,
+        };
+        let theirs: Option<Row> = c.row.as_deref().map(parse_json).transpose()?;
+        let mut fields = vec![];
+        for column in columns(conn, c.entity_type)? {
+            let mine_value = mine.as_ref().and_then(|r| r.get(&column));
+            let theirs_value = theirs.as_ref().and_then(|r| r.get(&column));
+            if column == "id" || mine_value == theirs_value {
+                continue;
+            }
+            fields.push(ConflictField {
+                mine: display_value(conn, c.entity_type, &column, mine_value)?,
+                theirs: display_value(conn, c.entity_type, &column, theirs_value)?,
+                field: column,
+            });
+        }
+        Ok(Conflict {
+            id: c.id,
+            entity_type: c.entity_type,
+            entity_id: entity_id.filter(|_| mine.is_some()),
+            title: mine
+                .as_ref()
+                .or_else(|| theirs.as_ref())
+                .map(title)
+                .unwrap_or_default(),
+            device_name: c.device_name,
+            detected_at: c.detected_at,
+            deleted_here: mine.is_none(),
+            deleted_there: theirs.is_none(),
+            fields,
+        })
+    })
+    .collect()
+}
+
+/// resolve a conflict. The version which is kept is queued as a new
+/// change, which supersedes both versions on all the devices.
+pub fn resolve(conn: &SqliteConnection, conflict_id: i32, resolution: Resolution) -> Result<()> {
+    let settings = settings(conn)?.ok_or(Error::NotFound)?;
+    conn.transaction(|| {
+        let conflict = diesel::sql_query(
+            "SELECT id, entity_type, global_id, device_name, row, clock, detected_at \
+               FROM sync_conflict WHERE id = ?",
+        )
+        .bind::<Integer, _>(conflict_id)
+        .load::<ConflictRow>(conn)?
+        .into_iter()
+        .next()
+        .ok_or(Error::NotFound)?;
+        // the local changes made since the conflict, for the clock to be up to date
+        collect_local_changes(conn, &settings.device_id, &HashSet::new())?;
+        let local = synced_entity(conn, conflict.entity_type, &conflict.global_id)?;
+        let local_clock: Clock = match &local {
+            Some(e) => parse_json(&e.clock)?,
+            None => Clock::new(),
+        };
+        let merged = clock_merge(&local_clock, &parse_json(&conflict.clock)?);
+        let mut written = HashSet::new();
+        let mut entity_id = local.and_then(|e| e.entity_id);
+        if resolution == Resolution::TakeTheirs {
+            let theirs: Option<Row> = conflict.row.as_deref().map(parse_json).transpose()?;
+            if !write_remote(
+                conn,
+                conflict.entity_type,
+                &conflict.global_id,
+                theirs.as_ref(),
+                &merged,
+                &mut written,
+            )? {
+                return Err(Error::Constraint(
+                    "their version references entities which were not received yet".to_string(),
+                ));
+            }
+            entity_id = synced_entity(conn, conflict.entity_type, &conflict.global_id)?
+                .and_then(|e| e.entity_id);
+        } else {
+            save_entity(
+                conn,
+                conflict.entity_type,
+                &conflict.global_id,
+                entity_id,
+                &merged,
+            )?;
+        }
+        let row = match entity_id {
+            Some(id) => export_row(conn, conflict.entity_type, id)?,
+            None => None,
+        };
+        enqueue(
+            conn,
+            &settings.device_id,
+            conflict.entity_type,
+            &conflict.global_id,
+            entity_id.filter(|_| row.is_some()),
+            row.as_ref(),
+        )?;
+        diesel::sql_query("DELETE FROM sync_conflict WHERE id = ?")
+            .bind::<Integer, _>(conflict_id)
+            .execute(conn)?;
+        collect_local_changes(conn, &settings.device_id, &written)
+    })
+}
@@ -0,0 +1,212 @@
+// TOTP codes (RFC 6238), for the two-factor authentication of the websites,
+// servers and extra user accounts. The secrets are stored as otpauth:// URIs,
+// the format of the QR codes that the authenticator apps scan:
+// otpauth://totp/Issuer:account?secret=BASE32&issuer=Issuer&digits=6&period=30
+// A bare base32 secret is also accepted, with the default parameters.
+use crate::error::Error;
+use ring::hmac;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const URI_PREFIX: &str = "otpauth://";
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn hmac_algorithm(self) -> hmac::Algorithm {
+        match self {
+            // what the authenticator apps use, and the RFC default
+            Algorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            Algorithm::Sha256 => hmac::HMAC_SHA256,
+            Algorithm::Sha512 => hmac::HMAC_SHA512,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Totp {
+    pub secret: Vec<u8>,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    /// in seconds
+    pub period: u64,
+    pub issuer: Option<String>,
+    pub account: Option<String>,
+}
+
+impl Totp {
+    /// the code for a time, in seconds since the unix epoch
+    pub fn code_at(&self, unix_time: u64) -> String {
+        let counter = unix_time / self.period;
+        let key = hmac::Key::new(self.algorithm.hmac_algorithm(), &self.secret);
+        let tag = hmac::sign(&key, &counter.to_be_bytes());
+        let hash = tag.as_ref();
+        // dynamic truncation, RFC 4226 section 5.3
+        let offset = (hash[hash.len() - 1] & 0xf) as usize;
+        let binary = (u32::from(hash[offset]) & 0x7f) << 24
+            | u32::from(hash[offset + 1]) << 16
+            | u32::from(hash[offset + 2]) << 8
+            | u32::from(hash[offset + 3]);
+        format!(
+            "{:0width$}",
+            binary % 10u32.pow(self.digits),
+            width = self.digits as usize
+        )
+    }
+
+    /// the current code, and for how many seconds it stays valid
+    pub fn current_code(&self) -> (String, u64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        (self.code_at(now), self.period - now % self.period)
+    }
+}
+
+impl FromStr for Totp {
+    type Err = Error;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        parse(value.trim()).map_err(Error::Invalid)
+    }
+}
+
+fn parse(value: &str) -> std::result::Result<Totp, String> {
+    let has_uri_prefix = value
+        .get(..URI_PREFIX.len())
+        .map_or(false, |p| p.eq_ignore_ascii_case(URI_PREFIX));
+    if has_uri_prefix {
+        parse_uri(&value[URI_PREFIX.len()..])
+    } else {
+        Ok(Totp {
+            secret: decode_base32(value)?,
+            algorithm: Algorithm::Sha1,
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            issuer: None,
+            account: None,
+        })
+    }
+}
+
+/// `totp/Issuer:account?secret=...`
+fn parse_uri(uri: &str) -> std::result::Result<Totp, String> {
+    let (otp_type, rest) = uri.split_at(uri.find('/').ok_or("Missing the OTP type in the URI")?);
+    if otp_type.eq_ignore_ascii_case("hotp") {
+        return Err("Only the time-based (TOTP) codes are supported, not HOTP".to_string());
+    }
+    if !otp_type.eq_ignore_ascii_case("totp") {
+        return Err(format!("Unknown OTP type: {}", otp_type));
+    }
+    let (label, query) = match rest[1..].find('?') {
+        Some(i) => (&rest[1..i + 1], &rest[i + 2..]),
+        None => (&rest[1..], ""),
+    };
+    let label = percent_decode(label)?;
+    let (mut issuer, account) = match label.find(':') {
+        Some(i) => (
+            Some(label[..i].trim().to_string()),
+            Some(label[i + 1..].trim().to_string()),
+        ),
+        None => (None, Some(label.trim().to_string())),
+    };
+    let mut totp = Totp {
+        secret: vec![],
+        algorithm: Algorithm::Sha1,
+        digits: DEFAULT_DIGITS,
+        period: DEFAULT_PERIOD,
+        issuer: None,
+        account: account.filter(|a| !a.is_empty()),
+    };
+    for param in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = match param.find('=') {
+            Some(i) => (&param[..i], percent_decode(&param[i + 1..])?),
+            None => (param, "".to_string()),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "secret" => totp.secret = decode_base32(&value)?,
+            // the issuer parameter is preferred to the prefix of the label
+            "issuer" => issuer = Some(value),
+            "algorithm" => {
+                totp.algorithm = match value.to_ascii_uppercase().as_str() {
+                    "SHA1" => Algorithm::Sha1,
+                    "SHA256" => Algorithm::Sha256,
+                    "SHA512" => Algorithm::Sha512,
+                    _ => return Err(format!("Unknown algorithm: {}", value)),
+                }
+            }
+            "digits" => {
+                totp.digits = value
+                    .parse()
+                    .ok()
+                    .filter(|d| (6..=8).contains(d))
+                    .ok_or_else(|| format!("Invalid number of digits: {}", value))?
+            }
+            "period" => {
+                totp.period = value
+                    .parse()
+                    .ok()
+                    .filter(|p| *p > 0)
+                    .ok_or_else(|| format!("Invalid period: {}", value))?
+            }
+            // for instance image, for the icon of the issuer
+            _ => {}
+        }
+    }
+    if totp.secret.is_empty() {
+        return Err("Missing the secret in the URI".to_string());
+    }
+    totp.issuer = issuer.filter(|i| !i.is_empty());
+    Ok(totp)
+}
+
+/// RFC 4648, ignoring the case, the spaces and the padding
+fn decode_base32(value: &str) -> std::result::Result<Vec<u8>, String> {
+    let mut result = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in value.chars().filter(|c| *c != ' ' && *c != '=') {
+        let digit = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return Err(format!("Invalid character in the secret: {}", c)),
+        };
+        buffer = (buffer << 5) | digit;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if result.is_empty() {
+        return Err("The secret is empty".to_string());
+    }
+    Ok(result)
+}
+
+fn percent_decode(value: &str) -> std::result::Result<String, String> {
+    let invalid = || format!("Invalid escape in the URI: {}", value);
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3).ok_or_else(invalid)?;
+            result.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(result).map_err(|_| invalid())
+}
@@ -0,0 +1,96 @@
+// soft delete: entities moved to the trash get a deleted_at timestamp
+// and are hidden from the listings, until they're restored or purged.
+// Triggers move the children of an entity with it (see migration 25).
+use crate::error::Result;
+use crate::models::EntityType;
+use crate::repo::check_modified;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text, Timestamp};
+use strum::IntoEnumIterator;
+
+/// same format as the timestamps of the change log
+const NOW: &str = "strftime('%Y-%m-%d %H:%M:%f', 'now')";
+
+/// a top-level entity in the trash: its parents are not in the trash.
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct TrashItem {
+    #[sql_type = "Text"]
+    pub entity_type: EntityType,
+    #[sql_type = "Integer"]
+    pub entity_id: i32,
+    #[sql_type = "Text"]
+    pub title: String,
+    #[sql_type = "Text"]
+    pub project_name: String,
+    #[sql_type = "Timestamp"]
+    pub deleted_at: NaiveDateTime,
+}
+
+/// the entities in the trash, most recently deleted first
+pub fn list(conn: &SqliteConnection) -> Result<Vec<TrashItem>> {
+    Ok(diesel::sql_query(
+        "SELECT trash.entity_type, trash.entity_id, trash.title, \
+                project.name AS project_name, trash.deleted_at \
+           FROM trash JOIN project ON project.id = trash.project_id \
+          ORDER BY trash.deleted_at DESC, trash.title",
+    )
+    .load(conn)?)
+}
+
+/// move an entity and its children to the trash
+pub fn move_to_trash(conn: &SqliteConnection, entity_type: EntityType, id: i32) -> Result<()> {
+    check_modified(
+        diesel::sql_query(format!(
+            "UPDATE {} SET deleted_at = {} WHERE id = ? AND deleted_at IS NULL",
+            entity_type, NOW
+        ))
+        .bind::<Integer, _>(id)
+        .execute(conn)?,
+    )
+}
+
+/// restore an entity from the trash, with the children that were moved
+/// to the trash with it. Only top-level entities in the trash can be restored.
+pub fn restore(conn: &SqliteConnection, entity_type: EntityType, id: i32) -> Result<()> {
+    check_modified(
+        diesel::sql_query(format!(
+            "UPDATE {} SET deleted_at = NULL WHERE id = ? AND id IN \
+                 (SELECT entity_id FROM trash WHERE entity_type = ?)",
+            entity_type
+        ))
+        .bind::<Integer, _>(id)
+        .bind::<Text, _>(entity_type.to_string())
+        .execute(conn)?,
+    )
+}
+
+/// delete for good an entity which is in the trash, and its children
+pub fn purge(conn: &SqliteConnection, entity_type: EntityType, id: i32) -> Result<()> {
+    check_modified(
+        diesel::sql_query(format!(
+            "DELETE FROM {} WHERE id = ? AND deleted_at IS NOT NULL",
+            entity_type
+        ))
+        .bind::<Integer, _>(id)
+        .execute(conn)?,
+    )
+}
+
+/// delete for good the entities which were moved to the trash more than
+/// `days` days ago. Returns the number of entities deleted, not counting
+/// the children that were deleted through their parent.
+pub fn purge_older_than(conn: &SqliteConnection, days: u32) -> Result<usize> {
+    conn.transaction(|| {
+        let mut count = 0;
+        for entity_type in EntityType::iter() {
+            count += diesel::sql_query(format!(
+                "DELETE FROM {} WHERE deleted_at < strftime('%Y-%m-%d %H:%M:%f', 'now', ?)",
+                entity_type
+            ))
+            .bind::<Text, _>(format!("-{} days", days))
+            .execute(conn)?;
+        }
+        Ok(count)
+    })
+}
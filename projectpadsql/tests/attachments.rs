@@ -0,0 +1,134 @@
+mod common;
+
+use common::test_db;
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::repo::{AttachmentRepo, ProjectRepo};
+use projectpadsql::Error;
+
+fn insert_project(conn: &SqliteConnection, name: &str) -> Project {
+    ProjectRepo::new(conn)
+        .insert(&Project {
+            id: 0,
+            name: name.to_string(),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn guess_mime() {
+    assert_eq!("application/x-pem-file", Attachment::guess_mime("ca.CRT"));
+    assert_eq!(
+        "application/x-openvpn-profile",
+        Attachment::guess_mime("office.ovpn")
+    );
+    assert_eq!(
+        "application/octet-stream",
+        Attachment::guess_mime("kubeconfig")
+    );
+    assert_eq!("application/octet-stream", Attachment::guess_mime("a.bin"));
+}
+
+#[test]
+fn add_list_and_delete() {
+    let conn = test_db();
+    let project = insert_project(&conn, "prj");
+    let repo = AttachmentRepo::new(&conn);
+    let cert = repo
+        .add(EntityType::Project, project.id, "ca.pem", b"-----BEGIN")
+        .unwrap();
+    assert_eq!("application/x-pem-file", cert.mime);
+    assert_eq!(10, cert.size);
+    repo.add(EntityType::Project, project.id, "a.txt", b"license")
+        .unwrap();
+    assert_eq!(
+        vec!["a.txt", "ca.pem"],
+        repo.list_for_item(EntityType::Project, project.id)
+            .unwrap()
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(b"-----BEGIN".to_vec(), repo.contents(cert.id).unwrap());
+    assert_eq!(
+        vec![project.id],
+        repo.item_ids_with_attachments(EntityType::Project)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>()
+    );
+    assert!(repo
+        .item_ids_with_attachments(EntityType::Server)
+        .unwrap()
+        .is_empty());
+
+    let renamed = repo.rename(cert.id, "ca.ovpn").unwrap();
+    assert_eq!("application/x-openvpn-profile", renamed.mime);
+
+    repo.delete(cert.id).unwrap();
+    assert!(matches!(repo.delete(cert.id), Err(Error::NotFound)));
+    assert_eq!(
+        1,
+        repo.list_for_item(EntityType::Project, project.id)
+            .unwrap()
+            .len()
+    );
+}
+
+#[test]
+fn constraint_violations() {
+    let conn = test_db();
+    let project = insert_project(&conn, "prj");
+    let repo = AttachmentRepo::new(&conn);
+    assert!(matches!(
+        repo.add(EntityType::ServerPoi, 1, "a.txt", b""),
+        Err(Error::Constraint(_))
+    ));
+    assert!(matches!(
+        repo.add(EntityType::Project, project.id, "", b""),
+        Err(Error::Constraint(_))
+    ));
+}
+
+#[test]
+fn deleting_an_item_deletes_its_attachments() {
+    let conn = test_db();
+    let project = insert_project(&conn, "prj");
+    let other = insert_project(&conn, "other");
+    let repo = AttachmentRepo::new(&conn);
+    repo.add(EntityType::Project, project.id, "a.txt", b"a")
+        .unwrap();
+    let kept = repo
+        .add(EntityType::Project, other.id, "b.txt", b"b")
+        .unwrap();
+    ProjectRepo::new(&conn).delete(project.id).unwrap();
+    assert!(repo
+        .list_for_item(EntityType::Project, project.id)
+        .unwrap()
+        .is_empty());
+    assert_eq!(kept, repo.get(kept.id).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn write_to_file_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+    let conn = test_db();
+    let project = insert_project(&conn, "prj");
+    let repo = AttachmentRepo::new(&conn);
+    let key = repo
+        .add(EntityType::Project, project.id, "id_rsa", b"private")
+        .unwrap();
+    let path = std::env::temp_dir().join(format!("projectpad-attachment-{}", std::process::id()));
+    std::fs::write(&path, b"previous, longer contents").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    repo.write_to_file(key.id, &path).unwrap();
+    assert_eq!(b"private".to_vec(), std::fs::read(&path).unwrap());
+    assert_eq!(
+        0o600,
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o777
+    );
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,165 @@
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use projectpadsql::backup::{self, DailyBackup};
+use projectpadsql::models::Project;
+use projectpadsql::repo::ProjectRepo;
+use projectpadsql::{migrations, Error};
+use std::path::Path;
+
+fn open_db(path: &Path, pass: &str) -> SqliteConnection {
+    let conn = SqliteConnection::establish(&path.to_string_lossy()).unwrap();
+    projectpadsql::try_unlock_db(&conn, pass).unwrap();
+    migrations::migrate_db_if_needed(&conn, None).unwrap();
+    conn
+}
+
+fn insert_project(conn: &SqliteConnection, name: &str) {
+    ProjectRepo::new(conn)
+        .insert(&Project {
+            id: 0,
+            name: name.to_string(),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap();
+}
+
+fn project_names(conn: &SqliteConnection) -> Vec<String> {
+    use projectpadsql::schema::project::dsl as prj;
+    prj::project
+        .select(prj::name)
+        .order(prj::id)
+        .load(conn)
+        .unwrap()
+}
+
+#[test]
+fn backup_and_restore() {
+    let folder = tempfile::tempdir().unwrap();
+    let db_path = folder.path().join("projectpad.db");
+    let backup_path = folder.path().join("backup.db");
+    let conn = open_db(&db_path, "test-pass");
+    insert_project(&conn, "first");
+
+    projectpadsql::backup(&conn, &backup_path).unwrap();
+    // the backup doesn't overwrite files
+    assert!(projectpadsql::backup(&conn, &backup_path).is_err());
+    insert_project(&conn, "second");
+    assert_eq!(
+        migrations::SCHEMA_VERSION,
+        backup::check_backup(&backup_path, "test-pass").unwrap()
+    );
+    drop(conn);
+
+    backup::restore(&backup_path, "test-pass", &db_path).unwrap();
+    let conn = open_db(&db_path, "test-pass");
+    assert_eq!(vec!["first"], project_names(&conn));
+    // the database which was replaced is kept
+    let previous = open_db(&backup::before_restore_path(&db_path), "test-pass");
+    assert_eq!(vec!["first", "second"], project_names(&previous));
+}
+
+#[test]
+fn backup_without_vacuum_into() {
+    assert!(!backup::supports_vacuum_into("3.26.0"));
+    assert!(backup::supports_vacuum_into("3.27.2"));
+    assert!(backup::supports_vacuum_into("3.31.1"));
+    let folder = tempfile::tempdir().unwrap();
+    let db_path = folder.path().join("projectpad.db");
+    let backup_path = folder.path().join("backup.db");
+    let conn = open_db(&db_path, "test-pass");
+    insert_project(&conn, "first");
+
+    backup::backup_by_copy(&conn, &backup_path).unwrap();
+    assert!(backup::backup_by_copy(&conn, &backup_path).is_err());
+    insert_project(&conn, "second");
+    assert_eq!(
+        migrations::SCHEMA_VERSION,
+        backup::check_backup(&backup_path, "test-pass").unwrap()
+    );
+    assert_eq!(
+        vec!["first"],
+        project_names(&open_db(&backup_path, "test-pass"))
+    );
+    // there is no file to copy
+    let in_memory = SqliteConnection::establish(":memory:").unwrap();
+    assert!(matches!(
+        backup::backup_by_copy(&in_memory, &folder.path().join("in-memory.db")),
+        Err(Error::Invalid(_))
+    ));
+}
+
+#[test]
+fn restore_checks_the_backup() {
+    let folder = tempfile::tempdir().unwrap();
+    let db_path = folder.path().join("projectpad.db");
+    let conn = open_db(&db_path, "test-pass");
+    insert_project(&conn, "first");
+    drop(conn);
+
+    let missing = folder.path().join("missing.db");
+    assert!(matches!(
+        backup::restore(&missing, "test-pass", &db_path),
+        Err(Error::Io(_))
+    ));
+    assert!(!missing.exists());
+
+    let garbage = folder.path().join("garbage.db");
+    std::fs::write(&garbage, vec![b'x'; 4096]).unwrap();
+    assert!(backup::restore(&garbage, "test-pass", &db_path).is_err());
+
+    // the database was left alone
+    let conn = open_db(&db_path, "test-pass");
+    assert_eq!(vec!["first"], project_names(&conn));
+    assert!(!backup::before_restore_path(&db_path).exists());
+}
+
+#[test]
+fn daily_backups_rotate() {
+    let folder = tempfile::tempdir().unwrap();
+    let backups = folder.path().join("backups");
+    let conn = open_db(&folder.path().join("projectpad.db"), "test-pass");
+    let day = |d| NaiveDate::from_ymd(2021, 3, d);
+
+    std::fs::create_dir(&backups).unwrap();
+    for other in &["projectpad-2021-03-01-work.db", "notes.txt"] {
+        std::fs::write(backups.join(other), b"").unwrap();
+    }
+
+    for d in 1..=4 {
+        assert!(backup::daily_backup(&conn, &backups, "default", day(d), 3)
+            .unwrap()
+            .is_some());
+    }
+    // once a day
+    assert_eq!(
+        None,
+        backup::daily_backup(&conn, &backups, "default", day(4), 3).unwrap()
+    );
+    assert_eq!(
+        vec![
+            DailyBackup {
+                date: day(4),
+                path: backups.join("projectpad-2021-03-04.db")
+            },
+            DailyBackup {
+                date: day(3),
+                path: backups.join("projectpad-2021-03-03.db")
+            },
+            DailyBackup {
+                date: day(2),
+                path: backups.join("projectpad-2021-03-02.db")
+            },
+        ],
+        backup::list_daily_backups(&backups, "default")
+    );
+    // the backups of the other profiles are left alone
+    assert_eq!(
+        vec![day(1)],
+        backup::list_daily_backups(&backups, "work")
+            .into_iter()
+            .map(|b| b.date)
+            .collect::<Vec<_>>()
+    );
+    assert!(backups.join("notes.txt").exists());
+}
@@ -0,0 +1,234 @@
+mod common;
+
+use common::test_db;
+use diesel::prelude::*;
+use projectpadsql::change_log;
+use projectpadsql::models::*;
+use projectpadsql::repo::{ProjectRepo, ServerPoiRepo, ServerRepo};
+use projectpadsql::Error;
+
+fn project(name: &str) -> Project {
+    Project {
+        id: 0,
+        name: name.to_string(),
+        icon: Some(b"icon".to_vec()),
+        deleted_at: None,
+    }
+}
+
+fn server(desc: &str, project_id: i32) -> Server {
+    Server {
+        auth_key: Some(vec![0, 1, 2, 255]),
+        auth_key_filename: Some("id_rsa".to_string()),
+        ..common::server(project_id, desc)
+    }
+}
+
+fn server_poi(desc: &str, server_id: i32) -> ServerPointOfInterest {
+    ServerPointOfInterest {
+        id: 0,
+        desc: desc.to_string(),
+        path: "/var/log".to_string(),
+        text: "app.log".to_string(),
+        interest_type: InterestType::PoiLogFile,
+        run_on: RunOn::RunOnServer,
+        group_name: None,
+        server_id,
+        deleted_at: None,
+    }
+}
+
+fn summary(changes: &[change_log::Change]) -> Vec<(ChangeOperation, EntityType, &str)> {
+    changes
+        .iter()
+        .map(|c| (c.operation, c.entity_type, c.entity_desc.as_str()))
+        .collect()
+}
+
+#[test]
+fn changes_are_logged() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let srv = ServerRepo::new(&conn)
+        .insert(&server("web", prj.id))
+        .unwrap();
+    // updates which change nothing are not logged
+    ServerRepo::new(&conn).update(&srv).unwrap();
+    ServerRepo::new(&conn)
+        .update(&Server {
+            desc: "www".to_string(),
+            password: "changed".to_string(),
+            ..srv.clone()
+        })
+        .unwrap();
+    ServerRepo::new(&conn).delete(srv.id).unwrap();
+    let changes = change_log::list_changes(&conn, 10).unwrap();
+    assert_eq!(
+        vec![
+            (ChangeOperation::Delete, EntityType::Server, "www"),
+            (ChangeOperation::Update, EntityType::Server, "web"),
+            (ChangeOperation::Insert, EntityType::Server, "web"),
+            (ChangeOperation::Insert, EntityType::Project, "alpha"),
+        ],
+        summary(&changes)
+    );
+    assert_eq!(
+        vec![
+            change_log::FieldChange {
+                field: "desc".to_string(),
+                old_value: Some("web".to_string()),
+                new_value: Some("www".to_string()),
+            },
+            change_log::FieldChange {
+                field: "password".to_string(),
+                old_value: Some("secret".to_string()),
+                new_value: Some("changed".to_string()),
+            }
+        ],
+        change_log::field_changes(&conn, changes[1].id).unwrap()
+    );
+    assert_eq!(2, change_log::list_changes(&conn, 2).unwrap().len());
+}
+
+#[test]
+fn restore_deleted_server_with_children() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let srv = ServerRepo::new(&conn)
+        .insert(&server("web", prj.id))
+        .unwrap();
+    let tunneled = ServerRepo::new(&conn)
+        .insert(&Server {
+            ssh_tunnel_through_server_id: Some(srv.id),
+            ..server("tunneled", prj.id)
+        })
+        .unwrap();
+    let poi_repo = ServerPoiRepo::new(&conn);
+    let poi = poi_repo.insert(&server_poi("logs", srv.id)).unwrap();
+    let other_poi = poi_repo.insert(&server_poi("other", tunneled.id)).unwrap();
+
+    conn.execute(&format!(
+        "UPDATE server SET ssh_tunnel_through_server_id = NULL WHERE id = {}",
+        tunneled.id
+    ))
+    .unwrap();
+    ServerRepo::new(&conn).delete(srv.id).unwrap();
+    assert!(poi_repo.list_for_server(srv.id).unwrap().is_empty());
+
+    let last_delete = change_log::last_delete(&conn).unwrap().unwrap();
+    assert_eq!(EntityType::Server, last_delete.entity_type);
+    assert_eq!(srv.id, last_delete.entity_id);
+    let restored_id = change_log::restore_deleted(&conn, last_delete.id).unwrap();
+    assert_eq!(srv.id, restored_id);
+    assert_eq!(srv, ServerRepo::new(&conn).get(srv.id).unwrap());
+    assert_eq!(vec![poi], poi_repo.list_for_server(srv.id).unwrap());
+    assert_eq!(
+        vec![other_poi],
+        poi_repo.list_for_server(tunneled.id).unwrap()
+    );
+
+    // can't undo twice
+    assert!(matches!(
+        change_log::restore_deleted(&conn, last_delete.id),
+        Err(Error::CannotUndo(_))
+    ));
+    assert_eq!(None, change_log::last_delete(&conn).unwrap());
+}
+
+#[test]
+fn restore_deleted_project_with_reused_ids() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let srv = ServerRepo::new(&conn)
+        .insert(&server("web", prj.id))
+        .unwrap();
+    let db = ServerRepo::new(&conn)
+        .insert(&Server {
+            ssh_tunnel_through_server_id: Some(srv.id),
+            ..server("db", prj.id)
+        })
+        .unwrap();
+    ServerPoiRepo::new(&conn)
+        .insert(&server_poi("logs", db.id))
+        .unwrap();
+    ProjectRepo::new(&conn).delete(prj.id).unwrap();
+    // the ids of the deleted rows are reused
+    let beta = ProjectRepo::new(&conn).insert(&project("beta")).unwrap();
+    let beta_srv = ServerRepo::new(&conn)
+        .insert(&server("beta web", beta.id))
+        .unwrap();
+    assert_eq!(prj.id, beta.id);
+    assert_eq!(srv.id, beta_srv.id);
+
+    let last_delete = change_log::last_delete(&conn).unwrap().unwrap();
+    let restored_id = change_log::restore_deleted(&conn, last_delete.id).unwrap();
+    assert_ne!(prj.id, restored_id);
+    let restored = ProjectRepo::new(&conn).get(restored_id).unwrap();
+    assert_eq!(
+        Project {
+            id: restored_id,
+            ..prj
+        },
+        restored
+    );
+    let servers = ServerRepo::new(&conn)
+        .list_for_project(restored_id)
+        .unwrap();
+    assert_eq!(
+        vec!["db", "web"],
+        servers.iter().map(|s| s.desc.as_str()).collect::<Vec<_>>()
+    );
+    // the references between restored rows follow the new ids
+    assert_eq!(Some(servers[1].id), servers[0].ssh_tunnel_through_server_id);
+    assert_eq!(
+        vec!["logs"],
+        ServerPoiRepo::new(&conn)
+            .list_for_server(servers[0].id)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.desc)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        1,
+        ServerRepo::new(&conn)
+            .list_for_project(beta.id)
+            .unwrap()
+            .len()
+    );
+}
+
+#[test]
+fn revert_field() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    ProjectRepo::new(&conn)
+        .update(&Project {
+            name: "beta".to_string(),
+            icon: Some(b"new icon".to_vec()),
+            ..prj.clone()
+        })
+        .unwrap();
+    let edit = change_log::list_changes(&conn, 1).unwrap().remove(0);
+    change_log::revert_field(&conn, edit.id, "icon").unwrap();
+    let reverted = ProjectRepo::new(&conn).get(prj.id).unwrap();
+    assert_eq!(prj.icon, reverted.icon);
+    assert_eq!("beta", reverted.name);
+    change_log::revert_field(&conn, edit.id, "name").unwrap();
+    assert_eq!(prj, ProjectRepo::new(&conn).get(prj.id).unwrap());
+
+    assert!(matches!(
+        change_log::revert_field(&conn, edit.id, "name\"; DROP TABLE project; --"),
+        Err(Error::CannotUndo(_))
+    ));
+    let insert = change_log::list_changes(&conn, 10).unwrap().pop().unwrap();
+    assert!(matches!(
+        change_log::revert_field(&conn, insert.id, "name"),
+        Err(Error::CannotUndo(_))
+    ));
+    ProjectRepo::new(&conn).delete(prj.id).unwrap();
+    assert!(matches!(
+        change_log::revert_field(&conn, edit.id, "name"),
+        Err(Error::NotFound)
+    ));
+}
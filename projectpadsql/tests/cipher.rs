@@ -0,0 +1,76 @@
+use projectpadsql::cipher::{self, CipherFormat};
+use std::fs;
+use std::path::Path;
+
+fn write_sidecar(db_path: &Path, contents: &str) {
+    fs::write(cipher::sidecar_path(db_path), contents).unwrap();
+}
+
+#[test]
+fn format_follows_the_sidecar() {
+    let folder = tempfile::tempdir().unwrap();
+    let db_path = folder.path().join("projectpad.db");
+    fs::write(&db_path, b"db").unwrap();
+    assert_eq!(
+        CipherFormat::Sqlcipher3Compat,
+        cipher::format_of(&db_path).unwrap()
+    );
+
+    write_sidecar(&db_path, "# comment\nformat=4\n");
+    assert_eq!(
+        CipherFormat::Sqlcipher4,
+        cipher::format_of(&db_path).unwrap()
+    );
+
+    // the switch to the sqlcipher 4 file didn't happen yet
+    fs::write(folder.path().join("projectpad.db.sqlcipher4.tmp"), b"db4").unwrap();
+    assert_eq!(
+        CipherFormat::Sqlcipher3Compat,
+        cipher::format_of(&db_path).unwrap()
+    );
+
+    write_sidecar(&db_path, "format=5\n");
+    assert!(cipher::format_of(&db_path).is_err());
+}
+
+#[test]
+fn rollback() {
+    let folder = tempfile::tempdir().unwrap();
+    let db_path = folder.path().join("projectpad.db");
+    fs::write(&db_path, b"db4").unwrap();
+    write_sidecar(&db_path, "format=4\n");
+    assert!(cipher::rollback(&db_path).is_err());
+
+    fs::write(cipher::rollback_path(&db_path), b"db3").unwrap();
+    assert!(cipher::rollback(&db_path).unwrap());
+    assert_eq!(b"db3".to_vec(), fs::read(&db_path).unwrap());
+    assert_eq!(
+        CipherFormat::Sqlcipher3Compat,
+        cipher::format_of(&db_path).unwrap()
+    );
+    // nothing to roll back anymore
+    assert!(!cipher::rollback(&db_path).unwrap());
+    assert_eq!(b"db3".to_vec(), fs::read(&db_path).unwrap());
+}
+
+#[test]
+fn interrupted_switch_is_completed() {
+    let folder = tempfile::tempdir().unwrap();
+    let db_path = folder.path().join("projectpad.db");
+    // a rollback stopped after recording the sqlcipher 3 format,
+    // before replacing the sqlcipher 4 database
+    fs::write(&db_path, b"db4").unwrap();
+    fs::write(folder.path().join("projectpad.db.sqlcipher3.tmp"), b"db3").unwrap();
+    write_sidecar(&db_path, "format=3\n");
+    assert_eq!(
+        CipherFormat::Sqlcipher4,
+        cipher::format_of(&db_path).unwrap()
+    );
+
+    assert!(!cipher::rollback(&db_path).unwrap());
+    assert_eq!(b"db3".to_vec(), fs::read(&db_path).unwrap());
+    assert_eq!(
+        CipherFormat::Sqlcipher3Compat,
+        cipher::format_of(&db_path).unwrap()
+    );
+}
@@ -0,0 +1,46 @@
+// shared by the integration tests, each of them uses only some of it
+#![allow(dead_code)]
+
+use diesel::prelude::*;
+use projectpadsql::migrations;
+use projectpadsql::models::{Server, ServerAccessType, ServerType};
+
+/// an unlocked, migrated in-memory database, with the foreign keys on like in the GUI
+pub fn test_db() -> SqliteConnection {
+    let conn = SqliteConnection::establish(":memory:").unwrap();
+    projectpadsql::try_unlock_db(&conn, "test-pass").unwrap();
+    migrations::migrate_db_if_needed(&conn, None).unwrap();
+    // after migrating: with foreign keys on, renaming tables in
+    // migrations would rewrite the references to them
+    conn.execute("PRAGMA foreign_keys = ON").unwrap();
+    conn
+}
+
+/// an ssh application server in production, to insert: tests
+/// override the fields they care about with `..server(project_id, desc)`
+pub fn server(project_id: i32, desc: &str) -> Server {
+    Server {
+        id: 0,
+        desc: desc.to_string(),
+        host: "10.0.0.1".to_string(),
+        port: None,
+        protocol: None,
+        path: "".to_string(),
+        text: "".to_string(),
+        is_retired: false,
+        username: "root".to_string(),
+        password: "secret".to_string(),
+        password_changed_at: None,
+        otp_secret: "".to_string(),
+        auth_key: None,
+        auth_key_filename: None,
+        server_type: ServerType::SrvApplication,
+        access_type: ServerAccessType::SrvAccessSsh,
+        ssh_tunnel_port: None,
+        ssh_tunnel_through_server_id: None,
+        environment: "Production".to_string(),
+        group_name: None,
+        project_id,
+        deleted_at: None,
+    }
+}
@@ -0,0 +1,362 @@
+mod common;
+
+use common::{server, test_db};
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::repo::{CustomFieldRepo, ProjectRepo, ServerRepo};
+use projectpadsql::search::SearchFilters;
+use projectpadsql::{secrets, Error};
+
+fn insert_server(conn: &SqliteConnection, desc: &str) -> Server {
+    let project = ProjectRepo::new(conn)
+        .insert(&Project {
+            id: 0,
+            name: format!("project of {}", desc),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap();
+    insert_server_in_project(conn, desc, project.id)
+}
+
+fn insert_server_in_project(conn: &SqliteConnection, desc: &str, project_id: i32) -> Server {
+    ServerRepo::new(conn)
+        .insert(&server(project_id, desc))
+        .unwrap()
+}
+
+fn definition(
+    entity_type: EntityType,
+    name: &str,
+    field_type: CustomFieldType,
+) -> CustomFieldDefinition {
+    CustomFieldDefinition {
+        id: 0,
+        project_id: 0,
+        entity_type,
+        name: name.to_string(),
+        field_type,
+        ordering: 0,
+    }
+}
+
+/// a server whose project defines a text field "rack", a secret
+/// field "license" and a date field "warranty" for the servers
+fn server_with_fields(conn: &SqliteConnection) -> (Server, Vec<CustomFieldDefinition>) {
+    let srv = insert_server(conn, "web");
+    let definitions = CustomFieldRepo::new(conn)
+        .save_definitions(
+            srv.project_id,
+            &[
+                definition(EntityType::Server, "rack", CustomFieldType::Text),
+                definition(EntityType::Server, "license", CustomFieldType::Secret),
+                definition(EntityType::Server, "warranty", CustomFieldType::Date),
+            ],
+        )
+        .unwrap();
+    (srv, definitions)
+}
+
+fn values(fields: &[CustomField]) -> Vec<(&str, &str)> {
+    fields
+        .iter()
+        .map(|f| (f.definition.name.as_str(), f.value.as_str()))
+        .collect()
+}
+
+fn search_servers(conn: &SqliteConnection, query: &str) -> Vec<i32> {
+    projectpadsql::search(
+        conn,
+        query,
+        &SearchFilters {
+            entity_types: vec![EntityType::Server],
+            ..SearchFilters::default()
+        },
+    )
+    .unwrap()
+    .into_iter()
+    .map(|hit| hit.entity_id)
+    .collect()
+}
+
+#[test]
+fn check_values() {
+    assert!(CustomFieldType::Text.check_value("anything").is_ok());
+    assert!(CustomFieldType::Url
+        .check_value("https://example.com")
+        .is_ok());
+    assert!(matches!(
+        CustomFieldType::Url.check_value("example.com"),
+        Err(Error::Invalid(_))
+    ));
+    assert!(CustomFieldType::Url.check_value("https://").is_err());
+    assert!(CustomFieldType::Date.check_value("2021-02-28").is_ok());
+    assert!(CustomFieldType::Date.check_value("2021-02-30").is_err());
+    assert!(CustomFieldType::Date.check_value("28/02/2021").is_err());
+}
+
+#[test]
+fn save_definitions() {
+    let conn = test_db();
+    let (srv, definitions) = server_with_fields(&conn);
+    let repo = CustomFieldRepo::new(&conn);
+    assert_eq!(
+        vec!["rack", "license", "warranty"],
+        definitions
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    // reorder, rename, delete and add
+    let saved = repo
+        .save_definitions(
+            srv.project_id,
+            &[
+                definitions[2].clone(),
+                CustomFieldDefinition {
+                    name: "location".to_string(),
+                    ..definitions[0].clone()
+                },
+                definition(EntityType::ServerWebsite, "cdn", CustomFieldType::Url),
+            ],
+        )
+        .unwrap();
+    assert_eq!(
+        vec![
+            (EntityType::Server, "warranty", 0),
+            (EntityType::Server, "location", 1),
+            (EntityType::ServerWebsite, "cdn", 2),
+        ],
+        saved
+            .iter()
+            .map(|d| (d.entity_type, d.name.as_str(), d.ordering))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        2,
+        repo.list_definitions(srv.project_id, EntityType::Server)
+            .unwrap()
+            .len()
+    );
+    assert_eq!(
+        1,
+        repo.list_definitions_for_server(srv.id, EntityType::ServerWebsite)
+            .unwrap()
+            .len()
+    );
+
+    assert!(matches!(
+        repo.save_definitions(
+            srv.project_id,
+            &[
+                definition(EntityType::Server, "rack", CustomFieldType::Text),
+                definition(EntityType::Server, "rack", CustomFieldType::Url),
+            ],
+        ),
+        Err(Error::Constraint(_))
+    ));
+    assert!(matches!(
+        repo.save_definitions(
+            srv.project_id,
+            &[definition(
+                EntityType::ServerNote,
+                "rack",
+                CustomFieldType::Text
+            )],
+        ),
+        Err(Error::Constraint(_))
+    ));
+    // the failed saves were rolled back
+    assert_eq!(
+        saved,
+        repo.list_definitions_for_project(srv.project_id).unwrap()
+    );
+}
+
+#[test]
+fn set_for_item_replaces_the_values() {
+    let conn = test_db();
+    let (srv, definitions) = server_with_fields(&conn);
+    let other = insert_server_in_project(&conn, "db", srv.project_id);
+    let repo = CustomFieldRepo::new(&conn);
+    let (rack, license, warranty) = (definitions[0].id, definitions[1].id, definitions[2].id);
+
+    let fields = repo
+        .set_for_item(
+            EntityType::Server,
+            srv.id,
+            &[
+                (warranty, "2024-01-31".to_string()),
+                (rack, "B12".to_string()),
+                (license, "".to_string()),
+            ],
+        )
+        .unwrap();
+    // in the order of the definitions, without the empty values
+    assert_eq!(
+        vec![("rack", "B12"), ("warranty", "2024-01-31")],
+        values(&fields)
+    );
+
+    let fields = repo
+        .set_for_item(
+            EntityType::Server,
+            srv.id,
+            &[(license, "XYZ-1".to_string()), (rack, "B13".to_string())],
+        )
+        .unwrap();
+    assert_eq!(vec![("rack", "B13"), ("license", "XYZ-1")], values(&fields));
+    assert!(repo
+        .list_for_item(EntityType::Server, other.id)
+        .unwrap()
+        .is_empty());
+    let by_item = repo
+        .list_for_items(EntityType::Server, &[srv.id, other.id])
+        .unwrap();
+    assert_eq!(1, by_item.len());
+    assert_eq!(fields, by_item[&srv.id]);
+
+    assert!(matches!(
+        repo.set_for_item(
+            EntityType::Server,
+            srv.id,
+            &[(warranty, "next year".to_string())]
+        ),
+        Err(Error::Constraint(_))
+    ));
+    let other_project = insert_server(&conn, "mail");
+    assert!(matches!(
+        repo.set_for_item(
+            EntityType::Server,
+            other_project.id,
+            &[(rack, "B1".to_string())]
+        ),
+        Err(Error::Constraint(_))
+    ));
+    assert!(matches!(
+        repo.set_for_item(
+            EntityType::ServerDatabase,
+            srv.id,
+            &[(rack, "B1".to_string())]
+        ),
+        Err(Error::NotFound)
+    ));
+    assert_eq!(
+        fields,
+        repo.list_for_item(EntityType::Server, srv.id).unwrap()
+    );
+}
+
+#[test]
+fn values_go_away_with_their_item_and_definition() {
+    let conn = test_db();
+    let (srv, definitions) = server_with_fields(&conn);
+    let other = insert_server_in_project(&conn, "db", srv.project_id);
+    let repo = CustomFieldRepo::new(&conn);
+    repo.set_for_item(
+        EntityType::Server,
+        srv.id,
+        &[
+            (definitions[0].id, "B12".to_string()),
+            (definitions[2].id, "2024-01-31".to_string()),
+        ],
+    )
+    .unwrap();
+    // the values of the other servers are kept
+    repo.set_for_item(
+        EntityType::Server,
+        other.id,
+        &[(definitions[2].id, "2025-06-30".to_string())],
+    )
+    .unwrap();
+
+    repo.save_definitions(srv.project_id, &definitions[1..])
+        .unwrap();
+    assert_eq!(
+        vec![("warranty", "2024-01-31")],
+        values(&repo.list_for_item(EntityType::Server, srv.id).unwrap())
+    );
+
+    ServerRepo::new(&conn).delete(srv.id).unwrap();
+    let value_count: i64 = projectpadsql::schema::custom_field_value::table
+        .count()
+        .get_result(&conn)
+        .unwrap();
+    assert_eq!(1, value_count);
+
+    ProjectRepo::new(&conn).delete(srv.project_id).unwrap();
+    assert!(repo
+        .list_definitions_for_project(srv.project_id)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn search_finds_the_values_except_secrets() {
+    let conn = test_db();
+    let (srv, definitions) = server_with_fields(&conn);
+    let repo = CustomFieldRepo::new(&conn);
+    repo.set_for_item(
+        EntityType::Server,
+        srv.id,
+        &[
+            (definitions[0].id, "rack-b12".to_string()),
+            (definitions[1].id, "XYZ-license".to_string()),
+        ],
+    )
+    .unwrap();
+    assert_eq!(vec![srv.id], search_servers(&conn, "rack-b12"));
+    assert!(search_servers(&conn, "XYZ-license").is_empty());
+
+    repo.set_for_item(
+        EntityType::Server,
+        srv.id,
+        &[(definitions[0].id, "rack-c3".to_string())],
+    )
+    .unwrap();
+    assert!(search_servers(&conn, "rack-b12").is_empty());
+    assert_eq!(vec![srv.id], search_servers(&conn, "rack-c3"));
+
+    // editing the server keeps the values in the index
+    ServerRepo::new(&conn)
+        .update(&Server {
+            desc: "proxy".to_string(),
+            ..srv.clone()
+        })
+        .unwrap();
+    assert_eq!(vec![srv.id], search_servers(&conn, "rack-c3"));
+
+    repo.save_definitions(srv.project_id, &definitions[1..])
+        .unwrap();
+    assert!(search_servers(&conn, "rack-c3").is_empty());
+    assert_eq!(vec![srv.id], search_servers(&conn, "proxy"));
+}
+
+#[test]
+fn master_passphrase_encrypts_the_secret_values() {
+    let conn = test_db();
+    let (srv, definitions) = server_with_fields(&conn);
+    let repo = CustomFieldRepo::new(&conn);
+    repo.set_for_item(
+        EntityType::Server,
+        srv.id,
+        &[
+            (definitions[0].id, "B12".to_string()),
+            (definitions[1].id, "XYZ-1".to_string()),
+        ],
+    )
+    .unwrap();
+
+    let key = secrets::enable(&conn, "master").unwrap();
+    let fields = repo.list_for_item(EntityType::Server, srv.id).unwrap();
+    assert_eq!("B12", fields[0].value);
+    assert!(secrets::is_encrypted(&fields[1].value));
+    assert_eq!("XYZ-1", key.decrypt(&fields[1].value).unwrap());
+
+    secrets::disable(&conn, &key).unwrap();
+    assert_eq!(
+        vec![("rack", "B12"), ("license", "XYZ-1")],
+        values(&repo.list_for_item(EntityType::Server, srv.id).unwrap())
+    );
+}
@@ -0,0 +1,257 @@
+mod common;
+
+use common::{server, test_db};
+use diesel::prelude::*;
+use projectpadsql::dependency_graph;
+use projectpadsql::models::*;
+use projectpadsql::repo::{ProjectRepo, ServerDependencyRepo, ServerRepo};
+use projectpadsql::{trash, Error};
+
+fn insert_project(conn: &SqliteConnection, name: &str) -> i32 {
+    ProjectRepo::new(conn)
+        .insert(&Project {
+            id: 0,
+            name: name.to_string(),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap()
+        .id
+}
+
+fn insert_server(conn: &SqliteConnection, project_id: i32, environment: &str, desc: &str) -> i32 {
+    ServerRepo::new(conn)
+        .insert(&Server {
+            password: "".to_string(),
+            environment: environment.to_string(),
+            ..server(project_id, desc)
+        })
+        .unwrap()
+        .id
+}
+
+fn depends_on(conn: &SqliteConnection, server_id: i32, on: i32, dependency_type: DependencyType) {
+    ServerDependencyRepo::new(conn)
+        .insert(&ServerDependency {
+            id: 0,
+            server_id,
+            depends_on_server_id: on,
+            dependency_type,
+            desc: "".to_string(),
+        })
+        .unwrap();
+}
+
+/// a website on `website_server_id` using a database on `database_server_id`
+fn insert_website_with_database(
+    conn: &SqliteConnection,
+    desc: &str,
+    website_server_id: i32,
+    database_server_id: i32,
+) {
+    use projectpadsql::schema::server_database::dsl as db;
+    use projectpadsql::schema::server_website::dsl as www;
+    diesel::insert_into(db::server_database)
+        .values((
+            db::desc.eq(desc),
+            db::name.eq(desc),
+            db::text.eq(""),
+            db::username.eq("app"),
+            db::password.eq(""),
+            db::server_id.eq(database_server_id),
+        ))
+        .execute(conn)
+        .unwrap();
+    let database_id: i32 = db::server_database
+        .filter(db::desc.eq(desc))
+        .select(db::id)
+        .first(conn)
+        .unwrap();
+    diesel::insert_into(www::server_website)
+        .values((
+            www::desc.eq(desc),
+            www::url.eq("https://example.com"),
+            www::text.eq(""),
+            www::username.eq(""),
+            www::password.eq(""),
+            www::otp_secret.eq(""),
+            www::server_database_id.eq(database_id),
+            www::server_id.eq(website_server_id),
+        ))
+        .execute(conn)
+        .unwrap();
+}
+
+fn dependent_descs(conn: &SqliteConnection, server_id: i32) -> Vec<(String, usize)> {
+    dependency_graph::dependents(conn, server_id)
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.server.desc, d.distance))
+        .collect()
+}
+
+#[test]
+fn invalid_dependencies_are_refused() {
+    let conn = test_db();
+    let project_id = insert_project(&conn, "shop");
+    let app = insert_server(&conn, project_id, "Production", "app");
+    let db = insert_server(&conn, project_id, "Production", "db");
+    depends_on(&conn, app, db, DependencyType::Database);
+    let repo = ServerDependencyRepo::new(&conn);
+    let dependency = ServerDependency {
+        id: 0,
+        server_id: app,
+        depends_on_server_id: app,
+        dependency_type: DependencyType::Cache,
+        desc: "".to_string(),
+    };
+    assert!(matches!(
+        repo.insert(&dependency),
+        Err(Error::Constraint(_))
+    ));
+    assert!(matches!(
+        repo.insert(&ServerDependency {
+            depends_on_server_id: db,
+            dependency_type: DependencyType::Database,
+            ..dependency.clone()
+        }),
+        Err(Error::Constraint(_))
+    ));
+    // another type of dependency on the same server is fine
+    repo.insert(&ServerDependency {
+        depends_on_server_id: db,
+        ..dependency
+    })
+    .unwrap();
+    assert_eq!(2, repo.list_for_server(app).unwrap().len());
+}
+
+#[test]
+fn dependencies_go_away_with_the_servers() {
+    let conn = test_db();
+    let project_id = insert_project(&conn, "shop");
+    let app = insert_server(&conn, project_id, "Production", "app");
+    let db = insert_server(&conn, project_id, "Production", "db");
+    depends_on(&conn, app, db, DependencyType::Database);
+
+    // the servers in the trash keep their dependencies, but don't count
+    trash::move_to_trash(&conn, EntityType::Server, db).unwrap();
+    assert!(dependent_descs(&conn, db).is_empty());
+    assert_eq!(
+        1,
+        ServerDependencyRepo::new(&conn)
+            .list_for_server(app)
+            .unwrap()
+            .len()
+    );
+    trash::restore(&conn, EntityType::Server, db).unwrap();
+    assert_eq!(vec![("app".to_string(), 1)], dependent_descs(&conn, db));
+
+    ServerRepo::new(&conn).delete(db).unwrap();
+    assert!(ServerDependencyRepo::new(&conn)
+        .list_for_server(app)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn dependents_are_transitive() {
+    let conn = test_db();
+    let shop = insert_project(&conn, "shop");
+    let infra = insert_project(&conn, "infra");
+    let db = insert_server(&conn, infra, "Production", "db");
+    let app = insert_server(&conn, shop, "Production", "app");
+    let proxy = insert_server(&conn, shop, "Production", "proxy");
+    let batch = insert_server(&conn, shop, "Production", "batch");
+    let web = insert_server(&conn, shop, "Production", "web");
+    depends_on(&conn, app, db, DependencyType::Database);
+    depends_on(&conn, proxy, app, DependencyType::Proxy);
+    depends_on(&conn, batch, app, DependencyType::Other);
+    // a cycle
+    depends_on(&conn, db, proxy, DependencyType::Authentication);
+    insert_website_with_database(&conn, "webshop", web, db);
+
+    assert_eq!(
+        vec![
+            ("app".to_string(), 1),
+            ("web".to_string(), 1),
+            ("batch".to_string(), 2),
+            ("proxy".to_string(), 2),
+        ],
+        dependent_descs(&conn, db)
+    );
+    let web_dependent = dependency_graph::dependents(&conn, db).unwrap().remove(1);
+    assert_eq!("shop", web_dependent.project_name);
+    assert_eq!(None, web_dependent.edge.dependency_id);
+    assert_eq!(DependencyType::Database, web_dependent.edge.dependency_type);
+    assert_eq!("webshop", web_dependent.edge.desc);
+    assert!(dependent_descs(&conn, web).is_empty());
+}
+
+#[test]
+fn environment_graph() {
+    let conn = test_db();
+    let shop = insert_project(&conn, "shop");
+    let infra = insert_project(&conn, "infra");
+    let db = insert_server(&conn, infra, "Production", "db");
+    let app = insert_server(&conn, shop, "Production", "app");
+    let proxy = insert_server(&conn, shop, "Production", "proxy");
+    insert_server(&conn, shop, "Production", "monitoring");
+    let staging_app = insert_server(&conn, shop, "Staging", "staging app");
+    let ldap = insert_server(&conn, infra, "Production", "ldap");
+    depends_on(&conn, proxy, app, DependencyType::Proxy);
+    depends_on(&conn, app, db, DependencyType::Database);
+    depends_on(&conn, staging_app, db, DependencyType::Database);
+    depends_on(&conn, db, ldap, DependencyType::Authentication);
+    {
+        use projectpadsql::schema::server_link::dsl as srv_link;
+        diesel::insert_into(srv_link::server_link)
+            .values((
+                srv_link::desc.eq("shared db"),
+                srv_link::linked_server_id.eq(db),
+                srv_link::environment.eq("Production"),
+                srv_link::project_id.eq(shop),
+            ))
+            .execute(&conn)
+            .unwrap();
+    }
+
+    let graph = dependency_graph::for_environment(&conn, shop, "Production").unwrap();
+    let servers: Vec<(&str, bool)> = graph
+        .servers
+        .iter()
+        .map(|s| (s.server.desc.as_str(), s.external))
+        .collect();
+    // the linked db is in the environment, the ldap and
+    // staging servers only connect to the environment
+    assert_eq!(
+        vec![
+            ("app", false),
+            ("db", false),
+            ("monitoring", false),
+            ("proxy", false),
+            ("ldap", true),
+            ("staging app", true),
+        ],
+        servers
+    );
+    assert_eq!(4, graph.edges.len());
+    let layers: Vec<Vec<&str>> = graph
+        .layers()
+        .into_iter()
+        .map(|l| {
+            l.into_iter()
+                .map(|idx| graph.servers[idx].server.desc.as_str())
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            vec!["monitoring", "proxy", "staging app"],
+            vec!["app"],
+            vec!["db"],
+            vec!["ldap"],
+        ],
+        layers
+    );
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::test_db;
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::repo::{EnvironmentRepo, ProjectRepo, ServerRepo};
+use projectpadsql::Error;
+
+fn project_with_defaults(conn: &SqliteConnection, name: &str) -> (Project, Vec<Environment>) {
+    let project = ProjectRepo::new(conn)
+        .insert(&Project {
+            id: 0,
+            name: name.to_string(),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap();
+    let envs = EnvironmentRepo::new(conn)
+        .save_for_project(project.id, &Environment::defaults(project.id))
+        .unwrap();
+    (project, envs)
+}
+
+fn server(desc: &str, environment: &str, project_id: i32) -> Server {
+    Server {
+        environment: environment.to_string(),
+        ..common::server(project_id, desc)
+    }
+}
+
+#[test]
+fn save_for_project_orders_inserts_and_deletes() {
+    let conn = test_db();
+    let (project, envs) = project_with_defaults(&conn, "p");
+    assert_eq!(
+        vec!["Development", "Staging", "UAT", "Production"],
+        envs.iter().map(|e| e.name.as_str()).collect::<Vec<_>>()
+    );
+    assert!(envs[3].dangerous);
+    assert_eq!(Some((0xdf, 0x42, 0x1e)), envs[3].rgb());
+
+    let repo = EnvironmentRepo::new(&conn);
+    let mut edited = vec![envs[3].clone(), envs[0].clone()];
+    edited.push(Environment {
+        id: 0,
+        name: "Demo".to_string(),
+        short_label: "DMO".to_string(),
+        ..envs[1].clone()
+    });
+    let saved = repo.save_for_project(project.id, &edited).unwrap();
+    assert_eq!(
+        vec![("Production", 0), ("Development", 1), ("Demo", 2)],
+        saved
+            .iter()
+            .map(|e| (e.name.as_str(), e.ordering))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn rename_cascades_to_servers() {
+    let conn = test_db();
+    let (project, envs) = project_with_defaults(&conn, "p");
+    let (other_project, _) = project_with_defaults(&conn, "other");
+    let srv = ServerRepo::new(&conn)
+        .insert(&server("web", "Production", project.id))
+        .unwrap();
+    let other_srv = ServerRepo::new(&conn)
+        .insert(&server("web", "Production", other_project.id))
+        .unwrap();
+
+    EnvironmentRepo::new(&conn)
+        .update(&Environment {
+            name: "Live".to_string(),
+            ..envs[3].clone()
+        })
+        .unwrap();
+    let repo = ServerRepo::new(&conn);
+    assert_eq!("Live", repo.get(srv.id).unwrap().environment);
+    assert_eq!("Production", repo.get(other_srv.id).unwrap().environment);
+}
+
+#[test]
+fn delete_in_use_fails() {
+    let conn = test_db();
+    let (project, envs) = project_with_defaults(&conn, "p");
+    ServerRepo::new(&conn)
+        .insert(&server("web", "Production", project.id))
+        .unwrap();
+    let repo = EnvironmentRepo::new(&conn);
+    assert!(matches!(repo.delete(envs[3].id), Err(Error::InUse(_))));
+    // also through save_for_project, which then changes nothing
+    assert!(matches!(
+        repo.save_for_project(project.id, &envs[0..3]),
+        Err(Error::InUse(_))
+    ));
+    assert_eq!(4, repo.list_for_project(project.id).unwrap().len());
+    repo.delete(envs[0].id).unwrap();
+    assert_eq!(3, repo.list_for_project(project.id).unwrap().len());
+}
+
+#[test]
+fn note_environments() {
+    use projectpadsql::schema::project_note::dsl as prj_note;
+    let conn = test_db();
+    let (project, envs) = project_with_defaults(&conn, "p");
+    diesel::insert_into(prj_note::project_note)
+        .values((
+            prj_note::title.eq("note"),
+            prj_note::contents.eq("contents"),
+            prj_note::project_id.eq(project.id),
+        ))
+        .execute(&conn)
+        .unwrap();
+    let note_id = prj_note::project_note
+        .select(prj_note::id)
+        .first::<i32>(&conn)
+        .unwrap();
+    let repo = EnvironmentRepo::new(&conn);
+    repo.set_for_note(note_id, &[envs[3].id, envs[0].id])
+        .unwrap();
+    assert_eq!(
+        vec!["Development", "Production"],
+        repo.list_for_note(note_id)
+            .unwrap()
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    repo.delete(envs[0].id).unwrap();
+    assert_eq!(1, repo.list_for_note(note_id).unwrap().len());
+    repo.set_for_note(note_id, &[]).unwrap();
+    assert!(repo.list_for_note(note_id).unwrap().is_empty());
+}
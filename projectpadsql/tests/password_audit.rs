@@ -0,0 +1,246 @@
+mod common;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use common::{server, test_db};
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::password_audit::{self, OverduePassword};
+use projectpadsql::repo::{EnvironmentRepo, ProjectRepo, ServerRepo};
+use projectpadsql::{secrets, trash};
+
+fn insert_project(conn: &SqliteConnection, name: &str) -> Project {
+    let project = ProjectRepo::new(conn)
+        .insert(&Project {
+            id: 0,
+            name: name.to_string(),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap();
+    EnvironmentRepo::new(conn)
+        .save_for_project(project.id, &Environment::defaults(project.id))
+        .unwrap();
+    project
+}
+
+fn insert_server(
+    conn: &SqliteConnection,
+    desc: &str,
+    password: &str,
+    environment: &str,
+    project_id: i32,
+) -> Server {
+    ServerRepo::new(conn)
+        .insert(&Server {
+            password: password.to_string(),
+            environment: environment.to_string(),
+            ..server(project_id, desc)
+        })
+        .unwrap()
+}
+
+fn insert_database(conn: &SqliteConnection, desc: &str, password: &str, server_id: i32) -> i32 {
+    use projectpadsql::schema::server_database::dsl as db;
+    diesel::insert_into(db::server_database)
+        .values((
+            db::desc.eq(desc),
+            db::name.eq(desc),
+            db::text.eq(""),
+            db::username.eq("app"),
+            db::password.eq(password),
+            db::server_id.eq(server_id),
+        ))
+        .execute(conn)
+        .unwrap();
+    db::server_database
+        .filter(db::desc.eq(desc))
+        .select(db::id)
+        .first(conn)
+        .unwrap()
+}
+
+fn set_server_password_changed_at(conn: &SqliteConnection, id: i32, at: Option<NaiveDateTime>) {
+    use projectpadsql::schema::server::dsl as srv;
+    diesel::update(srv::server.find(id))
+        .set(srv::password_changed_at.eq(at))
+        .execute(conn)
+        .unwrap();
+}
+
+fn overdue_titles(conn: &SqliteConnection, now: NaiveDateTime) -> Vec<(EntityType, String)> {
+    password_audit::overdue_passwords(conn, now)
+        .unwrap()
+        .into_iter()
+        .map(|p: OverduePassword| (p.entity_type, p.title))
+        .collect()
+}
+
+#[test]
+fn changing_the_password_records_the_date() {
+    let conn = test_db();
+    let project = insert_project(&conn, "p");
+    let srv = insert_server(&conn, "web", "secret", "Production", project.id);
+    let no_password = insert_server(&conn, "proxy", "", "Production", project.id);
+    assert!(srv.password_changed_at.is_some());
+    assert_eq!(None, no_password.password_changed_at);
+
+    let repo = ServerRepo::new(&conn);
+    set_server_password_changed_at(&conn, srv.id, None);
+    let srv = repo
+        .update(&Server {
+            desc: "www".to_string(),
+            ..repo.get(srv.id).unwrap()
+        })
+        .unwrap();
+    // other changes leave the date alone
+    assert_eq!(None, srv.password_changed_at);
+    let srv = repo
+        .update(&Server {
+            password: "new-secret".to_string(),
+            ..srv
+        })
+        .unwrap();
+    assert!(srv.password_changed_at.is_some());
+    let srv = repo
+        .update(&Server {
+            password: "".to_string(),
+            ..srv
+        })
+        .unwrap();
+    assert_eq!(None, srv.password_changed_at);
+}
+
+#[test]
+fn master_passphrase_is_not_a_password_change() {
+    let conn = test_db();
+    let project = insert_project(&conn, "p");
+    let srv = insert_server(&conn, "web", "secret", "Production", project.id);
+    let long_ago = Utc::now().naive_utc() - Duration::days(365);
+    set_server_password_changed_at(&conn, srv.id, Some(long_ago));
+
+    let repo = ServerRepo::new(&conn);
+    let key = secrets::enable(&conn, "master").unwrap();
+    let srv = repo.get(srv.id).unwrap();
+    assert!(secrets::is_encrypted(&srv.password));
+    assert_eq!(Some(long_ago), srv.password_changed_at);
+    secrets::disable(&conn, &key).unwrap();
+    assert_eq!(
+        Some(long_ago),
+        repo.get(srv.id).unwrap().password_changed_at
+    );
+}
+
+#[test]
+fn set_policy() {
+    let conn = test_db();
+    let project = insert_project(&conn, "p");
+    assert_eq!(None, password_audit::policy(&conn, project.id).unwrap());
+    let policy = PasswordPolicy {
+        project_id: project.id,
+        max_age_days: 90,
+        dangerous_environments_only: true,
+    };
+    password_audit::set_policy(&conn, project.id, Some(&policy)).unwrap();
+    let policy = PasswordPolicy {
+        max_age_days: 30,
+        ..policy
+    };
+    password_audit::set_policy(&conn, project.id, Some(&policy)).unwrap();
+    assert_eq!(
+        Some(policy),
+        password_audit::policy(&conn, project.id).unwrap()
+    );
+    assert!(password_audit::set_policy(
+        &conn,
+        project.id,
+        Some(&PasswordPolicy {
+            max_age_days: 0,
+            ..policy
+        })
+    )
+    .is_err());
+
+    password_audit::set_policy(&conn, project.id, None).unwrap();
+    assert_eq!(None, password_audit::policy(&conn, project.id).unwrap());
+
+    // the policy goes away with its project
+    password_audit::set_policy(&conn, project.id, Some(&policy)).unwrap();
+    ProjectRepo::new(&conn).delete(project.id).unwrap();
+    assert_eq!(None, password_audit::policy(&conn, project.id).unwrap());
+}
+
+#[test]
+fn overdue_passwords_follow_the_policy() {
+    let conn = test_db();
+    let now = Utc::now().naive_utc();
+    let project = insert_project(&conn, "p");
+    let prod = insert_server(&conn, "prod", "secret", "Production", project.id);
+    let uat = insert_server(&conn, "uat", "secret", "UAT", project.id);
+    insert_server(&conn, "no password", "", "Production", project.id);
+    insert_database(&conn, "db", "dbpass", prod.id);
+    let other_project = insert_project(&conn, "no policy");
+    let other = insert_server(&conn, "other", "secret", "Production", other_project.id);
+    set_server_password_changed_at(&conn, other.id, Some(now - Duration::days(1000)));
+
+    // no policy, nothing is overdue
+    assert!(overdue_titles(&conn, now).is_empty());
+
+    password_audit::set_policy(
+        &conn,
+        project.id,
+        Some(&PasswordPolicy {
+            project_id: project.id,
+            max_age_days: 90,
+            dangerous_environments_only: false,
+        }),
+    )
+    .unwrap();
+    assert!(overdue_titles(&conn, now).is_empty());
+    let in_100_days = now + Duration::days(100);
+    assert_eq!(
+        vec![
+            (EntityType::ServerDatabase, "db".to_string()),
+            (EntityType::Server, "prod".to_string()),
+            (EntityType::Server, "uat".to_string()),
+        ],
+        overdue_titles(&conn, in_100_days)
+    );
+
+    // a password with an unknown date is overdue
+    set_server_password_changed_at(&conn, uat.id, None);
+    let overdue = password_audit::overdue_passwords(&conn, now).unwrap();
+    assert_eq!(1, overdue.len());
+    assert_eq!(
+        ("uat", "p", "UAT", None, 90),
+        (
+            overdue[0].server_desc.as_str(),
+            overdue[0].project_name.as_str(),
+            overdue[0].environment.as_str(),
+            overdue[0].password_changed_at,
+            overdue[0].max_age_days
+        )
+    );
+
+    password_audit::set_policy(
+        &conn,
+        project.id,
+        Some(&PasswordPolicy {
+            project_id: project.id,
+            max_age_days: 90,
+            dangerous_environments_only: true,
+        }),
+    )
+    .unwrap();
+    assert!(overdue_titles(&conn, now).is_empty());
+    assert_eq!(
+        vec![
+            (EntityType::ServerDatabase, "db".to_string()),
+            (EntityType::Server, "prod".to_string()),
+        ],
+        overdue_titles(&conn, in_100_days)
+    );
+
+    // the items in the trash are ignored
+    trash::move_to_trash(&conn, EntityType::Server, prod.id).unwrap();
+    assert!(overdue_titles(&conn, in_100_days).is_empty());
+}
@@ -0,0 +1,53 @@
+use projectpadsql::profile::{self, DEFAULT_PROFILE};
+use projectpadsql::Error;
+use std::path::Path;
+
+#[test]
+fn profile_file_names() {
+    let folder = Path::new("/data/projectpad");
+    assert_eq!(
+        folder.join("projectpad.db"),
+        profile::file_path_in(folder, DEFAULT_PROFILE, "projectpad.db")
+    );
+    assert_eq!(
+        folder.join("projectpad-work.db"),
+        profile::file_path_in(folder, "work", "projectpad.db")
+    );
+    assert_eq!(
+        folder.join("cli-history-work"),
+        profile::file_path_in(folder, "work", "cli-history")
+    );
+    assert_eq!("projectpad-cli", profile::keyring_service(DEFAULT_PROFILE));
+    assert_eq!("projectpad-cli-work", profile::keyring_service("work"));
+}
+
+#[test]
+fn check_name() {
+    assert!(profile::check_name("work").is_ok());
+    assert!(profile::check_name("home-lab_2").is_ok());
+    assert!(matches!(profile::check_name(""), Err(Error::Invalid(_))));
+    assert!(profile::check_name("../work").is_err());
+    assert!(profile::check_name("my work").is_err());
+}
+
+#[test]
+fn list_profiles() {
+    let folder = tempfile::tempdir().unwrap();
+    // no database yet, the default profile is still there
+    assert_eq!(vec![DEFAULT_PROFILE], profile::list_in(folder.path()));
+
+    for file in &[
+        "projectpad.db",
+        "projectpad-work.db",
+        "projectpad-home.db",
+        "projectpad-work.db.v21.bak",
+        "projectpad-default.db",
+        "sessions-work.db",
+    ] {
+        std::fs::write(folder.path().join(file), b"").unwrap();
+    }
+    assert_eq!(
+        vec![DEFAULT_PROFILE, "home", "work"],
+        profile::list_in(folder.path())
+    );
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use common::{server, test_db};
+use projectpadsql::models::*;
+use projectpadsql::repo::{ProjectRepo, ServerPoiRepo, ServerRepo};
+use projectpadsql::Error;
+
+fn project(name: &str) -> Project {
+    Project {
+        id: 0,
+        name: name.to_string(),
+        icon: Some(b"icon".to_vec()),
+        deleted_at: None,
+    }
+}
+
+fn server_poi(desc: &str, server_id: i32) -> ServerPointOfInterest {
+    ServerPointOfInterest {
+        id: 0,
+        desc: desc.to_string(),
+        path: "/var/log".to_string(),
+        text: "app.log".to_string(),
+        interest_type: InterestType::PoiLogFile,
+        run_on: RunOn::RunOnServer,
+        group_name: None,
+        server_id,
+        deleted_at: None,
+    }
+}
+
+#[test]
+fn project_crud() {
+    let conn = test_db();
+    let repo = ProjectRepo::new(&conn);
+    let beta = repo.insert(&project("beta")).unwrap();
+    let alpha = repo.insert(&project("alpha")).unwrap();
+    assert_ne!(alpha.id, beta.id);
+    assert_eq!("alpha", alpha.name);
+    assert_eq!(vec![alpha.clone(), beta.clone()], repo.list().unwrap());
+
+    let renamed = repo
+        .update(&Project {
+            name: "gamma".to_string(),
+            ..alpha
+        })
+        .unwrap();
+    assert_eq!("gamma", repo.get(alpha.id).unwrap().name);
+    assert_eq!(vec![beta.clone(), renamed], repo.list().unwrap());
+
+    repo.delete(alpha.id).unwrap();
+    assert!(matches!(repo.get(alpha.id), Err(Error::NotFound)));
+    assert_eq!(vec![beta], repo.list().unwrap());
+}
+
+#[test]
+fn missing_rows_are_not_found() {
+    let conn = test_db();
+    let repo = ProjectRepo::new(&conn);
+    assert!(matches!(repo.get(42), Err(Error::NotFound)));
+    assert!(matches!(repo.delete(42), Err(Error::NotFound)));
+    let mut prj = project("alpha");
+    prj.id = 42;
+    assert!(matches!(repo.update(&prj), Err(Error::NotFound)));
+}
+
+#[test]
+fn project_search() {
+    let conn = test_db();
+    let repo = ProjectRepo::new(&conn);
+    repo.insert(&project("Billing")).unwrap();
+    repo.insert(&project("Intranet")).unwrap();
+    repo.insert(&project("100%_done")).unwrap();
+    let names = |text| {
+        repo.search(text)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec!["Billing", "Intranet"], names("in"));
+    assert_eq!(vec!["100%_done"], names("%_"));
+    assert!(names("_x").is_empty());
+}
+
+#[test]
+fn server_crud_and_search() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let other_prj = ProjectRepo::new(&conn).insert(&project("beta")).unwrap();
+    let repo = ServerRepo::new(&conn);
+    let web = repo.insert(&server(prj.id, "web")).unwrap();
+    let db = repo
+        .insert(&Server {
+            host: "db.example.com".to_string(),
+            port: None,
+            protocol: None,
+            path: "".to_string(),
+            ..server(prj.id, "database")
+        })
+        .unwrap();
+    repo.insert(&server(other_prj.id, "other")).unwrap();
+    assert_eq!("secret", web.password);
+    assert_eq!(
+        vec![db.clone(), web.clone()],
+        repo.list_for_project(prj.id).unwrap()
+    );
+    assert_eq!(vec![db.clone()], repo.search("example").unwrap());
+
+    let updated = repo
+        .update(&Server {
+            is_retired: true,
+            ..web.clone()
+        })
+        .unwrap();
+    assert!(updated.is_retired);
+
+    repo.delete(db.id).unwrap();
+    assert_eq!(vec![updated], repo.list_for_project(prj.id).unwrap());
+}
+
+#[test]
+fn constraint_violations() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let repo = ServerRepo::new(&conn);
+    // group names can't be empty
+    let res = repo.insert(&Server {
+        group_name: Some("".to_string()),
+        ..server(prj.id, "web")
+    });
+    assert!(matches!(res, Err(Error::Constraint(_))));
+    // the project must exist
+    let res = repo.insert(&server(prj.id + 1, "web"));
+    assert!(matches!(res, Err(Error::Constraint(_))));
+    // tunnel ports are unique
+    let tunnel = Server {
+        ssh_tunnel_port: Some(2222),
+        ..server(prj.id, "tunnel1")
+    };
+    repo.insert(&tunnel).unwrap();
+    let res = repo.insert(&Server {
+        desc: "tunnel2".to_string(),
+        ..tunnel
+    });
+    assert!(matches!(res, Err(Error::Constraint(_))));
+}
+
+#[test]
+fn deletes_cascade() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let srv = ServerRepo::new(&conn)
+        .insert(&server(prj.id, "web"))
+        .unwrap();
+    let poi_repo = ServerPoiRepo::new(&conn);
+    let poi = poi_repo.insert(&server_poi("logs", srv.id)).unwrap();
+    assert_eq!(vec![poi.clone()], poi_repo.list_for_server(srv.id).unwrap());
+    assert_eq!(vec![poi], poi_repo.search("app.LOG").unwrap());
+
+    ProjectRepo::new(&conn).delete(prj.id).unwrap();
+    assert!(ServerRepo::new(&conn).list().unwrap().is_empty());
+    assert!(poi_repo.list().unwrap().is_empty());
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use common::test_db;
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::repo::{ProjectRepo, ServerPoiRepo, ServerRepo};
+use projectpadsql::search::{SearchFilters, SNIPPET_MATCH_END, SNIPPET_MATCH_START};
+
+fn project(name: &str) -> Project {
+    Project {
+        id: 0,
+        name: name.to_string(),
+        icon: Some(b"icon".to_vec()),
+        deleted_at: None,
+    }
+}
+
+fn server(desc: &str, ip: &str, text: &str, project_id: i32) -> Server {
+    Server {
+        host: ip.to_string(),
+        text: text.to_string(),
+        ..common::server(project_id, desc)
+    }
+}
+
+fn server_poi(desc: &str, path: &str, server_id: i32) -> ServerPointOfInterest {
+    ServerPointOfInterest {
+        id: 0,
+        desc: desc.to_string(),
+        path: path.to_string(),
+        text: "".to_string(),
+        interest_type: InterestType::PoiLogFile,
+        run_on: RunOn::RunOnServer,
+        group_name: None,
+        server_id,
+        deleted_at: None,
+    }
+}
+
+/// (entity type, title) of the hits, in order
+fn search(
+    conn: &SqliteConnection,
+    query: &str,
+    filters: &SearchFilters,
+) -> Vec<(EntityType, String)> {
+    projectpadsql::search(conn, query, filters)
+        .unwrap()
+        .into_iter()
+        .map(|h| (h.entity_type, h.title))
+        .collect()
+}
+
+#[test]
+fn search_is_maintained_by_triggers() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("Billing")).unwrap();
+    let srv = ServerRepo::new(&conn)
+        .insert(&server("frontend", "10.0.0.1", "nginx", prj.id))
+        .unwrap();
+    let poi = ServerPoiRepo::new(&conn)
+        .insert(&server_poi("nginx logs", "/var/log/nginx", srv.id))
+        .unwrap();
+    let no_filters = SearchFilters::default();
+
+    assert_eq!(
+        vec![(EntityType::ServerPoi, "nginx logs".to_string())],
+        search(&conn, "nginx lo", &no_filters)
+    );
+
+    ServerRepo::new(&conn)
+        .update(&Server {
+            desc: "proxy".to_string(),
+            ..srv.clone()
+        })
+        .unwrap();
+    assert!(search(&conn, "frontend", &no_filters).is_empty());
+    assert_eq!(
+        vec![(EntityType::Server, "proxy".to_string())],
+        search(&conn, "prox", &no_filters)
+    );
+
+    ServerPoiRepo::new(&conn).delete(poi.id).unwrap();
+    assert_eq!(
+        vec![(EntityType::Server, "proxy".to_string())],
+        search(&conn, "nginx", &no_filters)
+    );
+
+    // cascading deletes also update the index
+    ProjectRepo::new(&conn).delete(prj.id).unwrap();
+    assert!(search(&conn, "", &no_filters).is_empty());
+}
+
+#[test]
+fn search_ranks_and_filters() {
+    let conn = test_db();
+    let billing = ProjectRepo::new(&conn).insert(&project("Billing")).unwrap();
+    let intranet = ProjectRepo::new(&conn)
+        .insert(&project("Intranet"))
+        .unwrap();
+    let srv_repo = ServerRepo::new(&conn);
+    let db = srv_repo
+        .insert(&server(
+            "database",
+            "10.0.0.2",
+            "postgres for the reports",
+            billing.id,
+        ))
+        .unwrap();
+    srv_repo
+        .insert(&server("reports", "10.0.0.3", "", intranet.id))
+        .unwrap();
+
+    // title matches rank first
+    let hits = projectpadsql::search(&conn, "report", &SearchFilters::default()).unwrap();
+    assert_eq!(
+        vec!["reports", "database"],
+        hits.iter().map(|h| h.title.as_str()).collect::<Vec<_>>()
+    );
+    let db_hit = &hits[1];
+    assert_eq!(db.id, db_hit.entity_id);
+    assert_eq!(billing.id, db_hit.project_id);
+    assert_eq!(Some(db.id), db_hit.server_id);
+    assert!(db_hit.snippet.contains(&format!(
+        "{}reports{}",
+        SNIPPET_MATCH_START, SNIPPET_MATCH_END
+    )));
+
+    // an ip address matches as a phrase
+    assert_eq!(
+        vec![(EntityType::Server, "database".to_string())],
+        search(&conn, "10.0.0.2", &SearchFilters::default())
+    );
+
+    let billing_only = SearchFilters {
+        project_name: Some("bill".to_string()),
+        ..SearchFilters::default()
+    };
+    assert_eq!(
+        vec![(EntityType::Server, "database".to_string())],
+        search(&conn, "report", &billing_only)
+    );
+
+    let projects_only = SearchFilters {
+        entity_types: vec![EntityType::Project],
+        ..SearchFilters::default()
+    };
+    assert_eq!(
+        vec![
+            (EntityType::Project, "Billing".to_string()),
+            (EntityType::Project, "Intranet".to_string())
+        ],
+        search(&conn, "", &projects_only)
+    );
+    let limited = SearchFilters {
+        limit: Some(1),
+        ..SearchFilters::default()
+    };
+    assert_eq!(1, search(&conn, "report", &limited).len());
+}
+
+#[test]
+fn search_query_syntax_is_not_interpreted() {
+    let conn = test_db();
+    ProjectRepo::new(&conn).insert(&project("Billing")).unwrap();
+    let no_filters = SearchFilters::default();
+    for query in &["\"", "bill*", "NOT bill", "bill OR", "(", "-", "title:bill"] {
+        projectpadsql::search(&conn, query, &no_filters).unwrap();
+    }
+    assert!(search(&conn, "NEAR(", &no_filters).is_empty());
+}
@@ -0,0 +1,242 @@
+#[macro_use]
+extern crate diesel;
+
+mod common;
+
+use common::test_db;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use projectpadsql::models::*;
+use projectpadsql::repo::{ProjectRepo, ServerRepo};
+use projectpadsql::secrets::{self, SecretSession};
+use projectpadsql::{change_log, Error};
+use std::time::Duration;
+
+fn server(password: &str, project_id: i32) -> Server {
+    Server {
+        password: password.to_string(),
+        ..common::server(project_id, "srv")
+    }
+}
+
+/// a project with a server and a note, and an edit of the server
+/// password in the change log
+fn db_with_passwords() -> (SqliteConnection, Server, i32) {
+    use projectpadsql::schema::project_note::dsl as prj_note;
+    let conn = test_db();
+    let project = ProjectRepo::new(&conn)
+        .insert(&Project {
+            id: 0,
+            name: "p".to_string(),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap();
+    let repo = ServerRepo::new(&conn);
+    let mut srv = repo.insert(&server("old-secret", project.id)).unwrap();
+    srv.password = "secret".to_string();
+    let srv = repo.update(&srv).unwrap();
+    diesel::insert_into(prj_note::project_note)
+        .values((
+            prj_note::title.eq("note"),
+            prj_note::contents.eq("the root password is [pass`note-secret`]."),
+            prj_note::project_id.eq(project.id),
+        ))
+        .execute(&conn)
+        .unwrap();
+    let note_id = prj_note::project_note
+        .select(prj_note::id)
+        .first::<i32>(&conn)
+        .unwrap();
+    (conn, srv, note_id)
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[sql_type = "Text"]
+    row: String,
+}
+
+fn change_log_rows(conn: &SqliteConnection) -> Vec<String> {
+    diesel::sql_query(
+        "SELECT COALESCE(old_row, '') || COALESCE(new_row, '') AS row FROM change_log",
+    )
+    .load::<Row>(conn)
+    .unwrap()
+    .into_iter()
+    .map(|r| r.row)
+    .collect()
+}
+
+fn note_contents(conn: &SqliteConnection, note_id: i32) -> String {
+    use projectpadsql::schema::project_note::dsl as prj_note;
+    prj_note::project_note
+        .find(note_id)
+        .select(prj_note::contents)
+        .first(conn)
+        .unwrap()
+}
+
+#[test]
+fn encrypt_and_decrypt() {
+    let conn = test_db();
+    let key = secrets::enable(&conn, "master").unwrap();
+    let encrypted = key.encrypt("p@ss");
+    assert!(secrets::is_encrypted(&encrypted));
+    assert!(!encrypted.contains("p@ss"));
+    // a new nonce every time
+    assert_ne!(encrypted, key.encrypt("p@ss"));
+    assert_eq!("p@ss", key.decrypt(&encrypted).unwrap());
+    assert_eq!("plain", key.decrypt("plain").unwrap());
+    assert_eq!("", key.encrypt(""));
+    assert!(matches!(
+        key.decrypt(&format!("{}AAAA", secrets::ENCRYPTED_PREFIX)),
+        Err(Error::Corrupt(_))
+    ));
+}
+
+#[test]
+fn encrypt_note_passwords() {
+    let conn = test_db();
+    let key = secrets::enable(&conn, "master").unwrap();
+    for note in &[
+        "hello *world [pass`se*c~~r*et`]*",
+        "hello *world [pass``sec`ret``]*",
+        "[pass`` `sec`ret` ``] and [pass`two`]",
+        "[pass`  spaces  `]",
+    ] {
+        assert!(secrets::note_has_plain_passwords(note), "{}", note);
+        let encrypted = key.encrypt_note(note);
+        assert!(!secrets::note_has_plain_passwords(&encrypted), "{}", note);
+        assert_eq!(*note, key.decrypt_note(&encrypted).unwrap());
+        // the encrypted passwords are left alone
+        assert_eq!(encrypted, key.encrypt_note(&encrypted));
+    }
+    for note in &["no passwords", "[pass`unterminated", "[pass``wrong fence`]"] {
+        assert!(!secrets::note_has_plain_passwords(note), "{}", note);
+        assert_eq!(*note, key.encrypt_note(note));
+    }
+}
+
+#[test]
+fn enable_encrypts_the_existing_passwords() {
+    let (conn, srv, note_id) = db_with_passwords();
+    let changes_before = change_log::list_changes(&conn, 100).unwrap().len();
+    assert!(!secrets::is_enabled(&conn).unwrap());
+
+    let key = secrets::enable(&conn, "master").unwrap();
+    assert!(secrets::is_enabled(&conn).unwrap());
+    let stored = ServerRepo::new(&conn).get(srv.id).unwrap().password;
+    assert!(secrets::is_encrypted(&stored));
+    assert_eq!("secret", key.decrypt(&stored).unwrap());
+    let note = note_contents(&conn, note_id);
+    assert!(!note.contains("note-secret"));
+    assert_eq!(
+        "the root password is [pass`note-secret`].",
+        key.decrypt_note(&note).unwrap()
+    );
+    // encrypting is not in the change log, and the change log doesn't
+    // keep the passwords in plain text
+    assert_eq!(
+        changes_before,
+        change_log::list_changes(&conn, 100).unwrap().len()
+    );
+    assert!(change_log_rows(&conn).iter().all(|r| {
+        !r.contains("old-secret") && !r.contains("note-secret") && !r.contains("\"secret\"")
+    }));
+    let password_edit = change_log::list_changes(&conn, 100)
+        .unwrap()
+        .into_iter()
+        .find(|c| c.operation == ChangeOperation::Update && c.entity_type == EntityType::Server)
+        .unwrap();
+    let field = change_log::field_changes(&conn, password_edit.id)
+        .unwrap()
+        .into_iter()
+        .find(|f| f.field == "password")
+        .unwrap();
+    assert_eq!(
+        "old-secret",
+        key.decrypt(&field.old_value.unwrap()).unwrap()
+    );
+    // the search index is kept up to date
+    assert!(
+        projectpadsql::search(&conn, "note-secret", &Default::default())
+            .unwrap()
+            .is_empty()
+    );
+
+    assert!(matches!(
+        secrets::enable(&conn, "other"),
+        Err(Error::Constraint(_))
+    ));
+}
+
+#[test]
+fn unlock_checks_the_passphrase() {
+    let conn = test_db();
+    assert!(matches!(
+        secrets::unlock(&conn, "master"),
+        Err(Error::NotFound)
+    ));
+    let key = secrets::enable(&conn, "master").unwrap();
+    let encrypted = key.encrypt("p@ss");
+    assert!(matches!(
+        secrets::unlock(&conn, "wrong"),
+        Err(Error::WrongPassphrase)
+    ));
+    let unlocked = secrets::unlock(&conn, "master").unwrap();
+    assert_eq!("p@ss", unlocked.decrypt(&encrypted).unwrap());
+}
+
+#[test]
+fn disable_decrypts_the_passwords() {
+    let (conn, srv, note_id) = db_with_passwords();
+    let before = change_log_rows(&conn);
+    let key = secrets::enable(&conn, "master").unwrap();
+    secrets::disable(&conn, &key).unwrap();
+    assert!(!secrets::is_enabled(&conn).unwrap());
+    assert_eq!(
+        "secret",
+        ServerRepo::new(&conn).get(srv.id).unwrap().password
+    );
+    assert_eq!(
+        "the root password is [pass`note-secret`].",
+        note_contents(&conn, note_id)
+    );
+    assert_eq!(before, change_log_rows(&conn));
+}
+
+#[test]
+fn with_plain_passwords_rolls_back() {
+    let (conn, srv, note_id) = db_with_passwords();
+    let key = secrets::enable(&conn, "master").unwrap();
+    let (password, note) = secrets::with_plain_passwords(&conn, &key, || {
+        (
+            ServerRepo::new(&conn).get(srv.id).unwrap().password,
+            note_contents(&conn, note_id),
+        )
+    })
+    .unwrap();
+    assert_eq!("secret", password);
+    assert_eq!("the root password is [pass`note-secret`].", note);
+    assert!(secrets::is_enabled(&conn).unwrap());
+    assert!(secrets::is_encrypted(
+        &ServerRepo::new(&conn).get(srv.id).unwrap().password
+    ));
+}
+
+#[test]
+fn session_forgets_the_key() {
+    let conn = test_db();
+    let key = secrets::enable(&conn, "master").unwrap();
+    let mut session = SecretSession::new(Duration::from_secs(3600));
+    assert!(session.key().is_none());
+    session.unlocked(key.clone());
+    assert!(session.key().is_some());
+    session.lock();
+    assert!(session.key().is_none());
+
+    let mut expired = SecretSession::new(Duration::from_secs(0));
+    expired.unlocked(key);
+    assert!(expired.key().is_none());
+}
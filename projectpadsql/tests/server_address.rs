@@ -0,0 +1,156 @@
+use diesel::prelude::*;
+use projectpadsql::change_log;
+use projectpadsql::migrations;
+use projectpadsql::models::ServerAddress;
+use projectpadsql::repo::ServerRepo;
+use projectpadsql::Error;
+
+fn address(protocol: Option<&str>, host: &str, port: Option<i32>, path: &str) -> ServerAddress {
+    ServerAddress {
+        protocol: protocol.map(|p| p.to_string()),
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+    }
+}
+
+#[test]
+fn parse_and_display() {
+    let cases = vec![
+        ("", address(None, "", None, "")),
+        ("10.0.0.1", address(None, "10.0.0.1", None, "")),
+        (
+            "db.example.com:2222",
+            address(None, "db.example.com", Some(2222), ""),
+        ),
+        ("[::1]:2222", address(None, "::1", Some(2222), "")),
+        ("fe80::1", address(None, "fe80::1", None, "")),
+        (
+            "https://intranet:8443/app?x=1",
+            address(Some("https"), "intranet", Some(8443), "/app?x=1"),
+        ),
+        ("http://[::1]/", address(Some("http"), "::1", None, "/")),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(
+            expected.clone(),
+            text.parse::<ServerAddress>().unwrap(),
+            "{}",
+            text
+        );
+        assert_eq!(text, expected.to_string());
+    }
+    assert_eq!(
+        address(None, "10.0.0.1", Some(22), ""),
+        " 10.0.0.1:22 ".parse::<ServerAddress>().unwrap()
+    );
+    // the brackets are only needed with a port
+    assert_eq!("::1", "[::1]".parse::<ServerAddress>().unwrap().to_string());
+}
+
+#[test]
+fn parse_errors() {
+    for text in &[
+        "host:0",
+        "host:65536",
+        "host:ssh",
+        "[::1",
+        "[::1]2222",
+        ":22",
+        "://host",
+        "two words",
+    ] {
+        assert!(
+            matches!(text.parse::<ServerAddress>(), Err(Error::Invalid(_))),
+            "{}",
+            text
+        );
+    }
+}
+
+/// a database with the migrations before the server addresses were parsed
+fn db_before_addresses() -> SqliteConnection {
+    let conn = SqliteConnection::establish(":memory:").unwrap();
+    projectpadsql::try_unlock_db(&conn, "test-pass").unwrap();
+    for version in 1..=27 {
+        let path = format!(
+            "{}/migrations/{:03}.sql",
+            env!("CARGO_MANIFEST_DIR"),
+            version
+        );
+        conn.execute(&std::fs::read_to_string(path).unwrap())
+            .unwrap();
+        conn.execute(&format!(
+            "INSERT INTO db_version (code, upgrade_date) VALUES ({}, datetime('now'))",
+            version
+        ))
+        .unwrap();
+    }
+    conn.execute("INSERT INTO project (name, icon) VALUES ('demo', x'00')")
+        .unwrap();
+    conn
+}
+
+fn insert_legacy_server(conn: &SqliteConnection, desc: &str, ip: &str) {
+    conn.execute(&format!(
+        "INSERT INTO server (desc, ip, text, is_retired, username, password, type, \
+         access_type, environment, project_id) \
+         VALUES ('{}', '{}', '', 0, 'root', '', 'SrvApplication', 'SrvAccessSsh', \
+         'Production', 1)",
+        desc, ip
+    ))
+    .unwrap();
+}
+
+#[test]
+fn migration_parses_the_addresses() {
+    let conn = db_before_addresses();
+    insert_legacy_server(&conn, "plain", "10.0.0.1");
+    insert_legacy_server(&conn, "port", "db.example.com:2222");
+    insert_legacy_server(&conn, "ipv6", "[::1]:2222");
+    insert_legacy_server(&conn, "bare ipv6", "fe80::1");
+    insert_legacy_server(&conn, "url", "https://intranet:8443/app");
+    insert_legacy_server(&conn, "bad port", "host:ssh");
+    insert_legacy_server(&conn, "deleted", "[::2]:22");
+    conn.execute("DELETE FROM server WHERE desc = 'deleted'")
+        .unwrap();
+    let changes_before = change_log::list_changes(&conn, 100).unwrap().len();
+    migrations::migrate_db_if_needed(&conn, None).unwrap();
+    // parsing the addresses is not in the change log
+    assert_eq!(
+        changes_before,
+        change_log::list_changes(&conn, 100).unwrap().len()
+    );
+
+    let addresses: Vec<_> = ServerRepo::new(&conn)
+        .list()
+        .unwrap()
+        .into_iter()
+        .map(|s| (s.desc.clone(), s.address()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("bad port".to_string(), address(None, "host:ssh", None, "")),
+            ("bare ipv6".to_string(), address(None, "fe80::1", None, "")),
+            ("ipv6".to_string(), address(None, "::1", Some(2222), "")),
+            ("plain".to_string(), address(None, "10.0.0.1", None, "")),
+            (
+                "port".to_string(),
+                address(None, "db.example.com", Some(2222), "")
+            ),
+            (
+                "url".to_string(),
+                address(Some("https"), "intranet", Some(8443), "/app")
+            ),
+        ],
+        addresses
+    );
+
+    // servers deleted before the migration can be restored
+    let delete = change_log::last_delete(&conn).unwrap().unwrap();
+    let id = change_log::restore_deleted(&conn, delete.id).unwrap();
+    assert_eq!(
+        address(None, "::2", Some(22), ""),
+        ServerRepo::new(&conn).get(id).unwrap().address()
+    );
+}
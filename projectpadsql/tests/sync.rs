@@ -0,0 +1,191 @@
+mod common;
+
+use common::test_db;
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::repo::{EnvironmentRepo, ProjectRepo, ServerRepo};
+use projectpadsql::sync::{self, BackendKind, FolderBackend, Resolution};
+use projectpadsql::Error;
+use std::path::Path;
+
+/// a database of a device which syncs through `folder`
+fn device_db(name: &str, folder: &Path) -> SqliteConnection {
+    let conn = test_db();
+    sync::enable(
+        &conn,
+        name,
+        BackendKind::Folder,
+        &folder.to_string_lossy(),
+        "team-pass",
+    )
+    .unwrap();
+    conn
+}
+
+fn sync_now(conn: &SqliteConnection, folder: &Path) -> sync::SyncReport {
+    sync::sync(conn, &FolderBackend::new(folder)).unwrap()
+}
+
+fn project(name: &str) -> Project {
+    Project {
+        id: 0,
+        name: name.to_string(),
+        icon: Some(b"icon".to_vec()),
+        deleted_at: None,
+    }
+}
+
+fn server(desc: &str, project_id: i32) -> Server {
+    Server {
+        auth_key: Some(vec![0, 1, 2, 255]),
+        auth_key_filename: Some("id_rsa".to_string()),
+        ..common::server(project_id, desc)
+    }
+}
+
+fn project_names(conn: &SqliteConnection) -> Vec<String> {
+    ProjectRepo::new(conn)
+        .list()
+        .unwrap()
+        .into_iter()
+        .map(|p| p.name)
+        .collect()
+}
+
+#[test]
+fn changes_propagate() {
+    let folder = tempfile::tempdir().unwrap();
+    let laptop = device_db("laptop", folder.path());
+    let desktop = device_db("desktop", folder.path());
+    let prj = ProjectRepo::new(&laptop).insert(&project("alpha")).unwrap();
+    let srv = ServerRepo::new(&laptop)
+        .insert(&server("web", prj.id))
+        .unwrap();
+
+    assert_eq!(2, sync_now(&laptop, folder.path()).pushed);
+    let report = sync_now(&desktop, folder.path());
+    assert_eq!(
+        (2, 2, 0),
+        (report.received, report.applied, report.conflicts)
+    );
+    let desktop_prj = ProjectRepo::new(&desktop).list().unwrap().remove(0);
+    assert_eq!(project("alpha").icon, desktop_prj.icon);
+    let desktop_srv = ServerRepo::new(&desktop)
+        .list_for_project(desktop_prj.id)
+        .unwrap()
+        .remove(0);
+    assert_eq!("web", desktop_srv.desc);
+    assert_eq!(srv.auth_key, desktop_srv.auth_key);
+    // the environment of the server is created
+    assert_eq!(
+        vec!["Production"],
+        EnvironmentRepo::new(&desktop)
+            .list_for_project(desktop_prj.id)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect::<Vec<_>>()
+    );
+    // the changes received are not sent back
+    assert_eq!(0, sync_now(&desktop, folder.path()).pushed);
+
+    ServerRepo::new(&desktop)
+        .update(&Server {
+            desc: "web server".to_string(),
+            ..desktop_srv
+        })
+        .unwrap();
+    sync_now(&desktop, folder.path());
+    sync_now(&laptop, folder.path());
+    assert_eq!(
+        "web server",
+        ServerRepo::new(&laptop).get(srv.id).unwrap().desc
+    );
+
+    ProjectRepo::new(&laptop).delete(prj.id).unwrap();
+    sync_now(&laptop, folder.path());
+    sync_now(&desktop, folder.path());
+    assert!(project_names(&desktop).is_empty());
+    assert!(ServerRepo::new(&desktop).get(desktop_srv.id).is_err());
+    assert!(sync::conflicts(&desktop).unwrap().is_empty());
+}
+
+fn rename_project(conn: &SqliteConnection, name: &str) {
+    let prj = ProjectRepo::new(conn).list().unwrap().remove(0);
+    ProjectRepo::new(conn)
+        .update(&Project {
+            name: name.to_string(),
+            ..prj
+        })
+        .unwrap();
+}
+
+#[test]
+fn concurrent_edits_are_conflicts() {
+    let folder = tempfile::tempdir().unwrap();
+    let laptop = device_db("laptop", folder.path());
+    let desktop = device_db("desktop", folder.path());
+    ProjectRepo::new(&laptop).insert(&project("alpha")).unwrap();
+    sync_now(&laptop, folder.path());
+    sync_now(&desktop, folder.path());
+
+    rename_project(&laptop, "beta");
+    rename_project(&desktop, "gamma");
+    sync_now(&laptop, folder.path());
+    assert_eq!(1, sync_now(&desktop, folder.path()).conflicts);
+    assert_eq!(1, sync_now(&laptop, folder.path()).conflicts);
+    let conflicts = sync::conflicts(&desktop).unwrap();
+    assert_eq!(1, conflicts.len());
+    assert_eq!("laptop", conflicts[0].device_name);
+    assert_eq!(
+        vec![sync::ConflictField {
+            field: "name".to_string(),
+            mine: Some("gamma".to_string()),
+            theirs: Some("beta".to_string()),
+        }],
+        conflicts[0].fields
+    );
+
+    // the resolution supersedes the conflict on the other device too
+    sync::resolve(&desktop, conflicts[0].id, Resolution::TakeTheirs).unwrap();
+    sync_now(&desktop, folder.path());
+    sync_now(&laptop, folder.path());
+    assert_eq!(vec!["beta"], project_names(&desktop));
+    assert_eq!(vec!["beta"], project_names(&laptop));
+    assert!(sync::conflicts(&desktop).unwrap().is_empty());
+    assert!(sync::conflicts(&laptop).unwrap().is_empty());
+
+    rename_project(&laptop, "delta");
+    rename_project(&desktop, "epsilon");
+    sync_now(&laptop, folder.path());
+    sync_now(&desktop, folder.path());
+    let conflict_id = sync::conflicts(&desktop).unwrap()[0].id;
+    sync::resolve(&desktop, conflict_id, Resolution::KeepMine).unwrap();
+    sync_now(&desktop, folder.path());
+    sync_now(&laptop, folder.path());
+    assert_eq!(vec!["epsilon"], project_names(&desktop));
+    assert_eq!(vec!["epsilon"], project_names(&laptop));
+}
+
+#[test]
+fn wrong_passphrase() {
+    let folder = tempfile::tempdir().unwrap();
+    let laptop = device_db("laptop", folder.path());
+    ProjectRepo::new(&laptop).insert(&project("alpha")).unwrap();
+    sync_now(&laptop, folder.path());
+
+    let intruder = test_db();
+    sync::enable(
+        &intruder,
+        "intruder",
+        BackendKind::Folder,
+        &folder.path().to_string_lossy(),
+        "guess",
+    )
+    .unwrap();
+    assert!(matches!(
+        sync::sync(&intruder, &FolderBackend::new(folder.path())),
+        Err(Error::Io(_))
+    ));
+    assert!(project_names(&intruder).is_empty());
+}
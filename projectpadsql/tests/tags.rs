@@ -0,0 +1,136 @@
+mod common;
+
+use common::{server, test_db};
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::repo::{ProjectRepo, ServerPoiRepo, ServerRepo, TagRepo};
+use projectpadsql::search::SearchFilters;
+
+fn insert_server(conn: &SqliteConnection, desc: &str) -> Server {
+    let project = ProjectRepo::new(conn)
+        .insert(&Project {
+            id: 0,
+            name: format!("project of {}", desc),
+            icon: Some(b"icon".to_vec()),
+            deleted_at: None,
+        })
+        .unwrap();
+    ServerRepo::new(conn)
+        .insert(&server(project.id, desc))
+        .unwrap()
+}
+
+fn names(tags: &[Tag]) -> Vec<&str> {
+    tags.iter().map(|t| t.name.as_str()).collect()
+}
+
+fn strings(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+#[test]
+fn tag_names_are_normalized() {
+    assert_eq!("on-call", Tag::normalize_name(" On Call "));
+    assert_eq!("", Tag::normalize_name(" , "));
+    assert_eq!(
+        vec!["k8s", "legacy", "customer-facing"],
+        Tag::parse_names("K8s, legacy  customer-facing,k8s")
+    );
+}
+
+#[test]
+fn set_for_item_replaces_the_tags() {
+    let conn = test_db();
+    let srv = insert_server(&conn, "web");
+    let other = insert_server(&conn, "db");
+    let repo = TagRepo::new(&conn);
+    let tags = repo
+        .set_for_item(EntityType::Server, srv.id, &strings(&["legacy", "K8s"]))
+        .unwrap();
+    assert_eq!(vec!["k8s", "legacy"], names(&tags));
+    repo.set_for_item(EntityType::Server, other.id, &strings(&["k8s"]))
+        .unwrap();
+    // the tag is shared
+    assert_eq!(2, repo.list().unwrap().len());
+
+    let tags = repo
+        .set_for_item(EntityType::Server, srv.id, &strings(&["k8s", "on-call"]))
+        .unwrap();
+    assert_eq!(vec!["k8s", "on-call"], names(&tags));
+    // nothing has the legacy tag anymore
+    assert_eq!(vec!["k8s", "on-call"], names(&repo.list().unwrap()));
+
+    let by_item = repo
+        .list_for_items(EntityType::Server, &[srv.id, other.id])
+        .unwrap();
+    assert_eq!(vec!["k8s", "on-call"], names(&by_item[&srv.id]));
+    assert_eq!(vec!["k8s"], names(&by_item[&other.id]));
+    // same ids, but another entity type
+    assert!(repo
+        .list_for_items(EntityType::ServerPoi, &[srv.id])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn deleting_an_item_deletes_its_tags() {
+    let conn = test_db();
+    let srv = insert_server(&conn, "web");
+    let poi = ServerPoiRepo::new(&conn)
+        .insert(&ServerPointOfInterest {
+            id: 0,
+            desc: "logs".to_string(),
+            path: "/var/log".to_string(),
+            text: "".to_string(),
+            interest_type: InterestType::PoiLogFile,
+            run_on: RunOn::RunOnServer,
+            group_name: None,
+            server_id: srv.id,
+            deleted_at: None,
+        })
+        .unwrap();
+    let repo = TagRepo::new(&conn);
+    repo.set_for_item(EntityType::Server, srv.id, &strings(&["k8s"]))
+        .unwrap();
+    repo.set_for_item(EntityType::ServerPoi, poi.id, &strings(&["k8s", "logs"]))
+        .unwrap();
+
+    ServerPoiRepo::new(&conn).delete(poi.id).unwrap();
+    assert_eq!(vec!["k8s"], names(&repo.list().unwrap()));
+    repo.set_for_item(EntityType::Server, srv.id, &[]).unwrap();
+    assert!(repo.list().unwrap().is_empty());
+}
+
+#[test]
+fn search_by_tag() {
+    let conn = test_db();
+    let web = insert_server(&conn, "web");
+    let db = insert_server(&conn, "db");
+    let repo = TagRepo::new(&conn);
+    repo.set_for_item(EntityType::Server, web.id, &strings(&["k8s", "on-call"]))
+        .unwrap();
+    repo.set_for_item(EntityType::Server, db.id, &strings(&["k8s"]))
+        .unwrap();
+
+    let search = |query: &str, tags: &[&str]| {
+        let mut ids: Vec<_> = projectpadsql::search(
+            &conn,
+            query,
+            &SearchFilters {
+                entity_types: vec![EntityType::Server],
+                tags: strings(tags),
+                ..SearchFilters::default()
+            },
+        )
+        .unwrap()
+        .into_iter()
+        .map(|hit| hit.entity_id)
+        .collect();
+        ids.sort_unstable();
+        ids
+    };
+    assert_eq!(vec![web.id, db.id], search("", &["k8s"]));
+    assert_eq!(vec![web.id], search("", &["k8s", "On-Call"]));
+    assert_eq!(vec![db.id], search("db", &["k8s"]));
+    assert!(search("", &["it's"]).is_empty());
+}
@@ -0,0 +1,94 @@
+use projectpadsql::totp::{Algorithm, Totp};
+use projectpadsql::Error;
+
+fn rfc_totp(secret: &str, algorithm: Algorithm) -> Totp {
+    Totp {
+        algorithm,
+        digits: 8,
+        ..secret.parse::<Totp>().unwrap()
+    }
+}
+
+// RFC 6238, appendix B
+#[test]
+fn rfc_6238_test_vectors() {
+    let sha1 = rfc_totp("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", Algorithm::Sha1);
+    let sha256 = rfc_totp(
+        "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA====",
+        Algorithm::Sha256,
+    );
+    let sha512 = rfc_totp(
+        "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+         GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA=",
+        Algorithm::Sha512,
+    );
+    for (time, code_sha1, code_sha256, code_sha512) in &[
+        (59, "94287082", "46119246", "90693936"),
+        (1111111109, "07081804", "68084774", "25091201"),
+        (1111111111, "14050471", "67062674", "99943326"),
+        (1234567890, "89005924", "91819424", "93441116"),
+        (2000000000, "69279037", "90698825", "38618901"),
+        (20000000000, "65353130", "77737706", "47863826"),
+    ] {
+        assert_eq!(*code_sha1, sha1.code_at(*time), "{}", time);
+        assert_eq!(*code_sha256, sha256.code_at(*time), "{}", time);
+        assert_eq!(*code_sha512, sha512.code_at(*time), "{}", time);
+    }
+}
+
+#[test]
+fn six_digits_by_default() {
+    let totp: Totp = "gezd gnbv gy3t qojq gezd gnbv gy3t qojq".parse().unwrap();
+    assert_eq!(b"12345678901234567890".to_vec(), totp.secret);
+    assert_eq!("287082", totp.code_at(59));
+    let (code, valid_for) = totp.current_code();
+    assert_eq!(6, code.len());
+    assert!((1..=30).contains(&valid_for));
+}
+
+#[test]
+fn parse_uri() {
+    let totp: Totp = "otpauth://totp/ACME%20Co:john@example.com?secret=JBSWY3DPEHPK3PXP\
+                      &issuer=ACME%20Corp&algorithm=SHA256&digits=8&period=60&image=x"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        Totp {
+            secret: b"Hello!\xde\xad\xbe\xef".to_vec(),
+            algorithm: Algorithm::Sha256,
+            digits: 8,
+            period: 60,
+            issuer: Some("ACME Corp".to_string()),
+            account: Some("john@example.com".to_string()),
+        },
+        totp
+    );
+    let totp: Totp = "OTPAUTH://TOTP/Example:alice?secret=JBSWY3DPEHPK3PXP"
+        .parse()
+        .unwrap();
+    assert_eq!(Algorithm::Sha1, totp.algorithm);
+    assert_eq!((6, 30), (totp.digits, totp.period));
+    assert_eq!(Some("Example".to_string()), totp.issuer);
+    assert_eq!(Some("alice".to_string()), totp.account);
+}
+
+#[test]
+fn parse_errors() {
+    for value in &[
+        "",
+        "not base32!",
+        "otpauth://hotp/x?secret=JBSWY3DPEHPK3PXP&counter=1",
+        "otpauth://totp/x",
+        "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&digits=12",
+        "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&period=0",
+        "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&algorithm=MD5",
+        "otpauth://totp/x%2?secret=JBSWY3DPEHPK3PXP",
+        "otpauth://totp",
+    ] {
+        assert!(
+            matches!(value.parse::<Totp>(), Err(Error::Invalid(_))),
+            "{}",
+            value
+        );
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{server, test_db};
+use diesel::prelude::*;
+use projectpadsql::models::*;
+use projectpadsql::repo::{ProjectRepo, ServerPoiRepo, ServerRepo};
+use projectpadsql::search::SearchFilters;
+use projectpadsql::{trash, Error};
+
+fn project(name: &str) -> Project {
+    Project {
+        id: 0,
+        name: name.to_string(),
+        icon: Some(b"icon".to_vec()),
+        deleted_at: None,
+    }
+}
+
+fn server_poi(desc: &str, server_id: i32) -> ServerPointOfInterest {
+    ServerPointOfInterest {
+        id: 0,
+        desc: desc.to_string(),
+        path: "/var/log".to_string(),
+        text: "app.log".to_string(),
+        interest_type: InterestType::PoiLogFile,
+        run_on: RunOn::RunOnServer,
+        group_name: None,
+        server_id,
+        deleted_at: None,
+    }
+}
+
+fn trash_contents(conn: &SqliteConnection) -> Vec<(EntityType, String)> {
+    trash::list(conn)
+        .unwrap()
+        .into_iter()
+        .map(|t| (t.entity_type, t.title))
+        .collect()
+}
+
+fn search_titles(conn: &SqliteConnection, query: &str) -> Vec<String> {
+    projectpadsql::search(conn, query, &SearchFilters::default())
+        .unwrap()
+        .into_iter()
+        .map(|h| h.title)
+        .collect()
+}
+
+#[test]
+fn trash_and_restore_with_children() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let srv = ServerRepo::new(&conn)
+        .insert(&server(prj.id, "web"))
+        .unwrap();
+    let poi_repo = ServerPoiRepo::new(&conn);
+    poi_repo.insert(&server_poi("logs", srv.id)).unwrap();
+    let old_poi = poi_repo.insert(&server_poi("old logs", srv.id)).unwrap();
+
+    trash::move_to_trash(&conn, EntityType::ServerPoi, old_poi.id).unwrap();
+    assert_eq!(
+        vec![(EntityType::ServerPoi, "old logs".to_string())],
+        trash_contents(&conn)
+    );
+    assert_eq!(1, poi_repo.list_for_server(srv.id).unwrap().len());
+    assert!(search_titles(&conn, "old").is_empty());
+
+    trash::move_to_trash(&conn, EntityType::Server, srv.id).unwrap();
+    // the children of the server are not listed separately
+    assert_eq!(
+        vec![(EntityType::Server, "web".to_string())],
+        trash_contents(&conn)
+    );
+    assert!(ServerRepo::new(&conn).list().unwrap().is_empty());
+    assert!(poi_repo.list().unwrap().is_empty());
+    assert!(search_titles(&conn, "logs").is_empty());
+    // the entities can still be fetched by id
+    assert!(ServerRepo::new(&conn)
+        .get(srv.id)
+        .unwrap()
+        .deleted_at
+        .is_some());
+    assert!(matches!(
+        trash::move_to_trash(&conn, EntityType::Server, srv.id),
+        Err(Error::NotFound)
+    ));
+
+    // only the children trashed with the server are restored
+    trash::restore(&conn, EntityType::Server, srv.id).unwrap();
+    assert_eq!(
+        vec!["logs"],
+        poi_repo
+            .list_for_server(srv.id)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.desc)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(vec!["logs"], search_titles(&conn, "logs"));
+    assert_eq!(
+        vec![(EntityType::ServerPoi, "old logs".to_string())],
+        trash_contents(&conn)
+    );
+    assert!(matches!(
+        trash::restore(&conn, EntityType::Server, srv.id),
+        Err(Error::NotFound)
+    ));
+}
+
+#[test]
+fn purge() {
+    let conn = test_db();
+    let prj = ProjectRepo::new(&conn).insert(&project("alpha")).unwrap();
+    let other = ProjectRepo::new(&conn).insert(&project("beta")).unwrap();
+    let srv = ServerRepo::new(&conn)
+        .insert(&server(prj.id, "web"))
+        .unwrap();
+    // only entities in the trash can be purged
+    assert!(matches!(
+        trash::purge(&conn, EntityType::Project, prj.id),
+        Err(Error::NotFound)
+    ));
+    trash::move_to_trash(&conn, EntityType::Project, prj.id).unwrap();
+    assert_eq!(vec![other.clone()], ProjectRepo::new(&conn).list().unwrap());
+    // nothing is old enough
+    assert_eq!(0, trash::purge_older_than(&conn, 30).unwrap());
+    trash::purge(&conn, EntityType::Project, prj.id).unwrap();
+    assert!(trash::list(&conn).unwrap().is_empty());
+    assert!(matches!(
+        ServerRepo::new(&conn).get(srv.id),
+        Err(Error::NotFound)
+    ));
+
+    trash::move_to_trash(&conn, EntityType::Project, other.id).unwrap();
+    conn.execute("UPDATE project SET deleted_at = '2000-01-01 10:00:00.000'")
+        .unwrap();
+    assert_eq!(1, trash::purge_older_than(&conn, 30).unwrap());
+    assert!(matches!(
+        ProjectRepo::new(&conn).get(other.id),
+        Err(Error::NotFound)
+    ));
+}
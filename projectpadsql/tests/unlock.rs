@@ -0,0 +1,81 @@
+use diesel::prelude::*;
+use projectpadsql::{migrations, Error};
+use std::path::Path;
+
+fn open(path: &Path) -> SqliteConnection {
+    SqliteConnection::establish(&path.to_string_lossy()).unwrap()
+}
+
+#[test]
+fn errors_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Error>();
+}
+
+#[test]
+fn wrong_password() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("projectpad.db");
+    {
+        let conn = open(&path);
+        projectpadsql::try_unlock_db(&conn, "right").unwrap();
+        migrations::migrate_db_if_needed(&conn, None).unwrap();
+    }
+    let conn = open(&path);
+    assert!(matches!(
+        projectpadsql::try_unlock_db(&conn, "wrong"),
+        Err(Error::WrongPassword)
+    ));
+    let conn = open(&path);
+    projectpadsql::try_unlock_db(&conn, "right").unwrap();
+}
+
+#[test]
+fn not_a_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let garbage = dir.path().join("garbage.db");
+    std::fs::write(&garbage, vec![b'x'; 1000]).unwrap();
+    assert!(matches!(
+        projectpadsql::try_unlock_db(&open(&garbage), "pass"),
+        Err(Error::NotADatabase)
+    ));
+
+    // an unencrypted sqlite database
+    let plain = dir.path().join("plain.db");
+    open(&plain)
+        .execute("CREATE TABLE test (id INTEGER PRIMARY KEY)")
+        .unwrap();
+    assert!(matches!(
+        projectpadsql::try_unlock_db(&open(&plain), "pass"),
+        Err(Error::NotADatabase)
+    ));
+}
+
+#[test]
+fn schema_version_checks() {
+    let conn = SqliteConnection::establish(":memory:").unwrap();
+    projectpadsql::try_unlock_db(&conn, "pass").unwrap();
+    migrations::migrate_db_if_needed(&conn, None).unwrap();
+    assert_eq!(
+        migrations::SCHEMA_VERSION,
+        migrations::check_schema_version(&conn, 21).unwrap()
+    );
+    assert!(matches!(
+        migrations::check_schema_version(&conn, migrations::SCHEMA_VERSION + 1),
+        Err(Error::SchemaTooOld { .. })
+    ));
+
+    conn.execute(&format!(
+        "INSERT INTO db_version (code, upgrade_date) VALUES ({}, datetime('now'))",
+        migrations::SCHEMA_VERSION + 1
+    ))
+    .unwrap();
+    assert!(matches!(
+        migrations::check_schema_version(&conn, 21),
+        Err(Error::SchemaTooNew { .. })
+    ));
+    assert!(matches!(
+        migrations::migrate_db_if_needed(&conn, None),
+        Err(Error::SchemaTooNew { .. })
+    ));
+}